@@ -1,3 +1,15 @@
 fn main() {
-    // Build script removed - no longer using PyO3
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    // Use the vendored protoc so building doesn't require a system install
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc");
+        std::env::set_var("PROTOC", protoc);
+    }
+    println!("cargo:rerun-if-changed=proto/pysandbox.proto");
+    tonic_build::compile_protos("proto/pysandbox.proto").expect("compile pysandbox.proto");
 }
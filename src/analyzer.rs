@@ -0,0 +1,1434 @@
+//! Static analysis of Python source without a Python runtime
+//!
+//! [`StaticAnalyzer`] parses code with tree-sitter's Python grammar and walks
+//! the syntax tree for imports outside the [`ImportPolicy`], blacklisted
+//! calls, access to escape-prone dunder attributes and frames, and
+//! `exec`/`eval` usage. Names assembled from string literals
+//! (`"ex" + "ec"`, `"".join([...])`, escapes) are folded before checking, and
+//! imports or attribute lookups whose names are only known at runtime are
+//! flagged. Engines that spawn an interpreter run it in `validate` so
+//! obviously disallowed code is refused before a process is started.
+//!
+//! [`explain`](crate::policy::SandboxPolicy::explain) uses the same parse to
+//! report why a [`SandboxPolicy`] would allow or refuse each import, process
+//! call and literal file path, so hosts can show it before running anything.
+//!
+//! The analysis is a cheap first line of defence; the in-process guards still
+//! enforce the policy at runtime.
+
+use crate::config::{from_import_denial, import_rule, CodeLimits, ImportPolicy};
+use crate::engine::{Diagnostic, Severity};
+use crate::errors::{Result, SandboxError};
+use crate::policy::{FilesystemPolicy, ImportPolicyType, ProcessPolicy, SandboxPolicy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tree_sitter::{Node, Parser, Tree};
+
+/// Builtins that run dynamically built code
+const DYNAMIC_EXECUTION_CALLS: &[&str] = &["exec", "eval", "compile"];
+
+/// Calls refused by default
+const DEFAULT_BLOCKED_CALLS: &[&str] = &["__import__", "breakpoint", "globals"];
+
+/// Dunder attributes used to walk from an object to the interpreter's internals
+const DEFAULT_BLOCKED_DUNDERS: &[&str] = &[
+    "__subclasses__",
+    "__globals__",
+    "__builtins__",
+    "__bases__",
+    "__base__",
+    "__mro__",
+    "__code__",
+    "__closure__",
+    "__getattribute__",
+    "__loader__",
+    "__spec__",
+    "__import__",
+];
+
+/// Frame and traceback attributes that reach other frames' globals
+const FRAME_ATTRIBUTES: &[&str] = &[
+    "f_back",
+    "f_globals",
+    "f_locals",
+    "f_builtins",
+    "gi_frame",
+    "cr_frame",
+    "ag_frame",
+    "tb_frame",
+];
+
+/// Functions that hand out frame objects
+const FRAME_FUNCTIONS: &[&str] = &[
+    "sys._getframe",
+    "inspect.currentframe",
+    "inspect.stack",
+    "inspect.trace",
+    "inspect.getouterframes",
+    "inspect.getinnerframes",
+];
+
+/// Nodes that open a level of nesting: blocks and brackets
+const NESTING_KINDS: &[&str] = &[
+    "block",
+    "parenthesized_expression",
+    "argument_list",
+    "list",
+    "tuple",
+    "set",
+    "dictionary",
+    "subscript",
+    "list_comprehension",
+    "set_comprehension",
+    "dictionary_comprehension",
+    "generator_expression",
+    "lambda",
+];
+
+/// Kind of problem found by the static analyzer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// Import of a module the policy doesn't allow
+    BlockedImport,
+    /// Call to a blacklisted function
+    BlockedCall,
+    /// Access to an escape-prone dunder attribute
+    DunderAccess,
+    /// Access to frame objects or their globals
+    FrameIntrospection,
+    /// `exec`, `eval` or `compile`
+    DynamicExecution,
+    /// `__import__` or `importlib.import_module` with a computed module name
+    DynamicImport,
+    /// `getattr`, `setattr` or `delattr` with an attribute name built at runtime
+    ComputedAttribute,
+}
+
+/// A single static analysis finding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub severity: Severity,
+    /// Module, function or attribute name the finding is about
+    pub name: String,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column
+    pub column: usize,
+    /// 1-based line where the flagged node ends
+    pub end_line: usize,
+    /// 1-based column where the flagged node ends (exclusive)
+    pub end_column: usize,
+}
+
+impl FindingKind {
+    /// Rule name used in [`Diagnostic::rule`]
+    pub fn rule(&self) -> &'static str {
+        match self {
+            FindingKind::BlockedImport => "blocked_import",
+            FindingKind::BlockedCall => "blocked_call",
+            FindingKind::DunderAccess => "dunder_access",
+            FindingKind::FrameIntrospection => "frame_introspection",
+            FindingKind::DynamicExecution => "dynamic_execution",
+            FindingKind::DynamicImport => "dynamic_import",
+            FindingKind::ComputedAttribute => "computed_attribute",
+        }
+    }
+}
+
+impl Finding {
+    /// Convert into the error returned by [`StaticAnalyzer::check`]
+    pub fn into_error(self) -> SandboxError {
+        match self.kind {
+            FindingKind::BlockedImport => SandboxError::ImportNotAllowed(self.name),
+            _ => SandboxError::DisallowedOperation(self.to_string()),
+        }
+    }
+}
+
+impl From<Finding> for Diagnostic {
+    fn from(finding: Finding) -> Self {
+        Diagnostic {
+            rule: finding.kind.rule().to_string(),
+            message: finding.to_string(),
+            line: Some(finding.line),
+            col: Some(finding.column),
+            severity: finding.severity,
+        }
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let what = match self.kind {
+            FindingKind::BlockedImport => "import of",
+            FindingKind::BlockedCall => "call to",
+            FindingKind::DunderAccess => "access to",
+            FindingKind::FrameIntrospection => "frame introspection via",
+            FindingKind::DynamicExecution => "dynamic execution via",
+            FindingKind::DynamicImport => "computed module name passed to",
+            FindingKind::ComputedAttribute => "computed attribute name passed to",
+        };
+        write!(
+            f,
+            "{} '{}' at line {}, column {}",
+            what, self.name, self.line, self.column
+        )
+    }
+}
+
+/// Python static analyzer driven by an [`ImportPolicy`]
+#[derive(Debug, Clone)]
+pub struct StaticAnalyzer {
+    import_policy: ImportPolicy,
+    blocked_calls: HashSet<String>,
+    blocked_dunders: HashSet<String>,
+    allow_dynamic_execution: bool,
+    escape_primitives: Severity,
+    code_limits: CodeLimits,
+}
+
+impl StaticAnalyzer {
+    /// Create an analyzer with the default call and dunder blacklists
+    pub fn new(import_policy: ImportPolicy) -> Self {
+        Self {
+            import_policy,
+            blocked_calls: DEFAULT_BLOCKED_CALLS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            blocked_dunders: DEFAULT_BLOCKED_DUNDERS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            allow_dynamic_execution: false,
+            escape_primitives: Severity::Error,
+            code_limits: CodeLimits::default(),
+        }
+    }
+
+    /// Create an analyzer for a [`SandboxPolicy`]
+    /// import policy
+    pub fn for_policy(imports: &ImportPolicyType) -> Self {
+        Self::new(imports.to_import_policy())
+    }
+
+    /// Also refuse calls to `name`
+    pub fn block_call(mut self, name: impl Into<String>) -> Self {
+        self.blocked_calls.insert(name.into());
+        self
+    }
+
+    /// Also refuse access to attribute `name`
+    pub fn block_attribute(mut self, name: impl Into<String>) -> Self {
+        self.blocked_dunders.insert(name.into());
+        self
+    }
+
+    /// Permit `exec`, `eval` and `compile`
+    pub fn allow_dynamic_execution(mut self, allow: bool) -> Self {
+        self.allow_dynamic_execution = allow;
+        self
+    }
+
+    /// How to treat dunder access and frame introspection, the usual
+    /// sandbox-escape primitives. [`Severity::Error`] by default.
+    pub fn escape_primitives(mut self, severity: Severity) -> Self {
+        self.escape_primitives = severity;
+        self
+    }
+
+    /// Size and complexity caps; code over them fails with
+    /// [`SandboxError::CodeTooComplex`]
+    pub fn code_limits(mut self, limits: CodeLimits) -> Self {
+        self.code_limits = limits;
+        self
+    }
+
+    /// Parse `code` and return every finding, in source order
+    pub fn analyze(&self, code: &str) -> Result<Vec<Finding>> {
+        self.code_limits.check_source(code)?;
+        let tree = parse(code)?;
+        let root = tree.root_node();
+
+        let limits = &self.code_limits;
+        let too_complex = |what: &str, limit: usize| {
+            Err(SandboxError::CodeTooComplex(format!(
+                "more than {} {}",
+                limit, what
+            )))
+        };
+
+        let source = code.as_bytes();
+        let mut findings = Vec::new();
+        let (mut nodes, mut functions) = (0, 0);
+        // Explicit stack so deeply nested code can't overflow ours
+        let mut stack = vec![(root, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            nodes += 1;
+            if nodes > limits.max_ast_nodes {
+                return too_complex("syntax tree nodes", limits.max_ast_nodes);
+            }
+            if depth > limits.max_nesting_depth {
+                return too_complex("levels of nesting", limits.max_nesting_depth);
+            }
+            if matches!(
+                node.kind(),
+                "function_definition" | "lambda" | "class_definition"
+            ) {
+                functions += 1;
+                if functions > limits.max_functions {
+                    return too_complex("function definitions", limits.max_functions);
+                }
+            }
+
+            self.visit(node, source, &mut findings);
+            let depth = depth + usize::from(NESTING_KINDS.contains(&node.kind()));
+            let mut cursor = node.walk();
+            let children: Vec<_> = node.named_children(&mut cursor).collect();
+            stack.extend(children.into_iter().rev().map(|child| (child, depth)));
+        }
+        for finding in &mut findings {
+            if matches!(
+                finding.kind,
+                FindingKind::DunderAccess | FindingKind::FrameIntrospection
+            ) {
+                finding.severity = self.escape_primitives;
+            }
+        }
+        Ok(findings)
+    }
+
+    /// Parse `code` and fail on the first finding that isn't a warning
+    pub fn check(&self, code: &str) -> Result<()> {
+        let denied = self
+            .analyze(code)?
+            .into_iter()
+            .find(|finding| finding.severity == Severity::Error);
+        match denied {
+            Some(finding) => Err(finding.into_error()),
+            None => Ok(()),
+        }
+    }
+
+    fn visit(&self, node: Node, source: &[u8], findings: &mut Vec<Finding>) {
+        match node.kind() {
+            "import_statement" => {
+                let mut cursor = node.walk();
+                for name in node.children_by_field_name("name", &mut cursor) {
+                    let module = match name.kind() {
+                        "aliased_import" => name.child_by_field_name("name"),
+                        _ => Some(name),
+                    };
+                    if let Some(module) = module {
+                        self.check_import(module, text(module, source), findings);
+                    }
+                }
+            }
+            "import_from_statement" => {
+                // Relative imports stay inside the user's own package
+                if let Some(module) = node
+                    .child_by_field_name("module_name")
+                    .filter(|m| m.kind() == "dotted_name")
+                {
+                    let names = imported_names(node, source);
+                    if let Some(denied) = self
+                        .import_policy
+                        .denied_from_import(text(module, source), &names)
+                    {
+                        findings.push(finding(FindingKind::BlockedImport, &denied, module));
+                    }
+                }
+            }
+            "call" => self.visit_call(node, source, findings),
+            "attribute" => {
+                let Some(attribute) = node.child_by_field_name("attribute") else {
+                    return;
+                };
+                let name = text(attribute, source);
+                let on_builtins = node.child_by_field_name("object").is_some_and(|object| {
+                    matches!(text(object, source), "builtins" | "__builtins__")
+                });
+                if self.blocked_dunders.contains(name) {
+                    findings.push(finding(FindingKind::DunderAccess, name, attribute));
+                } else if FRAME_ATTRIBUTES.contains(&name)
+                    || FRAME_FUNCTIONS.contains(&text(node, source))
+                {
+                    findings.push(finding(FindingKind::FrameIntrospection, name, attribute));
+                } else if on_builtins && self.blocks_dynamic_execution(name) {
+                    findings.push(finding(FindingKind::DynamicExecution, name, attribute));
+                }
+            }
+            "identifier" if is_reference(node) => {
+                let name = text(node, source);
+                if name == "__builtins__" && self.blocked_dunders.contains(name) {
+                    findings.push(finding(FindingKind::DunderAccess, name, node));
+                } else if self.blocks_dynamic_execution(name) {
+                    // Aliases like `run = exec` are caught here too
+                    findings.push(finding(FindingKind::DynamicExecution, name, node));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_call(&self, node: Node, source: &[u8], findings: &mut Vec<Finding>) {
+        let Some(function) = node.child_by_field_name("function") else {
+            return;
+        };
+        let name = match function.kind() {
+            "identifier" | "attribute" => text(function, source),
+            _ => return,
+        };
+
+        if self.blocked_calls.contains(name) {
+            findings.push(finding(FindingKind::BlockedCall, name, function));
+        }
+
+        match name {
+            "__import__" | "importlib.import_module" | "import_module" => {
+                let Some(module) = argument(node, 0) else {
+                    return;
+                };
+                match constant_string(module, source) {
+                    Some(value) => self.check_import(module, &value, findings),
+                    None => findings.push(finding(FindingKind::DynamicImport, name, module)),
+                }
+            }
+            "getattr" | "setattr" | "delattr" => {
+                let Some(attribute) = argument(node, 1) else {
+                    return;
+                };
+                match constant_string(attribute, source) {
+                    Some(value) => self.check_attribute_name(attribute, &value, findings),
+                    // A plain variable is common; a name assembled in place isn't
+                    None if !matches!(attribute.kind(), "identifier" | "attribute") => {
+                        findings.push(finding(FindingKind::ComputedAttribute, name, attribute))
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_import(&self, node: Node, module: &str, findings: &mut Vec<Finding>) {
+        if !self.import_policy.is_allowed(module) {
+            findings.push(finding(FindingKind::BlockedImport, module, node));
+        }
+    }
+
+    /// Check an attribute name passed to `getattr` and friends
+    fn check_attribute_name(&self, node: Node, name: &str, findings: &mut Vec<Finding>) {
+        if self.blocked_dunders.contains(name) {
+            findings.push(finding(FindingKind::DunderAccess, name, node));
+        } else if FRAME_ATTRIBUTES.contains(&name) {
+            findings.push(finding(FindingKind::FrameIntrospection, name, node));
+        } else if self.blocks_dynamic_execution(name) {
+            findings.push(finding(FindingKind::DynamicExecution, name, node));
+        } else if self.blocked_calls.contains(name) {
+            findings.push(finding(FindingKind::BlockedCall, name, node));
+        }
+    }
+
+    fn blocks_dynamic_execution(&self, name: &str) -> bool {
+        !self.allow_dynamic_execution && DYNAMIC_EXECUTION_CALLS.contains(&name)
+    }
+}
+
+/// One import statement, or one module of a multi-module `import`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportEntry {
+    /// Module as written, without leading dots; empty for `from . import x`
+    pub module: String,
+    /// Names imported by a `from` import, `*` for a wildcard
+    pub names: Vec<String>,
+    /// Number of leading dots of a relative import
+    pub level: usize,
+    /// 1-based line
+    pub line: usize,
+    /// Directly at module level rather than inside a block
+    pub top_level: bool,
+    /// Inside a branch, loop, `try`, `with`, `match` or function body, so it
+    /// may never run
+    pub conditional: bool,
+}
+
+impl ImportEntry {
+    /// Top-level package of an absolute import
+    pub fn root_module(&self) -> Option<&str> {
+        (self.level == 0).then(|| self.module.split('.').next().unwrap_or_default())
+    }
+}
+
+/// Imports found in a piece of code, in source order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub imports: Vec<ImportEntry>,
+}
+
+impl ImportReport {
+    /// Distinct top-level packages of absolute imports, e.g. to infer
+    /// requirements or to check a managed environment has them
+    pub fn root_modules(&self) -> std::collections::BTreeSet<String> {
+        self.imports
+            .iter()
+            .filter_map(ImportEntry::root_module)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Distinct top-level packages that are imported unconditionally
+    pub fn required_modules(&self) -> std::collections::BTreeSet<String> {
+        self.imports
+            .iter()
+            .filter(|import| !import.conditional)
+            .filter_map(ImportEntry::root_module)
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Blocks whose body may not run
+const CONDITIONAL_KINDS: &[&str] = &[
+    "if_statement",
+    "try_statement",
+    "for_statement",
+    "while_statement",
+    "with_statement",
+    "match_statement",
+    "function_definition",
+];
+
+/// List every import statement in `code`, including ones nested in
+/// functions and conditional blocks
+///
+/// `__future__` imports are left out. Source over the default
+/// [`CodeLimits::max_source_bytes`] is refused.
+pub fn extract_imports(code: &str) -> Result<ImportReport> {
+    CodeLimits::default().check_source(code)?;
+    let tree = parse(code)?;
+    let source = code.as_bytes();
+
+    let mut report = ImportReport::default();
+    let mut stack = vec![(tree.root_node(), false)];
+    while let Some((node, conditional)) = stack.pop() {
+        let top_level = node
+            .parent()
+            .is_some_and(|parent| parent.kind() == "module");
+        let line = node.start_position().row + 1;
+        let mut cursor = node.walk();
+        match node.kind() {
+            "import_statement" => {
+                for name in node.children_by_field_name("name", &mut cursor) {
+                    let module = match name.kind() {
+                        "aliased_import" => name.child_by_field_name("name"),
+                        _ => Some(name),
+                    };
+                    report.imports.extend(module.map(|module| ImportEntry {
+                        module: text(module, source).to_string(),
+                        names: Vec::new(),
+                        level: 0,
+                        line,
+                        top_level,
+                        conditional,
+                    }));
+                }
+                continue;
+            }
+            "import_from_statement" => {
+                let (module, level) = match node.child_by_field_name("module_name") {
+                    Some(module) if module.kind() == "relative_import" => {
+                        let written = text(module, source);
+                        let dotted = written.trim_start_matches('.');
+                        (dotted.to_string(), written.len() - dotted.len())
+                    }
+                    Some(module) => (text(module, source).to_string(), 0),
+                    None => continue,
+                };
+                let mut names: Vec<String> = node
+                    .children_by_field_name("name", &mut cursor)
+                    .filter_map(|name| match name.kind() {
+                        "aliased_import" => name.child_by_field_name("name"),
+                        _ => Some(name),
+                    })
+                    .map(|name| text(name, source).to_string())
+                    .collect();
+                let mut cursor = node.walk();
+                if node
+                    .named_children(&mut cursor)
+                    .any(|child| child.kind() == "wildcard_import")
+                {
+                    names.push("*".to_string());
+                }
+                report.imports.push(ImportEntry {
+                    module,
+                    names,
+                    level,
+                    line,
+                    top_level,
+                    conditional,
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        let conditional = conditional || CONDITIONAL_KINDS.contains(&node.kind());
+        let children: Vec<_> = node.named_children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev().map(|child| (child, conditional)));
+    }
+    Ok(report)
+}
+
+/// What a [`PolicyDecision`] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subject {
+    /// A module imported by `import`, `from ... import` or a constant
+    /// `__import__`/`importlib.import_module` argument
+    Import,
+    /// A function call
+    Call,
+    /// An attribute lookup
+    Attribute,
+    /// A literal path passed to `open` or `os.open`
+    Path,
+}
+
+/// Whether one thing found in the code would be allowed, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyDecision {
+    pub subject: Subject,
+    /// Module, function, attribute or path
+    pub name: String,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column
+    pub column: usize,
+    pub allowed: bool,
+    /// Policy primitive or analyzer rule that decides, e.g. `imports.blacklist`
+    pub rule: String,
+    /// Human-readable explanation
+    pub reason: String,
+}
+
+/// Result of [`SandboxPolicy::explain`](crate::policy::SandboxPolicy::explain)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyReport {
+    /// Name of the policy the code was checked against
+    pub policy: String,
+    /// Decisions in source order
+    pub decisions: Vec<PolicyDecision>,
+}
+
+impl PolicyReport {
+    /// Whether nothing in the code would be refused
+    pub fn allowed(&self) -> bool {
+        self.decisions.iter().all(|decision| decision.allowed)
+    }
+
+    /// Decisions that would refuse the code
+    pub fn denied(&self) -> impl Iterator<Item = &PolicyDecision> {
+        self.decisions.iter().filter(|decision| !decision.allowed)
+    }
+}
+
+/// Functions that start a process, and the argument holding the executable
+const PROCESS_CALLS: &[(&str, usize)] = &[
+    ("subprocess.run", 0),
+    ("subprocess.Popen", 0),
+    ("subprocess.call", 0),
+    ("subprocess.check_call", 0),
+    ("subprocess.check_output", 0),
+    ("os.system", 0),
+    ("os.popen", 0),
+    ("os.execv", 0),
+    ("os.execvp", 0),
+    ("os.execve", 0),
+    ("os.spawnv", 1),
+    ("os.spawnvp", 1),
+    ("os.posix_spawn", 0),
+    ("os.posix_spawnp", 0),
+];
+
+/// `os.open` flags that make the call a write
+const WRITE_FLAGS: &[&str] = &["O_WRONLY", "O_RDWR", "O_APPEND", "O_CREAT", "O_TRUNC"];
+
+/// Report what `policy` would decide for each import, call and literal file
+/// path in `code`, without running it
+pub(crate) fn explain(policy: &SandboxPolicy, code: &str) -> Result<PolicyReport> {
+    let findings = StaticAnalyzer::for_policy(&policy.imports).analyze(code)?;
+    let tree = parse(code)?;
+    let source = code.as_bytes();
+
+    let mut decisions = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        explain_node(policy, node, source, &mut decisions);
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.named_children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+
+    // Imports were decided above; everything else the analyzer refuses is
+    // decided by its own rules
+    decisions.extend(
+        findings
+            .into_iter()
+            .filter(|finding| finding.kind != FindingKind::BlockedImport)
+            .map(|finding| PolicyDecision {
+                subject: match finding.kind {
+                    FindingKind::DunderAccess | FindingKind::FrameIntrospection => {
+                        Subject::Attribute
+                    }
+                    _ => Subject::Call,
+                },
+                name: finding.name.clone(),
+                line: finding.line,
+                column: finding.column,
+                allowed: finding.severity != Severity::Error,
+                rule: format!("analyzer.{}", finding.kind.rule()),
+                reason: finding.to_string(),
+            }),
+    );
+    decisions.sort_by_key(|decision| (decision.line, decision.column));
+    Ok(PolicyReport {
+        policy: policy.name.clone(),
+        decisions,
+    })
+}
+
+fn explain_node(
+    policy: &SandboxPolicy,
+    node: Node,
+    source: &[u8],
+    decisions: &mut Vec<PolicyDecision>,
+) {
+    let decide = |subject, name: &str, at: Node, (allowed, rule, reason)| {
+        let start = at.start_position();
+        PolicyDecision {
+            subject,
+            name: name.to_string(),
+            line: start.row + 1,
+            column: start.column + 1,
+            allowed,
+            rule,
+            reason,
+        }
+    };
+
+    match node.kind() {
+        "import_statement" => {
+            let mut cursor = node.walk();
+            for name in node.children_by_field_name("name", &mut cursor) {
+                let module = match name.kind() {
+                    "aliased_import" => name.child_by_field_name("name"),
+                    _ => Some(name),
+                };
+                if let Some(module) = module {
+                    let name = text(module, source);
+                    let decision = import_decision(&policy.imports, name);
+                    decisions.push(decide(Subject::Import, name, module, decision));
+                }
+            }
+        }
+        "import_from_statement" => {
+            if let Some(module) = node
+                .child_by_field_name("module_name")
+                .filter(|m| m.kind() == "dotted_name")
+            {
+                let (whitelist, blacklist) = policy.imports.lists();
+                let names = imported_names(node, source);
+                let name = text(module, source);
+                let name = match from_import_denial(whitelist, blacklist, name, &names) {
+                    Some(denied) => denied,
+                    // Allowed through its submodules alone
+                    None if !policy.imports.is_allowed(name) => format!("{}.{}", name, names[0]),
+                    None => name.to_string(),
+                };
+                let decision = import_decision(&policy.imports, &name);
+                decisions.push(decide(Subject::Import, &name, module, decision));
+            }
+        }
+        "call" => {
+            let Some(function) = node
+                .child_by_field_name("function")
+                .filter(|f| matches!(f.kind(), "identifier" | "attribute"))
+            else {
+                return;
+            };
+            let name = text(function, source);
+            match name {
+                "__import__" | "importlib.import_module" | "import_module" => {
+                    if let Some(module) =
+                        argument(node, 0).and_then(|arg| Some((arg, constant_string(arg, source)?)))
+                    {
+                        let decision = import_decision(&policy.imports, &module.1);
+                        decisions.push(decide(Subject::Import, &module.1, module.0, decision));
+                    }
+                }
+                "open" | "io.open" | "builtins.open" | "os.open" => {
+                    let Some((path_node, path)) = argument(node, 0)
+                        .and_then(|arg| Some((arg, constant_string(arg, source)?)))
+                    else {
+                        return;
+                    };
+                    let write = if name == "os.open" {
+                        argument(node, 1).is_some_and(|flags| {
+                            WRITE_FLAGS.iter().any(|f| text(flags, source).contains(f))
+                        })
+                    } else {
+                        argument(node, 1)
+                            .or_else(|| keyword_argument(node, "mode", source))
+                            .and_then(|mode| constant_string(mode, source))
+                            .is_some_and(|mode| mode.contains(['w', 'a', 'x', '+']))
+                    };
+                    let decision = path_decision(&policy.filesystem, &path, write);
+                    decisions.push(decide(Subject::Path, &path, path_node, decision));
+                }
+                _ => {
+                    let Some(&(_, index)) = PROCESS_CALLS.iter().find(|(call, _)| *call == name)
+                    else {
+                        return;
+                    };
+                    let executable = argument(node, index).and_then(|arg| match arg.kind() {
+                        "list" | "tuple" => constant_string(arg.named_child(0)?, source),
+                        _ => constant_string(arg, source)
+                            .and_then(|cmd| cmd.split_whitespace().next().map(str::to_string)),
+                    });
+                    let decision = process_decision(&policy.process, name, executable.as_deref());
+                    decisions.push(decide(Subject::Call, name, function, decision));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Value of keyword argument `name` of a call
+fn keyword_argument<'a>(call: Node<'a>, name: &str, source: &[u8]) -> Option<Node<'a>> {
+    let arguments = call.child_by_field_name("arguments")?;
+    let mut cursor = arguments.walk();
+    let keyword = arguments
+        .named_children(&mut cursor)
+        .filter(|arg| arg.kind() == "keyword_argument")
+        .find(|arg| {
+            arg.child_by_field_name("name")
+                .is_some_and(|n| text(n, source) == name)
+        })?;
+    keyword.child_by_field_name("value")
+}
+
+/// Names bound by a `from ... import` statement, `*` for a wildcard
+fn imported_names<'a>(node: Node, source: &'a [u8]) -> Vec<&'a str> {
+    let mut cursor = node.walk();
+    let mut names: Vec<&str> = node
+        .children_by_field_name("name", &mut cursor)
+        .filter_map(|name| match name.kind() {
+            "aliased_import" => name.child_by_field_name("name"),
+            _ => Some(name),
+        })
+        .map(|name| text(name, source))
+        .collect();
+    let mut cursor = node.walk();
+    if node
+        .children(&mut cursor)
+        .any(|child| child.kind() == "wildcard_import")
+    {
+        names.push("*");
+    }
+    names
+}
+
+fn import_decision(imports: &ImportPolicyType, module: &str) -> (bool, String, String) {
+    if matches!(imports, ImportPolicyType::Unrestricted) {
+        return (
+            true,
+            "imports".to_string(),
+            "imports are unrestricted".to_string(),
+        );
+    }
+    let (whitelist, blacklist) = imports.lists();
+    match import_rule(whitelist, blacklist, module) {
+        Some((false, entry)) => (
+            false,
+            "imports.blacklist".to_string(),
+            format!("'{}' is blacklisted", entry),
+        ),
+        Some((true, entry)) => (
+            true,
+            "imports.whitelist".to_string(),
+            format!("'{}' is whitelisted", entry),
+        ),
+        None if whitelist.is_some() => (
+            false,
+            "imports.whitelist".to_string(),
+            format!("'{}' is not in the whitelist", module),
+        ),
+        None => (
+            true,
+            "imports.blacklist".to_string(),
+            format!("'{}' is not blacklisted", module),
+        ),
+    }
+}
+
+/// Decide a literal path the way the audit hook's `open()` guard would,
+/// without resolving symlinks; relative paths are taken to be in the workspace
+fn path_decision(filesystem: &FilesystemPolicy, path: &str, write: bool) -> (bool, String, String) {
+    let rules = filesystem.rules();
+    let access = if write { "write" } else { "read" };
+    let allow = |rule: &str, why: String| (true, format!("filesystem.{}", rule), why);
+    let deny = |rule: &str, why: String| (false, format!("filesystem.{}", rule), why);
+
+    let path = Path::new(path);
+    if path.is_relative() {
+        return if rules.write_any || rules.workspace {
+            allow("workspace", format!("{} inside the workspace", access))
+        } else if !write && rules.read_any {
+            allow("read_any", "any path can be read".to_string())
+        } else {
+            deny(
+                "workspace",
+                format!(
+                    "relative path, and the policy grants no workspace {}",
+                    access
+                ),
+            )
+        };
+    }
+
+    let path: PathBuf = path.components().fold(PathBuf::new(), |mut out, part| {
+        match part {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            part => out.push(part),
+        }
+        out
+    });
+    let covering = |roots: &[PathBuf]| roots.iter().find(|root| path.starts_with(root)).cloned();
+
+    if let Some(root) = covering(&rules.deny) {
+        return deny("deny", format!("{} is denied", root.display()));
+    }
+    if rules.write_any {
+        return allow("write_any", "any path can be written".to_string());
+    }
+    if let Some(root) = covering(&rules.write) {
+        return allow("write", format!("{} is writable", root.display()));
+    }
+    if write {
+        return if path == Path::new("/dev/null") {
+            allow("write", "/dev/null is always writable".to_string())
+        } else {
+            deny(
+                "write",
+                format!("no rule lets {} be written", path.display()),
+            )
+        };
+    }
+    if rules.read_any {
+        return allow("read_any", "any path can be read".to_string());
+    }
+    if let Some(root) = covering(&rules.read) {
+        return allow("read", format!("{} is readable", root.display()));
+    }
+    let system: Vec<PathBuf> = crate::audit_hook::SYSTEM_READ_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    if let Some(root) = covering(&system) {
+        return allow("read", format!("{} is a system path", root.display()));
+    }
+    deny("read", format!("no rule lets {} be read", path.display()))
+}
+
+fn process_decision(
+    process: &ProcessPolicy,
+    call: &str,
+    executable: Option<&str>,
+) -> (bool, String, String) {
+    match process {
+        ProcessPolicy::Unrestricted => (
+            true,
+            "process".to_string(),
+            "processes are unrestricted".to_string(),
+        ),
+        ProcessPolicy::Blocked => (
+            false,
+            "process".to_string(),
+            "the policy blocks starting processes".to_string(),
+        ),
+        ProcessPolicy::AllowList(_) if call == "os.system" || call == "os.popen" => (
+            false,
+            "process.allow_list".to_string(),
+            format!("{} runs a shell, which an allowlist doesn't permit", call),
+        ),
+        ProcessPolicy::AllowList(allowed) => {
+            let listed = executable.is_some_and(|exe| {
+                allowed.iter().any(|entry| {
+                    entry == exe
+                        || (!exe.contains('/')
+                            && Path::new(entry).file_name().is_some_and(|n| n == exe))
+                })
+            });
+            match executable {
+                Some(exe) if listed => (
+                    true,
+                    "process.allow_list".to_string(),
+                    format!("'{}' is allowlisted", exe),
+                ),
+                Some(exe) => (
+                    false,
+                    "process.allow_list".to_string(),
+                    format!("'{}' is not allowlisted", exe),
+                ),
+                None => (
+                    false,
+                    "process.allow_list".to_string(),
+                    "the executable is only known at runtime".to_string(),
+                ),
+            }
+        }
+    }
+}
+
+/// Parse Python source, failing on syntax errors
+fn parse(code: &str) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_python::LANGUAGE.into())
+        .map_err(|e| SandboxError::InternalError(e.to_string()))?;
+    let tree = parser
+        .parse(code, None)
+        .ok_or_else(|| SandboxError::InternalError("Python parser failed".to_string()))?;
+
+    let root = tree.root_node();
+    if root.has_error() {
+        let at = first_error(root).unwrap_or(root).start_position();
+        return Err(SandboxError::SyntaxError(format!(
+            "invalid syntax at line {}, column {}",
+            at.row + 1,
+            at.column + 1
+        )));
+    }
+    Ok(tree)
+}
+
+fn finding(kind: FindingKind, name: &str, node: Node) -> Finding {
+    let start = node.start_position();
+    let end = node.end_position();
+    Finding {
+        kind,
+        severity: Severity::Error,
+        name: name.to_string(),
+        line: start.row + 1,
+        column: start.column + 1,
+        end_line: end.row + 1,
+        end_column: end.column + 1,
+    }
+}
+
+fn text<'a>(node: Node, source: &'a [u8]) -> &'a str {
+    node.utf8_text(source).unwrap_or_default()
+}
+
+/// Positional argument `index` of a call
+fn argument(call: Node, index: usize) -> Option<Node> {
+    let arguments = call.child_by_field_name("arguments")?;
+    let mut cursor = arguments.walk();
+    let positional: Vec<_> = arguments
+        .named_children(&mut cursor)
+        .filter(|arg| !matches!(arg.kind(), "keyword_argument" | "comment"))
+        .collect();
+    positional.get(index).copied()
+}
+
+/// Whether an identifier refers to a value, rather than naming an attribute,
+/// keyword, parameter, definition or imported name
+fn is_reference(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return true;
+    };
+    let is_field = |field| parent.child_by_field_name(field) == Some(node);
+    match parent.kind() {
+        "attribute" => !is_field("attribute"),
+        "keyword_argument" | "function_definition" | "class_definition" => !is_field("name"),
+        "default_parameter" | "typed_default_parameter" => !is_field("name"),
+        "parameters" | "lambda_parameters" | "typed_parameter" => false,
+        "dotted_name" | "aliased_import" => false,
+        _ => true,
+    }
+}
+
+/// Value of an expression built only from string literals: plain and
+/// concatenated literals, `+`, and `"sep".join([...])`
+fn constant_string(node: Node, source: &[u8]) -> Option<String> {
+    let mut cursor = node.walk();
+    match node.kind() {
+        "string" => string_value(node, source),
+        "concatenated_string" => node
+            .named_children(&mut cursor)
+            .map(|part| constant_string(part, source))
+            .collect(),
+        "parenthesized_expression" => constant_string(node.named_child(0)?, source),
+        "binary_operator" => {
+            if text(node.child_by_field_name("operator")?, source) != "+" {
+                return None;
+            }
+            let left = constant_string(node.child_by_field_name("left")?, source)?;
+            let right = constant_string(node.child_by_field_name("right")?, source)?;
+            Some(left + &right)
+        }
+        "call" => {
+            let function = node.child_by_field_name("function")?;
+            if function.kind() != "attribute"
+                || text(function.child_by_field_name("attribute")?, source) != "join"
+            {
+                return None;
+            }
+            let separator = constant_string(function.child_by_field_name("object")?, source)?;
+            let parts = argument(node, 0).filter(|arg| matches!(arg.kind(), "list" | "tuple"))?;
+            let parts: Option<Vec<_>> = parts
+                .named_children(&mut cursor)
+                .map(|part| constant_string(part, source))
+                .collect();
+            Some(parts?.join(&separator))
+        }
+        _ => None,
+    }
+}
+
+/// Contents of a string literal with escapes decoded, or `None` for
+/// f-strings with interpolations
+fn string_value(node: Node, source: &[u8]) -> Option<String> {
+    let mut cursor = node.walk();
+    let mut raw = false;
+    let mut value = String::new();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "string_start" => raw = text(child, source).contains(['r', 'R']),
+            "string_end" => {}
+            "string_content" if raw => value.push_str(text(child, source)),
+            "string_content" => value.push_str(&unescape(text(child, source))?),
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+/// Decode Python string escapes; `None` for `\N{...}` names
+fn unescape(content: &str) -> Option<String> {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        let mut hex = |digits: usize| {
+            let code: String = (0..digits).filter_map(|_| chars.next()).collect();
+            u32::from_str_radix(&code, 16).ok().and_then(char::from_u32)
+        };
+        match escape {
+            '\n' => {}
+            '\\' | '\'' | '"' => out.push(escape),
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'a' => out.push('\x07'),
+            'b' => out.push('\x08'),
+            'f' => out.push('\x0c'),
+            'v' => out.push('\x0b'),
+            'x' => out.push(hex(2)?),
+            'u' => out.push(hex(4)?),
+            'U' => out.push(hex(8)?),
+            '0'..='7' => {
+                let mut code = escape.to_digit(8)?;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|d| d.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                out.push(char::from_u32(code)?);
+            }
+            'N' => return None,
+            other => {
+                out.push('\\');
+                out.push(other);
+            }
+        }
+    }
+    Some(out)
+}
+
+fn first_error(root: Node) -> Option<Node> {
+    let mut node = root;
+    loop {
+        if node.is_error() || node.is_missing() {
+            return Some(node);
+        }
+        let mut cursor = node.walk();
+        let next = node.children(&mut cursor).find(|child| child.has_error());
+        node = next?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_analyzer_findings() {
+        let analyzer = StaticAnalyzer::new(ImportPolicy::default());
+        let code = r#"
+import json, os.path as p
+from subprocess import run
+from . import sibling
+x = ().__class__.__bases__[0].__subclasses__()
+eval("1 + 1")
+importlib.import_module("socket")
+"#;
+        let findings = analyzer.analyze(code).unwrap();
+        let summary: Vec<_> = findings
+            .iter()
+            .map(|f| (f.kind, f.name.as_str(), f.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (FindingKind::BlockedImport, "os.path", 2),
+                (FindingKind::BlockedImport, "subprocess", 3),
+                (FindingKind::DunderAccess, "__subclasses__", 5),
+                (FindingKind::DunderAccess, "__bases__", 5),
+                (FindingKind::DynamicExecution, "eval", 6),
+                (FindingKind::BlockedImport, "socket", 7),
+            ]
+        );
+
+        assert!(analyzer.check("import json\nprint(json.dumps({}))").is_ok());
+        assert!(matches!(
+            analyzer.check("import socket"),
+            Err(SandboxError::ImportNotAllowed(m)) if m == "socket"
+        ));
+        assert!(matches!(
+            analyzer.check("def f(:\n"),
+            Err(SandboxError::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn test_static_analyzer_sees_through_obfuscation() {
+        let analyzer = StaticAnalyzer::new(ImportPolicy::default());
+        let code = r#"
+m = __import__("o" + "s")
+importlib.import_module(name)
+f = getattr(builtins, "ex" "ec")
+g = getattr(obj, "__glo" + "bals__")
+h = getattr(obj, "".join(["ev", "al"]))
+k = getattr(obj, "\x65val")
+v = getattr(obj, f"{prefix}_name")
+run = builtins.compile
+pattern = re.compile("x")
+ok = getattr(obj, name)
+"#;
+        let findings = analyzer.analyze(code).unwrap();
+        let summary: Vec<_> = findings
+            .iter()
+            .map(|f| (f.kind, f.name.as_str(), f.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (FindingKind::BlockedCall, "__import__", 2),
+                (FindingKind::BlockedImport, "os", 2),
+                (FindingKind::DynamicImport, "importlib.import_module", 3),
+                (FindingKind::DynamicExecution, "exec", 4),
+                (FindingKind::DunderAccess, "__globals__", 5),
+                (FindingKind::DynamicExecution, "eval", 6),
+                (FindingKind::DynamicExecution, "eval", 7),
+                (FindingKind::ComputedAttribute, "getattr", 8),
+                (FindingKind::DynamicExecution, "compile", 9),
+            ]
+        );
+
+        let computed = &findings[2];
+        assert_eq!((computed.column, computed.end_column), (25, 29));
+    }
+
+    #[test]
+    fn test_static_analyzer_escape_primitives() {
+        let code = r#"
+base = ().__class__.__bases__[0]
+frame = sys._getframe(1).f_back
+caller = getattr(frame, "f_glob" + "als")
+"#;
+        let analyzer = StaticAnalyzer::new(ImportPolicy::default());
+        let findings = analyzer.analyze(code).unwrap();
+        let summary: Vec<_> = findings
+            .iter()
+            .map(|f| (f.kind, f.name.as_str(), f.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (FindingKind::DunderAccess, "__bases__", 2),
+                (FindingKind::FrameIntrospection, "f_back", 3),
+                (FindingKind::FrameIntrospection, "_getframe", 3),
+                (FindingKind::FrameIntrospection, "f_globals", 4),
+            ]
+        );
+        assert!(matches!(
+            analyzer.check(code),
+            Err(SandboxError::DisallowedOperation(_))
+        ));
+
+        let warn = analyzer.escape_primitives(Severity::Warning);
+        let findings = warn.analyze(code).unwrap();
+        assert!(findings.iter().all(|f| f.severity == Severity::Warning));
+        assert!(warn.check(code).is_ok());
+        assert!(warn.check(&format!("{}eval('1')", code)).is_err());
+    }
+
+    #[test]
+    fn test_static_analyzer_for_policy_type() {
+        let imports = ImportPolicyType::Whitelist(["matplotlib".to_string()].into());
+        let analyzer = StaticAnalyzer::for_policy(&imports);
+        assert!(analyzer.check("import matplotlib.pyplot as plt").is_ok());
+        assert!(analyzer.check("from matplotlib import pyplot").is_ok());
+        assert!(analyzer.check("import matplotlibx").is_err());
+
+        let analyzer = StaticAnalyzer::for_policy(&ImportPolicyType::default());
+        assert!(analyzer.check("import asyncio").is_ok());
+        assert!(analyzer.check("import asyncio.subprocess").is_err());
+    }
+
+    #[test]
+    fn test_static_analyzer_code_limits() {
+        let limits = CodeLimits {
+            max_source_bytes: 64,
+            max_ast_nodes: 50,
+            max_nesting_depth: 5,
+            max_functions: 2,
+        };
+        let analyzer = StaticAnalyzer::new(ImportPolicy::default()).code_limits(limits);
+        let too_complex =
+            |code: &str| matches!(analyzer.analyze(code), Err(SandboxError::CodeTooComplex(_)));
+
+        assert!(!too_complex("x = [[1, 2], (3, 4)]"));
+        assert!(too_complex(&"x = 1\n".repeat(20)));
+        assert!(too_complex("x = [[[[[[1]]]]]]"));
+        assert!(too_complex("def a(): pass\ndef b(): pass\nc = lambda: 0"));
+        // Long flat expressions aren't nesting
+        assert!(!too_complex("x = 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8"));
+    }
+
+    #[test]
+    fn test_extract_imports() {
+        let code = r#"
+import numpy as np, os.path
+from pandas import DataFrame, Series as S
+from .util import *
+from .. import sibling
+
+try:
+    import ujson as json
+except ImportError:
+    import json
+
+def plot():
+    from matplotlib import pyplot
+"#;
+        let report = extract_imports(code).unwrap();
+        let summary: Vec<_> = report
+            .imports
+            .iter()
+            .map(|i| {
+                (
+                    i.module.as_str(),
+                    i.level,
+                    i.line,
+                    i.top_level,
+                    i.conditional,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("numpy", 0, 2, true, false),
+                ("os.path", 0, 2, true, false),
+                ("pandas", 0, 3, true, false),
+                ("util", 1, 4, true, false),
+                ("", 2, 5, true, false),
+                ("ujson", 0, 8, false, true),
+                ("json", 0, 10, false, true),
+                ("matplotlib", 0, 13, false, true),
+            ]
+        );
+        assert_eq!(report.imports[2].names, vec!["DataFrame", "Series"]);
+        assert_eq!(report.imports[3].names, vec!["*"]);
+        assert_eq!(
+            report.root_modules().into_iter().collect::<Vec<_>>(),
+            vec!["json", "matplotlib", "numpy", "os", "pandas", "ujson"]
+        );
+        assert_eq!(
+            report.required_modules().into_iter().collect::<Vec<_>>(),
+            vec!["numpy", "os", "pandas"]
+        );
+    }
+
+    #[test]
+    fn test_explain_policy() {
+        let policy = SandboxPolicy::custom(SandboxPolicy::data_science())
+            .filesystem(crate::policy::FilesystemPolicy::Paths(
+                crate::policy::FilesystemRules::new()
+                    .read("/data")
+                    .deny("/data/secrets"),
+            ))
+            .build();
+        let code = r#"
+import pandas as pd
+import socket
+df = pd.read_csv("in.csv")
+open("/data/sales.csv").read()
+open("/data/secrets/key", "r")
+open("/data/out.csv", mode="w")
+subprocess.run(["ls", "-l"])
+eval("1")
+"#;
+        let report = policy.explain(code).unwrap();
+        let summary: Vec<_> = report
+            .decisions
+            .iter()
+            .map(|d| (d.subject, d.name.as_str(), d.allowed, d.rule.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Subject::Import, "pandas", true, "imports.whitelist"),
+                (Subject::Import, "socket", false, "imports.whitelist"),
+                (Subject::Path, "/data/sales.csv", true, "filesystem.read"),
+                (Subject::Path, "/data/secrets/key", false, "filesystem.deny"),
+                (Subject::Path, "/data/out.csv", false, "filesystem.write"),
+                (Subject::Call, "subprocess.run", false, "process"),
+                (Subject::Call, "eval", false, "analyzer.dynamic_execution"),
+            ]
+        );
+        assert!(!report.allowed());
+        assert_eq!(report.denied().count(), 5);
+    }
+}
@@ -0,0 +1,445 @@
+//! Content-addressed store for exported output files
+//!
+//! Files a run leaves in its workspace `output/` directory are exported to
+//! an [`ArtifactStore`] when one is configured (`RZN_PYTHON_EXPORT_DIR`, or
+//! `generated/python_exports` under `RZN_APP_BASE_DIR`). Each file is stored
+//! once under its SHA-256 in `objects/`, with metadata recording its size,
+//! mime type and the runs that produced it, and hard-linked into
+//! `<run id>/<name>` so hosts keep finding outputs by name. Identical outputs
+//! from different runs share one object. Objects are read-only; an optional
+//! [`RetentionPolicy`] removes those not produced recently or beyond a size
+//! budget after each export.
+
+use crate::errors::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Most files exported from one run
+const MAX_FILES: usize = 32;
+/// Most bytes exported from one run
+const MAX_TOTAL_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Serializes metadata updates, which read, modify and rewrite a file
+static METADATA_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Exported files stored by content hash
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+    retention: RetentionPolicy,
+}
+
+/// Which artifacts to keep; the default keeps everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Remove artifacts no run has produced for this long
+    pub max_age: Option<Duration>,
+    /// Remove the least recently produced artifacts until the rest take at
+    /// most this many bytes
+    pub max_total_bytes: Option<u64>,
+}
+
+/// Metadata of a stored artifact
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactMeta {
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub mime_type: String,
+    /// Runs that produced this content, oldest first
+    pub origins: Vec<ArtifactOrigin>,
+}
+
+/// One run that exported an artifact
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactOrigin {
+    pub run_id: String,
+    /// File name in the run's output directory
+    pub name: String,
+    /// Unix seconds
+    pub stored_at: u64,
+}
+
+/// A file exported from a run, as listed in `exported_files`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredArtifact {
+    pub name: String,
+    /// Link to the object in the run's export directory
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub sha256: String,
+    pub mime_type: String,
+    /// Whether an earlier run already stored the same content
+    pub deduplicated: bool,
+}
+
+/// What [`ArtifactStore::apply_retention`] removed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionReport {
+    /// Hashes of the removed artifacts
+    pub removed: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+impl ArtifactMeta {
+    /// When a run last produced this content
+    fn last_stored(&self) -> u64 {
+        self.origins.iter().map(|o| o.stored_at).max().unwrap_or(0)
+    }
+}
+
+impl ArtifactStore {
+    /// Store rooted at `root`, created on first use
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            retention: RetentionPolicy::default(),
+        }
+    }
+
+    /// Store configured by the environment, if exports are enabled
+    ///
+    /// The root is `RZN_PYTHON_EXPORT_DIR`, else `generated/python_exports`
+    /// under `RZN_APP_BASE_DIR`. `RZN_PYTHON_EXPORT_MAX_AGE_SECS` and
+    /// `RZN_PYTHON_EXPORT_MAX_MB` set the retention policy.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let root = var("RZN_PYTHON_EXPORT_DIR")
+            .map(PathBuf::from)
+            .or_else(|| {
+                var("RZN_APP_BASE_DIR")
+                    .map(|base| PathBuf::from(base).join("generated").join("python_exports"))
+            })?;
+        let number = |name: &str| var(name).and_then(|v| v.parse::<u64>().ok());
+        Some(Self::new(root).with_retention(RetentionPolicy {
+            max_age: number("RZN_PYTHON_EXPORT_MAX_AGE_SECS").map(Duration::from_secs),
+            max_total_bytes: number("RZN_PYTHON_EXPORT_MAX_MB").map(|mb| mb * 1024 * 1024),
+        }))
+    }
+
+    /// Apply `retention` after every export
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Directory holding a run's exported files by name
+    pub fn run_dir(&self, run_id: &str) -> PathBuf {
+        self.root.join(run_id)
+    }
+
+    /// Store `src` as the output `name` of `run_id`
+    pub fn put(&self, run_id: &str, name: &str, src: &Path) -> Result<StoredArtifact> {
+        let sha256 = hash_file(src)?;
+        let object = self.object_path(&sha256);
+        let deduplicated = object.is_file();
+        if !deduplicated {
+            std::fs::create_dir_all(object.parent().unwrap_or(&self.root))?;
+            let tmp = object.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+            std::fs::copy(src, &tmp)?;
+            let mut permissions = std::fs::metadata(&tmp)?.permissions();
+            permissions.set_readonly(true);
+            std::fs::set_permissions(&tmp, permissions)?;
+            std::fs::rename(&tmp, &object)?;
+        }
+        let size_bytes = std::fs::metadata(&object)?.len();
+        let mime_type = mime_type_for(name).to_string();
+
+        let run_dir = self.run_dir(run_id);
+        std::fs::create_dir_all(&run_dir)?;
+        let path = run_dir.join(name);
+        if std::fs::symlink_metadata(&path).is_ok() {
+            std::fs::remove_file(&path)?;
+        }
+        if std::fs::hard_link(&object, &path).is_err() {
+            std::fs::copy(&object, &path)?;
+        }
+
+        let origin = ArtifactOrigin {
+            run_id: run_id.to_string(),
+            name: name.to_string(),
+            stored_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        {
+            let _guard = METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let mut meta = self.get(&sha256)?.unwrap_or_else(|| ArtifactMeta {
+                sha256: sha256.clone(),
+                size_bytes,
+                mime_type: mime_type.clone(),
+                origins: Vec::new(),
+            });
+            meta.origins
+                .retain(|o| o.run_id != origin.run_id || o.name != origin.name);
+            meta.origins.push(origin);
+            write_meta(&self.meta_path(&sha256), &meta)?;
+        }
+
+        Ok(StoredArtifact {
+            name: name.to_string(),
+            path,
+            size_bytes,
+            sha256,
+            mime_type,
+            deduplicated,
+        })
+    }
+
+    /// Store the regular files in a run's `output_dir`, returning the run's
+    /// export directory, or `None` if there was nothing to export
+    ///
+    /// Stops at 32 files or 200MB. Retention is applied afterwards.
+    pub fn export_outputs(
+        &self,
+        run_id: &str,
+        output_dir: &Path,
+    ) -> Result<Option<(PathBuf, Vec<StoredArtifact>)>> {
+        let mut exported = Vec::new();
+        let mut total_bytes: u64 = 0;
+        for entry in std::fs::read_dir(output_dir)? {
+            let Ok(entry) = entry else { continue };
+            if exported.len() >= MAX_FILES {
+                break;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if !file_type.is_file() {
+                continue;
+            }
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str() else {
+                continue;
+            };
+            let src = entry.path();
+            if let Ok(meta) = std::fs::metadata(&src) {
+                total_bytes = total_bytes.saturating_add(meta.len());
+                if total_bytes > MAX_TOTAL_BYTES {
+                    warn!(
+                        "[ARTIFACTS] Output export size limit exceeded ({} bytes), stopping export",
+                        total_bytes
+                    );
+                    break;
+                }
+            }
+            match self.put(run_id, name, &src) {
+                Ok(artifact) => exported.push(artifact),
+                Err(e) => warn!("[ARTIFACTS] Failed to export {:?}: {}", src, e),
+            }
+        }
+        if exported.is_empty() {
+            return Ok(None);
+        }
+
+        if self.retention != RetentionPolicy::default() {
+            if let Err(e) = self.apply_retention() {
+                warn!("[ARTIFACTS] Failed to apply retention: {}", e);
+            }
+        }
+        Ok(Some((self.run_dir(run_id), exported)))
+    }
+
+    /// Metadata of the artifact with this hash
+    pub fn get(&self, sha256: &str) -> Result<Option<ArtifactMeta>> {
+        if !is_hash(sha256) {
+            return Ok(None);
+        }
+        match std::fs::read(self.meta_path(sha256)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Metadata of every stored artifact
+    pub fn list(&self) -> Result<Vec<ArtifactMeta>> {
+        let shards = match std::fs::read_dir(self.root.join("objects")) {
+            Ok(shards) => shards,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut artifacts = Vec::new();
+        for shard in shards {
+            for entry in std::fs::read_dir(shard?.path())? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    match std::fs::read(&path)
+                        .map_err(crate::SandboxError::from)
+                        .and_then(|data| Ok(serde_json::from_slice(&data)?))
+                    {
+                        Ok(meta) => artifacts.push(meta),
+                        Err(e) => warn!("[ARTIFACTS] Skipping metadata {:?}: {}", path, e),
+                    }
+                }
+            }
+        }
+        Ok(artifacts)
+    }
+
+    /// Remove what the retention policy calls for, with the run directory
+    /// links to it
+    pub fn apply_retention(&self) -> Result<RetentionReport> {
+        let _guard = METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut artifacts = self.list()?;
+        artifacts.sort_by_key(ArtifactMeta::last_stored);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut total_bytes: u64 = artifacts.iter().map(|a| a.size_bytes).sum();
+        let mut report = RetentionReport::default();
+        for artifact in artifacts {
+            let age = Duration::from_secs(now.saturating_sub(artifact.last_stored()));
+            let expired = self.retention.max_age.is_some_and(|max| age > max);
+            let over_budget = self
+                .retention
+                .max_total_bytes
+                .is_some_and(|max| total_bytes > max);
+            if !expired && !over_budget {
+                continue;
+            }
+            self.remove(&artifact)?;
+            total_bytes -= artifact.size_bytes;
+            report.freed_bytes += artifact.size_bytes;
+            report.removed.push(artifact.sha256);
+        }
+        Ok(report)
+    }
+
+    fn remove(&self, artifact: &ArtifactMeta) -> Result<()> {
+        for origin in &artifact.origins {
+            let run_dir = self.run_dir(&origin.run_id);
+            let path = run_dir.join(&origin.name);
+            // Only a link to this object; a later run may have replaced it
+            if hash_file(&path).ok().as_deref() == Some(artifact.sha256.as_str()) {
+                std::fs::remove_file(&path)?;
+            }
+            // Fails, as intended, while the run still has other exports
+            let _ = std::fs::remove_dir(&run_dir);
+        }
+        remove_if_exists(&self.object_path(&artifact.sha256))?;
+        remove_if_exists(&self.meta_path(&artifact.sha256))?;
+        Ok(())
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        self.root.join("objects").join(&sha256[..2]).join(sha256)
+    }
+
+    fn meta_path(&self, sha256: &str) -> PathBuf {
+        self.object_path(sha256).with_extension("json")
+    }
+}
+
+/// Guess a mime type from a file name's extension
+pub fn mime_type_for(name: &str) -> &'static str {
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("pdf") => "application/pdf",
+        Some("json") => "application/json",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("md") => "text/markdown",
+        Some("txt" | "log") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn is_hash(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn write_meta(path: &Path, meta: &ArtifactMeta) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(meta)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifacts_deduplicate_and_expire() {
+        let root = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(root.path());
+        std::fs::write(output.path().join("plot.png"), b"same bytes").unwrap();
+
+        let (run_dir, first) = store
+            .export_outputs("run-1", output.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(run_dir, root.path().join("run-1"));
+        assert!(!first[0].deduplicated);
+        assert_eq!(first[0].mime_type, "image/png");
+        std::fs::rename(
+            output.path().join("plot.png"),
+            output.path().join("copy.png"),
+        )
+        .unwrap();
+        let (_, second) = store
+            .export_outputs("run-2", output.path())
+            .unwrap()
+            .unwrap();
+        assert!(second[0].deduplicated);
+        assert_eq!(second[0].sha256, first[0].sha256);
+        assert_eq!(std::fs::read(&second[0].path).unwrap(), b"same bytes");
+
+        let artifacts = store.list().unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].origins.len(), 2);
+
+        // A budget of zero bytes removes the object and both runs' links
+        let report = store
+            .with_retention(RetentionPolicy {
+                max_total_bytes: Some(0),
+                ..Default::default()
+            })
+            .apply_retention()
+            .unwrap();
+        assert_eq!(report.removed, vec![first[0].sha256.clone()]);
+        assert_eq!(report.freed_bytes, 10);
+        assert!(!run_dir.exists() && !second[0].path.exists());
+    }
+}
@@ -0,0 +1,724 @@
+//! Structured audit log of sandbox executions
+//!
+//! Attach an [`AuditLog`] to a [`PythonSandbox`](crate::PythonSandbox) with
+//! `with_audit_log` and every `execute` call records what ran, under which
+//! policy, and what happened. Policies with `audit_logging` set get one
+//! automatically from [`SandboxPolicy::build_sandbox`](crate::SandboxPolicy::build_sandbox).
+//!
+//! Events go to every [`AuditSink`] on the log: [`JsonlAuditSink`] for a
+//! file or writer, [`RotatingAuditSink`] for size/age-rotated files,
+//! [`SyslogAuditSink`] for syslog/journald, or [`CallbackAuditSink`] to hand
+//! them to the host. File sinks write one JSON object per line:
+//!
+//! ```json
+//! {"timestamp":"2026-01-05T09:12:44.120391Z","event":"execution_started","policy":"Balanced","code_hash":"9f86d0..."}
+//! {"timestamp":"2026-01-05T09:12:44.301187Z","event":"import_blocked","module":"subprocess","policy":"Balanced","code_hash":"9f86d0..."}
+//! {"timestamp":"2026-01-05T09:12:44.301190Z","event":"execution_finished","outcome":"error","message":"...","duration_ms":181,"policy":"Balanced","code_hash":"9f86d0..."}
+//! ```
+
+use crate::errors::SandboxError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Environment variable naming the JSONL file for [`JsonlAuditSink::from_env`]
+pub const AUDIT_LOG_ENV: &str = "RZN_AUDIT_LOG";
+
+/// One audit log entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// UTC time the event was recorded, ISO 8601
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub kind: AuditEventKind,
+    /// Name of the policy the code ran under, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// SHA-256 of the executed code, hex encoded
+    pub code_hash: String,
+}
+
+/// What an [`AuditEvent`] records
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEventKind {
+    /// Execution was handed to an engine
+    ExecutionStarted,
+    /// Execution ended, successfully or not
+    ExecutionFinished {
+        #[serde(flatten)]
+        outcome: AuditOutcome,
+        duration_ms: u64,
+        /// The output was served from the execution cache without running
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        cache_hit: bool,
+    },
+    /// The code did something the policy forbids
+    PolicyViolation { message: String },
+    /// An import was refused
+    ImportBlocked { module: String },
+    /// A network connection or lookup was refused
+    NetworkDenied { target: String },
+    /// A file from the workspace output directory was exported to the host
+    FileExported {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size_bytes: Option<u64>,
+    },
+}
+
+/// How an execution ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Timeout,
+    Cancelled,
+    Error { message: String },
+}
+
+/// Destination for audit events
+pub trait AuditSink: Send + Sync {
+    /// Record one event. Failures are the sink's to report; they never fail
+    /// the execution being audited.
+    fn record(&self, event: &AuditEvent);
+}
+
+/// Writes events as JSON lines
+pub struct JsonlAuditSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonlAuditSink {
+    /// Write to any writer, e.g. a socket or an in-memory buffer
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Append to a file, creating it if needed
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::new(file))
+    }
+
+    /// Append to the file in [`AUDIT_LOG_ENV`], or write to stderr if it
+    /// isn't set
+    pub fn from_env() -> std::io::Result<Self> {
+        match std::env::var_os(AUDIT_LOG_ENV).filter(|p| !p.is_empty()) {
+            Some(path) => Self::open(Path::new(&path)),
+            None => Ok(Self::new(std::io::stderr())),
+        }
+    }
+}
+
+impl AuditSink for JsonlAuditSink {
+    fn record(&self, event: &AuditEvent) {
+        let Some(line) = jsonl_line(event) else {
+            return;
+        };
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writer.write_all(&line).and_then(|_| writer.flush()) {
+            tracing::warn!("Failed to write audit event: {}", e);
+        }
+    }
+}
+
+impl AuditSink for Box<dyn AuditSink> {
+    fn record(&self, event: &AuditEvent) {
+        (**self).record(event)
+    }
+}
+
+/// Writes JSON lines to a file, rotating it by size and age
+///
+/// When the file would grow past `max_bytes`, or was started more than
+/// `max_age` ago, it is renamed to `<file>.1` (shifting older copies up to
+/// `<file>.<keep>`, dropping the oldest) and a new file is started.
+pub struct RotatingAuditSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_age: Option<Duration>,
+    keep: usize,
+    current: Mutex<Option<RotatingFile>>,
+}
+
+struct RotatingFile {
+    file: std::fs::File,
+    size: u64,
+    started: SystemTime,
+}
+
+impl RotatingAuditSink {
+    /// Rotate `path` at 10 MB, keeping 5 old files
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: 10 * 1024 * 1024,
+            max_age: None,
+            keep: 5,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Rotate before the file would exceed this size
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Rotate once the file is this old
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Number of rotated files to keep
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    fn write(&self, line: &[u8]) -> std::io::Result<()> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(open) = current.as_ref() {
+            let too_big = open.size > 0 && open.size + line.len() as u64 > self.max_bytes;
+            let too_old = self
+                .max_age
+                .is_some_and(|age| open.started.elapsed().unwrap_or_default() >= age);
+            if too_big || too_old {
+                *current = None;
+                self.rotate()?;
+            }
+        }
+        let open = match current.as_mut() {
+            Some(open) => open,
+            None => current.insert(self.open()?),
+        };
+        open.file.write_all(line)?;
+        open.file.flush()?;
+        open.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self) -> std::io::Result<RotatingFile> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let metadata = file.metadata()?;
+        let started = metadata
+            .created()
+            .or_else(|_| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        Ok(RotatingFile {
+            file,
+            size: metadata.len(),
+            started: if metadata.len() == 0 {
+                SystemTime::now()
+            } else {
+                started
+            },
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.as_os_str().to_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        for index in (1..self.keep).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(from, self.rotated_path(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))
+    }
+}
+
+impl AuditSink for RotatingAuditSink {
+    fn record(&self, event: &AuditEvent) {
+        let Some(line) = jsonl_line(event) else {
+            return;
+        };
+        if let Err(e) = self.write(&line) {
+            tracing::warn!(
+                "Failed to write audit event to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Sends events to the local syslog daemon (or journald) over `/dev/log`
+///
+/// Each message carries the event as JSON, at `notice` severity for
+/// executions and `warning` for denials.
+#[cfg(unix)]
+pub struct SyslogAuditSink {
+    socket: std::os::unix::net::UnixDatagram,
+    ident: String,
+}
+
+#[cfg(unix)]
+impl SyslogAuditSink {
+    /// `LOG_AUTHPRIV`: audit trails belong with other security messages
+    const FACILITY: u8 = 10;
+
+    /// Connect to the system log socket, tagging messages with `ident`
+    pub fn new(ident: &str) -> std::io::Result<Self> {
+        // macOS syslogd listens on /var/run/syslog
+        match Self::with_socket(Path::new("/dev/log"), ident) {
+            Err(_) if Path::new("/var/run/syslog").exists() => {
+                Self::with_socket(Path::new("/var/run/syslog"), ident)
+            }
+            result => result,
+        }
+    }
+
+    /// Connect to a specific datagram socket
+    pub fn with_socket(path: &Path, ident: &str) -> std::io::Result<Self> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self {
+            socket,
+            ident: ident.to_string(),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl AuditSink for SyslogAuditSink {
+    fn record(&self, event: &AuditEvent) {
+        let Ok(json) = serde_json::to_string(event) else {
+            return;
+        };
+        let severity = match &event.kind {
+            AuditEventKind::ExecutionStarted
+            | AuditEventKind::ExecutionFinished { .. }
+            | AuditEventKind::FileExported { .. } => 5,
+            _ => 4,
+        };
+        let message = format!(
+            "<{}>{}[{}]: {}",
+            Self::FACILITY * 8 + severity,
+            self.ident,
+            std::process::id(),
+            json
+        );
+        if let Err(e) = self.socket.send(message.as_bytes()) {
+            tracing::warn!("Failed to send audit event to syslog: {}", e);
+        }
+    }
+}
+
+/// Hands each event to a host callback, e.g. to forward it to the host's own
+/// telemetry pipeline
+pub struct CallbackAuditSink<F> {
+    callback: F,
+}
+
+impl<F: Fn(&AuditEvent) + Send + Sync> CallbackAuditSink<F> {
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+}
+
+impl<F: Fn(&AuditEvent) + Send + Sync> AuditSink for CallbackAuditSink<F> {
+    fn record(&self, event: &AuditEvent) {
+        (self.callback)(event)
+    }
+}
+
+/// `event` as one JSON line
+fn jsonl_line(event: &AuditEvent) -> Option<Vec<u8>> {
+    let mut line = serde_json::to_vec(event).ok()?;
+    line.push(b'\n');
+    Some(line)
+}
+
+/// Audit log handle: the sinks events go to plus the policy name stamped on
+/// each event
+#[derive(Clone)]
+pub struct AuditLog {
+    sinks: Vec<Arc<dyn AuditSink>>,
+    policy: Option<String>,
+}
+
+impl AuditLog {
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sinks: vec![Arc::new(sink)],
+            policy: None,
+        }
+    }
+
+    /// Also send every event to `sink`
+    pub fn with_sink(mut self, sink: impl AuditSink + 'static) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Stamp events with a policy name
+    pub fn with_policy(mut self, policy: &str) -> Self {
+        self.policy = Some(policy.to_string());
+        self
+    }
+
+    /// Record an event for `code`
+    pub fn record(&self, code: &str, kind: AuditEventKind) {
+        let event = AuditEvent {
+            timestamp: iso8601_now(),
+            kind,
+            policy: self.policy.clone(),
+            code_hash: code_hash(code),
+        };
+        for sink in &self.sinks {
+            sink.record(&event);
+        }
+    }
+
+    /// Record the end of an execution, with any violations and exported
+    /// files it implies
+    ///
+    /// Cached outputs are marked `cache_hit`; their files were exported by
+    /// the run that produced them, so aren't recorded again.
+    pub(crate) fn record_result(
+        &self,
+        code: &str,
+        result: &crate::Result<crate::engine::ExecutionResult>,
+        elapsed: Duration,
+    ) {
+        let cache_hit = result.as_ref().is_ok_and(|value| {
+            value.extra.get("cache_hit") == Some(&serde_json::Value::Bool(true))
+        });
+        let outcome = match result {
+            Ok(_) if cache_hit => AuditOutcome::Success,
+            Ok(value) => {
+                let exported = value.extra.get("exported_files").and_then(|f| f.as_array());
+                for file in exported.into_iter().flatten() {
+                    if let Some(path) = file.get("path").and_then(|p| p.as_str()) {
+                        self.record(
+                            code,
+                            AuditEventKind::FileExported {
+                                path: path.to_string(),
+                                size_bytes: file.get("size_bytes").and_then(|s| s.as_u64()),
+                            },
+                        );
+                    }
+                }
+                AuditOutcome::Success
+            }
+            Err(SandboxError::Timeout) => AuditOutcome::Timeout,
+            Err(SandboxError::Cancelled) => AuditOutcome::Cancelled,
+            Err(e) => {
+                if let Some(kind) = classify_error(e) {
+                    self.record(code, kind);
+                }
+                AuditOutcome::Error {
+                    message: e.to_string(),
+                }
+            }
+        };
+        self.record(
+            code,
+            AuditEventKind::ExecutionFinished {
+                outcome,
+                duration_ms: elapsed.as_millis() as u64,
+                cache_hit,
+            },
+        );
+    }
+}
+
+/// Map an execution error to the denial it reports, if any
+///
+/// Guards tag their errors with a denial marker, which engines map to the
+/// blocked variants. Errors that lost the marker, e.g. from a remote engine,
+/// are matched by message.
+fn classify_error(error: &SandboxError) -> Option<AuditEventKind> {
+    let message = match error {
+        SandboxError::ImportNotAllowed(module) | SandboxError::ImportBlocked { module } => {
+            return Some(AuditEventKind::ImportBlocked {
+                module: module.clone(),
+            })
+        }
+        SandboxError::NetworkBlocked { host } => {
+            return Some(AuditEventKind::NetworkDenied {
+                target: host.clone(),
+            })
+        }
+        SandboxError::FileWriteBlocked { .. } | SandboxError::SubprocessBlocked { .. } => {
+            return Some(AuditEventKind::PolicyViolation {
+                message: error.to_string(),
+            })
+        }
+        // The static analyzer reports "import of 'x' at line ..."
+        SandboxError::DisallowedOperation(message) if message.starts_with("import of '") => {
+            let module = message["import of '".len()..].split('\'').next();
+            return Some(AuditEventKind::ImportBlocked {
+                module: module.unwrap_or_default().to_string(),
+            });
+        }
+        SandboxError::SecurityViolation(message) | SandboxError::DisallowedOperation(message) => {
+            return Some(AuditEventKind::PolicyViolation {
+                message: message.clone(),
+            })
+        }
+        SandboxError::RuntimeError(message) => message.clone(),
+        SandboxError::PythonException(exception) => exception.to_string(),
+        _ => return None,
+    };
+
+    if let Some(rest) = message.split("Module '").nth(1) {
+        let is_denial = [
+            "blacklisted",
+            "not in whitelist",
+            "blocked by sandbox policy",
+        ]
+        .iter()
+        .any(|reason| rest.contains(reason));
+        if let (true, Some((module, _))) = (is_denial, rest.split_once('\'')) {
+            return Some(AuditEventKind::ImportBlocked {
+                module: module.to_string(),
+            });
+        }
+    }
+    if let Some(rest) = message.split("Network host not allowed: ").nth(1) {
+        return Some(AuditEventKind::NetworkDenied {
+            target: rest.lines().next().unwrap_or_default().trim().to_string(),
+        });
+    }
+    if let Some(line) = message
+        .lines()
+        .find(|line| line.contains("denied by sandbox policy"))
+    {
+        let line = line.trim().trim_start_matches("PermissionError: ");
+        if line.starts_with("socket.") {
+            let target = line
+                .split_once(" to ")
+                .or_else(|| line.split_once(" of "))
+                .map(|(_, rest)| rest.trim_end_matches(" denied by sandbox policy"))
+                .unwrap_or(line);
+            return Some(AuditEventKind::NetworkDenied {
+                target: target.to_string(),
+            });
+        }
+        return Some(AuditEventKind::PolicyViolation {
+            message: line.to_string(),
+        });
+    }
+    None
+}
+
+/// SHA-256 of `code`, hex encoded
+pub fn code_hash(code: &str) -> String {
+    Sha256::digest(code.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Current UTC time as ISO 8601 with microseconds
+pub(crate) fn iso8601_now() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (hour, minute, second) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = (secs / 86400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        now.subsec_micros()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shared in-memory writer
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_audit_log_writes_jsonl() {
+        let buffer = Buffer::default();
+        let log = AuditLog::new(JsonlAuditSink::new(buffer.clone())).with_policy("Balanced");
+
+        log.record("import os", AuditEventKind::ExecutionStarted);
+        log.record_result(
+            "import os",
+            &Err(SandboxError::RuntimeError(
+                "ImportError: Module 'os' is not in whitelist".to_string(),
+            )),
+            Duration::from_millis(12),
+        );
+        log.record_result(
+            "import subprocess",
+            &Err(SandboxError::DisallowedOperation(
+                "import of 'subprocess' at line 1, column 8".to_string(),
+            )),
+            Duration::from_millis(0),
+        );
+        log.record_result(
+            "urlopen(url)",
+            &Err(SandboxError::RuntimeError(
+                "PermissionError: Network host not allowed: example.com".to_string(),
+            )),
+            Duration::from_millis(3),
+        );
+        log.record_result(
+            "save()",
+            &crate::engine::ExecutionResult::from_value(serde_json::json!({
+                "exported_files": [{"name": "a.png", "path": "/exports/a.png", "size_bytes": 10}]
+            })),
+            Duration::from_millis(5),
+        );
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<AuditEvent> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let kinds: Vec<&AuditEventKind> = events.iter().map(|e| &e.kind).collect();
+
+        assert_eq!(events.len(), 9);
+        assert!(events
+            .iter()
+            .all(|e| e.policy.as_deref() == Some("Balanced")));
+        assert_eq!(events[0].code_hash, code_hash("import os"));
+        assert_eq!(events[0].code_hash.len(), 64);
+        assert_eq!(
+            kinds[1],
+            &AuditEventKind::ImportBlocked {
+                module: "os".to_string()
+            }
+        );
+        assert!(matches!(
+            kinds[2],
+            AuditEventKind::ExecutionFinished {
+                outcome: AuditOutcome::Error { .. },
+                duration_ms: 12,
+                cache_hit: false
+            }
+        ));
+        assert_eq!(
+            kinds[3],
+            &AuditEventKind::ImportBlocked {
+                module: "subprocess".to_string()
+            }
+        );
+        assert_eq!(
+            kinds[5],
+            &AuditEventKind::NetworkDenied {
+                target: "example.com".to_string()
+            }
+        );
+        assert_eq!(
+            kinds[7],
+            &AuditEventKind::FileExported {
+                path: "/exports/a.png".to_string(),
+                size_bytes: Some(10)
+            }
+        );
+        assert!(written
+            .lines()
+            .last()
+            .unwrap()
+            .contains(r#""outcome":"success""#));
+    }
+
+    #[test]
+    fn test_rotating_sink_keeps_newest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::new(RotatingAuditSink::new(&path).max_bytes(300).keep(2));
+        for _ in 0..8 {
+            log.record("x = 1", AuditEventKind::ExecutionStarted);
+        }
+
+        let mut files: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["audit.jsonl", "audit.jsonl.1", "audit.jsonl.2"]);
+        for file in &files {
+            let contents = std::fs::read_to_string(dir.path().join(file)).unwrap();
+            assert!(contents.len() <= 300);
+            assert!(contents.lines().all(|l| l.contains("execution_started")));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_syslog_and_callback_sinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("log.sock");
+        let server = std::os::unix::net::UnixDatagram::bind(&socket_path).unwrap();
+        let forwarded = Arc::new(Mutex::new(Vec::new()));
+        let seen = forwarded.clone();
+
+        let log = AuditLog::new(SyslogAuditSink::with_socket(&socket_path, "pysandbox").unwrap())
+            .with_sink(CallbackAuditSink::new(move |event: &AuditEvent| {
+                seen.lock().unwrap().push(event.kind.clone())
+            }));
+        log.record(
+            "import socket",
+            AuditEventKind::NetworkDenied {
+                target: "example.com".to_string(),
+            },
+        );
+
+        let mut buf = [0u8; 1024];
+        let n = server.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..n]).unwrap();
+        assert!(message.starts_with("<84>pysandbox["), "{message}");
+        assert!(message.contains(r#""event":"network_denied""#));
+        assert_eq!(forwarded.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,544 @@
+//! `sys.addaudithook` guard generation
+//!
+//! Renders a Python preamble from a [`SandboxPolicy`] that installs an audit
+//! hook (Python 3.8+) enforcing the policy's filesystem, network, process and
+//! import rules from inside the interpreter. Unlike the `builtins.__import__`
+//! guard, an audit hook can't be removed or replaced once installed, so it
+//! still holds when user code undoes the other in-process guards.
+//!
+//! The hook is installed before the wrapper's own setup runs and stays inert
+//! until [`ARM_AUDIT_HOOK`] runs right before user code, so the wrapper can
+//! import and read what it needs without widening the policy.
+
+use crate::policy::{ImportPolicyType, NetworkPolicy, ProcessPolicy, SandboxPolicy};
+use std::collections::HashSet;
+
+/// Locations the interpreter reads from while running, besides its own prefix
+/// and `sys.path`
+pub(crate) const SYSTEM_READ_PATHS: &[&str] = &[
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/share",
+    "/etc",
+    "/private/etc",
+    "/System",
+    "/Library/Frameworks",
+    "/dev/null",
+    "/dev/zero",
+    "/dev/random",
+    "/dev/urandom",
+];
+
+/// Python that arms the hook installed by [`render_audit_hook`]
+pub const ARM_AUDIT_HOOK: &str = "_rzn_arm_audit_hook()\ndel _rzn_arm_audit_hook\n";
+
+/// Render the audit hook preamble for a policy
+///
+/// The preamble defines `_rzn_arm_audit_hook`; run [`ARM_AUDIT_HOOK`] before
+/// user code to start enforcing. On interpreters without
+/// `sys.addaudithook` the preamble does nothing.
+pub fn render_audit_hook(policy: &SandboxPolicy) -> String {
+    let filesystem = policy.filesystem.rules();
+    let paths = |paths: &[std::path::PathBuf]| -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect()
+    };
+    let read_paths: Vec<String> = SYSTEM_READ_PATHS
+        .iter()
+        .map(|p| p.to_string())
+        .chain(paths(&filesystem.read))
+        .collect();
+    let write_paths = paths(&filesystem.write);
+    let deny_paths = paths(&filesystem.deny);
+
+    let (network, hosts) = match &policy.network {
+        NetworkPolicy::Blocked => ("blocked", Vec::new()),
+        NetworkPolicy::LocalhostOnly => ("localhost", Vec::new()),
+        NetworkPolicy::AllowList(hosts) => ("allowlist", hosts.clone()),
+        NetworkPolicy::Unrestricted => ("unrestricted", Vec::new()),
+    };
+
+    let (process, executables) = match &policy.process {
+        ProcessPolicy::Blocked => ("blocked", Vec::new()),
+        ProcessPolicy::AllowList(executables) => ("allowlist", executables.clone()),
+        ProcessPolicy::Unrestricted => ("unrestricted", Vec::new()),
+    };
+
+    let (whitelist, blacklist) = match &policy.imports {
+        ImportPolicyType::Unrestricted => (None, None),
+        ImportPolicyType::Blacklist(blacklist) => (None, Some(blacklist)),
+        ImportPolicyType::Whitelist(whitelist) => (Some(whitelist), None),
+        ImportPolicyType::WhitelistWithBlacklist {
+            whitelist,
+            blacklist,
+        } => (Some(whitelist), Some(blacklist)),
+    };
+
+    format!(
+        r#"
+def _rzn_install_audit_hook():
+    import ipaddress
+    import os
+    import socket
+    import sys
+    import threading
+
+    try:
+        import fcntl
+    except ImportError:
+        fcntl = None
+
+    if not hasattr(sys, "addaudithook"):
+        return lambda: None
+
+    # Bound now so user code can't unbind it
+    denied = _rzn_denied
+
+    READ_ANY = {read_any}
+    WRITE_ANY = {write_any}
+    WORKSPACE_WRITABLE = {workspace}
+    READ_PATHS = {read_paths}
+    WRITE_PATHS = {write_paths}
+    DENY_PATHS = {deny_paths}
+    NETWORK = {network}
+    NETWORK_ALLOWLIST = {hosts}
+    PROCESS = {process}
+    PROCESS_ALLOWLIST = {executables}
+    IMPORT_WHITELIST = {whitelist}
+    IMPORT_BLACKLIST = {blacklist}
+    WRITE_FLAGS = os.O_WRONLY | os.O_RDWR | os.O_APPEND | os.O_CREAT | os.O_TRUNC
+
+    def real(path):
+        try:
+            return os.path.realpath(os.fsdecode(path))
+        except Exception:
+            return None
+
+    def within(path, roots):
+        for root in roots:
+            if path == root or path.startswith(root.rstrip(os.sep) + os.sep):
+                return True
+        return False
+
+    def roots(paths):
+        return [r for r in map(real, paths) if r]
+
+    workspace = [os.environ.get("SANDBOX_WORKSPACE", ".")] if WORKSPACE_WRITABLE else []
+    interpreter = [sys.prefix, sys.exec_prefix, sys.base_prefix, sys.base_exec_prefix]
+    read_roots = roots(READ_PATHS + interpreter + [p for p in sys.path if p])
+    write_roots = roots(WRITE_PATHS + workspace)
+    deny_roots = roots(DENY_PATHS)
+
+    def fd_path(fd):
+        try:
+            if fcntl is not None and hasattr(fcntl, "F_GETPATH"):
+                return os.fsdecode(fcntl.fcntl(fd, fcntl.F_GETPATH, bytes(1024)).rstrip(b"\0"))
+            return os.readlink("/proc/self/fd/%d" % fd)
+        except Exception:
+            return None
+
+    def at(path, dir_fd):
+        # Resolve `path` the way the `*at` syscalls do; None when it can't be
+        if isinstance(path, int):
+            return fd_path(path)
+        if dir_fd is None or dir_fd < 0 or os.path.isabs(path):
+            return path
+        base = fd_path(dir_fd)
+        return None if base is None else os.path.join(base, os.fsdecode(path))
+
+    def check_path(event, path, write, dir_fd=None):
+        if WRITE_ANY and not deny_roots:
+            return
+        if isinstance(path, int) and event == "open":
+            # Descriptors were checked when they were opened
+            return
+        path = at(path, dir_fd)
+        resolved = None if path is None else real(path)
+        if resolved is None or within(resolved, deny_roots):
+            allowed = False
+        elif write:
+            allowed = WRITE_ANY or resolved == "/dev/null" or within(resolved, write_roots)
+        else:
+            allowed = READ_ANY or within(resolved, read_roots) or within(resolved, write_roots)
+        if not allowed:
+            path = resolved or path or "<unresolved>"
+            error = PermissionError("%s of %s denied by sandbox policy" % (event, path))
+            raise denied(error, "file_write", path) if write else error
+
+    def is_write(mode, flags):
+        if isinstance(mode, str):
+            return any(c in mode for c in "wax+")
+        return isinstance(flags, int) and bool(flags & WRITE_FLAGS)
+
+    local = threading.local()
+    resolved_hosts = {{}}
+
+    def norm(host):
+        if host is None:
+            return ""
+        if isinstance(host, bytes):
+            host = host.decode("ascii", "replace")
+        return str(host).strip().lower().rstrip(".")
+
+    def rule_ports(host):
+        # Ports allowlist entries for `host` allow; None in the set means any
+        ports = set()
+        for p, port in NETWORK_ALLOWLIST:
+            if p == "*" or p == host or (p.startswith("*.") and (host == p[2:] or host.endswith(p[1:]))):
+                ports.add(port)
+            elif "/" in p and in_network(host, p):
+                ports.add(port)
+        return ports
+
+    def port_allowed(ports, port):
+        return bool(ports) and (port is None or None in ports or port in ports)
+
+    def in_network(host, cidr):
+        try:
+            return ipaddress.ip_address(host.split("%")[0]) in ipaddress.ip_network(cidr, strict=False)
+        except ValueError:
+            return False
+
+    def listed(host, port=None):
+        return port_allowed(rule_ports(host), port)
+
+    def loopback(host):
+        if host == "localhost":
+            return True
+        try:
+            return ipaddress.ip_address(host.split("%")[0]).is_loopback
+        except ValueError:
+            return False
+
+    def host_allowed(host, port=None):
+        if NETWORK == "unrestricted":
+            return True
+        if NETWORK == "localhost":
+            return loopback(host)
+        if NETWORK == "allowlist":
+            return listed(host, port) or port_allowed(resolved_hosts.get(host, set()), port)
+        return False
+
+    def check_resolve(event, host):
+        h = norm(host)
+        if not h or NETWORK == "unrestricted" or getattr(local, "resolving", False):
+            return
+        if NETWORK == "allowlist" and listed(h):
+            # Remember the addresses so connecting to them is allowed, on
+            # the ports the host's entries allow
+            local.resolving = True
+            try:
+                for info in socket.getaddrinfo(h, None):
+                    resolved_hosts.setdefault(norm(info[4][0]), set()).update(rule_ports(h))
+            except Exception:
+                pass
+            finally:
+                local.resolving = False
+            return
+        if not host_allowed(h):
+            raise denied(PermissionError("%s of %s denied by sandbox policy" % (event, h)), "network", h)
+
+    def check_address(event, address):
+        if NETWORK == "unrestricted":
+            return
+        if isinstance(address, (str, bytes)):
+            allowed = NETWORK == "localhost"
+        elif isinstance(address, tuple) and address and isinstance(address[0], (str, bytes)):
+            port = address[1] if len(address) > 1 and isinstance(address[1], int) else None
+            allowed = host_allowed(norm(address[0]), port)
+        else:
+            allowed = False
+        if not allowed:
+            host = address[0] if isinstance(address, tuple) and address else address
+            error = PermissionError("%s to %r denied by sandbox policy" % (event, address))
+            raise denied(error, "network", norm(host))
+
+    def check_exec(event, executable):
+        if PROCESS == "unrestricted":
+            return
+        if PROCESS == "allowlist" and executable is not None:
+            exe = os.fsdecode(executable)
+            for entry in PROCESS_ALLOWLIST:
+                if exe == entry or (os.sep not in exe and os.path.basename(entry) == exe):
+                    return
+        error = PermissionError("%s of %r denied by sandbox policy" % (event, executable))
+        raise denied(error, "subprocess", os.fsdecode(executable) if executable else event)
+
+    def import_allowed(name):
+        # The longest listed prefix decides
+        parts = name.split(".")
+        for end in range(len(parts), 0, -1):
+            prefix = ".".join(parts[:end])
+            if IMPORT_BLACKLIST is not None and prefix in IMPORT_BLACKLIST:
+                return False
+            if IMPORT_WHITELIST is not None and prefix in IMPORT_WHITELIST:
+                return True
+        return IMPORT_WHITELIST is None or parts[0] == "builtins"
+
+    def check_import(name):
+        # A package is let through while one of its allowed submodules loads
+        # it: the submodule's event comes first, then its unloaded parents'
+        loading = local.__dict__.setdefault("loading_parents", set())
+        loading.difference_update([p for p in loading if p in sys.modules])
+        if name in loading:
+            loading.discard(name)
+            return
+        if not import_allowed(name):
+            error = ImportError("Module '%s' is blocked by sandbox policy" % name)
+            raise denied(error, "import", name)
+        parts = name.split(".")[:-1]
+        parents = (".".join(parts[:end]) for end in range(1, len(parts) + 1))
+        loading.update(p for p in parents if p not in sys.modules)
+
+    ctypes_allowed = import_allowed("ctypes")
+    armed = False
+
+    def hook(event, args):
+        if not armed:
+            return
+        if event == "open":
+            check_path(event, args[0], is_write(args[1], args[2]))
+        elif event in ("os.listdir", "os.scandir"):
+            check_path(event, "." if args[0] is None else args[0], False)
+        elif event in ("os.remove", "os.rmdir"):
+            check_path(event, args[0], True, args[1])
+        elif event in ("os.rename", "os.link"):
+            check_path(event, args[0], True, args[2])
+            check_path(event, args[1], True, args[3])
+        elif event == "os.symlink":
+            # The target is relative to the link's directory, not dir_fd
+            link = at(args[1], args[2])
+            target = args[0]
+            if link is not None and not os.path.isabs(target):
+                target = os.path.join(os.path.dirname(link), os.fsdecode(target))
+            check_path(event, target, True)
+            check_path(event, args[1], True, args[2])
+        elif event in ("os.mkdir", "os.chmod", "os.chown", "os.utime"):
+            check_path(event, args[0], True, args[-1])
+        elif event == "os.truncate":
+            check_path(event, args[0], True)
+        elif event in ("shutil.copyfile", "shutil.copymode", "shutil.copystat", "shutil.copytree"):
+            check_path(event, args[0], False)
+            check_path(event, args[1], True)
+        elif event == "shutil.move":
+            check_path(event, args[0], True)
+            check_path(event, args[1], True)
+        elif event in ("shutil.rmtree", "shutil.chown"):
+            check_path(event, args[0], True, args[1] if event == "shutil.rmtree" else None)
+        elif event == "shutil.make_archive":
+            check_path(event, args[0], True)
+            if args[2] is not None:
+                check_path(event, args[2], False)
+        elif event == "shutil.unpack_archive":
+            check_path(event, args[0], False)
+            check_path(event, "." if args[1] is None else args[1], True)
+        elif event in ("socket.getaddrinfo", "socket.gethostbyname", "socket.gethostbyname_ex"):
+            check_resolve(event, args[0])
+        elif event in ("socket.connect", "socket.sendto"):
+            check_address(event, args[1])
+        elif event in ("subprocess.Popen", "os.exec", "os.posix_spawn"):
+            check_exec(event, args[0])
+        elif event == "os.spawn":
+            check_exec(event, args[1])
+        elif event in ("os.system", "os.fork", "os.forkpty"):
+            if PROCESS == "blocked" or (PROCESS == "allowlist" and event == "os.system"):
+                target = args[0] if event == "os.system" else event
+                error = PermissionError("%s denied by sandbox policy" % event)
+                raise denied(error, "subprocess", os.fsdecode(target))
+        elif event == "import":
+            check_import(args[0])
+        elif event.startswith("ctypes."):
+            if not ctypes_allowed:
+                raise PermissionError("%s denied by sandbox policy" % event)
+        elif event in ("gc.get_objects", "gc.get_referrers", "gc.get_referents"):
+            # Would expose the hook's state to user code
+            raise PermissionError("%s denied by sandbox policy" % event)
+
+    def arm():
+        nonlocal armed
+        armed = True
+
+    sys.addaudithook(hook)
+    return arm
+
+_rzn_arm_audit_hook = _rzn_install_audit_hook()
+del _rzn_install_audit_hook
+"#,
+        read_any = py_bool(filesystem.read_any || filesystem.write_any),
+        write_any = py_bool(filesystem.write_any),
+        workspace = py_bool(filesystem.workspace),
+        read_paths = py_list(read_paths.iter()),
+        write_paths = py_list(write_paths.iter()),
+        deny_paths = py_list(deny_paths.iter()),
+        network = py_str(network),
+        hosts = crate::policy::python_network_rules(&hosts),
+        process = py_str(process),
+        executables = py_list(executables.iter()),
+        whitelist = py_set(whitelist.map(crate::config::whitelisted_modules).as_ref()),
+        blacklist = py_set(blacklist),
+    )
+}
+
+fn py_str(s: &str) -> String {
+    // JSON string literals are valid Python string literals
+    serde_json::Value::String(s.to_string()).to_string()
+}
+
+fn py_bool(b: bool) -> &'static str {
+    if b {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+fn py_list<'a>(items: impl Iterator<Item = &'a String>) -> String {
+    format!(
+        "[{}]",
+        items.map(|s| py_str(s)).collect::<Vec<_>>().join(", ")
+    )
+}
+
+fn py_set(items: Option<&HashSet<String>>) -> String {
+    match items {
+        Some(items) => {
+            let mut items: Vec<_> = items.iter().collect();
+            items.sort();
+            format!("frozenset({})", py_list(items.into_iter()))
+        }
+        None => "None".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FilesystemPolicy, FilesystemRules};
+
+    #[test]
+    fn test_render_audit_hook_embeds_policy() {
+        let policy = SandboxPolicy::custom(SandboxPolicy::enterprise())
+            .network(NetworkPolicy::AllowList(vec!["pypi.org".to_string()]))
+            .process(ProcessPolicy::AllowList(vec!["/usr/bin/git".to_string()]))
+            .build();
+        let preamble = render_audit_hook(&policy);
+        assert!(preamble.contains("WORKSPACE_WRITABLE = True"));
+        assert!(preamble.contains("WRITE_ANY = False"));
+        assert!(preamble.contains("NETWORK_ALLOWLIST = [(\"pypi.org\", None)]"));
+        assert!(preamble.contains("PROCESS_ALLOWLIST = [\"/usr/bin/git\"]"));
+        assert!(preamble.contains("IMPORT_BLACKLIST = None"));
+    }
+
+    #[test]
+    fn test_hook_denies_parents_and_listings() {
+        let readable = tempfile::tempdir().unwrap();
+        let hidden = tempfile::tempdir().unwrap();
+        let policy = SandboxPolicy::custom(SandboxPolicy::balanced())
+            .imports(ImportPolicyType::Whitelist(HashSet::from([
+                "xml.dom".to_string()
+            ])))
+            .filesystem(FilesystemPolicy::Paths(
+                FilesystemRules::new().read(readable.path()),
+            ))
+            .build();
+        let probes = format!(
+            r#"
+import os
+def _rzn_denied(error, kind, target):
+    return error
+{}{}
+def probe(action):
+    try:
+        action()
+        return "ok"
+    except (ImportError, PermissionError):
+        return "denied"
+print(probe(lambda: __import__("xml")))
+print(probe(lambda: __import__("xml.dom")))
+print(probe(lambda: os.listdir({readable:?})))
+print(probe(lambda: os.listdir({hidden:?})))
+print(probe(lambda: list(os.scandir({hidden:?}))))
+"#,
+            render_audit_hook(&policy),
+            ARM_AUDIT_HOOK,
+            readable = readable.path().to_string_lossy(),
+            hidden = hidden.path().to_string_lossy(),
+        );
+        let Ok(output) = std::process::Command::new("python3")
+            .arg("-c")
+            .arg(&probes)
+            .output()
+        else {
+            return;
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(
+            stdout.lines().collect::<Vec<_>>(),
+            ["denied", "ok", "ok", "denied", "denied"],
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn test_hook_checks_write_events_and_dir_fd() {
+        let writable = tempfile::tempdir().unwrap();
+        let readable = tempfile::tempdir().unwrap();
+        std::fs::write(readable.path().join("f"), "x").unwrap();
+        let policy = SandboxPolicy::custom(SandboxPolicy::balanced())
+            .filesystem(FilesystemPolicy::Paths(
+                FilesystemRules::new()
+                    .read(readable.path())
+                    .write(writable.path()),
+            ))
+            .build();
+        let probes = format!(
+            r#"
+import os, shutil
+def _rzn_denied(error, kind, target):
+    return error
+{}{}
+def probe(action):
+    try:
+        action()
+        return "ok"
+    except PermissionError:
+        return "denied"
+readable = {readable:?}
+writable = {writable:?}
+inside = os.open(writable, os.O_RDONLY)
+outside = os.open(readable, os.O_RDONLY)
+print(probe(lambda: os.truncate(os.path.join(readable, "f"), 0)))
+print(probe(lambda: os.chmod(os.path.join(readable, "f"), 0o600)))
+print(probe(lambda: os.symlink(os.path.join(readable, "f"), os.path.join(writable, "s"))))
+print(probe(lambda: os.symlink("t", os.path.join(writable, "t"))))
+print(probe(lambda: os.link(os.path.join(writable, "t"), os.path.join(readable, "l"))))
+print(probe(lambda: os.mkdir("d", dir_fd=outside)))
+print(probe(lambda: os.mkdir("d", dir_fd=inside)))
+print(probe(lambda: shutil.copyfile(os.path.join(readable, "f"), os.path.join(writable, "g"))))
+print(probe(lambda: shutil.rmtree(readable)))
+"#,
+            render_audit_hook(&policy),
+            ARM_AUDIT_HOOK,
+            readable = readable.path().to_string_lossy(),
+            writable = writable.path().to_string_lossy(),
+        );
+        let Ok(output) = std::process::Command::new("python3")
+            .arg("-c")
+            .arg(&probes)
+            .output()
+        else {
+            return;
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(
+            stdout.lines().collect::<Vec<_>>(),
+            ["denied", "denied", "denied", "ok", "denied", "denied", "ok", "ok", "denied"],
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(readable.path().join("f").exists());
+    }
+}
@@ -0,0 +1,53 @@
+use pysandbox::grpc::SandboxService;
+use pysandbox::{create_bundled_sandbox, create_default_sandbox};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:50051";
+
+fn arg_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            std::env::var("RUST_LOG")
+                .unwrap_or_else(|_| "pysandbox_server=info,pysandbox=info".to_string()),
+        )
+        .with_target(false)
+        .init();
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        eprintln!("usage:\n  pysandbox-server [--listen <addr>] [--python <path>]");
+        std::process::exit(2);
+    }
+
+    let listen: SocketAddr = arg_value(&args, "--listen")
+        .or_else(|| std::env::var("PYSANDBOX_LISTEN").ok())
+        .unwrap_or_else(|| DEFAULT_LISTEN_ADDR.to_string())
+        .parse()?;
+    let python_path = arg_value(&args, "--python")
+        .or_else(|| std::env::var("PYSANDBOX_PYTHON").ok())
+        .map(PathBuf::from);
+
+    let sandbox = match python_path {
+        Some(path) => create_bundled_sandbox(path).await?,
+        None => create_default_sandbox().await?,
+    };
+
+    tracing::info!("pysandbox-server listening on {}", listen);
+    tonic::transport::Server::builder()
+        .add_service(SandboxService::new(sandbox).into_server())
+        .serve_with_shutdown(listen, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
+}
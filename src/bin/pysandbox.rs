@@ -0,0 +1,85 @@
+use pysandbox::policy::SandboxPolicy;
+use pysandbox::transcript::{self, TranscriptStore};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage:
+  pysandbox policy schema [sandbox]    JSON Schema for SandboxPolicy files
+  pysandbox policy schema enterprise   JSON Schema for EnterprisePolicy files
+  pysandbox replay <id> [--dir <transcripts>] [--policy <template|file>]
+                                       Re-run a recorded transcript under the current policy";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        #[cfg(feature = "json-schema")]
+        ["policy", "schema"] | ["policy", "schema", "sandbox"] => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&SandboxPolicy::json_schema())?
+            );
+        }
+        #[cfg(feature = "json-schema")]
+        ["policy", "schema", "enterprise"] => {
+            let schema = pysandbox::policy::EnterprisePolicy::json_schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+        }
+        ["replay", id, rest @ ..] => replay(id, rest).await?,
+        _ => usage(),
+    }
+    Ok(())
+}
+
+async fn replay(id: &str, args: &[&str]) -> anyhow::Result<()> {
+    let mut store = TranscriptStore::from_env();
+    // Same default as the worker: RZN_PYTHON_POLICY_ID, else balanced
+    let mut policy_arg = std::env::var("RZN_PYTHON_POLICY_ID")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "balanced".to_string());
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (*arg, args.next()) {
+            ("--dir", Some(dir)) => store = TranscriptStore::new(PathBuf::from(dir)),
+            ("--policy", Some(policy)) => policy_arg = policy.to_string(),
+            _ => usage(),
+        }
+    }
+
+    let policy = match SandboxPolicy::template(policy_arg.trim()) {
+        Some(policy) => policy,
+        None => SandboxPolicy::from_file(Path::new(&policy_arg)).map_err(anyhow::Error::msg)?,
+    };
+    let recorded = store.load(id)?;
+    let replay = transcript::replay(&recorded, &policy).await?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&serde_json::json!({
+            "id": replay.id,
+            "matches": replay.matches,
+            "recorded": {
+                "policy": recorded.policy.as_ref().map(|p| &p.name),
+                "output": recorded.output,
+                "error": recorded.error,
+            },
+            "replayed": {
+                "policy": replay.policy,
+                "output": replay.replayed.output,
+                "error": replay.replayed.error,
+            },
+        }))?
+    );
+    if !replay.matches {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
@@ -1,6 +1,7 @@
 use base64::engine::general_purpose::STANDARD as b64;
 use base64::Engine;
-use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use pysandbox::signing;
 use rand_core::OsRng;
 use std::path::{Path, PathBuf};
 
@@ -56,11 +57,6 @@ fn cmd_keygen(args: &[String]) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn read_b64_file(path: &Path) -> anyhow::Result<Vec<u8>> {
-    let s = std::fs::read_to_string(path)?.trim().to_string();
-    Ok(b64.decode(s.as_bytes())?)
-}
-
 fn cmd_sign(args: &[String]) -> anyhow::Result<()> {
     let key_path = arg_value(args, "--key").ok_or_else(|| anyhow::anyhow!("missing --key"))?;
     let input_path =
@@ -68,21 +64,9 @@ fn cmd_sign(args: &[String]) -> anyhow::Result<()> {
     let output_path =
         arg_value(args, "--output").ok_or_else(|| anyhow::anyhow!("missing --output"))?;
 
-    let key_bytes = read_b64_file(Path::new(&key_path))?;
-    if key_bytes.len() < 32 {
-        return Err(anyhow::anyhow!(
-            "invalid Ed25519 private key length: {} (expected at least 32)",
-            key_bytes.len()
-        ));
-    }
-    let mut seed = [0u8; 32];
-    seed.copy_from_slice(&key_bytes[..32]);
-    let signing = SigningKey::from_bytes(&seed);
-
+    let signing = signing::read_signing_key(Path::new(&key_path))?;
     let message = std::fs::read(&input_path)?;
-    let sig: Signature = signing.sign(&message);
-
-    std::fs::write(output_path, format!("{}\n", b64.encode(sig.to_bytes())))?;
+    std::fs::write(output_path, signing::sign(&signing, &message))?;
     Ok(())
 }
 
@@ -93,27 +77,9 @@ fn cmd_verify(args: &[String]) -> anyhow::Result<()> {
         arg_value(args, "--input").ok_or_else(|| anyhow::anyhow!("missing --input"))?;
     let sig_path = arg_value(args, "--sig").ok_or_else(|| anyhow::anyhow!("missing --sig"))?;
 
-    let pk_bytes = read_b64_file(Path::new(&public_path))?;
-    if pk_bytes.len() != 32 {
-        return Err(anyhow::anyhow!(
-            "invalid Ed25519 public key length: {} (expected 32)",
-            pk_bytes.len()
-        ));
-    }
-    let mut pk_arr = [0u8; 32];
-    pk_arr.copy_from_slice(&pk_bytes);
-    let verifying = VerifyingKey::from_bytes(&pk_arr)?;
-
-    let sig_bytes = read_b64_file(Path::new(&sig_path))?;
-    if sig_bytes.len() != 64 {
-        return Err(anyhow::anyhow!(
-            "invalid Ed25519 signature length: {} (expected 64)",
-            sig_bytes.len()
-        ));
-    }
-    let sig = Signature::from_slice(&sig_bytes)?;
-
+    let verifying = signing::read_verifying_key(Path::new(&public_path))?;
+    let signature = std::fs::read_to_string(&sig_path)?;
     let message = std::fs::read(&input_path)?;
-    verifying.verify(&message, &sig)?;
+    signing::verify(&verifying, &message, &signature)?;
     Ok(())
 }
@@ -1,18 +1,45 @@
+use base64::engine::general_purpose::STANDARD as b64;
+use base64::Engine;
+use pysandbox::artifacts::mime_type_for;
+use pysandbox::discovery::PythonDiscovery;
+use pysandbox::pip_audit::{self, EnvironmentAudit};
+use pysandbox::sbom::{self, SbomFormat};
+use pysandbox::session::SessionHandle;
+use pysandbox::standalone::StandaloneRuntime;
 use pysandbox::{
-    ExecutionMode, ExecutionOptions, NativePythonEngine, PythonEngine, PythonSandbox,
-    SandboxConfig, SandboxedPythonEngine, SecurityProfile,
+    conda::CondaEnv,
+    envs::{self, CreateEnvOptions, EnvManager, ManagedEnvMetadata, ManagedEnvRegistry},
+    event_channel, Diagnostic, EnterprisePolicy, ExecutionEvent, ExecutionMode, ExecutionOptions,
+    ExecutionResult, NativePythonEngine, NetworkPolicy, PackageInstallPolicy, PythonEngine,
+    PythonSandbox, SandboxConfig, SandboxError, SandboxPolicy, SandboxedPythonEngine,
+    SecurityProfile, Severity, WorkspaceJanitor,
 };
-use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 
-const DEFAULT_ENVS_DIR_NAME: &str = "python_envs";
-const ENV_METADATA_FILENAME: &str = "rzn_env.json";
 const ENV_TOOL_TIMEOUT_SECS: u64 = 300;
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+const DEFAULT_QUEUE_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_WORKSPACE_TTL_SECS: u64 = 24 * 60 * 60;
+const WORKSPACE_GC_INTERVAL_SECS: u64 = 10 * 60;
+#[cfg(feature = "static-analyzer")]
+const PREFLIGHT_TIMEOUT_SECS: u64 = 30;
+/// Policy ids accepted by `policy_id`, most to least permissive
+const POLICY_IDS: &[&str] = &[
+    "yolo",
+    "balanced",
+    "data_science",
+    "document_processing",
+    "enterprise",
+];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PythonRuntime {
@@ -30,6 +57,14 @@ impl PythonRuntime {
             _ => None,
         }
     }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Bundled => "bundled",
+            Self::System => "system",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -39,10 +74,21 @@ struct WorkerConfig {
     python_runtime_explicit: bool,
     python_path_override: Option<PathBuf>,
     sandbox_profile_path: Option<PathBuf>,
+    /// Tool calls that run Python or pip at once; others wait for a slot
+    max_concurrency: usize,
+    /// How long a tool call waits for a slot before failing
+    queue_timeout: std::time::Duration,
+    /// Organization policy every `policy_id` is checked against
+    enterprise_policy: Option<Arc<EnterprisePolicy>>,
+    /// Leftover workspaces older than this are garbage collected; `None`
+    /// keeps them regardless of age
+    workspace_ttl: Option<std::time::Duration>,
+    /// Size budget for all leftover workspaces together
+    workspace_max_bytes: Option<u64>,
 }
 
 impl WorkerConfig {
-    fn from_env_and_args() -> Self {
+    fn from_env_and_args() -> Result<Self, String> {
         let args: Vec<String> = std::env::args().collect();
 
         let plugin_dir = std::env::var("RZN_PLUGIN_DIR")
@@ -59,6 +105,27 @@ impl WorkerConfig {
         let mut sandbox_profile_path = std::env::var("RZN_PYTHON_SANDBOX_PROFILE")
             .ok()
             .map(PathBuf::from);
+        let mut max_concurrency = std::env::var("RZN_PYTHON_MAX_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+        let mut queue_timeout_secs = std::env::var("RZN_PYTHON_QUEUE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_QUEUE_TIMEOUT_SECS);
+        let mut enterprise_policy_path = std::env::var("RZN_ENTERPRISE_POLICY_FILE")
+            .ok()
+            .map(PathBuf::from);
+        let mut enterprise_policy_key_path = std::env::var("RZN_ENTERPRISE_POLICY_KEY")
+            .ok()
+            .map(PathBuf::from);
+        let mut workspace_ttl_secs = std::env::var("RZN_PYTHON_WORKSPACE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_WORKSPACE_TTL_SECS);
+        let mut workspace_max_mb = std::env::var("RZN_PYTHON_WORKSPACE_MAX_MB")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok());
 
         let mut i = 1;
         while i < args.len() {
@@ -84,141 +151,123 @@ impl WorkerConfig {
                     }
                     i += 2;
                 }
+                "--max-concurrency" => {
+                    if let Some(v) = args.get(i + 1).and_then(|v| v.parse().ok()) {
+                        max_concurrency = v;
+                    }
+                    i += 2;
+                }
+                "--queue-timeout" => {
+                    if let Some(v) = args.get(i + 1).and_then(|v| v.parse().ok()) {
+                        queue_timeout_secs = v;
+                    }
+                    i += 2;
+                }
+                "--enterprise-policy" => {
+                    if let Some(v) = args.get(i + 1) {
+                        enterprise_policy_path = Some(PathBuf::from(v));
+                    }
+                    i += 2;
+                }
+                "--enterprise-policy-key" => {
+                    if let Some(v) = args.get(i + 1) {
+                        enterprise_policy_key_path = Some(PathBuf::from(v));
+                    }
+                    i += 2;
+                }
+                "--workspace-ttl" => {
+                    if let Some(v) = args.get(i + 1).and_then(|v| v.parse().ok()) {
+                        workspace_ttl_secs = v;
+                    }
+                    i += 2;
+                }
+                "--workspace-max-mb" => {
+                    if let Some(v) = args.get(i + 1).and_then(|v| v.parse().ok()) {
+                        workspace_max_mb = Some(v);
+                    }
+                    i += 2;
+                }
                 _ => i += 1,
             }
         }
 
-        Self {
+        // A configured enterprise policy that fails to load must not
+        // silently fall back to no restrictions
+        let enterprise_policy = enterprise_policy_path
+            .map(|path| {
+                load_enterprise_policy(&path, enterprise_policy_key_path.as_deref())
+                    .map(Arc::new)
+                    .map_err(|e| format!("enterprise policy {}: {}", path.display(), e))
+            })
+            .transpose()?;
+
+        Ok(Self {
             plugin_dir,
             python_runtime,
             python_runtime_explicit,
             python_path_override,
             sandbox_profile_path,
-        }
+            max_concurrency: max_concurrency.max(1),
+            queue_timeout: std::time::Duration::from_secs(queue_timeout_secs),
+            enterprise_policy,
+            // 0 turns the TTL off
+            workspace_ttl: (workspace_ttl_secs > 0)
+                .then(|| std::time::Duration::from_secs(workspace_ttl_secs)),
+            workspace_max_bytes: workspace_max_mb.map(|mb| mb * 1024 * 1024),
+        })
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ManagedEnvMetadata {
-    alias: String,
-    env_dir: String,
-    python_path: String,
-    base_python: Option<String>,
-    created_at_unix_seconds: u64,
-}
-
-fn managed_envs_base_dir() -> PathBuf {
-    if let Ok(v) = std::env::var("RZN_PYTHON_ENVS_DIR") {
-        let trimmed = v.trim();
-        if !trimmed.is_empty() {
-            return PathBuf::from(trimmed);
-        }
-    }
-    if let Ok(v) = std::env::var("RZN_APP_BASE_DIR") {
-        let trimmed = v.trim();
-        if !trimmed.is_empty() {
-            return PathBuf::from(trimmed).join(DEFAULT_ENVS_DIR_NAME);
-        }
-    }
-    if let Ok(v) = std::env::var("HOME") {
-        let trimmed = v.trim();
-        if !trimmed.is_empty() {
-            return PathBuf::from(trimmed)
-                .join(".rzn")
-                .join(DEFAULT_ENVS_DIR_NAME);
+/// Load the enterprise policy at `path`; with a public key file, the policy
+/// must carry a valid detached signature (`<path>.sig`) from that key
+fn load_enterprise_policy(
+    path: &Path,
+    key_path: Option<&Path>,
+) -> std::result::Result<EnterprisePolicy, String> {
+    match key_path {
+        Some(key_path) => {
+            let key = pysandbox::signing::read_verifying_key(key_path)
+                .map_err(|e| format!("signing key {}: {}", key_path.display(), e))?;
+            EnterprisePolicy::from_signed_file(path, &key)
         }
+        None => EnterprisePolicy::from_file(path),
     }
-    std::env::temp_dir().join("rzn").join(DEFAULT_ENVS_DIR_NAME)
-}
-
-fn validate_env_alias(raw: &str) -> std::result::Result<String, Value> {
-    let alias = raw.trim();
-    if alias.is_empty() {
-        return Err(json!({
-            "code": -32602,
-            "message": "python_env alias must be non-empty"
-        }));
-    }
-    if alias.len() > 64 {
-        return Err(json!({
-            "code": -32602,
-            "message": "python_env alias is too long (max 64 chars)"
-        }));
-    }
-    if !alias
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
-    {
-        return Err(json!({
-            "code": -32602,
-            "message": "python_env alias may only contain [a-zA-Z0-9._-]"
-        }));
-    }
-    Ok(alias.to_string())
 }
 
-fn managed_env_dir(alias: &str) -> PathBuf {
-    managed_envs_base_dir().join(alias)
-}
-
-fn managed_env_python_path(env_dir: &Path) -> PathBuf {
-    #[cfg(target_os = "windows")]
-    {
-        env_dir.join("Scripts").join("python.exe")
-    }
-    #[cfg(not(target_os = "windows"))]
-    {
-        let python3 = env_dir.join("bin").join("python3");
-        if python3.exists() {
-            return python3;
-        }
-        env_dir.join("bin").join("python")
+/// JSON-RPC error for a managed env failure; rejected arguments are invalid
+/// params
+fn env_error(e: SandboxError) -> Value {
+    match e {
+        SandboxError::InvalidArgument(message) => json!({ "code": -32602, "message": message }),
+        SandboxError::InternalError(message) => json!({ "code": -32000, "message": message }),
+        e => json!({ "code": -32000, "message": e.to_string() }),
     }
 }
 
-fn metadata_path_for_env(env_dir: &Path) -> PathBuf {
-    env_dir.join(ENV_METADATA_FILENAME)
+/// [`env_error`] carrying `data` about the env involved
+fn env_error_with_data(e: SandboxError, data: Value) -> Value {
+    let mut error = env_error(e);
+    error["data"] = data;
+    error
 }
 
-fn read_env_metadata(env_dir: &Path) -> Option<ManagedEnvMetadata> {
-    let metadata_path = metadata_path_for_env(env_dir);
-    let raw = std::fs::read_to_string(metadata_path).ok()?;
-    serde_json::from_str::<ManagedEnvMetadata>(&raw).ok()
+fn validate_env_alias(raw: &str) -> std::result::Result<String, Value> {
+    envs::validate_alias(raw).map_err(env_error)
 }
 
-fn write_env_metadata(
-    alias: &str,
-    env_dir: &Path,
-    python_path: &Path,
-    base_python: Option<&Path>,
-) -> std::result::Result<ManagedEnvMetadata, Value> {
-    let created_at_unix_seconds = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_secs())
-        .unwrap_or_default();
-
-    let metadata = ManagedEnvMetadata {
-        alias: alias.to_string(),
-        env_dir: env_dir.to_string_lossy().to_string(),
-        python_path: python_path.to_string_lossy().to_string(),
-        base_python: base_python.map(|p| p.to_string_lossy().to_string()),
-        created_at_unix_seconds,
-    };
-
-    let metadata_path = metadata_path_for_env(env_dir);
-    let serialized = serde_json::to_string_pretty(&metadata).map_err(|e| {
-        json!({
-            "code": -32000,
-            "message": format!("Failed to serialize env metadata: {}", e)
-        })
-    })?;
-    std::fs::write(metadata_path, serialized).map_err(|e| {
-        json!({
-            "code": -32000,
-            "message": format!("Failed to persist env metadata: {}", e)
+/// `env_manager` argument, else `RZN_PYTHON_ENV_MANAGER`, else auto: uv
+/// when it is in PATH, pip otherwise
+fn env_manager_from_args(args: &Value) -> std::result::Result<EnvManager, Value> {
+    let requested = parse_optional_string(args, "env_manager")
+        .or_else(|| parse_optional_string(args, "envManager"))
+        .or_else(|| {
+            std::env::var("RZN_PYTHON_ENV_MANAGER")
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
         })
-    })?;
-    Ok(metadata)
+        .unwrap_or_else(|| "auto".to_string());
+    EnvManager::from_name(&requested).map_err(env_error)
 }
 
 fn parse_python_env_alias(args: &Value) -> std::result::Result<Option<String>, Value> {
@@ -242,32 +291,31 @@ fn parse_python_env_alias(args: &Value) -> std::result::Result<Option<String>, V
     Ok(from_env)
 }
 
+/// Mark a managed env as running code, for `python_env.prune`
+///
+/// Tracking is best effort and never fails the run.
+fn record_env_use(alias: Option<&str>) {
+    let Some(alias) = alias else {
+        return;
+    };
+    if let Err(e) = ManagedEnvRegistry::from_env().touch(alias) {
+        tracing::warn!("failed to record use of managed env {}: {}", alias, e);
+    }
+}
+
 fn resolve_existing_managed_env(alias: &str) -> std::result::Result<(PathBuf, PathBuf), Value> {
-    let env_dir = managed_env_dir(alias);
-    if !env_dir.exists() {
-        return Err(json!({
-            "code": -32000,
-            "message": format!("Managed python env '{}' not found", alias),
-            "data": {
+    let registry = ManagedEnvRegistry::from_env();
+    let env = registry.resolve(alias).map_err(|e| {
+        env_error_with_data(
+            e,
+            json!({
                 "alias": alias,
-                "env_dir": env_dir,
+                "env_dir": registry.base_dir().join(alias),
                 "hint": "Use python_env.create first."
-            }
-        }));
-    }
-    let python_path = managed_env_python_path(&env_dir);
-    if !python_path.exists() {
-        return Err(json!({
-            "code": -32000,
-            "message": format!("Managed python env '{}' is missing interpreter", alias),
-            "data": {
-                "alias": alias,
-                "env_dir": env_dir,
-                "python_path": python_path
-            }
-        }));
-    }
-    Ok((env_dir, python_path))
+            }),
+        )
+    })?;
+    Ok((env.env_dir, env.python_path))
 }
 
 async fn run_cmd_capture(
@@ -279,6 +327,7 @@ async fn run_cmd_capture(
         cmd.stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .output(),
     )
     .await
@@ -305,17 +354,26 @@ async fn main() -> anyhow::Result<()> {
         .with_target(false)
         .init();
 
-    let cfg = WorkerConfig::from_env_and_args();
+    let cfg = Arc::new(WorkerConfig::from_env_and_args().map_err(anyhow::Error::msg)?);
     tracing::info!(
         "rzn-python-worker starting (plugin_dir={:?})",
         cfg.plugin_dir
     );
 
+    // Sweeps workspaces left behind by crashed runs while the worker is up
+    let _janitor = (cfg.workspace_ttl.is_some() || cfg.workspace_max_bytes.is_some()).then(|| {
+        workspace_janitor(&cfg).spawn(std::time::Duration::from_secs(WORKSPACE_GC_INTERVAL_SECS))
+    });
+
     let stdin = BufReader::new(io::stdin());
     let mut lines = stdin.lines();
-    let mut stdout = io::stdout();
+    let outbox = Outbox(Arc::new(Mutex::new(io::stdout())));
+    let state = Arc::new(WorkerState::new(&cfg));
+    // Requests run concurrently so cancellations can arrive while a tool runs
+    let mut requests = JoinSet::new();
 
     while let Some(line) = lines.next_line().await? {
+        while requests.try_join_next().is_some() {}
         let line = line.trim();
         if line.is_empty() {
             continue;
@@ -328,30 +386,416 @@ async fn main() -> anyhow::Result<()> {
             }
         };
 
-        let response = handle_message(&cfg, parsed).await;
-        if let Some(resp) = response {
-            let s = serde_json::to_string(&resp)?;
-            stdout.write_all(s.as_bytes()).await?;
-            stdout.write_all(b"\n").await?;
-            stdout.flush().await?;
-        }
+        let (cfg, state, outbox) = (cfg.clone(), state.clone(), outbox.clone());
+        requests.spawn(async move {
+            let resp = match parsed {
+                Value::Array(batch) => handle_batch(&cfg, &state, batch, &outbox).await,
+                msg => handle_message(&cfg, &state, msg, &outbox).await,
+            };
+            if let Some(resp) = resp {
+                if let Err(e) = outbox.send(&resp).await {
+                    tracing::error!("failed to write response: {}", e);
+                }
+            }
+        });
     }
 
+    // Let in-flight requests answer before exiting on EOF
+    while requests.join_next().await.is_some() {}
     Ok(())
 }
 
-async fn handle_message(cfg: &WorkerConfig, msg: Value) -> Option<Value> {
+/// Writes JSON-RPC messages to stdout, one per line; shared so notifications
+/// can be sent while a request is still running
+#[derive(Clone)]
+struct Outbox(Arc<Mutex<io::Stdout>>);
+
+impl Outbox {
+    async fn send(&self, msg: &Value) -> io::Result<()> {
+        let mut line = serde_json::to_vec(msg)?;
+        line.push(b'\n');
+        let mut stdout = self.0.lock().await;
+        stdout.write_all(&line).await?;
+        stdout.flush().await
+    }
+}
+
+/// Where to report progress for a request that carried `_meta.progressToken`
+struct Progress {
+    outbox: Outbox,
+    token: Value,
+}
+
+/// `notifications/progress` for a running `python_sandbox` call
+///
+/// `progress` is the elapsed time in seconds; `message` carries the output
+/// chunk, if any.
+fn progress_notification(token: &Value, elapsed_secs: f64, chunk: Option<&str>) -> Value {
+    let mut params = json!({
+        "progressToken": token,
+        "progress": elapsed_secs,
+    });
+    if let Some(chunk) = chunk {
+        params["message"] = json!(chunk);
+    }
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/progress",
+        "params": params,
+    })
+}
+
+/// Per-process state shared by tool calls
+struct WorkerState {
+    sessions: Sessions,
+    resources: Resources,
+    in_flight: InFlight,
+    /// One permit per tool call allowed to run Python or pip
+    tool_slots: tokio::sync::Semaphore,
+}
+
+impl WorkerState {
+    fn new(cfg: &WorkerConfig) -> Self {
+        Self {
+            sessions: Sessions::default(),
+            resources: Resources::default(),
+            in_flight: InFlight::default(),
+            tool_slots: tokio::sync::Semaphore::new(cfg.max_concurrency),
+        }
+    }
+}
+
+/// Tools that run Python or pip and so take a concurrency slot; the rest
+/// answer immediately even when the worker is saturated
+fn needs_tool_slot(tool: &str) -> bool {
+    matches!(
+        tool,
+        "python_sandbox"
+            | "python_sandbox_validate"
+            | "python_session_start"
+            | "python_session_exec"
+            | "python_env_create"
+            | "python_env_install"
+            | "python_env_info"
+            | "python_env_freeze"
+            | "python_env_audit"
+            | "python_env_sbom"
+            | "python_env_clone"
+            | "python_runtime_provision"
+            | "python_runtime_discover"
+    )
+}
+
+/// Cancellation tokens of running `tools/call` requests, by request id
+#[derive(Default)]
+struct InFlight(std::sync::Mutex<HashMap<String, CancellationToken>>);
+
+impl InFlight {
+    fn start(&self, id: &Value) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.lock().insert(request_key(id), token.clone());
+        token
+    }
+
+    /// Forget a finished request; false if `notifications/cancelled` already
+    /// did, in which case no response should be sent
+    fn finish(&self, id: &Value) -> bool {
+        self.lock().remove(&request_key(id)).is_some()
+    }
+
+    /// Cancel a running request, returning whether one was found. With
+    /// `forget`, its response is suppressed.
+    fn cancel(&self, id: &Value, forget: bool) -> bool {
+        let mut in_flight = self.lock();
+        let key = request_key(id);
+        let token = if forget {
+            in_flight.remove(&key)
+        } else {
+            in_flight.get(&key).cloned()
+        };
+        token.map(|t| t.cancel()).is_some()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CancellationToken>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Request ids may be strings or numbers; `7` and `"7"` name the same request
+fn request_key(id: &Value) -> String {
+    match id {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Most `python_session` interpreters alive at once
+const MAX_SESSIONS: usize = 8;
+
+/// Live `python_session` interpreters, by session id
+///
+/// Each session has its own lock, so a long call on one doesn't hold up
+/// calls on the others; the map's lock is only held to look sessions up.
+#[derive(Default)]
+struct Sessions(std::sync::Mutex<SessionTable>);
+
+#[derive(Default)]
+struct SessionTable {
+    open: HashMap<String, Arc<WorkerSession>>,
+    /// Slots reserved by starts still spawning their interpreter
+    starting: usize,
+}
+
+impl Sessions {
+    /// Reserve a slot for a new session, if fewer than [`MAX_SESSIONS`] are
+    /// open or starting
+    fn reserve(&self) -> Option<SessionSlot<'_>> {
+        let mut table = self.lock();
+        if table.open.len() + table.starting >= MAX_SESSIONS {
+            return None;
+        }
+        table.starting += 1;
+        Some(SessionSlot {
+            sessions: self,
+            filled: false,
+        })
+    }
+
+    fn get(&self, session_id: &str) -> Option<Arc<WorkerSession>> {
+        self.lock().open.get(session_id).cloned()
+    }
+
+    fn remove(&self, session_id: &str) -> Option<Arc<WorkerSession>> {
+        self.lock().open.remove(session_id)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SessionTable> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A reserved session slot, given back when dropped unless filled
+struct SessionSlot<'a> {
+    sessions: &'a Sessions,
+    filled: bool,
+}
+
+impl SessionSlot<'_> {
+    fn fill(mut self, session_id: String, session: WorkerSession) {
+        let mut table = self.sessions.lock();
+        table.starting -= 1;
+        table.open.insert(session_id, Arc::new(session));
+        self.filled = true;
+    }
+}
+
+impl Drop for SessionSlot<'_> {
+    fn drop(&mut self) {
+        if !self.filled {
+            self.sessions.lock().starting -= 1;
+        }
+    }
+}
+
+struct WorkerSession {
+    handle: Mutex<SessionHandle>,
+    policy_id: String,
+    /// Cancelled by `python_session_close`, which stops a running call and
+    /// turns away calls still waiting for the session
+    closed: CancellationToken,
+}
+
+/// URI prefix of exported artifacts served through `resources/read`
+const EXPORTS_URI_PREFIX: &str = "rzn-python://exports/";
+
+/// Most exported artifacts remembered; older ones drop out of `resources/list`
+const MAX_RESOURCES: usize = 256;
+
+/// Files exported by `python_sandbox` runs, served as MCP resources
+///
+/// Only registered files can be read, so a client can't use `resources/read`
+/// to reach anything else on disk.
+#[derive(Default)]
+struct Resources(Mutex<Vec<ExportedResource>>);
+
+#[derive(Debug, Clone)]
+struct ExportedResource {
+    uri: String,
+    name: String,
+    path: PathBuf,
+    mime_type: &'static str,
+    size_bytes: Option<u64>,
+}
+
+impl ExportedResource {
+    fn to_json(&self) -> Value {
+        json!({
+            "uri": self.uri,
+            "name": self.name,
+            "mimeType": self.mime_type,
+            "size": self.size_bytes,
+        })
+    }
+}
+
+impl Resources {
+    /// Register the `exported_files` of a run's output, returning them
+    async fn register(&self, payload: &ExecutionResult) -> Vec<ExportedResource> {
+        let exported = exported_resources(payload);
+        if !exported.is_empty() {
+            let mut resources = self.0.lock().await;
+            resources.retain(|r| !exported.iter().any(|e| e.uri == r.uri));
+            resources.extend(exported.iter().cloned());
+            let excess = resources.len().saturating_sub(MAX_RESOURCES);
+            resources.drain(..excess);
+        }
+        exported
+    }
+
+    async fn list(&self) -> Vec<Value> {
+        self.0.lock().await.iter().map(|r| r.to_json()).collect()
+    }
+
+    async fn read(&self, uri: &str) -> std::result::Result<Value, Value> {
+        let resource = self
+            .0
+            .lock()
+            .await
+            .iter()
+            .find(|r| r.uri == uri)
+            .cloned()
+            .ok_or_else(|| {
+                json!({
+                    "code": -32002,
+                    "message": "Resource not found",
+                    "data": { "uri": uri }
+                })
+            })?;
+        let bytes = tokio::fs::read(&resource.path).await.map_err(|e| {
+            json!({
+                "code": -32002,
+                "message": format!("Failed to read resource: {}", e),
+                "data": { "uri": uri }
+            })
+        })?;
+        let mut contents = json!({ "uri": resource.uri, "mimeType": resource.mime_type });
+        match String::from_utf8(bytes) {
+            Ok(text) if is_text_mime(resource.mime_type) => contents["text"] = json!(text),
+            Ok(text) => contents["blob"] = json!(b64.encode(text)),
+            Err(e) => contents["blob"] = json!(b64.encode(e.into_bytes())),
+        }
+        Ok(json!({ "contents": [contents] }))
+    }
+}
+
+/// Resources for the `exported_files` of a run's output, keyed by the export
+/// directory's workspace id
+fn exported_resources(payload: &ExecutionResult) -> Vec<ExportedResource> {
+    let Some(workspace_id) = payload
+        .extra
+        .get("export_dir")
+        .and_then(|d| d.as_str())
+        .and_then(|d| Path::new(d).file_name())
+        .and_then(|d| d.to_str())
+    else {
+        return Vec::new();
+    };
+    payload
+        .extra
+        .get("exported_files")
+        .and_then(|f| f.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|file| {
+            let name = file.get("name")?.as_str()?;
+            let path = file.get("path")?.as_str()?;
+            Some(ExportedResource {
+                uri: format!("{}{}/{}", EXPORTS_URI_PREFIX, workspace_id, name),
+                name: name.to_string(),
+                path: PathBuf::from(path),
+                mime_type: mime_type_for(name),
+                size_bytes: file.get("size_bytes").and_then(|s| s.as_u64()),
+            })
+        })
+        .collect()
+}
+
+fn is_text_mime(mime_type: &str) -> bool {
+    mime_type.starts_with("text/")
+        || mime_type == "application/json"
+        || mime_type == "image/svg+xml"
+}
+
+/// JSON-RPC 2.0 batch: members run concurrently and their responses are
+/// sent back as one array in request order; a batch of only notifications
+/// gets no reply
+async fn handle_batch(
+    cfg: &Arc<WorkerConfig>,
+    state: &Arc<WorkerState>,
+    batch: Vec<Value>,
+    outbox: &Outbox,
+) -> Option<Value> {
+    let invalid_request = || {
+        json!({
+            "jsonrpc": "2.0",
+            "error": { "code": -32600, "message": "Invalid Request" },
+            "id": null
+        })
+    };
+    if batch.is_empty() {
+        return Some(invalid_request());
+    }
+
+    let mut members = JoinSet::new();
+    for (idx, msg) in batch.into_iter().enumerate() {
+        let (cfg, state, outbox) = (cfg.clone(), state.clone(), outbox.clone());
+        members.spawn(async move {
+            let resp = if msg.is_object() {
+                handle_message(&cfg, &state, msg, &outbox).await
+            } else {
+                Some(invalid_request())
+            };
+            (idx, resp)
+        });
+    }
+    let mut responses = Vec::new();
+    while let Some(joined) = members.join_next().await {
+        match joined {
+            Ok((idx, Some(resp))) => responses.push((idx, resp)),
+            Ok((_, None)) => {}
+            Err(e) => tracing::error!("batch member failed: {}", e),
+        }
+    }
+    responses.sort_by_key(|(idx, _)| *idx);
+    (!responses.is_empty()).then(|| Value::Array(responses.into_iter().map(|(_, r)| r).collect()))
+}
+
+async fn handle_message(
+    cfg: &WorkerConfig,
+    state: &WorkerState,
+    msg: Value,
+    outbox: &Outbox,
+) -> Option<Value> {
     let method = msg.get("method").and_then(|m| m.as_str()).unwrap_or("");
     let id = msg.get("id").cloned();
     let params = msg.get("params").cloned().unwrap_or_else(|| json!({}));
 
-    // Notifications have no id: ignore most, but allow "exit" to terminate.
-    if id.is_none() {
-        if method == "exit" {
-            std::process::exit(0);
+    // Notifications have no id: ignore most, but allow "exit" to terminate
+    // and "notifications/cancelled" to stop a running tool call.
+    let Some(id) = id else {
+        match method {
+            "exit" => std::process::exit(0),
+            "notifications/cancelled" => {
+                if let Some(request_id) = params.get("requestId") {
+                    if state.in_flight.cancel(request_id, true) {
+                        tracing::info!("cancelled request {}", request_id);
+                    }
+                }
+            }
+            _ => {}
         }
         return None;
-    }
+    };
 
     let result = match method {
         "initialize" => Ok(json!({
@@ -362,14 +806,47 @@ async fn handle_message(cfg: &WorkerConfig, msg: Value) -> Option<Value> {
             },
             "capabilities": {
                 "tools": { "listChanged": false },
-                "resources": { "listChanged": false },
+                "resources": { "listChanged": true },
                 "prompts": { "listChanged": false },
                 "experimental": {}
             }
         })),
         "tools/list" => Ok(json!({ "tools": tools_list() })),
-        "tools/call" => call_tool(cfg, &params).await,
-        "resources/list" => Ok(json!({ "resources": [] })),
+        "tools/call" => {
+            let progress = params
+                .pointer("/_meta/progressToken")
+                .filter(|t| t.is_string() || t.is_number())
+                .map(|token| Progress {
+                    outbox: outbox.clone(),
+                    token: token.clone(),
+                });
+            let cancel = state.in_flight.start(&id);
+            let result = call_tool(cfg, state, &params, progress, cancel).await;
+            if !state.in_flight.finish(&id) {
+                return None;
+            }
+            let exported = result
+                .as_ref()
+                .ok()
+                .and_then(|r| r.get("content"))
+                .and_then(|c| c.as_array())
+                .is_some_and(|c| c.iter().any(|item| item["type"] == "resource_link"));
+            if exported {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/resources/list_changed"
+                });
+                if let Err(e) = outbox.send(&notification).await {
+                    tracing::warn!("failed to send resources/list_changed: {}", e);
+                }
+            }
+            result
+        }
+        "resources/list" => Ok(json!({ "resources": state.resources.list().await })),
+        "resources/read" => match params.get("uri").and_then(|u| u.as_str()) {
+            Some(uri) => state.resources.read(uri).await,
+            None => Err(json!({ "code": -32602, "message": "Missing required param: uri" })),
+        },
         "prompts/list" => Ok(json!({ "prompts": [] })),
         "shutdown" => {
             std::process::exit(0);
@@ -436,84 +913,361 @@ fn tools_list() -> Vec<Value> {
                     "alias": { "type": "string", "description": "Environment alias ([a-zA-Z0-9._-], max 64 chars)." },
                     "python_path": { "type": "string", "description": "Optional base interpreter path used to create the venv." },
                     "recreate": { "type": "boolean", "description": "If true, delete and recreate an existing env alias." },
-                    "without_pip": { "type": "boolean", "description": "If true, skip bootstrapping pip in the new venv." }
+                    "env_manager": { "type": "string", "enum": ["auto","uv","pip","micromamba"], "description": "Use uv, venv+pip, or a micromamba conda env (default: RZN_PYTHON_ENV_MANAGER, else uv when in PATH)." },
+                    "without_pip": { "type": "boolean", "description": "If true, skip bootstrapping pip in the new venv." },
+                    "channels": { "type": "array", "items": { "type": "string" }, "description": "Conda channels in priority order (micromamba only, default conda-forge); refused while the enterprise policy restricts installs." },
+                    "python_version": { "type": "string", "description": "Python version spec such as 3.11 (micromamba only)." },
+                    "packages": { "type": "array", "items": { "type": "string" }, "description": "Conda package specs installed at creation, e.g. gdal (micromamba only)." }
                 },
                 "required": ["alias"],
                 "additionalProperties": true
             }
         }),
         json!({
-            "name": "python_env.install",
-            "description": "Install dependencies into a managed Python environment using pip.",
+            "name": "python_env.info",
+            "description": "Report a managed Python environment's interpreter version, installed package count, disk usage and metadata.",
             "inputSchema": {
                 "type": "object",
                 "properties": {
-                    "alias": { "type": "string", "description": "Environment alias to install into." },
-                    "packages": {
-                        "description": "Package targets for pip install (package names, wheel paths, or URLs).",
-                        "oneOf": [
-                            { "type": "array", "items": { "type": "string" } },
-                            { "type": "string", "description": "Comma-separated package targets" }
-                        ]
-                    },
-                    "requirements_file": { "type": "string", "description": "Optional requirements file path for pip -r." },
-                    "upgrade": { "type": "boolean", "description": "If true, pass --upgrade to pip." },
-                    "no_deps": { "type": "boolean", "description": "If true, pass --no-deps to pip." },
-                    "index_url": { "type": "string", "description": "Optional pip --index-url value." },
-                    "extra_index_url": { "type": "string", "description": "Optional pip --extra-index-url value." }
+                    "alias": { "type": "string", "description": "Environment alias to inspect." }
                 },
                 "required": ["alias"],
                 "additionalProperties": true
             }
         }),
         json!({
-            "name": "python_sandbox",
-            "description": "Execute Python code with policy-selected sandboxing and runtime selection.",
+            "name": "python_env.audit",
+            "description": "Check a managed Python environment's installed packages for known vulnerabilities with pip-audit (RZN_PIP_AUDIT, else pip-audit in PATH, else the env's own pip_audit module). Returns each vulnerable package with its advisory ids and fixed versions, so a host can warn before running code in the env.",
             "inputSchema": {
                 "type": "object",
                 "properties": {
-                    "code": { "type": "string", "description": "Python code to execute. You may set a `result` variable to return structured data." },
-                    "inputs": { "type": "object", "description": "JSON object available as `inputs` in the Python program." },
-                    "policy_id": { "type": "string", "description": "Host-selected policy id (e.g. yolo, balanced, data_science, enterprise)." },
-                    "policyId": { "type": "string", "description": "Alias of policy_id (legacy)." },
-                    "python_env": { "type": "string", "description": "Managed env alias to run with (YOLO mode). Alias of pythonEnv/env_alias." },
-                    "pythonEnv": { "type": "string", "description": "Alias of python_env (legacy camelCase)." },
-                    "env_alias": { "type": "string", "description": "Alias of python_env (legacy snake_case)." },
-                    "approved_folders": { "type": "array", "items": { "type": "string" }, "description": "Host-managed allowlist of approved folders (informational; OS boundary enforces in Secure mode)." },
-                    "python_runtime": { "type": "string", "enum": ["auto","bundled","system"], "description": "Select which Python interpreter to use." },
-                    "network_allowlist": {
-                        "description": "Optional outbound host allowlist for runtime network access. Use exact hosts, wildcard suffix entries like '*.example.com', or '*'.",
-                        "oneOf": [
-                            { "type": "array", "items": { "type": "string" } },
-                            { "type": "string", "description": "Comma-separated hosts" }
-                        ]
-                    },
-                    "networkAllowlist": {
-                        "description": "Alias of network_allowlist (legacy camelCase).",
-                        "oneOf": [
-                            { "type": "array", "items": { "type": "string" } },
-                            { "type": "string", "description": "Comma-separated hosts" }
+                    "alias": { "type": "string", "description": "Environment alias to audit." },
+                    "service": { "type": "string", "enum": ["pypi","osv"], "description": "Vulnerability database to query (default: pypi)." }
+                },
+                "required": ["alias"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_env.sbom",
+            "description": "Write a software bill of materials for a managed Python environment: every installed package with its version, license, archive SHA-256 when pip recorded one, and a SHA-256 of its RECORD as a named property. Saved next to rzn_env.json as sbom.cdx.json (CycloneDX 1.5) or sbom.spdx.json (SPDX 2.3) and returned.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "alias": { "type": "string", "description": "Environment alias to describe." },
+                    "format": { "type": "string", "enum": ["cyclonedx","spdx"], "description": "Document format (default: cyclonedx)." }
+                },
+                "required": ["alias"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_env.freeze",
+            "description": "Pin a managed Python environment's packages with pip freeze, returning the requirements and writing them to requirements.lock next to rzn_env.json. Install the file into a new env with python_env.install requirements_file to reproduce it.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "alias": { "type": "string", "description": "Environment alias to freeze." },
+                    "env_manager": { "type": "string", "enum": ["auto","uv","pip"], "description": "Use uv or venv+pip (default: RZN_PYTHON_ENV_MANAGER, else uv when in PATH)." }
+                },
+                "required": ["alias"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_env.clone",
+            "description": "Clone a managed Python environment to a new alias: creates a fresh venv from the same base interpreter and installs the source env's frozen requirements. The source env is not modified.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "source": { "type": "string", "description": "Alias of the env to clone." },
+                    "alias": { "type": "string", "description": "Alias for the new env ([a-zA-Z0-9._-], max 64 chars)." },
+                    "env_manager": { "type": "string", "enum": ["auto","uv","pip"], "description": "Use uv or venv+pip (default: RZN_PYTHON_ENV_MANAGER, else uv when in PATH)." }
+                },
+                "required": ["source", "alias"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_env.delete",
+            "description": "Delete a managed Python environment and everything installed in it.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "alias": { "type": "string", "description": "Environment alias to delete." }
+                },
+                "required": ["alias"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_env.prune",
+            "description": "Delete managed Python environments that have not run code for a number of days (envs never used count from their creation). Returns the deleted aliases and the disk space freed.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "days": { "type": "integer", "minimum": 0, "description": "Delete envs unused for at least this many days." },
+                    "dry_run": { "type": "boolean", "description": "If true, report what would be deleted without deleting it." }
+                },
+                "required": ["days"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_env.install",
+            "description": "Install dependencies into a managed Python environment using pip. An enterprise policy can limit installs to allowlisted packages, forbid URL, VCS and path targets, and require hashes.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "alias": { "type": "string", "description": "Environment alias to install into." },
+                    "env_manager": { "type": "string", "enum": ["auto","uv","pip","micromamba"], "description": "Use uv, pip, or micromamba for conda packages (default: RZN_PYTHON_ENV_MANAGER, else uv when in PATH)." },
+                    "channels": { "type": "array", "items": { "type": "string" }, "description": "Conda channels for micromamba installs (default: the env's channels); refused while the enterprise policy restricts installs." },
+                    "packages": {
+                        "description": "Package targets for pip install (package names, wheel paths, or URLs).",
+                        "oneOf": [
+                            { "type": "array", "items": { "type": "string" } },
+                            { "type": "string", "description": "Comma-separated package targets" }
+                        ]
+                    },
+                    "requirements_file": { "type": "string", "description": "Optional requirements file path for pip -r." },
+                    "upgrade": { "type": "boolean", "description": "If true, pass --upgrade to pip." },
+                    "no_deps": { "type": "boolean", "description": "If true, pass --no-deps to pip." },
+                    "require_hashes": { "type": "boolean", "description": "If true, pass --require-hashes to pip, so every requirement must pin a hash (always on when the enterprise policy requires it)." },
+                    "index_url": { "type": "string", "description": "Optional pip --index-url value; refused while the enterprise policy restricts installs." },
+                    "extra_index_url": { "type": "string", "description": "Optional pip --extra-index-url value; refused while the enterprise policy restricts installs." }
+                },
+                "required": ["alias"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_runtime.provision",
+            "description": "Download a python-build-standalone CPython for this OS and architecture into the app data dir, verify its SHA-256, and use it as the bundled runtime when no Python ships under resources/python/.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "python_version": { "type": "string", "description": "CPython version, e.g. 3.12.7 (default: the worker's pinned version)." },
+                    "release": { "type": "string", "description": "python-build-standalone release tag containing that version, e.g. 20241016." },
+                    "sha256": { "type": "string", "description": "Expected archive digest; default: look it up in the release's SHA256SUMS." },
+                    "force": { "type": "boolean", "description": "Reinstall even if this version is already provisioned." }
+                },
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_runtime.discover",
+            "description": "List every Python interpreter the worker could use (system, bundled, managed envs) with its version and architecture, whether it meets min_version, and what each python_runtime setting resolves to.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "min_version": { "type": "string", "description": "Minimum Python version to check against, e.g. 3.10." }
+                },
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_workspaces.gc",
+            "description": "Remove sandbox workspaces left behind by crashed or killed runs: those older than ttl_seconds, then the oldest until the rest fit max_total_mb. Workspaces modified in the last ten minutes are never removed.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ttl_seconds": { "type": "integer", "minimum": 0, "description": "Remove workspaces not modified for this long; 0 disables the TTL (default: RZN_PYTHON_WORKSPACE_TTL_SECS, else one day)." },
+                    "max_total_mb": { "type": "integer", "minimum": 0, "description": "Size budget for all workspaces together (default: RZN_PYTHON_WORKSPACE_MAX_MB, else none)." },
+                    "dry_run": { "type": "boolean", "description": "If true, report what would be removed without removing it." }
+                },
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_sandbox",
+            "description": "Execute Python code with policy-selected sandboxing and runtime selection. Send _meta.progressToken to receive stdout/stderr chunks as notifications/progress while it runs.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "code": { "type": "string", "description": "Python code to execute. You may set a `result` variable to return structured data." },
+                    "inputs": { "type": "object", "description": "JSON object available as `inputs` in the Python program." },
+                    "policy_id": { "type": "string", "description": "Host-selected policy id (e.g. yolo, balanced, data_science, enterprise)." },
+                    "policyId": { "type": "string", "description": "Alias of policy_id (legacy)." },
+                    "python_env": { "type": "string", "description": "Managed env alias to run with (YOLO mode). Alias of pythonEnv/env_alias." },
+                    "pythonEnv": { "type": "string", "description": "Alias of python_env (legacy camelCase)." },
+                    "env_alias": { "type": "string", "description": "Alias of python_env (legacy snake_case)." },
+                    "approved_folders": { "type": "array", "items": { "type": "string" }, "description": "Host-managed allowlist of approved folders. input_files must lie inside one; otherwise informational (OS boundary enforces in Secure mode)." },
+                    "input_files": {
+                        "type": "array",
+                        "description": "Files copied into INPUT_DIR before the run (the workspace input/ directory in workspace execution modes).",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "path": { "type": "string", "description": "Host path; must be inside approved_folders." },
+                                "workspace_name": { "type": "string", "description": "File name inside input/. Defaults to the source file name." }
+                            },
+                            "required": ["path"]
+                        }
+                    },
+                    "python_runtime": { "type": "string", "enum": ["auto","bundled","system"], "description": "Select which Python interpreter to use." },
+                    "network_allowlist": {
+                        "description": "Optional outbound host allowlist for runtime network access. Use exact hosts, wildcard suffix entries like '*.example.com', or '*', optionally with a port ('api.example.com:443') or scheme ('https://api.example.com'). Connections to raw IPs must match an address an allowed host resolved to.",
+                        "oneOf": [
+                            { "type": "array", "items": { "type": "string" } },
+                            { "type": "string", "description": "Comma-separated hosts" }
+                        ]
+                    },
+                    "networkAllowlist": {
+                        "description": "Alias of network_allowlist (legacy camelCase).",
+                        "oneOf": [
+                            { "type": "array", "items": { "type": "string" } },
+                            { "type": "string", "description": "Comma-separated hosts" }
                         ]
                     },
                     "python_path": { "type": "string", "description": "Override Python executable path. Relative paths are resolved against RZN_PLUGIN_DIR when present." },
                     "execution_mode": { "type": "string", "enum": ["native","workspace_isolated","platform_sandboxed"], "description": "Override execution mode. If omitted, derived from policy_id." },
-                    "timeout_seconds": { "type": "integer", "minimum": 1, "maximum": 600, "description": "Wall-clock timeout for the run." }
+                    "timeout_seconds": { "type": "integer", "minimum": 1, "maximum": 600, "description": "Wall-clock timeout for the run." },
+                    "preflight": { "type": "boolean", "description": "With python_env, check the code's unconditional imports are installed before running and fail with missing_packages if not (default true)." },
+                    "auto_install": { "type": "boolean", "description": "With python_env, install missing packages instead of failing (default false)." }
+                },
+                "required": ["code"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_sandbox.validate",
+            "description": "Dry run: check Python code against a policy without executing it. Returns syntax and static-analysis diagnostics (the same checks python_sandbox refuses code for) plus a per-import/call/path policy report, so generated code can be fixed before running it.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "code": { "type": "string", "description": "Python code to check." },
+                    "policy_id": { "type": "string", "description": "Policy id to check against (e.g. yolo, balanced, data_science, enterprise)." },
+                    "policyId": { "type": "string", "description": "Alias of policy_id (legacy)." },
+                    "python_env": { "type": "string", "description": "Managed env alias whose interpreter checks the syntax (YOLO mode)." },
+                    "python_runtime": { "type": "string", "enum": ["auto","bundled","system"], "description": "Select which Python interpreter checks the syntax." },
+                    "network_allowlist": {
+                        "description": "Outbound host allowlist the run would use.",
+                        "oneOf": [
+                            { "type": "array", "items": { "type": "string" } },
+                            { "type": "string", "description": "Comma-separated hosts" }
+                        ]
+                    },
+                    "python_path": { "type": "string", "description": "Override Python executable path." }
                 },
                 "required": ["code"],
                 "additionalProperties": true
             }
         }),
+        json!({
+            "name": "python_sandbox.cancel",
+            "description": "Cancel a running python_sandbox call by its JSON-RPC request id, killing its process. The cancelled call returns an error result. Clients can also send notifications/cancelled.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "request_id": { "description": "JSON-RPC id of the tools/call request to cancel.", "oneOf": [{ "type": "string" }, { "type": "integer" }] }
+                },
+                "required": ["request_id"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_session.start",
+            "description": "Start a persistent Python session whose variables, imports and DataFrames survive between python_session.exec calls. Uses the same policy mapping as python_sandbox; the policy's import, network and native-extension guards run inside one long-lived interpreter, so execution_mode=platform_sandboxed is not supported.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "policy_id": { "type": "string", "description": "Host-selected policy id (e.g. yolo, balanced, data_science, enterprise)." },
+                    "python_env": { "type": "string", "description": "Managed env alias to run with (YOLO mode)." },
+                    "python_runtime": { "type": "string", "enum": ["auto","bundled","system"], "description": "Select which Python interpreter to use." },
+                    "python_path": { "type": "string", "description": "Override Python executable path." },
+                    "network_allowlist": {
+                        "description": "Optional outbound host allowlist, as for python_sandbox.",
+                        "oneOf": [
+                            { "type": "array", "items": { "type": "string" } },
+                            { "type": "string", "description": "Comma-separated hosts" }
+                        ]
+                    },
+                    "timeout_seconds": { "type": "integer", "minimum": 1, "maximum": 600, "description": "Wall-clock timeout for each python_session.exec call. A call that times out ends the session." }
+                },
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "python_session.exec",
+            "description": "Run Python code in a session started with python_session.start.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "session_id": { "type": "string" },
+                    "code": { "type": "string", "description": "Python code to execute. You may set a `result` variable to return structured data." },
+                    "inputs": { "type": "object", "description": "JSON object available as `inputs` for this call." }
+                },
+                "required": ["session_id", "code"],
+                "additionalProperties": true
+            }
+        }),
+        json!({
+            "name": "sandbox.policies.list",
+            "description": "List the policy ids accepted by `policy_id`, with their description, security level (0-10), resource limits, network access, and whether the organization's enterprise policy rejects or tightens them.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }
+        }),
+        json!({
+            "name": "python_session.close",
+            "description": "Close a Python session and discard its state.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "session_id": { "type": "string" }
+                },
+                "required": ["session_id"],
+                "additionalProperties": true
+            }
+        }),
     ]
 }
 
-async fn call_tool(cfg: &WorkerConfig, params: &Value) -> std::result::Result<Value, Value> {
+async fn call_tool(
+    cfg: &WorkerConfig,
+    state: &WorkerState,
+    params: &Value,
+    progress: Option<Progress>,
+    cancel: CancellationToken,
+) -> std::result::Result<Value, Value> {
     let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
     let args = params
         .get("arguments")
         .cloned()
         .unwrap_or_else(|| json!({}));
+    let tool = normalize_tool_name(name);
+
+    let _slot = if needs_tool_slot(&tool) {
+        let acquire = tokio::time::timeout(cfg.queue_timeout, state.tool_slots.acquire());
+        tokio::select! {
+            slot = acquire => match slot {
+                Ok(Ok(permit)) => Some(permit),
+                Ok(Err(_)) | Err(_) => {
+                    return Err(json!({
+                        "code": -32000,
+                        "message": format!(
+                            "Worker busy: {} tool call(s) already running, none finished within {}s",
+                            cfg.max_concurrency,
+                            cfg.queue_timeout.as_secs()
+                        ),
+                        "data": {
+                            "max_concurrency": cfg.max_concurrency,
+                            "queue_timeout_seconds": cfg.queue_timeout.as_secs()
+                        }
+                    }));
+                }
+            },
+            _ = cancel.cancelled() => {
+                return Err(json!({ "code": -32800, "message": "Request cancelled while queued" }));
+            }
+        }
+    } else {
+        None
+    };
 
-    match normalize_tool_name(name).as_str() {
+    match tool.as_str() {
         "python_tools_ping" => Ok(json!({
             "content": [{ "type": "text", "text": "pong" }],
             "isError": false,
@@ -525,6 +1279,8 @@ async fn call_tool(cfg: &WorkerConfig, params: &Value) -> std::result::Result<Va
                 "worker": "rzn-python-worker",
                 "version": env!("CARGO_PKG_VERSION"),
                 "plugin_dir": cfg.plugin_dir,
+                "max_concurrency": cfg.max_concurrency,
+                "running_tool_calls": cfg.max_concurrency - state.tool_slots.available_permits(),
             },
             "isError": false
         })),
@@ -536,9 +1292,31 @@ async fn call_tool(cfg: &WorkerConfig, params: &Value) -> std::result::Result<Va
             "isError": false
         })),
         "python_env_list" => python_env_list_call(&args).await,
-        "python_env_create" => python_env_create_call(cfg, &args).await,
-        "python_env_install" => python_env_install_call(&args).await,
-        "python_sandbox" => python_sandbox_call(cfg, &args).await,
+        "python_env_create" => until_cancelled(&cancel, python_env_create_call(cfg, &args)).await,
+        "python_env_install" => until_cancelled(&cancel, python_env_install_call(cfg, &args)).await,
+        "python_env_info" => until_cancelled(&cancel, python_env_info_call(&args)).await,
+        "python_env_freeze" => until_cancelled(&cancel, python_env_freeze_call(&args)).await,
+        "python_env_audit" => until_cancelled(&cancel, python_env_audit_call(&args)).await,
+        "python_env_sbom" => until_cancelled(&cancel, python_env_sbom_call(&args)).await,
+        "python_env_clone" => until_cancelled(&cancel, python_env_clone_call(cfg, &args)).await,
+        "python_env_delete" => python_env_delete_call(&args).await,
+        "python_env_prune" => python_env_prune_call(&args).await,
+        "python_runtime_provision" => {
+            until_cancelled(&cancel, python_runtime_provision_call(&args)).await
+        }
+        "python_runtime_discover" => {
+            until_cancelled(&cancel, python_runtime_discover_call(cfg, &args)).await
+        }
+        "python_workspaces_gc" => python_workspaces_gc_call(cfg, &args).await,
+        "python_sandbox" => {
+            python_sandbox_call(cfg, &state.resources, &args, progress, cancel).await
+        }
+        "python_sandbox_cancel" => python_sandbox_cancel_call(&state.in_flight, &args),
+        "python_sandbox_validate" => python_sandbox_validate_call(cfg, &args).await,
+        "python_session_start" => python_session_start_call(cfg, &state.sessions, &args).await,
+        "python_session_exec" => python_session_exec_call(&state.sessions, &args, cancel).await,
+        "python_session_close" => python_session_close_call(&state.sessions, &args).await,
+        "sandbox_policies_list" => Ok(sandbox_policies_list_call(cfg)),
         other => Ok(json!({
             "content": [{ "type": "text", "text": format!("Unknown tool: {}", other) }],
             "isError": true
@@ -546,8 +1324,20 @@ async fn call_tool(cfg: &WorkerConfig, params: &Value) -> std::result::Result<Va
     }
 }
 
+/// Run a tool call until its request is cancelled; dropping it kills the
+/// pip, uv, micromamba or download it's waiting on
+async fn until_cancelled(
+    cancel: &CancellationToken,
+    call: impl std::future::Future<Output = std::result::Result<Value, Value>>,
+) -> std::result::Result<Value, Value> {
+    tokio::select! {
+        result = call => result,
+        _ = cancel.cancelled() => Err(json!({ "code": -32800, "message": "Request cancelled" })),
+    }
+}
+
 fn normalize_tool_name(name: &str) -> String {
-    name.replace('.', "_").replace('/', "_")
+    name.replace(['.', '/'], "_")
 }
 
 fn policy_id_from_args(args: &Value) -> String {
@@ -565,6 +1355,88 @@ fn policy_id_from_args(args: &Value) -> String {
         .unwrap_or_else(|| "balanced".to_string())
 }
 
+/// Refuse a policy id the enterprise policy does not allow
+fn check_enterprise_policy(cfg: &WorkerConfig, policy_id: &str) -> std::result::Result<(), Value> {
+    let Some(enterprise) = &cfg.enterprise_policy else {
+        return Ok(());
+    };
+    enterprise
+        .apply(map_policy_to_sandbox_policy(policy_id))
+        .map(|_| ())
+        .map_err(|message| {
+            json!({
+                "code": -32602,
+                "message": message,
+                "data": {
+                    "policy_id": policy_id,
+                    "policy_message": enterprise.policy_message
+                }
+            })
+        })
+}
+
+fn sandbox_policies_list_call(cfg: &WorkerConfig) -> Value {
+    let default_id = policy_id_from_args(&json!({}));
+    let policies: Vec<Value> = POLICY_IDS
+        .iter()
+        .map(|&id| {
+            let policy = map_policy_to_sandbox_policy(id);
+            let enterprise = cfg.enterprise_policy.as_ref().map(|enterprise| {
+                match enterprise.changes(&policy) {
+                    Ok(changes) => json!({
+                        "allowed": true,
+                        "modified": !changes.is_empty(),
+                        "changes": changes,
+                    }),
+                    Err(message) => json!({
+                        "allowed": false,
+                        "modified": false,
+                        "changes": [],
+                        "message": message,
+                    }),
+                }
+            });
+            json!({
+                "id": id,
+                "name": policy.name,
+                "description": policy.description,
+                "security_level": policy.security_level(),
+                "default": id == default_id,
+                "execution_mode": format!("{:?}", map_policy_to_execution_mode(id)),
+                "security_profile": format!("{:?}", map_policy_to_profile(id)),
+                "network": policy.network,
+                "resources": policy.resources,
+                "enterprise": enterprise,
+            })
+        })
+        .collect();
+    let text = policies
+        .iter()
+        .map(|p| {
+            format!(
+                "{} (level {}): {}",
+                p["id"].as_str().unwrap_or_default(),
+                p["security_level"],
+                p["description"].as_str().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "structuredContent": {
+            "policies": policies,
+            "default_policy_id": default_id,
+            "enterprise_policy": cfg.enterprise_policy.as_ref().map(|e| json!({
+                "minimum_security_level": e.minimum_security_level,
+                "package_install": e.package_install,
+                "policy_message": e.policy_message,
+            })),
+        },
+        "isError": false
+    })
+}
+
 fn map_policy_to_profile(policy_id: &str) -> SecurityProfile {
     match policy_id {
         "yolo" => SecurityProfile::Yolo,
@@ -575,6 +1447,16 @@ fn map_policy_to_profile(policy_id: &str) -> SecurityProfile {
     }
 }
 
+fn map_policy_to_sandbox_policy(policy_id: &str) -> SandboxPolicy {
+    match policy_id {
+        "yolo" => SandboxPolicy::yolo(),
+        "enterprise" => SandboxPolicy::enterprise(),
+        "data_science" => SandboxPolicy::data_science(),
+        "document_processing" => SandboxPolicy::document_processing(),
+        _ => SandboxPolicy::balanced(),
+    }
+}
+
 fn map_policy_to_execution_mode(policy_id: &str) -> ExecutionMode {
     match policy_id {
         "enterprise" | "data_science" | "document_processing" => ExecutionMode::WorkspaceIsolated,
@@ -676,41 +1558,213 @@ fn parse_network_allowlist(args: &Value) -> std::result::Result<Option<Vec<Strin
     Ok(None)
 }
 
-fn env_alias_from_args(args: &Value) -> std::result::Result<String, Value> {
-    let alias = args
-        .get("alias")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| json!({ "code": -32602, "message": "Missing required argument: alias" }))?;
-    validate_env_alias(alias)
+/// Import names whose PyPI distribution is named differently
+#[cfg(feature = "static-analyzer")]
+const PACKAGE_NAMES: &[(&str, &str)] = &[
+    ("attr", "attrs"),
+    ("bs4", "beautifulsoup4"),
+    ("Crypto", "pycryptodome"),
+    ("cv2", "opencv-python"),
+    ("dateutil", "python-dateutil"),
+    ("docx", "python-docx"),
+    ("dotenv", "python-dotenv"),
+    ("fitz", "pymupdf"),
+    ("jwt", "pyjwt"),
+    ("PIL", "pillow"),
+    ("pptx", "python-pptx"),
+    ("serial", "pyserial"),
+    ("skimage", "scikit-image"),
+    ("sklearn", "scikit-learn"),
+    ("yaml", "pyyaml"),
+];
+
+/// PyPI package that provides top-level module `module`
+#[cfg(feature = "static-analyzer")]
+fn package_for_module(module: &str) -> &str {
+    PACKAGE_NAMES
+        .iter()
+        .find(|(m, _)| *m == module)
+        .map_or(module, |(_, package)| package)
 }
 
-fn parse_string_list(args: &Value, key: &str) -> std::result::Result<Vec<String>, Value> {
-    let Some(value) = args.get(key) else {
+/// Unconditional imports of `code` that `python` cannot find, as
+/// `{module, package, line}`
+///
+/// Imports inside `try`, `if` or function bodies are skipped; they are often
+/// optional fallbacks.
+#[cfg(feature = "static-analyzer")]
+async fn missing_packages_for(python: &Path, code: &str) -> std::result::Result<Vec<Value>, Value> {
+    const PROBE: &str = r#"
+import importlib.util, json, sys
+missing = []
+for name in json.loads(sys.argv[1]):
+    try:
+        found = importlib.util.find_spec(name) is not None
+    except (ImportError, ValueError):
+        found = False
+    if not found:
+        missing.append(name)
+print(json.dumps(missing))
+"#;
+    // Code that does not parse is reported by the run itself
+    let Ok(report) = pysandbox::analyzer::extract_imports(code) else {
         return Ok(Vec::new());
     };
-    match value {
-        Value::String(s) => Ok(s
-            .split(',')
-            .map(|part| part.trim().to_string())
-            .filter(|part| !part.is_empty())
-            .collect()),
-        Value::Array(items) => {
-            let mut out = Vec::new();
-            for item in items {
-                let s = item.as_str().ok_or_else(|| {
-                    json!({
-                        "code": -32602,
-                        "message": format!("Invalid '{}': expected string entries", key)
-                    })
-                })?;
-                let trimmed = s.trim();
-                if !trimmed.is_empty() {
-                    out.push(trimmed.to_string());
-                }
-            }
-            Ok(out)
-        }
-        _ => Err(json!({
+    let modules = report.required_modules();
+    if modules.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut cmd = Command::new(python);
+    cmd.arg("-c").arg(PROBE).arg(json!(modules).to_string());
+    let output = run_cmd_capture(&mut cmd, PREFLIGHT_TIMEOUT_SECS).await?;
+    if !output.status.success() {
+        return Err(json!({
+            "code": -32000,
+            "message": "Import preflight failed",
+            "data": { "stderr": String::from_utf8_lossy(&output.stderr).trim() }
+        }));
+    }
+    let missing: Vec<String> = serde_json::from_slice(&output.stdout).unwrap_or_default();
+    Ok(missing
+        .iter()
+        .map(|module| {
+            let line = report
+                .imports
+                .iter()
+                .find(|i| !i.conditional && i.root_module() == Some(module.as_str()))
+                .map(|i| i.line);
+            json!({ "module": module, "package": package_for_module(module), "line": line })
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "static-analyzer"))]
+async fn missing_packages_for(
+    _python: &Path,
+    _code: &str,
+) -> std::result::Result<Vec<Value>, Value> {
+    Ok(Vec::new())
+}
+
+/// `input_files: [{path, workspace_name}]`, each resolved and checked to lie
+/// inside one of `approved_folders`
+fn parse_input_files(args: &Value) -> std::result::Result<Vec<(PathBuf, String)>, Value> {
+    let invalid = |message: String| json!({ "code": -32602, "message": message });
+    let Some(entries) = args.get("input_files").or_else(|| args.get("inputFiles")) else {
+        return Ok(Vec::new());
+    };
+    let entries = entries
+        .as_array()
+        .ok_or_else(|| invalid("Invalid input_files: expected an array".to_string()))?;
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let approved: Vec<PathBuf> = args
+        .get("approved_folders")
+        .and_then(|v| v.as_array())
+        .map(|folders| {
+            folders
+                .iter()
+                .filter_map(|f| f.as_str())
+                .filter_map(|f| std::fs::canonicalize(f).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    if approved.is_empty() {
+        return Err(invalid(
+            "input_files requires approved_folders containing the files".to_string(),
+        ));
+    }
+
+    let mut files = Vec::new();
+    let mut names = HashSet::new();
+    for entry in entries {
+        let path = entry
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| invalid("Invalid input_files entry: missing path".to_string()))?;
+        // Symlinks and `..` are resolved before the folder check
+        let source = std::fs::canonicalize(path)
+            .map_err(|e| invalid(format!("Input file {}: {}", path, e)))?;
+        if !source.is_file() {
+            return Err(invalid(format!(
+                "Input file {} is not a regular file",
+                path
+            )));
+        }
+        if !approved.iter().any(|folder| source.starts_with(folder)) {
+            return Err(json!({
+                "code": -32602,
+                "message": format!("Input file {} is outside approved_folders", path),
+                "data": { "path": path, "approved_folders": approved }
+            }));
+        }
+
+        let name = match entry
+            .get("workspace_name")
+            .or_else(|| entry.get("workspaceName"))
+            .and_then(|v| v.as_str())
+        {
+            Some(name) => name.to_string(),
+            None => source
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        let mut components = Path::new(&name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(std::path::Component::Normal(_)), None)
+        ) {
+            return Err(invalid(format!(
+                "Invalid workspace_name {:?}: must be a plain file name",
+                name
+            )));
+        }
+        if !names.insert(name.clone()) {
+            return Err(invalid(format!("Duplicate workspace_name {:?}", name)));
+        }
+        files.push((source, name));
+    }
+    Ok(files)
+}
+
+fn env_alias_from_args(args: &Value) -> std::result::Result<String, Value> {
+    let alias = args
+        .get("alias")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| json!({ "code": -32602, "message": "Missing required argument: alias" }))?;
+    validate_env_alias(alias)
+}
+
+fn parse_string_list(args: &Value, key: &str) -> std::result::Result<Vec<String>, Value> {
+    let Some(value) = args.get(key) else {
+        return Ok(Vec::new());
+    };
+    match value {
+        Value::String(s) => Ok(s
+            .split(',')
+            .map(|part| part.trim().to_string())
+            .filter(|part| !part.is_empty())
+            .collect()),
+        Value::Array(items) => {
+            let mut out = Vec::new();
+            for item in items {
+                let s = item.as_str().ok_or_else(|| {
+                    json!({
+                        "code": -32602,
+                        "message": format!("Invalid '{}': expected string entries", key)
+                    })
+                })?;
+                let trimmed = s.trim();
+                if !trimmed.is_empty() {
+                    out.push(trimmed.to_string());
+                }
+            }
+            Ok(out)
+        }
+        _ => Err(json!({
             "code": -32602,
             "message": format!("Invalid '{}': expected string or array of strings", key)
         })),
@@ -730,56 +1784,21 @@ fn parse_optional_string(args: &Value, key: &str) -> Option<String> {
 
 async fn python_env_list_call(args: &Value) -> std::result::Result<Value, Value> {
     let include_broken = parse_bool(args, "include_broken", true);
-    let base_dir = managed_envs_base_dir();
-    std::fs::create_dir_all(&base_dir).map_err(|e| {
-        json!({
-            "code": -32000,
-            "message": format!("Failed to ensure env base dir exists: {}", e),
-            "data": { "envs_dir": base_dir }
-        })
-    })?;
-
-    let mut envs = Vec::<Value>::new();
-    let entries = std::fs::read_dir(&base_dir).map_err(|e| {
-        json!({
-            "code": -32000,
-            "message": format!("Failed to read env base dir: {}", e),
-            "data": { "envs_dir": base_dir }
+    let registry = ManagedEnvRegistry::from_env();
+    let envs: Vec<Value> = registry
+        .list(include_broken)
+        .map_err(|e| env_error_with_data(e, json!({ "envs_dir": registry.base_dir() })))?
+        .into_iter()
+        .map(|env| {
+            json!({
+                "alias": env.alias,
+                "healthy": env.is_healthy(),
+                "env_dir": env.env_dir,
+                "python_path": env.python_path,
+                "metadata": env.metadata,
+            })
         })
-    })?;
-
-    for entry in entries {
-        let entry = match entry {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-        let Ok(file_type) = entry.file_type() else {
-            continue;
-        };
-        if !file_type.is_dir() {
-            continue;
-        }
-        let alias = entry.file_name().to_string_lossy().to_string();
-        let env_dir = entry.path();
-        let python_path = managed_env_python_path(&env_dir);
-        let python_exists = python_path.exists();
-        if !python_exists && !include_broken {
-            continue;
-        }
-        let metadata = read_env_metadata(&env_dir);
-        envs.push(json!({
-            "alias": alias,
-            "env_dir": env_dir,
-            "python_path": python_path,
-            "healthy": python_exists,
-            "metadata": metadata,
-        }));
-    }
-    envs.sort_by(|a, b| {
-        let ak = a.get("alias").and_then(|v| v.as_str()).unwrap_or("");
-        let bk = b.get("alias").and_then(|v| v.as_str()).unwrap_or("");
-        ak.cmp(bk)
-    });
+        .collect();
 
     Ok(json!({
         "content": [{
@@ -787,7 +1806,7 @@ async fn python_env_list_call(args: &Value) -> std::result::Result<Value, Value>
             "text": format!("{} managed python env(s)", envs.len())
         }],
         "structuredContent": {
-            "envs_dir": base_dir,
+            "envs_dir": registry.base_dir(),
             "envs": envs,
         },
         "isError": false
@@ -800,133 +1819,89 @@ async fn python_env_create_call(
 ) -> std::result::Result<Value, Value> {
     let alias = env_alias_from_args(args)?;
     let recreate = parse_bool(args, "recreate", false);
-    let without_pip = parse_bool(args, "without_pip", false);
+    let env_manager = env_manager_from_args(args)?;
 
-    let envs_dir = managed_envs_base_dir();
-    std::fs::create_dir_all(&envs_dir).map_err(|e| {
-        json!({
-            "code": -32000,
-            "message": format!("Failed to create env base dir: {}", e),
-            "data": { "envs_dir": envs_dir }
-        })
-    })?;
-
-    let env_dir = managed_env_dir(&alias);
-    if env_dir.exists() {
-        if recreate {
-            std::fs::remove_dir_all(&env_dir).map_err(|e| {
-                json!({
-                    "code": -32000,
-                    "message": format!("Failed to remove existing env for recreate: {}", e),
-                    "data": { "alias": alias, "env_dir": env_dir }
-                })
-            })?;
-        } else {
-            let (_, python_path) = resolve_existing_managed_env(&alias)?;
-            let metadata = read_env_metadata(&env_dir);
-            return Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": format!("Managed env '{}' already exists", alias)
-                }],
-                "structuredContent": {
-                    "alias": alias,
-                    "env_dir": env_dir,
-                    "python_path": python_path,
-                    "created": false,
-                    "metadata": metadata,
-                },
-                "isError": false
-            }));
-        }
-    }
-
-    let python_path_override = parse_optional_string(args, "python_path")
-        .or_else(|| parse_optional_string(args, "pythonPath"));
-    let base_python = if let Some(raw) = python_path_override {
-        resolve_maybe_relative(cfg.plugin_dir.as_deref(), Path::new(&raw))
-    } else {
-        which::which("python3")
-            .or_else(|_| which::which("python"))
-            .map_err(|_| {
-                json!({
-                    "code": -32000,
-                    "message": "Python not found in PATH for venv creation"
-                })
-            })?
-    };
-
-    let mut create_cmd = Command::new(&base_python);
-    create_cmd.arg("-m").arg("venv").arg(&env_dir);
-    if without_pip {
-        create_cmd.arg("--without-pip");
-    }
-    let create_output = run_cmd_capture(&mut create_cmd, ENV_TOOL_TIMEOUT_SECS).await?;
-    if !create_output.status.success() {
-        let stderr = String::from_utf8_lossy(&create_output.stderr);
-        return Err(json!({
-            "code": -32000,
-            "message": format!("Failed to create managed env '{}'", alias),
-            "data": {
-                "stderr": stderr,
-                "stdout": String::from_utf8_lossy(&create_output.stdout),
-                "base_python": base_python,
-                "env_dir": env_dir
-            }
+    let registry = ManagedEnvRegistry::from_env();
+    let env_dir = registry.env_dir(&alias).map_err(env_error)?;
+    if env_dir.exists() && !recreate {
+        let (_, python_path) = resolve_existing_managed_env(&alias)?;
+        let metadata = ManagedEnvMetadata::read(&env_dir);
+        return Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": format!("Managed env '{}' already exists", alias)
+            }],
+            "structuredContent": {
+                "alias": alias,
+                "env_dir": env_dir,
+                "python_path": python_path,
+                "created": false,
+                "metadata": metadata,
+            },
+            "isError": false
         }));
     }
 
-    let python_path = managed_env_python_path(&env_dir);
-    if !python_path.exists() {
-        return Err(json!({
+    // Conda packages are installed too, so the package policy applies
+    let package_policy = package_install_policy(cfg);
+    let packages = parse_string_list(args, "packages")?;
+    let channels = parse_string_list(args, "channels")?;
+    check_install_sources(
+        cfg,
+        &package_policy,
+        "python_env.create",
+        &[("channels", !channels.is_empty())],
+    )?;
+    check_install_targets(cfg, &package_policy, "python_env.create", &packages, None)?;
+
+    // Micromamba envs take the conda arguments, venvs the base interpreter
+    let options = CreateEnvOptions {
+        env_manager: Some(env_manager.clone()),
+        base_python: parse_optional_string(args, "python_path")
+            .or_else(|| parse_optional_string(args, "pythonPath"))
+            .map(|raw| resolve_maybe_relative(cfg.plugin_dir.as_deref(), Path::new(&raw))),
+        without_pip: parse_bool(args, "without_pip", false),
+        recreate,
+        python_version: parse_optional_string(args, "python_version"),
+        packages,
+        channels,
+    };
+    let env = tokio::time::timeout(
+        std::time::Duration::from_secs(ENV_TOOL_TIMEOUT_SECS),
+        registry.create(&alias, &options),
+    )
+    .await
+    .map_err(|_| {
+        json!({
             "code": -32000,
-            "message": "Venv created but python interpreter is missing",
-            "data": { "alias": alias, "env_dir": env_dir, "python_path": python_path }
-        }));
-    }
-
-    if !without_pip {
-        let mut pip_check_cmd = Command::new(&python_path);
-        pip_check_cmd.arg("-m").arg("pip").arg("--version");
-        let pip_check = run_cmd_capture(&mut pip_check_cmd, 60).await?;
-        if !pip_check.status.success() {
-            let mut ensurepip_cmd = Command::new(&python_path);
-            ensurepip_cmd.arg("-m").arg("ensurepip").arg("--upgrade");
-            let ensurepip_output = run_cmd_capture(&mut ensurepip_cmd, 120).await?;
-            if !ensurepip_output.status.success() {
-                let stderr = String::from_utf8_lossy(&ensurepip_output.stderr);
-                return Err(json!({
-                    "code": -32000,
-                    "message": format!("Managed env '{}' created but pip setup failed", alias),
-                    "data": {
-                        "stderr": stderr,
-                        "stdout": String::from_utf8_lossy(&ensurepip_output.stdout),
-                        "python_path": python_path
-                    }
-                }));
-            }
-        }
-    }
+            "message": format!("Process timed out after {}s", ENV_TOOL_TIMEOUT_SECS)
+        })
+    })?
+    .map_err(|e| env_error_with_data(e, json!({ "alias": alias, "env_dir": env_dir })))?;
 
-    let metadata = write_env_metadata(&alias, &env_dir, &python_path, Some(&base_python))?;
+    let base_python = env.metadata.as_ref().and_then(|m| m.base_python.clone());
     Ok(json!({
         "content": [{
             "type": "text",
             "text": format!("Created managed env '{}'", alias)
         }],
         "structuredContent": {
-            "alias": alias,
-            "env_dir": env_dir,
-            "python_path": python_path,
+            "alias": env.alias,
+            "env_dir": env.env_dir,
+            "python_path": env.python_path,
             "base_python": base_python,
+            "env_manager": env_manager.name(),
             "created": true,
-            "metadata": metadata,
+            "metadata": env.metadata,
         },
         "isError": false
     }))
 }
 
-async fn python_env_install_call(args: &Value) -> std::result::Result<Value, Value> {
+async fn python_env_install_call(
+    cfg: &WorkerConfig,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
     let alias = env_alias_from_args(args)?;
     let (env_dir, python_path) = resolve_existing_managed_env(&alias)?;
 
@@ -941,6 +1916,8 @@ async fn python_env_install_call(args: &Value) -> std::result::Result<Value, Val
     let no_deps = parse_bool(args, "no_deps", false);
     let index_url = parse_optional_string(args, "index_url");
     let extra_index_url = parse_optional_string(args, "extra_index_url");
+    let package_policy = package_install_policy(cfg);
+    let require_hashes = package_policy.require_hashes || parse_bool(args, "require_hashes", false);
 
     if package_targets.is_empty() && requirements_file.is_none() {
         return Err(json!({
@@ -948,24 +1925,71 @@ async fn python_env_install_call(args: &Value) -> std::result::Result<Value, Val
             "message": "python_env.install requires packages or requirements_file"
         }));
     }
-
-    let mut cmd = Command::new(&python_path);
-    cmd.arg("-m").arg("pip").arg("install");
-    if upgrade {
-        cmd.arg("--upgrade");
-    }
-    if no_deps {
-        cmd.arg("--no-deps");
-    }
-    if let Some(index_url) = &index_url {
-        cmd.arg("--index-url").arg(index_url);
-    }
-    if let Some(extra_index_url) = &extra_index_url {
-        cmd.arg("--extra-index-url").arg(extra_index_url);
-    }
+    check_install_sources(
+        cfg,
+        &package_policy,
+        "python_env.install",
+        &[
+            ("index_url", index_url.is_some()),
+            ("extra_index_url", extra_index_url.is_some()),
+            ("channels", !parse_string_list(args, "channels")?.is_empty()),
+        ],
+    )?;
+    check_install_targets(
+        cfg,
+        &package_policy,
+        "python_env.install",
+        &package_targets,
+        requirements_file.as_deref(),
+    )?;
+
+    let env_manager = env_manager_from_args(args)?;
+    let mut cmd = match &env_manager {
+        EnvManager::Micromamba(micromamba) => {
+            if upgrade
+                || no_deps
+                || require_hashes
+                || index_url.is_some()
+                || extra_index_url.is_some()
+            {
+                return Err(json!({
+                    "code": -32602,
+                    "message": "upgrade, no_deps, require_hashes and index URLs are pip options, not supported with env_manager=micromamba"
+                }));
+            }
+            // Same channels the env was created with, unless overridden
+            let channels = ManagedEnvMetadata::read(&env_dir)
+                .map(|m| m.channels)
+                .unwrap_or_default();
+            conda_env_from_args(micromamba, &env_dir, args, &channels)?.install_command(&[])
+        }
+        _ => {
+            let mut cmd = env_manager.pip_command(&python_path, "install");
+            if upgrade {
+                cmd.arg("--upgrade");
+            }
+            if no_deps {
+                cmd.arg("--no-deps");
+            }
+            if require_hashes {
+                cmd.arg("--require-hashes");
+            }
+            if let Some(index_url) = &index_url {
+                cmd.arg("--index-url").arg(index_url);
+            }
+            if let Some(extra_index_url) = &extra_index_url {
+                cmd.arg("--extra-index-url").arg(extra_index_url);
+            }
+            cmd
+        }
+    };
     if let Some(req) = requirements_file {
         let req_path = resolve_maybe_relative(None, Path::new(&req));
-        cmd.arg("-r").arg(req_path);
+        let flag = match env_manager {
+            EnvManager::Micromamba(_) => "--file",
+            _ => "-r",
+        };
+        cmd.arg(flag).arg(req_path);
     }
     for package in &package_targets {
         cmd.arg(package);
@@ -988,6 +2012,7 @@ async fn python_env_install_call(args: &Value) -> std::result::Result<Value, Val
                 "env_dir": env_dir,
                 "python_path": python_path,
                 "ok": false,
+                "env_manager": env_manager.name(),
                 "command_args": args_for_result,
                 "stdout": stdout,
                 "stderr": stderr
@@ -996,7 +2021,7 @@ async fn python_env_install_call(args: &Value) -> std::result::Result<Value, Val
         }));
     }
 
-    let metadata = read_env_metadata(&env_dir);
+    let metadata = ManagedEnvMetadata::read(&env_dir);
     Ok(json!({
         "content": [{ "type": "text", "text": format!("Installed dependencies in env '{}'", alias) }],
         "structuredContent": {
@@ -1004,6 +2029,7 @@ async fn python_env_install_call(args: &Value) -> std::result::Result<Value, Val
             "env_dir": env_dir,
             "python_path": python_path,
             "ok": true,
+            "env_manager": env_manager.name(),
             "command_args": args_for_result,
             "stdout": stdout,
             "stderr": stderr,
@@ -1013,6 +2039,709 @@ async fn python_env_install_call(args: &Value) -> std::result::Result<Value, Val
     }))
 }
 
+/// Package install limits of the enterprise policy, if any
+fn package_install_policy(cfg: &WorkerConfig) -> PackageInstallPolicy {
+    cfg.enterprise_policy
+        .as_ref()
+        .map(|e| e.package_install.clone())
+        .unwrap_or_default()
+}
+
+/// Refuse install targets the enterprise package policy doesn't allow,
+/// including those listed in a requirements file
+fn check_install_targets(
+    cfg: &WorkerConfig,
+    policy: &PackageInstallPolicy,
+    tool: &str,
+    targets: &[String],
+    requirements_file: Option<&str>,
+) -> std::result::Result<(), Value> {
+    let requirements = match requirements_file {
+        Some(path) if policy.is_restricted() => {
+            let path = resolve_maybe_relative(None, Path::new(path));
+            std::fs::read_to_string(&path).map_err(|e| {
+                json!({
+                    "code": -32602,
+                    "message": format!("cannot read requirements_file {}: {}", path.display(), e)
+                })
+            })?
+        }
+        _ => String::new(),
+    };
+    targets
+        .iter()
+        .try_for_each(|target| policy.check_target(target))
+        .and_then(|()| policy.check_requirements(&requirements))
+        .map_err(|message| install_refused(cfg, tool, &message))
+}
+
+/// Refuse package index and channel overrides while the policy restricts
+/// installs, as an allowlisted name could otherwise come from any index
+fn check_install_sources(
+    cfg: &WorkerConfig,
+    policy: &PackageInstallPolicy,
+    tool: &str,
+    overrides: &[(&str, bool)],
+) -> std::result::Result<(), Value> {
+    if !policy.is_restricted() {
+        return Ok(());
+    }
+    match overrides.iter().find(|(_, given)| *given) {
+        Some((name, _)) => Err(install_refused(
+            cfg,
+            tool,
+            &format!("'{}' is not allowed while installs are restricted", name),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn install_refused(cfg: &WorkerConfig, tool: &str, message: &str) -> Value {
+    json!({
+        "code": -32602,
+        "message": format!("{} refused: {}", tool, message),
+        "data": {
+            "policy_message": cfg.enterprise_policy.as_ref().and_then(|e| e.policy_message.clone())
+        }
+    })
+}
+
+async fn python_env_info_call(args: &Value) -> std::result::Result<Value, Value> {
+    let alias = env_alias_from_args(args)?;
+    let (env_dir, python_path) = resolve_existing_managed_env(&alias)?;
+
+    // importlib.metadata works without pip, so --without-pip envs report too
+    let mut probe_cmd = Command::new(&python_path);
+    probe_cmd.arg("-c").arg(
+        "import importlib.metadata as m, json, sys; \
+         print(json.dumps({'version': sys.version.split()[0], \
+         'packages': len({d.metadata['Name'] for d in m.distributions()})}))",
+    );
+    let probe_output = run_cmd_capture(&mut probe_cmd, 60).await?;
+    if !probe_output.status.success() {
+        return Err(json!({
+            "code": -32000,
+            "message": format!("Failed to inspect managed env '{}'", alias),
+            "data": {
+                "stderr": String::from_utf8_lossy(&probe_output.stderr),
+                "python_path": python_path
+            }
+        }));
+    }
+    let probe: Value = serde_json::from_slice(&probe_output.stdout).unwrap_or_default();
+    let python_version = probe.get("version").cloned().unwrap_or(Value::Null);
+    let package_count = probe.get("packages").cloned().unwrap_or(Value::Null);
+    let disk_usage_bytes = envs::disk_usage(&env_dir);
+    let metadata = ManagedEnvMetadata::read(&env_dir);
+
+    Ok(json!({
+        "content": [{
+            "type": "text",
+            "text": format!(
+                "Managed env '{}': Python {}, {} package(s), {} bytes",
+                alias,
+                python_version.as_str().unwrap_or("unknown"),
+                package_count,
+                disk_usage_bytes
+            )
+        }],
+        "structuredContent": {
+            "alias": alias,
+            "env_dir": env_dir,
+            "python_path": python_path,
+            "python_version": python_version,
+            "package_count": package_count,
+            "disk_usage_bytes": disk_usage_bytes,
+            "metadata": metadata,
+        },
+        "isError": false
+    }))
+}
+
+/// Micromamba env at `env_dir` with the `channels` argument, else
+/// `default_channels`
+fn conda_env_from_args(
+    micromamba: &Path,
+    env_dir: &Path,
+    args: &Value,
+    default_channels: &[String],
+) -> std::result::Result<CondaEnv, Value> {
+    let mut channels = parse_string_list(args, "channels")?;
+    if channels.is_empty() {
+        channels = default_channels.to_vec();
+    }
+    Ok(channels.iter().fold(
+        CondaEnv::with_micromamba(micromamba, env_dir),
+        |env, channel| env.channel(channel),
+    ))
+}
+
+async fn python_env_freeze_call(args: &Value) -> std::result::Result<Value, Value> {
+    let alias = env_alias_from_args(args)?;
+    let (env_dir, python_path) = resolve_existing_managed_env(&alias)?;
+
+    let env_manager = env_manager_from_args(args)?;
+    let output = run_pip_freeze(&env_manager, &python_path).await?;
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        return Ok(json!({
+            "content": [{ "type": "text", "text": format!("pip freeze failed for env '{}'", alias) }],
+            "structuredContent": {
+                "alias": alias,
+                "env_dir": env_dir,
+                "python_path": python_path,
+                "ok": false,
+                "stderr": stderr
+            },
+            "isError": true
+        }));
+    }
+
+    let requirements = String::from_utf8_lossy(&output.stdout).to_string();
+    let lockfile = env_dir.join(envs::LOCKFILE_NAME);
+    std::fs::write(&lockfile, &requirements).map_err(|e| {
+        json!({
+            "code": -32000,
+            "message": format!("Failed to write lockfile: {}", e),
+            "data": { "alias": alias, "lockfile": lockfile }
+        })
+    })?;
+    let packages = parse_frozen_requirements(&requirements);
+
+    Ok(json!({
+        "content": [{
+            "type": "text",
+            "text": format!("Froze {} package(s) in env '{}' to {}", packages.len(), alias, lockfile.display())
+        }],
+        "structuredContent": {
+            "alias": alias,
+            "env_dir": env_dir,
+            "python_path": python_path,
+            "ok": true,
+            "lockfile": lockfile,
+            "requirements": requirements,
+            "packages": packages,
+            "metadata": ManagedEnvMetadata::read(&env_dir),
+        },
+        "isError": false
+    }))
+}
+
+async fn python_env_audit_call(args: &Value) -> std::result::Result<Value, Value> {
+    let alias = env_alias_from_args(args)?;
+    let (env_dir, python_path) = resolve_existing_managed_env(&alias)?;
+
+    let mut audit = EnvironmentAudit::new(&python_path);
+    if let Some(service) = parse_optional_string(args, "service") {
+        if !matches!(service.as_str(), "pypi" | "osv") {
+            return Err(json!({
+                "code": -32602,
+                "message": format!("unknown vulnerability service '{}' (expected pypi or osv)", service)
+            }));
+        }
+        audit = audit.service(service);
+    }
+    let report = match tokio::time::timeout(
+        std::time::Duration::from_secs(ENV_TOOL_TIMEOUT_SECS),
+        audit.run(),
+    )
+    .await
+    {
+        Ok(Ok(report)) => report,
+        Ok(Err(e)) => {
+            return Err(json!({
+                "code": -32000,
+                "message": format!("Failed to audit managed env '{}': {}", alias, e),
+                "data": {
+                    "python_path": python_path,
+                    "hint": format!("Install pip-audit or set {}.", pip_audit::PIP_AUDIT_ENV)
+                }
+            }))
+        }
+        Err(_) => {
+            return Err(json!({
+                "code": -32000,
+                "message": format!("Auditing managed env '{}' timed out", alias)
+            }))
+        }
+    };
+
+    let text = if report.is_vulnerable() {
+        let findings: Vec<String> = report
+            .vulnerabilities
+            .iter()
+            .map(|v| {
+                let fix = if v.fix_versions.is_empty() {
+                    "no fix yet".to_string()
+                } else {
+                    format!("fixed in {}", v.fix_versions.join(", "))
+                };
+                format!("{} {}: {} ({})", v.package, v.version, v.id, fix)
+            })
+            .collect();
+        format!(
+            "Env '{}' has {} known vulnerabilit{}:\n{}",
+            alias,
+            findings.len(),
+            if findings.len() == 1 { "y" } else { "ies" },
+            findings.join("\n")
+        )
+    } else {
+        format!(
+            "No known vulnerabilities in env '{}' ({} package(s) audited)",
+            alias, report.packages
+        )
+    };
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "structuredContent": {
+            "alias": alias,
+            "env_dir": env_dir,
+            "python_path": python_path,
+            "vulnerable": report.is_vulnerable(),
+            "packages": report.packages,
+            "vulnerabilities": report.vulnerabilities,
+            "skipped": report.skipped,
+        },
+        "isError": false
+    }))
+}
+
+async fn python_env_sbom_call(args: &Value) -> std::result::Result<Value, Value> {
+    let alias = env_alias_from_args(args)?;
+    let (env_dir, python_path) = resolve_existing_managed_env(&alias)?;
+    let format = match parse_optional_string(args, "format") {
+        Some(format) => SbomFormat::parse(&format).ok_or_else(|| {
+            json!({
+                "code": -32602,
+                "message": format!("unknown SBOM format '{}' (expected cyclonedx or spdx)", format)
+            })
+        })?,
+        None => SbomFormat::default(),
+    };
+
+    let packages = sbom::installed_packages(&python_path).await.map_err(|e| {
+        json!({
+            "code": -32000,
+            "message": format!("Failed to inspect managed env '{}': {}", alias, e),
+            "data": { "python_path": python_path }
+        })
+    })?;
+    let document = sbom::document(format, &alias, &packages);
+    let sbom_path = env_dir.join(format.file_name());
+    let written = serde_json::to_vec_pretty(&document)
+        .map_err(std::io::Error::from)
+        .and_then(|bytes| std::fs::write(&sbom_path, bytes));
+    if let Err(e) = written {
+        return Err(json!({
+            "code": -32000,
+            "message": format!("Failed to write SBOM: {}", e),
+            "data": { "alias": alias, "sbom_path": sbom_path }
+        }));
+    }
+
+    Ok(json!({
+        "content": [{
+            "type": "text",
+            "text": format!("Wrote an SBOM of {} package(s) in env '{}' to {}", packages.len(), alias, sbom_path.display())
+        }],
+        "structuredContent": {
+            "alias": alias,
+            "env_dir": env_dir,
+            "python_path": python_path,
+            "format": format,
+            "sbom_path": sbom_path,
+            "package_count": packages.len(),
+            "document": document,
+        },
+        "isError": false
+    }))
+}
+
+async fn run_pip_freeze(
+    env_manager: &EnvManager,
+    python_path: &Path,
+) -> std::result::Result<std::process::Output, Value> {
+    let mut freeze_cmd = env_manager.pip_command(python_path, "freeze");
+    run_cmd_capture(&mut freeze_cmd, ENV_TOOL_TIMEOUT_SECS).await
+}
+
+async fn python_env_clone_call(
+    cfg: &WorkerConfig,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
+    let source = args
+        .get("source")
+        .or_else(|| args.get("source_alias"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| json!({ "code": -32602, "message": "Missing required argument: source" }))
+        .and_then(validate_env_alias)?;
+    let alias = env_alias_from_args(args)?;
+    let (source_dir, source_python) = resolve_existing_managed_env(&source)?;
+    let target_dir = ManagedEnvRegistry::from_env()
+        .env_dir(&alias)
+        .map_err(env_error)?;
+    if target_dir.exists() {
+        return Err(json!({
+            "code": -32602,
+            "message": format!("Managed env '{}' already exists", alias),
+            "data": { "alias": alias, "env_dir": target_dir }
+        }));
+    }
+
+    let env_manager = env_manager_from_args(args)?;
+    let freeze = run_pip_freeze(&env_manager, &source_python).await?;
+    if !freeze.status.success() {
+        return Err(json!({
+            "code": -32000,
+            "message": format!("pip freeze failed for env '{}'", source),
+            "data": { "stderr": String::from_utf8_lossy(&freeze.stderr) }
+        }));
+    }
+    let requirements = String::from_utf8_lossy(&freeze.stdout).to_string();
+
+    // Same base interpreter as the source, so the pins resolve the same way
+    let mut create_args = json!({ "alias": alias, "env_manager": env_manager.name() });
+    if let Some(base_python) = ManagedEnvMetadata::read(&source_dir).and_then(|m| m.base_python) {
+        create_args["python_path"] = json!(base_python);
+    }
+    python_env_create_call(cfg, &create_args).await?;
+    // Don't leave a half-built clone behind, also when the call is cancelled
+    let unfinished = envs::UnfinishedEnv::new(&target_dir);
+    let python_path = envs::python_path(&target_dir);
+
+    let lockfile = target_dir.join(envs::LOCKFILE_NAME);
+    let install = async {
+        std::fs::write(&lockfile, &requirements).map_err(|e| {
+            json!({
+                "code": -32000,
+                "message": format!("Failed to write lockfile: {}", e)
+            })
+        })?;
+        if parse_frozen_requirements(&requirements).is_empty() {
+            return Ok(None);
+        }
+        let mut install_cmd = env_manager.pip_command(&python_path, "install");
+        install_cmd.arg("-r").arg(&lockfile);
+        run_cmd_capture(&mut install_cmd, ENV_TOOL_TIMEOUT_SECS)
+            .await
+            .map(Some)
+    };
+    let (stdout, stderr, error) = match install.await {
+        Ok(None) => (String::new(), String::new(), None),
+        Ok(Some(output)) => (
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+            (!output.status.success()).then(|| json!({ "message": "pip install failed" })),
+        ),
+        Err(e) => (String::new(), String::new(), Some(e)),
+    };
+    if let Some(error) = error {
+        return Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": format!("Failed to install '{}' packages into clone '{}'", source, alias)
+            }],
+            "structuredContent": {
+                "source": source,
+                "alias": alias,
+                "ok": false,
+                "error": error,
+                "stdout": stdout,
+                "stderr": stderr,
+            },
+            "isError": true
+        }));
+    }
+
+    unfinished.finish();
+    Ok(json!({
+        "content": [{
+            "type": "text",
+            "text": format!("Cloned managed env '{}' to '{}'", source, alias)
+        }],
+        "structuredContent": {
+            "source": source,
+            "alias": alias,
+            "env_dir": target_dir,
+            "python_path": python_path,
+            "ok": true,
+            "env_manager": env_manager.name(),
+            "lockfile": lockfile,
+            "requirements": requirements,
+            "stdout": stdout,
+            "stderr": stderr,
+            "metadata": ManagedEnvMetadata::read(&target_dir),
+        },
+        "isError": false
+    }))
+}
+
+/// One entry per requirement line of `pip freeze` output: `name==version`
+/// pins become `{name, version}`, `name @ url` and editable installs keep the
+/// raw line as `requirement`
+fn parse_frozen_requirements(requirements: &str) -> Vec<Value> {
+    requirements
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if let Some((name, version)) = line.split_once("==") {
+                json!({ "name": name.trim(), "version": version.trim() })
+            } else if let Some((name, _)) = line.split_once(" @ ") {
+                json!({ "name": name.trim(), "requirement": line })
+            } else {
+                json!({ "requirement": line })
+            }
+        })
+        .collect()
+}
+
+fn workspace_janitor(cfg: &WorkerConfig) -> WorkspaceJanitor {
+    WorkspaceJanitor::new(pysandbox::default_workspace_base())
+        .with_ttl(cfg.workspace_ttl)
+        .with_max_total_bytes(cfg.workspace_max_bytes)
+}
+
+async fn python_workspaces_gc_call(
+    cfg: &WorkerConfig,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
+    let mut janitor = workspace_janitor(cfg);
+    if let Some(ttl) = args.get("ttl_seconds").and_then(|v| v.as_u64()) {
+        janitor = janitor.with_ttl((ttl > 0).then(|| std::time::Duration::from_secs(ttl)));
+    }
+    if let Some(mb) = args.get("max_total_mb").and_then(|v| v.as_u64()) {
+        janitor = janitor.with_max_total_bytes(Some(mb * 1024 * 1024));
+    }
+    let dry_run = args
+        .get("dry_run")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let report = tokio::task::spawn_blocking(move || {
+        if dry_run {
+            janitor.preview()
+        } else {
+            janitor.sweep()
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|report| report.map_err(|e| e.to_string()))
+    .map_err(|e| {
+        json!({
+            "code": -32000,
+            "message": format!("Failed to clean up workspaces: {}", e)
+        })
+    })?;
+    let text = format!(
+        "{} {} workspaces ({} bytes), {} bytes remain",
+        if dry_run { "Would remove" } else { "Removed" },
+        report.removed.len(),
+        report.freed_bytes,
+        report.remaining_bytes
+    );
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "structuredContent": {
+            "dry_run": dry_run,
+            "removed": report.removed,
+            "freed_bytes": report.freed_bytes,
+            "remaining_bytes": report.remaining_bytes,
+        },
+        "isError": false
+    }))
+}
+
+async fn python_env_prune_call(args: &Value) -> std::result::Result<Value, Value> {
+    let days = args
+        .get("days")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| json!({ "code": -32602, "message": "Missing required argument: days" }))?;
+    let dry_run = parse_bool(args, "dry_run", false);
+    let unused_for = std::time::Duration::from_secs(days * 24 * 60 * 60);
+
+    let registry = ManagedEnvRegistry::from_env();
+    let envs_dir = registry.base_dir().to_path_buf();
+    let report = tokio::task::spawn_blocking(move || registry.prune(unused_for, dry_run))
+        .await
+        .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?
+        .map_err(|e| env_error_with_data(e, json!({ "envs_dir": envs_dir })))?;
+    let aliases: Vec<&str> = report
+        .removed
+        .iter()
+        .map(|env| env.alias.as_str())
+        .collect();
+    let text = format!(
+        "{} {} managed env(s) unused for {} day(s) ({} bytes)",
+        if dry_run { "Would delete" } else { "Deleted" },
+        aliases.len(),
+        days,
+        report.freed_bytes
+    );
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "structuredContent": {
+            "dry_run": dry_run,
+            "removed": aliases,
+            "freed_bytes": report.freed_bytes,
+        },
+        "isError": false
+    }))
+}
+
+async fn python_runtime_provision_call(args: &Value) -> std::result::Result<Value, Value> {
+    let mut runtime = StandaloneRuntime::from_env();
+    if let Some(version) = parse_optional_string(args, "python_version") {
+        runtime = runtime.python_version(version);
+    }
+    if let Some(release) = parse_optional_string(args, "release") {
+        runtime = runtime.release(release);
+    }
+    if let Some(sha256) = parse_optional_string(args, "sha256") {
+        runtime = runtime.sha256(sha256);
+    }
+    let force = parse_bool(args, "force", false);
+
+    let provisioned = tokio::time::timeout(
+        std::time::Duration::from_secs(ENV_TOOL_TIMEOUT_SECS),
+        runtime.provision(force),
+    )
+    .await
+    .map_err(|_| {
+        json!({
+            "code": -32000,
+            "message": format!("Process timed out after {}s", ENV_TOOL_TIMEOUT_SECS)
+        })
+    })?
+    .map_err(|e| env_error_with_data(e, json!({ "runtime_dir": runtime.base_dir() })))?;
+    Ok(json!({
+        "content": [{
+            "type": "text",
+            "text": format!(
+                "Python {} ({}) provisioned at {}",
+                provisioned.python_version,
+                provisioned.target,
+                provisioned.python_path.display()
+            )
+        }],
+        "structuredContent": provisioned,
+        "isError": false
+    }))
+}
+
+async fn python_runtime_discover_call(
+    cfg: &WorkerConfig,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
+    let mut discovery = PythonDiscovery::new();
+    if let Some(min_version) = parse_optional_string(args, "min_version") {
+        discovery = discovery.min_version(min_version);
+    }
+    if let Some(path) = cfg.plugin_dir.as_deref().and_then(bundled_python_path) {
+        discovery = discovery.bundled(path);
+    }
+    let interpreters = discovery.run().await;
+
+    // What each runtime setting picks, or why it can't
+    let mut resolution = json!({ "configured": cfg.python_runtime.name() });
+    for runtime in [
+        PythonRuntime::Auto,
+        PythonRuntime::Bundled,
+        PythonRuntime::System,
+    ] {
+        resolution[runtime.name()] = match resolve_python_path(cfg, runtime, None) {
+            Ok((_, resolved)) => resolved,
+            Err(error) => json!({ "error": error }),
+        };
+    }
+    let usable = interpreters.iter().filter(|p| p.usable).count();
+    Ok(json!({
+        "content": [{
+            "type": "text",
+            "text": format!(
+                "{} Python interpreter(s) found, {} usable",
+                interpreters.len(),
+                usable
+            )
+        }],
+        "structuredContent": {
+            "interpreters": interpreters,
+            "resolution": resolution,
+        },
+        "isError": false
+    }))
+}
+
+async fn python_env_delete_call(args: &Value) -> std::result::Result<Value, Value> {
+    let alias = env_alias_from_args(args)?;
+    let registry = ManagedEnvRegistry::from_env();
+    let env_dir = registry.delete(&alias).map_err(|e| {
+        env_error_with_data(
+            e,
+            json!({ "alias": alias, "env_dir": registry.base_dir().join(&alias) }),
+        )
+    })?;
+    Ok(json!({
+        "content": [{ "type": "text", "text": format!("Deleted managed env '{}'", alias) }],
+        "structuredContent": {
+            "alias": alias,
+            "env_dir": env_dir,
+            "deleted": true,
+        },
+        "isError": false
+    }))
+}
+
+/// Interpreter chosen for a call from its runtime, path and env arguments
+struct PythonSelection {
+    managed_env_alias: Option<String>,
+    runtime: PythonRuntime,
+    /// `None` means Python from PATH
+    path: Option<PathBuf>,
+    resolution: Value,
+}
+
+fn select_python(
+    cfg: &WorkerConfig,
+    args: &Value,
+    policy_id: &str,
+) -> std::result::Result<PythonSelection, Value> {
+    let managed_env_alias = parse_python_env_alias(args)?;
+    if managed_env_alias.is_some() && policy_id != "yolo" {
+        return Err(json!({
+            "code": -32602,
+            "message": "python_env is only allowed with policy_id=yolo",
+            "data": { "policy_id": policy_id }
+        }));
+    }
+
+    let runtime = effective_python_runtime(cfg, args, policy_id);
+    let python_path_override = parse_optional_string(args, "python_path")
+        .or_else(|| parse_optional_string(args, "pythonPath"));
+    let (path, resolution) = if let Some(alias) = managed_env_alias.as_ref() {
+        let (env_dir, python_path) = resolve_existing_managed_env(alias)?;
+        (
+            Some(python_path.clone()),
+            json!({
+                "kind": "managed_env",
+                "alias": alias,
+                "env_dir": env_dir,
+                "path": python_path
+            }),
+        )
+    } else {
+        resolve_python_path(cfg, runtime, python_path_override.as_deref())?
+    };
+    Ok(PythonSelection {
+        managed_env_alias,
+        runtime,
+        path,
+        resolution,
+    })
+}
+
 fn resolve_python_path(
     cfg: &WorkerConfig,
     runtime: PythonRuntime,
@@ -1033,11 +2762,17 @@ fn resolve_python_path(
         ));
     }
 
-    // 2) Resolve bundled python from plugin dir/resources.
+    // 2) Resolve bundled python from plugin dir/resources, else one
+    // provisioned with python_runtime.provision.
     let bundled = cfg
         .plugin_dir
         .as_deref()
-        .and_then(|dir| bundled_python_path(dir));
+        .and_then(bundled_python_path)
+        .or_else(|| {
+            StandaloneRuntime::from_env()
+                .installed()
+                .map(|r| r.python_path)
+        });
 
     match runtime {
         PythonRuntime::System => Ok((None, json!({ "kind": "system" }))),
@@ -1045,7 +2780,7 @@ fn resolve_python_path(
             let Some(path) = bundled else {
                 return Err(json!({
                     "code": -32000,
-                    "message": "Bundled Python not found (resources/python/...).",
+                    "message": "Bundled Python not found (resources/python/...); run python_runtime.provision to download one.",
                     "data": { "plugin_dir": cfg.plugin_dir }
                 }));
             };
@@ -1101,7 +2836,10 @@ fn resolve_maybe_relative(base: Option<&Path>, p: &Path) -> PathBuf {
 
 async fn python_sandbox_call(
     cfg: &WorkerConfig,
+    resources: &Resources,
     args: &Value,
+    progress: Option<Progress>,
+    cancel: CancellationToken,
 ) -> std::result::Result<Value, Value> {
     let code = args
         .get("code")
@@ -1110,6 +2848,7 @@ async fn python_sandbox_call(
     let inputs = args.get("inputs").cloned().unwrap_or_else(|| json!({}));
 
     let policy_id = policy_id_from_args(args);
+    check_enterprise_policy(cfg, &policy_id)?;
     let security_profile = map_policy_to_profile(&policy_id);
     let execution_mode = execution_mode_from_args(args, &policy_id);
 
@@ -1119,49 +2858,92 @@ async fn python_sandbox_call(
         .unwrap_or(30)
         .clamp(1, 600);
 
-    let managed_env_alias = parse_python_env_alias(args)?;
-    if managed_env_alias.is_some() && policy_id != "yolo" {
-        return Err(json!({
-            "code": -32602,
-            "message": "python_env is only allowed with policy_id=yolo",
-            "data": { "policy_id": policy_id }
-        }));
-    }
-
-    let runtime = effective_python_runtime(cfg, args, &policy_id);
-    let python_path_override = parse_optional_string(args, "python_path")
-        .or_else(|| parse_optional_string(args, "pythonPath"));
-    let (python_path_opt, python_resolution) = if let Some(alias) = managed_env_alias.as_ref() {
-        let (env_dir, python_path) = resolve_existing_managed_env(alias)?;
-        (
-            Some(python_path.clone()),
-            json!({
-                "kind": "managed_env",
-                "alias": alias,
-                "env_dir": env_dir,
-                "path": python_path
-            }),
-        )
-    } else {
-        resolve_python_path(cfg, runtime, python_path_override.as_deref())?
-    };
+    let PythonSelection {
+        managed_env_alias,
+        runtime,
+        path: python_path_opt,
+        resolution: python_resolution,
+    } = select_python(cfg, args, &policy_id)?;
+    record_env_use(managed_env_alias.as_deref());
     let network_allowlist = parse_network_allowlist(args)?;
-
+    let input_files = parse_input_files(args)?;
+    let mut missing_packages = Vec::new();
+    let mut auto_installed = false;
+    if let (Some(alias), Some(python_path)) = (&managed_env_alias, &python_path_opt) {
+        if parse_bool(args, "preflight", true) {
+            missing_packages = missing_packages_for(python_path, code).await?;
+        }
+        if !missing_packages.is_empty() {
+            if !parse_bool(args, "auto_install", false) {
+                let names: Vec<&str> = missing_packages
+                    .iter()
+                    .filter_map(|m| m["package"].as_str())
+                    .collect();
+                return Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": format!(
+                            "error: python env '{}' is missing packages: {}. Install them with python_env.install or pass auto_install=true.",
+                            alias,
+                            names.join(", ")
+                        )
+                    }],
+                    "structuredContent": {
+                        "policy_id": policy_id,
+                        "python_env": alias,
+                        "python": python_resolution,
+                        "missing_packages": missing_packages,
+                        "error": "missing packages"
+                    },
+                    "isError": true
+                }));
+            }
+            let mut install_args = json!({
+                "alias": alias,
+                "packages": missing_packages.iter().map(|m| m["package"].clone()).collect::<Vec<_>>(),
+            });
+            if let Some(manager) = args.get("env_manager").or_else(|| args.get("envManager")) {
+                install_args["env_manager"] = manager.clone();
+            }
+            let installed = python_env_install_call(cfg, &install_args).await?;
+            if installed["isError"] == json!(true) {
+                let mut installed = installed;
+                installed["structuredContent"]["missing_packages"] = json!(missing_packages);
+                return Ok(installed);
+            }
+            auto_installed = true;
+        }
+    }
     let limits = security_profile.resource_limits();
 
+    // Platform-sandboxed runs without a configured .sb file get a profile
+    // generated from the policy template.
+    let platform_policy = (execution_mode == ExecutionMode::PlatformSandboxed).then(|| {
+        let mut policy = map_policy_to_sandbox_policy(&policy_id);
+        if let Some(hosts) = &network_allowlist {
+            policy.network = NetworkPolicy::AllowList(hosts.clone());
+        }
+        policy
+    });
+
+    // Native runs still get the policy's path rules
+    let filesystem = map_policy_to_sandbox_policy(&policy_id).filesystem;
     let engine: Box<dyn PythonEngine> = match (execution_mode, python_path_opt) {
         (ExecutionMode::Native, Some(p)) => Box::new(
             NativePythonEngine::with_python_path_and_limits(p, limits.clone())
-                .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?,
+                .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?
+                .with_filesystem_policy(filesystem),
         ),
         (ExecutionMode::Native, None) => Box::new(
             NativePythonEngine::with_limits(limits.clone())
-                .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?,
+                .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?
+                .with_filesystem_policy(filesystem),
         ),
         (ExecutionMode::WorkspaceIsolated | ExecutionMode::PlatformSandboxed, Some(p)) => {
             let config = SandboxConfig {
                 python_path: p,
                 sandbox_profile: cfg.sandbox_profile_path.clone(),
+                policy: platform_policy.clone(),
                 limits: limits.clone(),
                 ..Default::default()
             };
@@ -1184,6 +2966,7 @@ async fn python_sandbox_call(
             let config = SandboxConfig {
                 python_path: sys_path,
                 sandbox_profile: cfg.sandbox_profile_path.clone(),
+                policy: platform_policy.clone(),
                 limits: limits.clone(),
                 ..Default::default()
             };
@@ -1195,21 +2978,59 @@ async fn python_sandbox_call(
     };
 
     let sandbox = PythonSandbox::new(vec![engine]);
-    let options = ExecutionOptions {
+    let mut options = ExecutionOptions {
         timeout: std::time::Duration::from_secs(timeout_seconds),
         import_policy: security_profile.to_import_policy(),
         network_allowlist: network_allowlist.clone(),
+        block_native_extensions: security_profile.blocks_native_extensions(),
         env_vars: HashMap::new(),
+        input_files,
+        cancellation: Some(cancel),
         ..Default::default()
     };
 
+    // Stream output chunks and heartbeats as progress notifications
+    let forwarder = progress.map(|progress| {
+        let (events, mut receiver) = event_channel();
+        options.events = Some(events);
+        tokio::spawn(async move {
+            let started = std::time::Instant::now();
+            while let Some(event) = receiver.recv().await {
+                // One clock for both, so `progress` keeps increasing
+                let elapsed = started.elapsed().as_secs_f64();
+                let notification = match &event {
+                    ExecutionEvent::OutputChunk { data, .. } => {
+                        progress_notification(&progress.token, elapsed, Some(data))
+                    }
+                    ExecutionEvent::Heartbeat { .. } => {
+                        progress_notification(&progress.token, elapsed, None)
+                    }
+                    _ => continue,
+                };
+                if let Err(e) = progress.outbox.send(&notification).await {
+                    tracing::warn!("failed to send progress notification: {}", e);
+                    break;
+                }
+            }
+        })
+    });
+
     let exec = sandbox.execute(code, inputs, options).await;
+    // Every notification goes out before the response
+    if let Some(forwarder) = forwarder {
+        let _ = forwarder.await;
+    }
 
     match exec {
         Ok(payload) => {
-            let summary = summarize_payload(&payload);
+            let mut content = vec![json!({ "type": "text", "text": summarize_payload(&payload) })];
+            for resource in resources.register(&payload).await {
+                let mut link = resource.to_json();
+                link["type"] = json!("resource_link");
+                content.push(link);
+            }
             Ok(json!({
-                "content": [{ "type": "text", "text": summary }],
+                "content": content,
                 "structuredContent": {
                     "policy_id": policy_id,
                     "security_profile": format!("{:?}", security_profile).to_ascii_lowercase(),
@@ -1218,6 +3039,8 @@ async fn python_sandbox_call(
                     "runtime": format!("{:?}", runtime).to_ascii_lowercase(),
                     "python_env": managed_env_alias.clone(),
                     "network_allowlist": network_allowlist,
+                    "missing_packages": missing_packages,
+                    "auto_installed": auto_installed,
                     "output": payload
                 },
                 "metadata": {
@@ -1235,23 +3058,308 @@ async fn python_sandbox_call(
             "structuredContent": {
                 "policy_id": policy_id,
                 "python": python_resolution,
-                "error": e.to_string()
+                "error": e.to_string(),
+                "exception": e.python_exception(),
+                "policy_denied": e.is_policy_denial()
+            },
+            "isError": true
+        })),
+    }
+}
+
+async fn python_sandbox_validate_call(
+    cfg: &WorkerConfig,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
+    let code = args
+        .get("code")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| json!({ "code": -32602, "message": "Missing required argument: code" }))?;
+    let policy_id = policy_id_from_args(args);
+    let security_profile = map_policy_to_profile(&policy_id);
+    let PythonSelection {
+        path: python_path_opt,
+        resolution: python_resolution,
+        ..
+    } = select_python(cfg, args, &policy_id)?;
+    let network_allowlist = parse_network_allowlist(args)?;
+
+    // Every mode applies the same checks before running, so a native engine
+    // on the selected interpreter validates for all of them
+    let limits = security_profile.resource_limits();
+    let engine = match python_path_opt {
+        Some(p) => NativePythonEngine::with_python_path_and_limits(p, limits),
+        None => NativePythonEngine::with_limits(limits),
+    }
+    .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?;
+    let options = ExecutionOptions {
+        import_policy: security_profile.to_import_policy(),
+        network_allowlist: network_allowlist.clone(),
+        block_native_extensions: security_profile.blocks_native_extensions(),
+        ..Default::default()
+    };
+    let mut diagnostics = engine
+        .validate(code, &options)
+        .await
+        .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?;
+    if let Err(e) = check_enterprise_policy(cfg, &policy_id) {
+        diagnostics.push(Diagnostic {
+            rule: "enterprise_policy".to_string(),
+            message: e["message"].as_str().unwrap_or_default().to_string(),
+            line: None,
+            col: None,
+            severity: Severity::Error,
+        });
+    }
+
+    let mut policy = map_policy_to_sandbox_policy(&policy_id);
+    if let Some(hosts) = &network_allowlist {
+        policy.network = NetworkPolicy::AllowList(hosts.clone());
+    }
+    #[cfg(feature = "static-analyzer")]
+    let policy_report = policy.explain(code).ok();
+    #[cfg(not(feature = "static-analyzer"))]
+    let policy_report: Option<()> = None;
+
+    let valid = !diagnostics.iter().any(Diagnostic::is_error);
+    let mut lines = vec![if valid {
+        format!("ok: code passes policy {}", policy_id)
+    } else {
+        format!("invalid: code would be refused by policy {}", policy_id)
+    }];
+    for d in &diagnostics {
+        let location = match (d.line, d.col) {
+            (Some(line), Some(col)) => format!("{}:{}: ", line, col),
+            (Some(line), None) => format!("{}: ", line),
+            _ => String::new(),
+        };
+        lines.push(format!(
+            "{}{:?} [{}] {}",
+            location, d.severity, d.rule, d.message
+        ));
+    }
+
+    Ok(json!({
+        "content": [{ "type": "text", "text": lines.join("\n") }],
+        "structuredContent": {
+            "valid": valid,
+            "policy_id": policy_id,
+            "security_profile": format!("{:?}", security_profile).to_ascii_lowercase(),
+            "python": python_resolution,
+            "diagnostics": diagnostics,
+            "policy_report": policy_report,
+        },
+        "isError": false
+    }))
+}
+
+fn python_sandbox_cancel_call(
+    in_flight: &InFlight,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
+    let request_id = args
+        .get("request_id")
+        .or_else(|| args.get("requestId"))
+        .filter(|id| id.is_string() || id.is_number())
+        .ok_or_else(
+            || json!({ "code": -32602, "message": "Missing required argument: request_id" }),
+        )?;
+    let cancelled = in_flight.cancel(request_id, false);
+    let text = if cancelled {
+        format!("cancelled request {}", request_id)
+    } else {
+        format!("no running request {}", request_id)
+    };
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "structuredContent": { "request_id": request_id, "cancelled": cancelled },
+        "isError": false
+    }))
+}
+
+async fn python_session_start_call(
+    cfg: &WorkerConfig,
+    sessions: &Sessions,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
+    let policy_id = policy_id_from_args(args);
+    check_enterprise_policy(cfg, &policy_id)?;
+    let security_profile = map_policy_to_profile(&policy_id);
+    if execution_mode_from_args(args, &policy_id) == ExecutionMode::PlatformSandboxed {
+        return Err(json!({
+            "code": -32602,
+            "message": "python_session does not support execution_mode=platform_sandboxed",
+            "data": { "policy_id": policy_id }
+        }));
+    }
+    // Held until the session is in the table, so concurrent starts can't
+    // exceed the cap; a failed start gives it back
+    let slot = sessions.reserve().ok_or_else(|| {
+        json!({
+            "code": -32000,
+            "message": format!("Too many open sessions (max {}); close one first", MAX_SESSIONS)
+        })
+    })?;
+
+    let timeout_seconds = args
+        .get("timeout_seconds")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(30)
+        .clamp(1, 600);
+    let PythonSelection {
+        managed_env_alias,
+        runtime,
+        path,
+        resolution,
+    } = select_python(cfg, args, &policy_id)?;
+    record_env_use(managed_env_alias.as_deref());
+    let network_allowlist = parse_network_allowlist(args)?;
+
+    let mut sandbox =
+        PythonSandbox::new(Vec::new()).with_session_limits(security_profile.resource_limits());
+    if let Some(path) = path {
+        sandbox = sandbox.with_session_python(path);
+    }
+    let closed = CancellationToken::new();
+    let options = ExecutionOptions {
+        timeout: std::time::Duration::from_secs(timeout_seconds),
+        import_policy: security_profile.to_import_policy(),
+        network_allowlist: network_allowlist.clone(),
+        block_native_extensions: security_profile.blocks_native_extensions(),
+        env_vars: HashMap::new(),
+        cancellation: Some(closed.clone()),
+        ..Default::default()
+    };
+    let handle = sandbox
+        .create_session(options)
+        .await
+        .map_err(|e| json!({ "code": -32000, "message": e.to_string() }))?;
+
+    let session_id = format!("session-{}", handle.id());
+    slot.fill(
+        session_id.clone(),
+        WorkerSession {
+            handle: Mutex::new(handle),
+            policy_id: policy_id.clone(),
+            closed,
+        },
+    );
+
+    Ok(json!({
+        "content": [{ "type": "text", "text": format!("session started: {}", session_id) }],
+        "structuredContent": {
+            "session_id": session_id,
+            "policy_id": policy_id,
+            "security_profile": format!("{:?}", security_profile).to_ascii_lowercase(),
+            "python": resolution,
+            "runtime": format!("{:?}", runtime).to_ascii_lowercase(),
+            "python_env": managed_env_alias,
+            "network_allowlist": network_allowlist,
+        },
+        "isError": false
+    }))
+}
+
+async fn python_session_exec_call(
+    sessions: &Sessions,
+    args: &Value,
+    cancel: CancellationToken,
+) -> std::result::Result<Value, Value> {
+    let session_id = session_id_from_args(args)?;
+    let code = args
+        .get("code")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| json!({ "code": -32602, "message": "Missing required argument: code" }))?;
+    let inputs = args.get("inputs").cloned().unwrap_or_else(|| json!({}));
+
+    let session = sessions
+        .get(session_id)
+        .ok_or_else(|| unknown_session(session_id))?;
+    let mut handle = session.handle.lock().await;
+    // Closed while this call waited for the previous one
+    if session.closed.is_cancelled() {
+        return Err(unknown_session(session_id));
+    }
+    let exec = handle.execute_cancellable(code, inputs, &cancel).await;
+    let policy_id = session.policy_id.clone();
+    // Timeouts, cancellations and crashes end the session; forget it so its
+    // id is reported as unknown from now on
+    let alive = handle.is_alive();
+    drop(handle);
+    if !alive {
+        session.closed.cancel();
+        sessions.remove(session_id);
+    }
+
+    match exec {
+        Ok(payload) => Ok(json!({
+            "content": [{ "type": "text", "text": summarize_payload(&payload) }],
+            "structuredContent": {
+                "session_id": session_id,
+                "policy_id": policy_id,
+                "output": payload
+            },
+            "isError": false
+        })),
+        Err(e) => Ok(json!({
+            "content": [{ "type": "text", "text": format!("error: {}", e) }],
+            "structuredContent": {
+                "session_id": session_id,
+                "policy_id": policy_id,
+                "session_alive": alive,
+                "error": e.to_string(),
+                "exception": e.python_exception(),
+                "policy_denied": e.is_policy_denial()
             },
             "isError": true
         })),
     }
 }
 
-fn summarize_payload(payload: &Value) -> String {
+async fn python_session_close_call(
+    sessions: &Sessions,
+    args: &Value,
+) -> std::result::Result<Value, Value> {
+    let session_id = session_id_from_args(args)?;
+    let session = sessions
+        .remove(session_id)
+        .ok_or_else(|| unknown_session(session_id))?;
+    // Stops a running call; the interpreter is killed once no call holds
+    // the session any more
+    session.closed.cancel();
+    Ok(json!({
+        "content": [{ "type": "text", "text": format!("session closed: {}", session_id) }],
+        "structuredContent": { "session_id": session_id },
+        "isError": false
+    }))
+}
+
+fn session_id_from_args(args: &Value) -> std::result::Result<&str, Value> {
+    args.get("session_id")
+        .or_else(|| args.get("sessionId"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(
+            || json!({ "code": -32602, "message": "Missing required argument: session_id" }),
+        )
+}
+
+fn unknown_session(session_id: &str) -> Value {
+    json!({
+        "code": -32602,
+        "message": format!("Unknown or closed session: {}", session_id)
+    })
+}
+
+fn summarize_payload(payload: &ExecutionResult) -> String {
     let mut out = String::new();
-    if let Some(stdout) = payload.get("stdout").and_then(|v| v.as_str()) {
+    if let Some(stdout) = payload.stdout.as_deref() {
         if !stdout.trim().is_empty() {
             out.push_str("stdout:\n");
             out.push_str(stdout.trim_end());
             out.push('\n');
         }
     }
-    if let Some(stderr) = payload.get("stderr").and_then(|v| v.as_str()) {
+    if let Some(stderr) = payload.stderr.as_deref() {
         if !stderr.trim().is_empty() {
             if !out.is_empty() {
                 out.push('\n');
@@ -1262,7 +3370,7 @@ fn summarize_payload(payload: &Value) -> String {
         }
     }
     if out.is_empty() {
-        if let Some(result) = payload.get("result") {
+        if let Some(result) = &payload.result {
             out.push_str("result:\n");
             out.push_str(&result.to_string());
         } else {
@@ -1283,6 +3391,11 @@ mod tests {
             python_runtime_explicit: explicit,
             python_path_override: None,
             sandbox_profile_path: None,
+            max_concurrency: 1,
+            queue_timeout: std::time::Duration::from_millis(50),
+            enterprise_policy: None,
+            workspace_ttl: None,
+            workspace_max_bytes: None,
         }
     }
 
@@ -1291,6 +3404,7 @@ mod tests {
         assert!(validate_env_alias("team-alpha_1").is_ok());
         assert!(validate_env_alias("bad alias").is_err());
         assert!(validate_env_alias("../escape").is_err());
+        assert!(validate_env_alias("..").is_err());
     }
 
     #[test]
@@ -1308,6 +3422,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn env_manager_prefers_uv_when_available() {
+        let uv = PathBuf::from("/usr/bin/uv");
+        assert_eq!(
+            EnvManager::resolve("auto", Some(uv.clone())).unwrap(),
+            EnvManager::Uv(uv.clone())
+        );
+        assert_eq!(EnvManager::resolve("auto", None).unwrap(), EnvManager::Pip);
+        assert_eq!(
+            EnvManager::resolve("pip", Some(uv)).unwrap(),
+            EnvManager::Pip
+        );
+        assert!(EnvManager::resolve("uv", None).is_err());
+        assert!(EnvManager::resolve("conda", None).is_err());
+    }
+
+    #[test]
+    fn frozen_requirements_are_parsed() {
+        let packages = parse_frozen_requirements(
+            "numpy==2.1.0\n# comment\n\nmypkg @ file:///src/mypkg\n-e git+https://x/y.git#egg=y\n",
+        );
+        assert_eq!(
+            packages,
+            vec![
+                json!({ "name": "numpy", "version": "2.1.0" }),
+                json!({ "name": "mypkg", "requirement": "mypkg @ file:///src/mypkg" }),
+                json!({ "requirement": "-e git+https://x/y.git#egg=y" }),
+            ]
+        );
+    }
+
     #[test]
     fn yolo_auto_defaults_to_system_when_not_explicit() {
         let cfg = mk_cfg(PythonRuntime::Auto, false);
@@ -1322,9 +3467,425 @@ mod tests {
         assert_eq!(runtime, PythonRuntime::Bundled);
     }
 
+    #[test]
+    fn progress_notification_carries_chunk_and_elapsed() {
+        let notification = progress_notification(&json!("tok-1"), 1.5, Some("line\n"));
+        assert_eq!(
+            notification,
+            json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": { "progressToken": "tok-1", "progress": 1.5, "message": "line\n" }
+            })
+        );
+        let heartbeat = progress_notification(&json!(7), 2.0, None);
+        assert!(heartbeat["params"].get("message").is_none());
+    }
+
+    #[tokio::test]
+    async fn session_tools_reject_unknown_ids_and_platform_sandbox() {
+        let cfg = mk_cfg(PythonRuntime::System, false);
+        let sessions = Sessions::default();
+        let err = python_session_exec_call(
+            &sessions,
+            &json!({ "session_id": "session-404", "code": "x = 1" }),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err["code"], -32602);
+        assert!(python_session_close_call(&sessions, &json!({}))
+            .await
+            .is_err());
+        let err = python_session_start_call(
+            &cfg,
+            &sessions,
+            &json!({ "execution_mode": "platform_sandboxed" }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err["message"]
+            .as_str()
+            .unwrap()
+            .contains("platform_sandboxed"));
+    }
+
+    #[test]
+    fn session_slots_are_reserved_up_front() {
+        let sessions = Sessions::default();
+        let slots: Vec<_> = (0..MAX_SESSIONS)
+            .map(|_| sessions.reserve().unwrap())
+            .collect();
+        assert!(sessions.reserve().is_none());
+        drop(slots);
+        assert!(sessions.reserve().is_some());
+        assert_eq!(sessions.lock().starting, 0);
+    }
+
+    #[tokio::test]
+    async fn session_calls_run_concurrently_and_cancel() {
+        let cfg = mk_cfg(PythonRuntime::System, false);
+        let sessions = Sessions::default();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let Ok(started) =
+                python_session_start_call(&cfg, &sessions, &json!({ "policy_id": "yolo" })).await
+            else {
+                return;
+            };
+            ids.push(started["structuredContent"]["session_id"].clone());
+        }
+
+        let slow_args = json!({ "session_id": ids[0], "code": "import time\ntime.sleep(5)" });
+        let slow = python_session_exec_call(&sessions, &slow_args, CancellationToken::new());
+        let fast = async {
+            let started = std::time::Instant::now();
+            let output = python_session_exec_call(
+                &sessions,
+                &json!({ "session_id": ids[1], "code": "result = 2" }),
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(output["structuredContent"]["output"]["result"], 2);
+            let elapsed = started.elapsed();
+            // Closing the slow session stops its running call
+            python_session_close_call(&sessions, &json!({ "session_id": ids[0] }))
+                .await
+                .unwrap();
+            elapsed
+        };
+        let (slow, elapsed) = tokio::join!(slow, fast);
+        assert!(elapsed < std::time::Duration::from_secs(4));
+        assert_eq!(slow.unwrap()["isError"], true);
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            trigger.cancel();
+        });
+        let cancelled = python_session_exec_call(
+            &sessions,
+            &json!({ "session_id": ids[1], "code": "while True: pass" }),
+            cancel,
+        )
+        .await
+        .unwrap();
+        assert_eq!(cancelled["structuredContent"]["session_alive"], false);
+        assert!(sessions.lock().open.is_empty());
+    }
+
+    #[tokio::test]
+    async fn exported_files_are_served_as_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        let export_dir = dir.path().join("ws-1");
+        std::fs::create_dir_all(&export_dir).unwrap();
+        std::fs::write(export_dir.join("data.csv"), "a,b\n1,2\n").unwrap();
+        std::fs::write(export_dir.join("plot.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        let file =
+            |name: &str| json!({ "name": name, "path": export_dir.join(name), "size_bytes": 4 });
+        let payload = ExecutionResult::from_value(json!({
+            "export_dir": export_dir,
+            "exported_files": [file("data.csv"), file("plot.png")]
+        }))
+        .unwrap();
+
+        let resources = Resources::default();
+        let registered = resources.register(&payload).await;
+        assert_eq!(registered[0].uri, "rzn-python://exports/ws-1/data.csv");
+        assert_eq!(resources.list().await.len(), 2);
+
+        let csv = resources
+            .read("rzn-python://exports/ws-1/data.csv")
+            .await
+            .unwrap();
+        assert_eq!(csv["contents"][0]["text"], "a,b\n1,2\n");
+        let png = resources
+            .read("rzn-python://exports/ws-1/plot.png")
+            .await
+            .unwrap();
+        assert_eq!(png["contents"][0]["mimeType"], "image/png");
+        assert_eq!(
+            png["contents"][0]["blob"],
+            b64.encode([0x89, b'P', b'N', b'G'])
+        );
+        assert!(resources
+            .read("rzn-python://exports/ws-1/../../etc/passwd")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn in_flight_requests_can_be_cancelled() {
+        let in_flight = InFlight::default();
+        let tool = in_flight.start(&json!(7));
+        assert!(in_flight.cancel(&json!("7"), false));
+        assert!(tool.is_cancelled());
+        assert!(in_flight.finish(&json!(7)));
+
+        let notified = in_flight.start(&json!("req-2"));
+        assert!(in_flight.cancel(&json!("req-2"), true));
+        assert!(notified.is_cancelled());
+        // Cancelled by notification: the response is dropped
+        assert!(!in_flight.finish(&json!("req-2")));
+        assert!(!in_flight.cancel(&json!(99), false));
+    }
+
+    #[tokio::test]
+    async fn saturated_worker_queues_heavy_tools_only() {
+        let cfg = mk_cfg(PythonRuntime::System, false);
+        let state = WorkerState::new(&cfg);
+        let _busy = state.tool_slots.acquire().await.unwrap();
+
+        let ping = call_tool(
+            &cfg,
+            &state,
+            &json!({ "name": "python_tools.ping" }),
+            None,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ping["content"][0]["text"], "pong");
+
+        let err = call_tool(
+            &cfg,
+            &state,
+            &json!({ "name": "python_sandbox", "arguments": { "code": "x = 1" } }),
+            None,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err["data"]["max_concurrency"], 1);
+    }
+
+    #[tokio::test]
+    async fn policies_list_reports_enterprise_restrictions() {
+        let mut cfg = mk_cfg(PythonRuntime::Auto, false);
+        cfg.enterprise_policy = Some(Arc::new(EnterprisePolicy::financial_services_example()));
+        let listed = sandbox_policies_list_call(&cfg);
+        let policies = listed["structuredContent"]["policies"].as_array().unwrap();
+        let ids: Vec<&str> = policies.iter().filter_map(|p| p["id"].as_str()).collect();
+        assert_eq!(ids, POLICY_IDS);
+
+        let yolo = &policies[0];
+        assert_eq!(yolo["enterprise"]["allowed"], false);
+        let enterprise = &policies[4];
+        assert_eq!(enterprise["enterprise"]["allowed"], true);
+        assert!(enterprise["security_level"].as_u64().unwrap() >= 7);
+
+        let err = python_sandbox_call(
+            &cfg,
+            &Resources::default(),
+            &json!({ "code": "x = 1", "policy_id": "yolo" }),
+            None,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err["code"], -32602);
+    }
+
+    #[cfg(feature = "static-analyzer")]
+    #[tokio::test]
+    async fn restricted_installs_refuse_other_sources_and_create_packages() {
+        let mut cfg = mk_cfg(PythonRuntime::Auto, false);
+        let mut policy = EnterprisePolicy::default();
+        policy.package_install.allowed_packages = Some(["requests".to_string()].into());
+        cfg.enterprise_policy = Some(Arc::new(policy));
+        let package_policy = package_install_policy(&cfg);
+
+        let err = check_install_sources(
+            &cfg,
+            &package_policy,
+            "python_env.install",
+            &[("index_url", false), ("extra_index_url", true)],
+        )
+        .unwrap_err();
+        assert!(err["message"].as_str().unwrap().contains("extra_index_url"));
+        assert!(check_install_sources(
+            &cfg,
+            &PackageInstallPolicy::default(),
+            "python_env.install",
+            &[("index_url", true)],
+        )
+        .is_ok());
+
+        let alias = format!("policy-{}", uuid::Uuid::new_v4().simple());
+        let err =
+            python_env_create_call(&cfg, &json!({ "alias": alias, "packages": ["evil-pkg"] }))
+                .await
+                .unwrap_err();
+        assert!(err["message"]
+            .as_str()
+            .unwrap()
+            .contains("python_env.create refused"));
+        let err = python_env_create_call(
+            &cfg,
+            &json!({ "alias": alias, "channels": ["https://evil.example/conda"] }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err["message"].as_str().unwrap().contains("'channels'"));
+    }
+
+    #[tokio::test]
+    async fn validate_reports_diagnostics_without_running() {
+        let cfg = mk_cfg(PythonRuntime::System, true);
+        let ok = python_sandbox_validate_call(
+            &cfg,
+            &json!({ "code": "open('/tmp/should-not-exist-validate', 'w')\nx = 1" }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ok["structuredContent"]["valid"], true);
+        assert!(!Path::new("/tmp/should-not-exist-validate").exists());
+
+        let refused = python_sandbox_validate_call(
+            &cfg,
+            &json!({ "code": "import subprocess\nsubprocess.run(['ls'])" }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(refused["structuredContent"]["valid"], false);
+        let diagnostics = refused["structuredContent"]["diagnostics"]
+            .as_array()
+            .unwrap();
+        assert!(diagnostics.iter().any(|d| d["line"] == 1));
+        assert_eq!(
+            refused["structuredContent"]["policy_report"]["decisions"][0]["name"],
+            "subprocess"
+        );
+
+        let broken = python_sandbox_validate_call(&cfg, &json!({ "code": "def f(:" }))
+            .await
+            .unwrap();
+        assert_eq!(
+            broken["structuredContent"]["diagnostics"][0]["rule"],
+            "syntax_error"
+        );
+    }
+
+    #[tokio::test]
+    async fn input_files_are_copied_from_approved_folders() {
+        let approved = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        std::fs::write(approved.path().join("report.txt"), "quarterly").unwrap();
+        std::fs::write(other.path().join("secret.txt"), "nope").unwrap();
+        let cfg = mk_cfg(PythonRuntime::System, true);
+
+        let result = python_sandbox_call(
+            &cfg,
+            &Resources::default(),
+            &json!({
+                "code": "result = open(INPUT_DIR + '/doc.txt').read()",
+                "policy_id": "document_processing",
+                "approved_folders": [approved.path()],
+                "input_files": [{ "path": approved.path().join("report.txt"), "workspace_name": "doc.txt" }]
+            }),
+            None,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["isError"], false, "{}", result);
+        assert_eq!(result["structuredContent"]["output"]["result"], "quarterly");
+
+        let escape = |path: PathBuf, name: &str| {
+            parse_input_files(&json!({
+                "approved_folders": [approved.path()],
+                "input_files": [{ "path": path, "workspace_name": name }]
+            }))
+        };
+        assert!(escape(other.path().join("secret.txt"), "x.txt").is_err());
+        assert!(escape(approved.path().join("../").join("secret.txt"), "x.txt").is_err());
+        assert!(escape(approved.path().join("report.txt"), "../x.txt").is_err());
+        assert!(parse_input_files(&json!({
+            "input_files": [{ "path": approved.path().join("report.txt") }]
+        }))
+        .is_err());
+    }
+
+    #[cfg(feature = "static-analyzer")]
+    #[tokio::test]
+    async fn preflight_reports_missing_unconditional_imports() {
+        let python = which::which("python3").unwrap();
+        let code = "import json\nimport rzn_missing_module.sub\ntry:\n    import rzn_optional_module\nexcept ImportError:\n    pass\n";
+        let missing = missing_packages_for(&python, code).await.unwrap();
+        assert_eq!(
+            missing,
+            [json!({ "module": "rzn_missing_module", "package": "rzn_missing_module", "line": 2 })]
+        );
+        assert_eq!(package_for_module("sklearn"), "scikit-learn");
+        assert_eq!(package_for_module("numpy"), "numpy");
+    }
+
+    #[tokio::test]
+    async fn batch_requests_answer_in_order() {
+        let cfg = Arc::new(mk_cfg(PythonRuntime::Auto, false));
+        let state = Arc::new(WorkerState::new(&cfg));
+        let outbox = Outbox(Arc::new(Mutex::new(io::stdout())));
+        let batch = vec![
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize" }),
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            json!({ "jsonrpc": "2.0", "id": "b", "method": "tools/list" }),
+            json!(5),
+        ];
+        let resp = handle_batch(&cfg, &state, batch, &outbox).await.unwrap();
+        let ids: Vec<&Value> = resp.as_array().unwrap().iter().map(|r| &r["id"]).collect();
+        assert_eq!(ids, [&json!(1), &json!("b"), &Value::Null]);
+        assert!(resp[1]["result"]["tools"].is_array());
+        assert_eq!(resp[2]["error"]["code"], -32600);
+
+        let notifications =
+            vec![json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })];
+        assert!(handle_batch(&cfg, &state, notifications, &outbox)
+            .await
+            .is_none());
+        let empty = handle_batch(&cfg, &state, Vec::new(), &outbox)
+            .await
+            .unwrap();
+        assert_eq!(empty["error"]["code"], -32600);
+    }
+
     #[test]
     fn policy_id_is_case_normalized() {
         let policy = policy_id_from_args(&json!({ "policy_id": "YOLO" }));
         assert_eq!(policy, "yolo");
     }
+
+    #[test]
+    fn enterprise_policy_signature_is_checked_when_keyed() {
+        use base64::Engine;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("enterprise.json");
+        let content = serde_json::to_string(&EnterprisePolicy {
+            minimum_security_level: Some(4),
+            ..Default::default()
+        })
+        .unwrap();
+        std::fs::write(&path, &content).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        let key_path = dir.path().join("enterprise.pub");
+        std::fs::write(
+            &key_path,
+            base64::engine::general_purpose::STANDARD.encode(key.verifying_key().as_bytes()),
+        )
+        .unwrap();
+
+        // Without a key the file loads as is; with one it must be signed
+        assert!(load_enterprise_policy(&path, None).is_ok());
+        let err = load_enterprise_policy(&path, Some(&key_path)).unwrap_err();
+        assert!(err.contains("unsigned"), "{err}");
+
+        let signature = pysandbox::signing::sign(&key, content.as_bytes());
+        std::fs::write(pysandbox::signing::signature_path(&path), signature).unwrap();
+        let policy = load_enterprise_policy(&path, Some(&key_path)).unwrap();
+        assert_eq!(policy.minimum_security_level, Some(4));
+
+        std::fs::write(&path, content.replace('4', "0")).unwrap();
+        assert!(load_enterprise_policy(&path, Some(&key_path)).is_err());
+    }
 }
@@ -0,0 +1,185 @@
+//! Synchronous API for callers without an async runtime
+//!
+//! [`PythonSandbox`] owns a small tokio runtime and blocks on the async
+//! [`crate::PythonSandbox`], so CLI tools and other non-async hosts can run
+//! code without setting up tokio themselves:
+//!
+//! ```no_run
+//! let output = pysandbox::blocking::execute(
+//!     "result = 6 * 7",
+//!     serde_json::json!({}),
+//!     pysandbox::ExecutionOptions::default(),
+//! )?;
+//! assert_eq!(output.result_as::<i64>()?, 42);
+//! # Ok::<(), pysandbox::SandboxError>(())
+//! ```
+//!
+//! Calling these from inside an async runtime panics, as tokio does not allow
+//! nested `block_on`; use the async API there.
+
+use crate::{
+    engine::{Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine},
+    errors::Result,
+    policy::SandboxPolicy,
+};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// Blocking counterpart of [`crate::PythonSandbox`]
+pub struct PythonSandbox {
+    inner: crate::PythonSandbox,
+    runtime: Runtime,
+}
+
+impl PythonSandbox {
+    /// Create a sandbox with the specified engines
+    ///
+    /// The engines are wrapped inside the sandbox's runtime, so ones that
+    /// spawn background tasks can be used.
+    pub fn new(engines: Vec<Box<dyn PythonEngine>>) -> Result<Self> {
+        let runtime = runtime()?;
+        let inner = {
+            let _guard = runtime.enter();
+            crate::PythonSandbox::new(engines)
+        };
+        Ok(Self { inner, runtime })
+    }
+
+    /// Sandbox with the default engines, see [`crate::create_default_sandbox`]
+    pub fn create_default() -> Result<Self> {
+        Self::build(crate::create_default_sandbox())
+    }
+
+    /// Sandbox on a bundled Python, see [`crate::create_bundled_sandbox`]
+    pub fn create_bundled(python_path: std::path::PathBuf) -> Result<Self> {
+        Self::build(crate::create_bundled_sandbox(python_path))
+    }
+
+    /// Run an async constructor on a new runtime and keep its sandbox
+    ///
+    /// Use this to configure the sandbox with the async builder methods,
+    /// e.g. `with_audit_log` or `with_redactor`.
+    pub fn build<F>(sandbox: F) -> Result<Self>
+    where
+        F: Future<Output = Result<crate::PythonSandbox>>,
+    {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(sandbox)?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Execute Python code using the primary engine with fallback support
+    pub fn execute(
+        &self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        self.runtime
+            .block_on(self.inner.execute(code, inputs, options))
+    }
+
+    /// Typed execution, see [`crate::PythonSandbox::execute_typed`]
+    pub fn execute_typed<I, O>(
+        &self,
+        code: &str,
+        inputs: &I,
+        options: ExecutionOptions,
+    ) -> Result<O>
+    where
+        I: serde::Serialize + ?Sized,
+        O: serde::de::DeserializeOwned,
+    {
+        self.runtime
+            .block_on(self.inner.execute_typed(code, inputs, options))
+    }
+
+    /// Validate code with the primary engine
+    pub fn validate(&self, code: &str, options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        self.runtime.block_on(self.inner.validate(code, options))
+    }
+
+    /// Get capabilities of all engines
+    pub fn capabilities(&self) -> Vec<EngineCapabilities> {
+        self.runtime.block_on(self.inner.capabilities())
+    }
+
+    /// Current effective policy, see [`crate::PythonSandbox::effective_policy`]
+    pub fn effective_policy(&self) -> Option<Arc<SandboxPolicy>> {
+        self.inner.effective_policy()
+    }
+
+    /// Drive any other future on the sandbox's runtime, e.g. a session from
+    /// `sandbox.block_on(sandbox.inner().create_session(options))`
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// The async sandbox this wraps
+    pub fn inner(&self) -> &crate::PythonSandbox {
+        &self.inner
+    }
+}
+
+/// Execute code once on a default sandbox
+///
+/// Builds a new sandbox and runtime for the call; keep a [`PythonSandbox`]
+/// around to run more than one snippet.
+pub fn execute(
+    code: &str,
+    inputs: serde_json::Value,
+    options: ExecutionOptions,
+) -> Result<ExecutionResult> {
+    PythonSandbox::create_default()?.execute(code, inputs, options)
+}
+
+/// One worker thread keeps spawned tasks like policy watches running between
+/// calls
+fn runtime() -> Result<Runtime> {
+    Ok(tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("pysandbox-blocking")
+        .enable_all()
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_execute() {
+        let Ok(engine) = crate::NativePythonEngine::new() else {
+            return;
+        };
+        let sandbox = PythonSandbox::new(vec![Box::new(engine)]).unwrap();
+        let output = sandbox
+            .execute(
+                "result = inputs['x'] * 2",
+                serde_json::json!({"x": 21}),
+                ExecutionOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(output.result_as::<i64>().unwrap(), 42);
+        assert_eq!(sandbox.capabilities().len(), 1);
+
+        #[derive(serde::Serialize)]
+        struct Point {
+            x: f64,
+            y: f64,
+        }
+        let norm: f64 = sandbox
+            .execute_typed(
+                "result = (inputs['x'] ** 2 + inputs['y'] ** 2) ** 0.5",
+                &Point { x: 3.0, y: 4.0 },
+                ExecutionOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(norm, 5.0);
+        let missing: Option<String> = sandbox
+            .execute_typed("x = 1", &(), ExecutionOptions::default())
+            .unwrap();
+        assert_eq!(missing, None);
+    }
+}
@@ -0,0 +1,340 @@
+//! Result caching for repeated executions
+//!
+//! Attach an [`ExecutionCache`] to a [`PythonSandbox`](crate::PythonSandbox)
+//! with `with_cache` and a successful `execute` is remembered under a key
+//! hashed from the code, its inputs, the effective policy and the execution
+//! options. Running the same snippet again returns the stored output, marked
+//! `cache_hit`, without starting an interpreter. Errors are never cached, and
+//! neither are runs with `input_files` or `stdin`, whose content isn't part
+//! of the key. [`CacheControl`] in the options skips the cache per call.
+//! Hits are still audited, marked `cache_hit`, and counted in the metrics
+//! under the `cache` engine.
+
+use crate::engine::{ExecutionOptions, ExecutionResult};
+use crate::errors::Result;
+use crate::policy::SandboxPolicy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+/// Engine label cache hits are counted under in the metrics
+pub(crate) const CACHE_ENGINE_NAME: &str = "cache";
+
+/// How an execution uses the sandbox's [`ExecutionCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheControl {
+    /// Return a cached output if there is one, else run and store it
+    #[default]
+    Use,
+    /// Always run, replacing the cached output
+    Refresh,
+    /// Neither read nor write the cache
+    Bypass,
+}
+
+/// In-memory LRU of execution outputs, optionally backed by a directory
+///
+/// Clones share the same cache.
+#[derive(Debug, Clone)]
+pub struct ExecutionCache {
+    inner: Arc<Mutex<Lru>>,
+    dir: Option<PathBuf>,
+    ttl: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Lru {
+    capacity: usize,
+    entries: HashMap<String, (ExecutionResult, Instant)>,
+    /// Keys, least recently used first
+    order: VecDeque<String>,
+}
+
+impl ExecutionCache {
+    /// Keep up to `capacity` outputs in memory
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Lru {
+                capacity,
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+            dir: None,
+            ttl: None,
+        }
+    }
+
+    /// Also store outputs in `dir`, one `<key>.json` file each, so they
+    /// survive restarts
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Expire outputs older than `ttl`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Cache key for an execution, or `None` if it can't be cached
+    pub fn key(
+        code: &str,
+        inputs: &serde_json::Value,
+        policy: Option<&SandboxPolicy>,
+        options: &ExecutionOptions,
+    ) -> Option<String> {
+        if !options.input_files.is_empty() || options.stdin.is_some() {
+            return None;
+        }
+        let mut options = serde_json::to_value(options).ok()?;
+        if let Some(options) = options.as_object_mut() {
+            options.remove("cache");
+        }
+        let mut policy = serde_json::to_value(policy).ok()?;
+        sort_arrays(&mut options);
+        sort_arrays(&mut policy);
+        let material = serde_json::to_vec(&(code, inputs, policy, options)).ok()?;
+        Some(
+            Sha256::digest(&material)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        )
+    }
+
+    /// Cached output for `key`, marked with `cache_hit`
+    ///
+    /// An output listing files that have since been deleted with their
+    /// workspace or export is dropped, so the code runs again.
+    pub fn get(&self, key: &str) -> Option<ExecutionResult> {
+        let mut output = self.get_memory(key).or_else(|| {
+            let output = self.get_disk(key)?;
+            self.insert_memory(key, output.clone());
+            Some(output)
+        })?;
+        if !drop_stale_paths(&mut output) {
+            self.remove(key);
+            return None;
+        }
+        output
+            .extra
+            .insert("cache_hit".to_string(), serde_json::Value::Bool(true));
+        Some(output)
+    }
+
+    /// Store an output under `key`
+    pub fn insert(&self, key: &str, output: &ExecutionResult) {
+        let mut output = output.clone();
+        output.extra.remove("transcript_id");
+        if let Some(dir) = &self.dir {
+            if let Err(e) = write_entry(dir, key, &output) {
+                warn!("Failed to write cache entry {}: {}", key, e);
+            }
+        }
+        self.insert_memory(key, output);
+    }
+
+    /// Drop every cached output, on disk too
+    pub fn clear(&self) -> Result<()> {
+        let mut lru = self.lock();
+        lru.entries.clear();
+        lru.order.clear();
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) {
+        let mut lru = self.lock();
+        lru.entries.remove(key);
+        lru.order.retain(|k| k != key);
+        if let Some(dir) = &self.dir {
+            let _ = std::fs::remove_file(dir.join(format!("{}.json", key)));
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn expired(&self, age: Duration) -> bool {
+        self.ttl.is_some_and(|ttl| age > ttl)
+    }
+
+    fn get_memory(&self, key: &str) -> Option<ExecutionResult> {
+        let mut lru = self.lock();
+        let (output, stored) = lru.entries.get(key)?;
+        if self.expired(stored.elapsed()) {
+            lru.entries.remove(key);
+            lru.order.retain(|k| k != key);
+            return None;
+        }
+        let output = output.clone();
+        lru.order.retain(|k| k != key);
+        lru.order.push_back(key.to_string());
+        Some(output)
+    }
+
+    fn insert_memory(&self, key: &str, output: ExecutionResult) {
+        let mut lru = self.lock();
+        if lru.capacity == 0 {
+            return;
+        }
+        lru.order.retain(|k| k != key);
+        lru.order.push_back(key.to_string());
+        lru.entries
+            .insert(key.to_string(), (output, Instant::now()));
+        while lru.order.len() > lru.capacity {
+            if let Some(oldest) = lru.order.pop_front() {
+                lru.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn get_disk(&self, key: &str) -> Option<ExecutionResult> {
+        let path = self.dir.as_ref()?.join(format!("{}.json", key));
+        let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if self.expired(age) {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        let data = std::fs::read(&path).ok()?;
+        ExecutionResult::from_value(serde_json::from_slice(&data).ok()?).ok()
+    }
+}
+
+/// Forget the workspace of a cached output once it's deleted; false if the
+/// output lists output or exported files that no longer exist
+fn drop_stale_paths(output: &mut ExecutionResult) -> bool {
+    if output.workspace.as_ref().is_some_and(|w| !w.exists()) {
+        output.workspace = None;
+    }
+    let outputs_present = match &output.workspace {
+        Some(workspace) => output
+            .output_files
+            .iter()
+            .all(|name| workspace.join("output").join(name).exists()),
+        None => output.output_files.is_empty(),
+    };
+    let exported = output
+        .extra
+        .get("exported_files")
+        .and_then(|f| f.as_array())
+        .into_iter()
+        .flatten();
+    outputs_present
+        && exported
+            .filter_map(|file| file.get("path")?.as_str())
+            .all(|path| Path::new(path).exists())
+}
+
+/// Put arrays in a fixed order, as options and policies hold hash sets
+/// whose serialized order varies between runs
+fn sort_arrays(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Array(items) => {
+            items.iter_mut().for_each(sort_arrays);
+            items.sort_by_cached_key(|item| item.to_string());
+        }
+        serde_json::Value::Object(map) => map.values_mut().for_each(sort_arrays),
+        _ => {}
+    }
+}
+
+fn write_entry(dir: &Path, key: &str, output: &ExecutionResult) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.json", key));
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec(&output.to_value())?)?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_lru_and_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = ExecutionOptions::default();
+        let key =
+            |code: &str| ExecutionCache::key(code, &serde_json::json!({}), None, &options).unwrap();
+        let output = |n: i64| ExecutionResult {
+            result: Some(n.into()),
+            ..Default::default()
+        };
+
+        let cache = ExecutionCache::new(1).with_dir(dir.path());
+        cache.insert(&key("a"), &output(1));
+        cache.insert(&key("b"), &output(2));
+        assert_eq!(cache.get(&key("b")).unwrap().result, Some(2.into()));
+        // Evicted from memory but still on disk
+        let hit = cache.get(&key("a")).unwrap();
+        assert_eq!(hit.result, Some(1.into()));
+        assert_eq!(hit.extra["cache_hit"], true);
+
+        // Options are part of the key, the cache control isn't
+        let bypass = ExecutionOptions {
+            cache: CacheControl::Bypass,
+            ..Default::default()
+        };
+        let slow = ExecutionOptions {
+            timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let inputs = serde_json::json!({});
+        assert_eq!(
+            ExecutionCache::key("a", &inputs, None, &bypass),
+            Some(key("a"))
+        );
+        assert_ne!(
+            ExecutionCache::key("a", &inputs, None, &slow),
+            Some(key("a"))
+        );
+
+        // Files of a deleted workspace aren't served
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::create_dir(workspace.path().join("output")).unwrap();
+        std::fs::write(workspace.path().join("output").join("plot.png"), "png").unwrap();
+        let with_files = |workspace: PathBuf| ExecutionResult {
+            output_files: vec!["plot.png".to_string()],
+            workspace: Some(workspace),
+            ..output(3)
+        };
+        cache.insert(&key("c"), &with_files(workspace.path().to_path_buf()));
+        assert!(cache.get(&key("c")).is_some());
+        cache.insert(&key("d"), &with_files(dir.path().join("deleted")));
+        assert!(cache.get(&key("d")).is_none());
+        let gone = ExecutionResult {
+            workspace: Some(dir.path().join("deleted")),
+            ..output(4)
+        };
+        cache.insert(&key("e"), &gone);
+        assert_eq!(cache.get(&key("e")).unwrap().workspace, None);
+
+        cache.clear().unwrap();
+        assert!(cache.get(&key("a")).is_none());
+    }
+}
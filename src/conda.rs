@@ -0,0 +1,204 @@
+//! Conda-style environments via micromamba
+//!
+//! Some data-science stacks (GDAL, PROJ, GEOS, ...) only ship reliable
+//! binaries through conda channels. A [`CondaEnv`] creates a prefix
+//! environment with micromamba and installs packages into it; its
+//! [`python_path`](CondaEnv::python_path) can then back any engine, e.g.
+//! `NativePythonEngine::with_python_path`.
+
+use crate::errors::{Result, SandboxError};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Environment variable naming the micromamba executable
+pub const MICROMAMBA_ENV: &str = "RZN_MICROMAMBA";
+
+/// Channel used when none is configured
+pub const DEFAULT_CHANNEL: &str = "conda-forge";
+
+/// Locate micromamba: [`MICROMAMBA_ENV`], then `MAMBA_EXE` (set by
+/// micromamba's shell hook), then PATH
+pub fn find_micromamba() -> Option<PathBuf> {
+    [MICROMAMBA_ENV, "MAMBA_EXE"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .find(|p| p.is_file())
+        .or_else(|| which::which("micromamba").ok())
+}
+
+/// A conda prefix environment managed with micromamba
+#[derive(Debug, Clone)]
+pub struct CondaEnv {
+    micromamba: PathBuf,
+    prefix: PathBuf,
+    channels: Vec<String>,
+    python_version: Option<String>,
+    packages: Vec<String>,
+}
+
+impl CondaEnv {
+    /// Environment at `prefix`, using micromamba from [`find_micromamba`]
+    pub fn new(prefix: impl Into<PathBuf>) -> Result<Self> {
+        let micromamba = find_micromamba().ok_or_else(|| {
+            SandboxError::InternalError(format!(
+                "micromamba not found; install it or set {}",
+                MICROMAMBA_ENV
+            ))
+        })?;
+        Ok(Self::with_micromamba(micromamba, prefix))
+    }
+
+    /// Environment at `prefix`, using a specific micromamba executable
+    pub fn with_micromamba(micromamba: impl Into<PathBuf>, prefix: impl Into<PathBuf>) -> Self {
+        Self {
+            micromamba: micromamba.into(),
+            prefix: prefix.into(),
+            channels: Vec::new(),
+            python_version: None,
+            packages: Vec::new(),
+        }
+    }
+
+    /// Add a channel, in priority order (defaults to [`DEFAULT_CHANNEL`])
+    pub fn channel(mut self, channel: &str) -> Self {
+        self.channels.push(channel.to_string());
+        self
+    }
+
+    /// Python version spec for the environment, e.g. `3.11`
+    pub fn python_version(mut self, version: &str) -> Self {
+        self.python_version = Some(version.to_string());
+        self
+    }
+
+    /// Conda package spec installed when the environment is created
+    pub fn package(mut self, spec: &str) -> Self {
+        self.packages.push(spec.to_string());
+        self
+    }
+
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    /// Channels in effect
+    pub fn channels(&self) -> Vec<String> {
+        if self.channels.is_empty() {
+            vec![DEFAULT_CHANNEL.to_string()]
+        } else {
+            self.channels.clone()
+        }
+    }
+
+    /// Interpreter inside the environment
+    pub fn python_path(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.prefix.join("python.exe")
+        } else {
+            self.prefix.join("bin").join("python")
+        }
+    }
+
+    /// `micromamba create` for this environment
+    pub fn create_command(&self) -> Command {
+        let mut cmd = self.command("create");
+        let python = match &self.python_version {
+            Some(version) => format!("python={}", version),
+            None => "python".to_string(),
+        };
+        cmd.arg(python).args(&self.packages);
+        cmd
+    }
+
+    /// `micromamba install` of `specs` into this environment
+    pub fn install_command(&self, specs: &[String]) -> Command {
+        let mut cmd = self.command("install");
+        cmd.args(specs);
+        cmd
+    }
+
+    /// Create the environment, returning its interpreter
+    pub async fn create(&self) -> Result<PathBuf> {
+        run(self.create_command(), "create").await?;
+        Ok(self.python_path())
+    }
+
+    /// Install conda packages into the environment
+    pub async fn install(&self, specs: &[String]) -> Result<()> {
+        run(self.install_command(specs), "install").await
+    }
+
+    /// Only the configured channels are used, so an environment resolves the
+    /// same way regardless of the user's `.condarc`
+    fn command(&self, subcommand: &str) -> Command {
+        let mut cmd = Command::new(&self.micromamba);
+        cmd.arg(subcommand)
+            .arg("--yes")
+            .arg("--prefix")
+            .arg(&self.prefix)
+            .arg("--override-channels");
+        for channel in self.channels() {
+            cmd.arg("--channel").arg(channel);
+        }
+        cmd
+    }
+}
+
+async fn run(mut cmd: Command, action: &str) -> Result<()> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| SandboxError::InternalError(format!("Failed to run micromamba: {}", e)))?;
+    if !output.status.success() {
+        return Err(SandboxError::InternalError(format!(
+            "micromamba {} failed: {}",
+            action,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conda_env_commands() {
+        let env = CondaEnv::with_micromamba("/opt/micromamba", "/envs/geo")
+            .channel("conda-forge")
+            .channel("bioconda")
+            .python_version("3.11")
+            .package("gdal>=3.8");
+        let args: Vec<String> = env
+            .create_command()
+            .as_std()
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            args,
+            [
+                "create",
+                "--yes",
+                "--prefix",
+                "/envs/geo",
+                "--override-channels",
+                "--channel",
+                "conda-forge",
+                "--channel",
+                "bioconda",
+                "python=3.11",
+                "gdal>=3.8"
+            ]
+        );
+        assert_eq!(
+            CondaEnv::with_micromamba("mm", "/envs/x").channels(),
+            [DEFAULT_CHANNEL]
+        );
+    }
+}
@@ -86,6 +86,9 @@ impl SecurityProfile {
                 cpu_seconds: 300,
                 max_processes: 50,
                 max_threads: 16,
+                max_output_bytes: 100 * 1024 * 1024,
+                max_open_files: 4096,
+                max_workspace_bytes: 10 * 1024 * 1024 * 1024,
             },
             SecurityProfile::Blacklist => ResourceLimits::default(),
             SecurityProfile::DataScience => ResourceLimits::default(),
@@ -94,10 +97,18 @@ impl SecurityProfile {
                 cpu_seconds: 10,
                 max_processes: 1,
                 max_threads: 2,
+                max_output_bytes: 1024 * 1024,
+                max_open_files: 64,
+                max_workspace_bytes: 100 * 1024 * 1024,
             },
         }
     }
 
+    /// Whether to refuse native extensions outside the import whitelist
+    pub fn blocks_native_extensions(&self) -> bool {
+        matches!(self, SecurityProfile::Strict)
+    }
+
     /// Whether to restrict file write access
     pub fn restrict_file_writes(&self) -> bool {
         !matches!(self, SecurityProfile::Yolo)
@@ -127,6 +138,29 @@ pub struct ResourceLimits {
     pub max_processes: u64,
     /// Maximum number of threads for scientific libraries
     pub max_threads: u32,
+    /// Maximum bytes kept from each of stdout and stderr; the rest is dropped
+    /// and flagged with `stdout_truncated` / `stderr_truncated`
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+    /// Maximum open file descriptors (RLIMIT_NOFILE)
+    #[serde(default = "default_max_open_files")]
+    pub max_open_files: u64,
+    /// Maximum bytes written to the workspace; also caps any single file
+    /// (RLIMIT_FSIZE) for engines with a workspace
+    #[serde(default = "default_max_workspace_bytes")]
+    pub max_workspace_bytes: u64,
+}
+
+fn default_max_output_bytes() -> usize {
+    10 * 1024 * 1024 // 10MB, matching `ResourceLimitsPolicy`
+}
+
+fn default_max_open_files() -> u64 {
+    256
+}
+
+fn default_max_workspace_bytes() -> u64 {
+    1024 * 1024 * 1024 // 1GB
 }
 
 impl Default for ResourceLimits {
@@ -136,16 +170,62 @@ impl Default for ResourceLimits {
             cpu_seconds: 30,
             max_processes: 10,
             max_threads: 4,
+            max_output_bytes: default_max_output_bytes(),
+            max_open_files: default_max_open_files(),
+            max_workspace_bytes: default_max_workspace_bytes(),
+        }
+    }
+}
+
+/// Caps on the submitted source, checked by `validate` before the code is
+/// analyzed or run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeLimits {
+    /// Maximum source size in bytes
+    pub max_source_bytes: usize,
+    /// Maximum syntax tree nodes
+    pub max_ast_nodes: usize,
+    /// Maximum syntax tree nesting depth
+    pub max_nesting_depth: usize,
+    /// Maximum function, lambda and class definitions
+    pub max_functions: usize,
+}
+
+impl Default for CodeLimits {
+    fn default() -> Self {
+        Self {
+            max_source_bytes: 1024 * 1024, // 1MB
+            max_ast_nodes: 200_000,
+            max_nesting_depth: 100,
+            max_functions: 2_000,
         }
     }
 }
 
+impl CodeLimits {
+    /// Check the source size, the only cap that doesn't need a parse
+    pub fn check_source(&self, code: &str) -> crate::Result<()> {
+        if code.len() > self.max_source_bytes {
+            return Err(crate::SandboxError::CodeTooComplex(format!(
+                "source is {} bytes, limit is {}",
+                code.len(),
+                self.max_source_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// Import control policy
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ImportPolicy {
     /// Block specific modules
     Blacklist(HashSet<String>),
     /// Only allow specific modules
+    ///
+    /// An entry may carry a pip-style version requirement, `requests>=2.31`
+    /// or `yaml>=5.4,!=6.0.0`; the guard then refuses the module when the
+    /// installed distribution doesn't satisfy it.
     Whitelist(HashSet<String>),
     /// Both whitelist and blacklist
     Both {
@@ -181,18 +261,143 @@ impl Default for ImportPolicy {
     }
 }
 
+/// Module name of a whitelist entry, without its version requirement
+pub(crate) fn entry_module(entry: &str) -> &str {
+    entry[..entry.find(REQUIREMENT_OPERATORS).unwrap_or(entry.len())].trim()
+}
+
+/// Version requirement of a whitelist entry, `None` for a bare module name
+pub(crate) fn entry_requirement(entry: &str) -> Option<&str> {
+    entry
+        .find(REQUIREMENT_OPERATORS)
+        .map(|start| entry[start..].trim())
+}
+
+const REQUIREMENT_OPERATORS: [char; 5] = ['<', '>', '=', '!', '~'];
+
+/// Module names of whitelist entries, without version requirements
+pub(crate) fn whitelisted_modules(whitelist: &HashSet<String>) -> HashSet<String> {
+    whitelist
+        .iter()
+        .map(|entry| entry_module(entry).to_string())
+        .collect()
+}
+
+/// Python dict of whitelisted modules to their version requirements
+pub(crate) fn python_version_requirements(whitelist: Option<&HashSet<String>>) -> String {
+    let mut requirements: Vec<_> = whitelist
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some((entry_module(entry), entry_requirement(entry)?)))
+        .map(|(module, requirement)| format!("'{}': '{}'", module, requirement))
+        .collect();
+    requirements.sort();
+    format!("{{{}}}", requirements.join(", "))
+}
+
+/// The most specific entry covering `module` in either list, and whether it
+/// allows the import
+///
+/// Entries match dotted prefixes and the longest one wins, so `os.path` in
+/// the whitelist beats `os` in the blacklist. A blacklist entry wins a tie.
+pub(crate) fn import_rule(
+    whitelist: Option<&HashSet<String>>,
+    blacklist: Option<&HashSet<String>>,
+    module: &str,
+) -> Option<(bool, String)> {
+    // `posix` and `nt` hold what `os` re-exports, so they follow its rules
+    let root = module.split('.').next().unwrap_or_default();
+    let module = if OS_ALIASES.contains(&root) {
+        format!("os{}", &module[root.len()..])
+    } else {
+        module.to_string()
+    };
+    std::iter::once(module.as_str())
+        .chain(module.rmatch_indices('.').map(|(end, _)| &module[..end]))
+        .find_map(|name| {
+            if blacklist.is_some_and(|list| list.contains(name)) {
+                Some((false, name.to_string()))
+            } else if whitelist.is_some_and(|list| {
+                list.contains(name) || list.iter().any(|entry| entry_module(entry) == name)
+            }) {
+                Some((true, name.to_string()))
+            } else {
+                None
+            }
+        })
+}
+
+/// Platform modules of `os`, which import rules for `os` also cover
+const OS_ALIASES: [&str; 2] = ["posix", "nt"];
+
+/// [`ImportPolicy::is_allowed`] over a whitelist and blacklist, either optional
+pub(crate) fn import_allowed(
+    whitelist: Option<&HashSet<String>>,
+    blacklist: Option<&HashSet<String>>,
+    module: &str,
+) -> bool {
+    match import_rule(whitelist, blacklist, module) {
+        Some((allowed, _)) => allowed,
+        None => whitelist.is_none(),
+    }
+}
+
+/// [`ImportPolicy::denied_from_import`] over a whitelist and blacklist
+pub(crate) fn from_import_denial(
+    whitelist: Option<&HashSet<String>>,
+    blacklist: Option<&HashSet<String>>,
+    module: &str,
+    names: &[&str],
+) -> Option<String> {
+    let allowed = |name: &str| import_allowed(whitelist, blacklist, name);
+    let blocked_name = names
+        .iter()
+        .filter(|name| **name != "*")
+        .map(|name| format!("{}.{}", module, name))
+        .find(|name| !allowed(name));
+    if allowed(module) {
+        return blocked_name;
+    }
+    // A blocked package can still hand out submodules allowed on their own
+    if names.is_empty() || names.contains(&"*") || blocked_name.is_some() {
+        return Some(module.to_string());
+    }
+    None
+}
+
 impl ImportPolicy {
     /// Check if an import is allowed
+    ///
+    /// Entries cover their submodules and the longest matching entry wins:
+    /// `matplotlib` allows `matplotlib.pyplot`, `asyncio.subprocess` blocks
+    /// only that subpackage, and with `os` blacklisted a whitelisted
+    /// `os.path` stays importable. Version requirements need the installed
+    /// packages, so only the guard checks them.
     pub fn is_allowed(&self, module: &str) -> bool {
-        let root_module = module.split('.').next().unwrap_or(module);
+        let (whitelist, blacklist) = self.lists();
+        import_allowed(whitelist, blacklist, module)
+    }
+
+    /// The module a `from module import names` statement is refused for, if
+    /// any
+    ///
+    /// The statement binds only the names, so it's allowed when the module is
+    /// and no name is a blocked submodule (`from os import system` with
+    /// `os.system` blacklisted is refused), or when every name is an allowed
+    /// submodule of a blocked package (`from os import path`).
+    pub fn denied_from_import(&self, module: &str, names: &[&str]) -> Option<String> {
+        let (whitelist, blacklist) = self.lists();
+        from_import_denial(whitelist, blacklist, module, names)
+    }
 
+    fn lists(&self) -> (Option<&HashSet<String>>, Option<&HashSet<String>>) {
         match self {
-            ImportPolicy::Blacklist(blacklist) => !blacklist.contains(root_module),
-            ImportPolicy::Whitelist(whitelist) => whitelist.contains(root_module),
+            ImportPolicy::Blacklist(blacklist) => (None, Some(blacklist)),
+            ImportPolicy::Whitelist(whitelist) => (Some(whitelist), None),
             ImportPolicy::Both {
                 whitelist,
                 blacklist,
-            } => whitelist.contains(root_module) && !blacklist.contains(root_module),
+            } => (Some(whitelist), Some(blacklist)),
         }
     }
 
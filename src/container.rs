@@ -0,0 +1,430 @@
+use crate::{
+    config::ResourceLimits,
+    engine::{Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine},
+    errors::{execution_error, Result, SandboxError},
+    policy::NetworkRule,
+    result_file,
+    sandboxed::{IsolatedWorkspace, SandboxedPythonEngine},
+    supervisor::{supervise, ResultChannel, SetupClock, SupervisorLimits},
+};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Path the workspace is mounted at inside the container
+const CONTAINER_WORKSPACE: &str = "/workspace";
+
+/// Container runtime CLI used to launch containers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContainerRuntime {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    /// Name of the CLI binary
+    pub fn binary(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+}
+
+/// Configuration for the container engine
+#[derive(Debug, Clone)]
+pub struct ContainerConfig {
+    /// Container runtime CLI to use
+    pub runtime: ContainerRuntime,
+    /// OCI image with a Python interpreter
+    pub image: String,
+    /// Python executable inside the image
+    pub python_command: String,
+    /// Container network to attach. `None` runs with `--network=none` unless the
+    /// execution supplies a network allowlist, in which case the default bridge
+    /// network is used and hosts are filtered by the in-process guard.
+    pub network: Option<String>,
+    /// Base directory for creating isolated workspaces (mounted at /workspace)
+    pub workspace_base: PathBuf,
+    /// Resource limits
+    pub limits: ResourceLimits,
+    /// CPUs the container may use (`--cpus`); `None` leaves CPU use to the
+    /// CPU-time ulimit
+    pub cpus: Option<f64>,
+    /// `user[:group]` to run as. `None` runs as the host process's uid and gid,
+    /// so files written to the mounted workspace belong to the host user
+    pub user: Option<String>,
+    /// Extra arguments passed to `run` before the image name
+    pub extra_args: Vec<String>,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            runtime: ContainerRuntime::default(),
+            image: "python:3.12-slim".to_string(),
+            python_command: "python3".to_string(),
+            network: None,
+            workspace_base: crate::sandboxed::default_workspace_base(),
+            limits: ResourceLimits::default(),
+            cpus: None,
+            user: None,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+/// Python engine that runs code in a Docker/Podman container
+///
+/// Each execution gets a fresh `--rm` container with a read-only root
+/// filesystem, all capabilities dropped, and the isolated workspace mounted at
+/// `/workspace`.
+pub struct ContainerPythonEngine {
+    config: ContainerConfig,
+}
+
+impl ContainerPythonEngine {
+    /// Create a new container engine
+    pub fn new(config: ContainerConfig) -> Result<Self> {
+        which::which(config.runtime.binary()).map_err(|_| SandboxError::NoEngineAvailable)?;
+        std::fs::create_dir_all(&config.workspace_base)?;
+        Ok(Self { config })
+    }
+
+    /// Create with an image using Docker and default limits
+    pub fn with_image(image: &str) -> Result<Self> {
+        Self::new(ContainerConfig {
+            image: image.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Check if the runtime CLI is installed and its daemon is reachable
+    pub async fn is_available(runtime: ContainerRuntime) -> bool {
+        Command::new(runtime.binary())
+            .arg("version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .map(|s| s.success())
+            .unwrap_or(false)
+    }
+
+    /// Build the `run` arguments for an execution
+    fn build_run_args(
+        &self,
+        workspace_path: &Path,
+        container_name: &str,
+        options: &ExecutionOptions,
+    ) -> Vec<String> {
+        let limits = &self.config.limits;
+        let memory_mb = limits.memory_mb.min(options.memory_mb);
+        let cpu_seconds = limits.cpu_seconds.min(options.cpu_seconds);
+
+        // A container without a network still has its own loopback, which
+        // is all a localhost-only allowlist needs
+        let network = match &self.config.network {
+            Some(network) => network.clone(),
+            None if options.network_allowlist.as_ref().is_some_and(|hosts| {
+                hosts
+                    .iter()
+                    .any(|host| !NetworkRule::parse(host).is_loopback())
+            }) =>
+            {
+                "bridge".to_string()
+            }
+            None => "none".to_string(),
+        };
+
+        let mut args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            "-i".to_string(),
+            "--name".to_string(),
+            container_name.to_string(),
+            format!("--network={}", network),
+            format!("--memory={}m", memory_mb),
+            format!("--memory-swap={}m", memory_mb),
+            format!("--pids-limit={}", limits.max_processes.max(1)),
+            "--ulimit".to_string(),
+            format!("cpu={}:{}", cpu_seconds, cpu_seconds),
+            "--ulimit".to_string(),
+            format!("nofile={}:{}", limits.max_open_files, limits.max_open_files),
+            "--read-only".to_string(),
+            "--tmpfs".to_string(),
+            "/tmp".to_string(),
+            "--cap-drop=ALL".to_string(),
+            "--security-opt=no-new-privileges".to_string(),
+            "-v".to_string(),
+            format!("{}:{}", workspace_path.display(), CONTAINER_WORKSPACE),
+            "-w".to_string(),
+            CONTAINER_WORKSPACE.to_string(),
+            "-e".to_string(),
+            format!("SANDBOX_WORKSPACE={}", CONTAINER_WORKSPACE),
+            "-e".to_string(),
+            "PYTHONIOENCODING=utf-8".to_string(),
+            "-e".to_string(),
+            "PYTHONDONTWRITEBYTECODE=1".to_string(),
+            // The host uid usually has no passwd entry or home in the image
+            "-e".to_string(),
+            "HOME=/tmp".to_string(),
+        ];
+
+        if let Some(cpus) = self.config.cpus {
+            args.push(format!("--cpus={}", cpus));
+        }
+        match &self.config.user {
+            Some(user) => args.push(format!("--user={}", user)),
+            None => args.extend(host_user_args(self.config.runtime)),
+        }
+
+        for var in ["OMP_NUM_THREADS", "OPENBLAS_NUM_THREADS", "MKL_NUM_THREADS"] {
+            args.push("-e".to_string());
+            args.push(format!("{}={}", var, limits.max_threads));
+        }
+        for (key, value) in &options.env_vars {
+            args.push("-e".to_string());
+            args.push(format!("{}={}", key, value));
+        }
+
+        args.extend(self.config.extra_args.iter().cloned());
+        args.push(self.config.image.clone());
+        args.push(self.config.python_command.clone());
+        // Read the wrapper from stdin so large programs don't hit argv limits
+        args.push("-".to_string());
+        args
+    }
+
+    /// Force-remove a container that outlived its client
+    async fn kill_container(&self, container_name: &str) {
+        let result = Command::new(self.config.runtime.binary())
+            .args(["rm", "-f", container_name])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+        if let Err(e) = result {
+            warn!(
+                "[CONTAINER] Failed to remove container {}: {}",
+                container_name, e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl PythonEngine for ContainerPythonEngine {
+    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        // Syntax errors are reported by the interpreter inside the container
+        Ok(Vec::new())
+    }
+
+    async fn execute(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let setup = SetupClock::start(options);
+        let workspace = IsolatedWorkspace::new(&self.config.workspace_base)?;
+        workspace.write_inputs(&inputs)?;
+        workspace.stage_inputs(&options.input_files)?;
+        // The wrapper goes to the container's stdin, so stdin data is staged
+        if let Some(stdin) = &options.stdin {
+            setup.within(stdin.stage(&workspace.path)).await?;
+        }
+        let channel = ResultChannel::in_workspace(&workspace.path);
+        let wrapper_code = SandboxedPythonEngine::workspace_wrapper(
+            code,
+            options,
+            false,
+            self.config.limits.max_output_bytes,
+            None,
+            &channel,
+        )?;
+
+        let container_name = format!("pysandbox-{}", &uuid::Uuid::new_v4().to_string()[..12]);
+        let args = self.build_run_args(&workspace.path, &container_name, options);
+
+        info!(
+            "[CONTAINER] Running {} with {} ({})",
+            self.config.image,
+            self.config.runtime.binary(),
+            container_name
+        );
+
+        setup.finish()?;
+        let mut child = Command::new(self.config.runtime.binary())
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(wrapper_code.as_bytes()).await?;
+            stdin.shutdown().await?;
+        }
+
+        let output = match supervise(
+            child,
+            options,
+            &SupervisorLimits {
+                max_output_bytes: Some(self.config.limits.max_output_bytes),
+                ..Default::default()
+            }
+            .with_workspace(&workspace.path, &self.config.limits),
+        )
+        .await
+        {
+            Ok(output) => output,
+            Err(e @ (SandboxError::Timeout | SandboxError::Cancelled)) => {
+                // Killing the CLI client does not stop the container
+                self.kill_container(&container_name).await;
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        // Extract structured output
+        if let Some(mut parsed) = channel.take(&stdout) {
+            if let Some(error) = execution_error(&parsed, code) {
+                return Err(error);
+            }
+            result_file::persist_from_workspace(&mut parsed, &workspace.path)?;
+            return ExecutionResult::from_value(parsed);
+        }
+
+        // Output that bypassed capture overran the pipe cap
+        if output.truncated {
+            return Err(SandboxError::RuntimeError(
+                "Output exceeded the configured size limit".to_string(),
+            ));
+        }
+
+        // Fallback: map container exit conditions
+        if !output.status.success() {
+            // 137 = SIGKILL, which the OOM killer uses when --memory is hit
+            if output.status.code() == Some(137) || stderr.contains("MemoryError") {
+                return Err(SandboxError::MemoryLimitExceeded { peak_mb: None });
+            }
+            return Err(SandboxError::RuntimeError(stderr.to_string()));
+        }
+
+        Ok(ExecutionResult::default())
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: format!(
+                "Container ({}: {})",
+                self.config.runtime.binary(),
+                self.config.image
+            ),
+            numpy: true, // Depends on the image
+            matplotlib: true,
+            pandas: true,
+            max_memory_mb: self.config.limits.memory_mb,
+            max_cpu_seconds: self.config.limits.cpu_seconds,
+            security_level: 8, // Namespace isolation, no network by default
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        // Containers are started with --rm, nothing to clean up
+        Ok(())
+    }
+}
+
+/// `run` arguments mapping the container user to the host process's
+#[cfg(unix)]
+fn host_user_args(runtime: ContainerRuntime) -> Vec<String> {
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let mut args = vec![format!("--user={}:{}", uid, gid)];
+    // Rootless Podman maps container uids to subordinate ones, so keep ours
+    if runtime == ContainerRuntime::Podman && uid != 0 {
+        args.push("--userns=keep-id".to_string());
+    }
+    args
+}
+
+#[cfg(not(unix))]
+fn host_user_args(_runtime: ContainerRuntime) -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> ContainerPythonEngine {
+        ContainerPythonEngine {
+            config: ContainerConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_run_args_default_to_no_network() {
+        let args = engine().build_run_args(
+            Path::new("/tmp/ws"),
+            "pysandbox-test",
+            &ExecutionOptions::default(),
+        );
+        assert!(args.contains(&"--network=none".to_string()));
+        assert!(args.contains(&"/tmp/ws:/workspace".to_string()));
+        assert!(args.contains(&"--memory=2048m".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("--cpus")));
+        #[cfg(unix)]
+        assert!(args.contains(&format!(
+            "--user={}:{}",
+            unsafe { libc::getuid() },
+            unsafe { libc::getgid() }
+        )));
+        assert_eq!(args.last().map(String::as_str), Some("-"));
+    }
+
+    #[test]
+    fn test_run_args_use_configured_cpus_and_user() {
+        let engine = ContainerPythonEngine {
+            config: ContainerConfig {
+                cpus: Some(1.5),
+                user: Some("nobody".to_string()),
+                ..Default::default()
+            },
+        };
+        let args = engine.build_run_args(
+            Path::new("/tmp/ws"),
+            "pysandbox-test",
+            &ExecutionOptions::default(),
+        );
+        assert!(args.contains(&"--cpus=1.5".to_string()));
+        assert!(args.contains(&"--user=nobody".to_string()));
+        assert_eq!(
+            args.iter().filter(|arg| arg.starts_with("--user")).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_run_args_use_bridge_for_allowlist() {
+        let options = ExecutionOptions {
+            network_allowlist: Some(vec!["pypi.org".to_string()]),
+            memory_mb: 512,
+            ..Default::default()
+        };
+        let args = engine().build_run_args(Path::new("/tmp/ws"), "pysandbox-test", &options);
+        assert!(args.contains(&"--network=bridge".to_string()));
+        assert!(args.contains(&"--memory=512m".to_string()));
+    }
+}
@@ -0,0 +1,278 @@
+//! Discovery of the Python interpreters available to a host
+//!
+//! [`discover_pythons`] probes every interpreter a host could run code with:
+//! `python3` and `python` on PATH, the bundled runtime provisioned by
+//! [`StandaloneRuntime`], extra bundled interpreters the host names, and the
+//! managed envs of the [`ManagedEnvRegistry`]. Each one is started once to
+//! report its version and architecture, so a broken or too-old interpreter
+//! shows up with the reason instead of failing a later run.
+
+use crate::envs::ManagedEnvRegistry;
+use crate::standalone::StandaloneRuntime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// How long an interpreter gets to report its version
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const PROBE: &str = "import json, platform\n\
+     print(json.dumps({'version': platform.python_version(), 'arch': platform.machine(), \
+     'implementation': platform.python_implementation()}))";
+
+/// Where a discovered interpreter comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PythonSource {
+    /// Found on PATH
+    System,
+    /// Shipped with or provisioned for the host
+    Bundled,
+    /// A managed env's interpreter
+    ManagedEnv,
+}
+
+/// An interpreter found by [`PythonDiscovery`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscoveredPython {
+    pub source: PythonSource,
+    pub path: PathBuf,
+    /// Alias of the managed env
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// e.g. `3.12.7`; `None` if the interpreter didn't start
+    pub version: Option<String>,
+    /// Machine architecture, e.g. `x86_64` or `arm64`
+    pub arch: Option<String>,
+    /// e.g. `CPython` or `PyPy`
+    pub implementation: Option<String>,
+    /// Whether it started and reported its version
+    pub usable: bool,
+    /// Whether its version is at least the requested minimum; `true` when
+    /// no minimum was given and the interpreter is usable
+    pub satisfies_minimum: bool,
+    /// Why it isn't usable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Finds and probes the interpreters available to a host
+#[derive(Debug, Clone, Default)]
+pub struct PythonDiscovery {
+    min_version: Option<String>,
+    bundled: Vec<PathBuf>,
+    registry: Option<ManagedEnvRegistry>,
+    runtime: Option<StandaloneRuntime>,
+}
+
+impl PythonDiscovery {
+    /// Discovery with the managed envs and provisioned runtime found through
+    /// the environment, like the worker's
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Judge interpreters against a minimum version, e.g. `3.10`
+    pub fn min_version(mut self, version: impl Into<String>) -> Self {
+        self.min_version = Some(version.into());
+        self
+    }
+
+    /// Also probe a bundled interpreter, e.g. one under `resources/python/`
+    pub fn bundled(mut self, python_path: impl Into<PathBuf>) -> Self {
+        self.bundled.push(python_path.into());
+        self
+    }
+
+    /// Look for managed envs in `registry`
+    pub fn registry(mut self, registry: ManagedEnvRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Look for a provisioned runtime in `runtime`
+    pub fn runtime(mut self, runtime: StandaloneRuntime) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Probe every interpreter found, bundled ones first
+    ///
+    /// An interpreter reachable under several names (e.g. `python` and
+    /// `python3` linking to the same binary) is listed once.
+    pub async fn run(&self) -> Vec<DiscoveredPython> {
+        let runtime = self
+            .runtime
+            .clone()
+            .unwrap_or_else(StandaloneRuntime::from_env);
+        let registry = self
+            .registry
+            .clone()
+            .unwrap_or_else(ManagedEnvRegistry::from_env);
+
+        let mut candidates: Vec<(PythonSource, PathBuf, Option<String>)> = Vec::new();
+        for path in self
+            .bundled
+            .iter()
+            .cloned()
+            .chain(runtime.installed().map(|r| r.python_path))
+        {
+            candidates.push((PythonSource::Bundled, path, None));
+        }
+        for name in ["python3", "python"] {
+            for path in which::which_all(name).into_iter().flatten() {
+                candidates.push((PythonSource::System, path, None));
+            }
+        }
+        // Listing fails only if the base dir can't be created; no envs then
+        for env in registry.list(true).unwrap_or_default() {
+            candidates.push((PythonSource::ManagedEnv, env.python_path, Some(env.alias)));
+        }
+
+        let mut seen = HashSet::new();
+        let mut found = Vec::new();
+        for (source, path, alias) in candidates {
+            // Venvs link to their base interpreter, so only dedupe the rest
+            let key = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if source != PythonSource::ManagedEnv && !seen.insert(key) {
+                continue;
+            }
+            found.push(self.probe(source, path, alias).await);
+        }
+        found
+    }
+
+    async fn probe(
+        &self,
+        source: PythonSource,
+        path: PathBuf,
+        alias: Option<String>,
+    ) -> DiscoveredPython {
+        let mut python = DiscoveredPython {
+            source,
+            path,
+            alias,
+            version: None,
+            arch: None,
+            implementation: None,
+            usable: false,
+            satisfies_minimum: false,
+            error: None,
+        };
+        let report = match probe(&python.path).await {
+            Ok(report) => report,
+            Err(e) => {
+                python.error = Some(e);
+                return python;
+            }
+        };
+        python.usable = true;
+        python.satisfies_minimum = self
+            .min_version
+            .as_deref()
+            .is_none_or(|min| version_at_least(&report.version, min));
+        python.version = Some(report.version);
+        python.arch = Some(report.arch);
+        python.implementation = Some(report.implementation);
+        python
+    }
+}
+
+/// Probe the interpreters on PATH, the provisioned runtime and the managed
+/// envs; see [`PythonDiscovery`] to add bundled ones or a minimum version
+pub async fn discover_pythons() -> Vec<DiscoveredPython> {
+    PythonDiscovery::new().run().await
+}
+
+#[derive(Deserialize)]
+struct ProbeReport {
+    version: String,
+    arch: String,
+    implementation: String,
+}
+
+async fn probe(path: &Path) -> std::result::Result<ProbeReport, String> {
+    if !path.exists() {
+        return Err("interpreter not found".to_string());
+    }
+    let output = Command::new(path)
+        .arg("-c")
+        .arg(PROBE)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(PROBE_TIMEOUT, output).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("failed to start: {}", e)),
+        Err(_) => return Err(format!("no answer within {}s", PROBE_TIMEOUT.as_secs())),
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|_| "not a Python 3 interpreter".to_string())
+}
+
+/// Whether dotted `version` is at least `min`, comparing numerically;
+/// pre-release suffixes like `3.13.0rc1` count as their release
+pub fn version_at_least(version: &str, min: &str) -> bool {
+    let parts = |v: &str| -> Vec<u64> {
+        v.trim()
+            .split('.')
+            .map(|part| {
+                let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+                digits.parse().unwrap_or(0)
+            })
+            .collect()
+    };
+    let (version, min) = (parts(version), parts(min));
+    let len = version.len().max(min.len());
+    let padded =
+        |v: &[u64]| -> Vec<u64> { (0..len).map(|i| v.get(i).copied().unwrap_or(0)).collect() };
+    padded(&version) >= padded(&min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_discover_pythons() {
+        assert!(version_at_least("3.12.7", "3.10"));
+        assert!(version_at_least("3.10", "3.10.0"));
+        assert!(!version_at_least("3.9.18", "3.10"));
+        assert!(version_at_least("3.13.0rc1", "3.13"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ManagedEnvRegistry::new(dir.path().join("envs"));
+        std::fs::create_dir_all(registry.env_dir("broken").unwrap()).unwrap();
+        let found = PythonDiscovery::new()
+            .min_version("99.0")
+            .bundled(dir.path().join("missing").join("python3"))
+            .registry(registry)
+            .runtime(StandaloneRuntime::new(dir.path().join("runtime")))
+            .run()
+            .await;
+
+        assert_eq!(found[0].source, PythonSource::Bundled);
+        assert!(!found[0].usable);
+        assert_eq!(found[0].error.as_deref(), Some("interpreter not found"));
+        let broken = found
+            .iter()
+            .find(|p| p.alias.as_deref() == Some("broken"))
+            .unwrap();
+        assert_eq!(broken.source, PythonSource::ManagedEnv);
+        assert!(!broken.usable);
+        if let Some(system) = found.iter().find(|p| p.source == PythonSource::System) {
+            assert!(system.usable);
+            assert!(system.version.is_some());
+            assert!(!system.satisfies_minimum);
+        }
+    }
+}
@@ -0,0 +1,591 @@
+//! Proxy-based enforcement of network allowlists
+//!
+//! The in-process network guard patches `socket`, which code reaching the raw
+//! `_socket` module or a C extension can step around. With
+//! [`NetworkEnforcement::Proxy`] the native and sandboxed engines instead
+//! start an [`EgressProxy`] on the loopback interface for the run, point the
+//! child's `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY` at it (after `env_vars`, so
+//! they can't be overridden) and drop `NO_PROXY`. The proxy speaks HTTP
+//! `CONNECT`, absolute-URI HTTP and SOCKS5, and only dials hosts on the
+//! allowlist.
+//!
+//! Direct egress is blocked at the OS layer where the engine already runs one:
+//! on macOS the generated Seatbelt profile then only allows outbound traffic
+//! to the proxy port. Linux has no network namespace backend yet, so there the
+//! in-process guard, limited to the proxy and the allowlist, remains the only
+//! check on direct connections.
+
+use crate::engine::ExecutionOptions;
+use crate::errors::Result;
+use crate::policy::NetworkRule;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, info, warn};
+
+/// Largest request head the proxy reads before giving up
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// How [`ExecutionOptions::network_allowlist`] is enforced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkEnforcement {
+    /// Patch `socket` inside the interpreter
+    #[default]
+    InProcess,
+    /// Route traffic through a local allowlisting proxy and block direct
+    /// egress at the OS layer where the engine has one
+    Proxy,
+}
+
+/// Per-execution caps on outbound traffic
+///
+/// The in-process guard counts connections. Bytes sent and received are
+/// metered by the [`EgressProxy`], which a budget turns on whenever there is
+/// an allowlist, as a socket patch can't see traffic below Python. With
+/// `max_bytes` set the guard only lets code reach the proxy, so traffic to
+/// allowlisted hosts can't go around the meter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkBudget {
+    /// Outbound connections the code may open
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+    /// Bytes the code may send and receive, together
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+/// Traffic through a proxy, checked against its budget
+#[derive(Debug, Default)]
+struct Meter {
+    budget: NetworkBudget,
+    connections: AtomicU32,
+    bytes: AtomicU64,
+    exhausted: AtomicBool,
+}
+
+impl Meter {
+    /// Count a new connection, false once over budget
+    fn open_connection(&self) -> bool {
+        let opened = self.connections.fetch_add(1, Ordering::Relaxed) + 1;
+        self.within(self.budget.max_connections.is_none_or(|max| opened <= max))
+    }
+
+    /// Count `n` transferred bytes, false once over budget
+    fn transfer(&self, n: usize) -> bool {
+        let total = self.bytes.fetch_add(n as u64, Ordering::Relaxed) + n as u64;
+        self.within(self.budget.max_bytes.is_none_or(|max| total <= max))
+    }
+
+    fn within(&self, ok: bool) -> bool {
+        if !ok {
+            self.exhausted.store(true, Ordering::Relaxed);
+        }
+        ok
+    }
+}
+
+/// What a proxy checks connections against
+struct Gate {
+    allowlist: Vec<String>,
+    meter: Meter,
+}
+
+/// Allowlisting HTTP(S)/SOCKS5 proxy on `127.0.0.1`, stopped when dropped
+pub(crate) struct EgressProxy {
+    addr: SocketAddr,
+    gate: Arc<Gate>,
+    task: JoinHandle<()>,
+}
+
+impl EgressProxy {
+    /// Start a proxy only allowing connections to `allowlist` hosts, within
+    /// `budget`
+    pub(crate) async fn start(allowlist: &[String], budget: NetworkBudget) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let gate = Arc::new(Gate {
+            allowlist: allowlist.to_vec(),
+            meter: Meter {
+                budget,
+                ..Default::default()
+            },
+        });
+        let shared = gate.clone();
+        info!("[EGRESS] Proxy listening on {}", addr);
+        let task = tokio::spawn(async move {
+            // Dropping the set when the proxy stops closes open tunnels too
+            let mut connections = JoinSet::new();
+            loop {
+                let client = match listener.accept().await {
+                    Ok((client, _)) => client,
+                    Err(e) => {
+                        warn!("[EGRESS] Accept failed: {}", e);
+                        continue;
+                    }
+                };
+                while connections.try_join_next().is_some() {}
+                let gate = shared.clone();
+                connections.spawn(async move {
+                    if let Err(e) = serve(client, &gate).await {
+                        debug!("[EGRESS] Connection ended: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self { addr, gate, task })
+    }
+
+    /// Proxy for a run, when `options` asks for proxy enforcement of an
+    /// allowlist or sets a [`NetworkBudget`] for it
+    pub(crate) async fn for_options(options: &ExecutionOptions) -> Result<Option<Self>> {
+        let Some(allowlist) = &options.network_allowlist else {
+            return Ok(None);
+        };
+        if options.network_enforcement != NetworkEnforcement::Proxy
+            && options.network_budget.is_none()
+        {
+            return Ok(None);
+        }
+        Ok(Some(
+            Self::start(allowlist, options.network_budget.unwrap_or_default()).await?,
+        ))
+    }
+
+    /// Record the traffic that went through the proxy as `network_usage`
+    pub(crate) fn report(&self, output: &mut serde_json::Value) {
+        let meter = &self.gate.meter;
+        if let Some(output) = output.as_object_mut() {
+            output.insert(
+                "network_usage".to_string(),
+                serde_json::json!({
+                    "connections": meter.connections.load(Ordering::Relaxed),
+                    "bytes": meter.bytes.load(Ordering::Relaxed),
+                    "budget_exhausted": meter.exhausted.load(Ordering::Relaxed),
+                }),
+            );
+        }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Point a child at the proxy, overriding any proxy settings it was given
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        let http = format!("http://{}", self.addr);
+        let socks = format!("socks5h://{}", self.addr);
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            cmd.env(var, &http);
+        }
+        for var in ["ALL_PROXY", "all_proxy"] {
+            cmd.env(var, &socks);
+        }
+        for var in ["NO_PROXY", "no_proxy"] {
+            cmd.env_remove(var);
+        }
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Options for the wrapper's in-process guard, which must also let the child
+/// reach the proxy, and only on its port
+///
+/// A byte budget is only metered by the proxy, so with one the guard allows
+/// nothing but the proxy and allowlisted hosts can't be dialed directly.
+pub(crate) fn guard_options<'a>(
+    options: &'a ExecutionOptions,
+    proxy: Option<&EgressProxy>,
+) -> Cow<'a, ExecutionOptions> {
+    match proxy {
+        Some(proxy) => {
+            let mut options = options.clone();
+            let allowlist = options.network_allowlist.get_or_insert_with(Vec::new);
+            if options
+                .network_budget
+                .is_some_and(|budget| budget.max_bytes.is_some())
+            {
+                allowlist.clear();
+            }
+            allowlist.push(proxy.addr.to_string());
+            Cow::Owned(options)
+        }
+        None => Cow::Borrowed(options),
+    }
+}
+
+/// Whether an allowlist entry allows `host` on `port`, see [`NetworkRule`]
+pub(crate) fn host_allowed(allowlist: &[String], host: &str, port: u16) -> bool {
+    allowlist
+        .iter()
+        .any(|entry| NetworkRule::parse(entry).matches(host, port))
+}
+
+async fn serve(client: TcpStream, gate: &Gate) -> std::io::Result<()> {
+    let mut first = [0u8; 1];
+    if client.peek(&mut first).await? == 0 {
+        return Ok(());
+    }
+    if first[0] == 0x05 {
+        socks5(client, gate).await
+    } else {
+        http(client, gate).await
+    }
+}
+
+async fn http(client: TcpStream, gate: &Gate) -> std::io::Result<()> {
+    let mut reader = BufReader::new(client);
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") && !head.ends_with(b"\n\n") {
+        if reader.read_until(b'\n', &mut head).await? == 0 {
+            return Ok(());
+        }
+        if head.len() > MAX_HEAD_BYTES {
+            return respond(reader.get_mut(), "431 Request Header Fields Too Large").await;
+        }
+    }
+    // Bytes the client sent past the head, e.g. a request body
+    let pending = reader.buffer().to_vec();
+    let mut client = reader.into_inner();
+
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return respond(&mut client, "400 Bad Request").await;
+    };
+
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let Some((host, port)) = split_host_port(target, 443) else {
+            return respond(&mut client, "400 Bad Request").await;
+        };
+        if !host_allowed(&gate.allowlist, &host, port) {
+            info!("[EGRESS] Denied CONNECT to {}", host);
+            return respond(&mut client, "403 Forbidden").await;
+        }
+        if !gate.meter.open_connection() {
+            info!("[EGRESS] Connection budget exhausted");
+            return respond(&mut client, "429 Too Many Requests").await;
+        }
+        let Ok(upstream) = TcpStream::connect((host.as_str(), port)).await else {
+            return respond(&mut client, "502 Bad Gateway").await;
+        };
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+        return relay(client, upstream, pending, &gate.meter).await;
+    }
+
+    // Plain HTTP arrives with an absolute URI; HTTPS goes through CONNECT
+    let Some(rest) = target.strip_prefix("http://") else {
+        return respond(&mut client, "400 Bad Request").await;
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let Some((host, port)) = split_host_port(authority, 80) else {
+        return respond(&mut client, "400 Bad Request").await;
+    };
+    if !host_allowed(&gate.allowlist, &host, port) {
+        info!("[EGRESS] Denied request to {}", host);
+        return respond(&mut client, "403 Forbidden").await;
+    }
+    if !gate.meter.open_connection() {
+        info!("[EGRESS] Connection budget exhausted");
+        return respond(&mut client, "429 Too Many Requests").await;
+    }
+    let Ok(upstream) = TcpStream::connect((host.as_str(), port)).await else {
+        return respond(&mut client, "502 Bad Gateway").await;
+    };
+    let mut forwarded = format!("{} {} {}\r\n", method, path, version);
+    for line in lines.filter(|line| !line.is_empty()) {
+        let name = line
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if name.starts_with("proxy-") || name == "connection" || name == "keep-alive" {
+            continue;
+        }
+        forwarded.push_str(line);
+        forwarded.push_str("\r\n");
+    }
+    // One request per connection keeps every request behind the host check
+    forwarded.push_str("Connection: close\r\n\r\n");
+    let mut head = forwarded.into_bytes();
+    head.extend_from_slice(&pending);
+    relay(client, upstream, head, &gate.meter).await
+}
+
+async fn socks5(mut client: TcpStream, gate: &Gate) -> std::io::Result<()> {
+    let mut greeting = [0u8; 2];
+    client.read_exact(&mut greeting).await?;
+    let mut methods = vec![0u8; greeting[1] as usize];
+    client.read_exact(&mut methods).await?;
+    if !methods.contains(&0) {
+        return client.write_all(&[5, 0xff]).await;
+    }
+    client.write_all(&[5, 0]).await?;
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request).await?;
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let mut len = [0u8; 1];
+            client.read_exact(&mut len).await?;
+            let mut name = vec![0u8; len[0] as usize];
+            client.read_exact(&mut name).await?;
+            String::from_utf8_lossy(&name).into_owned()
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            client.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        _ => return client.write_all(&socks_reply(8)).await,
+    };
+    let mut port = [0u8; 2];
+    client.read_exact(&mut port).await?;
+    let port = u16::from_be_bytes(port);
+
+    // Only CONNECT; BIND and UDP ASSOCIATE would open other paths out
+    if request[1] != 1 {
+        return client.write_all(&socks_reply(7)).await;
+    }
+    if !host_allowed(&gate.allowlist, &host, port) {
+        info!("[EGRESS] Denied SOCKS connection to {}", host);
+        return client.write_all(&socks_reply(2)).await;
+    }
+    if !gate.meter.open_connection() {
+        info!("[EGRESS] Connection budget exhausted");
+        return client.write_all(&socks_reply(2)).await;
+    }
+    let Ok(upstream) = TcpStream::connect((host.as_str(), port)).await else {
+        return client.write_all(&socks_reply(5)).await;
+    };
+    client.write_all(&socks_reply(0)).await?;
+    relay(client, upstream, Vec::new(), &gate.meter).await
+}
+
+/// Copy between client and upstream, starting with `pending` client bytes,
+/// until both sides close or the byte budget runs out
+async fn relay(
+    mut client: TcpStream,
+    mut upstream: TcpStream,
+    pending: Vec<u8>,
+    meter: &Meter,
+) -> std::io::Result<()> {
+    if !meter.transfer(pending.len()) {
+        return Err(budget_exhausted());
+    }
+    upstream.write_all(&pending).await?;
+    let (mut client_read, mut client_write) = client.split();
+    let (mut upstream_read, mut upstream_write) = upstream.split();
+    tokio::try_join!(
+        pump(&mut client_read, &mut upstream_write, meter),
+        pump(&mut upstream_read, &mut client_write, meter),
+    )?;
+    Ok(())
+}
+
+async fn pump(
+    from: &mut (impl AsyncRead + Unpin),
+    to: &mut (impl AsyncWrite + Unpin),
+    meter: &Meter,
+) -> std::io::Result<()> {
+    let mut buf = vec![0u8; 16 * 1024];
+    loop {
+        let n = from.read(&mut buf).await?;
+        if n == 0 {
+            return to.shutdown().await;
+        }
+        if !meter.transfer(n) {
+            info!("[EGRESS] Byte budget exhausted");
+            return Err(budget_exhausted());
+        }
+        to.write_all(&buf[..n]).await?;
+    }
+}
+
+fn budget_exhausted() -> std::io::Error {
+    std::io::Error::other("network budget exhausted")
+}
+
+/// SOCKS5 reply with `code` and an unspecified bound address
+fn socks_reply(code: u8) -> [u8; 10] {
+    [5, code, 0, 1, 0, 0, 0, 0, 0, 0]
+}
+
+/// Split `host:port`, `[v6]:port` or a bare host
+fn split_host_port(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None => default_port,
+        };
+        return Some((host.to_string(), port));
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Some((host.to_string(), port.parse().ok()?)),
+        None => Some((authority.to_string(), default_port)),
+    }
+    .filter(|(host, _)| !host.is_empty())
+}
+
+async fn respond(client: &mut TcpStream, status: &str) -> std::io::Result<()> {
+    let body = format!("pysandbox egress proxy: {}\n", status);
+    client
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Port of a local server echoing what it's sent
+    async fn echo_server() -> u16 {
+        let upstream = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = upstream.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = upstream.accept().await {
+                tokio::spawn(async move {
+                    let (mut read, mut write) = stream.split();
+                    let _ = tokio::io::copy(&mut read, &mut write).await;
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_egress_proxy_enforces_allowlist() {
+        let port = echo_server().await;
+        let proxy = EgressProxy::start(
+            &["127.0.0.1".to_string(), "*.example.com".to_string()],
+            NetworkBudget::default(),
+        )
+        .await
+        .unwrap();
+        let connect = |host: &str| format!("CONNECT {}:{} HTTP/1.1\r\n\r\n", host, port);
+
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client
+            .write_all(connect("127.0.0.1").as_bytes())
+            .await
+            .unwrap();
+        let mut reply = [0u8; 39];
+        client.read_exact(&mut reply).await.unwrap();
+        assert!(reply.starts_with(b"HTTP/1.1 200"));
+        client.write_all(b"ping").await.unwrap();
+        let mut echo = [0u8; 4];
+        client.read_exact(&mut echo).await.unwrap();
+        assert_eq!(&echo, b"ping");
+
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client
+            .write_all(connect("localhost").as_bytes())
+            .await
+            .unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).await.unwrap();
+        assert!(reply.starts_with("HTTP/1.1 403"));
+
+        // SOCKS5 by name: not on the allowlist
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client.write_all(&[5, 1, 0]).await.unwrap();
+        let mut method = [0u8; 2];
+        client.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, [5, 0]);
+        let mut request = vec![5, 1, 0, 3, 8];
+        request.extend_from_slice(b"evil.com");
+        request.extend_from_slice(&port.to_be_bytes());
+        client.write_all(&request).await.unwrap();
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[1], 2);
+
+        assert!(host_allowed(
+            &["*.example.com".to_string()],
+            "Example.COM.",
+            80
+        ));
+        assert!(!host_allowed(
+            &["*.example.com".to_string()],
+            "badexample.com",
+            80
+        ));
+
+        let profile = crate::seatbelt::render_proxied_profile(
+            &crate::SandboxPolicy::balanced(),
+            proxy.port(),
+        );
+        assert!(profile.contains(&format!("(remote ip \"localhost:{}\")", proxy.port())));
+        assert!(!profile.contains("(allow network-outbound)\n"));
+    }
+
+    #[tokio::test]
+    async fn test_egress_budget_caps_connections_and_bytes() {
+        let port = echo_server().await;
+        let budget = NetworkBudget {
+            max_connections: Some(1),
+            max_bytes: Some(8),
+        };
+        let proxy = EgressProxy::start(&["127.0.0.1".to_string()], budget)
+            .await
+            .unwrap();
+        let connect = format!("CONNECT 127.0.0.1:{} HTTP/1.1\r\n\r\n", port);
+
+        let mut client = TcpStream::connect(proxy.addr).await.unwrap();
+        client.write_all(connect.as_bytes()).await.unwrap();
+        let mut reply = [0u8; 39];
+        client.read_exact(&mut reply).await.unwrap();
+        // 4 bytes out and 4 echoed back use up the budget
+        client.write_all(b"ping").await.unwrap();
+        let mut echo = [0u8; 4];
+        client.read_exact(&mut echo).await.unwrap();
+        client.write_all(b"more").await.unwrap();
+        let mut rest = Vec::new();
+        let _ = client.read_to_end(&mut rest).await;
+        assert!(rest.is_empty());
+
+        let mut second = TcpStream::connect(proxy.addr).await.unwrap();
+        second.write_all(connect.as_bytes()).await.unwrap();
+        let mut reply = String::new();
+        second.read_to_string(&mut reply).await.unwrap();
+        assert!(reply.starts_with("HTTP/1.1 429"));
+
+        let mut output = serde_json::json!({});
+        proxy.report(&mut output);
+        assert_eq!(output["network_usage"]["connections"], 2);
+        assert_eq!(output["network_usage"]["budget_exhausted"], true);
+    }
+}
@@ -1,6 +1,7 @@
-use crate::errors::Result;
+use crate::errors::{Result, SandboxError};
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::PathBuf;
 use std::time::Duration;
 
 /// Options for Python code execution
@@ -10,14 +11,65 @@ pub struct ExecutionOptions {
     pub memory_mb: usize,
     /// Maximum CPU time in seconds
     pub cpu_seconds: u64,
-    /// Wall clock timeout
+    /// Wall clock timeout for running the code, from interpreter start
     pub timeout: Duration,
+    /// Budget for preparing the run before the code starts: validation,
+    /// staging inputs and warming up the interpreter. `None` allows as long
+    /// as `timeout`, without taking from it
+    #[serde(default)]
+    pub setup_timeout: Option<Duration>,
     /// Import policy
     pub import_policy: crate::config::ImportPolicy,
-    /// Optional outbound host allowlist (exact host or `*.domain` suffix entries)
+    /// Optional outbound host allowlist: exact hosts, `*.domain` suffixes or
+    /// address ranges, optionally with a port, see [`crate::NetworkRule`]
     pub network_allowlist: Option<Vec<String>>,
+    /// How the allowlist is enforced, see [`crate::egress`]
+    #[serde(default)]
+    pub network_enforcement: crate::egress::NetworkEnforcement,
+    /// Caps on connections and bytes transferred (native and sandboxed engines)
+    #[serde(default)]
+    pub network_budget: Option<crate::egress::NetworkBudget>,
+    /// Refuse to load `ctypes`, `cffi` and C extension modules unless their
+    /// package is in the import whitelist
+    #[serde(default)]
+    pub block_native_extensions: bool,
+    /// Caps on source size and syntax tree complexity checked by `validate`
+    #[serde(default)]
+    pub code_limits: crate::config::CodeLimits,
     /// Environment variables to set
     pub env_vars: std::collections::HashMap<String, String>,
+    /// Files copied in before the run as `(source_path, name)`; code finds
+    /// them under `INPUT_DIR`. Host paths, so never sent to remote engines
+    #[serde(skip)]
+    pub input_files: Vec<(std::path::PathBuf, String)>,
+    /// Engine to run on first, by its registry key or capabilities name (see
+    /// `PythonSandbox::with_engine_key`); fallback continues from there.
+    /// `None` uses the sandbox's primary engine.
+    #[serde(default)]
+    pub preferred_engine: Option<String>,
+    /// Queue order in a [`crate::SandboxPool`]
+    #[serde(default)]
+    pub priority: crate::sandbox_pool::Priority,
+    /// How this call uses the sandbox's [`crate::cache::ExecutionCache`]
+    #[serde(default)]
+    pub cache: crate::cache::CacheControl,
+    /// Data for the code's standard input, see [`crate::stdin`]
+    #[serde(skip)]
+    pub stdin: Option<crate::stdin::StdinSource>,
+    /// Receives progress events while the code runs (local process engines)
+    #[serde(skip)]
+    pub events: Option<crate::events::EventSender>,
+    /// Aborts the execution when cancelled, killing the interpreter's process group
+    #[serde(skip)]
+    pub cancellation: Option<tokio_util::sync::CancellationToken>,
+    /// Time between SIGINT and SIGKILL when stopping on timeout or
+    /// cancellation, so `finally` blocks can run; zero kills immediately
+    #[serde(default = "default_kill_grace_period")]
+    pub kill_grace_period: Duration,
+}
+
+fn default_kill_grace_period() -> Duration {
+    Duration::from_secs(2)
 }
 
 impl Default for ExecutionOptions {
@@ -26,13 +78,165 @@ impl Default for ExecutionOptions {
             memory_mb: 2048,
             cpu_seconds: 30,
             timeout: Duration::from_secs(35),
+            setup_timeout: None,
             import_policy: crate::config::ImportPolicy::default(),
             network_allowlist: None,
+            network_enforcement: crate::egress::NetworkEnforcement::default(),
+            network_budget: None,
+            block_native_extensions: false,
+            code_limits: crate::config::CodeLimits::default(),
             env_vars: std::collections::HashMap::new(),
+            input_files: Vec::new(),
+            stdin: None,
+            preferred_engine: None,
+            priority: crate::sandbox_pool::Priority::default(),
+            cache: crate::cache::CacheControl::default(),
+            events: None,
+            cancellation: None,
+            kill_grace_period: default_kill_grace_period(),
+        }
+    }
+}
+
+impl ExecutionOptions {
+    /// Time allowed for setup, see [`ExecutionOptions::setup_timeout`]
+    pub fn setup_budget(&self) -> Duration {
+        self.setup_timeout.unwrap_or(self.timeout)
+    }
+
+    /// Options as sent to a remote server, without the local engine choice
+    #[cfg(any(feature = "remote-engine", feature = "grpc"))]
+    pub(crate) fn to_forwarded_value(&self) -> serde_json::Result<serde_json::Value> {
+        let mut options = serde_json::to_value(self)?;
+        if let Some(options) = options.as_object_mut() {
+            options.remove("preferred_engine");
+        }
+        Ok(options)
+    }
+}
+
+/// Resource usage of one execution, returned as `metrics` in the payload
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionMetrics {
+    /// Wall-clock time from spawn to exit
+    pub wall_time_ms: u64,
+    /// Time spent before the code started, counted against
+    /// [`ExecutionOptions::setup_timeout`]
+    #[serde(default)]
+    pub setup_ms: Option<u64>,
+    /// User CPU time of the interpreter and the children it waited for
+    pub user_cpu_ms: Option<u64>,
+    /// System CPU time of the interpreter and the children it waited for
+    pub system_cpu_ms: Option<u64>,
+    /// Peak resident set size
+    pub max_rss_kb: Option<u64>,
+    /// Exit code, if the interpreter exited normally
+    pub exit_code: Option<i32>,
+    /// Terminating signal, if the interpreter was killed
+    pub signal: Option<i32>,
+}
+
+/// Output of a successful execution
+///
+/// Fields an engine reports beyond these, such as `exported_files`,
+/// `displays` or `transcript_id`, are kept in `extra`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    /// Captured stdout, if anything was printed
+    pub stdout: Option<String>,
+    /// Captured stderr, if anything was printed
+    pub stderr: Option<String>,
+    /// The `result` variable, if the code set one
+    pub result: Option<serde_json::Value>,
+    /// Files written to the workspace's `output` directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<ExecutionMetrics>,
+    /// Workspace the code ran in, as the interpreter saw it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<PathBuf>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ExecutionResult {
+    /// Parse the JSON output of an engine's wrapper
+    ///
+    /// The wrapper's `error` field is dropped, since failures are returned as
+    /// errors instead.
+    pub fn from_value(mut value: serde_json::Value) -> Result<Self> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+        if let Some(object) = value.as_object_mut() {
+            object.remove("error");
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Back to the JSON shape engines report
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Deserialize `result`, loading it from its file if it was spilled
+    ///
+    /// A missing `result` deserializes from `null`, so `Option<T>` accepts it.
+    pub fn result_as<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_value(crate::result_file::read_result(
+            self,
+        )?)?)
+    }
+}
+
+/// Whether a diagnostic blocks execution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Reported, but the code still runs
+    Warning,
+    /// The code is refused
+    #[default]
+    Error,
+}
+
+/// A problem found while validating code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Rule that produced the diagnostic, e.g. `syntax_error` or `blocked_import`
+    pub rule: String,
+    pub message: String,
+    /// 1-based line, if known
+    pub line: Option<usize>,
+    /// 1-based column, if known
+    pub col: Option<usize>,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    /// Whether this diagnostic refuses execution
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Convert into the error `execute` returns for it
+    pub fn into_error(self) -> SandboxError {
+        match self.rule.as_str() {
+            "syntax_error" => SandboxError::SyntaxError(self.message),
+            _ => SandboxError::DisallowedOperation(self.message),
         }
     }
 }
 
+/// Fail with the first blocking diagnostic, if any
+pub fn ensure_valid(diagnostics: Vec<Diagnostic>) -> Result<()> {
+    match diagnostics.into_iter().find(Diagnostic::is_error) {
+        Some(diagnostic) => Err(diagnostic.into_error()),
+        None => Ok(()),
+    }
+}
+
 /// Capabilities of a Python execution engine
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EngineCapabilities {
@@ -56,7 +260,10 @@ pub struct EngineCapabilities {
 #[async_trait]
 pub trait PythonEngine: Send + Sync {
     /// Validate code before execution
-    async fn validate(&self, code: &str, options: &ExecutionOptions) -> Result<()>;
+    ///
+    /// Returns every problem found; `Err` means validation itself failed.
+    /// Diagnostics with [`Severity::Error`] make `execute` refuse the code.
+    async fn validate(&self, code: &str, options: &ExecutionOptions) -> Result<Vec<Diagnostic>>;
 
     /// Execute Python code
     async fn execute(
@@ -64,11 +271,28 @@ pub trait PythonEngine: Send + Sync {
         code: &str,
         inputs: serde_json::Value,
         options: &ExecutionOptions,
-    ) -> Result<serde_json::Value>;
+    ) -> Result<ExecutionResult>;
 
     /// Get engine capabilities
     fn capabilities(&self) -> EngineCapabilities;
 
+    /// Pre-import `modules` in long-lived interpreters so the first execution
+    /// doesn't pay for them
+    ///
+    /// Engines that start a fresh interpreter per execution ignore it.
+    async fn warmup(&mut self, _modules: &[&str]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check that the engine can run code, e.g. that its interpreter or
+    /// server is reachable
+    ///
+    /// Used to lift a quarantine, see [`crate::health`]. Should be cheap, as
+    /// it runs periodically.
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Shutdown the engine
     async fn shutdown(&mut self) -> Result<()>;
 }
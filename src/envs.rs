@@ -0,0 +1,1161 @@
+//! Managed Python environments
+//!
+//! A [`ManagedEnvRegistry`] keeps named virtual environments (or micromamba
+//! prefixes) as directories under one base dir, each with an `rzn_env.json`
+//! recording how it was made. Hosts create, list, resolve and delete them by
+//! alias and run code with the resolved interpreter, e.g. through
+//! `NativePythonEngine::with_python_path`. The `rzn-python-worker` binary
+//! serves its `python_env.*` tools from the same registry.
+//!
+//! For reproducibility an env's packages can be pinned with
+//! [`ManagedEnvRegistry::export_lockfile`] and rebuilt elsewhere, from that
+//! lockfile or a `uv.lock`, with [`ManagedEnvRegistry::create_from_lockfile`].
+
+use crate::conda::{self, CondaEnv};
+use crate::errors::{Result, SandboxError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+
+/// Environment variable overriding the base dir of managed envs
+pub const ENVS_DIR_ENV: &str = "RZN_PYTHON_ENVS_DIR";
+
+/// File inside each env holding its [`ManagedEnvMetadata`]
+pub const METADATA_FILE_NAME: &str = "rzn_env.json";
+
+/// File inside each env pinning its installed packages
+pub const LOCKFILE_NAME: &str = "requirements.lock";
+
+const DEFAULT_ENVS_DIR_NAME: &str = "python_envs";
+
+/// Check an alias is usable as a directory name, returning it trimmed
+///
+/// Aliases are 1-64 characters of `[a-zA-Z0-9._-]` and not all dots.
+pub fn validate_alias(raw: &str) -> Result<String> {
+    let alias = raw.trim();
+    let invalid = |message: &str| Err(SandboxError::InvalidArgument(message.to_string()));
+    if alias.is_empty() {
+        return invalid("python_env alias must be non-empty");
+    }
+    if alias.len() > 64 {
+        return invalid("python_env alias is too long (max 64 chars)");
+    }
+    if !alias
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return invalid("python_env alias may only contain [a-zA-Z0-9._-]");
+    }
+    if alias.chars().all(|c| c == '.') {
+        return invalid("python_env alias must not be '.' or '..'");
+    }
+    Ok(alias.to_string())
+}
+
+/// Interpreter of the env at `env_dir`, whether or not it exists
+pub fn python_path(env_dir: &Path) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        let venv_python = env_dir.join("Scripts").join("python.exe");
+        if venv_python.exists() {
+            return venv_python;
+        }
+        // Conda prefixes keep the interpreter at the root
+        env_dir.join("python.exe")
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let python3 = env_dir.join("bin").join("python3");
+        if python3.exists() {
+            return python3;
+        }
+        env_dir.join("bin").join("python")
+    }
+}
+
+/// How a managed env was created, stored as [`METADATA_FILE_NAME`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedEnvMetadata {
+    pub alias: String,
+    pub env_dir: String,
+    pub python_path: String,
+    pub base_python: Option<String>,
+    pub created_at_unix_seconds: u64,
+    /// `pip`, `uv` or `micromamba`; absent for envs created before this was
+    /// recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_manager: Option<String>,
+    /// Conda channels of micromamba envs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<String>,
+    /// When the env last ran code, see [`ManagedEnvRegistry::touch`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_unix_seconds: Option<u64>,
+}
+
+impl ManagedEnvMetadata {
+    /// Metadata of the env at `env_dir`, if it has readable metadata
+    pub fn read(env_dir: &Path) -> Option<Self> {
+        let raw = std::fs::read_to_string(env_dir.join(METADATA_FILE_NAME)).ok()?;
+        serde_json::from_str(&raw).ok()
+    }
+
+    /// Replace the env's metadata file in one rename, so readers never see
+    /// a partly written one. Hold [`metadata_lock`] for the env around it.
+    fn write(&self, env_dir: &Path) -> Result<()> {
+        let serialized = serde_json::to_string_pretty(self)?;
+        let persist = || -> std::io::Result<()> {
+            let mut tmp = tempfile::Builder::new()
+                .prefix(".rzn_env.")
+                .tempfile_in(env_dir)?;
+            std::io::Write::write_all(&mut tmp, serialized.as_bytes())?;
+            tmp.persist(env_dir.join(METADATA_FILE_NAME))?;
+            Ok(())
+        };
+        persist().map_err(|e| {
+            SandboxError::InternalError(format!("Failed to persist env metadata: {}", e))
+        })
+    }
+}
+
+/// Per-env locks serializing metadata updates, which read, modify and
+/// rewrite a file
+static METADATA_LOCKS: Mutex<BTreeMap<PathBuf, Arc<Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+/// The metadata lock for the env at `env_dir`
+fn metadata_lock(env_dir: &Path) -> Arc<Mutex<()>> {
+    METADATA_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(env_dir.to_path_buf())
+        .or_default()
+        .clone()
+}
+
+/// Tool that creates managed envs and installs into them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvManager {
+    /// `python -m venv` and `python -m pip`
+    Pip,
+    /// `uv venv` and `uv pip`, with the uv executable found in PATH
+    Uv(PathBuf),
+    /// Conda prefix envs created with micromamba; `pip` runs the env's own
+    Micromamba(PathBuf),
+}
+
+impl EnvManager {
+    /// `auto`, `uv`, `pip` or `micromamba`; auto picks uv when it is in PATH
+    /// and pip otherwise
+    pub fn from_name(requested: &str) -> Result<Self> {
+        let requested = requested.trim().to_ascii_lowercase();
+        if matches!(requested.as_str(), "micromamba" | "mamba" | "conda") {
+            return conda::find_micromamba()
+                .map(Self::Micromamba)
+                .ok_or_else(|| {
+                    SandboxError::InternalError(format!(
+                        "env_manager={} requested but micromamba was not found (install it or set {})",
+                        requested,
+                        conda::MICROMAMBA_ENV
+                    ))
+                });
+        }
+        Self::resolve(&requested, which::which("uv").ok())
+    }
+
+    /// Like [`from_name`](Self::from_name) for the venv managers, with uv at
+    /// `uv` if it is installed
+    pub fn resolve(requested: &str, uv: Option<PathBuf>) -> Result<Self> {
+        match (requested, uv) {
+            ("pip", _) => Ok(Self::Pip),
+            ("uv" | "auto", Some(uv)) => Ok(Self::Uv(uv)),
+            ("auto", None) => Ok(Self::Pip),
+            ("uv", None) => Err(SandboxError::InternalError(
+                "env_manager=uv requested but uv was not found in PATH".to_string(),
+            )),
+            (other, _) => Err(SandboxError::InvalidArgument(format!(
+                "Unknown env_manager '{}' (expected auto, uv, pip or micromamba)",
+                other
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pip => "pip",
+            Self::Uv(_) => "uv",
+            Self::Micromamba(_) => "micromamba",
+        }
+    }
+
+    /// Command creating a venv at `env_dir` from `base_python`
+    ///
+    /// Micromamba envs are conda prefixes, created with [`CondaEnv`] instead.
+    pub fn venv_command(&self, base_python: &Path, env_dir: &Path, without_pip: bool) -> Command {
+        match self {
+            Self::Pip | Self::Micromamba(_) => {
+                let mut cmd = Command::new(base_python);
+                cmd.arg("-m").arg("venv").arg(env_dir);
+                if without_pip {
+                    cmd.arg("--without-pip");
+                }
+                cmd
+            }
+            Self::Uv(uv) => {
+                let mut cmd = Command::new(uv);
+                cmd.arg("venv").arg("--python").arg(base_python);
+                // uv venvs have no pip unless seeded
+                if !without_pip {
+                    cmd.arg("--seed");
+                }
+                cmd.arg(env_dir);
+                cmd
+            }
+        }
+    }
+
+    /// Command running pip `subcommand` against the env owning `python_path`
+    pub fn pip_command(&self, python_path: &Path, subcommand: &str) -> Command {
+        match self {
+            Self::Pip | Self::Micromamba(_) => {
+                let mut cmd = Command::new(python_path);
+                cmd.arg("-m").arg("pip").arg(subcommand);
+                cmd
+            }
+            Self::Uv(uv) => {
+                let mut cmd = Command::new(uv);
+                cmd.arg("pip")
+                    .arg(subcommand)
+                    .arg("--python")
+                    .arg(python_path);
+                cmd
+            }
+        }
+    }
+}
+
+/// Options for [`ManagedEnvRegistry::create`]
+#[derive(Debug, Clone, Default)]
+pub struct CreateEnvOptions {
+    /// Manager to create the env with; `None` is [`EnvManager::from_name`]
+    /// with `auto`
+    pub env_manager: Option<EnvManager>,
+    /// Interpreter venvs are made from; `None` is python3 from PATH
+    pub base_python: Option<PathBuf>,
+    /// Create the venv without pip
+    pub without_pip: bool,
+    /// Replace an existing env with the same alias
+    pub recreate: bool,
+    /// Python version of micromamba envs, e.g. `3.11`
+    pub python_version: Option<String>,
+    /// Conda packages installed into micromamba envs at creation
+    pub packages: Vec<String>,
+    /// Conda channels of micromamba envs
+    pub channels: Vec<String>,
+}
+
+/// A managed env found in the registry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedEnv {
+    pub alias: String,
+    pub env_dir: PathBuf,
+    pub python_path: PathBuf,
+    pub metadata: Option<ManagedEnvMetadata>,
+}
+
+impl ManagedEnv {
+    /// Whether the env still has its interpreter
+    pub fn is_healthy(&self) -> bool {
+        self.python_path.exists()
+    }
+
+    /// When the env last ran code, else when it was created, else when its
+    /// directory was last modified
+    pub fn last_used(&self) -> SystemTime {
+        let recorded = self.metadata.as_ref().map(|m| {
+            m.last_used_unix_seconds
+                .unwrap_or(m.created_at_unix_seconds)
+        });
+        match recorded {
+            Some(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+            None => std::fs::metadata(&self.env_dir)
+                .and_then(|m| m.modified())
+                .unwrap_or(UNIX_EPOCH),
+        }
+    }
+}
+
+/// Envs a [`ManagedEnvRegistry::prune`] removed, or would remove
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+    pub removed: Vec<ManagedEnv>,
+    pub freed_bytes: u64,
+}
+
+/// Named Python environments under one base directory
+#[derive(Debug, Clone)]
+pub struct ManagedEnvRegistry {
+    base_dir: PathBuf,
+}
+
+impl Default for ManagedEnvRegistry {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+impl ManagedEnvRegistry {
+    /// Registry of the envs under `base_dir`
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+
+    /// Registry at [`ENVS_DIR_ENV`], else `$RZN_APP_BASE_DIR/python_envs`,
+    /// else `~/.rzn/python_envs`, else under the temp dir
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        let base_dir = var(ENVS_DIR_ENV)
+            .or_else(|| var("RZN_APP_BASE_DIR").map(|dir| dir.join(DEFAULT_ENVS_DIR_NAME)))
+            .or_else(|| var("HOME").map(|home| home.join(".rzn").join(DEFAULT_ENVS_DIR_NAME)))
+            .unwrap_or_else(|| std::env::temp_dir().join("rzn").join(DEFAULT_ENVS_DIR_NAME));
+        Self::new(base_dir)
+    }
+
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Directory of the env named `alias`, whether or not it exists
+    pub fn env_dir(&self, alias: &str) -> Result<PathBuf> {
+        Ok(self.base_dir.join(validate_alias(alias)?))
+    }
+
+    /// The existing env named `alias`, which must have its interpreter
+    pub fn resolve(&self, alias: &str) -> Result<ManagedEnv> {
+        let alias = validate_alias(alias)?;
+        let env_dir = self.base_dir.join(&alias);
+        if !env_dir.exists() {
+            return Err(SandboxError::InternalError(format!(
+                "Managed python env '{}' not found",
+                alias
+            )));
+        }
+        let env = self.entry(alias, env_dir);
+        if !env.is_healthy() {
+            return Err(SandboxError::InternalError(format!(
+                "Managed python env '{}' is missing interpreter",
+                env.alias
+            )));
+        }
+        Ok(env)
+    }
+
+    /// All envs sorted by alias, including ones that lost their interpreter
+    /// if `include_broken`
+    pub fn list(&self, include_broken: bool) -> Result<Vec<ManagedEnv>> {
+        self.ensure_base_dir()?;
+        let entries = std::fs::read_dir(&self.base_dir).map_err(|e| {
+            SandboxError::InternalError(format!("Failed to read env base dir: {}", e))
+        })?;
+        let mut envs: Vec<ManagedEnv> = entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+            .map(|entry| {
+                self.entry(
+                    entry.file_name().to_string_lossy().to_string(),
+                    entry.path(),
+                )
+            })
+            .filter(|env| include_broken || env.is_healthy())
+            .collect();
+        envs.sort_by(|a, b| a.alias.cmp(&b.alias));
+        Ok(envs)
+    }
+
+    /// Create the env named `alias`
+    ///
+    /// An existing env is returned as is unless `options.recreate` is set.
+    /// Callers wanting a deadline can wrap this in `tokio::time::timeout`;
+    /// the commands it runs are killed when it is dropped, and the half-built
+    /// env is removed, as it is when creation fails.
+    pub async fn create(&self, alias: &str, options: &CreateEnvOptions) -> Result<ManagedEnv> {
+        let alias = validate_alias(alias)?;
+        let env_manager = match &options.env_manager {
+            Some(env_manager) => env_manager.clone(),
+            None => EnvManager::from_name("auto")?,
+        };
+        self.ensure_base_dir()?;
+
+        let env_dir = self.base_dir.join(&alias);
+        if env_dir.exists() {
+            if !options.recreate {
+                return self.resolve(&alias);
+            }
+            std::fs::remove_dir_all(&env_dir).map_err(|e| {
+                SandboxError::InternalError(format!(
+                    "Failed to remove existing env for recreate: {}",
+                    e
+                ))
+            })?;
+        }
+        let unfinished = UnfinishedEnv::new(&env_dir);
+
+        // Conda envs bring their own interpreter; venvs are made from a base one
+        let (create_cmd, base_python, channels) = match &env_manager {
+            EnvManager::Micromamba(micromamba) => {
+                let mut conda = options.channels.iter().fold(
+                    CondaEnv::with_micromamba(micromamba, &env_dir),
+                    |env, channel| env.channel(channel),
+                );
+                if let Some(version) = &options.python_version {
+                    conda = conda.python_version(version);
+                }
+                for spec in &options.packages {
+                    conda = conda.package(spec);
+                }
+                (conda.create_command(), None, conda.channels())
+            }
+            _ => {
+                let base_python = match &options.base_python {
+                    Some(path) => path.clone(),
+                    None => which::which("python3")
+                        .or_else(|_| which::which("python"))
+                        .map_err(|_| {
+                            SandboxError::InternalError(
+                                "Python not found in PATH for venv creation".to_string(),
+                            )
+                        })?,
+                };
+                let cmd = env_manager.venv_command(&base_python, &env_dir, options.without_pip);
+                (cmd, Some(base_python), Vec::new())
+            }
+        };
+        run(
+            create_cmd,
+            &format!("Failed to create managed env '{}'", alias),
+        )
+        .await?;
+
+        let python_path = python_path(&env_dir);
+        if !python_path.exists() {
+            return Err(SandboxError::InternalError(
+                "Venv created but python interpreter is missing".to_string(),
+            ));
+        }
+
+        // uv seeds pip itself when asked to
+        if !options.without_pip && env_manager == EnvManager::Pip {
+            let mut pip_check = Command::new(&python_path);
+            pip_check.arg("-m").arg("pip").arg("--version");
+            if run(pip_check, "pip check").await.is_err() {
+                let mut ensurepip = Command::new(&python_path);
+                ensurepip.arg("-m").arg("ensurepip").arg("--upgrade");
+                run(
+                    ensurepip,
+                    &format!("Managed env '{}' created but pip setup failed", alias),
+                )
+                .await?;
+            }
+        }
+
+        let metadata = ManagedEnvMetadata {
+            alias: alias.clone(),
+            env_dir: env_dir.to_string_lossy().to_string(),
+            python_path: python_path.to_string_lossy().to_string(),
+            base_python: base_python.map(|p| p.to_string_lossy().to_string()),
+            created_at_unix_seconds: unix_seconds(SystemTime::now()),
+            env_manager: Some(env_manager.name().to_string()),
+            channels,
+            last_used_unix_seconds: None,
+        };
+        {
+            let lock = metadata_lock(&env_dir);
+            let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+            metadata.write(&env_dir)?;
+        }
+        unfinished.finish();
+        Ok(ManagedEnv {
+            alias,
+            env_dir,
+            python_path,
+            metadata: Some(metadata),
+        })
+    }
+
+    /// Delete the env named `alias`, returning its directory
+    ///
+    /// Only a real directory directly inside the base dir is removed; a
+    /// symlinked alias could point anywhere.
+    pub fn delete(&self, alias: &str) -> Result<PathBuf> {
+        let alias = validate_alias(alias)?;
+        let env_dir = self.base_dir.join(&alias);
+        let Ok(file_type) = std::fs::symlink_metadata(&env_dir).map(|m| m.file_type()) else {
+            return Err(SandboxError::InternalError(format!(
+                "Managed python env '{}' not found",
+                alias
+            )));
+        };
+        let base_dir = std::fs::canonicalize(&self.base_dir).ok();
+        let inside_base = std::fs::canonicalize(&env_dir)
+            .ok()
+            .and_then(|dir| dir.parent().map(Path::to_path_buf))
+            .is_some_and(|parent| Some(parent) == base_dir);
+        if !file_type.is_dir() || !inside_base {
+            return Err(SandboxError::InternalError(format!(
+                "Refusing to delete '{}': not a managed env directory",
+                alias
+            )));
+        }
+        std::fs::remove_dir_all(&env_dir).map_err(|e| {
+            SandboxError::InternalError(format!("Failed to delete managed env: {}", e))
+        })?;
+        Ok(env_dir)
+    }
+
+    /// Pin everything installed in the env named `alias` to
+    /// [`LOCKFILE_NAME`] inside it, returning the lockfile's path
+    ///
+    /// Each pin carries the `--hash=sha256:` of the artifact the index (or
+    /// direct URL) serves for it, taken from `pip install --dry-run --report`
+    /// or `uv pip compile --generate-hashes`, so the lockfile needs the index
+    /// to be reachable. Packages installed from a directory or VCS checkout
+    /// have no artifact to hash and fail the export.
+    pub async fn export_lockfile(&self, alias: &str) -> Result<PathBuf> {
+        let env = self.resolve(alias)?;
+        let installer = installer(&env)?;
+        let frozen = run(
+            installer.pip_command(&env.python_path, "freeze"),
+            &format!("pip freeze failed for env '{}'", env.alias),
+        )
+        .await?;
+        let requirements = if frozen.trim().is_empty() {
+            frozen
+        } else {
+            let pins = tempfile::Builder::new()
+                .prefix(".rzn-freeze-")
+                .tempfile_in(&env.env_dir)?;
+            std::fs::write(pins.path(), &frozen)?;
+            let context = format!("Hashing the pins of env '{}' failed", env.alias);
+            match &installer {
+                EnvManager::Uv(_) => {
+                    let mut cmd = installer.pip_command(&env.python_path, "compile");
+                    cmd.arg(pins.path())
+                        .args(["--generate-hashes", "--no-deps", "--no-header"])
+                        .args(["--no-annotate", "--quiet"]);
+                    run(cmd, &context).await?
+                }
+                EnvManager::Pip | EnvManager::Micromamba(_) => {
+                    let mut cmd = installer.pip_command(&env.python_path, "install");
+                    cmd.args(["--dry-run", "--ignore-installed", "--no-deps", "--quiet"])
+                        .args(["--report", "-", "-r"])
+                        .arg(pins.path());
+                    requirements_from_pip_report(&run(cmd, &context).await?)?
+                }
+            }
+        };
+        let lockfile = env.env_dir.join(LOCKFILE_NAME);
+        std::fs::write(&lockfile, requirements)
+            .map_err(|e| SandboxError::InternalError(format!("Failed to write lockfile: {}", e)))?;
+        Ok(lockfile)
+    }
+
+    /// Create the env named `alias` with exactly the packages pinned in
+    /// `lockfile`, a requirements file or a `uv.lock`
+    ///
+    /// Dependencies aren't resolved again, so the env gets the locked
+    /// versions and nothing else. When the lock has hashes they are
+    /// required, so every pin must match a locked file. The pins are kept as [`LOCKFILE_NAME`] in the env; if
+    /// installing them fails the env is removed again.
+    pub async fn create_from_lockfile(
+        &self,
+        alias: &str,
+        lockfile: &Path,
+        options: &CreateEnvOptions,
+    ) -> Result<ManagedEnv> {
+        let raw = std::fs::read_to_string(lockfile).map_err(|e| {
+            SandboxError::InternalError(format!(
+                "Failed to read lockfile {}: {}",
+                lockfile.display(),
+                e
+            ))
+        })?;
+        let requirements = match LockfileFormat::from_path(lockfile) {
+            LockfileFormat::Requirements => raw,
+            LockfileFormat::UvLock => requirements_from_uv_lock(&raw)?,
+        };
+        if self.env_dir(alias)?.exists() && !options.recreate {
+            return Err(SandboxError::InvalidArgument(format!(
+                "Managed env '{}' already exists",
+                alias.trim()
+            )));
+        }
+
+        let env = self.create(alias, options).await?;
+        let unfinished = UnfinishedEnv::new(&env.env_dir);
+        let pinned = env.env_dir.join(LOCKFILE_NAME);
+        let install = async {
+            std::fs::write(&pinned, &requirements).map_err(|e| {
+                SandboxError::InternalError(format!("Failed to write lockfile: {}", e))
+            })?;
+            let mut cmd = installer(&env)?.pip_command(&env.python_path, "install");
+            cmd.arg("--no-deps").arg("-r").arg(&pinned);
+            if requirements.contains("--hash=") {
+                cmd.arg("--require-hashes");
+            }
+            run(
+                cmd,
+                &format!("Installing lockfile into env '{}' failed", env.alias),
+            )
+            .await
+        };
+        install.await?;
+        unfinished.finish();
+        Ok(env)
+    }
+
+    /// Record that the env named `alias` is running code now
+    ///
+    /// Envs created by hand get metadata for tracking, with their directory's
+    /// modification time as the creation time.
+    pub fn touch(&self, alias: &str) -> Result<()> {
+        let lock = metadata_lock(&self.env_dir(alias)?);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let env = self.resolve(alias)?;
+        let now = unix_seconds(SystemTime::now());
+        let mut metadata = match env.metadata {
+            Some(metadata) => metadata,
+            None => ManagedEnvMetadata {
+                alias: env.alias.clone(),
+                env_dir: env.env_dir.to_string_lossy().to_string(),
+                python_path: env.python_path.to_string_lossy().to_string(),
+                base_python: None,
+                created_at_unix_seconds: std::fs::metadata(&env.env_dir)
+                    .and_then(|m| m.modified())
+                    .map(unix_seconds)
+                    .unwrap_or(now),
+                env_manager: None,
+                channels: Vec::new(),
+                last_used_unix_seconds: None,
+            },
+        };
+        metadata.last_used_unix_seconds = Some(now);
+        metadata.write(&env.env_dir)
+    }
+
+    /// Delete the envs that haven't run code for `unused_for`
+    ///
+    /// With `dry_run` the envs are only reported. See
+    /// [`ManagedEnv::last_used`] for how use is judged.
+    pub fn prune(&self, unused_for: Duration, dry_run: bool) -> Result<PruneReport> {
+        let cutoff = SystemTime::now()
+            .checked_sub(unused_for)
+            .unwrap_or(UNIX_EPOCH);
+        let mut report = PruneReport::default();
+        for env in self.list(true)? {
+            // Directories named unlike aliases aren't the registry's to remove
+            if validate_alias(&env.alias).is_err() || env.last_used() > cutoff {
+                continue;
+            }
+            let bytes = disk_usage(&env.env_dir);
+            if !dry_run {
+                self.delete(&env.alias)?;
+            }
+            report.freed_bytes += bytes;
+            report.removed.push(env);
+        }
+        Ok(report)
+    }
+
+    fn entry(&self, alias: String, env_dir: PathBuf) -> ManagedEnv {
+        ManagedEnv {
+            alias,
+            python_path: python_path(&env_dir),
+            metadata: ManagedEnvMetadata::read(&env_dir),
+            env_dir,
+        }
+    }
+
+    fn ensure_base_dir(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.base_dir).map_err(|e| {
+            SandboxError::InternalError(format!("Failed to ensure env base dir exists: {}", e))
+        })
+    }
+}
+
+/// Total size of the files under `dir`, not following symlinks
+pub fn disk_usage(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(m) if m.is_dir() => disk_usage(&entry.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Run `cmd` for its stdout, failing with `context` and its stderr if it
+/// exits non-zero
+async fn run(mut cmd: Command, context: &str) -> Result<String> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| SandboxError::InternalError(format!("{}: {}", context, e)))?;
+    if !output.status.success() {
+        return Err(SandboxError::InternalError(format!(
+            "{}: {}",
+            context,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Installer for an existing env: uv for envs uv created, pip otherwise
+fn installer(env: &ManagedEnv) -> Result<EnvManager> {
+    match env.metadata.as_ref().and_then(|m| m.env_manager.as_deref()) {
+        Some("uv") => EnvManager::from_name("uv"),
+        _ => Ok(EnvManager::Pip),
+    }
+}
+
+/// Removes an env directory that is being built when dropped, unless
+/// [`finish`](Self::finish) was called, so a failed or cancelled build
+/// doesn't leave a broken env behind
+pub struct UnfinishedEnv(Option<PathBuf>);
+
+impl UnfinishedEnv {
+    pub fn new(env_dir: &Path) -> Self {
+        Self(Some(env_dir.to_path_buf()))
+    }
+
+    /// Keep the env
+    pub fn finish(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for UnfinishedEnv {
+    fn drop(&mut self) {
+        if let Some(env_dir) = self.0.take() {
+            let _ = std::fs::remove_dir_all(env_dir);
+        }
+    }
+}
+
+/// Format of a lockfile given to [`ManagedEnvRegistry::create_from_lockfile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockfileFormat {
+    /// pip requirements, e.g. `pip freeze` output or [`LOCKFILE_NAME`]
+    Requirements,
+    /// uv's project lockfile
+    UvLock,
+}
+
+impl LockfileFormat {
+    /// `uv.lock` by its file name, requirements otherwise
+    pub fn from_path(path: &Path) -> Self {
+        if path.file_name().is_some_and(|name| name == "uv.lock") {
+            Self::UvLock
+        } else {
+            Self::Requirements
+        }
+    }
+}
+
+/// Hash-pinned requirements for the registry packages of a `uv.lock`
+///
+/// The project itself (an editable or virtual source) is skipped; git, URL
+/// and path sources can't be pinned by hash and are rejected.
+pub fn requirements_from_uv_lock(raw: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct UvLock {
+        #[serde(default)]
+        package: Vec<UvPackage>,
+    }
+    #[derive(Deserialize)]
+    struct UvPackage {
+        name: String,
+        version: Option<String>,
+        #[serde(default)]
+        source: toml::Table,
+        sdist: Option<UvArtifact>,
+        #[serde(default)]
+        wheels: Vec<UvArtifact>,
+    }
+    #[derive(Deserialize)]
+    struct UvArtifact {
+        hash: Option<String>,
+    }
+
+    let lock: UvLock = toml::from_str(raw)
+        .map_err(|e| SandboxError::InvalidArgument(format!("Invalid uv.lock: {}", e)))?;
+    let mut requirements = String::new();
+    for package in lock.package {
+        if package.source.contains_key("editable") || package.source.contains_key("virtual") {
+            continue;
+        }
+        let (true, Some(version)) = (package.source.contains_key("registry"), &package.version)
+        else {
+            return Err(SandboxError::InvalidArgument(format!(
+                "uv.lock package '{}' is not from a registry and can't be pinned",
+                package.name
+            )));
+        };
+        requirements.push_str(&format!("{}=={}", package.name, version));
+        for hash in package
+            .sdist
+            .iter()
+            .chain(&package.wheels)
+            .filter_map(|artifact| artifact.hash.as_deref())
+        {
+            requirements.push_str(&format!(" \\\n    --hash={}", hash));
+        }
+        requirements.push('\n');
+    }
+    Ok(requirements)
+}
+
+/// Hash-pinned requirements for the packages a `pip install --report`
+/// would install
+///
+/// Index packages are pinned by version and direct references by URL;
+/// directory and VCS sources have no archive hash and are rejected.
+pub fn requirements_from_pip_report(raw: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct PipReport {
+        #[serde(default)]
+        install: Vec<PipInstall>,
+    }
+    #[derive(Deserialize)]
+    struct PipInstall {
+        download_info: PipDownload,
+        #[serde(default)]
+        is_direct: bool,
+        metadata: PipMetadata,
+    }
+    #[derive(Deserialize)]
+    struct PipDownload {
+        url: String,
+        archive_info: Option<PipArchive>,
+    }
+    #[derive(Deserialize)]
+    struct PipArchive {
+        #[serde(default)]
+        hashes: BTreeMap<String, String>,
+    }
+    #[derive(Deserialize)]
+    struct PipMetadata {
+        name: String,
+        version: String,
+    }
+
+    let report: PipReport = serde_json::from_str(raw)?;
+    let mut requirements = String::new();
+    for package in report.install {
+        let name = &package.metadata.name;
+        let Some(hash) = package
+            .download_info
+            .archive_info
+            .as_ref()
+            .and_then(|archive| archive.hashes.get("sha256"))
+        else {
+            return Err(SandboxError::InvalidArgument(format!(
+                "Package '{}' from {} has no archive hash and can't be pinned",
+                name, package.download_info.url
+            )));
+        };
+        if package.is_direct {
+            requirements.push_str(&format!("{} @ {}", name, package.download_info.url));
+        } else {
+            requirements.push_str(&format!("{}=={}", name, package.metadata.version));
+        }
+        requirements.push_str(&format!(" \\\n    --hash=sha256:{}\n", hash));
+    }
+    Ok(requirements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_managed_env_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ManagedEnvRegistry::new(dir.path().join("envs"));
+
+        assert_eq!(validate_alias(" team-alpha_1 ").unwrap(), "team-alpha_1");
+        assert!(matches!(
+            registry.env_dir("../escape"),
+            Err(SandboxError::InvalidArgument(_))
+        ));
+        assert!(registry.resolve("missing").is_err());
+
+        // A venv layout without metadata, and one that lost its interpreter
+        let bin = registry.env_dir("analysis").unwrap().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("python3"), "").unwrap();
+        std::fs::create_dir_all(registry.env_dir("broken").unwrap()).unwrap();
+
+        let aliases = |envs: Vec<ManagedEnv>| -> Vec<String> {
+            envs.into_iter().map(|env| env.alias).collect()
+        };
+        assert_eq!(
+            aliases(registry.list(true).unwrap()),
+            ["analysis", "broken"]
+        );
+        assert_eq!(aliases(registry.list(false).unwrap()), ["analysis"]);
+        let env = registry.resolve("analysis").unwrap();
+        assert_eq!(env.python_path, bin.join("python3"));
+        assert!(env.metadata.is_none());
+        assert!(registry.resolve("broken").is_err());
+
+        #[cfg(unix)]
+        {
+            let outside = dir.path().join("outside");
+            std::fs::create_dir(&outside).unwrap();
+            std::os::unix::fs::symlink(&outside, registry.env_dir("link").unwrap()).unwrap();
+            assert!(registry.delete("link").is_err());
+            assert!(outside.exists());
+        }
+        registry.delete("broken").unwrap();
+        assert!(registry.delete("broken").is_err());
+    }
+
+    #[test]
+    fn test_uv_lock_requirements() {
+        let lock = r#"
+version = 1
+requires-python = ">=3.11"
+
+[[package]]
+name = "analysis"
+version = "0.1.0"
+source = { virtual = "." }
+dependencies = [
+    { name = "idna" },
+]
+
+[[package]]
+name = "idna"
+version = "3.7"
+source = { registry = "https://pypi.org/simple" }
+sdist = { url = "https://files.example/idna-3.7.tar.gz", hash = "sha256:aa", size = 189575 }
+wheels = [
+    { url = "https://files.example/idna-3.7-py3-none-any.whl", hash = "sha256:bb", size = 66836 },
+]
+"#;
+        assert_eq!(
+            requirements_from_uv_lock(lock).unwrap(),
+            "idna==3.7 \\\n    --hash=sha256:aa \\\n    --hash=sha256:bb\n"
+        );
+        assert_eq!(
+            LockfileFormat::from_path(Path::new("/audit/uv.lock")),
+            LockfileFormat::UvLock
+        );
+        assert_eq!(
+            LockfileFormat::from_path(Path::new("requirements.lock")),
+            LockfileFormat::Requirements
+        );
+
+        let git = "[[package]]\nname = \"tool\"\nversion = \"1.0\"\nsource = { git = \"https://example.com/tool\" }\n";
+        assert!(matches!(
+            requirements_from_uv_lock(git),
+            Err(SandboxError::InvalidArgument(_))
+        ));
+    }
+    #[test]
+    fn test_pip_report_requirements() {
+        let report = r#"{"version": "1", "install": [
+            {"download_info": {"url": "https://files.example/idna-3.7-py3-none-any.whl",
+              "archive_info": {"hash": "sha256=bb", "hashes": {"sha256": "bb"}}},
+             "is_direct": false, "metadata": {"name": "idna", "version": "3.7"}},
+            {"download_info": {"url": "file:///wheels/tool-1.0-py3-none-any.whl",
+              "archive_info": {"hashes": {"sha256": "cc"}}},
+             "is_direct": true, "metadata": {"name": "tool", "version": "1.0"}}
+        ]}"#;
+        assert_eq!(
+            requirements_from_pip_report(report).unwrap(),
+            "idna==3.7 \\\n    --hash=sha256:bb\n\
+             tool @ file:///wheels/tool-1.0-py3-none-any.whl \\\n    --hash=sha256:cc\n"
+        );
+
+        let checkout = r#"{"install": [{"download_info": {"url": "file:///src/tool",
+            "dir_info": {}}, "is_direct": true, "metadata": {"name": "tool", "version": "1.0"}}]}"#;
+        assert!(matches!(
+            requirements_from_pip_report(checkout),
+            Err(SandboxError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lockfile_round_trip_requires_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ManagedEnvRegistry::new(dir.path().join("envs"));
+        let options = CreateEnvOptions {
+            env_manager: Some(EnvManager::Pip),
+            ..Default::default()
+        };
+        let Ok(source) = registry.create("source", &options).await else {
+            return;
+        };
+
+        // A local wheel, so nothing needs the network
+        let wheel = dir.path().join("rzn_demo-0.1-py3-none-any.whl");
+        let build = std::process::Command::new(&source.python_path)
+            .arg("-c")
+            .arg(
+                "import sys, zipfile\n\
+                 with zipfile.ZipFile(sys.argv[1], 'w') as z:\n\
+                 \x20   z.writestr('rzn_demo/__init__.py', '')\n\
+                 \x20   z.writestr('rzn_demo-0.1.dist-info/METADATA', 'Metadata-Version: 2.1\\nName: rzn-demo\\nVersion: 0.1\\n')\n\
+                 \x20   z.writestr('rzn_demo-0.1.dist-info/WHEEL', 'Wheel-Version: 1.0\\nRoot-Is-Purelib: true\\nTag: py3-none-any\\n')\n\
+                 \x20   z.writestr('rzn_demo-0.1.dist-info/RECORD', '')\n",
+            )
+            .arg(&wheel)
+            .status()
+            .unwrap();
+        assert!(build.success());
+        let mut install = EnvManager::Pip.pip_command(&source.python_path, "install");
+        install.arg("--no-deps").arg(&wheel);
+        run(install, "install").await.unwrap();
+
+        let lockfile = registry.export_lockfile("source").await.unwrap();
+        let pins = std::fs::read_to_string(&lockfile).unwrap();
+        assert!(pins.starts_with("rzn-demo @ file://"), "{pins}");
+        assert!(pins.contains("--hash=sha256:"), "{pins}");
+
+        let copy = registry
+            .create_from_lockfile("copy", &lockfile, &options)
+            .await
+            .unwrap();
+        assert!(std::fs::read_to_string(copy.env_dir.join(LOCKFILE_NAME))
+            .unwrap()
+            .contains("--hash=sha256:"));
+
+        // A pin whose file doesn't match its hash is refused
+        let tampered = dir.path().join("tampered.lock");
+        let hash = pins.split("sha256:").nth(1).unwrap().trim();
+        std::fs::write(&tampered, pins.replace(hash, &"0".repeat(64))).unwrap();
+        assert!(registry
+            .create_from_lockfile("tampered", &tampered, &options)
+            .await
+            .is_err());
+        assert!(!registry.env_dir("tampered").unwrap().exists());
+    }
+
+    #[test]
+    fn test_concurrent_touches_keep_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ManagedEnvRegistry::new(dir.path());
+        let env_dir = registry.env_dir("shared").unwrap();
+        std::fs::create_dir_all(env_dir.join("bin")).unwrap();
+        std::fs::write(env_dir.join("bin").join("python3"), "#!").unwrap();
+        ManagedEnvMetadata {
+            alias: "shared".to_string(),
+            env_dir: env_dir.to_string_lossy().to_string(),
+            python_path: String::new(),
+            base_python: None,
+            created_at_unix_seconds: 1,
+            env_manager: Some("micromamba".to_string()),
+            channels: vec!["conda-forge".to_string()],
+            last_used_unix_seconds: None,
+        }
+        .write(&env_dir)
+        .unwrap();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..50 {
+                        registry.touch("shared").unwrap();
+                        // Never a torn file, never the hand-made fallback
+                        let metadata = ManagedEnvMetadata::read(&env_dir).unwrap();
+                        assert_eq!(metadata.env_manager.as_deref(), Some("micromamba"));
+                        assert_eq!(metadata.channels, ["conda-forge"]);
+                        assert_eq!(metadata.created_at_unix_seconds, 1);
+                    }
+                });
+            }
+        });
+        let leftovers: Vec<_> = std::fs::read_dir(&env_dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(".rzn_env."))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_prune_unused_envs() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ManagedEnvRegistry::new(dir.path());
+        let day = 24 * 60 * 60;
+        let now = unix_seconds(SystemTime::now());
+        for (alias, created_days_ago) in [("stale", 40), ("busy", 40), ("fresh", 2)] {
+            let env_dir = registry.env_dir(alias).unwrap();
+            std::fs::create_dir_all(env_dir.join("bin")).unwrap();
+            std::fs::write(env_dir.join("bin").join("python3"), "#!").unwrap();
+            ManagedEnvMetadata {
+                alias: alias.to_string(),
+                env_dir: env_dir.to_string_lossy().to_string(),
+                python_path: String::new(),
+                base_python: None,
+                created_at_unix_seconds: now - created_days_ago * day,
+                env_manager: None,
+                channels: Vec::new(),
+                last_used_unix_seconds: None,
+            }
+            .write(&env_dir)
+            .unwrap();
+        }
+        registry.touch("busy").unwrap();
+        assert!(
+            registry
+                .resolve("busy")
+                .unwrap()
+                .metadata
+                .unwrap()
+                .last_used_unix_seconds
+                >= Some(now)
+        );
+
+        let unused_for = Duration::from_secs(30 * day);
+        let preview = registry.prune(unused_for, true).unwrap();
+        assert_eq!(preview.removed.len(), 1);
+        assert_eq!(preview.removed[0].alias, "stale");
+        assert!(preview.freed_bytes > 0);
+        assert!(registry.resolve("stale").is_ok());
+
+        registry.prune(unused_for, false).unwrap();
+        let left: Vec<String> = registry
+            .list(true)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.alias)
+            .collect();
+        assert_eq!(left, ["busy", "fresh"]);
+    }
+}
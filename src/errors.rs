@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, SandboxError>;
@@ -10,6 +11,12 @@ pub enum SandboxError {
     #[error("No execution engine available")]
     NoEngineAvailable,
 
+    #[error("Unknown engine: {0}")]
+    UnknownEngine(String),
+
+    #[error("Execution queue is full")]
+    QueueFull,
+
     #[error("Syntax error in Python code: {0}")]
     SyntaxError(String),
 
@@ -19,18 +26,47 @@ pub enum SandboxError {
     #[error("Runtime error during execution: {0}")]
     RuntimeError(String),
 
+    /// An exception raised by user code, with its traceback
+    #[error("Runtime error during execution: {0}")]
+    PythonException(Box<PythonException>),
+
     #[error("Execution timeout exceeded")]
     Timeout,
 
-    #[error("Memory limit exceeded")]
-    MemoryLimitExceeded,
+    #[error("Execution cancelled")]
+    Cancelled,
+
+    #[error("Memory limit exceeded{}", .peak_mb.map(|mb| format!(" (peak {} MB)", mb)).unwrap_or_default())]
+    MemoryLimitExceeded { peak_mb: Option<usize> },
 
     #[error("Process limit exceeded")]
     ProcessLimitExceeded,
 
+    #[error("Workspace disk quota exceeded ({used_bytes} bytes used)")]
+    DiskQuotaExceeded { used_bytes: u64 },
+
+    #[error("Code too complex: {0}")]
+    CodeTooComplex(String),
+
     #[error("Import not allowed: {0}")]
     ImportNotAllowed(String),
 
+    /// A runtime guard refused an import
+    #[error("Import of '{module}' blocked by sandbox policy")]
+    ImportBlocked { module: String },
+
+    /// A runtime guard refused a connection or DNS lookup
+    #[error("Network access to '{host}' blocked by sandbox policy")]
+    NetworkBlocked { host: String },
+
+    /// A runtime guard refused a write, rename or delete
+    #[error("File write to '{path}' blocked by sandbox policy")]
+    FileWriteBlocked { path: String },
+
+    /// A runtime guard refused to start a process
+    #[error("Subprocess '{cmd}' blocked by sandbox policy")]
+    SubprocessBlocked { cmd: String },
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -40,6 +76,10 @@ pub enum SandboxError {
     #[error("Internal error: {0}")]
     InternalError(String),
 
+    /// A caller-supplied value was rejected, e.g. a malformed env alias
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
     #[error("Microsandbox error: {0}")]
     MicrosandboxError(String),
 
@@ -52,3 +92,268 @@ pub enum SandboxError {
     #[error("Process killed by signal")]
     ProcessKilled,
 }
+
+impl SandboxError {
+    /// The Python exception behind this error, if user code raised one
+    pub fn python_exception(&self) -> Option<&PythonException> {
+        match self {
+            SandboxError::PythonException(exception) => Some(exception),
+            _ => None,
+        }
+    }
+
+    /// Whether the sandbox policy, not the code itself, refused the operation
+    ///
+    /// These errors call for relaxing the policy rather than fixing the code.
+    pub fn is_policy_denial(&self) -> bool {
+        match self {
+            SandboxError::ImportNotAllowed(_)
+            | SandboxError::ImportBlocked { .. }
+            | SandboxError::NetworkBlocked { .. }
+            | SandboxError::FileWriteBlocked { .. }
+            | SandboxError::SubprocessBlocked { .. } => true,
+            // The static analyzer reports "import of 'x' at line ..."
+            SandboxError::DisallowedOperation(message) => message.starts_with("import of '"),
+            _ => false,
+        }
+    }
+
+    /// Whether the engine couldn't run the code at all, e.g. its interpreter
+    /// is missing or failed to start, as opposed to the code failing
+    pub fn is_engine_unavailable(&self) -> bool {
+        matches!(
+            self,
+            SandboxError::PythonNotFound
+                | SandboxError::NoEngineAvailable
+                | SandboxError::MicrosandboxError(_)
+                | SandboxError::IoError(_)
+        )
+    }
+}
+
+/// File name user code is reported under in traceback frames
+pub const USER_CODE_FILE: &str = "<sandbox>";
+
+/// File names of the wrapper scripts engines run user code in
+const WRAPPER_FILES: [&str; 2] = ["<string>", "<stdin>"];
+
+/// A Python exception parsed from its traceback
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PythonException {
+    /// Exception class, e.g. `ValueError`
+    #[serde(rename = "type")]
+    pub exc_type: String,
+    pub message: String,
+    /// Stack frames, innermost last
+    pub frames: Vec<TracebackFrame>,
+}
+
+/// One frame of a Python traceback
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TracebackFrame {
+    /// Source file, [`USER_CODE_FILE`] for the executed code
+    pub file: String,
+    /// 1-based line; lines of user code count from its first line
+    pub line: usize,
+    /// Enclosing function, `<module>` at top level
+    pub func: Option<String>,
+    pub source_line: Option<String>,
+}
+
+impl PythonException {
+    /// Parse the text of `traceback.format_exc()`
+    ///
+    /// For a chained exception only the last one is kept. Returns `None` if
+    /// the text has no exception line.
+    pub fn parse(traceback: &str) -> Option<Self> {
+        let mut frames: Vec<TracebackFrame> = Vec::new();
+        let mut exception: Option<(String, Vec<&str>)> = None;
+        for line in traceback.lines() {
+            if line.starts_with("Traceback (most recent call last):") {
+                frames.clear();
+                exception = None;
+            } else if let Some(frame) = line.strip_prefix("  File \"") {
+                let Some((file, rest)) = frame.split_once("\", line ") else {
+                    continue;
+                };
+                let (number, func) = match rest.split_once(", in ") {
+                    Some((number, func)) => (number, Some(func.to_string())),
+                    None => (rest, None),
+                };
+                frames.push(TracebackFrame {
+                    file: file.to_string(),
+                    line: number.trim().parse().unwrap_or(0),
+                    func,
+                    source_line: None,
+                });
+            } else if let Some((_, message)) = exception.as_mut() {
+                message.push(line);
+            } else if let Some(source) = line.strip_prefix("    ") {
+                // Skip the `^^^` markers added by Python 3.11+
+                let source = source.trim();
+                let is_marker = source.chars().all(|c| matches!(c, '^' | '~' | ' '));
+                if let (Some(frame), false) = (frames.last_mut(), is_marker) {
+                    frame.source_line.get_or_insert_with(|| source.to_string());
+                }
+            } else if !line.is_empty() && !line.starts_with(' ') {
+                let (exc_type, first) = line.split_once(": ").unwrap_or((line, ""));
+                exception = Some((exc_type.to_string(), vec![first]));
+            }
+        }
+        let (exc_type, message) = exception?;
+        Some(Self {
+            exc_type,
+            message: message.join("\n").trim_end().to_string(),
+            frames,
+        })
+    }
+
+    /// Report frames of the wrapper script in terms of the user `code`
+    ///
+    /// User code starts at line `code_line` of the wrapper; its frames are
+    /// renumbered from 1 under [`USER_CODE_FILE`], and the rest of the
+    /// wrapper is dropped. Code run with `-c` has no source for the
+    /// traceback to show, so user frames get theirs from `code`.
+    pub(crate) fn relocate(mut self, code_line: Option<usize>, code: &str) -> Self {
+        let lines: Vec<&str> = code.lines().collect();
+        self.frames.retain_mut(|frame| {
+            if WRAPPER_FILES.contains(&frame.file.as_str()) {
+                match code_line {
+                    Some(first) if (first..first + lines.len()).contains(&frame.line) => {
+                        frame.file = USER_CODE_FILE.to_string();
+                        frame.line -= first - 1;
+                    }
+                    _ => return false,
+                }
+            }
+            if frame.file == USER_CODE_FILE && frame.source_line.is_none() {
+                frame.source_line = lines
+                    .get(frame.line.wrapping_sub(1))
+                    .map(|l| l.trim().to_string());
+            }
+            true
+        });
+        self
+    }
+
+    /// Innermost frame in user code, the line that raised
+    pub fn user_frame(&self) -> Option<&TracebackFrame> {
+        self.frames.iter().rev().find(|f| f.file == USER_CODE_FILE)
+    }
+}
+
+impl std::fmt::Display for PythonException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{}", self.exc_type)
+        } else {
+            write!(f, "{}: {}", self.exc_type, self.message)
+        }
+    }
+}
+
+/// The error for a wrapper's output, if it reports one
+///
+/// A `traceback` in the output makes it a [`SandboxError::PythonException`]
+/// with frames relative to `code`, which the wrapper placed at `code_line`.
+pub(crate) fn execution_error(output: &serde_json::Value, code: &str) -> Option<SandboxError> {
+    let error = output.get("error")?.as_str().filter(|e| !e.is_empty())?;
+    if let Some(denial) = output.get("denial").and_then(policy_denial) {
+        return Some(denial);
+    }
+    let code_line = output
+        .get("code_line")
+        .and_then(|l| l.as_u64())
+        .map(|l| l as usize);
+    let exception = output
+        .get("traceback")
+        .and_then(|t| t.as_str())
+        .and_then(PythonException::parse);
+    Some(match exception {
+        Some(exception) => {
+            SandboxError::PythonException(Box::new(exception.relocate(code_line, code)))
+        }
+        None => SandboxError::RuntimeError(error.to_string()),
+    })
+}
+
+/// Map the `denial` marker from [`crate::supervisor::DENIAL_MARKER`]
+fn policy_denial(denial: &serde_json::Value) -> Option<SandboxError> {
+    let target = denial.get("target")?.as_str()?.to_string();
+    Some(match denial.get("kind")?.as_str()? {
+        "import" => SandboxError::ImportBlocked { module: target },
+        "network" => SandboxError::NetworkBlocked { host: target },
+        "file_write" => SandboxError::FileWriteBlocked { path: target },
+        "subprocess" => SandboxError::SubprocessBlocked { cmd: target },
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traceback() {
+        let traceback = r#"Traceback (most recent call last):
+  File "<string>", line 40, in <module>
+    helper()
+  File "<string>", line 39, in helper
+    return {}["missing"]
+           ~~^^^^^^^^^^^
+KeyError: 'missing'
+
+During handling of the above exception, another exception occurred:
+
+Traceback (most recent call last):
+  File "<string>", line 40, in <module>
+    helper()
+  File "<string>", line 39, in helper
+    raise ValueError("bad\nvalue")
+  File "<string>", line 12, in restricted_open
+    raise PermissionError()
+ValueError: bad
+value
+"#;
+        let exception = PythonException::parse(traceback).unwrap();
+        assert_eq!(exception.exc_type, "ValueError");
+        assert_eq!(exception.message, "bad\nvalue");
+        assert_eq!(exception.frames.len(), 3);
+        assert_eq!(
+            exception.frames[1].source_line.as_deref(),
+            Some(r#"raise ValueError("bad\nvalue")"#)
+        );
+
+        // User code at lines 38-40 of the wrapper; the guard frame is dropped
+        let code = "def helper():\n    raise ValueError()\nhelper()";
+        let exception = exception.relocate(Some(38), code);
+        assert_eq!(exception.frames.len(), 2);
+        let frame = exception.user_frame().unwrap();
+        assert_eq!((frame.line, frame.func.as_deref()), (2, Some("helper")));
+        let top = &exception.frames[0];
+        assert_eq!(
+            (top.line, top.source_line.as_deref()),
+            (3, Some("helper()"))
+        );
+        assert_eq!(exception.to_string(), "ValueError: bad\nvalue");
+    }
+
+    #[test]
+    fn test_execution_error_maps_denial() {
+        let output = serde_json::json!({
+            "error": "PermissionError: socket.getaddrinfo of evil.com denied by sandbox policy",
+            "denial": {"kind": "network", "target": "evil.com"},
+        });
+        let error = execution_error(&output, "").unwrap();
+        assert!(error.is_policy_denial());
+        assert!(matches!(error, SandboxError::NetworkBlocked { host } if host == "evil.com"));
+
+        // An unknown kind falls back to the plain runtime error
+        let output = serde_json::json!({
+            "error": "ValueError: bad",
+            "denial": {"kind": "gpu", "target": "0"},
+        });
+        let error = execution_error(&output, "").unwrap();
+        assert!(!error.is_policy_denial());
+    }
+}
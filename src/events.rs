@@ -0,0 +1,53 @@
+//! Progress events from running executions
+//!
+//! Set [`ExecutionOptions::events`](crate::ExecutionOptions::events) to receive
+//! events while code runs. Heartbeats are sampled from the child process, so a
+//! host can render progress and spot a hung job (CPU time not advancing) well
+//! before the wall-clock timeout fires.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Sending half of an execution event channel
+pub type EventSender = mpsc::UnboundedSender<ExecutionEvent>;
+
+/// Receiving half of an execution event channel
+pub type EventReceiver = mpsc::UnboundedReceiver<ExecutionEvent>;
+
+/// Create a channel for [`ExecutionOptions::events`](crate::ExecutionOptions::events)
+pub fn event_channel() -> (EventSender, EventReceiver) {
+    mpsc::unbounded_channel()
+}
+
+/// Stream a chunk of output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Event emitted while an execution runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecutionEvent {
+    /// The interpreter process started
+    Started { pid: Option<u32> },
+    /// Periodic resource sample of the interpreter process
+    Heartbeat {
+        /// User + system CPU time consumed so far
+        cpu_secs: f64,
+        /// Current resident set size
+        rss_mb: f64,
+        /// Wall-clock time since the process started
+        elapsed: Duration,
+    },
+    /// Output written by user code, as it is written
+    OutputChunk { stream: OutputStream, data: String },
+    /// The interpreter process is gone; `exit_code` is `None` if it was killed
+    Finished {
+        exit_code: Option<i32>,
+        elapsed: Duration,
+    },
+}
@@ -0,0 +1,71 @@
+//! When [`PythonSandbox`](crate::PythonSandbox) moves on to its next engine
+//!
+//! By default a failed execution is retried on every other engine in order,
+//! which can silently run code somewhere weaker than the primary, e.g. on the
+//! native engine after a VM engine failed. Set a [`FallbackStrategy`] with
+//! `with_fallback_strategy` to limit that.
+
+use crate::errors::SandboxError;
+use serde::{Deserialize, Serialize};
+
+/// Which failures of the primary engine are retried, and on which engines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackStrategy {
+    /// Only ever run on the primary engine
+    Never,
+    /// Retry only when the primary engine couldn't run the code at all, see
+    /// [`SandboxError::is_engine_unavailable`]
+    OnEngineUnavailable,
+    /// Retry after any error except cancellation
+    #[default]
+    OnAnyError,
+    /// Retry after any error, but only on engines whose
+    /// [`security_level`](crate::EngineCapabilities::security_level) is at
+    /// least this
+    SecurityFloor(u8),
+}
+
+impl FallbackStrategy {
+    /// Whether `error` from the primary engine is retried on other engines
+    pub fn retries(&self, error: &SandboxError) -> bool {
+        if matches!(error, SandboxError::Cancelled) {
+            return false;
+        }
+        match self {
+            FallbackStrategy::Never => false,
+            FallbackStrategy::OnEngineUnavailable => error.is_engine_unavailable(),
+            FallbackStrategy::OnAnyError | FallbackStrategy::SecurityFloor(_) => true,
+        }
+    }
+
+    /// Whether an engine with `security_level` may run a retry
+    pub fn admits(&self, security_level: u8) -> bool {
+        match self {
+            FallbackStrategy::SecurityFloor(floor) => security_level >= *floor,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_strategies() {
+        let unavailable = SandboxError::PythonNotFound;
+        let failed = SandboxError::Timeout;
+
+        assert!(!FallbackStrategy::Never.retries(&unavailable));
+        assert!(FallbackStrategy::OnEngineUnavailable.retries(&unavailable));
+        assert!(!FallbackStrategy::OnEngineUnavailable.retries(&failed));
+        assert!(FallbackStrategy::OnAnyError.retries(&failed));
+        assert!(!FallbackStrategy::OnAnyError.retries(&SandboxError::Cancelled));
+
+        let floor = FallbackStrategy::SecurityFloor(7);
+        assert!(floor.retries(&failed));
+        assert!(floor.admits(8) && !floor.admits(5));
+        assert!(FallbackStrategy::OnAnyError.admits(0));
+    }
+}
@@ -0,0 +1,392 @@
+//! gRPC service and client engine
+//!
+//! [`SandboxService`] exposes a [`PythonSandbox`] over the `pysandbox.v1.Sandbox`
+//! service defined in `proto/pysandbox.proto`, and [`GrpcPythonEngine`] is the
+//! matching [`PythonEngine`] for processes that share a sandbox daemon. Inputs,
+//! options and results travel as JSON so every call carries its own policy.
+
+use crate::{
+    engine::{
+        Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine, Severity,
+    },
+    errors::{Result, SandboxError},
+    PythonSandbox,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status};
+
+/// Generated protobuf types
+pub mod proto {
+    tonic::include_proto!("pysandbox.v1");
+}
+
+use proto::sandbox_client::SandboxClient;
+use proto::sandbox_server::{Sandbox, SandboxServer};
+use proto::{execute_response::Outcome, Failure};
+
+/// Stable error kind sent to clients
+fn failure_from_error(error: &SandboxError) -> Failure {
+    let kind = match error {
+        SandboxError::SyntaxError(_) => "syntax_error",
+        SandboxError::DisallowedOperation(_) => "disallowed_operation",
+        SandboxError::RuntimeError(_) | SandboxError::PythonException(_) => "runtime_error",
+        SandboxError::Timeout => "timeout",
+        SandboxError::Cancelled => "cancelled",
+        SandboxError::MemoryLimitExceeded { .. } => "memory_limit_exceeded",
+        SandboxError::ProcessLimitExceeded => "process_limit_exceeded",
+        SandboxError::DiskQuotaExceeded { .. } => "disk_quota_exceeded",
+        SandboxError::ImportNotAllowed(_) => "import_not_allowed",
+        SandboxError::ImportBlocked { .. } => "import_blocked",
+        SandboxError::NetworkBlocked { .. } => "network_blocked",
+        SandboxError::FileWriteBlocked { .. } => "file_write_blocked",
+        SandboxError::SubprocessBlocked { .. } => "subprocess_blocked",
+        SandboxError::CodeTooComplex(_) => "code_too_complex",
+        SandboxError::SecurityViolation(_) => "security_violation",
+        SandboxError::PythonNotFound | SandboxError::NoEngineAvailable => "engine_unavailable",
+        _ => "internal",
+    };
+    let message = match error {
+        SandboxError::SyntaxError(m)
+        | SandboxError::DisallowedOperation(m)
+        | SandboxError::RuntimeError(m)
+        | SandboxError::ImportNotAllowed(m)
+        | SandboxError::CodeTooComplex(m)
+        | SandboxError::SecurityViolation(m)
+        | SandboxError::ImportBlocked { module: m }
+        | SandboxError::NetworkBlocked { host: m }
+        | SandboxError::FileWriteBlocked { path: m }
+        | SandboxError::SubprocessBlocked { cmd: m } => m.clone(),
+        SandboxError::PythonException(exception) => exception.to_string(),
+        other => other.to_string(),
+    };
+    Failure {
+        kind: kind.to_string(),
+        message,
+        exception_json: error
+            .python_exception()
+            .and_then(|e| serde_json::to_string(e).ok())
+            .unwrap_or_default(),
+    }
+}
+
+/// Rebuild the server's error on the client side
+fn error_from_failure(failure: Failure) -> SandboxError {
+    match failure.kind.as_str() {
+        "syntax_error" => SandboxError::SyntaxError(failure.message),
+        "disallowed_operation" => SandboxError::DisallowedOperation(failure.message),
+        "runtime_error" => match serde_json::from_str(&failure.exception_json) {
+            Ok(exception) => SandboxError::PythonException(Box::new(exception)),
+            Err(_) => SandboxError::RuntimeError(failure.message),
+        },
+        "timeout" => SandboxError::Timeout,
+        "cancelled" => SandboxError::Cancelled,
+        "memory_limit_exceeded" => SandboxError::MemoryLimitExceeded { peak_mb: None },
+        "process_limit_exceeded" => SandboxError::ProcessLimitExceeded,
+        "disk_quota_exceeded" => SandboxError::DiskQuotaExceeded { used_bytes: 0 },
+        "import_not_allowed" => SandboxError::ImportNotAllowed(failure.message),
+        "import_blocked" => SandboxError::ImportBlocked {
+            module: failure.message,
+        },
+        "network_blocked" => SandboxError::NetworkBlocked {
+            host: failure.message,
+        },
+        "file_write_blocked" => SandboxError::FileWriteBlocked {
+            path: failure.message,
+        },
+        "subprocess_blocked" => SandboxError::SubprocessBlocked {
+            cmd: failure.message,
+        },
+        "code_too_complex" => SandboxError::CodeTooComplex(failure.message),
+        "security_violation" => SandboxError::SecurityViolation(failure.message),
+        "engine_unavailable" => SandboxError::NoEngineAvailable,
+        _ => SandboxError::InternalError(failure.message),
+    }
+}
+
+fn diagnostic_to_proto(diagnostic: Diagnostic) -> proto::Diagnostic {
+    proto::Diagnostic {
+        rule: diagnostic.rule,
+        message: diagnostic.message,
+        line: diagnostic.line.unwrap_or(0) as u32,
+        col: diagnostic.col.unwrap_or(0) as u32,
+        severity: match diagnostic.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+        .to_string(),
+    }
+}
+
+fn diagnostic_from_proto(diagnostic: proto::Diagnostic) -> Diagnostic {
+    Diagnostic {
+        rule: diagnostic.rule,
+        message: diagnostic.message,
+        line: (diagnostic.line > 0).then_some(diagnostic.line as usize),
+        col: (diagnostic.col > 0).then_some(diagnostic.col as usize),
+        // Unknown severities are treated as blocking
+        severity: match diagnostic.severity.as_str() {
+            "warning" => Severity::Warning,
+            _ => Severity::Error,
+        },
+    }
+}
+
+fn parse_options(options_json: &str) -> serde_json::Result<ExecutionOptions> {
+    if options_json.is_empty() {
+        return Ok(ExecutionOptions::default());
+    }
+    serde_json::from_str(options_json)
+}
+
+fn invalid_options(e: serde_json::Error) -> Status {
+    Status::invalid_argument(format!("Invalid options: {}", e))
+}
+
+fn status_error(status: Status) -> SandboxError {
+    match status.code() {
+        tonic::Code::DeadlineExceeded => SandboxError::Timeout,
+        tonic::Code::Unavailable => SandboxError::NoEngineAvailable,
+        _ => SandboxError::InternalError(format!("gRPC error: {}", status)),
+    }
+}
+
+/// gRPC service backed by a [`PythonSandbox`]
+pub struct SandboxService {
+    sandbox: Arc<PythonSandbox>,
+}
+
+impl SandboxService {
+    /// Create a service for a sandbox
+    pub fn new(sandbox: PythonSandbox) -> Self {
+        Self {
+            sandbox: Arc::new(sandbox),
+        }
+    }
+
+    /// Wrap the service for `tonic::transport::Server::add_service`
+    pub fn into_server(self) -> SandboxServer<Self> {
+        SandboxServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl Sandbox for SandboxService {
+    async fn execute(
+        &self,
+        request: Request<proto::ExecuteRequest>,
+    ) -> std::result::Result<Response<proto::ExecuteResponse>, Status> {
+        let request = request.into_inner();
+        let options = parse_options(&request.options_json).map_err(invalid_options)?;
+        let inputs = if request.inputs_json.is_empty() {
+            serde_json::json!({})
+        } else {
+            serde_json::from_str(&request.inputs_json)
+                .map_err(|e| Status::invalid_argument(format!("Invalid inputs: {}", e)))?
+        };
+
+        let outcome = match self.sandbox.execute(&request.code, inputs, options).await {
+            Ok(result) => Outcome::ResultJson(
+                serde_json::to_string(&result).map_err(|e| Status::internal(e.to_string()))?,
+            ),
+            Err(e) => Outcome::Failure(failure_from_error(&e)),
+        };
+
+        Ok(Response::new(proto::ExecuteResponse {
+            outcome: Some(outcome),
+        }))
+    }
+
+    async fn validate(
+        &self,
+        request: Request<proto::ValidateRequest>,
+    ) -> std::result::Result<Response<proto::ValidateResponse>, Status> {
+        let request = request.into_inner();
+        let options = parse_options(&request.options_json).map_err(invalid_options)?;
+        let response = match self.sandbox.validate(&request.code, &options).await {
+            Ok(diagnostics) => proto::ValidateResponse {
+                failure: None,
+                diagnostics: diagnostics.into_iter().map(diagnostic_to_proto).collect(),
+            },
+            Err(e) => proto::ValidateResponse {
+                failure: Some(failure_from_error(&e)),
+                diagnostics: Vec::new(),
+            },
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn capabilities(
+        &self,
+        _request: Request<proto::CapabilitiesRequest>,
+    ) -> std::result::Result<Response<proto::CapabilitiesResponse>, Status> {
+        let engines = self
+            .sandbox
+            .capabilities()
+            .await
+            .into_iter()
+            .map(|caps| proto::EngineCapabilities {
+                name: caps.name,
+                numpy: caps.numpy,
+                matplotlib: caps.matplotlib,
+                pandas: caps.pandas,
+                max_memory_mb: caps.max_memory_mb as u64,
+                max_cpu_seconds: caps.max_cpu_seconds,
+                security_level: caps.security_level as u32,
+            })
+            .collect();
+
+        Ok(Response::new(proto::CapabilitiesResponse { engines }))
+    }
+}
+
+/// Python engine that executes on a `pysandbox-server` over gRPC
+pub struct GrpcPythonEngine {
+    client: SandboxClient<Channel>,
+    capabilities: EngineCapabilities,
+}
+
+impl GrpcPythonEngine {
+    /// Connect to a server, e.g. `http://127.0.0.1:50051`
+    ///
+    /// Capabilities of the server's primary engine are fetched once here.
+    pub async fn connect(endpoint: &str) -> Result<Self> {
+        let mut client = SandboxClient::connect(endpoint.to_string())
+            .await
+            .map_err(|e| {
+                SandboxError::InternalError(format!("Failed to connect to {}: {}", endpoint, e))
+            })?;
+
+        let response = client
+            .capabilities(proto::CapabilitiesRequest {})
+            .await
+            .map_err(status_error)?
+            .into_inner();
+        let primary = response
+            .engines
+            .into_iter()
+            .next()
+            .ok_or(SandboxError::NoEngineAvailable)?;
+
+        Ok(Self {
+            client,
+            capabilities: EngineCapabilities {
+                name: format!("gRPC ({})", primary.name),
+                numpy: primary.numpy,
+                matplotlib: primary.matplotlib,
+                pandas: primary.pandas,
+                max_memory_mb: primary.max_memory_mb as usize,
+                max_cpu_seconds: primary.max_cpu_seconds,
+                security_level: primary.security_level.min(10) as u8,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl PythonEngine for GrpcPythonEngine {
+    async fn validate(&self, code: &str, options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        let request = proto::ValidateRequest {
+            code: code.to_string(),
+            options_json: options.to_forwarded_value()?.to_string(),
+        };
+        // Clients are cheap to clone and share the channel
+        let response = self
+            .client
+            .clone()
+            .validate(request)
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        match response.failure {
+            Some(failure) => Err(error_from_failure(failure)),
+            None => Ok(response
+                .diagnostics
+                .into_iter()
+                .map(diagnostic_from_proto)
+                .collect()),
+        }
+    }
+
+    async fn execute(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let mut request = Request::new(proto::ExecuteRequest {
+            code: code.to_string(),
+            inputs_json: serde_json::to_string(&inputs)?,
+            options_json: options.to_forwarded_value()?.to_string(),
+        });
+        // Let the server's own timeout fire first
+        request.set_timeout(options.timeout + std::time::Duration::from_secs(5));
+
+        let response = self
+            .client
+            .execute(request)
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        match response.outcome {
+            Some(Outcome::ResultJson(json)) => {
+                ExecutionResult::from_value(serde_json::from_str(&json)?)
+            }
+            Some(Outcome::Failure(failure)) => Err(error_from_failure(failure)),
+            None => Err(SandboxError::InternalError(
+                "Server returned an empty response".to_string(),
+            )),
+        }
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_round_trip() {
+        let cases = [
+            SandboxError::Timeout,
+            SandboxError::ImportNotAllowed("socket".to_string()),
+            SandboxError::NetworkBlocked {
+                host: "example.com".to_string(),
+            },
+            SandboxError::RuntimeError("ValueError: bad".to_string()),
+            SandboxError::PythonException(Box::new(
+                crate::errors::PythonException::parse("ValueError: bad").unwrap(),
+            )),
+        ];
+        for error in cases {
+            let expected = error.to_string();
+            let restored = error_from_failure(failure_from_error(&error));
+            assert_eq!(restored.to_string(), expected);
+            assert_eq!(restored.python_exception(), error.python_exception());
+        }
+    }
+
+    #[test]
+    fn test_diagnostic_round_trip() {
+        let diagnostic = Diagnostic {
+            rule: "dunder_access".to_string(),
+            message: "access to '__globals__'".to_string(),
+            line: Some(3),
+            col: None,
+            severity: Severity::Warning,
+        };
+        let restored = diagnostic_from_proto(diagnostic_to_proto(diagnostic.clone()));
+        assert_eq!(restored, diagnostic);
+    }
+}
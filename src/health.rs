@@ -0,0 +1,249 @@
+//! Engine health tracking and quarantine
+//!
+//! With `with_health_checks`, a [`PythonSandbox`](crate::PythonSandbox)
+//! counts consecutive engine failures, i.e. errors where the engine rather
+//! than the code failed (see [`is_engine_failure`]). An engine that reaches
+//! [`HealthConfig::failure_threshold`] is quarantined: executions skip it as
+//! if it were unavailable, subject to the sandbox's
+//! [`FallbackStrategy`](crate::FallbackStrategy). A passing
+//! [`PythonEngine::health_check`](crate::PythonEngine::health_check), run
+//! every [`HealthConfig::check_interval`] or on demand with `check_health`,
+//! lifts the quarantine. Every change is broadcast as an [`EngineHealthEvent`].
+
+use crate::engine::PythonEngine;
+use crate::errors::{Result, SandboxError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+
+/// When engines are quarantined and checked
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// Consecutive engine failures, from executions or health checks, before
+    /// an engine is quarantined
+    pub failure_threshold: u32,
+    /// How often every engine's health check runs; `None` runs them only on
+    /// `check_health`
+    pub check_interval: Option<Duration>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            check_interval: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+/// An engine was quarantined or recovered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineHealthEvent {
+    Quarantined {
+        engine: String,
+        /// Consecutive failures that led to it
+        failures: u32,
+        /// The last failure
+        error: String,
+    },
+    Recovered {
+        engine: String,
+    },
+}
+
+/// Whether `error` means the engine, not the code, failed
+pub fn is_engine_failure(error: &SandboxError) -> bool {
+    error.is_engine_unavailable() || matches!(error, SandboxError::InternalError(_))
+}
+
+#[derive(Debug, Default)]
+struct EngineState {
+    failures: u32,
+    quarantined: bool,
+}
+
+/// Health of each of a sandbox's engines, by index
+pub(crate) struct EngineHealth {
+    threshold: u32,
+    names: Vec<String>,
+    states: Vec<Mutex<EngineState>>,
+    events: broadcast::Sender<EngineHealthEvent>,
+}
+
+impl EngineHealth {
+    pub(crate) fn new(threshold: u32, names: Vec<String>) -> Self {
+        let (events, _) = broadcast::channel(16);
+        Self {
+            threshold: threshold.max(1),
+            states: names.iter().map(|_| Mutex::default()).collect(),
+            names,
+            events,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<EngineHealthEvent> {
+        self.events.subscribe()
+    }
+
+    fn state(&self, idx: usize) -> std::sync::MutexGuard<'_, EngineState> {
+        self.states[idx].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn is_quarantined(&self, idx: usize) -> bool {
+        self.state(idx).quarantined
+    }
+
+    pub(crate) fn quarantined(&self) -> Vec<String> {
+        (0..self.names.len())
+            .filter(|&idx| self.is_quarantined(idx))
+            .map(|idx| self.names[idx].clone())
+            .collect()
+    }
+
+    /// Count an execution's outcome; errors caused by the code don't count
+    ///
+    /// `shown` is the error as it may be reported, e.g. redacted.
+    pub(crate) fn record<T>(
+        &self,
+        idx: usize,
+        result: &Result<T>,
+        shown: impl Fn(&SandboxError) -> String,
+    ) {
+        match result {
+            Ok(_) => self.state(idx).failures = 0,
+            Err(e) if is_engine_failure(e) => self.failed(idx, shown(e)),
+            Err(_) => {}
+        }
+    }
+
+    /// Apply a health check's outcome; a pass lifts the quarantine
+    pub(crate) fn record_check(&self, idx: usize, result: &Result<()>) {
+        match result {
+            Ok(()) => {
+                let recovered = {
+                    let mut state = self.state(idx);
+                    state.failures = 0;
+                    std::mem::take(&mut state.quarantined)
+                };
+                if recovered {
+                    tracing::info!("Engine {} recovered", self.names[idx]);
+                    let _ = self.events.send(EngineHealthEvent::Recovered {
+                        engine: self.names[idx].clone(),
+                    });
+                }
+            }
+            Err(e) => self.failed(idx, e.to_string()),
+        }
+    }
+
+    fn failed(&self, idx: usize, error: String) {
+        let failures = {
+            let mut state = self.state(idx);
+            state.failures += 1;
+            if state.quarantined || state.failures < self.threshold {
+                return;
+            }
+            state.quarantined = true;
+            state.failures
+        };
+        tracing::warn!(
+            "Quarantining engine {} after {} failures: {}",
+            self.names[idx],
+            failures,
+            error
+        );
+        let _ = self.events.send(EngineHealthEvent::Quarantined {
+            engine: self.names[idx].clone(),
+            failures,
+            error,
+        });
+    }
+}
+
+type SharedEngine = Arc<RwLock<Box<dyn PythonEngine>>>;
+
+/// Run every engine's health check once
+///
+/// Engines busy executing are skipped, as an engine that runs code is alive.
+pub(crate) async fn check_engines(
+    engines: &[SharedEngine],
+    health: &EngineHealth,
+) -> Vec<(String, Option<Result<()>>)> {
+    let mut report = Vec::with_capacity(engines.len());
+    for (idx, engine) in engines.iter().enumerate() {
+        let result = match engine.try_read() {
+            Ok(engine) => Some(engine.health_check().await),
+            Err(_) => None,
+        };
+        if let Some(result) = &result {
+            health.record_check(idx, result);
+        }
+        report.push((health.names[idx].clone(), result));
+    }
+    report
+}
+
+/// Periodic health checks; dropping it stops them
+pub(crate) struct HealthTask(JoinHandle<()>);
+
+impl HealthTask {
+    pub(crate) fn spawn(
+        engines: Vec<SharedEngine>,
+        health: Arc<EngineHealth>,
+        interval: Duration,
+    ) -> Self {
+        HealthTask(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                check_engines(&engines, &health).await;
+            }
+        }))
+    }
+}
+
+impl Drop for HealthTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine_after_consecutive_failures() {
+        let health = EngineHealth::new(2, vec!["vm".to_string(), "native".to_string()]);
+        let mut events = health.subscribe();
+        let down: Result<()> = Err(SandboxError::MicrosandboxError("server down".to_string()));
+        let shown = |e: &SandboxError| e.to_string();
+
+        // Code errors and successes don't add up to a quarantine
+        health.record(0, &down, shown);
+        health.record::<()>(0, &Err(SandboxError::Timeout), shown);
+        health.record(0, &Ok(()), shown);
+        health.record(0, &down, shown);
+        assert!(!health.is_quarantined(0));
+
+        health.record(0, &down, shown);
+        assert_eq!(health.quarantined(), vec!["vm".to_string()]);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            EngineHealthEvent::Quarantined { failures: 2, .. }
+        ));
+
+        health.record_check(0, &Ok(()));
+        assert!(!health.is_quarantined(0));
+        assert_eq!(
+            events.try_recv().unwrap(),
+            EngineHealthEvent::Recovered {
+                engine: "vm".to_string()
+            }
+        );
+    }
+}
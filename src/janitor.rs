@@ -0,0 +1,241 @@
+//! Garbage collection of leftover workspaces
+//!
+//! Workspaces are deleted when their [`IsolatedWorkspace`] is dropped, so a
+//! crashed or killed host leaves them behind, as do workspaces kept with
+//! `keep()`. A [`WorkspaceJanitor`] sweeps a workspace base directory and
+//! removes workspaces, with their snapshots, that are older than a TTL, then
+//! the oldest ones until the rest fit a size budget. Only directories named
+//! like workspaces are touched, and none modified within
+//! [`WorkspaceJanitor::with_min_age`], so running executions are safe.
+//!
+//! [`IsolatedWorkspace`]: crate::IsolatedWorkspace
+
+use crate::errors::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Removes old workspaces under a base directory
+#[derive(Debug, Clone)]
+pub struct WorkspaceJanitor {
+    base: PathBuf,
+    ttl: Option<Duration>,
+    max_total_bytes: Option<u64>,
+    min_age: Duration,
+}
+
+/// What a sweep removed, or would remove for a preview
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JanitorReport {
+    pub removed: Vec<PathBuf>,
+    pub freed_bytes: u64,
+    /// Size of the workspaces left in place
+    pub remaining_bytes: u64,
+}
+
+struct Entry {
+    path: PathBuf,
+    bytes: u64,
+    modified: SystemTime,
+}
+
+impl WorkspaceJanitor {
+    /// Janitor for `base`, e.g. [`crate::default_workspace_base`],
+    /// removing workspaces older than a day
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            ttl: Some(Duration::from_secs(24 * 60 * 60)),
+            max_total_bytes: None,
+            min_age: Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// Remove workspaces not modified for `ttl`; `None` keeps them regardless
+    /// of age
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Remove the least recently modified workspaces until the rest take at
+    /// most `bytes`
+    pub fn with_max_total_bytes(mut self, bytes: Option<u64>) -> Self {
+        self.max_total_bytes = bytes;
+        self
+    }
+
+    /// Never remove workspaces modified more recently than this (default ten
+    /// minutes)
+    pub fn with_min_age(mut self, min_age: Duration) -> Self {
+        self.min_age = min_age;
+        self
+    }
+
+    /// Remove what the TTL and size budget call for
+    pub fn sweep(&self) -> Result<JanitorReport> {
+        self.run(true)
+    }
+
+    /// Report what [`sweep`](Self::sweep) would remove, without removing it
+    pub fn preview(&self) -> Result<JanitorReport> {
+        self.run(false)
+    }
+
+    /// Sweep every `interval` on the current tokio runtime until the
+    /// returned task is dropped
+    pub fn spawn(self, interval: Duration) -> JanitorTask {
+        JanitorTask(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let janitor = self.clone();
+                match tokio::task::spawn_blocking(move || janitor.sweep()).await {
+                    Ok(Ok(report)) if !report.removed.is_empty() => info!(
+                        "[JANITOR] Removed {} workspaces, freed {} bytes",
+                        report.removed.len(),
+                        report.freed_bytes
+                    ),
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => warn!("[JANITOR] Sweep failed: {}", e),
+                    Err(e) => warn!("[JANITOR] Sweep panicked: {}", e),
+                }
+            }
+        }))
+    }
+
+    fn run(&self, remove: bool) -> Result<JanitorReport> {
+        let mut entries = match self.entries() {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        // Oldest first, so the size budget evicts them first
+        entries.sort_by_key(|entry| entry.modified);
+        let now = SystemTime::now();
+        let age = |entry: &Entry| now.duration_since(entry.modified).unwrap_or_default();
+
+        let mut remaining_bytes: u64 = entries.iter().map(|entry| entry.bytes).sum();
+        let mut report = JanitorReport::default();
+        for entry in entries {
+            let age = age(&entry);
+            if age < self.min_age {
+                continue;
+            }
+            let expired = self.ttl.is_some_and(|ttl| age > ttl);
+            let over_budget = self
+                .max_total_bytes
+                .is_some_and(|max| remaining_bytes > max);
+            if !expired && !over_budget {
+                continue;
+            }
+            if remove {
+                if let Err(e) = std::fs::remove_dir_all(&entry.path) {
+                    warn!("[JANITOR] Failed to remove {:?}: {}", entry.path, e);
+                    continue;
+                }
+            }
+            remaining_bytes -= entry.bytes;
+            report.freed_bytes += entry.bytes;
+            report.removed.push(entry.path);
+        }
+        report.remaining_bytes = remaining_bytes;
+        Ok(report)
+    }
+
+    fn entries(&self) -> std::io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for dir_entry in std::fs::read_dir(&self.base)? {
+            let dir_entry = dir_entry?;
+            let path = dir_entry.path();
+            if !dir_entry.file_type()?.is_dir() || !is_workspace_dir(&path) {
+                continue;
+            }
+            let (bytes, modified) = tree_usage(&path)?;
+            entries.push(Entry {
+                path,
+                bytes,
+                modified,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// A workspace (`<uuid>`) or its snapshots (`<uuid>.snapshots`)
+fn is_workspace_dir(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let id = name.strip_suffix(".snapshots").unwrap_or(name);
+    uuid::Uuid::parse_str(id).is_ok()
+}
+
+/// Total file size and latest modification under `path`, not following
+/// symlinks
+fn tree_usage(path: &Path) -> std::io::Result<(u64, SystemTime)> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    let mut modified = metadata.modified()?;
+    let mut bytes = 0;
+    if metadata.is_file() {
+        bytes = metadata.len();
+    } else if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let (entry_bytes, entry_modified) = tree_usage(&entry?.path())?;
+            bytes += entry_bytes;
+            modified = modified.max(entry_modified);
+        }
+    }
+    Ok((bytes, modified))
+}
+
+/// Periodic sweeps; dropping it stops them
+pub struct JanitorTask(JoinHandle<()>);
+
+impl Drop for JanitorTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_janitor_ttl_and_budget() {
+        let base = tempfile::tempdir().unwrap();
+        let workspace = |age_secs: u64, bytes: usize| {
+            let path = base.path().join(uuid::Uuid::new_v4().to_string());
+            std::fs::create_dir(&path).unwrap();
+            let file = std::fs::File::create(path.join("data")).unwrap();
+            file.set_len(bytes as u64).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(age_secs);
+            file.set_modified(modified).unwrap();
+            std::fs::File::open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+            path
+        };
+        let ancient = workspace(3 * 24 * 3600, 10);
+        let old = workspace(3600, 1000);
+        let newer = workspace(1800, 1000);
+        let running = workspace(0, 5000);
+        let unrelated = base.path().join("exports");
+        std::fs::create_dir(&unrelated).unwrap();
+
+        let janitor = WorkspaceJanitor::new(base.path()).with_max_total_bytes(Some(6500));
+        let preview = janitor.preview().unwrap();
+        assert_eq!(preview.removed, vec![ancient.clone(), old.clone()]);
+        assert!(ancient.exists());
+
+        let report = janitor.sweep().unwrap();
+        assert_eq!(report.removed, preview.removed);
+        assert!(!ancient.exists() && !old.exists());
+        assert!(newer.exists() && running.exists() && unrelated.exists());
+    }
+}
@@ -0,0 +1,521 @@
+//! Jupyter kernel engine
+//!
+//! Runs code in an existing, host-managed Jupyter kernel over the kernel wire
+//! protocol (ZMQ). The import guard is installed for the duration of each cell
+//! and removed afterwards so the kernel's own machinery keeps working between
+//! executions. Rich outputs (`display_data`/`execute_result`) are returned as
+//! MIME bundles under `displays`.
+
+use crate::{
+    audit::iso8601_now,
+    config::ResourceLimits,
+    engine::{Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine},
+    errors::{Result, SandboxError},
+    sandboxed::SandboxedPythonEngine,
+    supervisor::{ResultChannel, DENIAL_MARKER, RESULT_SERIALIZER},
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+use zeromq::{DealerSocket, Socket, SocketRecv, SocketSend, SubSocket, ZmqMessage};
+
+/// Frame separating ZMQ routing identities from the Jupyter message
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// Jupyter messaging protocol version spoken by this client
+const PROTOCOL_VERSION: &str = "5.3";
+
+/// Connection details written by a kernel (`kernel-*.json`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct KernelConnection {
+    pub transport: String,
+    pub ip: String,
+    pub shell_port: u16,
+    pub iopub_port: u16,
+    pub control_port: u16,
+    pub key: String,
+    #[serde(default = "default_signature_scheme")]
+    pub signature_scheme: String,
+    #[serde(default)]
+    pub kernel_name: Option<String>,
+}
+
+fn default_signature_scheme() -> String {
+    "hmac-sha256".to_string()
+}
+
+impl KernelConnection {
+    /// Read a kernel connection file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn endpoint(&self, port: u16) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, port)
+    }
+}
+
+/// Configuration for the Jupyter kernel engine
+#[derive(Debug, Clone)]
+pub struct JupyterConfig {
+    /// Connection file of the kernel to attach to
+    pub connection_file: PathBuf,
+    /// Resource limits advertised for the kernel
+    pub limits: ResourceLimits,
+    /// How long to wait for the kernel to answer during connection setup
+    pub connect_timeout: Duration,
+    /// How long to wait for an interrupted cell to wind down after a timeout
+    pub interrupt_grace: Duration,
+}
+
+impl Default for JupyterConfig {
+    fn default() -> Self {
+        Self {
+            connection_file: PathBuf::new(),
+            limits: ResourceLimits::default(),
+            connect_timeout: Duration::from_secs(10),
+            interrupt_grace: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A decoded Jupyter message
+#[derive(Debug, Clone)]
+struct KernelMessage {
+    header: Value,
+    parent_header: Value,
+    content: Value,
+}
+
+impl KernelMessage {
+    fn msg_type(&self) -> &str {
+        self.header["msg_type"].as_str().unwrap_or_default()
+    }
+
+    fn parent_id(&self) -> &str {
+        self.parent_header["msg_id"].as_str().unwrap_or_default()
+    }
+}
+
+/// Output collected from one cell
+#[derive(Debug, Default)]
+struct CellOutput {
+    stdout: String,
+    stderr: String,
+    displays: Vec<Value>,
+    error: Option<String>,
+    execution_count: Option<u64>,
+}
+
+/// Python engine that executes code in a running Jupyter kernel
+pub struct JupyterKernelEngine {
+    config: JupyterConfig,
+    connection: KernelConnection,
+    session: String,
+    shell: DealerSocket,
+    control: DealerSocket,
+    iopub: SubSocket,
+}
+
+impl JupyterKernelEngine {
+    /// Attach to the kernel described by `config.connection_file`
+    pub async fn connect(config: JupyterConfig) -> Result<Self> {
+        let connection = KernelConnection::from_file(&config.connection_file)?;
+        if !connection.key.is_empty() && connection.signature_scheme != "hmac-sha256" {
+            return Err(SandboxError::InternalError(format!(
+                "Unsupported signature scheme: {}",
+                connection.signature_scheme
+            )));
+        }
+
+        let zmq_err = |e: zeromq::ZmqError| {
+            SandboxError::InternalError(format!("Failed to connect to kernel: {}", e))
+        };
+        let mut shell = DealerSocket::new();
+        shell
+            .connect(&connection.endpoint(connection.shell_port))
+            .await
+            .map_err(zmq_err)?;
+        let mut control = DealerSocket::new();
+        control
+            .connect(&connection.endpoint(connection.control_port))
+            .await
+            .map_err(zmq_err)?;
+        let mut iopub = SubSocket::new();
+        iopub
+            .connect(&connection.endpoint(connection.iopub_port))
+            .await
+            .map_err(zmq_err)?;
+        iopub.subscribe("").await.map_err(zmq_err)?;
+
+        let mut engine = Self {
+            config,
+            connection,
+            session: uuid::Uuid::new_v4().to_string(),
+            shell,
+            control,
+            iopub,
+        };
+
+        let connect_timeout = engine.config.connect_timeout;
+        tokio::time::timeout(connect_timeout, engine.wait_for_iopub())
+            .await
+            .map_err(|_| SandboxError::NoEngineAvailable)??;
+
+        info!(
+            "[JUPYTER] Attached to kernel {}",
+            engine.connection.kernel_name.as_deref().unwrap_or("python")
+        );
+        Ok(engine)
+    }
+
+    /// Attach to a kernel by connection file with default settings
+    pub async fn with_connection_file(connection_file: PathBuf) -> Result<Self> {
+        Self::connect(JupyterConfig {
+            connection_file,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Send `kernel_info_request` until its status shows up on iopub
+    ///
+    /// SUB sockets drop everything published before the subscription reaches
+    /// the kernel, so the first requests may go unseen.
+    async fn wait_for_iopub(&mut self) -> Result<()> {
+        loop {
+            let msg_id = self.send_shell("kernel_info_request", json!({})).await?;
+            let probe = tokio::time::timeout(Duration::from_millis(500), async {
+                loop {
+                    let msg = self.recv_iopub().await?;
+                    if msg.parent_id() == msg_id {
+                        return Ok::<_, SandboxError>(());
+                    }
+                }
+            })
+            .await;
+            if let Ok(result) = probe {
+                return result;
+            }
+        }
+    }
+
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.connection.key.is_empty() {
+            return String::new();
+        }
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.connection.key.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn encode(&self, msg_type: &str, content: Value) -> Result<(String, ZmqMessage)> {
+        let msg_id = uuid::Uuid::new_v4().to_string();
+        let header = serde_json::to_vec(&json!({
+            "msg_id": msg_id,
+            "session": self.session,
+            "username": "pysandbox",
+            "date": iso8601_now(),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        }))?;
+        let parent_header = b"{}".to_vec();
+        let metadata = b"{}".to_vec();
+        let content = serde_json::to_vec(&content)?;
+        let signature = self.sign(&[&header, &parent_header, &metadata, &content]);
+
+        let frames: Vec<Bytes> = vec![
+            Bytes::from_static(DELIMITER),
+            Bytes::from(signature),
+            Bytes::from(header),
+            Bytes::from(parent_header),
+            Bytes::from(metadata),
+            Bytes::from(content),
+        ];
+        let message = ZmqMessage::try_from(frames)
+            .map_err(|_| SandboxError::InternalError("Empty kernel message".to_string()))?;
+        Ok((msg_id, message))
+    }
+
+    /// Decode a message, returning `None` for malformed or badly signed ones
+    fn decode(&self, message: ZmqMessage) -> Option<KernelMessage> {
+        let frames = message.into_vec();
+        let start = frames.iter().position(|f| f.as_ref() == DELIMITER)?;
+        let parts = frames.get(start + 1..start + 6)?;
+
+        let expected = self.sign(&[&parts[1], &parts[2], &parts[3], &parts[4]]);
+        if parts[0].as_ref() != expected.as_bytes() {
+            warn!("[JUPYTER] Dropping message with invalid signature");
+            return None;
+        }
+
+        Some(KernelMessage {
+            header: serde_json::from_slice(&parts[1]).ok()?,
+            parent_header: serde_json::from_slice(&parts[2]).ok()?,
+            content: serde_json::from_slice(&parts[4]).ok()?,
+        })
+    }
+
+    async fn send_shell(&mut self, msg_type: &str, content: Value) -> Result<String> {
+        let (msg_id, message) = self.encode(msg_type, content)?;
+        self.shell.send(message).await.map_err(send_error)?;
+        Ok(msg_id)
+    }
+
+    async fn recv_iopub(&mut self) -> Result<KernelMessage> {
+        loop {
+            let message = self.iopub.recv().await.map_err(recv_error)?;
+            if let Some(msg) = self.decode(message) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Wait for the shell reply to `msg_id`, skipping replies to older requests
+    async fn recv_shell_reply(&mut self, msg_id: &str) -> Result<KernelMessage> {
+        loop {
+            let message = self.shell.recv().await.map_err(recv_error)?;
+            if let Some(msg) = self.decode(message) {
+                if msg.parent_id() == msg_id {
+                    return Ok(msg);
+                }
+            }
+        }
+    }
+
+    /// Execute a cell and collect its output until the kernel goes idle
+    async fn run_cell(&mut self, code: &str, silent: bool) -> Result<CellOutput> {
+        let msg_id = self
+            .send_shell(
+                "execute_request",
+                json!({
+                    "code": code,
+                    "silent": silent,
+                    "store_history": false,
+                    "user_expressions": {},
+                    "allow_stdin": false,
+                    "stop_on_error": false,
+                }),
+            )
+            .await?;
+
+        let mut output = CellOutput::default();
+        loop {
+            let msg = self.recv_iopub().await?;
+            if msg.parent_id() != msg_id {
+                continue;
+            }
+            let content = &msg.content;
+            match msg.msg_type() {
+                "stream" => {
+                    let text = content["text"].as_str().unwrap_or_default();
+                    if content["name"] == "stderr" {
+                        output.stderr.push_str(text);
+                    } else {
+                        output.stdout.push_str(text);
+                    }
+                }
+                "display_data" | "execute_result" => {
+                    output.displays.push(json!({
+                        "data": content["data"],
+                        "metadata": content["metadata"],
+                    }));
+                }
+                "error" => {
+                    output.error = Some(format!(
+                        "{}: {}",
+                        content["ename"].as_str().unwrap_or("Error"),
+                        content["evalue"].as_str().unwrap_or_default()
+                    ));
+                }
+                "status" if content["execution_state"] == "idle" => break,
+                _ => {}
+            }
+        }
+
+        let reply = self.recv_shell_reply(&msg_id).await?;
+        output.execution_count = reply.content["execution_count"].as_u64();
+        if output.error.is_none() && reply.content["status"] == "error" {
+            output.error = Some(format!(
+                "{}: {}",
+                reply.content["ename"].as_str().unwrap_or("Error"),
+                reply.content["evalue"].as_str().unwrap_or_default()
+            ));
+        }
+        Ok(output)
+    }
+
+    /// Ask the kernel to interrupt the running cell
+    async fn interrupt(&mut self) -> Result<()> {
+        let (_, message) = self.encode("interrupt_request", json!({}))?;
+        self.control.send(message).await.map_err(send_error)
+    }
+}
+
+fn send_error(e: zeromq::ZmqError) -> SandboxError {
+    SandboxError::InternalError(format!("Failed to send to kernel: {}", e))
+}
+
+fn recv_error(e: zeromq::ZmqError) -> SandboxError {
+    SandboxError::InternalError(format!("Failed to receive from kernel: {}", e))
+}
+
+/// Cell that installs the import guard and inputs before user code
+fn setup_cell(inputs: &Value, options: &ExecutionOptions) -> Result<String> {
+    Ok(format!(
+        r#"
+import builtins as _rzn_builtins
+import json as _rzn_json
+import sys as _rzn_sys
+if not hasattr(_rzn_builtins, '_rzn_kernel_import'):
+    _rzn_builtins._rzn_kernel_import = _rzn_builtins.__import__
+_rzn_builtins.__import__ = _rzn_builtins._rzn_kernel_import
+# The guard drops disallowed modules from the cache; the kernel needs them back
+_rzn_builtins._rzn_kernel_modules = dict(_rzn_sys.modules)
+globals().pop('result', None)
+inputs = _rzn_json.loads({})
+{}
+# Security setup
+{}
+"#,
+        // A JSON string is also a valid Python string literal
+        serde_json::to_string(&serde_json::to_string(inputs)?)?,
+        DENIAL_MARKER,
+        SandboxedPythonEngine::generate_import_control(&options.import_policy)
+    ))
+}
+
+/// Cell that removes the import guard and reports `result`
+///
+/// The kernel may run on another host, so `channel` only has sentinels.
+fn teardown_cell(channel: &ResultChannel) -> String {
+    format!(
+        "{}{}\n{}\n",
+        REMOVE_IMPORT_GUARD,
+        RESULT_SERIALIZER,
+        channel.report("_rzn_result_value(globals().get('result'))", "None")
+    )
+}
+
+const REMOVE_IMPORT_GUARD: &str = r#"
+import builtins as _rzn_builtins
+_rzn_builtins.__import__ = _rzn_builtins._rzn_kernel_import
+import sys as _rzn_sys
+_rzn_sys.meta_path[:] = [
+    f for f in _rzn_sys.meta_path if getattr(f, '__name__', None) != '_RznImportPolicyFinder'
+]
+for _rzn_name, _rzn_module in _rzn_builtins._rzn_kernel_modules.items():
+    _rzn_sys.modules.setdefault(_rzn_name, _rzn_module)
+"#;
+
+#[async_trait]
+impl PythonEngine for JupyterKernelEngine {
+    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        // Syntax errors are reported by the kernel
+        Ok(Vec::new())
+    }
+
+    async fn execute(
+        &mut self,
+        code: &str,
+        inputs: Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let setup = self.run_cell(&setup_cell(&inputs, options)?, true).await?;
+        if let Some(error) = setup.error {
+            return Err(SandboxError::InternalError(format!(
+                "Failed to prepare kernel: {}",
+                error
+            )));
+        }
+
+        let channel = ResultChannel::sentinels();
+        let cell = match tokio::time::timeout(options.timeout, self.run_cell(code, false)).await {
+            Ok(cell) => cell,
+            Err(_) => {
+                // Interrupt the cell and give it a moment to unwind before
+                // removing the guard; late messages are filtered by parent id
+                self.interrupt().await?;
+                tokio::time::sleep(self.config.interrupt_grace).await;
+                self.run_cell(&teardown_cell(&channel), true).await?;
+                return Err(SandboxError::Timeout);
+            }
+        };
+        let teardown = self.run_cell(&teardown_cell(&channel), false).await?;
+        let cell = cell?;
+
+        if let Some(error) = cell.error {
+            return Err(SandboxError::RuntimeError(error));
+        }
+
+        let result = channel.take(&teardown.stdout).unwrap_or(Value::Null);
+
+        ExecutionResult::from_value(json!({
+            "stdout": (!cell.stdout.is_empty()).then_some(cell.stdout),
+            "stderr": (!cell.stderr.is_empty()).then_some(cell.stderr),
+            "result": result,
+            "displays": cell.displays,
+            "execution_count": cell.execution_count,
+        }))
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: format!(
+                "Jupyter kernel ({})",
+                self.connection.kernel_name.as_deref().unwrap_or("python")
+            ),
+            numpy: true, // Depends on the kernel environment
+            matplotlib: true,
+            pandas: true,
+            max_memory_mb: self.config.limits.memory_mb,
+            max_cpu_seconds: self.config.limits.cpu_seconds,
+            security_level: 3, // Import guard only, kernel is host-managed
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        // The kernel belongs to the host; only detach from it
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_file_defaults() {
+        let connection: KernelConnection = serde_json::from_str(
+            r#"{"transport": "tcp", "ip": "127.0.0.1", "shell_port": 1, "iopub_port": 2,
+                "control_port": 3, "stdin_port": 4, "hb_port": 5, "key": "secret"}"#,
+        )
+        .unwrap();
+        assert_eq!(connection.signature_scheme, "hmac-sha256");
+        assert_eq!(
+            connection.endpoint(connection.shell_port),
+            "tcp://127.0.0.1:1"
+        );
+    }
+
+    #[test]
+    fn test_iso8601_now_shape() {
+        let now = iso8601_now();
+        assert_eq!(now.len(), "2024-01-01T00:00:00.000000Z".len());
+        assert!(now.ends_with('Z'));
+    }
+}
@@ -0,0 +1,206 @@
+//! Landlock (Linux) enforcement of filesystem path rules
+//!
+//! Turns the same [`FilesystemRules`] the Seatbelt profile and the audit hook
+//! consume into a Landlock ruleset the child applies to itself right before
+//! `exec`, through raw syscalls since libc has no wrappers.
+//!
+//! Landlock can only grant access, so a `deny` entry is enforced here only
+//! when no read or write tree contains it; denials carved out of a granted
+//! tree are left to the audit hook. Kernels without Landlock get no ruleset,
+//! and the in-process guards stay the only enforcement.
+
+use crate::policy::FilesystemRules;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use tokio::process::Command;
+
+const CREATE_RULESET_VERSION: libc::c_uint = 1;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+const ACCESS_EXECUTE: u64 = 1 << 0;
+const ACCESS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_READ_FILE: u64 = 1 << 2;
+const ACCESS_READ_DIR: u64 = 1 << 3;
+/// Removing and making entries of every kind (ABI 1)
+const ACCESS_MAKE_AND_REMOVE: u64 = 0x1ff0;
+/// Linking or renaming into another directory (ABI 2)
+const ACCESS_REFER: u64 = 1 << 13;
+/// `truncate` and `O_TRUNC` (ABI 3)
+const ACCESS_TRUNCATE: u64 = 1 << 14;
+
+const ACCESS_READ: u64 = ACCESS_EXECUTE | ACCESS_READ_FILE | ACCESS_READ_DIR;
+/// Rights that apply to a file rather than a directory
+const ACCESS_FILE: u64 = ACCESS_EXECUTE | ACCESS_WRITE_FILE | ACCESS_READ_FILE | ACCESS_TRUNCATE;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: libc::c_int,
+}
+
+/// A Landlock ruleset, ready to restrict a child
+pub(crate) struct Ruleset {
+    fd: OwnedFd,
+}
+
+impl Ruleset {
+    /// Build a ruleset granting the read and write trees of `rules`
+    ///
+    /// Returns `None` for rules Landlock can't narrow (`write_any`) and on
+    /// kernels without Landlock. Trees that don't exist are skipped.
+    pub(crate) fn new(rules: &FilesystemRules) -> std::io::Result<Option<Self>> {
+        if rules.write_any {
+            return Ok(None);
+        }
+        // SAFETY: a version query takes no attribute
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 1 {
+            return Ok(None);
+        }
+        let mut write = ACCESS_READ | ACCESS_WRITE_FILE | ACCESS_MAKE_AND_REMOVE;
+        if abi >= 2 {
+            write |= ACCESS_REFER;
+        }
+        if abi >= 3 {
+            write |= ACCESS_TRUNCATE;
+        }
+        let attr = RulesetAttr {
+            handled_access_fs: write,
+        };
+        // SAFETY: `attr` outlives the call and its size is passed along
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0 as libc::c_uint,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // SAFETY: the kernel just handed us this descriptor (close-on-exec)
+        let ruleset = Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) },
+        };
+        if rules.read_any {
+            ruleset.allow(Path::new("/"), ACCESS_READ)?;
+        }
+        for path in &rules.read {
+            ruleset.allow(path, ACCESS_READ)?;
+        }
+        for path in &rules.write {
+            ruleset.allow(path, write)?;
+        }
+        Ok(Some(ruleset))
+    }
+
+    fn allow(&self, path: &Path, access: u64) -> std::io::Result<()> {
+        let file = match std::fs::File::options()
+            .read(true)
+            .custom_flags(libc::O_PATH)
+            .open(path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        // Directory rights on a file are rejected
+        let access = if file.metadata()?.is_dir() {
+            access
+        } else {
+            access & ACCESS_FILE
+        };
+        let attr = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: file.as_raw_fd(),
+        };
+        // SAFETY: `attr` and the descriptors outlive the call
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                self.fd.as_raw_fd(),
+                RULE_PATH_BENEATH,
+                &attr as *const PathBeneathAttr,
+                0 as libc::c_uint,
+            )
+        };
+        if rc != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Restrict the child to this ruleset right before it execs
+    ///
+    /// The ruleset must stay alive until the command is spawned.
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        let fd = self.fd.as_raw_fd();
+        // SAFETY: only async-signal-safe syscalls run between fork and exec
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if libc::syscall(libc::SYS_landlock_restrict_self, fd, 0 as libc::c_uint) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ruleset_limits_child_to_granted_trees() {
+        let readable = tempfile::tempdir().unwrap();
+        let writable = tempfile::tempdir().unwrap();
+        let hidden = tempfile::tempdir().unwrap();
+        std::fs::write(readable.path().join("f"), "x").unwrap();
+        std::fs::write(hidden.path().join("f"), "x").unwrap();
+        let rules = FilesystemRules::new()
+            .read("/usr")
+            .read("/lib")
+            .read("/lib64")
+            .read("/bin")
+            .read(readable.path())
+            .write(writable.path());
+        let Ok(Some(ruleset)) = Ruleset::new(&rules) else {
+            return;
+        };
+        let probe = |args: &str| {
+            let mut cmd = Command::new("/bin/sh");
+            cmd.arg("-c")
+                .arg(args)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null());
+            ruleset.apply(&mut cmd);
+            cmd
+        };
+        let status = |mut cmd: Command| async move { cmd.status().await.unwrap().success() };
+        let read = |dir: &Path| format!("cat {}", dir.join("f").display());
+        let write = |dir: &Path| format!("echo y > {}", dir.join("g").display());
+
+        assert!(status(probe(&read(readable.path()))).await);
+        assert!(!status(probe(&write(readable.path()))).await);
+        assert!(status(probe(&write(writable.path()))).await);
+        assert!(!status(probe(&read(hidden.path()))).await);
+    }
+}
@@ -1,10 +1,43 @@
+pub mod artifacts;
+pub mod audit;
+pub mod audit_hook;
+pub mod blocking;
+pub mod cache;
+pub mod conda;
 pub mod config;
+pub mod container;
+pub mod discovery;
+pub mod egress;
 pub mod engine;
+pub mod envs;
 pub mod errors;
+pub mod events;
+pub mod fallback;
+pub mod health;
+pub mod janitor;
+pub mod metrics;
 pub mod native;
+pub mod pip_audit;
 pub mod policy;
+pub mod pool;
+pub mod redact;
+pub mod repl;
+pub mod result_file;
+pub mod rustpython_engine;
 pub mod sandbox_builder;
+pub mod sandbox_pool;
 pub mod sandboxed;
+pub mod sbom;
+pub mod seatbelt;
+pub mod session;
+pub mod signing;
+pub mod standalone;
+pub mod stdin;
+mod supervisor;
+pub mod transcript;
+
+#[cfg(target_os = "linux")]
+mod landlock;
 
 #[cfg(feature = "microsandbox-engine")]
 pub mod microsandbox_engine;
@@ -15,10 +48,53 @@ pub mod microsandbox_setup;
 #[cfg(feature = "microsandbox-engine")]
 pub mod microsandbox_auth;
 
-pub use config::{ExecutionMode, ImportPolicy, ResourceLimits, SecurityProfile};
-pub use engine::{EngineCapabilities, ExecutionOptions, PythonEngine};
-pub use errors::{Result, SandboxError};
+#[cfg(feature = "wasm-engine")]
+pub mod wasm_engine;
+
+#[cfg(feature = "remote-engine")]
+pub mod remote;
+
+#[cfg(feature = "remote-policy")]
+pub mod policy_fetch;
+
+#[cfg(feature = "grpc")]
+pub mod grpc;
+
+#[cfg(feature = "jupyter-engine")]
+pub mod jupyter;
 
+#[cfg(feature = "static-analyzer")]
+pub mod analyzer;
+
+#[cfg(feature = "ndarray")]
+pub mod npy;
+
+pub use artifacts::{ArtifactMeta, ArtifactStore, RetentionPolicy, StoredArtifact};
+#[cfg(unix)]
+pub use audit::SyslogAuditSink;
+pub use audit::{
+    AuditEvent, AuditEventKind, AuditLog, AuditOutcome, AuditSink, CallbackAuditSink,
+    JsonlAuditSink, RotatingAuditSink,
+};
+pub use cache::{CacheControl, ExecutionCache};
+pub use config::{CodeLimits, ExecutionMode, ImportPolicy, ResourceLimits, SecurityProfile};
+pub use discovery::{discover_pythons, DiscoveredPython, PythonDiscovery, PythonSource};
+pub use egress::{NetworkBudget, NetworkEnforcement};
+pub use engine::{
+    Diagnostic, EngineCapabilities, ExecutionMetrics, ExecutionOptions, ExecutionResult,
+    PythonEngine, Severity,
+};
+pub use envs::{CreateEnvOptions, EnvManager, ManagedEnv, ManagedEnvRegistry};
+pub use errors::{PythonException, Result, SandboxError, TracebackFrame};
+pub use events::{event_channel, EventReceiver, EventSender, ExecutionEvent, OutputStream};
+pub use fallback::FallbackStrategy;
+pub use health::{EngineHealthEvent, HealthConfig};
+pub use janitor::{JanitorReport, WorkspaceJanitor};
+pub use stdin::StdinSource;
+
+use health::{EngineHealth, HealthTask};
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -26,56 +102,454 @@ use tokio::sync::RwLock;
 pub struct PythonSandbox {
     engines: Vec<Arc<RwLock<Box<dyn PythonEngine>>>>,
     primary_engine: usize,
+    session_python: Option<PathBuf>,
+    session_limits: ResourceLimits,
+    policy_watch: Option<PolicyWatch>,
+    audit_log: Option<AuditLog>,
+    engine_names: Vec<String>,
+    engine_keys: HashMap<String, usize>,
+    policy_name: Option<String>,
+    policy: Option<Arc<SandboxPolicy>>,
+    transcripts: Option<TranscriptStore>,
+    redactor: Option<Redactor>,
+    cache: Option<ExecutionCache>,
+    fallback: FallbackStrategy,
+    health: Option<Arc<EngineHealth>>,
+    health_task: Option<HealthTask>,
 }
 
 impl PythonSandbox {
     /// Create a new sandbox with the specified engines
     pub fn new(engines: Vec<Box<dyn PythonEngine>>) -> Self {
         Self {
+            engine_names: engines.iter().map(|e| e.capabilities().name).collect(),
             engines: engines
                 .into_iter()
                 .map(|e| Arc::new(RwLock::new(e)))
                 .collect(),
+            engine_keys: HashMap::new(),
             primary_engine: 0,
+            session_python: None,
+            session_limits: ResourceLimits::default(),
+            policy_watch: None,
+            audit_log: None,
+            policy_name: None,
+            policy: None,
+            transcripts: None,
+            redactor: None,
+            cache: None,
+            fallback: FallbackStrategy::default(),
+            health: None,
+            health_task: None,
+        }
+    }
+
+    /// Register `key` for the engine at `index` of those passed to `new`, so
+    /// [`ExecutionOptions::preferred_engine`] can pick it
+    pub fn with_engine_key(mut self, index: usize, key: &str) -> Self {
+        if index < self.engines.len() {
+            self.engine_keys.insert(key.to_string(), index);
+        }
+        self
+    }
+
+    /// Index of the engine an execution starts on: the preferred engine,
+    /// matched by key or case-insensitively by name, else the primary one
+    fn first_engine(&self, options: &ExecutionOptions) -> Result<usize> {
+        let Some(preferred) = &options.preferred_engine else {
+            return Ok(self.primary_engine);
+        };
+        self.engine_keys
+            .get(preferred)
+            .copied()
+            .or_else(|| {
+                self.engine_names
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(preferred))
+            })
+            .ok_or_else(|| SandboxError::UnknownEngine(preferred.clone()))
+    }
+
+    /// Use a specific Python for sessions (defaults to Python from PATH)
+    pub fn with_session_python(mut self, python_path: PathBuf) -> Self {
+        self.session_python = Some(python_path);
+        self
+    }
+
+    /// Resource limits applied to session interpreters
+    pub fn with_session_limits(mut self, limits: ResourceLimits) -> Self {
+        self.session_limits = limits;
+        self
+    }
+
+    /// Keep a watched policy with the sandbox so consumers can follow reloads
+    pub fn with_policy_watch(mut self, watch: PolicyWatch) -> Self {
+        self.policy_watch = Some(watch);
+        self
+    }
+
+    /// Record every execution to an audit log
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Policy name used to label this sandbox's [`metrics`]
+    pub fn with_policy_name(mut self, name: &str) -> Self {
+        self.policy_name = Some(name.to_string());
+        self
+    }
+
+    /// Record the policy this sandbox was built for, for transcripts and
+    /// metrics
+    pub fn with_policy(mut self, policy: SandboxPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Save a [`Transcript`](transcript::Transcript) of every execution
+    ///
+    /// Successful results carry the transcript's id as `transcript_id`.
+    pub fn with_transcripts(mut self, store: TranscriptStore) -> Self {
+        self.transcripts = Some(store);
+        self
+    }
+
+    /// Scrub secrets from results, streamed output, errors, audit events and
+    /// transcripts
+    ///
+    /// Values of the call's `env_vars` are matched according to the
+    /// redactor's rules, see [`redact`].
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Reuse the outputs of identical executions, see [`cache`]
+    pub fn with_cache(mut self, cache: ExecutionCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Control when a failed execution is retried on the other engines, see
+    /// [`fallback`]
+    pub fn with_fallback_strategy(mut self, strategy: FallbackStrategy) -> Self {
+        self.fallback = strategy;
+        self
+    }
+
+    /// Quarantine engines that keep failing and check their health, see
+    /// [`health`]
+    ///
+    /// Periodic checks run on the current tokio runtime.
+    pub fn with_health_checks(mut self, config: HealthConfig) -> Self {
+        let health = Arc::new(EngineHealth::new(
+            config.failure_threshold,
+            self.engine_names.clone(),
+        ));
+        self.health_task = config
+            .check_interval
+            .map(|interval| HealthTask::spawn(self.engines.clone(), health.clone(), interval));
+        self.health = Some(health);
+        self
+    }
+
+    /// Run every engine's health check now, lifting the quarantine of those
+    /// that pass
+    ///
+    /// Returns each engine's name and outcome, `None` for engines busy
+    /// executing. Without `with_health_checks` nothing is tracked.
+    pub async fn check_health(&self) -> Vec<(String, Option<Result<()>>)> {
+        match &self.health {
+            Some(health) => health::check_engines(&self.engines, health).await,
+            None => {
+                let untracked = EngineHealth::new(1, self.engine_names.clone());
+                health::check_engines(&self.engines, &untracked).await
+            }
         }
     }
 
-    /// Execute Python code using the primary engine with fallback support
+    /// Names of the engines currently quarantined
+    pub fn quarantined_engines(&self) -> Vec<String> {
+        self.health
+            .as_ref()
+            .map(|health| health.quarantined())
+            .unwrap_or_default()
+    }
+
+    /// Receive an [`EngineHealthEvent`] whenever an engine is quarantined or
+    /// recovers
+    pub fn subscribe_engine_health(
+        &self,
+    ) -> Option<tokio::sync::broadcast::Receiver<EngineHealthEvent>> {
+        self.health.as_ref().map(|health| health.subscribe())
+    }
+
+    /// Current effective policy: the watched policy if a policy watch is
+    /// attached, else the one set with `with_policy`
+    pub fn effective_policy(&self) -> Option<Arc<SandboxPolicy>> {
+        self.policy_watch
+            .as_ref()
+            .map(PolicyWatch::effective_policy)
+            .or_else(|| self.policy.clone())
+    }
+
+    /// Receive a [`PolicyChanged`] whenever the attached policy watch reloads
+    pub fn subscribe_policy_changes(
+        &self,
+    ) -> Option<tokio::sync::broadcast::Receiver<PolicyChanged>> {
+        self.policy_watch.as_ref().map(PolicyWatch::subscribe)
+    }
+
+    /// Start a persistent session whose variables survive between calls
+    ///
+    /// The session runs in its own long-lived interpreter with the import and
+    /// network guards from `options` installed once at startup. Its
+    /// `input_files` are staged once too, under `INPUT_DIR` for every call.
+    pub async fn create_session(&self, options: ExecutionOptions) -> Result<SessionHandle> {
+        let python_path = match &self.session_python {
+            Some(path) => path.clone(),
+            None => which::which("python3")
+                .or_else(|_| which::which("python"))
+                .map_err(|_| SandboxError::PythonNotFound)?,
+        };
+        SessionHandle::start(&python_path, &self.session_limits, options).await
+    }
+
+    /// Start an interactive REPL on a fresh session
+    pub async fn create_repl(&self, options: ExecutionOptions) -> Result<ReplHandle> {
+        Ok(ReplHandle::new(self.create_session(options).await?))
+    }
+
+    /// Execute Python code using the primary engine, or the call's
+    /// `preferred_engine`, with fallback support
     pub async fn execute(
         &self,
         code: &str,
         inputs: serde_json::Value,
         options: ExecutionOptions,
-    ) -> Result<serde_json::Value> {
-        let primary = &self.engines[self.primary_engine];
+    ) -> Result<ExecutionResult> {
+        let cache_key = match (&self.cache, options.cache) {
+            (Some(_), CacheControl::Use | CacheControl::Refresh) => {
+                ExecutionCache::key(code, &inputs, self.effective_policy().as_deref(), &options)
+            }
+            _ => None,
+        };
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(code, AuditEventKind::ExecutionStarted);
+        }
+        let recorded_inputs = self.transcripts.as_ref().map(|_| inputs.clone());
+        let started = std::time::Instant::now();
+        let cached = match (&self.cache, &cache_key, options.cache) {
+            (Some(cache), Some(key), CacheControl::Use) => cache.get(key),
+            _ => None,
+        };
+        let (engine, mut result) = match cached {
+            // Stored redacted, so served as is
+            Some(output) => (cache::CACHE_ENGINE_NAME, Ok(output)),
+            None => {
+                let (engine, result) = self
+                    .execute_uncached(code, inputs, options, cache_key.as_deref())
+                    .await;
+                (self.engine_names[engine].as_str(), result)
+            }
+        };
+        let elapsed = started.elapsed();
 
-        match primary
-            .write()
-            .await
-            .execute(code, inputs.clone(), &options)
-            .await
-        {
-            Ok(result) => Ok(result),
-            Err(e) if self.engines.len() > 1 => {
-                tracing::warn!("Primary engine failed: {}, trying fallback", e);
+        if let (Some(store), Some(inputs)) = (&self.transcripts, recorded_inputs) {
+            let policy = self.effective_policy().map(|p| (*p).clone());
+            let transcript = transcript::Transcript::new(code, &inputs, policy, &result, elapsed);
+            match store.save(&transcript) {
+                Ok(_) => {
+                    if let Ok(output) = &mut result {
+                        output.extra.insert(
+                            "transcript_id".to_string(),
+                            serde_json::Value::String(transcript.id),
+                        );
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to save transcript: {}", e),
+            }
+        }
+
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record_result(code, &result, elapsed);
+        }
+        let policy = match (&self.policy_name, self.effective_policy()) {
+            (Some(name), _) => name.clone(),
+            (None, Some(policy)) => policy.name.clone(),
+            (None, None) => "none".to_string(),
+        };
+        metrics::registry().record_execution(engine, &policy, &result, elapsed);
+        result
+    }
+
+    /// Run on the engines, redacting the output and caching it under
+    /// `cache_key`; returns the index of the engine that produced it
+    async fn execute_uncached(
+        &self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: ExecutionOptions,
+        cache_key: Option<&str>,
+    ) -> (usize, Result<ExecutionResult>) {
+        let redactor = self
+            .redactor
+            .as_ref()
+            .map(|r| r.for_env_vars(&options.env_vars));
+        let options = match (&redactor, &options.events) {
+            (Some(redactor), Some(events)) => ExecutionOptions {
+                events: Some(redact::redact_events(redactor.clone(), events.clone())),
+                ..options
+            },
+            _ => options,
+        };
+
+        let (engine, mut result) = self
+            .execute_with_fallback(code, inputs, options, redactor.as_ref())
+            .await;
+
+        if let Some(redactor) = &redactor {
+            result = match result {
+                Ok(mut output) => {
+                    redactor.redact_result(&mut output);
+                    Ok(output)
+                }
+                Err(e) => Err(redactor.redact_error(e)),
+            };
+        }
+
+        // Spilled results live in files that may be taken, so they aren't kept
+        if let (Some(cache), Some(key), Ok(output)) = (&self.cache, cache_key, &result) {
+            if !result_file::is_spilled(output) {
+                cache.insert(key, output);
+            }
+        }
+        (engine, result)
+    }
+
+    /// Execute with `inputs` serialized from `I`, deserializing the `result`
+    /// variable as `O`
+    ///
+    /// A spilled result is loaded from its file; code that sets no `result`
+    /// deserializes from `null`, so `O = Option<T>` accepts it.
+    pub async fn execute_typed<I, O>(
+        &self,
+        code: &str,
+        inputs: &I,
+        options: ExecutionOptions,
+    ) -> Result<O>
+    where
+        I: serde::Serialize + ?Sized,
+        O: serde::de::DeserializeOwned,
+    {
+        let inputs = serde_json::to_value(inputs)?;
+        self.execute(code, inputs, options).await?.result_as()
+    }
+
+    /// Run on the primary engine, then each fallback; returns the index of
+    /// the engine that produced the result
+    ///
+    /// Quarantined engines are skipped, a quarantined primary counting as
+    /// unavailable.
+    async fn execute_with_fallback(
+        &self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: ExecutionOptions,
+        redactor: Option<&Redactor>,
+    ) -> (usize, Result<ExecutionResult>) {
+        let shown = |e: &SandboxError| match redactor {
+            Some(redactor) => redactor.redact(&e.to_string()).into_owned(),
+            None => e.to_string(),
+        };
+        let quarantined = |idx| self.health.as_ref().is_some_and(|h| h.is_quarantined(idx));
+        let first = match self.first_engine(&options) {
+            Ok(first) => first,
+            Err(e) => return (self.primary_engine, Err(e)),
+        };
+
+        let primary = if quarantined(first) {
+            tracing::debug!("Primary engine {} is quarantined", first);
+            Err(SandboxError::NoEngineAvailable)
+        } else {
+            self.run_engine(first, code, inputs.clone(), &options, &shown)
+                .await
+        };
+        match primary {
+            Err(e) if self.engines.len() > 1 && self.fallback.retries(&e) => {
+                tracing::warn!("Primary engine failed: {}, trying fallback", shown(&e));
                 // Try fallback engines
                 for (idx, engine) in self.engines.iter().enumerate() {
-                    if idx != self.primary_engine {
-                        match engine
-                            .write()
-                            .await
-                            .execute(code, inputs.clone(), &options)
+                    if idx != first && !quarantined(idx) {
+                        let security_level = engine.read().await.capabilities().security_level;
+                        if !self.fallback.admits(security_level) {
+                            tracing::debug!(
+                                "Skipping fallback engine {} below the security floor",
+                                idx
+                            );
+                            continue;
+                        }
+                        match self
+                            .run_engine(idx, code, inputs.clone(), &options, &shown)
                             .await
                         {
-                            Ok(result) => return Ok(result),
-                            Err(e) => tracing::warn!("Fallback engine {} failed: {}", idx, e),
+                            Ok(result) => return (idx, Ok(result)),
+                            Err(SandboxError::Cancelled) => {
+                                return (idx, Err(SandboxError::Cancelled))
+                            }
+                            Err(e) => {
+                                tracing::warn!("Fallback engine {} failed: {}", idx, shown(&e))
+                            }
                         }
                     }
                 }
-                Err(e)
+                (first, Err(e))
             }
-            Err(e) => Err(e),
+            result => (first, result),
+        }
+    }
+
+    /// Execute on one engine, counting the outcome towards its health
+    async fn run_engine(
+        &self,
+        idx: usize,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+        shown: &impl Fn(&SandboxError) -> String,
+    ) -> Result<ExecutionResult> {
+        let result = self.engines[idx]
+            .write()
+            .await
+            .execute(code, inputs, options)
+            .await;
+        if let Some(health) = &self.health {
+            health.record(idx, &result, shown);
         }
+        result
+    }
+
+    /// Validate code with the primary engine, or the call's preferred one
+    pub async fn validate(
+        &self,
+        code: &str,
+        options: &ExecutionOptions,
+    ) -> Result<Vec<Diagnostic>> {
+        self.engines[self.first_engine(options)?]
+            .read()
+            .await
+            .validate(code, options)
+            .await
+    }
+
+    /// Pre-import `modules` in every engine that keeps interpreters warm, see
+    /// [`PythonEngine::warmup`]
+    pub async fn warmup(&self, modules: &[&str]) -> Result<()> {
+        for engine in &self.engines {
+            engine.write().await.warmup(modules).await?;
+        }
+        Ok(())
     }
 
     /// Get capabilities of all engines
@@ -97,10 +571,53 @@ pub use sandbox_builder::{
 // Re-export native engine for direct use
 pub use native::NativePythonEngine;
 
+// Re-export warm pool engine
+pub use pool::{PoolConfig, PooledPythonEngine};
+
+// Re-export concurrent execution pool
+pub use sandbox_pool::{Priority, SandboxPool, SandboxPoolConfig};
+
+// Re-export persistent sessions
+pub use session::SessionHandle;
+
+// Re-export execution transcripts
+pub use transcript::{Transcript, TranscriptStore};
+
+// Re-export secret redaction
+pub use redact::Redactor;
+
+// Re-export interactive REPL
+pub use repl::{ReplHandle, ReplOutput, ReplResult};
+
+// Re-export conda environments
+pub use conda::CondaEnv;
+
+// Re-export container engine
+pub use container::{ContainerConfig, ContainerPythonEngine, ContainerRuntime};
+
+// Re-export RustPython engine
+pub use rustpython_engine::{RustPythonConfig, RustPythonEngine};
+
+// Re-export WebAssembly engine
+#[cfg(feature = "wasm-engine")]
+pub use wasm_engine::{WasmConfig, WasmPythonEngine};
+
+// Re-export remote engine
+#[cfg(feature = "remote-engine")]
+pub use remote::{RemoteConfig, RemotePythonEngine, RemoteTlsConfig};
+
+// Re-export gRPC client engine
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcPythonEngine;
+
+// Re-export Jupyter kernel engine
+#[cfg(feature = "jupyter-engine")]
+pub use jupyter::{JupyterConfig, JupyterKernelEngine, KernelConnection};
+
 // Re-export sandboxed engine and types
 pub use sandboxed::{
-    IsolatedWorkspace, SandboxConfig, SandboxedExecutionBuilder, SandboxedExecutionResult,
-    SandboxedPythonEngine,
+    default_workspace_base, IsolatedWorkspace, SandboxConfig, SandboxedExecutionBuilder,
+    SandboxedExecutionResult, SandboxedPythonEngine, SnapshotId,
 };
 
 // Re-export policy system
@@ -112,10 +629,106 @@ pub use policy::{
     ImportPolicyType,
     // Primitives
     NetworkPolicy,
+    NetworkRule,
+    PackageInstallPolicy,
+    PolicyChanged,
     PolicyManager,
+    PolicyResolver,
+    PolicyWatch,
     ProcessPolicy,
     ResourceLimitsPolicy,
     // Policy
     SandboxPolicy,
     SandboxPolicyBuilder,
+    TenantPolicy,
+    TenantPolicyMap,
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_hits_are_audited_and_counted() {
+        let Ok(native) = NativePythonEngine::new() else {
+            return;
+        };
+        let log = tempfile::NamedTempFile::new().unwrap();
+        let sandbox = PythonSandbox::new(vec![Box::new(native)])
+            .with_cache(ExecutionCache::new(4))
+            .with_audit_log(AuditLog::new(JsonlAuditSink::open(log.path()).unwrap()))
+            .with_policy_name("cache-audit-test");
+        for _ in 0..2 {
+            let output = sandbox
+                .execute("result = 6 * 7", serde_json::json!({}), Default::default())
+                .await
+                .unwrap();
+            assert_eq!(output.result_as::<i64>().unwrap(), 42);
+        }
+
+        let events: Vec<serde_json::Value> = std::fs::read_to_string(log.path())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let kinds: Vec<&str> = events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "execution_started",
+                "execution_finished",
+                "execution_started",
+                "execution_finished"
+            ]
+        );
+        assert!(events[1].get("cache_hit").is_none());
+        assert_eq!(events[3]["cache_hit"], true);
+        assert!(metrics::gather().contains(
+            "pysandbox_executions_total{engine=\"cache\",policy=\"cache-audit-test\",outcome=\"success\"} 1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_preferred_engine_selection() {
+        let (Ok(native), Ok(pool)) = (
+            NativePythonEngine::new(),
+            PooledPythonEngine::new(PoolConfig::default()),
+        ) else {
+            return;
+        };
+        let sandbox = PythonSandbox::new(vec![Box::new(native), Box::new(pool)])
+            .with_engine_key(0, "native")
+            .with_engine_key(1, "pool");
+        let prefer = |engine: &str| ExecutionOptions {
+            preferred_engine: Some(engine.to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sandbox.first_engine(&ExecutionOptions::default()).unwrap(),
+            0
+        );
+        assert_eq!(sandbox.first_engine(&prefer("pool")).unwrap(), 1);
+        assert_eq!(
+            sandbox
+                .first_engine(&prefer("native python (warm pool)"))
+                .unwrap(),
+            1
+        );
+
+        let output = sandbox
+            .execute("result = 1 + 1", serde_json::json!({}), prefer("pool"))
+            .await
+            .unwrap();
+        assert_eq!(output.result_as::<i64>().unwrap(), 2);
+        assert!(matches!(
+            sandbox
+                .execute("result = 1", serde_json::json!({}), prefer("vm"))
+                .await,
+            Err(SandboxError::UnknownEngine(name)) if name == "vm"
+        ));
+    }
+}
@@ -0,0 +1,342 @@
+//! Prometheus metrics
+//!
+//! A process-wide registry counts executions run through
+//! [`PythonSandbox::execute`](crate::PythonSandbox::execute) by engine, policy
+//! and outcome, with histograms of their duration and output size, and tracks
+//! how many workspaces and pooled interpreters are alive.
+//!
+//! Embedding hosts add [`gather`] to their own `/metrics` handler; others can
+//! start the bundled listener with [`serve`].
+
+use crate::errors::{Result, SandboxError};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Upper bounds of the execution duration buckets, in seconds
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Upper bounds of the output size buckets, in bytes
+const OUTPUT_BUCKETS: &[f64] = &[
+    256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+];
+
+/// Process-wide sandbox metrics
+pub struct Metrics {
+    executions: Mutex<BTreeMap<[String; 3], u64>>,
+    durations: Mutex<BTreeMap<[String; 2], Histogram>>,
+    output_sizes: Mutex<BTreeMap<[String; 2], Histogram>>,
+    active_workspaces: AtomicI64,
+    pooled_interpreters: AtomicI64,
+}
+
+/// The process-wide registry
+pub fn registry() -> &'static Metrics {
+    static REGISTRY: OnceLock<Metrics> = OnceLock::new();
+    REGISTRY.get_or_init(|| Metrics {
+        executions: Mutex::new(BTreeMap::new()),
+        durations: Mutex::new(BTreeMap::new()),
+        output_sizes: Mutex::new(BTreeMap::new()),
+        active_workspaces: AtomicI64::new(0),
+        pooled_interpreters: AtomicI64::new(0),
+    })
+}
+
+/// All metrics in the Prometheus text exposition format
+pub fn gather() -> String {
+    registry().gather()
+}
+
+impl Metrics {
+    /// Record one finished execution
+    pub(crate) fn record_execution(
+        &self,
+        engine: &str,
+        policy: &str,
+        result: &Result<crate::engine::ExecutionResult>,
+        elapsed: Duration,
+    ) {
+        let outcome = match result {
+            Ok(_) => "success",
+            Err(SandboxError::Timeout) => "timeout",
+            Err(SandboxError::Cancelled) => "cancelled",
+            Err(_) => "error",
+        };
+        let labels = [engine.to_string(), policy.to_string()];
+
+        *lock(&self.executions)
+            .entry([engine.to_string(), policy.to_string(), outcome.to_string()])
+            .or_default() += 1;
+        lock(&self.durations)
+            .entry(labels.clone())
+            .or_insert_with(|| Histogram::new(DURATION_BUCKETS))
+            .observe(elapsed.as_secs_f64());
+        if let Ok(value) = result {
+            let size = serde_json::to_vec(value).map_or(0, |v| v.len());
+            lock(&self.output_sizes)
+                .entry(labels)
+                .or_insert_with(|| Histogram::new(OUTPUT_BUCKETS))
+                .observe(size as f64);
+        }
+    }
+
+    /// Count a workspace as alive until the guard is dropped
+    pub(crate) fn track_workspace(&'static self) -> GaugeGuard {
+        GaugeGuard::new(&self.active_workspaces)
+    }
+
+    /// Count a pooled interpreter as alive until the guard is dropped
+    pub(crate) fn track_pooled_interpreter(&'static self) -> GaugeGuard {
+        GaugeGuard::new(&self.pooled_interpreters)
+    }
+
+    /// All metrics in the Prometheus text exposition format
+    pub fn gather(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str(
+            "# HELP pysandbox_executions_total Executions by engine, policy and outcome.\n",
+        );
+        out.push_str("# TYPE pysandbox_executions_total counter\n");
+        for ([engine, policy, outcome], count) in lock(&self.executions).iter() {
+            let _ = writeln!(
+                out,
+                "pysandbox_executions_total{{engine=\"{}\",policy=\"{}\",outcome=\"{}\"}} {}",
+                escape(engine),
+                escape(policy),
+                outcome,
+                count
+            );
+        }
+
+        write_histograms(
+            &mut out,
+            "pysandbox_execution_duration_seconds",
+            "Wall-clock execution time.",
+            &lock(&self.durations),
+        );
+        write_histograms(
+            &mut out,
+            "pysandbox_output_bytes",
+            "Size of successful execution results, serialized as JSON.",
+            &lock(&self.output_sizes),
+        );
+
+        for (name, help, gauge) in [
+            (
+                "pysandbox_active_workspaces",
+                "Isolated workspaces currently on disk.",
+                &self.active_workspaces,
+            ),
+            (
+                "pysandbox_pooled_interpreters",
+                "Warm pool interpreters currently running.",
+                &self.pooled_interpreters,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, gauge.load(Ordering::Relaxed));
+        }
+        out
+    }
+}
+
+/// Serve [`gather`] over HTTP at `GET /metrics`
+///
+/// Binds before returning so address errors surface here. The listener stops
+/// when the returned [`MetricsServer`] is dropped. Must be called from within
+/// a tokio runtime.
+pub async fn serve(addr: SocketAddr) -> std::io::Result<MetricsServer> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    tracing::info!("Serving metrics on http://{}/metrics", addr);
+    let task = tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = [0u8; 1024];
+                let n = stream.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..n]);
+                let response = if request.starts_with("GET /metrics ") {
+                    let body = gather();
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: text/plain; version=0.0.4\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        .to_string()
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    Ok(MetricsServer { addr, task })
+}
+
+/// Running `/metrics` listener; stops when dropped
+pub struct MetricsServer {
+    addr: SocketAddr,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// Address the listener is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Increments a gauge while alive
+pub(crate) struct GaugeGuard(&'static AtomicI64);
+
+impl GaugeGuard {
+    fn new(gauge: &'static AtomicI64) -> Self {
+        gauge.fetch_add(1, Ordering::Relaxed);
+        Self(gauge)
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Cumulative histogram with fixed buckets
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&mut self.counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+fn write_histograms(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    histograms: &BTreeMap<[String; 2], Histogram>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for ([engine, policy], histogram) in histograms {
+        let labels = format!(
+            "engine=\"{}\",policy=\"{}\"",
+            escape(engine),
+            escape(policy)
+        );
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, histogram.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count);
+    }
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics_exposition() {
+        let metrics = registry();
+        metrics.record_execution(
+            "Test \"Engine\"",
+            "Balanced",
+            &crate::engine::ExecutionResult::from_value(serde_json::json!({"result": 2})),
+            Duration::from_millis(30),
+        );
+        metrics.record_execution(
+            "Test \"Engine\"",
+            "Balanced",
+            &Err(SandboxError::Timeout),
+            Duration::from_secs(90),
+        );
+        let workspace = metrics.track_workspace();
+
+        let server = serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(server.local_addr())
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, text) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200"));
+
+        let labels = r#"engine="Test \"Engine\"",policy="Balanced""#;
+        assert!(text.contains(&format!(
+            "pysandbox_executions_total{{{},outcome=\"timeout\"}} 1",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "pysandbox_execution_duration_seconds_bucket{{{},le=\"0.05\"}} 1",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "pysandbox_execution_duration_seconds_bucket{{{},le=\"+Inf\"}} 2",
+            labels
+        )));
+        assert!(text.contains(&format!("pysandbox_output_bytes_count{{{}}} 1", labels)));
+        assert!(text.contains("# TYPE pysandbox_active_workspaces gauge"));
+        drop(workspace);
+    }
+}
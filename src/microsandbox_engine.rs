@@ -1,6 +1,7 @@
 use crate::{
-    engine::{EngineCapabilities, ExecutionOptions, PythonEngine},
+    engine::{Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine},
     errors::{Result, SandboxError},
+    supervisor::SetupClock,
 };
 use async_trait::async_trait;
 use microsandbox::{BaseSandbox, PythonSandbox, StartOptions};
@@ -55,9 +56,9 @@ impl MicrosandboxEngine {
 
 #[async_trait]
 impl PythonEngine for MicrosandboxEngine {
-    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<()> {
+    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
         // Microsandbox will handle validation during execution
-        Ok(())
+        Ok(Vec::new())
     }
 
     async fn execute(
@@ -65,7 +66,7 @@ impl PythonEngine for MicrosandboxEngine {
         code: &str,
         inputs: serde_json::Value,
         options: &ExecutionOptions,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<ExecutionResult> {
         // Create a unique sandbox name for this execution
         let sandbox_name = format!(
             "{}-{}",
@@ -73,23 +74,30 @@ impl PythonEngine for MicrosandboxEngine {
             uuid::Uuid::new_v4().to_string()[..8].to_string()
         );
 
-        // Create the Python sandbox
-        let mut sandbox = PythonSandbox::create(&sandbox_name).await.map_err(|e| {
-            SandboxError::MicrosandboxError(format!("Failed to create sandbox: {}", e))
-        })?;
+        // Creating and booting the VM counts against the setup budget
+        let setup = SetupClock::start(options);
+        let mut sandbox = setup
+            .within(async {
+                // Create the Python sandbox
+                let mut sandbox = PythonSandbox::create(&sandbox_name).await.map_err(|e| {
+                    SandboxError::MicrosandboxError(format!("Failed to create sandbox: {}", e))
+                })?;
 
-        // Configure start options
-        let start_options = StartOptions {
-            image: Some("microsandbox/python".to_string()),
-            memory: options.memory_mb as u32,
-            cpus: 1.0,
-            timeout: options.timeout.as_secs_f32(),
-        };
+                // Configure start options
+                let start_options = StartOptions {
+                    image: Some("microsandbox/python".to_string()),
+                    memory: options.memory_mb as u32,
+                    cpus: 1.0,
+                    timeout: options.timeout.as_secs_f32(),
+                };
 
-        // Start the sandbox
-        sandbox.start(Some(start_options)).await.map_err(|e| {
-            SandboxError::MicrosandboxError(format!("Failed to start sandbox: {}", e))
-        })?;
+                // Start the sandbox
+                sandbox.start(Some(start_options)).await.map_err(|e| {
+                    SandboxError::MicrosandboxError(format!("Failed to start sandbox: {}", e))
+                })?;
+                Ok(sandbox)
+            })
+            .await?;
 
         // Prepare code with input injection and result capture
         let wrapped_code = format!(
@@ -100,7 +108,7 @@ import io
 import base64
 
 # Inject inputs
-inputs = json.loads('''{}''')
+inputs = json.loads({})
 
 # Capture stdout
 old_stdout = sys.stdout
@@ -150,7 +158,8 @@ except Exception as e:
     }}
     print(json.dumps(error_data))
 "#,
-            serde_json::to_string(&inputs)?.replace("'", "\\'"),
+            // A JSON string is also a valid Python string literal
+            serde_json::to_string(&serde_json::to_string(&inputs)?)?,
             code.lines()
                 .map(|line| format!("    {}", line))
                 .collect::<Vec<_>>()
@@ -190,24 +199,30 @@ except Exception as e:
             Ok(json_result) => {
                 // Check if it has our expected structure
                 if let Some(result) = json_result.get("result") {
-                    Ok(result.clone())
+                    Ok(ExecutionResult {
+                        result: (!result.is_null()).then(|| result.clone()),
+                        ..Default::default()
+                    })
                 } else if let Some(error) = json_result.get("error") {
                     Err(SandboxError::RuntimeError(
                         error.as_str().unwrap_or("Unknown error").to_string(),
                     ))
                 } else if json_result.get("stdout").is_some() {
                     // No explicit result but has stdout - return the whole thing
-                    Ok(json_result)
+                    ExecutionResult::from_value(json_result)
                 } else {
-                    Ok(serde_json::Value::Null)
+                    Ok(ExecutionResult::default())
                 }
             }
             Err(_) => {
-                // Return as string if not JSON
+                // Return as stdout if not JSON
                 if output.trim().is_empty() {
-                    Ok(serde_json::Value::Null)
+                    Ok(ExecutionResult::default())
                 } else {
-                    Ok(serde_json::Value::String(output))
+                    Ok(ExecutionResult {
+                        stdout: Some(output),
+                        ..Default::default()
+                    })
                 }
             }
         }
@@ -225,6 +240,15 @@ except Exception as e:
         }
     }
 
+    async fn health_check(&self) -> Result<()> {
+        if !crate::microsandbox_setup::check_server_running().await {
+            return Err(SandboxError::MicrosandboxError(
+                "Microsandbox server is not running".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     async fn shutdown(&mut self) -> Result<()> {
         // Sandboxes are ephemeral, nothing to clean up
         Ok(())
@@ -188,7 +188,7 @@ fn get_cli_version() -> Option<String> {
 }
 
 /// Check if microsandbox server is running
-async fn check_server_running() -> bool {
+pub(crate) async fn check_server_running() -> bool {
     // Try to connect to the default microsandbox port
     tokio::net::TcpStream::connect("127.0.0.1:5555")
         .await
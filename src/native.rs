@@ -1,7 +1,25 @@
+#[cfg(target_os = "linux")]
+use crate::{audit_hook::SYSTEM_READ_PATHS, policy::FilesystemRules};
 use crate::{
-    config::{ImportPolicy, ResourceLimits},
-    engine::{EngineCapabilities, ExecutionOptions, PythonEngine},
-    errors::{Result, SandboxError},
+    audit_hook::{render_audit_hook, ARM_AUDIT_HOOK},
+    config::{python_version_requirements, whitelisted_modules, ImportPolicy, ResourceLimits},
+    egress::{guard_options, EgressProxy},
+    engine::{
+        ensure_valid, Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult,
+        PythonEngine,
+    },
+    errors::{execution_error, Result, SandboxError},
+    policy::{FilesystemPolicy, SandboxPolicy},
+    result_file,
+    sandboxed::{
+        export_outputs, stage_input_dir, static_precheck, syntax_diagnostics,
+        SandboxedPythonEngine, IMPORT_POLICY_CHECK, IMPORT_POLICY_FINDER,
+    },
+    stdin::child_stdin,
+    supervisor::{
+        apply_file_limits, output_capture, supervise, write_inputs_file, ResultChannel, SetupClock,
+        SupervisorLimits, DENIAL_MARKER, INPUTS_FILE_ENV, MARK_TRUNCATED, RESULT_SERIALIZER,
+    },
 };
 use async_trait::async_trait;
 use std::path::PathBuf;
@@ -12,8 +30,201 @@ use tokio::process::Command;
 pub struct NativePythonEngine {
     python_path: PathBuf,
     limits: ResourceLimits,
+    filesystem: Option<FilesystemPolicy>,
 }
 
+/// Python limiting `open()` writes to `write_roots`, or nothing for `None`
+///
+/// Installed before the import guard, which would block its `os` import.
+fn render_open_guard(write_roots: Option<&[PathBuf]>) -> String {
+    let Some(write_roots) = write_roots else {
+        return String::new();
+    };
+    let roots: Vec<String> = write_roots
+        .iter()
+        .map(|root| root.to_string_lossy().into_owned())
+        .collect();
+    format!(
+        r#"
+# Allow open() writes only under the writable roots
+def _rzn_restrict_open(original_open, roots):
+    import os
+
+    roots = [os.path.realpath(root) for root in roots]
+
+    def writable(file):
+        if isinstance(file, int):
+            return False
+        path = os.path.realpath(os.fsdecode(file))
+        return any(path == root or path.startswith(root.rstrip(os.sep) + os.sep) for root in roots)
+
+    def restricted_open(file, mode='r', *args, **kwargs):
+        if any(c in mode for c in "wax+") and not writable(file):
+            raise _rzn_denied(PermissionError("Write access is not allowed"), "file_write", file)
+        return original_open(file, mode, *args, **kwargs)
+
+    return restricted_open
+
+if hasattr(builtins, 'open'):
+    builtins.open = _rzn_restrict_open(builtins.open, {})
+del _rzn_restrict_open
+"#,
+        serde_json::to_string(&roots).unwrap_or_else(|_| "[]".to_string())
+    )
+}
+
+/// A run's path rules, with the files the wrapper itself uses allowed
+struct FilesystemGuard {
+    /// The rules as a policy restricting nothing else, for the audit hook
+    policy: SandboxPolicy,
+    /// Working directory for policies granting the workspace, with an
+    /// `output/` directory for `OUTPUT_DIR`
+    scratch: Option<tempfile::TempDir>,
+    /// Where user code may write, `None` for anywhere not denied
+    write_roots: Option<Vec<PathBuf>>,
+    /// Serve `open()` from memory, for [`FilesystemPolicy::None`]
+    memory: bool,
+    /// Seatbelt profile rendered from the rules
+    #[cfg(target_os = "macos")]
+    profile: tempfile::NamedTempFile,
+    /// Landlock ruleset built from the rules, when the kernel has Landlock
+    #[cfg(target_os = "linux")]
+    landlock: Option<crate::landlock::Ruleset>,
+    /// Wrapper files created up front so Landlock can grant them
+    #[cfg(target_os = "linux")]
+    placeholders: Vec<PathBuf>,
+}
+
+impl FilesystemGuard {
+    /// Wrapper code installing the guard, before the import guard
+    fn preamble(&self) -> String {
+        let mut preamble = render_audit_hook(&self.policy);
+        if self.memory {
+            preamble.push_str(MEMORY_FILESYSTEM);
+        }
+        preamble
+    }
+
+    /// Wrapper code enforcing the guard, right before user code
+    fn arm(&self) -> String {
+        let mut arm = String::new();
+        if self.memory {
+            arm.push_str("_rzn_mount_memory_filesystem()\ndel _rzn_mount_memory_filesystem\n");
+        }
+        arm.push_str(ARM_AUDIT_HOOK);
+        arm
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for FilesystemGuard {
+    fn drop(&mut self) {
+        // Files the wrapper ended up writing belong to the result
+        for path in &self.placeholders {
+            if std::fs::metadata(path).is_ok_and(|m| m.len() == 0) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// In-memory filesystem for code run under [`FilesystemPolicy::None`]
+///
+/// Defines `_rzn_mount_memory_filesystem`, which swaps `open`, `io.open` and
+/// the common `os` file helpers for versions that keep written files in a
+/// dict, pyfakefs-style. Writes never reach the disk; reading a path that
+/// was never written falls through to the real `open`, where the audit hook
+/// only admits the interpreter's own files. Modules are imported when the
+/// preamble runs, ahead of the import guard.
+const MEMORY_FILESYSTEM: &str = r#"
+def _rzn_memory_filesystem():
+    import builtins
+    import io
+    import os
+
+    files = {}
+    real_open = io.open
+    real_os = {name: getattr(os, name) for name in ("listdir", "remove", "unlink")}
+    real_path = {name: getattr(os.path, name) for name in ("exists", "isfile", "getsize")}
+
+    def key(file):
+        try:
+            return None if isinstance(file, int) else os.path.abspath(os.fsdecode(file))
+        except TypeError:
+            return None
+
+    class MemoryFile(io.BytesIO):
+        def __init__(self, path, data):
+            super().__init__(data)
+            self.path = path
+
+        def flush(self):
+            if not self.closed:
+                files[self.path] = self.getvalue()
+            super().flush()
+
+        def close(self):
+            self.flush()
+            super().close()
+
+    def memory_open(file, mode="r", buffering=-1, encoding=None, errors=None, newline=None,
+                    closefd=True, opener=None):
+        path = key(file)
+        writing = any(c in mode for c in "wax+")
+        if path is None or (not writing and path not in files):
+            return real_open(file, mode, buffering, encoding, errors, newline, closefd, opener)
+        if "x" in mode and path in files:
+            raise FileExistsError(17, "File exists", file)
+        if "r" in mode and path not in files:
+            raise FileNotFoundError(2, "No such file or directory", file)
+        data = b"" if "w" in mode else files.get(path, b"")
+        files[path] = data
+        raw = MemoryFile(path, data)
+        if "a" in mode:
+            raw.seek(0, io.SEEK_END)
+        if "b" in mode:
+            return raw
+        return io.TextIOWrapper(raw, encoding=encoding or "utf-8", errors=errors,
+                                newline=newline, write_through=True)
+
+    def exists(path):
+        return key(path) in files or real_path["exists"](path)
+
+    def isfile(path):
+        return key(path) in files or real_path["isfile"](path)
+
+    def getsize(path):
+        data = files.get(key(path))
+        return real_path["getsize"](path) if data is None else len(data)
+
+    def listdir(path="."):
+        directory = key(path)
+        names = {os.path.basename(p) for p in files if os.path.dirname(p) == directory}
+        try:
+            names.update(real_os["listdir"](path))
+        except FileNotFoundError:
+            if not names:
+                raise
+        return sorted(names)
+
+    def remover(name):
+        def remove(path, *args, **kwargs):
+            if files.pop(key(path), None) is None:
+                real_os[name](path, *args, **kwargs)
+        return remove
+
+    def mount():
+        builtins.open = io.open = memory_open
+        os.path.exists, os.path.isfile, os.path.getsize = exists, isfile, getsize
+        os.listdir = listdir
+        os.remove, os.unlink = remover("remove"), remover("unlink")
+
+    return mount
+
+_rzn_mount_memory_filesystem = _rzn_memory_filesystem()
+del _rzn_memory_filesystem
+"#;
+
 impl NativePythonEngine {
     /// Create a new native Python engine (finds Python in PATH)
     pub fn new() -> Result<Self> {
@@ -24,6 +235,7 @@ impl NativePythonEngine {
         Ok(Self {
             python_path,
             limits: ResourceLimits::default(),
+            filesystem: None,
         })
     }
 
@@ -44,6 +256,7 @@ impl NativePythonEngine {
         Ok(Self {
             python_path,
             limits: ResourceLimits::default(),
+            filesystem: None,
         })
     }
 
@@ -64,13 +277,170 @@ impl NativePythonEngine {
         Ok(engine)
     }
 
+    /// Restrict file access to `policy`'s path rules
+    ///
+    /// An audit hook checks every `open`, `os.open` and `pathlib` access, and
+    /// removals and renames, against the rules, and the blacklist guard's
+    /// `open()` accepts writes to the policy's writable paths instead of
+    /// none. On macOS the interpreter also runs under `sandbox-exec` with a
+    /// profile rendered from them. Policies granting the workspace get a
+    /// scratch directory as working directory, `WORKSPACE` and
+    /// `SANDBOX_WORKSPACE`, with `OUTPUT_DIR` inside it; files left there
+    /// are listed in `output_files` and exported like the sandboxed engine's
+    /// before the directory is removed. [`FilesystemPolicy::None`] gets no
+    /// directory at all: files the code writes live in memory for the run.
+    pub fn with_filesystem_policy(mut self, policy: FilesystemPolicy) -> Self {
+        self.filesystem = Some(policy);
+        self
+    }
+
     /// Get the Python executable path being used
     pub fn python_path(&self) -> &PathBuf {
         &self.python_path
     }
 
+    /// Guard for this run's filesystem policy, also allowing the host files
+    /// the wrapper reads and writes. On macOS its profile only lets network
+    /// traffic out through the egress proxy when there is one
+    fn filesystem_guard(
+        &self,
+        read: Vec<PathBuf>,
+        write: Vec<PathBuf>,
+        proxy_port: Option<u16>,
+    ) -> Result<Option<FilesystemGuard>> {
+        #[cfg(not(target_os = "macos"))]
+        let _ = proxy_port;
+        let Some(filesystem) = &self.filesystem else {
+            return Ok(None);
+        };
+        if *filesystem == FilesystemPolicy::Unrestricted {
+            return Ok(None);
+        }
+        let mut rules = filesystem.rules();
+        let scratch = if rules.workspace {
+            let scratch = tempfile::Builder::new()
+                .prefix("pysandbox-native-")
+                .tempdir()?;
+            std::fs::create_dir(scratch.path().join("output"))?;
+            Some(scratch)
+        } else {
+            None
+        };
+        let write_roots = (!rules.write_any).then(|| {
+            rules
+                .write
+                .iter()
+                .cloned()
+                .chain(scratch.as_ref().map(|dir| dir.path().to_path_buf()))
+                .collect()
+        });
+        #[cfg(target_os = "linux")]
+        let placeholders = write
+            .iter()
+            .filter(|path| std::fs::File::create_new(path).is_ok())
+            .cloned()
+            .collect();
+        rules.read.extend(read);
+        rules.write.extend(write);
+        #[cfg(target_os = "linux")]
+        let landlock = self.landlock_ruleset(&rules, scratch.as_ref())?;
+        // Imports and network keep their own guards
+        let policy = SandboxPolicy::custom(SandboxPolicy::yolo())
+            .filesystem(FilesystemPolicy::Paths(rules))
+            .build();
+        Ok(Some(FilesystemGuard {
+            #[cfg(target_os = "macos")]
+            profile: crate::seatbelt::write_guard_profile(&policy, proxy_port)?,
+            #[cfg(target_os = "linux")]
+            landlock,
+            #[cfg(target_os = "linux")]
+            placeholders,
+            policy,
+            scratch,
+            write_roots,
+            memory: *filesystem == FilesystemPolicy::None,
+        }))
+    }
+
+    /// Landlock ruleset for a guard's rules, adding the interpreter, the
+    /// system locations it reads, `/dev/null` and the scratch workspace
+    ///
+    /// Like the audit hook, it grants `sys.path` entries only when they sit
+    /// under the interpreter's prefix or a read rule.
+    #[cfg(target_os = "linux")]
+    fn landlock_ruleset(
+        &self,
+        rules: &FilesystemRules,
+        scratch: Option<&tempfile::TempDir>,
+    ) -> Result<Option<crate::landlock::Ruleset>> {
+        let mut rules = rules.clone();
+        let resolved =
+            std::fs::canonicalize(&self.python_path).unwrap_or_else(|_| self.python_path.clone());
+        for python in [&self.python_path, &resolved] {
+            rules
+                .read
+                .extend(python.ancestors().nth(2).map(PathBuf::from));
+        }
+        rules.read.extend(
+            SYSTEM_READ_PATHS
+                .iter()
+                // Shells and tools behind launcher scripts such as pyenv shims
+                .chain(&["/bin", "/usr/bin", "/lib", "/lib64"])
+                .map(PathBuf::from),
+        );
+        rules.write.push(PathBuf::from("/dev/null"));
+        rules
+            .write
+            .extend(scratch.map(|dir| dir.path().to_path_buf()));
+        crate::landlock::Ruleset::new(&rules).map_err(|e| {
+            SandboxError::InternalError(format!("Failed to build Landlock ruleset: {}", e))
+        })
+    }
+
+    /// Command starting the interpreter, under `sandbox-exec` on macOS when
+    /// there is a filesystem guard, restricted by Landlock on Linux
+    fn command(&self, guard: Option<&FilesystemGuard>) -> Command {
+        #[cfg(target_os = "macos")]
+        if let Some(guard) = guard {
+            let python_home = self
+                .python_path
+                .ancestors()
+                .nth(2)
+                .unwrap_or(std::path::Path::new("/"));
+            let mut cmd = Command::new("sandbox-exec");
+            cmd.arg("-f")
+                .arg(guard.profile.path())
+                .arg("-D")
+                .arg(format!("PYTHON_HOME={}", python_home.display()));
+            if let Some(scratch) = &guard.scratch {
+                // Seatbelt matches real paths, so resolve e.g. /var -> /private/var
+                let workspace = std::fs::canonicalize(scratch.path())
+                    .unwrap_or_else(|_| scratch.path().to_path_buf());
+                cmd.arg("-D")
+                    .arg(format!("WORKSPACE={}", workspace.display()));
+            }
+            cmd.arg(&self.python_path);
+            return cmd;
+        }
+        let mut cmd = Command::new(&self.python_path);
+        #[cfg(target_os = "linux")]
+        if let Some(ruleset) = guard.and_then(|guard| guard.landlock.as_ref()) {
+            ruleset.apply(&mut cmd);
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        let _ = guard;
+        cmd
+    }
+
     /// Generate import control code based on policy
-    fn generate_import_control(&self, policy: &ImportPolicy) -> String {
+    ///
+    /// The blacklist guard also limits `open()` writes to `write_roots`;
+    /// `None` leaves writes to the filesystem policy.
+    fn generate_import_control(
+        &self,
+        policy: &ImportPolicy,
+        write_roots: Option<&[PathBuf]>,
+    ) -> String {
         match policy {
             ImportPolicy::Blacklist(blacklist) => {
                 let blacklist_str = if blacklist.is_empty() {
@@ -90,38 +460,29 @@ impl NativePythonEngine {
 import builtins
 import sys
 
+WHITELIST = None
 BLACKLIST = {blacklist}
+VERSION_REQUIREMENTS = {{}}
 
 original_import = builtins.__import__
 
-def safe_import(name, globals=None, locals=None, fromlist=(), level=0):
-    # For relative imports (level > 0), allow them - they're within an already-imported package
-    if level > 0:
-        return original_import(name, globals, locals, fromlist, level)
-
-    root_module = name.split('.')[0]
-    if root_module in BLACKLIST:
-        raise ImportError(f"Module '{{root_module}}' is blacklisted for safety")
-    return original_import(name, globals, locals, fromlist, level)
+{check}
 
+{open_guard}
 builtins.__import__ = safe_import
-
-# Restrict open to read-only
-if hasattr(builtins, 'open'):
-    _original_open = builtins.open
-    def restricted_open(file, mode='r', *args, **kwargs):
-        if 'w' in mode or 'a' in mode or 'x' in mode:
-            raise PermissionError("Write access is not allowed")
-        return _original_open(file, mode, *args, **kwargs)
-    builtins.open = restricted_open
-
+{finder}
 # Note: We keep exec, eval, compile as libraries need them
 # The import restrictions provide the main security
 "#,
-                    blacklist = blacklist_str
+                    blacklist = blacklist_str,
+                    check = IMPORT_POLICY_CHECK,
+                    finder = IMPORT_POLICY_FINDER,
+                    open_guard = render_open_guard(write_roots),
                 )
             }
             ImportPolicy::Whitelist(whitelist) => {
+                let requirements = python_version_requirements(Some(whitelist));
+                let whitelist = &whitelisted_modules(whitelist);
                 let whitelist_str = if whitelist.is_empty() {
                     "set()".to_string()
                 } else {
@@ -144,28 +505,27 @@ import json  # Needed by our wrapper for input/output handling
 import re    # Common dependency
 
 WHITELIST = {whitelist}
+BLACKLIST = None
+VERSION_REQUIREMENTS = {requirements}
 
 original_import = builtins.__import__
 
-def safe_import(name, globals=None, locals=None, fromlist=(), level=0):
-    # For relative imports (level > 0), allow them - they're within an already-imported package
-    if level > 0:
-        return original_import(name, globals, locals, fromlist, level)
-
-    root_module = name.split('.')[0]
-    if root_module not in WHITELIST and root_module != 'builtins':
-        raise ImportError(f"Module '{{root_module}}' is not in whitelist")
-    return original_import(name, globals, locals, fromlist, level)
+{check}
 
 builtins.__import__ = safe_import
-"#,
-                    whitelist = whitelist_str
+{finder}"#,
+                    whitelist = whitelist_str,
+                    requirements = requirements,
+                    check = IMPORT_POLICY_CHECK,
+                    finder = IMPORT_POLICY_FINDER,
                 )
             }
             ImportPolicy::Both {
                 whitelist,
                 blacklist,
             } => {
+                let requirements = python_version_requirements(Some(whitelist));
+                let whitelist = &whitelisted_modules(whitelist);
                 let whitelist_str = if whitelist.is_empty() {
                     "set()".to_string()
                 } else {
@@ -200,112 +560,31 @@ import re    # Common dependency
 
 WHITELIST = {whitelist}
 BLACKLIST = {blacklist}
+VERSION_REQUIREMENTS = {requirements}
 
 original_import = builtins.__import__
 
-def safe_import(name, globals=None, locals=None, fromlist=(), level=0):
-    # For relative imports (level > 0), allow them - they're within an already-imported package
-    if level > 0:
-        return original_import(name, globals, locals, fromlist, level)
-
-    root_module = name.split('.')[0]
-    if root_module in BLACKLIST:
-        raise ImportError(f"Module '{{root_module}}' is blacklisted")
-    if root_module not in WHITELIST and root_module != 'builtins':
-        raise ImportError(f"Module '{{root_module}}' is not in whitelist")
-    return original_import(name, globals, locals, fromlist, level)
+{check}
 
 builtins.__import__ = safe_import
-"#,
+{finder}"#,
                     whitelist = whitelist_str,
-                    blacklist = blacklist_str
+                    requirements = requirements,
+                    blacklist = blacklist_str,
+                    check = IMPORT_POLICY_CHECK,
+                    finder = IMPORT_POLICY_FINDER,
                 )
             }
         }
     }
 
     /// Generate network control code based on optional host allowlist
-    fn generate_network_control(&self, allowlist: Option<&[String]>) -> String {
-        let Some(allowlist) = allowlist else {
-            return String::new();
-        };
-        if allowlist.is_empty() {
-            return String::new();
-        }
-
-        let allowlist_str = format!(
-            "[{}]",
-            allowlist
-                .iter()
-                .map(|s| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")))
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-
-        format!(
-            r#"
-_RZN_NETWORK_ALLOWLIST = {allowlist}
-
-if _RZN_NETWORK_ALLOWLIST:
-    try:
-        import socket
-    except Exception:
-        socket = None
-
-    if socket is not None:
-        def _rzn_norm_host(value):
-            if value is None:
-                return ""
-            return str(value).strip().lower().rstrip(".")
-
-        def _rzn_host_allowed(host):
-            h = _rzn_norm_host(host)
-            if not h:
-                return True
-            for pattern in _RZN_NETWORK_ALLOWLIST:
-                p = _rzn_norm_host(pattern)
-                if not p:
-                    continue
-                if p == "*":
-                    return True
-                if p.startswith("*."):
-                    base = p[2:]
-                    if h == base or h.endswith("." + base):
-                        return True
-                elif h == p:
-                    return True
-            return False
-
-        def _rzn_host_from_address(address):
-            if isinstance(address, tuple) and len(address) > 0:
-                return address[0]
-            return None
-
-        _rzn_orig_getaddrinfo = socket.getaddrinfo
-        def _rzn_guarded_getaddrinfo(host, *args, **kwargs):
-            if not _rzn_host_allowed(host):
-                raise PermissionError(f"Network host not allowed: {{host}}")
-            return _rzn_orig_getaddrinfo(host, *args, **kwargs)
-        socket.getaddrinfo = _rzn_guarded_getaddrinfo
-
-        _rzn_orig_create_connection = socket.create_connection
-        def _rzn_guarded_create_connection(address, *args, **kwargs):
-            host = _rzn_host_from_address(address)
-            if not _rzn_host_allowed(host):
-                raise PermissionError(f"Network host not allowed: {{host}}")
-            return _rzn_orig_create_connection(address, *args, **kwargs)
-        socket.create_connection = _rzn_guarded_create_connection
-
-        _rzn_orig_socket_connect = socket.socket.connect
-        def _rzn_guarded_socket_connect(sock, address):
-            host = _rzn_host_from_address(address)
-            if not _rzn_host_allowed(host):
-                raise PermissionError(f"Network host not allowed: {{host}}")
-            return _rzn_orig_socket_connect(sock, address)
-        socket.socket.connect = _rzn_guarded_socket_connect
-"#,
-            allowlist = allowlist_str
-        )
+    fn generate_network_control(
+        &self,
+        allowlist: Option<&[String]>,
+        max_connections: Option<u32>,
+    ) -> String {
+        SandboxedPythonEngine::generate_network_control(allowlist, max_connections)
     }
 
     /// Apply resource limits to the command
@@ -373,34 +652,11 @@ if _RZN_NETWORK_ALLOWLIST:
 
 #[async_trait]
 impl PythonEngine for NativePythonEngine {
-    async fn validate(&self, code: &str, _options: &ExecutionOptions) -> Result<()> {
-        // Basic syntax validation
-        let validation_code = format!(
-            r#"
-import ast
-try:
-    ast.parse('''{}''')
-    print("OK")
-except SyntaxError as e:
-    print(f"SYNTAX_ERROR: {{e}}")
-"#,
-            code.replace("'''", "\\'''")
-        );
-
-        let output = Command::new(&self.python_path)
-            .arg("-c")
-            .arg(&validation_code)
-            .output()
-            .await?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if stdout.starts_with("SYNTAX_ERROR:") {
-            return Err(SandboxError::SyntaxError(
-                stdout.trim_start_matches("SYNTAX_ERROR: ").to_string(),
-            ));
+    async fn validate(&self, code: &str, options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        if let Some(diagnostics) = static_precheck(code, options)? {
+            return Ok(diagnostics);
         }
-
-        Ok(())
+        syntax_diagnostics(&self.python_path, code).await
     }
 
     async fn execute(
@@ -408,16 +664,43 @@ except SyntaxError as e:
         code: &str,
         inputs: serde_json::Value,
         options: &ExecutionOptions,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<ExecutionResult> {
         // Validate first
-        self.validate(code, options).await?;
+        let setup = SetupClock::start(options);
+        ensure_valid(setup.within(self.validate(code, options)).await?)?;
 
         // Create execution wrapper that captures stdout/stderr
+        let output_path = result_file::spill_path()?;
+        let spill_path = result_file::spill_path()?;
+        let channel = ResultChannel::file(output_path.clone());
+        let input_dir = stage_input_dir(&options.input_files)?;
+        let inputs_file = write_inputs_file(&inputs)?;
+        let egress = EgressProxy::for_options(options).await?;
+        let guard = self.filesystem_guard(
+            [inputs_file.path().to_path_buf()]
+                .into_iter()
+                .chain(input_dir.as_ref().map(|dir| dir.path().to_path_buf()))
+                .collect(),
+            vec![output_path.clone(), spill_path.clone()],
+            egress.as_ref().map(EgressProxy::port),
+        )?;
+        let scratch = guard.as_ref().and_then(|guard| guard.scratch.as_ref());
         let wrapper_code = format!(
             r#"
-# Security setup
+# Result serialization and denial markers, defined before the import guard
+{}{}
+# Filesystem guard, armed right before user code
 {}
 
+# Inputs, loaded before the import guard from the file the host wrote
+import os as _rzn_os
+with open(_rzn_os.environ.pop("{}"), encoding="utf-8") as _rzn_inputs_file:
+    inputs = _rzn_json.load(_rzn_inputs_file)
+del _rzn_os, _rzn_inputs_file
+
+# Security setup
+{}{}
+
 # Network setup
 {}
 
@@ -427,11 +710,13 @@ import json
 import sys
 from io import StringIO
 
-inputs = json.loads('''{}''')
+INPUT_DIR = {}
+# Scratch workspace of policies granting one
+WORKSPACE = {}
+OUTPUT_DIR = None if WORKSPACE is None else WORKSPACE + "/output"
 
 # Capture stdout/stderr
-_captured_stdout = StringIO()
-_captured_stderr = StringIO()
+{}
 _original_stdout = sys.stdout
 _original_stderr = sys.stderr
 sys.stdout = _captured_stdout
@@ -440,7 +725,9 @@ sys.stderr = _captured_stderr
 _exec_result = None
 _exec_error = None
 
+{}
 # User code execution
+_rzn_code_line = _rzn_sys._getframe().f_lineno + 2
 try:
     {}
 
@@ -449,6 +736,8 @@ try:
         _exec_result = result
 except Exception as e:
     _exec_error = f"{{type(e).__name__}}: {{e}}"
+    _exec_traceback = _rzn_traceback.format_exc()
+    _exec_denial = _rzn_denial_of(e)
 
 # Restore stdout/stderr
 sys.stdout = _original_stdout
@@ -461,41 +750,74 @@ _output = {{
     "result": None,
     "error": _exec_error
 }}
-
-if _exec_result is not None:
-    if isinstance(_exec_result, (dict, list, str, int, float, bool, type(None))):
-        _output["result"] = _exec_result
-    elif isinstance(_exec_result, (bytes, bytearray, memoryview)):
-        _bytes = bytes(_exec_result)
-        _output["result"] = {{
-            "type": "bytes",
-            "encoding": "base64",
-            "data": base64.b64encode(_bytes).decode("utf-8")
-        }}
-    else:
-        _output["result"] = {{"type": str(type(_exec_result).__name__), "repr": str(_exec_result)}}
-
-print("OUTPUT_JSON_START")
-print(json.dumps(_output))
-print("OUTPUT_JSON_END")
+if _exec_error:
+    _output["traceback"] = _exec_traceback
+    _output["code_line"] = _rzn_code_line
+    if _exec_denial:
+        _output["denial"] = _exec_denial
+{}
+_output["result"] = _rzn_result_value(_exec_result)
+_rzn_spill_path = {}
+_rzn_spill_result(_output, _rzn_spill_path, _rzn_spill_path, {})
+{}
 
 if _exec_error:
     sys.exit(1)
 "#,
-            self.generate_import_control(&options.import_policy),
-            self.generate_network_control(options.network_allowlist.as_deref()),
-            serde_json::to_string(&inputs)?.replace("'", "\\'"),
-            code.replace('\n', "\n    ")
+            RESULT_SERIALIZER,
+            DENIAL_MARKER,
+            guard
+                .as_ref()
+                .map(FilesystemGuard::preamble)
+                .unwrap_or_default(),
+            INPUTS_FILE_ENV,
+            SandboxedPythonEngine::generate_native_extension_control(
+                options.block_native_extensions,
+                &options.import_policy,
+            ),
+            self.generate_import_control(
+                &options.import_policy,
+                match &guard {
+                    Some(guard) => guard.write_roots.as_deref(),
+                    None => Some(&[]),
+                },
+            ),
+            self.generate_network_control(
+                guard_options(options, egress.as_ref())
+                    .network_allowlist
+                    .as_deref(),
+                options.network_budget.and_then(|b| b.max_connections)
+            ),
+            match &input_dir {
+                Some(dir) => serde_json::to_string(dir.path())?,
+                None => "None".to_string(),
+            },
+            match &scratch {
+                Some(dir) => serde_json::to_string(dir.path())?,
+                None => "None".to_string(),
+            },
+            output_capture(options.events.is_some(), self.limits.max_output_bytes),
+            guard.as_ref().map(FilesystemGuard::arm).unwrap_or_default(),
+            code.replace('\n', "\n    "),
+            MARK_TRUNCATED,
+            serde_json::to_string(&spill_path)?,
+            result_file::SPILL_THRESHOLD_BYTES,
+            channel.report("_output", &serde_json::to_string(&output_path)?),
         );
 
         // Create command
-        let mut cmd = Command::new(&self.python_path);
+        let mut cmd = self.command(guard.as_ref());
+        if let Some(scratch) = scratch {
+            cmd.current_dir(scratch.path())
+                .env("SANDBOX_WORKSPACE", scratch.path());
+        }
         cmd.arg("-c")
             .arg(&wrapper_code)
-            .stdin(Stdio::null())
+            .stdin(child_stdin(options.stdin.as_ref()))
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .env("PYTHONIOENCODING", "utf-8");
+            .env("PYTHONIOENCODING", "utf-8")
+            .env(INPUTS_FILE_ENV, inputs_file.path());
 
         // Set thread limits
         cmd.env("OMP_NUM_THREADS", self.limits.max_threads.to_string())
@@ -504,59 +826,68 @@ if _exec_error:
         for (key, value) in &options.env_vars {
             cmd.env(key, value);
         }
+        if let Some(egress) = &egress {
+            egress.apply(&mut cmd);
+        }
 
         // Apply resource limits
         self.apply_resource_limits(&mut cmd, &self.limits);
+        apply_file_limits(&mut cmd, self.limits.max_open_files, None);
 
         // Execute with timeout
+        let setup = setup.finish()?;
         let child = cmd.spawn()?;
-        let pid = child.id();
-
-        match tokio::time::timeout(options.timeout, child.wait_with_output()).await {
-            Ok(Ok(output)) => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let stderr = String::from_utf8_lossy(&output.stderr);
-
-                // Extract structured output
-                if let Some(start) = stdout.find("OUTPUT_JSON_START") {
-                    if let Some(end) = stdout.find("OUTPUT_JSON_END") {
-                        let json_str = &stdout[start + 17..end].trim();
-                        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(json_str) {
-                            // Check if there was an execution error
-                            if let Some(error) = parsed.get("error").and_then(|e| e.as_str()) {
-                                if !error.is_empty() {
-                                    return Err(SandboxError::RuntimeError(error.to_string()));
-                                }
-                            }
-                            return Ok(parsed);
-                        }
-                    }
-                }
+        let output = supervise(child, options, &SupervisorLimits::for_limits(&self.limits))
+            .await?
+            .with_setup(setup);
 
-                // Fallback: check for memory errors
-                if !output.status.success() {
-                    if stderr.contains("MemoryError") {
-                        return Err(SandboxError::MemoryLimitExceeded);
-                    }
-                    return Err(SandboxError::RuntimeError(stderr.to_string()));
-                }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
 
-                Ok(serde_json::Value::Null)
+        // Extract structured output
+        if let Some(mut parsed) = channel.take(&stdout) {
+            // Check if there was an execution error
+            if let Some(error) = execution_error(&parsed, code) {
+                return Err(error);
             }
-            Ok(Err(e)) => Err(SandboxError::IoError(e)),
-            Err(_) => {
-                // Timeout - kill process
-                #[cfg(unix)]
-                {
-                    if let Some(pid) = pid {
-                        unsafe {
-                            libc::kill(-(pid as i32), libc::SIGKILL);
-                        }
-                    }
-                }
-                Err(SandboxError::Timeout)
+            // Files left in OUTPUT_DIR go before the scratch workspace does
+            if let Some(scratch) = scratch {
+                let output_dir = scratch.path().join("output");
+                let mut output_files: Vec<String> = std::fs::read_dir(&output_dir)?
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .collect();
+                output_files.sort();
+                parsed["output_files"] = serde_json::json!(output_files);
+                let run_id = scratch
+                    .path()
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("native");
+                export_outputs(&mut parsed, run_id, &output_dir);
+            }
+            if let Some(egress) = &egress {
+                egress.report(&mut parsed);
+            }
+            output.attach_metrics(&mut parsed);
+            return ExecutionResult::from_value(parsed);
+        }
+
+        // Output that bypassed capture overran the pipe cap
+        if output.truncated {
+            return Err(SandboxError::RuntimeError(
+                "Output exceeded the configured size limit".to_string(),
+            ));
+        }
+
+        // Fallback: check for memory errors
+        if !output.status.success() {
+            if stderr.contains("MemoryError") {
+                return Err(SandboxError::MemoryLimitExceeded { peak_mb: None });
             }
+            return Err(SandboxError::RuntimeError(stderr.to_string()));
         }
+
+        Ok(ExecutionResult::default())
     }
 
     fn capabilities(&self) -> EngineCapabilities {
@@ -571,8 +902,516 @@ if _exec_error:
         }
     }
 
+    async fn health_check(&self) -> Result<()> {
+        if !self.python_path.is_file() {
+            return Err(SandboxError::PythonNotFound);
+        }
+        Ok(())
+    }
+
     async fn shutdown(&mut self) -> Result<()> {
         // Cleanup temp files if any
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FilesystemRules;
+
+    #[tokio::test]
+    async fn test_filesystem_policy_limits_reads() {
+        let Ok(engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let (shared, secret) = (dir.path().join("shared"), dir.path().join("secret"));
+        for (path, text) in [(&shared, "public"), (&secret, "hidden")] {
+            std::fs::create_dir(path).unwrap();
+            std::fs::write(path.join("data.txt"), text).unwrap();
+        }
+        let mut engine = engine.with_filesystem_policy(FilesystemPolicy::Paths(
+            FilesystemRules::new().workspace(true).read(&shared),
+        ));
+        let options = ExecutionOptions::default();
+        let read = |path: &std::path::Path| {
+            format!(
+                "import pathlib\nresult = pathlib.Path({:?}).read_text()",
+                path.join("data.txt")
+            )
+        };
+
+        let output = engine
+            .execute(&read(&shared), serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        assert_eq!(output.result_as::<String>().unwrap(), "public");
+        let denied = engine
+            .execute(&read(&secret), serde_json::json!({}), &options)
+            .await;
+        assert!(denied.is_err());
+        let denied = engine
+            .execute(
+                &format!("open({:?}, 'w')", shared.join("new.txt")),
+                serde_json::json!({}),
+                &options,
+            )
+            .await;
+        assert!(
+            matches!(denied, Err(SandboxError::FileWriteBlocked { .. })),
+            "{:?}",
+            denied
+        );
+
+        // The workspace is a writable scratch directory
+        let output = engine
+            .execute(
+                "import pathlib\npathlib.Path('out.txt').write_text('x')\n\
+                 result = sorted(p.name for p in pathlib.Path('.').iterdir())",
+                serde_json::json!({}),
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            output.result_as::<Vec<String>>().unwrap(),
+            vec!["out.txt", "output"]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_landlock_confines_child_processes() {
+        let Ok(engine) = NativePythonEngine::new() else {
+            return;
+        };
+        if crate::landlock::Ruleset::new(&FilesystemRules::new())
+            .ok()
+            .flatten()
+            .is_none()
+        {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let (shared, secret) = (dir.path().join("shared"), dir.path().join("secret"));
+        for path in [&shared, &secret] {
+            std::fs::create_dir(path).unwrap();
+            std::fs::write(path.join("data.txt"), "x").unwrap();
+        }
+        let mut engine = engine.with_filesystem_policy(FilesystemPolicy::Paths(
+            FilesystemRules::new().read(&shared),
+        ));
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(std::collections::HashSet::new()),
+            ..Default::default()
+        };
+        // `cat` opens the files itself, out of the audit hook's sight
+        let code = format!(
+            "import subprocess\n\
+             run = lambda path: subprocess.run(['cat', path], capture_output=True)\n\
+             result = [run(p).returncode == 0 for p in ({:?}, {:?})]",
+            shared.join("data.txt"),
+            secret.join("data.txt"),
+        );
+        let output = engine
+            .execute(&code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        assert_eq!(output.result_as::<Vec<bool>>().unwrap(), vec![true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_workspace_writes_under_read_any_policy() {
+        let Ok(engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let mut engine = engine.with_filesystem_policy(FilesystemPolicy::ReadAnyWriteWorkspace);
+        let options = ExecutionOptions::default();
+        let output = engine
+            .execute(
+                "open(OUTPUT_DIR + '/clean.csv', 'w').write('a,b\\n')",
+                serde_json::json!({}),
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.output_files, vec!["clean.csv"]);
+
+        let outside = tempfile::tempdir().unwrap();
+        let denied = engine
+            .execute(
+                &format!("open({:?}, 'w')", outside.path().join("x.txt")),
+                serde_json::json!({}),
+                &options,
+            )
+            .await;
+        assert!(matches!(denied, Err(SandboxError::FileWriteBlocked { .. })));
+        assert!(!outside.path().join("x.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_memory_filesystem_leaves_no_files() {
+        let Ok(engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let mut engine = engine.with_filesystem_policy(FilesystemPolicy::None);
+        let options = ExecutionOptions::default();
+        let name = format!("pysandbox-memfs-{}.csv", uuid::Uuid::new_v4());
+        let output = engine
+            .execute(
+                &format!(
+                    "with open({name:?}, 'w') as f:\n    f.write('a,b\\n')\n\
+                     with open({name:?}, 'a') as f:\n    f.write('1,2\\n')\n\
+                     result = open({name:?}).read()"
+                ),
+                serde_json::json!({}),
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.result_as::<String>().unwrap(), "a,b\n1,2\n");
+        assert!(!std::path::Path::new(&name).exists());
+
+        let secret = tempfile::NamedTempFile::new().unwrap();
+        let denied = engine
+            .execute(
+                &format!("open({:?}).read()", secret.path()),
+                serde_json::json!({}),
+                &options,
+            )
+            .await;
+        assert!(denied.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_network_allowlist_pins_resolved_addresses() {
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(Default::default()),
+            network_allowlist: Some(vec!["localhost".to_string()]),
+            ..Default::default()
+        };
+        let code = format!(
+            "import socket\n\
+             def attempt(host):\n    \
+                 s = socket.socket()\n    \
+                 try:\n        \
+                     s.connect((host, {port}))\n        \
+                     return 'connected'\n    \
+                 except PermissionError:\n        \
+                     return 'denied'\n    \
+                 finally:\n        \
+                     s.close()\n\
+             result = [attempt('127.0.0.1'), attempt('10.255.255.1')]"
+        );
+        let output = engine
+            .execute(&code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        // localhost resolves to 127.0.0.1; the other address was never resolved
+        assert_eq!(
+            output.result_as::<Vec<String>>().unwrap(),
+            ["connected", "denied"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proxy_mode_only_opens_proxy_port() {
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(Default::default()),
+            network_allowlist: Some(vec!["example.com".to_string()]),
+            network_enforcement: crate::egress::NetworkEnforcement::Proxy,
+            ..Default::default()
+        };
+        let code = format!(
+            "import os, socket\n\
+             def attempt(port):\n    \
+                 s = socket.socket()\n    \
+                 try:\n        \
+                     s.connect(('127.0.0.1', port))\n        \
+                     return 'connected'\n    \
+                 except PermissionError:\n        \
+                     return 'denied'\n    \
+                 finally:\n        \
+                     s.close()\n\
+             proxy_port = int(os.environ['HTTP_PROXY'].rpartition(':')[2])\n\
+             result = [attempt(proxy_port), attempt({port})]"
+        );
+        let output = engine
+            .execute(&code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            output.result_as::<Vec<String>>().unwrap(),
+            ["connected", "denied"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_byte_budget_denies_direct_connections() {
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(Default::default()),
+            network_allowlist: Some(vec![format!("127.0.0.1:{port}")]),
+            network_budget: Some(crate::egress::NetworkBudget {
+                max_connections: None,
+                max_bytes: Some(1024),
+            }),
+            ..Default::default()
+        };
+        let code = format!(
+            "import os, socket\n\
+             def attempt(port):\n    \
+                 try:\n        \
+                     socket.create_connection(('127.0.0.1', port)).close()\n        \
+                     return 'connected'\n    \
+                 except PermissionError:\n        \
+                     return 'denied'\n\
+             proxy_port = int(os.environ['HTTP_PROXY'].rpartition(':')[2])\n\
+             result = [attempt(proxy_port), attempt({port})]"
+        );
+        let output = engine
+            .execute(&code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        // The allowlisted host is only reachable through the metering proxy
+        assert_eq!(
+            output.result_as::<Vec<String>>().unwrap(),
+            ["connected", "denied"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocked_policy_refuses_connections() {
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(Default::default()),
+            ..SandboxPolicy::custom(SandboxPolicy::yolo())
+                .network(crate::policy::NetworkPolicy::Blocked)
+                .build()
+                .to_execution_options()
+        };
+        let code = format!(
+            "import socket\n\
+             def attempt(family, address):\n    \
+                 s = socket.socket(family)\n    \
+                 try:\n        \
+                     s.connect(address)\n        \
+                     return 'connected'\n    \
+                 except PermissionError:\n        \
+                     return 'denied'\n    \
+                 except OSError:\n        \
+                     return 'unreachable'\n    \
+                 finally:\n        \
+                     s.close()\n\
+             result = [\n    \
+                 attempt(socket.AF_INET, ('127.0.0.1', {port})),\n    \
+                 attempt(socket.AF_UNIX, '/nonexistent/pysandbox.sock'),\n\
+             ]"
+        );
+        let output = engine
+            .execute(&code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            output.result_as::<Vec<String>>().unwrap(),
+            ["denied", "denied"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_localhost_only_policy_allows_loopback() {
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(Default::default()),
+            ..SandboxPolicy::custom(SandboxPolicy::yolo())
+                .network(crate::policy::NetworkPolicy::LocalhostOnly)
+                .build()
+                .to_execution_options()
+        };
+        let code = format!(
+            "import socket\n\
+             def attempt(family, address):\n    \
+                 s = socket.socket(family)\n    \
+                 try:\n        \
+                     s.connect(address)\n        \
+                     return 'connected'\n    \
+                 except PermissionError:\n        \
+                     return 'denied'\n    \
+                 except OSError:\n        \
+                     return 'unreachable'\n    \
+                 finally:\n        \
+                     s.close()\n\
+             result = [\n    \
+                 attempt(socket.AF_INET, ('127.0.0.1', {port})),\n    \
+                 attempt(socket.AF_INET, ('127.0.0.2', {port})),\n    \
+                 attempt(socket.AF_UNIX, '/nonexistent/pysandbox.sock'),\n    \
+                 attempt(socket.AF_INET, ('10.255.255.1', {port})),\n\
+             ]"
+        );
+        let output = engine
+            .execute(&code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        // Anything on loopback or a Unix socket gets past the guard
+        assert_eq!(
+            output.result_as::<Vec<String>>().unwrap(),
+            ["connected", "unreachable", "unreachable", "denied"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_policy_submodule_rules() {
+        let set = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        let policy = ImportPolicy::Both {
+            whitelist: set(&["os.path", "json", "math"]),
+            blacklist: set(&["json.tool"]),
+        };
+        assert!(policy.is_allowed("os.path"));
+        assert!(!policy.is_allowed("os"));
+        assert!(policy.is_allowed("json.decoder"));
+        assert!(!policy.is_allowed("json.tool.main"));
+        assert_eq!(policy.denied_from_import("os", &["path"]), None);
+        assert_eq!(
+            policy.denied_from_import("os", &["path", "system"]),
+            Some("os".to_string())
+        );
+        assert_eq!(
+            policy.denied_from_import("json", &["tool"]),
+            Some("json.tool".to_string())
+        );
+
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        // `os` itself stays importable, only the named parts are refused
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(set(&["os.system", "json.tool"])),
+            ..Default::default()
+        };
+        let code = "from os import path\n\
+                    import json.decoder\n\
+                    result = path.join('a', json.decoder.__name__)";
+        let output = engine
+            .execute(code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        assert_eq!(output.result_as::<String>().unwrap(), "a/json.decoder");
+        for denied in [
+            "from os import system",
+            "import json.tool",
+            "from json import tool",
+        ] {
+            assert!(engine
+                .execute(denied, serde_json::json!({}), &options)
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_finder_denies_dynamic_imports() {
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(["os".to_string()].into()),
+            ..Default::default()
+        };
+        assert!(!options.import_policy.is_allowed("posix"));
+        assert!(!options.import_policy.is_allowed("nt"));
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        // Names are built at runtime, so only the interpreter's guards see them
+        let code = "import importlib\n\
+                    def attempt(load, name):\n    \
+                        try:\n        \
+                            load(name)\n        \
+                            return 'loaded'\n    \
+                        except ImportError:\n        \
+                            return 'denied'\n\
+                    os_name, posix_name = 'o' + 's', 'po' + 'six'\n\
+                    result = [\n    \
+                        attempt(importlib.import_module, os_name),\n    \
+                        attempt(__import__, os_name),\n    \
+                        attempt(importlib.import_module, posix_name),\n    \
+                        attempt(__import__, posix_name),\n    \
+                        attempt(importlib.import_module, 'json'),\n\
+                    ]";
+        let output = engine
+            .execute(code, serde_json::json!({}), &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            output.result_as::<Vec<String>>().unwrap(),
+            ["denied", "denied", "denied", "denied", "loaded"]
+        );
+        assert!(engine
+            .execute("import posix", serde_json::json!({}), &options)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_whitelist_version_requirements() {
+        assert_eq!(crate::config::entry_module("yaml >=5.4,!=6.0.0"), "yaml");
+        let Ok(mut engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let site = tempfile::tempdir().unwrap();
+        std::fs::create_dir(site.path().join("fakepkg")).unwrap();
+        std::fs::write(site.path().join("fakepkg/__init__.py"), "VALUE = 1\n").unwrap();
+        let dist_info = site.path().join("fakepkg-1.2.dist-info");
+        std::fs::create_dir(&dist_info).unwrap();
+        std::fs::write(
+            dist_info.join("METADATA"),
+            "Metadata-Version: 2.1\nName: fakepkg\nVersion: 1.2\n",
+        )
+        .unwrap();
+        std::fs::write(dist_info.join("top_level.txt"), "fakepkg\n").unwrap();
+
+        let options = |requirement: &str| {
+            let ImportPolicy::Whitelist(mut whitelist) = ImportPolicy::data_science_whitelist()
+            else {
+                unreachable!()
+            };
+            whitelist.insert(format!("fakepkg{}", requirement));
+            ExecutionOptions {
+                import_policy: ImportPolicy::Whitelist(whitelist),
+                env_vars: [("PYTHONPATH".to_string(), site.path().display().to_string())].into(),
+                ..Default::default()
+            }
+        };
+        let code = "import fakepkg\nresult = fakepkg.VALUE";
+        let output = engine
+            .execute(code, serde_json::json!({}), &options(">=1.0,<2"))
+            .await
+            .unwrap();
+        assert_eq!(output.result, Some(1.into()));
+        let err = engine
+            .execute(code, serde_json::json!({}), &options("~=1.3"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SandboxError::ImportBlocked { module } if module == "fakepkg"));
+    }
+}
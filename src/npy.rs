@@ -0,0 +1,110 @@
+//! Loading numpy array results into `ndarray`
+//!
+//! A numpy `result` too large to inline comes back as
+//! `{"type": "ndarray", "artifact": {"file": "result.npy", ...}}`, saved in
+//! the workspace output directory and exported with the other output files.
+//! [`load_result_array`] finds the exported file and reads it as an
+//! [`ArrayD`]; [`load_npy`] reads any `.npy` file.
+
+use crate::engine::ExecutionResult;
+use crate::errors::{Result, SandboxError};
+use ndarray::{ArrayD, IxDyn, ShapeBuilder};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Element types that can be read from a `.npy` file, e.g. `f64`, `i32`, `bool`
+pub use npyz::Deserialize as NpyElement;
+
+/// Read a `.npy` file
+pub fn load_npy<T: NpyElement>(path: impl AsRef<Path>) -> Result<ArrayD<T>> {
+    read_npy(std::fs::File::open(path)?)
+}
+
+/// Read `.npy` data from `reader`
+///
+/// Fails if the file's dtype cannot be read as `T`.
+pub fn read_npy<T: NpyElement, R: Read>(reader: R) -> Result<ArrayD<T>> {
+    let npy = npyz::NpyFile::new(reader)?;
+    let shape: Vec<usize> = npy.shape().iter().map(|&d| d as usize).collect();
+    let fortran = npy.order() == npyz::Order::Fortran;
+    let data = npy
+        .data::<T>()
+        .map_err(|e| SandboxError::InternalError(format!("Unsupported npy dtype: {}", e)))?
+        .collect::<std::io::Result<Vec<T>>>()?;
+    ArrayD::from_shape_vec(IxDyn(&shape).set_f(fortran), data)
+        .map_err(|e| SandboxError::InternalError(format!("Invalid npy shape: {}", e)))
+}
+
+/// Exported `.npy` artifact of an execution result, if it has one
+pub fn result_array_path(output: &ExecutionResult) -> Option<PathBuf> {
+    let result = output.result.as_ref()?;
+    if result.get("type")?.as_str()? != "ndarray" {
+        return None;
+    }
+    result
+        .pointer("/artifact/path")
+        .and_then(|p| p.as_str())
+        .map(PathBuf::from)
+}
+
+/// Read the `.npy` artifact of an execution result
+pub fn load_result_array<T: NpyElement>(output: &ExecutionResult) -> Result<ArrayD<T>> {
+    let path = result_array_path(output).ok_or_else(|| {
+        SandboxError::InternalError(
+            "Result has no exported ndarray artifact; set RZN_PYTHON_EXPORT_DIR".to_string(),
+        )
+    })?;
+    load_npy(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `.npy` bytes for a little-endian f64 array
+    fn npy_bytes(shape: &str, fortran: bool, values: &[f64]) -> Vec<u8> {
+        let mut header = format!(
+            "{{'descr': '<f8', 'fortran_order': {}, 'shape': {}, }}",
+            if fortran { "True" } else { "False" },
+            shape
+        );
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        for v in values {
+            bytes.extend(v.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_read_npy() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let array: ArrayD<f64> = read_npy(&npy_bytes("(2, 3)", false, &values)[..]).unwrap();
+        assert_eq!(array.shape(), [2, 3]);
+        assert_eq!(array[[1, 0]], 4.0);
+
+        let fortran: ArrayD<f64> = read_npy(&npy_bytes("(2, 3)", true, &values)[..]).unwrap();
+        assert_eq!(fortran[[1, 0]], 2.0);
+        assert!(read_npy::<i32, _>(&npy_bytes("(6,)", false, &values)[..]).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("result.npy");
+        std::fs::write(&path, npy_bytes("(6,)", false, &values)).unwrap();
+        let output = ExecutionResult::from_value(serde_json::json!({
+            "result": {"type": "ndarray", "artifact": {"file": "result.npy", "path": path}}
+        }))
+        .unwrap();
+        let loaded: ArrayD<f64> = load_result_array(&output).unwrap();
+        assert_eq!(loaded.as_slice().unwrap(), values);
+        let inline = ExecutionResult {
+            result: Some(serde_json::json!(1)),
+            ..Default::default()
+        };
+        assert!(load_result_array::<f64>(&inline).is_err());
+    }
+}
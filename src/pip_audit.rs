@@ -0,0 +1,306 @@
+//! Vulnerability audits of Python environments with pip-audit
+//!
+//! An [`EnvironmentAudit`] lists the distributions installed for an
+//! interpreter, pins them as `name==version` requirements and hands those to
+//! pip-audit, which looks them up in the PyPI advisory database or OSV. The
+//! [`AuditReport`] says which packages have known vulnerabilities, so a host
+//! can warn before running code in that environment. Listing goes through
+//! `importlib.metadata`, so environments created without pip (uv) work too.
+
+use crate::errors::{Result, SandboxError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Environment variable naming the pip-audit executable
+pub const PIP_AUDIT_ENV: &str = "RZN_PIP_AUDIT";
+
+/// Locate pip-audit: [`PIP_AUDIT_ENV`], then PATH
+pub fn find_pip_audit() -> Option<PathBuf> {
+    std::env::var_os(PIP_AUDIT_ENV)
+        .map(PathBuf::from)
+        .filter(|p| p.is_file())
+        .or_else(|| which::which("pip-audit").ok())
+}
+
+/// A known vulnerability in an installed package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vulnerability {
+    pub package: String,
+    pub version: String,
+    /// Advisory id, e.g. `PYSEC-2023-74` or `GHSA-...`
+    pub id: String,
+    /// Other ids for the same advisory, usually CVEs
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Versions that fix it, empty if there is none yet
+    #[serde(default)]
+    pub fix_versions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A package pip-audit couldn't check, with its reason
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedPackage {
+    pub package: String,
+    pub reason: String,
+}
+
+/// Findings of an [`EnvironmentAudit`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    /// Number of distributions audited
+    pub packages: usize,
+    pub vulnerabilities: Vec<Vulnerability>,
+    pub skipped: Vec<SkippedPackage>,
+}
+
+impl AuditReport {
+    /// Whether any audited package has a known vulnerability
+    pub fn is_vulnerable(&self) -> bool {
+        !self.vulnerabilities.is_empty()
+    }
+
+    /// Parse pip-audit's `--format json` output
+    pub fn from_pip_audit_json(json: &[u8]) -> Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Output {
+            Report { dependencies: Vec<Dependency> },
+            // pip-audit before 2.0 printed the bare list
+            Legacy(Vec<Dependency>),
+        }
+        #[derive(Deserialize)]
+        struct Dependency {
+            name: String,
+            #[serde(default)]
+            version: Option<String>,
+            #[serde(default)]
+            vulns: Vec<Vuln>,
+            #[serde(default)]
+            skip_reason: Option<String>,
+        }
+        #[derive(Deserialize)]
+        struct Vuln {
+            id: String,
+            #[serde(default)]
+            aliases: Vec<String>,
+            #[serde(default)]
+            fix_versions: Vec<String>,
+            #[serde(default)]
+            description: Option<String>,
+        }
+
+        let dependencies = match serde_json::from_slice(json)? {
+            Output::Report { dependencies } | Output::Legacy(dependencies) => dependencies,
+        };
+        let mut report = AuditReport {
+            packages: dependencies.len(),
+            ..Default::default()
+        };
+        for dependency in dependencies {
+            if let Some(reason) = dependency.skip_reason {
+                report.skipped.push(SkippedPackage {
+                    package: dependency.name,
+                    reason,
+                });
+                continue;
+            }
+            let version = dependency.version.unwrap_or_default();
+            report
+                .vulnerabilities
+                .extend(dependency.vulns.into_iter().map(|vuln| Vulnerability {
+                    package: dependency.name.clone(),
+                    version: version.clone(),
+                    id: vuln.id,
+                    aliases: vuln.aliases,
+                    fix_versions: vuln.fix_versions,
+                    description: vuln.description.filter(|d| !d.is_empty()),
+                }));
+        }
+        Ok(report)
+    }
+}
+
+/// Python printing the installed distributions as `name==version` lines
+const LIST_DISTRIBUTIONS: &str = "import importlib.metadata as m\n\
+     for n, v in sorted({(d.metadata['Name'], d.version) for d in m.distributions() if d.metadata['Name']}):\n    \
+         print(f'{n}=={v}')";
+
+/// A pip-audit run against the packages installed for one interpreter
+#[derive(Debug, Clone)]
+pub struct EnvironmentAudit {
+    python: PathBuf,
+    pip_audit: Option<PathBuf>,
+    service: Option<String>,
+}
+
+impl EnvironmentAudit {
+    /// Audit the environment of `python`, using pip-audit from
+    /// [`find_pip_audit`], else `python -m pip_audit`
+    pub fn new(python: impl Into<PathBuf>) -> Self {
+        Self {
+            python: python.into(),
+            pip_audit: find_pip_audit(),
+            service: None,
+        }
+    }
+
+    /// Use a specific pip-audit executable
+    pub fn pip_audit(mut self, pip_audit: impl Into<PathBuf>) -> Self {
+        self.pip_audit = Some(pip_audit.into());
+        self
+    }
+
+    /// Vulnerability service to query, `pypi` (pip-audit's default) or `osv`
+    pub fn service(mut self, service: impl Into<String>) -> Self {
+        self.service = Some(service.into());
+        self
+    }
+
+    /// pip-audit over the pinned requirements in `requirements`
+    ///
+    /// Dependencies aren't resolved again: the file already lists every
+    /// installed distribution.
+    pub fn command(&self, requirements: &Path) -> Command {
+        let mut cmd = match &self.pip_audit {
+            Some(pip_audit) => Command::new(pip_audit),
+            None => {
+                let mut cmd = Command::new(&self.python);
+                cmd.arg("-m").arg("pip_audit");
+                cmd
+            }
+        };
+        cmd.arg("--requirement")
+            .arg(requirements)
+            .arg("--no-deps")
+            .arg("--disable-pip")
+            .arg("--format")
+            .arg("json")
+            .arg("--progress-spinner")
+            .arg("off");
+        if let Some(service) = &self.service {
+            cmd.arg("--vulnerability-service").arg(service);
+        }
+        cmd
+    }
+
+    /// Pinned requirements for everything installed for the interpreter
+    pub async fn installed_requirements(&self) -> Result<String> {
+        let output = Command::new(&self.python)
+            .arg("-c")
+            .arg(LIST_DISTRIBUTIONS)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| {
+                SandboxError::InternalError(format!(
+                    "Failed to run {}: {}",
+                    self.python.display(),
+                    e
+                ))
+            })?;
+        if !output.status.success() {
+            return Err(SandboxError::InternalError(format!(
+                "listing installed packages failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// List the installed packages and audit them
+    pub async fn run(&self) -> Result<AuditReport> {
+        let requirements = self.installed_requirements().await?;
+        if requirements.trim().is_empty() {
+            return Ok(AuditReport::default());
+        }
+        let mut file = tempfile::Builder::new()
+            .prefix("rzn-audit-")
+            .suffix(".txt")
+            .tempfile()?;
+        std::io::Write::write_all(&mut file, requirements.as_bytes())?;
+
+        let output = self
+            .command(file.path())
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| {
+                SandboxError::InternalError(format!(
+                    "Failed to run pip-audit: {}; install it or set {}",
+                    e, PIP_AUDIT_ENV
+                ))
+            })?;
+        // pip-audit exits 1 when it finds vulnerabilities, so judge by output
+        AuditReport::from_pip_audit_json(&output.stdout).map_err(|_| {
+            SandboxError::InternalError(format!(
+                "pip-audit failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pip_audit_report_parsing() {
+        let json = br#"{
+            "dependencies": [
+                {"name": "pyyaml", "version": "5.3", "vulns": [{
+                    "id": "PYSEC-2021-142",
+                    "fix_versions": ["5.4"],
+                    "aliases": ["CVE-2020-14343"],
+                    "description": "Arbitrary code execution via full_load"
+                }]},
+                {"name": "requests", "version": "2.31.0", "vulns": []},
+                {"name": "internal-tool", "skip_reason": "Dependency not found on PyPI"}
+            ],
+            "fixes": []
+        }"#;
+        let report = AuditReport::from_pip_audit_json(json).unwrap();
+        assert!(report.is_vulnerable());
+        assert_eq!(report.packages, 3);
+        assert_eq!(report.vulnerabilities.len(), 1);
+        assert_eq!(report.vulnerabilities[0].package, "pyyaml");
+        assert_eq!(report.vulnerabilities[0].fix_versions, ["5.4"]);
+        assert_eq!(report.skipped[0].package, "internal-tool");
+
+        let legacy = br#"[{"name": "requests", "version": "2.31.0", "vulns": []}]"#;
+        assert!(!AuditReport::from_pip_audit_json(legacy)
+            .unwrap()
+            .is_vulnerable());
+
+        let audit = EnvironmentAudit::new("/envs/x/bin/python")
+            .pip_audit("/usr/bin/pip-audit")
+            .service("osv");
+        let args: Vec<String> = audit
+            .command(Path::new("reqs.txt"))
+            .as_std()
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            args,
+            [
+                "--requirement",
+                "reqs.txt",
+                "--no-deps",
+                "--disable-pip",
+                "--format",
+                "json",
+                "--progress-spinner",
+                "off",
+                "--vulnerability-service",
+                "osv"
+            ]
+        );
+    }
+}
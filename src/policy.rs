@@ -24,36 +24,180 @@
 //! └─────────────────────────────────────────────────────────────┘
 //! ```
 
+use ed25519_dalek::VerifyingKey;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 // ============================================================================
 // PRIMITIVES - Individual configurable options
 // ============================================================================
 
 /// Network access policy
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum NetworkPolicy {
     /// All network access blocked (most secure)
+    #[default]
     Blocked,
-    /// Only localhost allowed
+    /// Only loopback addresses (`127.0.0.0/8`, `::1`) and Unix sockets allowed
     LocalhostOnly,
-    /// Specific domains/IPs allowed
+    /// Specific domains/IPs allowed, each optionally limited to a port or
+    /// scheme, see [`NetworkRule`]
     AllowList(Vec<String>),
     /// All network access allowed (least secure)
     Unrestricted,
 }
 
-impl Default for NetworkPolicy {
-    fn default() -> Self {
-        NetworkPolicy::Blocked
+/// A parsed network allowlist entry
+///
+/// Entries are an exact host, a `*.domain` suffix or `*`, optionally with a
+/// port (`api.example.com:443`, `*.internal:5432`) or a scheme standing for
+/// its default port (`https://api.example.com`). Schemes without a known
+/// default port don't limit the port. An address range in CIDR notation
+/// (`127.0.0.0/8`) matches the IPs inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkRule {
+    /// Host pattern, lowercased without a trailing dot
+    pub host: String,
+    /// Port connections must use; `None` allows any
+    pub port: Option<u16>,
+}
+
+impl NetworkRule {
+    pub fn parse(entry: &str) -> Self {
+        let entry = entry.trim().to_ascii_lowercase();
+        let (scheme, rest) = match entry.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme), rest),
+            None => (None, entry.as_str()),
+        };
+        if scheme.is_none() && in_network("", rest).is_some() {
+            return Self {
+                host: rest.to_string(),
+                port: None,
+            };
+        }
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (host, port) = if let Some(v6) = authority.strip_prefix('[') {
+            let (host, rest) = v6.split_once(']').unwrap_or((v6, ""));
+            (host, rest.strip_prefix(':').and_then(|p| p.parse().ok()))
+        } else {
+            match authority.rsplit_once(':') {
+                // More than one colon is a bare IPv6 address
+                Some((host, port)) if !host.contains(':') => (host, port.parse().ok()),
+                _ => (authority, None),
+            }
+        };
+        Self {
+            host: host.trim_end_matches('.').to_string(),
+            port: port.or_else(|| scheme.and_then(default_port)),
+        }
     }
+
+    /// Whether the host pattern matches `host`, whatever the port
+    pub fn matches_host(&self, host: &str) -> bool {
+        let host = host
+            .trim()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        if self.host == "*" {
+            return true;
+        }
+        if let Some(inside) = in_network(&host, &self.host) {
+            return inside;
+        }
+        match self.host.strip_prefix("*.") {
+            Some(base) => host == base || host.ends_with(&format!(".{}", base)),
+            None => host == self.host,
+        }
+    }
+
+    /// Whether a connection to `host` on `port` is allowed
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        self.port.is_none_or(|p| p == port) && self.matches_host(host)
+    }
+
+    /// Whether the rule only reaches this machine
+    pub fn is_loopback(&self) -> bool {
+        if self.host == "localhost" {
+            return true;
+        }
+        let (network, bits) = match self.host.split_once('/') {
+            Some((network, bits)) => (network, bits.parse::<u32>().ok()),
+            None => (self.host.as_str(), None),
+        };
+        // A range stays on loopback if it's no wider than the loopback block
+        match network.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => ip.is_loopback() && bits.is_none_or(|bits| bits >= 8),
+            Ok(IpAddr::V6(ip)) => ip.is_loopback() && bits.is_none_or(|bits| bits == 128),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Whether `host` is an IP inside `cidr`, or `None` if `cidr` isn't an
+/// address range
+fn in_network(host: &str, cidr: &str) -> Option<bool> {
+    let (network, bits) = cidr.split_once('/')?;
+    let network: IpAddr = network.parse().ok()?;
+    let bits: u32 = bits.parse().ok()?;
+    let ip = host.parse::<IpAddr>().ok();
+    match network {
+        IpAddr::V4(network) if bits <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - bits).unwrap_or(0);
+            Some(matches!(ip, Some(IpAddr::V4(ip))
+                if u32::from(ip) & mask == u32::from(network) & mask))
+        }
+        IpAddr::V6(network) if bits <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - bits).unwrap_or(0);
+            Some(matches!(ip, Some(IpAddr::V6(ip))
+                if u128::from(ip) & mask == u128::from(network) & mask))
+        }
+        _ => None,
+    }
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    Some(match scheme {
+        "http" | "ws" => 80,
+        "https" | "wss" => 443,
+        "ftp" => 21,
+        "ssh" | "sftp" => 22,
+        "postgres" | "postgresql" => 5432,
+        "mysql" => 3306,
+        "redis" => 6379,
+        "mongodb" => 27017,
+        _ => return None,
+    })
+}
+
+/// Allowlist entries as a Python `[(host, port or None), ...]` literal for
+/// the in-process guards
+pub(crate) fn python_network_rules(entries: &[String]) -> String {
+    let rules: Vec<String> = entries
+        .iter()
+        .map(|entry| NetworkRule::parse(entry))
+        .filter(|rule| !rule.host.is_empty())
+        .map(|rule| {
+            format!(
+                "({}, {})",
+                serde_json::Value::String(rule.host),
+                rule.port.map_or("None".to_string(), |p| p.to_string())
+            )
+        })
+        .collect();
+    format!("[{}]", rules.join(", "))
 }
 
 /// Filesystem access policy
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum FilesystemPolicy {
     /// No filesystem access (code runs in memory only)
@@ -61,24 +205,110 @@ pub enum FilesystemPolicy {
     /// Read-only access to specified paths
     ReadOnly(Vec<PathBuf>),
     /// Read/write only within isolated workspace
+    #[default]
     WorkspaceOnly,
     /// Read anywhere, write only to workspace
     ReadAnyWriteWorkspace,
     /// Full read/write access (least secure)
     Unrestricted,
+    /// Per-path read, write and deny rules
+    Paths(FilesystemRules),
 }
 
-impl Default for FilesystemPolicy {
-    fn default() -> Self {
-        FilesystemPolicy::WorkspaceOnly
+impl FilesystemPolicy {
+    /// The path rules this policy stands for
+    ///
+    /// Every mode lowers to the same rule set, which is what the Seatbelt
+    /// profile, the Landlock ruleset and the in-process `open()` guard
+    /// enforce.
+    pub fn rules(&self) -> FilesystemRules {
+        match self {
+            FilesystemPolicy::None => FilesystemRules::default(),
+            FilesystemPolicy::ReadOnly(paths) => FilesystemRules {
+                read: paths.clone(),
+                ..Default::default()
+            },
+            FilesystemPolicy::WorkspaceOnly => FilesystemRules::default().workspace(true),
+            FilesystemPolicy::ReadAnyWriteWorkspace => FilesystemRules {
+                read_any: true,
+                workspace: true,
+                ..Default::default()
+            },
+            FilesystemPolicy::Unrestricted => FilesystemRules {
+                read_any: true,
+                write_any: true,
+                ..Default::default()
+            },
+            FilesystemPolicy::Paths(rules) => rules.clone(),
+        }
+    }
+}
+
+/// Granular filesystem rules
+///
+/// Write access implies read access, and a `deny` entry wins over everything
+/// else, so `deny` can carve a secret directory out of a readable tree.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct FilesystemRules {
+    /// Read anywhere not denied
+    #[serde(default)]
+    pub read_any: bool,
+    /// Write anywhere not denied
+    #[serde(default)]
+    pub write_any: bool,
+    /// Read/write access to the isolated workspace
+    #[serde(default)]
+    pub workspace: bool,
+    /// Trees that can be read
+    #[serde(default)]
+    pub read: Vec<PathBuf>,
+    /// Trees that can be read and written
+    #[serde(default)]
+    pub write: Vec<PathBuf>,
+    /// Trees that can be neither read nor written
+    #[serde(default)]
+    pub deny: Vec<PathBuf>,
+}
+
+impl FilesystemRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read_any(mut self, enabled: bool) -> Self {
+        self.read_any = enabled;
+        self
+    }
+
+    pub fn workspace(mut self, enabled: bool) -> Self {
+        self.workspace = enabled;
+        self
+    }
+
+    pub fn read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read.push(path.into());
+        self
+    }
+
+    pub fn write(mut self, path: impl Into<PathBuf>) -> Self {
+        self.write.push(path.into());
+        self
+    }
+
+    pub fn deny(mut self, path: impl Into<PathBuf>) -> Self {
+        self.deny.push(path.into());
+        self
     }
 }
 
 /// Process/subprocess execution policy
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ProcessPolicy {
     /// No subprocess execution allowed
+    #[default]
     Blocked,
     /// Only allow specific executables
     AllowList(Vec<String>),
@@ -86,14 +316,9 @@ pub enum ProcessPolicy {
     Unrestricted,
 }
 
-impl Default for ProcessPolicy {
-    fn default() -> Self {
-        ProcessPolicy::Blocked
-    }
-}
-
 /// Python import policy
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ImportPolicyType {
     /// No restrictions on imports
@@ -198,6 +423,7 @@ impl ImportPolicyType {
             "unicodedata",
             "hashlib",
             "base64",
+            "binascii",
             // Data structures
             "array",
             "struct",
@@ -247,6 +473,26 @@ impl ImportPolicyType {
         .collect()
     }
 
+    /// Check if an import is allowed, with the same matching as
+    /// [`ImportPolicy::is_allowed`](crate::config::ImportPolicy::is_allowed)
+    pub fn is_allowed(&self, module: &str) -> bool {
+        let (whitelist, blacklist) = self.lists();
+        crate::config::import_allowed(whitelist, blacklist, module)
+    }
+
+    /// The whitelist and blacklist, each `None` when the policy has none
+    pub(crate) fn lists(&self) -> (Option<&HashSet<String>>, Option<&HashSet<String>>) {
+        match self {
+            ImportPolicyType::Unrestricted => (None, None),
+            ImportPolicyType::Blacklist(blacklist) => (None, Some(blacklist)),
+            ImportPolicyType::Whitelist(whitelist) => (Some(whitelist), None),
+            ImportPolicyType::WhitelistWithBlacklist {
+                whitelist,
+                blacklist,
+            } => (Some(whitelist), Some(blacklist)),
+        }
+    }
+
     /// Convert to the ImportPolicy used by the engine
     pub fn to_import_policy(&self) -> crate::config::ImportPolicy {
         match self {
@@ -268,6 +514,7 @@ impl ImportPolicyType {
 
 /// Resource limits
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ResourceLimitsPolicy {
     /// Maximum memory in MB
     pub max_memory_mb: usize,
@@ -294,29 +541,26 @@ impl Default for ResourceLimitsPolicy {
 }
 
 /// Execution environment policy
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ExecutionEnvironment {
     /// Native Python with guardrails only
     Native,
     /// Workspace-isolated execution
+    #[default]
     WorkspaceIsolated,
     /// Full platform sandbox (sandbox-exec on macOS, etc.)
     PlatformSandboxed,
 }
 
-impl Default for ExecutionEnvironment {
-    fn default() -> Self {
-        ExecutionEnvironment::WorkspaceIsolated
-    }
-}
-
 // ============================================================================
 // SANDBOX POLICY - Combines all primitives
 // ============================================================================
 
 /// Complete sandbox policy combining all primitives
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct SandboxPolicy {
     /// Human-readable name for this policy
     pub name: String,
@@ -334,8 +578,11 @@ pub struct SandboxPolicy {
     pub resources: ResourceLimitsPolicy,
     /// Execution environment
     pub environment: ExecutionEnvironment,
-    /// Whether to log all executions for audit
+    /// Whether to log all executions for audit (see [`crate::audit`])
     pub audit_logging: bool,
+    /// Enforce this policy from a `sys.addaudithook` hook inside the interpreter
+    #[serde(default)]
+    pub audit_hooks: bool,
     /// Custom sandbox profile path (macOS .sb file)
     pub custom_sandbox_profile: Option<PathBuf>,
 }
@@ -347,6 +594,158 @@ impl Default for SandboxPolicy {
 }
 
 impl SandboxPolicy {
+    /// Load from a JSON, TOML or YAML file, picked by extension
+    ///
+    /// A file with `extends = "<template>"` only needs the primitives it
+    /// changes; here the base must be a built-in template. Use
+    /// [`PolicyManager::load_policies_dir`] to extend other policy files.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let value = read_policy_value(path)?;
+        let mut resolved = std::collections::HashMap::new();
+        resolve_policy(
+            &path.display().to_string(),
+            &value,
+            &std::collections::HashMap::new(),
+            &builtin_templates(),
+            &mut resolved,
+            &mut Vec::new(),
+        )
+    }
+
+    /// Report, for each import, process call and literal file path in `code`,
+    /// whether this policy allows it and which rule decides, without
+    /// running anything
+    #[cfg(feature = "static-analyzer")]
+    pub fn explain(&self, code: &str) -> crate::errors::Result<crate::analyzer::PolicyReport> {
+        crate::analyzer::explain(self, code)
+    }
+
+    /// Engine resource limits for this policy
+    pub fn resource_limits(&self) -> crate::config::ResourceLimits {
+        crate::config::ResourceLimits {
+            memory_mb: self.resources.max_memory_mb,
+            cpu_seconds: self.resources.max_cpu_seconds,
+            max_threads: self.resources.max_threads,
+            max_output_bytes: self.resources.max_output_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// Execution mode this policy's environment runs in
+    pub fn execution_mode(&self) -> crate::config::ExecutionMode {
+        match self.environment {
+            ExecutionEnvironment::Native => crate::config::ExecutionMode::Native,
+            ExecutionEnvironment::WorkspaceIsolated => {
+                crate::config::ExecutionMode::WorkspaceIsolated
+            }
+            ExecutionEnvironment::PlatformSandboxed => {
+                crate::config::ExecutionMode::PlatformSandboxed
+            }
+        }
+    }
+
+    /// Per-execution options enforcing this policy's imports, network and
+    /// resource limits
+    ///
+    /// [`NetworkPolicy::Blocked`] becomes an empty allowlist, which the
+    /// in-process network guard reads as refusing every connection.
+    pub fn to_execution_options(&self) -> crate::engine::ExecutionOptions {
+        let network_allowlist = match &self.network {
+            NetworkPolicy::Blocked => Some(Vec::new()),
+            NetworkPolicy::Unrestricted => None,
+            NetworkPolicy::LocalhostOnly => Some(
+                ["localhost", "127.0.0.0/8", "::1"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            NetworkPolicy::AllowList(hosts) => Some(hosts.clone()),
+        };
+        crate::engine::ExecutionOptions {
+            memory_mb: self.resources.max_memory_mb,
+            cpu_seconds: self.resources.max_cpu_seconds,
+            timeout: std::time::Duration::from_secs(self.resources.max_timeout_seconds),
+            import_policy: self.imports.to_import_policy(),
+            network_allowlist,
+            ..Default::default()
+        }
+    }
+
+    /// Build a sandbox whose engine matches this policy's environment, using
+    /// Python from PATH
+    ///
+    /// Pair it with [`to_execution_options`](Self::to_execution_options) when
+    /// executing.
+    pub fn build_sandbox(&self) -> crate::errors::Result<crate::PythonSandbox> {
+        let python_path = which::which("python3")
+            .or_else(|_| which::which("python"))
+            .map_err(|_| crate::errors::SandboxError::PythonNotFound)?;
+        self.build_sandbox_with_python(python_path)
+    }
+
+    /// [`build_sandbox`](Self::build_sandbox) with a specific Python
+    ///
+    /// `Native` runs [`NativePythonEngine`](crate::NativePythonEngine),
+    /// `WorkspaceIsolated` a [`SandboxedPythonEngine`](crate::sandboxed::SandboxedPythonEngine)
+    /// and `PlatformSandboxed` the same engine with this policy attached, so
+    /// its Seatbelt profile and audit hooks are generated from it.
+    ///
+    /// With `audit_logging` set, executions are recorded to
+    /// [`JsonlAuditSink::from_env`](crate::audit::JsonlAuditSink::from_env).
+    pub fn build_sandbox_with_python(
+        &self,
+        python_path: PathBuf,
+    ) -> crate::errors::Result<crate::PythonSandbox> {
+        use crate::sandboxed::{SandboxConfig, SandboxedPythonEngine};
+
+        let limits = self.resource_limits();
+        let engine: Box<dyn crate::PythonEngine> = match self.environment {
+            ExecutionEnvironment::Native => Box::new(
+                crate::NativePythonEngine::with_python_path_and_limits(
+                    python_path.clone(),
+                    limits.clone(),
+                )?
+                .with_filesystem_policy(self.filesystem.clone()),
+            ),
+            ExecutionEnvironment::WorkspaceIsolated | ExecutionEnvironment::PlatformSandboxed => {
+                let platform = self.environment == ExecutionEnvironment::PlatformSandboxed;
+                Box::new(SandboxedPythonEngine::new(SandboxConfig {
+                    python_path: python_path.clone(),
+                    policy: platform.then(|| self.clone()),
+                    limits: limits.clone(),
+                    ..Default::default()
+                })?)
+            }
+        };
+        let sandbox = crate::PythonSandbox::new(vec![engine])
+            .with_session_python(python_path)
+            .with_session_limits(limits)
+            .with_policy(self.clone());
+        if !self.audit_logging {
+            return Ok(sandbox);
+        }
+        let sink = crate::audit::JsonlAuditSink::from_env()?;
+        Ok(sandbox.with_audit_log(crate::audit::AuditLog::new(sink).with_policy(&self.name)))
+    }
+
+    /// Built-in template by name, as registered in [`PolicyManager::new`]
+    pub fn template(name: &str) -> Option<Self> {
+        match name {
+            "yolo" => Some(Self::yolo()),
+            "balanced" => Some(Self::balanced()),
+            "data_science" => Some(Self::data_science()),
+            "document_processing" => Some(Self::document_processing()),
+            "enterprise" => Some(Self::enterprise()),
+            _ => None,
+        }
+    }
+
+    /// JSON Schema for policy files
+    #[cfg(feature = "json-schema")]
+    pub fn json_schema() -> serde_json::Value {
+        schemars::schema_for!(SandboxPolicy).to_value()
+    }
+
     /// Calculate security level (0-10)
     pub fn security_level(&self) -> u8 {
         let mut level = 0u8;
@@ -366,6 +765,8 @@ impl SandboxPolicy {
             FilesystemPolicy::ReadOnly(_) => 1,
             FilesystemPolicy::ReadAnyWriteWorkspace => 1,
             FilesystemPolicy::Unrestricted => 0,
+            FilesystemPolicy::Paths(rules) if rules.write_any => 0,
+            FilesystemPolicy::Paths(_) => 1,
         };
 
         // Process (0-2 points)
@@ -417,6 +818,7 @@ impl SandboxPolicy {
             },
             environment: ExecutionEnvironment::Native,
             audit_logging: false,
+            audit_hooks: false,
             custom_sandbox_profile: None,
         }
     }
@@ -435,6 +837,7 @@ impl SandboxPolicy {
             resources: ResourceLimitsPolicy::default(),
             environment: ExecutionEnvironment::WorkspaceIsolated,
             audit_logging: false,
+            audit_hooks: false,
             custom_sandbox_profile: None,
         }
     }
@@ -459,6 +862,7 @@ impl SandboxPolicy {
             },
             environment: ExecutionEnvironment::WorkspaceIsolated,
             audit_logging: true,
+            audit_hooks: false,
             custom_sandbox_profile: None,
         }
     }
@@ -489,6 +893,7 @@ impl SandboxPolicy {
             },
             environment: ExecutionEnvironment::WorkspaceIsolated,
             audit_logging: true,
+            audit_hooks: false,
             custom_sandbox_profile: None,
         }
     }
@@ -512,6 +917,7 @@ impl SandboxPolicy {
             },
             environment: ExecutionEnvironment::PlatformSandboxed,
             audit_logging: true,
+            audit_hooks: true,
             custom_sandbox_profile: None,
         }
     }
@@ -573,6 +979,11 @@ impl SandboxPolicyBuilder {
         self
     }
 
+    pub fn audit_hooks(mut self, enabled: bool) -> Self {
+        self.policy.audit_hooks = enabled;
+        self
+    }
+
     pub fn custom_sandbox_profile(mut self, path: PathBuf) -> Self {
         self.policy.custom_sandbox_profile = Some(path);
         self
@@ -589,6 +1000,7 @@ impl SandboxPolicyBuilder {
 
 /// Enterprise policy that can lock certain settings
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct EnterprisePolicy {
     /// Minimum required security level (0-10)
     pub minimum_security_level: Option<u8>,
@@ -605,6 +1017,10 @@ pub struct EnterprisePolicy {
     /// Required audit logging
     pub require_audit_logging: bool,
 
+    /// Required in-interpreter audit hooks
+    #[serde(default)]
+    pub require_audit_hooks: bool,
+
     /// Required platform sandboxing
     pub require_platform_sandbox: bool,
 
@@ -614,11 +1030,35 @@ pub struct EnterprisePolicy {
     /// Maximum allowed resource limits
     pub max_allowed_resources: Option<ResourceLimitsPolicy>,
 
+    /// What `python_env.install` may install into managed environments
+    #[serde(default)]
+    pub package_install: PackageInstallPolicy,
+
     /// Custom message to show users about this policy
     pub policy_message: Option<String>,
 }
 
 impl EnterprisePolicy {
+    /// Load from a JSON, TOML or YAML file, picked by extension
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        read_policy_file(path, "enterprise policy")
+    }
+
+    /// Load from a policy file signed with `key`
+    ///
+    /// The detached signature is read from `<path>.sig` (base64, as written by
+    /// `rzn-plugin-devkit sign`). Missing, malformed or non-matching
+    /// signatures are refused.
+    pub fn from_signed_file(path: &Path, key: &VerifyingKey) -> Result<Self, String> {
+        read_signed_policy_file(path, key, "enterprise policy")
+    }
+
+    /// JSON Schema for enterprise policy files
+    #[cfg(feature = "json-schema")]
+    pub fn json_schema() -> serde_json::Value {
+        schemars::schema_for!(EnterprisePolicy).to_value()
+    }
+
     /// Apply enterprise policy to a user policy
     /// Returns Ok(modified_policy) or Err(violation_message)
     pub fn apply(&self, mut user_policy: SandboxPolicy) -> Result<SandboxPolicy, String> {
@@ -652,6 +1092,11 @@ impl EnterprisePolicy {
             user_policy.audit_logging = true;
         }
 
+        // Require audit hooks
+        if self.require_audit_hooks {
+            user_policy.audit_hooks = true;
+        }
+
         // Require platform sandbox
         if self.require_platform_sandbox {
             user_policy.environment = ExecutionEnvironment::PlatformSandboxed;
@@ -671,7 +1116,11 @@ impl EnterprisePolicy {
                     // Remove blocked modules from whitelist
                     let filtered: HashSet<_> = wl
                         .into_iter()
-                        .filter(|m| !self.always_blocked_modules.contains(m))
+                        .filter(|m| {
+                            !self
+                                .always_blocked_modules
+                                .contains(crate::config::entry_module(m))
+                        })
                         .collect();
                     ImportPolicyType::Whitelist(filtered)
                 }
@@ -682,7 +1131,11 @@ impl EnterprisePolicy {
                     blacklist.extend(self.always_blocked_modules.clone());
                     let filtered: HashSet<_> = whitelist
                         .into_iter()
-                        .filter(|m| !self.always_blocked_modules.contains(m))
+                        .filter(|m| {
+                            !self
+                                .always_blocked_modules
+                                .contains(crate::config::entry_module(m))
+                        })
                         .collect();
                     ImportPolicyType::WhitelistWithBlacklist {
                         whitelist: filtered,
@@ -719,6 +1172,32 @@ impl EnterprisePolicy {
         Ok(user_policy)
     }
 
+    /// Primitives of `policy` this enterprise policy would change
+    /// (`network`, `filesystem`, `process`, `imports`, `resources`,
+    /// `environment`, `audit_logging`, `audit_hooks`), or the violation
+    /// message if it rejects the policy outright
+    pub fn changes(&self, policy: &SandboxPolicy) -> Result<Vec<&'static str>, String> {
+        let applied = self.apply(policy.clone())?;
+        let changed = [
+            ("network", applied.network != policy.network),
+            ("filesystem", applied.filesystem != policy.filesystem),
+            ("process", applied.process != policy.process),
+            ("imports", applied.imports != policy.imports),
+            ("resources", applied.resources != policy.resources),
+            ("environment", applied.environment != policy.environment),
+            (
+                "audit_logging",
+                applied.audit_logging != policy.audit_logging,
+            ),
+            ("audit_hooks", applied.audit_hooks != policy.audit_hooks),
+        ];
+        Ok(changed
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| name)
+            .collect())
+    }
+
     /// Example enterprise policy for a financial services company
     pub fn financial_services_example() -> Self {
         Self {
@@ -726,6 +1205,7 @@ impl EnterprisePolicy {
             locked_network: Some(NetworkPolicy::Blocked),
             locked_process: Some(ProcessPolicy::Blocked),
             require_audit_logging: true,
+            require_audit_hooks: true,
             require_platform_sandbox: true,
             always_blocked_modules: [
                 "os",
@@ -765,6 +1245,7 @@ impl EnterprisePolicy {
             locked_filesystem: Some(FilesystemPolicy::WorkspaceOnly),
             locked_process: Some(ProcessPolicy::Blocked),
             require_audit_logging: true,
+            require_audit_hooks: true,
             require_platform_sandbox: true,
             always_blocked_modules: [
                 "os",
@@ -797,11 +1278,197 @@ impl EnterprisePolicy {
     }
 }
 
+/// Limits on the packages `python_env.install` and `python_env.create` install
+///
+/// Checked against each package target and each line of a requirements
+/// file before pip or micromamba runs. While installs are restricted, the
+/// worker also refuses index URL and channel overrides.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct PackageInstallPolicy {
+    /// Distributions that may be installed, compared by normalized name
+    /// (`PyYAML` matches `pyyaml`); `None` allows any
+    #[serde(default)]
+    pub allowed_packages: Option<HashSet<String>>,
+
+    /// Always pass `--require-hashes`, so every requirement needs a pinned hash
+    #[serde(default)]
+    pub require_hashes: bool,
+
+    /// Refuse URLs, VCS links and local paths as install targets
+    #[serde(default)]
+    pub forbid_direct_references: bool,
+}
+
+impl PackageInstallPolicy {
+    /// Whether the policy restricts what can be installed
+    pub fn is_restricted(&self) -> bool {
+        self.allowed_packages.is_some() || self.forbid_direct_references
+    }
+
+    /// Check one install target, a requirement like `requests>=2.31`, a URL
+    /// or a path
+    pub fn check_target(&self, target: &str) -> Result<(), String> {
+        let target = target.trim();
+        if !self.is_restricted() {
+            return Ok(());
+        }
+        // Anything pip would read as an option could change the index or
+        // pull in another requirements file
+        if target.starts_with('-') {
+            return Err(format!("install option '{}' is not allowed", target));
+        }
+        let name = requirement_name(target);
+        if self.forbid_direct_references && is_direct_reference(target, name) {
+            return Err(format!(
+                "'{}' is a URL, VCS or path target, which policy forbids",
+                target
+            ));
+        }
+        if let Some(allowed) = &self.allowed_packages {
+            let name = normalize_package_name(name);
+            if name.is_empty() || !allowed.iter().any(|a| normalize_package_name(a) == name) {
+                return Err(format!(
+                    "package '{}' is not in the install allowlist",
+                    target
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every requirement in the contents of a requirements file
+    ///
+    /// `--hash` options on a requirement line are fine; any other option
+    /// line is refused while the policy restricts installs.
+    pub fn check_requirements(&self, contents: &str) -> Result<(), String> {
+        let joined = contents.replace("\\\n", " ");
+        for line in joined.lines() {
+            let line = match line.find(" #") {
+                Some(comment) => &line[..comment],
+                None if line.trim_start().starts_with('#') => "",
+                None => line,
+            };
+            let requirement = line
+                .split_whitespace()
+                .take_while(|token| !token.starts_with("--hash"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !requirement.is_empty() {
+                self.check_target(&requirement)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Project name at the start of a PEP 508 requirement
+fn requirement_name(requirement: &str) -> &str {
+    let end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    &requirement[..end]
+}
+
+/// PEP 503 normalized project name
+fn normalize_package_name(name: &str) -> String {
+    name.split(['-', '_', '.'])
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_ascii_lowercase()
+}
+
+fn is_direct_reference(target: &str, name: &str) -> bool {
+    let rest = target[name.len()..].trim_start();
+    target.contains("://")
+        || rest.starts_with(['@', '/', '\\', ':'])
+        || name.is_empty()
+        || name.starts_with('.')
+        || [".whl", ".zip", ".tar.gz", ".tgz", ".tar.bz2"]
+            .iter()
+            .any(|ext| target.ends_with(ext))
+}
+
+// ============================================================================
+// TENANT POLICIES - Per-caller policy resolution
+// ============================================================================
+
+/// Template and overrides a caller runs under
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct TenantPolicy {
+    /// Name of a template registered with the `PolicyManager`
+    pub template: String,
+    /// Policy fields laid over the template, in policy file form. Top-level
+    /// keys replace the template's; `resources` is merged field by field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overrides: Option<serde_json::Value>,
+}
+
+impl TenantPolicy {
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: template.to_string(),
+            overrides: None,
+        }
+    }
+
+    pub fn with_overrides(mut self, overrides: serde_json::Value) -> Self {
+        self.overrides = Some(overrides);
+        self
+    }
+}
+
+/// Picks the policy for a tenant or user id
+///
+/// Set on a [`PolicyManager`] with `set_resolver`; implement it to look
+/// callers up in an external directory, or use [`TenantPolicyMap`].
+pub trait PolicyResolver: Send + Sync {
+    /// Policy for `caller`, or `None` to use the manager's selected policy
+    fn resolve(&self, caller: &str) -> Option<TenantPolicy>;
+}
+
+/// Fixed map of callers to policies, with an optional fallback
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct TenantPolicyMap {
+    /// Policy per tenant or user id
+    #[serde(default)]
+    pub tenants: std::collections::HashMap<String, TenantPolicy>,
+    /// Policy for callers not listed in `tenants`
+    #[serde(default)]
+    pub default: Option<TenantPolicy>,
+}
+
+impl TenantPolicyMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tenant(mut self, caller: &str, policy: TenantPolicy) -> Self {
+        self.tenants.insert(caller.to_string(), policy);
+        self
+    }
+
+    pub fn default_policy(mut self, policy: TenantPolicy) -> Self {
+        self.default = Some(policy);
+        self
+    }
+}
+
+impl PolicyResolver for TenantPolicyMap {
+    fn resolve(&self, caller: &str) -> Option<TenantPolicy> {
+        self.tenants.get(caller).or(self.default.as_ref()).cloned()
+    }
+}
+
 // ============================================================================
 // POLICY MANAGER - Loads and manages policies
 // ============================================================================
 
 /// Manages policy loading, caching, and application
+#[derive(Clone)]
 pub struct PolicyManager {
     /// Enterprise policy (loaded from config)
     enterprise_policy: Option<EnterprisePolicy>,
@@ -809,38 +1476,93 @@ pub struct PolicyManager {
     templates: std::collections::HashMap<String, SandboxPolicy>,
     /// User's selected policy name
     selected_policy: String,
+    /// Key enterprise policy files must be signed with, if any
+    enterprise_policy_key: Option<VerifyingKey>,
+    /// Maps callers to templates for `get_policy_for`
+    resolver: Option<Arc<dyn PolicyResolver>>,
 }
 
 impl PolicyManager {
     /// Create a new policy manager
     pub fn new() -> Self {
-        let mut templates = std::collections::HashMap::new();
-        templates.insert("yolo".to_string(), SandboxPolicy::yolo());
-        templates.insert("balanced".to_string(), SandboxPolicy::balanced());
-        templates.insert("data_science".to_string(), SandboxPolicy::data_science());
-        templates.insert(
-            "document_processing".to_string(),
-            SandboxPolicy::document_processing(),
-        );
-        templates.insert("enterprise".to_string(), SandboxPolicy::enterprise());
-
         Self {
             enterprise_policy: None,
-            templates,
+            templates: builtin_templates(),
             selected_policy: "balanced".to_string(),
+            enterprise_policy_key: None,
+            resolver: None,
         }
     }
 
-    /// Load enterprise policy from a JSON file
-    pub fn load_enterprise_policy(&mut self, path: &PathBuf) -> Result<(), String> {
-        let content = std::fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read enterprise policy: {}", e))?;
-        let policy: EnterprisePolicy = serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to parse enterprise policy: {}", e))?;
+    /// Require enterprise policy files to carry a valid signature from `key`
+    ///
+    /// Once set, `load_enterprise_policy` refuses files without a matching
+    /// detached `<file>.sig`.
+    pub fn require_signed_enterprise_policy(&mut self, key: VerifyingKey) {
+        self.enterprise_policy_key = Some(key);
+    }
+
+    /// Key enterprise policies must be signed with, if required
+    #[cfg(feature = "remote-policy")]
+    pub(crate) fn enterprise_policy_key(&self) -> Option<&VerifyingKey> {
+        self.enterprise_policy_key.as_ref()
+    }
+
+    /// Load enterprise policy from a JSON, TOML or YAML file
+    pub fn load_enterprise_policy(&mut self, path: &Path) -> Result<(), String> {
+        let policy = match &self.enterprise_policy_key {
+            Some(key) => EnterprisePolicy::from_signed_file(path, key)?,
+            None => EnterprisePolicy::from_file(path)?,
+        };
         self.enterprise_policy = Some(policy);
         Ok(())
     }
 
+    /// Register every policy file in a directory as a template named after
+    /// the file stem, replacing templates of the same name
+    ///
+    /// Files without a `.json`, `.toml`, `.yaml` or `.yml` extension are
+    /// skipped. A file may set `extends = "<name>"` to start from another
+    /// file in the directory or an already registered template (a file
+    /// extending its own name extends the template it replaces) and only list
+    /// the primitives it changes. Returns the registered names, sorted.
+    pub fn load_policies_dir(&mut self, path: &Path) -> Result<Vec<String>, String> {
+        let entries = std::fs::read_dir(path)
+            .map_err(|e| format!("Failed to read policy directory {}: {}", path.display(), e))?;
+
+        let mut files = std::collections::HashMap::new();
+        for entry in entries {
+            let file = entry
+                .map_err(|e| format!("Failed to read policy directory {}: {}", path.display(), e))?
+                .path();
+            if !file.is_file() || PolicyFileFormat::from_path(&file).is_none() {
+                continue;
+            }
+            let Some(name) = file.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let value = read_policy_value(&file)?;
+            files.insert(name.to_string(), value);
+        }
+
+        let mut resolved = std::collections::HashMap::new();
+        for (name, value) in &files {
+            resolve_policy(
+                name,
+                value,
+                &files,
+                &self.templates,
+                &mut resolved,
+                &mut Vec::new(),
+            )?;
+        }
+
+        let mut loaded: Vec<String> = resolved.keys().cloned().collect();
+        loaded.sort();
+        self.templates.extend(resolved);
+        Ok(loaded)
+    }
+
     /// Set enterprise policy directly
     pub fn set_enterprise_policy(&mut self, policy: EnterprisePolicy) {
         self.enterprise_policy = Some(policy);
@@ -893,12 +1615,432 @@ impl PolicyManager {
         }
     }
 
+    /// Resolve per-caller policies with `resolver` in `get_policy_for`
+    pub fn set_resolver(&mut self, resolver: impl PolicyResolver + 'static) {
+        self.resolver = Some(Arc::new(resolver));
+    }
+
+    /// Get the effective policy for a tenant or user id
+    ///
+    /// The resolver picks a template and overrides for `caller`; enterprise
+    /// constraints are applied on top, as in `get_effective_policy`. Callers
+    /// the resolver doesn't know, or any caller when no resolver is set, get
+    /// the selected policy.
+    pub fn get_policy_for(&self, caller: &str) -> Result<SandboxPolicy, String> {
+        let Some(tenant) = self.resolver.as_ref().and_then(|r| r.resolve(caller)) else {
+            return self.get_effective_policy();
+        };
+
+        let base = self.templates.get(&tenant.template).ok_or_else(|| {
+            format!(
+                "Policy for '{}' uses unknown template '{}'",
+                caller, tenant.template
+            )
+        })?;
+        let user_policy = match &tenant.overrides {
+            Some(overrides) => serde_json::from_value(merge_policy_value(base, overrides)?)
+                .map_err(|e| format!("Invalid policy overrides for '{}': {}", caller, e))?,
+            None => base.clone(),
+        };
+
+        match &self.enterprise_policy {
+            Some(enterprise) => enterprise.apply(user_policy),
+            None => Ok(user_policy),
+        }
+    }
+
     /// Get enterprise policy message if any
     pub fn get_enterprise_message(&self) -> Option<&str> {
         self.enterprise_policy
             .as_ref()
             .and_then(|p| p.policy_message.as_deref())
     }
+
+    /// Watch a policy file or directory and reload it when it changes
+    ///
+    /// A directory is loaded with [`load_policies_dir`](Self::load_policies_dir),
+    /// a file with [`load_enterprise_policy`](Self::load_enterprise_policy)
+    /// (so a configured signing key still applies). The path is loaded once
+    /// before this returns, then polled every [`POLICY_POLL_INTERVAL`].
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn watch(self, path: impl Into<PathBuf>) -> Result<PolicyWatch, String> {
+        self.watch_every(path, POLICY_POLL_INTERVAL)
+    }
+
+    /// [`watch`](Self::watch) with a custom polling interval
+    pub fn watch_every(
+        mut self,
+        path: impl Into<PathBuf>,
+        interval: std::time::Duration,
+    ) -> Result<PolicyWatch, String> {
+        let path = path.into();
+        let fingerprint = policy_fingerprint(&path);
+        self.reload_from(&path)?;
+        let policy = Arc::new(self.get_effective_policy()?);
+
+        let state = Arc::new(std::sync::RwLock::new(WatchedPolicy {
+            manager: self,
+            effective: policy,
+        }));
+        let (changes, _) = tokio::sync::broadcast::channel(16);
+        let task = tokio::spawn(poll_policy(
+            path,
+            interval,
+            fingerprint,
+            state.clone(),
+            changes.clone(),
+        ));
+        Ok(PolicyWatch {
+            state,
+            changes,
+            task,
+        })
+    }
+
+    fn reload_from(&mut self, path: &Path) -> Result<(), String> {
+        if path.is_dir() {
+            self.load_policies_dir(path).map(|_| ())
+        } else {
+            self.load_enterprise_policy(path)
+        }
+    }
+}
+
+/// How often [`PolicyManager::watch`] checks policy files for changes
+pub const POLICY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// A watched policy file was reloaded and the effective policy replaced
+#[derive(Debug, Clone)]
+pub struct PolicyChanged {
+    /// Watched file or directory
+    pub path: PathBuf,
+    /// Effective policy after the reload
+    pub policy: Arc<SandboxPolicy>,
+}
+
+struct WatchedPolicy {
+    manager: PolicyManager,
+    effective: Arc<SandboxPolicy>,
+}
+
+/// Handle to a [`PolicyManager`] whose policy files are being watched
+///
+/// Dropping the handle stops watching.
+pub struct PolicyWatch {
+    state: Arc<std::sync::RwLock<WatchedPolicy>>,
+    changes: tokio::sync::broadcast::Sender<PolicyChanged>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl PolicyWatch {
+    /// Current effective policy
+    pub fn effective_policy(&self) -> Arc<SandboxPolicy> {
+        self.state
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .effective
+            .clone()
+    }
+
+    /// Receive a [`PolicyChanged`] for every successful reload
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<PolicyChanged> {
+        self.changes.subscribe()
+    }
+}
+
+impl Drop for PolicyWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn poll_policy(
+    path: PathBuf,
+    interval: std::time::Duration,
+    mut fingerprint: Vec<(PathBuf, Option<std::time::SystemTime>, u64)>,
+    state: Arc<std::sync::RwLock<WatchedPolicy>>,
+    changes: tokio::sync::broadcast::Sender<PolicyChanged>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let current = policy_fingerprint(&path);
+        if current == fingerprint {
+            continue;
+        }
+        fingerprint = current;
+
+        // Reload into a copy so a bad file leaves the current policy in
+        // place, and readers aren't blocked on the file reads. This task is
+        // the only writer, so the copy can't go stale before the swap.
+        let mut manager = state
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .manager
+            .clone();
+        let reloaded = manager
+            .reload_from(&path)
+            .and_then(|_| manager.get_effective_policy())
+            .map(|policy| {
+                let policy = Arc::new(policy);
+                *state.write().unwrap_or_else(|e| e.into_inner()) = WatchedPolicy {
+                    manager,
+                    effective: policy.clone(),
+                };
+                policy
+            });
+        match reloaded {
+            Ok(policy) => {
+                tracing::info!("Reloaded policy from {}", path.display());
+                let _ = changes.send(PolicyChanged {
+                    path: path.clone(),
+                    policy,
+                });
+            }
+            Err(e) => tracing::warn!("Keeping previous policy, reload failed: {}", e),
+        }
+    }
+}
+
+/// Size and modification time of every file a reload would read
+fn policy_fingerprint(path: &Path) -> Vec<(PathBuf, Option<std::time::SystemTime>, u64)> {
+    let mut files = match std::fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|file| PolicyFileFormat::from_path(file).is_some())
+            .collect(),
+        Err(_) => vec![path.to_path_buf(), crate::signing::signature_path(path)],
+    };
+    files.sort();
+    files
+        .into_iter()
+        .map(|file| {
+            let meta = std::fs::metadata(&file).ok();
+            let modified = meta.as_ref().and_then(|m| m.modified().ok());
+            let len = meta.map(|m| m.len()).unwrap_or(0);
+            (file, modified, len)
+        })
+        .collect()
+}
+
+fn builtin_templates() -> std::collections::HashMap<String, SandboxPolicy> {
+    [
+        "yolo",
+        "balanced",
+        "data_science",
+        "document_processing",
+        "enterprise",
+    ]
+    .into_iter()
+    .filter_map(|name| Some((name.to_string(), SandboxPolicy::template(name)?)))
+    .collect()
+}
+
+/// Resolve a parsed policy file, following its `extends` chain
+///
+/// Bases are looked up among `files` first, then `templates`. Top-level keys
+/// of the file replace the base's; `resources` is merged field by field.
+fn resolve_policy(
+    name: &str,
+    value: &serde_json::Value,
+    files: &std::collections::HashMap<String, serde_json::Value>,
+    templates: &std::collections::HashMap<String, SandboxPolicy>,
+    resolved: &mut std::collections::HashMap<String, SandboxPolicy>,
+    chain: &mut Vec<String>,
+) -> Result<SandboxPolicy, String> {
+    if let Some(policy) = resolved.get(name) {
+        return Ok(policy.clone());
+    }
+    if chain.iter().any(|n| n == name) {
+        chain.push(name.to_string());
+        return Err(format!("Policy extends itself: {}", chain.join(" -> ")));
+    }
+
+    let mut value = value.clone();
+    let base = match value.as_object_mut().and_then(|o| o.remove("extends")) {
+        None => None,
+        Some(serde_json::Value::String(base)) => {
+            let policy = match files.get(&base) {
+                Some(file) if base != name => {
+                    chain.push(name.to_string());
+                    let policy = resolve_policy(&base, file, files, templates, resolved, chain);
+                    chain.pop();
+                    policy?
+                }
+                _ => templates.get(&base).cloned().ok_or_else(|| {
+                    format!("Policy '{}' extends unknown policy '{}'", name, base)
+                })?,
+            };
+            Some(policy)
+        }
+        Some(other) => {
+            return Err(format!(
+                "Policy '{}' has a non-string extends: {}",
+                name, other
+            ))
+        }
+    };
+
+    if let Some(base) = base {
+        value = merge_policy_value(&base, &value)?;
+    }
+
+    let policy: SandboxPolicy = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse policy '{}': {}", name, e))?;
+    resolved.insert(name.to_string(), policy.clone());
+    Ok(policy)
+}
+
+/// Lay `overrides` over `base`: top-level keys replace the base's and
+/// `resources` is merged field by field
+fn merge_policy_value(
+    base: &SandboxPolicy,
+    overrides: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let mut merged = serde_json::to_value(base).map_err(|e| e.to_string())?;
+    if let (Some(merged), Some(overrides)) = (merged.as_object_mut(), overrides.as_object()) {
+        for (key, override_value) in overrides {
+            match (merged.get_mut(key), override_value) {
+                (Some(serde_json::Value::Object(base)), serde_json::Value::Object(fields))
+                    if key == "resources" =>
+                {
+                    base.extend(fields.clone());
+                }
+                _ => {
+                    merged.insert(key.clone(), override_value.clone());
+                }
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Serialization formats accepted for policy files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PolicyFileFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl PolicyFileFormat {
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+fn read_policy_file<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T, String> {
+    let content = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {} {}: {}", what, path.display(), e))?;
+    parse_policy_file(path, &content, what)
+}
+
+/// Read a policy file as an untyped value, for `extends` merging
+fn read_policy_value(path: &Path) -> Result<serde_json::Value, String> {
+    if PolicyFileFormat::from_path(path) != Some(PolicyFileFormat::Yaml) {
+        return read_policy_file(path, "policy");
+    }
+    let value: serde_yaml::Value = read_policy_file(path, "policy")?;
+    yaml_to_json(value).map_err(|e| format!("Failed to parse policy {}: {}", path.display(), e))
+}
+
+/// Convert YAML to JSON, turning `!variant value` tags into `{variant: value}`
+/// so enum primitives keep serde's externally tagged shape
+fn yaml_to_json(value: serde_yaml::Value) -> Result<serde_json::Value, String> {
+    use serde_yaml::Value;
+    Ok(match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Bool(b) => serde_json::Value::Bool(b),
+        Value::Number(n) => serde_json::to_value(n).map_err(|e| e.to_string())?,
+        Value::String(s) => serde_json::Value::String(s),
+        Value::Sequence(items) => serde_json::Value::Array(
+            items
+                .into_iter()
+                .map(yaml_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Mapping(map) => {
+            let mut object = serde_json::Map::new();
+            for (key, value) in map {
+                let key = match key {
+                    Value::String(key) => key,
+                    other => return Err(format!("non-string key {:?}", other)),
+                };
+                object.insert(key, yaml_to_json(value)?);
+            }
+            serde_json::Value::Object(object)
+        }
+        Value::Tagged(tagged) => {
+            let tag = tagged.tag.to_string();
+            let mut object = serde_json::Map::new();
+            object.insert(
+                tag.trim_start_matches('!').to_string(),
+                yaml_to_json(tagged.value)?,
+            );
+            serde_json::Value::Object(object)
+        }
+    })
+}
+
+/// Read a policy file and check it against its detached `.sig` before parsing
+///
+/// The bytes that were verified are the bytes that get parsed, so the file
+/// can't be swapped between the two steps.
+fn read_signed_policy_file<T: DeserializeOwned>(
+    path: &Path,
+    key: &VerifyingKey,
+    what: &str,
+) -> Result<T, String> {
+    let content = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {} {}: {}", what, path.display(), e))?;
+    let sig_path = crate::signing::signature_path(path);
+    let signature = std::fs::read_to_string(&sig_path).map_err(|e| {
+        format!(
+            "Refusing unsigned {} {}: cannot read {}: {}",
+            what,
+            path.display(),
+            sig_path.display(),
+            e
+        )
+    })?;
+    crate::signing::verify(key, &content, &signature).map_err(|e| {
+        format!(
+            "Refusing {} {}: signature check failed: {}",
+            what,
+            path.display(),
+            e
+        )
+    })?;
+    parse_policy_file(path, &content, what)
+}
+
+pub(crate) fn parse_policy_file<T: DeserializeOwned>(
+    path: &Path,
+    content: &[u8],
+    what: &str,
+) -> Result<T, String> {
+    let format = PolicyFileFormat::from_path(path).ok_or_else(|| {
+        format!(
+            "Unsupported {} format: {} (expected .json, .toml, .yaml or .yml)",
+            what,
+            path.display()
+        )
+    })?;
+    let content = std::str::from_utf8(content)
+        .map_err(|e| format!("Failed to read {} {}: {}", what, path.display(), e))?;
+    let parsed = match format {
+        PolicyFileFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        PolicyFileFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+        PolicyFileFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+    };
+    parsed.map_err(|e| format!("Failed to parse {} {}: {}", what, path.display(), e))
 }
 
 impl Default for PolicyManager {
@@ -911,6 +2053,75 @@ impl Default for PolicyManager {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_policy_files_in_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = SandboxPolicy::custom(SandboxPolicy::data_science())
+            .name("analysts")
+            .network(NetworkPolicy::AllowList(vec!["pypi.org".to_string()]))
+            .build();
+        std::fs::write(
+            dir.path().join("analysts.toml"),
+            toml::to_string(&policy).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("analysts_yaml.yml"),
+            serde_yaml::to_string(&policy).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("analysts_json.json"),
+            serde_json::to_string(&policy).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("README.md"), "not a policy").unwrap();
+
+        let mut manager = PolicyManager::new();
+        let loaded = manager.load_policies_dir(dir.path()).unwrap();
+        assert_eq!(loaded, vec!["analysts", "analysts_json", "analysts_yaml"]);
+        for name in &loaded {
+            manager.select_policy(name).unwrap();
+            let effective = manager.get_effective_policy().unwrap();
+            assert_eq!(effective.name, policy.name);
+            assert_eq!(effective.network, policy.network);
+            assert_eq!(effective.filesystem, policy.filesystem);
+            assert_eq!(effective.imports, policy.imports);
+            assert_eq!(effective.resources, policy.resources);
+        }
+
+        let enterprise_path = dir.path().join("enterprise.yaml");
+        std::fs::write(
+            &enterprise_path,
+            "minimum_security_level: 9\nrequire_audit_logging: true\nrequire_platform_sandbox: false\nalways_blocked_modules: [ctypes]\n",
+        )
+        .unwrap();
+        manager.load_enterprise_policy(&enterprise_path).unwrap();
+        assert!(manager.get_effective_policy().is_err());
+
+        assert!(SandboxPolicy::from_file(&dir.path().join("README.md")).is_err());
+    }
+
+    #[test]
+    fn test_import_matching_is_hierarchical() {
+        let whitelist = ImportPolicyType::Whitelist(["matplotlib".to_string()].into());
+        assert!(whitelist.is_allowed("matplotlib"));
+        assert!(whitelist.is_allowed("matplotlib.pyplot"));
+        assert!(!whitelist.is_allowed("matplotlibx"));
+
+        let blacklist = ImportPolicyType::default();
+        assert!(blacklist.is_allowed("asyncio"));
+        assert!(!blacklist.is_allowed("asyncio.subprocess"));
+        assert!(!blacklist.is_allowed("os.path"));
+
+        for module in ["asyncio", "asyncio.subprocess", "os.path", "json"] {
+            assert_eq!(
+                blacklist.is_allowed(module),
+                blacklist.to_import_policy().is_allowed(module)
+            );
+        }
+    }
+
     #[test]
     fn test_security_levels() {
         assert_eq!(SandboxPolicy::yolo().security_level(), 0);
@@ -934,6 +2145,201 @@ mod tests {
         let result = enterprise.apply(SandboxPolicy::balanced());
         assert!(result.is_ok());
         assert!(result.unwrap().audit_logging);
+
+        assert!(enterprise.changes(&SandboxPolicy::yolo()).is_err());
+        assert_eq!(
+            enterprise.changes(&SandboxPolicy::balanced()).unwrap(),
+            ["audit_logging"]
+        );
+        assert_eq!(
+            enterprise.changes(&SandboxPolicy::enterprise()).unwrap(),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn test_filesystem_path_rules() {
+        assert_eq!(
+            FilesystemPolicy::ReadAnyWriteWorkspace.rules(),
+            FilesystemRules::new().read_any(true).workspace(true)
+        );
+
+        let filesystem: FilesystemPolicy = serde_json::from_str(
+            r#"{"paths": {"read": ["/data"], "write": ["/data/out"], "deny": ["/data/secrets"]}}"#,
+        )
+        .unwrap();
+        let rules = filesystem.rules();
+        assert_eq!(rules.read, vec![PathBuf::from("/data")]);
+        assert_eq!(rules.deny, vec![PathBuf::from("/data/secrets")]);
+        assert!(!rules.workspace && !rules.read_any);
+
+        let policy = SandboxPolicy::custom(SandboxPolicy::enterprise())
+            .filesystem(filesystem)
+            .build();
+        assert_eq!(
+            policy.security_level(),
+            SandboxPolicy::enterprise().security_level() - 1
+        );
+    }
+
+    #[test]
+    fn test_network_rules_ports_and_schemes() {
+        let rule = NetworkRule::parse("https://API.example.com/v1");
+        assert_eq!(
+            (rule.host.as_str(), rule.port),
+            ("api.example.com", Some(443))
+        );
+        assert!(rule.matches("api.example.com.", 443));
+        assert!(!rule.matches("api.example.com", 80));
+
+        let wildcard = NetworkRule::parse("*.internal:5432");
+        assert!(wildcard.matches("db.internal", 5432));
+        assert!(!wildcard.matches("db.internal", 22));
+        assert_eq!(NetworkRule::parse("::1").port, None);
+        assert_eq!(NetworkRule::parse("[::1]:8080").port, Some(8080));
+        assert_eq!(
+            python_network_rules(&["pypi.org".to_string(), "*.internal:5432".to_string()]),
+            r#"[("pypi.org", None), ("*.internal", 5432)]"#
+        );
+
+        // The OS profile narrows outbound traffic once every entry has a port
+        let policy = SandboxPolicy::custom(SandboxPolicy::balanced())
+            .network(NetworkPolicy::AllowList(vec![
+                "https://api.example.com".to_string(),
+                "*.internal:5432".to_string(),
+            ]))
+            .build();
+        let profile = crate::seatbelt::render_profile(&policy);
+        assert!(profile
+            .contains("(allow network-outbound (remote ip \"*:443\") (remote ip \"*:5432\"))"));
+    }
+
+    #[test]
+    fn test_signed_enterprise_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("org.toml");
+        let content = toml::to_string(&EnterprisePolicy::default()).unwrap();
+        std::fs::write(&path, &content).unwrap();
+
+        let key = ed25519_dalek::SigningKey::from_bytes(&[3u8; 32]);
+        let mut manager = PolicyManager::new();
+        manager.require_signed_enterprise_policy(key.verifying_key());
+        let err = manager.load_enterprise_policy(&path).unwrap_err();
+        assert!(err.contains("unsigned"), "{err}");
+
+        let sig_path = crate::signing::signature_path(&path);
+        std::fs::write(&sig_path, crate::signing::sign(&key, content.as_bytes())).unwrap();
+        manager.load_enterprise_policy(&path).unwrap();
+
+        std::fs::write(&path, content.replace("false", "true")).unwrap();
+        let err = manager.load_enterprise_policy(&path).unwrap_err();
+        assert!(err.contains("signature check failed"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_watch_reloads_policy_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("team.json");
+        let write = |name: &str| {
+            let policy = SandboxPolicy::custom(SandboxPolicy::balanced())
+                .name(name)
+                .build();
+            std::fs::write(&file, serde_json::to_string(&policy).unwrap()).unwrap();
+        };
+        write("v1");
+
+        let mut manager = PolicyManager::new();
+        manager.load_policies_dir(dir.path()).unwrap();
+        manager.select_policy("team").unwrap();
+        let watch = manager
+            .watch_every(dir.path(), std::time::Duration::from_millis(20))
+            .unwrap();
+        let mut changes = watch.subscribe();
+        assert_eq!(watch.effective_policy().name, "v1");
+
+        write("version two");
+        let changed = tokio::time::timeout(std::time::Duration::from_secs(5), changes.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(changed.policy.name, "version two");
+        assert_eq!(watch.effective_policy().name, "version two");
+
+        // A broken file keeps the previous policy
+        std::fs::write(&file, "{ not json").unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(watch.effective_policy().name, "version two");
+    }
+
+    #[test]
+    fn test_policy_extends() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("analysts.toml"),
+            "extends = \"data_science\"\nname = \"Analysts\"\nnetwork = \"localhost_only\"\n\n[resources]\nmax_memory_mb = 8192\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("analysts_audited.yaml"),
+            "extends: analysts\naudit_logging: true\n",
+        )
+        .unwrap();
+
+        let mut manager = PolicyManager::new();
+        let loaded = manager.load_policies_dir(dir.path()).unwrap();
+        assert_eq!(loaded, vec!["analysts", "analysts_audited"]);
+        manager.select_policy("analysts_audited").unwrap();
+        let policy = manager.get_effective_policy().unwrap();
+        let base = SandboxPolicy::data_science();
+        assert_eq!(policy.name, "Analysts");
+        assert_eq!(policy.network, NetworkPolicy::LocalhostOnly);
+        assert_eq!(policy.imports, base.imports);
+        assert_eq!(policy.resources.max_memory_mb, 8192);
+        assert_eq!(
+            policy.resources.max_cpu_seconds,
+            base.resources.max_cpu_seconds
+        );
+        assert!(policy.audit_logging);
+
+        std::fs::write(
+            dir.path().join("analysts.toml"),
+            "extends = \"analysts_audited\"\n",
+        )
+        .unwrap();
+        let err = PolicyManager::new()
+            .load_policies_dir(dir.path())
+            .unwrap_err();
+        assert!(err.contains("extends itself"), "{err}");
+        let err = SandboxPolicy::from_file(&dir.path().join("analysts.toml")).unwrap_err();
+        assert!(err.contains("unknown policy 'analysts_audited'"), "{err}");
+    }
+
+    #[test]
+    fn test_policy_to_execution_options() {
+        let policy = SandboxPolicy::custom(SandboxPolicy::data_science())
+            .network(NetworkPolicy::LocalhostOnly)
+            .environment(ExecutionEnvironment::Native)
+            .build();
+        let options = policy.to_execution_options();
+        assert_eq!(options.memory_mb, policy.resources.max_memory_mb);
+        assert_eq!(
+            options.timeout,
+            std::time::Duration::from_secs(policy.resources.max_timeout_seconds)
+        );
+        assert!(options.import_policy.is_allowed("pandas"));
+        assert!(!options.import_policy.is_allowed("socket"));
+        assert!(options
+            .network_allowlist
+            .unwrap()
+            .contains(&"localhost".to_string()));
+        assert_eq!(
+            policy.execution_mode(),
+            crate::config::ExecutionMode::Native
+        );
+        assert_eq!(
+            policy.resource_limits().max_threads,
+            policy.resources.max_threads
+        );
     }
 
     #[test]
@@ -948,4 +2354,91 @@ mod tests {
         assert_eq!(policy.network, NetworkPolicy::LocalhostOnly);
         assert!(policy.audit_logging);
     }
+
+    #[cfg(feature = "json-schema")]
+    #[test]
+    fn test_json_schema_describes_policy_fields() {
+        let schema = SandboxPolicy::json_schema();
+        for field in [
+            "network",
+            "filesystem",
+            "imports",
+            "resources",
+            "audit_hooks",
+        ] {
+            assert!(schema["properties"].get(field).is_some(), "{field}");
+        }
+        let schema = EnterprisePolicy::json_schema();
+        assert!(schema["properties"].get("always_blocked_modules").is_some());
+    }
+
+    #[test]
+    fn test_policy_for_tenant() {
+        let mut manager = PolicyManager::new();
+        assert_eq!(manager.get_policy_for("anyone").unwrap().name, "Balanced");
+
+        manager.set_resolver(
+            TenantPolicyMap::new()
+                .tenant(
+                    "analytics",
+                    TenantPolicy::new("data_science").with_overrides(serde_json::json!({
+                        "network": "localhost_only",
+                        "resources": { "max_memory_mb": 8192 }
+                    })),
+                )
+                .tenant("typo", TenantPolicy::new("nonexistent"))
+                .default_policy(TenantPolicy::new("enterprise")),
+        );
+
+        let analytics = manager.get_policy_for("analytics").unwrap();
+        assert_eq!(analytics.network, NetworkPolicy::LocalhostOnly);
+        assert_eq!(analytics.resources.max_memory_mb, 8192);
+        assert_eq!(
+            analytics.resources.max_cpu_seconds,
+            SandboxPolicy::data_science().resources.max_cpu_seconds
+        );
+        assert_eq!(manager.get_policy_for("guest").unwrap().name, "Enterprise");
+        assert!(manager.get_policy_for("typo").is_err());
+
+        // Enterprise constraints still cap tenant overrides
+        manager.set_enterprise_policy(EnterprisePolicy {
+            max_allowed_resources: Some(ResourceLimitsPolicy::default()),
+            ..Default::default()
+        });
+        let analytics = manager.get_policy_for("analytics").unwrap();
+        assert_eq!(analytics.resources.max_memory_mb, 2048);
+    }
+
+    #[test]
+    fn test_package_install_policy() {
+        let policy = PackageInstallPolicy {
+            allowed_packages: Some(["PyYAML".to_string(), "requests".to_string()].into()),
+            forbid_direct_references: true,
+            ..Default::default()
+        };
+        assert!(policy.check_target("pyyaml>=6.0").is_ok());
+        assert!(policy
+            .check_target("requests[socks] ; python_version > '3.8'")
+            .is_ok());
+        for refused in [
+            "paramiko",
+            "git+https://github.com/psf/requests",
+            "requests @ https://example.com/requests.whl",
+            "./requests",
+            "requests-2.31.0-py3-none-any.whl",
+            "--index-url=https://example.com/simple",
+        ] {
+            assert!(policy.check_target(refused).is_err(), "{}", refused);
+        }
+        let requirements = "# pinned\n\
+                            requests==2.31.0 \\\n    --hash=sha256:abc\n\
+                            pyyaml==6.0.1  # config\n";
+        assert!(policy.check_requirements(requirements).is_ok());
+        assert!(policy
+            .check_requirements("requests\n-r other.txt\n")
+            .is_err());
+        assert!(PackageInstallPolicy::default()
+            .check_target("git+https://example.com/x")
+            .is_ok());
+    }
 }
@@ -0,0 +1,476 @@
+//! Enterprise policy fetched from an HTTPS URL
+//!
+//! Centrally managed fleets point `RZN_ENTERPRISE_POLICY_URL` at a policy
+//! file instead of pushing it to every machine. Responses are cached on disk
+//! with their `ETag`, so unchanged policies cost a `304`, and the last good
+//! copy is used when the server can't be reached.
+//!
+//! Fetching is library-only: hosts call
+//! [`PolicyManager::fetch_enterprise_policy_from_env`]. The
+//! `rzn-python-worker` binary doesn't read the URL and only loads the file
+//! named by `RZN_ENTERPRISE_POLICY_FILE`.
+//!
+//! When [`PolicyManager::require_signed_enterprise_policy`] is set, the
+//! detached signature is fetched from `<url>.sig` and checked against the
+//! downloaded bytes. The cached copy is checked again every time it's used,
+//! since the cache lives on a filesystem end users control.
+
+use crate::policy::{parse_policy_file, EnterprisePolicy, PolicyFileFormat, PolicyManager};
+use reqwest::{header, Client, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Environment variable holding the enterprise policy URL
+pub const ENTERPRISE_POLICY_URL_ENV: &str = "RZN_ENTERPRISE_POLICY_URL";
+
+/// Where and how to fetch the enterprise policy
+#[derive(Debug, Clone)]
+pub struct RemotePolicyConfig {
+    /// Policy URL; the format is picked by its extension, JSON if it has none
+    pub url: String,
+    /// Directory for the cached copy, created private to the current user
+    pub cache_dir: PathBuf,
+    /// Time allowed for each request
+    pub timeout: Duration,
+    /// Largest policy or signature accepted
+    pub max_bytes: usize,
+    /// Allow plain `http://` URLs. Only for local development.
+    pub allow_http: bool,
+}
+
+impl RemotePolicyConfig {
+    /// Fetch from `url` with the default cache directory and timeout
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            cache_dir: default_cache_dir(),
+            timeout: Duration::from_secs(10),
+            max_bytes: 1024 * 1024,
+            allow_http: false,
+        }
+    }
+
+    /// Config for the URL in [`ENTERPRISE_POLICY_URL_ENV`], if set
+    pub fn from_env() -> Option<Self> {
+        std::env::var(ENTERPRISE_POLICY_URL_ENV)
+            .ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .map(Self::new)
+    }
+
+    /// Use a different cache directory
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+}
+
+/// `$RZN_APP_BASE_DIR/policy_cache`, else `~/.rzn/policy_cache`, else a
+/// directory under the temp dir named for the current user
+fn default_cache_dir() -> PathBuf {
+    let var = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    var("RZN_APP_BASE_DIR")
+        .map(|dir| dir.join("policy_cache"))
+        .or_else(|| var("HOME").map(|home| home.join(".rzn").join("policy_cache")))
+        .unwrap_or_else(|| {
+            #[cfg(unix)]
+            let user = unsafe { libc::geteuid() }.to_string();
+            #[cfg(not(unix))]
+            let user = std::env::var("USERNAME").unwrap_or_default();
+            std::env::temp_dir().join(format!("pysandbox-policy-cache-{}", user))
+        })
+}
+
+/// Where the loaded enterprise policy came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyFetchOutcome {
+    /// Downloaded and cached
+    Fetched,
+    /// The server answered `304`; the cached copy is current
+    NotModified,
+    /// The fetch failed and the last cached copy was used
+    Stale { error: String },
+}
+
+impl PolicyManager {
+    /// Fetch the enterprise policy from the URL in
+    /// [`ENTERPRISE_POLICY_URL_ENV`]; `Ok(None)` if it isn't set
+    pub async fn fetch_enterprise_policy_from_env(
+        &mut self,
+    ) -> Result<Option<PolicyFetchOutcome>, String> {
+        match RemotePolicyConfig::from_env() {
+            Some(config) => self.fetch_enterprise_policy(&config).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch the enterprise policy from `config.url` and apply it
+    ///
+    /// On any failure the cached copy is used instead, if it still verifies;
+    /// without one the error is returned and the current policy is kept.
+    pub async fn fetch_enterprise_policy(
+        &mut self,
+        config: &RemotePolicyConfig,
+    ) -> Result<PolicyFetchOutcome, String> {
+        let cache = PolicyCache::new(config);
+        let cached = cache.load();
+
+        let (policy, outcome) = match self.fetch(config, &cache, cached.as_ref()).await {
+            Ok(fetched) => fetched,
+            Err(error) => {
+                let Some(cached) = cached else {
+                    return Err(error);
+                };
+                warn!(
+                    "Enterprise policy fetch from {} failed, using cached copy: {}",
+                    config.url, error
+                );
+                (
+                    self.decode(config, &cached)?,
+                    PolicyFetchOutcome::Stale { error },
+                )
+            }
+        };
+        self.set_enterprise_policy(policy);
+        Ok(outcome)
+    }
+
+    async fn fetch(
+        &self,
+        config: &RemotePolicyConfig,
+        cache: &PolicyCache,
+        cached: Option<&CachedPolicy>,
+    ) -> Result<(EnterprisePolicy, PolicyFetchOutcome), String> {
+        let url = Url::parse(&config.url)
+            .map_err(|e| format!("Invalid enterprise policy URL {}: {}", config.url, e))?;
+        if url.scheme() != "https" && !config.allow_http {
+            return Err(format!(
+                "Enterprise policy URL must use https: {}",
+                config.url
+            ));
+        }
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        let mut request = client.get(url.clone());
+        if let Some(etag) = cached.and_then(|c| c.etag.as_deref()) {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                let policy = self.decode(config, cached)?;
+                return Ok((policy, PolicyFetchOutcome::NotModified));
+            }
+        }
+        if !response.status().is_success() {
+            return Err(format!("{} returned {}", url, response.status()));
+        }
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = read_body(response, config.max_bytes).await?;
+
+        let signature = match self.enterprise_policy_key() {
+            Some(_) => {
+                let mut sig_url = url.clone();
+                sig_url.set_path(&format!("{}.sig", url.path()));
+                let response = client
+                    .get(sig_url.clone())
+                    .send()
+                    .await
+                    .map_err(|e| format!("Request to {} failed: {}", sig_url, e))?;
+                if !response.status().is_success() {
+                    return Err(format!("{} returned {}", sig_url, response.status()));
+                }
+                let signature = read_body(response, config.max_bytes).await?;
+                Some(String::from_utf8_lossy(&signature).into_owned())
+            }
+            None => None,
+        };
+
+        let fetched = CachedPolicy {
+            body,
+            etag,
+            signature,
+        };
+        let policy = self.decode(config, &fetched)?;
+        if let Err(e) = cache.store(&fetched) {
+            warn!("Failed to cache enterprise policy: {}", e);
+        }
+        info!("Fetched enterprise policy from {}", url);
+        Ok((policy, PolicyFetchOutcome::Fetched))
+    }
+
+    /// Check the signature, if one is required, and parse
+    fn decode(
+        &self,
+        config: &RemotePolicyConfig,
+        policy: &CachedPolicy,
+    ) -> Result<EnterprisePolicy, String> {
+        if let Some(key) = self.enterprise_policy_key() {
+            let signature = policy.signature.as_deref().ok_or_else(|| {
+                format!("Refusing unsigned enterprise policy from {}", config.url)
+            })?;
+            crate::signing::verify(key, &policy.body, signature).map_err(|e| {
+                format!(
+                    "Refusing enterprise policy from {}: signature check failed: {}",
+                    config.url, e
+                )
+            })?;
+        }
+        let path = Url::parse(&config.url)
+            .ok()
+            .map(|url| PathBuf::from(url.path()))
+            .filter(|path| PolicyFileFormat::from_path(path).is_some())
+            .unwrap_or_else(|| PathBuf::from("policy.json"));
+        parse_policy_file(&path, &policy.body, "enterprise policy")
+    }
+}
+
+async fn read_body(mut response: reqwest::Response, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(format!("Response exceeded {} bytes", max_bytes));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Policy bytes as downloaded, with their `ETag` and signature
+struct CachedPolicy {
+    body: Vec<u8>,
+    etag: Option<String>,
+    signature: Option<String>,
+}
+
+/// On-disk copy of the last good policy for one URL
+struct PolicyCache {
+    base: PathBuf,
+}
+
+impl PolicyCache {
+    fn new(config: &RemotePolicyConfig) -> Self {
+        let digest: String = Sha256::digest(config.url.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Self {
+            base: config.cache_dir.join(format!("enterprise-{}", digest)),
+        }
+    }
+
+    fn dir(&self) -> &Path {
+        self.base.parent().unwrap_or(Path::new("."))
+    }
+
+    fn path(&self, extension: &str) -> PathBuf {
+        self.base.with_extension(extension)
+    }
+
+    fn load(&self) -> Option<CachedPolicy> {
+        if let Err(e) = check_private_dir(self.dir()) {
+            warn!("Ignoring enterprise policy cache: {}", e);
+            return None;
+        }
+        let body = std::fs::read(self.path("body")).ok()?;
+        let read = |ext| std::fs::read_to_string(self.path(ext)).ok();
+        Some(CachedPolicy {
+            body,
+            etag: read("etag"),
+            signature: read("sig"),
+        })
+    }
+
+    fn store(&self, policy: &CachedPolicy) -> std::io::Result<()> {
+        create_private_dir(self.dir())?;
+        write_atomic(&self.path("body"), &policy.body)?;
+        for (ext, value) in [("etag", &policy.etag), ("sig", &policy.signature)] {
+            match value {
+                Some(value) => write_atomic(&self.path(ext), value.as_bytes())?,
+                None => match std::fs::remove_file(self.path(ext)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Create `dir` readable and writable by the current user only
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    match builder.create(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    check_private_dir(dir)
+}
+
+/// Refuse a cache directory someone else owns or can write to, since its
+/// contents decide the policy when no signing key is configured
+fn check_private_dir(dir: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let meta = std::fs::symlink_metadata(dir)?;
+        let uid = unsafe { libc::geteuid() };
+        if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o022 != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is not a directory only this user can write",
+                    dir.display()
+                ),
+            ));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(contents)?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve `body` with ETag `"v1"`, answering `304` to a matching
+    /// `If-None-Match`, and `signature` at `/policy.json.sig`
+    async fn serve(
+        body: String,
+        signature: String,
+    ) -> (String, Arc<AtomicUsize>, tokio::task::JoinHandle<()>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/policy.json", listener.local_addr().unwrap());
+        let not_modified = Arc::new(AtomicUsize::new(0));
+        let counter = not_modified.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0u8; 4096];
+                let n = stream.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..n]).to_ascii_lowercase();
+                let response = if request.starts_with("get /policy.json.sig ") {
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        signature.len(),
+                        signature
+                    )
+                } else if request.contains("if-none-match: \"v1\"") {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    "HTTP/1.1 304 Not Modified\r\nconnection: close\r\n\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, not_modified, server)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_caches_and_falls_back() {
+        let body = serde_json::to_string(&EnterprisePolicy {
+            minimum_security_level: Some(4),
+            ..Default::default()
+        })
+        .unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[5u8; 32]);
+        let signature = crate::signing::sign(&key, body.as_bytes());
+        let (url, not_modified, server) = serve(body, signature).await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let mut config = RemotePolicyConfig::new(&url).with_cache_dir(cache.path().to_path_buf());
+        assert!(PolicyManager::new()
+            .fetch_enterprise_policy(&config)
+            .await
+            .unwrap_err()
+            .contains("https"));
+        config.allow_http = true;
+
+        let mut manager = PolicyManager::new();
+        manager.require_signed_enterprise_policy(key.verifying_key());
+        let outcome = manager.fetch_enterprise_policy(&config).await.unwrap();
+        assert_eq!(outcome, PolicyFetchOutcome::Fetched);
+        assert!(manager.get_effective_policy().is_ok());
+
+        let outcome = manager.fetch_enterprise_policy(&config).await.unwrap();
+        assert_eq!(outcome, PolicyFetchOutcome::NotModified);
+        assert_eq!(not_modified.load(Ordering::SeqCst), 1);
+
+        // Unreachable server: the cached copy is used
+        server.abort();
+        let _ = server.await;
+        let outcome = manager.fetch_enterprise_policy(&config).await.unwrap();
+        assert!(matches!(outcome, PolicyFetchOutcome::Stale { .. }));
+
+        // A tampered cache is refused
+        std::fs::write(PolicyCache::new(&config).path("body"), "{}").unwrap();
+        let err = manager.fetch_enterprise_policy(&config).await.unwrap_err();
+        assert!(err.contains("signature check failed"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_dir_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("cache");
+        create_private_dir(&dir).unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // A cache others can write to is neither used nor written
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(create_private_dir(&dir).is_err());
+        let config = RemotePolicyConfig::new("https://example.com/policy.json").with_cache_dir(dir);
+        let cache = PolicyCache::new(&config);
+        std::fs::write(cache.path("body"), "{}").unwrap();
+        assert!(cache.load().is_none());
+    }
+}
@@ -0,0 +1,699 @@
+use crate::{
+    config::{ImportPolicy, ResourceLimits},
+    engine::{
+        Diagnostic, EngineCapabilities, ExecutionMetrics, ExecutionOptions, ExecutionResult,
+        PythonEngine,
+    },
+    errors::{execution_error, Result, SandboxError},
+    sandboxed::{stage_input_dir, SandboxedPythonEngine},
+    supervisor::{
+        apply_file_limits, cancelled, capture_class, interrupt_then_kill, SetupClock,
+        DENIAL_MARKER, RESULT_SERIALIZER,
+    },
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as b64, Engine};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tracing::{debug, info, warn};
+
+/// Configuration for the warm interpreter pool
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Number of pre-warmed workers kept ready
+    pub size: usize,
+    /// Recycle a worker after this many executions
+    pub max_runs_per_worker: usize,
+    /// Modules imported while warming up (e.g. `numpy`), before guards are installed
+    pub warmup_imports: Vec<String>,
+    /// Resource limits applied to each worker
+    pub limits: ResourceLimits,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 2,
+            max_runs_per_worker: 50,
+            warmup_imports: Vec::new(),
+            limits: ResourceLimits::default(),
+        }
+    }
+}
+
+/// Guard settings a worker was started with; a change means recycling
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct WorkerPolicy {
+    import_policy: ImportPolicy,
+    network_allowlist: Option<Vec<String>>,
+    block_native_extensions: bool,
+}
+
+impl WorkerPolicy {
+    pub(crate) fn from_options(options: &ExecutionOptions) -> Self {
+        Self {
+            import_policy: options.import_policy.clone(),
+            network_allowlist: options.network_allowlist.clone(),
+            block_native_extensions: options.block_native_extensions,
+        }
+    }
+}
+
+/// A long-lived guarded interpreter serving one execution at a time
+pub(crate) struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    ready: bool,
+    runs: usize,
+    /// Set for pool workers so they show up in [`crate::metrics`]
+    pooled: Option<crate::metrics::GaugeGuard>,
+}
+
+impl Worker {
+    /// Start a worker; it warms up in the background until `wait_ready`
+    pub(crate) fn spawn(
+        python_path: &Path,
+        policy: &WorkerPolicy,
+        warmup_imports: &[String],
+        limits: &ResourceLimits,
+    ) -> Result<Self> {
+        let script = worker_script(policy, warmup_imports);
+
+        let mut cmd = Command::new(python_path);
+        cmd.arg("-c")
+            .arg(&script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .env("PYTHONIOENCODING", "utf-8")
+            .env("OMP_NUM_THREADS", limits.max_threads.to_string())
+            .env("OPENBLAS_NUM_THREADS", limits.max_threads.to_string())
+            .env("MKL_NUM_THREADS", limits.max_threads.to_string())
+            .kill_on_drop(true);
+        apply_worker_limits(&mut cmd, limits);
+        apply_file_limits(&mut cmd, limits.max_open_files, None);
+
+        let mut child = cmd.spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| SandboxError::InternalError("Worker has no stdin".to_string()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| SandboxError::InternalError("Worker has no stdout".to_string()))?;
+        debug!("[POOL] Spawned worker {:?}", child.id());
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            ready: false,
+            runs: 0,
+            pooled: None,
+        })
+    }
+
+    /// Wait for the warm-up to finish
+    pub(crate) async fn wait_ready(&mut self) -> Result<()> {
+        if self.ready {
+            return Ok(());
+        }
+        match self.stdout.next_line().await? {
+            Some(line) if line == "READY" => {
+                self.ready = true;
+                Ok(())
+            }
+            _ => Err(SandboxError::InternalError(
+                "Pool worker failed to start".to_string(),
+            )),
+        }
+    }
+
+    /// Send one request and read its response line
+    pub(crate) async fn request(&mut self, request: &serde_json::Value) -> Result<Option<String>> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.stdin.write_all(line.as_bytes()).await?;
+        self.stdin.flush().await?;
+        Ok(self.stdout.next_line().await?)
+    }
+
+    /// Map a worker that exited mid-execution to an error
+    pub(crate) async fn exit_error(&mut self) -> SandboxError {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Ok(status) = self.child.wait().await {
+                match status.signal() {
+                    Some(libc::SIGXCPU) => return SandboxError::Timeout,
+                    Some(libc::SIGKILL) => {
+                        return SandboxError::MemoryLimitExceeded { peak_mb: None }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = self.child.wait().await;
+        SandboxError::RuntimeError("Worker exited during execution".to_string())
+    }
+
+    /// Interrupt the worker, then kill it and anything it spawned after `grace`
+    pub(crate) async fn stop(mut self, grace: Duration) {
+        interrupt_then_kill(&mut self.child, grace).await;
+    }
+
+    /// Kill the worker and anything it spawned
+    pub(crate) fn kill(mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGKILL);
+            }
+        }
+        let _ = self.child.start_kill();
+    }
+}
+
+/// Python engine that dispatches executions to pre-warmed interpreters
+///
+/// Workers start with the import and network guards already installed, so an
+/// execution only pays for running the code itself. Each execution gets a
+/// fresh namespace, but imported modules persist inside a worker until it is
+/// recycled (after `max_runs_per_worker` runs or when the policy changes).
+pub struct PooledPythonEngine {
+    python_path: PathBuf,
+    config: PoolConfig,
+    policy: Option<WorkerPolicy>,
+    idle: Vec<Worker>,
+}
+
+impl PooledPythonEngine {
+    /// Create a pool using Python from PATH
+    pub fn new(config: PoolConfig) -> Result<Self> {
+        let python_path = which::which("python3")
+            .or_else(|_| which::which("python"))
+            .map_err(|_| SandboxError::PythonNotFound)?;
+        Self::with_python_path(python_path, config)
+    }
+
+    /// Create a pool with an explicit Python path (for bundled Python)
+    pub fn with_python_path(python_path: PathBuf, config: PoolConfig) -> Result<Self> {
+        if !python_path.exists() {
+            return Err(SandboxError::PythonNotFound);
+        }
+        Ok(Self {
+            python_path,
+            config,
+            policy: None,
+            idle: Vec::new(),
+        })
+    }
+
+    /// Start workers for a policy ahead of the first execution
+    pub fn prewarm(&mut self, options: &ExecutionOptions) -> Result<()> {
+        self.set_policy(WorkerPolicy::from_options(options));
+        self.replenish()
+    }
+
+    /// Number of idle workers
+    pub fn idle_workers(&self) -> usize {
+        self.idle.len()
+    }
+
+    /// Switch policy, discarding workers started with a different one
+    fn set_policy(&mut self, policy: WorkerPolicy) {
+        if self.policy.as_ref() == Some(&policy) {
+            return;
+        }
+        if !self.idle.is_empty() {
+            info!(
+                "[POOL] Policy changed, recycling {} workers",
+                self.idle.len()
+            );
+        }
+        for worker in self.idle.drain(..) {
+            worker.kill();
+        }
+        self.policy = Some(policy);
+    }
+
+    /// Top the pool up to its configured size
+    ///
+    /// Spawning doesn't wait for warm-up, so new workers warm in the background.
+    fn replenish(&mut self) -> Result<()> {
+        while self.idle.len() < self.config.size.max(1) {
+            let worker = self.spawn_worker()?;
+            self.idle.push(worker);
+        }
+        Ok(())
+    }
+
+    fn spawn_worker(&self) -> Result<Worker> {
+        let policy = self
+            .policy
+            .as_ref()
+            .ok_or_else(|| SandboxError::InternalError("Pool has no policy".to_string()))?;
+        let mut worker = Worker::spawn(
+            &self.python_path,
+            policy,
+            &self.config.warmup_imports,
+            &self.config.limits,
+        )?;
+        worker.pooled = Some(crate::metrics::registry().track_pooled_interpreter());
+        Ok(worker)
+    }
+}
+
+/// Apply memory limits to a worker; CPU is limited per run inside the worker
+#[cfg(unix)]
+fn apply_worker_limits(cmd: &mut Command, limits: &ResourceLimits) {
+    #[cfg(not(target_os = "macos"))]
+    let memory_bytes = limits.memory_mb * 1024 * 1024;
+    #[cfg(target_os = "macos")]
+    let _ = limits;
+
+    unsafe {
+        cmd.pre_exec(move || {
+            // Create new process group for easier cleanup
+            libc::setpgid(0, 0);
+
+            // macOS doesn't support RLIMIT_AS properly, skip it
+            #[cfg(not(target_os = "macos"))]
+            {
+                let rlimit = libc::rlimit {
+                    rlim_cur: memory_bytes as libc::rlim_t,
+                    rlim_max: memory_bytes as libc::rlim_t,
+                };
+                if libc::setrlimit(libc::RLIMIT_AS, &rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_worker_limits(_cmd: &mut Command, _limits: &ResourceLimits) {
+    // Rely on timeout only
+}
+
+/// Python source for a pool worker
+///
+/// Requests and responses are JSON lines on a private copy of the original
+/// stdout; fd 1 itself is pointed at stderr so stray writes from user code or
+/// C extensions can't corrupt the protocol.
+fn worker_script(policy: &WorkerPolicy, warmup_imports: &[String]) -> String {
+    let warmup = serde_json::to_string(warmup_imports).unwrap_or_else(|_| "[]".to_string());
+    format!(
+        r#"
+import ast
+import base64
+import builtins as _rzn_builtins
+import codeop
+import json
+import os
+import sys
+from io import BytesIO, StringIO, TextIOWrapper
+
+try:
+    import resource as _rzn_resource
+except ImportError:
+    _rzn_resource = None
+
+_rzn_proto_out = os.fdopen(os.dup(1), "w", encoding="utf-8")
+os.dup2(2, 1)
+_rzn_proto_in = sys.stdin
+sys.stdin = _rzn_null_stdin = open(os.devnull)
+
+for _rzn_module in {warmup}:
+    try:
+        __import__(_rzn_module)
+    except Exception:
+        pass
+
+# Result serialization and denial markers, defined before the import guard
+{result_serializer}{denial_marker}
+
+# Security setup
+{native_extension_control}{import_control}
+
+# Network setup
+{network_control}
+
+{capture_class}
+def _rzn_limit_cpu(seconds):
+    if _rzn_resource is None:
+        return
+    usage = _rzn_resource.getrusage(_rzn_resource.RUSAGE_SELF)
+    _soft, hard = _rzn_resource.getrlimit(_rzn_resource.RLIMIT_CPU)
+    soft = int(usage.ru_utime + usage.ru_stime) + seconds + 1
+    if hard != _rzn_resource.RLIM_INFINITY:
+        soft = min(soft, hard)
+    _rzn_resource.setrlimit(_rzn_resource.RLIMIT_CPU, (soft, hard))
+
+_rzn_session_namespace = {{"__name__": "__main__", "__builtins__": _rzn_builtins}}
+
+def _rzn_incomplete(code):
+    # A buffer ending inside an indented block waits for a blank line, like
+    # the interactive console
+    try:
+        if codeop.compile_command(code, "<sandbox>", "exec") is None:
+            return True
+    except (SyntaxError, ValueError, OverflowError):
+        return False
+    lines = code.split("\n")
+    return len(lines) > 1 and lines[-1][:1] in (" ", "\t")
+
+def _rzn_exec(code, namespace, echo):
+    tree = ast.parse(code, "<sandbox>", "exec")
+    last = None
+    if echo and tree.body and isinstance(tree.body[-1], ast.Expr):
+        last = ast.Expression(tree.body.pop().value)
+    exec(compile(tree, "<sandbox>", "exec"), namespace)
+    if last is not None:
+        value = eval(compile(last, "<sandbox>", "eval"), namespace)
+        if value is not None:
+            namespace["_"] = value
+            return repr(value)
+    return None
+
+def _rzn_run(request):
+    if request.get("check_complete") and _rzn_incomplete(request["code"]):
+        return {{"incomplete": True}}
+    _rzn_limit_cpu(request["cpu_seconds"])
+    saved_env = {{k: os.environ.get(k) for k in request["env"]}}
+    os.environ.update(request["env"])
+
+    max_output_bytes = request.get("max_output_bytes", {default_max_output})
+    stdout = _RznCapture("stdout", max_output_bytes)
+    stderr = _RznCapture("stderr", max_output_bytes)
+    sys.stdout, sys.stderr = stdout, stderr
+    if request.get("keep_namespace"):
+        namespace = _rzn_session_namespace
+        namespace.pop("result", None)
+    else:
+        namespace = {{"__name__": "__main__", "__builtins__": _rzn_builtins}}
+    namespace["inputs"] = request["inputs"]
+    namespace["INPUT_DIR"] = request.get("input_dir")
+    if request.get("stdin") is not None:
+        sys.stdin = TextIOWrapper(BytesIO(base64.b64decode(request["stdin"])), encoding="utf-8")
+    error = None
+    traceback = None
+    denial = None
+    echo = None
+    try:
+        echo = _rzn_exec(request["code"], namespace, request.get("echo", False))
+    except SystemExit as e:
+        if e.code not in (None, 0):
+            error = f"SystemExit: {{e.code}}"
+    except Exception as e:
+        error = f"{{type(e).__name__}}: {{e}}"
+        traceback = _rzn_traceback.format_exc()
+        denial = _rzn_denial_of(e)
+    finally:
+        sys.stdout, sys.stderr = sys.__stdout__, sys.__stderr__
+        sys.stdin = _rzn_null_stdin
+        for k, v in saved_env.items():
+            if v is None:
+                os.environ.pop(k, None)
+            else:
+                os.environ[k] = v
+
+    try:
+        result = _rzn_result_value(namespace.get("result"))
+        json.dumps(result)
+    except Exception as e:
+        result = {{"type": "unserializable", "repr": str(e)}}
+    response = {{
+        "stdout": stdout.getvalue() or None,
+        "stderr": stderr.getvalue() or None,
+        "result": result,
+        "error": error,
+        "echo": echo,
+    }}
+    if traceback:
+        response["traceback"] = traceback
+    if denial:
+        response["denial"] = denial
+    if stdout.truncated:
+        response["stdout_truncated"] = True
+    if stderr.truncated:
+        response["stderr_truncated"] = True
+    return response
+
+_rzn_proto_out.write("READY\n")
+_rzn_proto_out.flush()
+for _rzn_line in _rzn_proto_in:
+    _rzn_proto_out.write(json.dumps(_rzn_run(json.loads(_rzn_line))) + "\n")
+    _rzn_proto_out.flush()
+"#,
+        warmup = warmup,
+        capture_class = capture_class(false),
+        result_serializer = RESULT_SERIALIZER,
+        denial_marker = DENIAL_MARKER,
+        default_max_output = ResourceLimits::default().max_output_bytes,
+        native_extension_control = SandboxedPythonEngine::generate_native_extension_control(
+            policy.block_native_extensions,
+            &policy.import_policy,
+        ),
+        import_control = SandboxedPythonEngine::generate_import_control(&policy.import_policy),
+        network_control = SandboxedPythonEngine::generate_network_control(
+            policy.network_allowlist.as_deref(),
+            None
+        ),
+    )
+}
+
+#[async_trait]
+impl PythonEngine for PooledPythonEngine {
+    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        // Syntax errors are reported by the worker
+        Ok(Vec::new())
+    }
+
+    async fn execute(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let setup = SetupClock::start(options);
+        self.set_policy(WorkerPolicy::from_options(options));
+        self.replenish()?;
+        let mut worker = self.idle.remove(0);
+
+        let input_dir = stage_input_dir(&options.input_files)?;
+        // Workers read requests from stdin, so the data travels in the request
+        let stdin = match &options.stdin {
+            Some(source) => Some(b64.encode(source.read_all().await?)),
+            None => None,
+        };
+        let request = serde_json::json!({
+            "code": code,
+            "inputs": inputs,
+            "input_dir": input_dir.as_ref().map(|dir| dir.path()),
+            "stdin": stdin,
+            "env": options.env_vars,
+            "cpu_seconds": self.config.limits.cpu_seconds.min(options.cpu_seconds),
+            "max_output_bytes": self.config.limits.max_output_bytes,
+        });
+
+        // Warm-up counts against the setup budget, the request against `timeout`
+        let run = async {
+            match setup.within(worker.wait_ready()).await {
+                Ok(()) => {}
+                Err(SandboxError::Timeout) => return Err(SandboxError::Timeout),
+                Err(e) => return Ok(Err(e)),
+            }
+            let setup = setup.finish()?;
+            let started = std::time::Instant::now();
+            let response = tokio::time::timeout(options.timeout, worker.request(&request))
+                .await
+                .map_err(|_| SandboxError::Timeout)?;
+            Ok(response.map(|line| (line, setup, started.elapsed())))
+        };
+        let response = tokio::select! {
+            response = run => response,
+            _ = cancelled(options.cancellation.as_ref()) => Err(SandboxError::Cancelled),
+        };
+
+        let (line, setup, wall_time) = match response {
+            Ok(Ok((Some(line), setup, wall_time))) => (line, setup, wall_time),
+            Ok(Ok((None, _, _))) => {
+                let error = worker.exit_error().await;
+                worker.kill();
+                self.replenish()?;
+                return Err(error);
+            }
+            Ok(Err(e)) => {
+                worker.kill();
+                self.replenish()?;
+                return Err(e);
+            }
+            Err(error) => {
+                warn!("[POOL] {}, stopping worker", error);
+                worker.stop(options.kill_grace_period).await;
+                self.replenish()?;
+                return Err(error);
+            }
+        };
+
+        worker.runs += 1;
+        if worker.runs >= self.config.max_runs_per_worker {
+            debug!("[POOL] Recycling worker after {} runs", worker.runs);
+            worker.kill();
+        } else {
+            self.idle.push(worker);
+        }
+        self.replenish()?;
+
+        let parsed: serde_json::Value = serde_json::from_str(&line)?;
+        if let Some(error) = execution_error(&parsed, code) {
+            return Err(error);
+        }
+        let mut output = ExecutionResult::from_value(parsed)?;
+        output.metrics = Some(ExecutionMetrics {
+            wall_time_ms: wall_time.as_millis() as u64,
+            setup_ms: Some(setup.as_millis() as u64),
+            ..Default::default()
+        });
+        Ok(output)
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: "Native Python (Warm Pool)".to_string(),
+            numpy: true, // Assumes user has it installed
+            matplotlib: true,
+            pandas: true,
+            max_memory_mb: self.config.limits.memory_mb,
+            max_cpu_seconds: self.config.limits.cpu_seconds,
+            security_level: 5, // Same guardrails as the native engine
+        }
+    }
+
+    /// Add `modules` to the warm-up imports and wait for the idle workers to
+    /// import them, for at most the CPU limit
+    ///
+    /// Before the first execution, workers are started with the guards of
+    /// the default options.
+    async fn warmup(&mut self, modules: &[&str]) -> Result<()> {
+        let mut added = false;
+        for module in modules {
+            if !self.config.warmup_imports.iter().any(|m| m == module) {
+                self.config.warmup_imports.push(module.to_string());
+                added = true;
+            }
+        }
+        if added {
+            for worker in self.idle.drain(..) {
+                worker.kill();
+            }
+        }
+        if self.policy.is_none() {
+            self.policy = Some(WorkerPolicy::from_options(&ExecutionOptions::default()));
+        }
+        self.replenish()?;
+
+        let timeout = Duration::from_secs(self.config.limits.cpu_seconds);
+        let ready = tokio::time::timeout(timeout, async {
+            for worker in &mut self.idle {
+                worker.wait_ready().await?;
+            }
+            Ok::<_, SandboxError>(())
+        })
+        .await;
+        match ready {
+            Ok(Ok(())) => Ok(()),
+            failed => {
+                // Drop the stuck or dead workers; the next execution respawns them
+                for worker in self.idle.drain(..) {
+                    worker.kill();
+                }
+                failed.map_err(|_| SandboxError::Timeout)?
+            }
+        }
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        if !self.python_path.is_file() {
+            return Err(SandboxError::PythonNotFound);
+        }
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        for worker in self.idle.drain(..) {
+            worker.kill();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_script_installs_guards_after_warmup() {
+        let policy = WorkerPolicy::from_options(&ExecutionOptions {
+            network_allowlist: Some(vec!["pypi.org".to_string()]),
+            ..Default::default()
+        });
+        let script = worker_script(&policy, &["numpy".to_string()]);
+
+        let warmup = script.find("for _rzn_module in [\"numpy\"]").unwrap();
+        let guard = script.find("builtins.__import__ = safe_import").unwrap();
+        assert!(warmup < guard);
+        assert!(script.contains("sys.meta_path.insert(0, _RznImportPolicyFinder)"));
+        assert!(script.contains("pypi.org"));
+        assert!(script.contains("READY"));
+        assert!(!script.contains("_rzn_guard_native_extensions"));
+    }
+
+    #[test]
+    fn test_worker_script_blocks_native_extensions_before_import_guard() {
+        let policy = WorkerPolicy::from_options(&ExecutionOptions {
+            import_policy: ImportPolicy::data_science_whitelist(),
+            block_native_extensions: true,
+            ..Default::default()
+        });
+        let script = worker_script(&policy, &[]);
+
+        let extensions = script
+            .find("_imp.create_dynamic = guarded_create_dynamic")
+            .unwrap();
+        let guard = script.find("builtins.__import__ = safe_import").unwrap();
+        assert!(extensions < guard);
+        assert!(script.contains("'numpy'"));
+    }
+
+    #[tokio::test]
+    async fn test_warmup_readies_workers() {
+        let Ok(mut pool) = PooledPythonEngine::new(PoolConfig::default()) else {
+            return;
+        };
+        pool.warmup(&["json", "json"]).await.unwrap();
+        assert_eq!(pool.config.warmup_imports, vec!["json".to_string()]);
+        assert!(!pool.idle.is_empty() && pool.idle.iter().all(|w| w.ready));
+
+        let output = pool
+            .execute(
+                "import json\nresult = json.dumps([1])",
+                serde_json::json!({}),
+                &ExecutionOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.result_as::<String>().unwrap(), "[1]");
+        assert!(output.metrics.unwrap().setup_ms.is_some());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    config::ResourceLimits,
+    engine::{Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine},
+    errors::{execution_error, Result, SandboxError},
+};
+use async_trait::async_trait;
+use reqwest::{Certificate, Client, StatusCode};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::info;
+
+/// TLS settings for the remote endpoint
+#[derive(Debug, Clone, Default)]
+pub struct RemoteTlsConfig {
+    /// PEM-encoded CA certificate to trust in addition to the system roots
+    /// (for servers behind a private CA)
+    pub ca_certificate: Option<PathBuf>,
+    /// Trust only `ca_certificate`, not the built-in roots
+    pub ca_certificate_only: bool,
+    /// Skip certificate verification entirely. Only for local development.
+    pub accept_invalid_certs: bool,
+    /// Allow plain `http://` endpoints. Only for local development.
+    pub allow_http: bool,
+}
+
+/// Configuration for the remote engine
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    /// Execution endpoint, e.g. `https://sandbox.example.com/v1/execute`
+    pub endpoint: String,
+    /// Bearer token sent in the `Authorization` header
+    pub auth_token: Option<String>,
+    /// TLS settings
+    pub tls: RemoteTlsConfig,
+    /// Time allowed to establish the connection
+    pub connect_timeout: Duration,
+    /// Extra time on top of the execution timeout for the round trip
+    pub request_overhead: Duration,
+    /// Largest response body accepted from the server
+    pub max_response_bytes: usize,
+    /// Resource limits advertised by the server
+    pub limits: ResourceLimits,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            auth_token: None,
+            tls: RemoteTlsConfig::default(),
+            connect_timeout: Duration::from_secs(10),
+            request_overhead: Duration::from_secs(10),
+            max_response_bytes: 64 * 1024 * 1024,
+            limits: ResourceLimits::default(),
+        }
+    }
+}
+
+/// Python engine that offloads execution to a remote server over HTTPS
+///
+/// Each execution POSTs `{code, inputs, options}` as JSON to the endpoint. The
+/// server replies with the same structured result the local engines produce
+/// (`stdout`, `stderr`, `result`, `error`, ...). Timeouts are reported as HTTP
+/// 408 or 504.
+pub struct RemotePythonEngine {
+    config: RemoteConfig,
+    client: Client,
+}
+
+impl RemotePythonEngine {
+    /// Create a new remote engine
+    pub fn new(config: RemoteConfig) -> Result<Self> {
+        if !config.endpoint.starts_with("https://") && !config.tls.allow_http {
+            return Err(SandboxError::SecurityViolation(format!(
+                "Remote endpoint must use https: {}",
+                config.endpoint
+            )));
+        }
+
+        let mut builder = Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .danger_accept_invalid_certs(config.tls.accept_invalid_certs)
+            .tls_built_in_root_certs(!config.tls.ca_certificate_only);
+
+        if let Some(path) = &config.tls.ca_certificate {
+            let pem = std::fs::read(path)?;
+            let certificate = Certificate::from_pem(&pem).map_err(|e| {
+                SandboxError::InternalError(format!("Invalid CA certificate {:?}: {}", path, e))
+            })?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        let client = builder.build().map_err(|e| {
+            SandboxError::InternalError(format!("Failed to create HTTP client: {}", e))
+        })?;
+
+        Ok(Self { config, client })
+    }
+
+    /// Create for an endpoint with an optional bearer token
+    pub fn with_endpoint(endpoint: &str, auth_token: Option<String>) -> Result<Self> {
+        Self::new(RemoteConfig {
+            endpoint: endpoint.to_string(),
+            auth_token,
+            ..Default::default()
+        })
+    }
+
+    /// Read the response body in chunks, refusing bodies over the size limit
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(request_error)? {
+            if body.len() + chunk.len() > self.config.max_response_bytes {
+                return Err(SandboxError::InternalError(format!(
+                    "Remote response exceeded {} bytes",
+                    self.config.max_response_bytes
+                )));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+fn request_error(e: reqwest::Error) -> SandboxError {
+    if e.is_timeout() {
+        SandboxError::Timeout
+    } else {
+        SandboxError::InternalError(format!("Remote request failed: {}", e))
+    }
+}
+
+#[async_trait]
+impl PythonEngine for RemotePythonEngine {
+    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        // Syntax errors are reported by the remote interpreter
+        Ok(Vec::new())
+    }
+
+    async fn execute(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let payload = serde_json::json!({
+            "code": code,
+            "inputs": inputs,
+            "options": options.to_forwarded_value()?,
+        });
+
+        info!("[REMOTE] Executing on {}", self.config.endpoint);
+
+        let mut request = self
+            .client
+            .post(&self.config.endpoint)
+            .timeout(options.timeout + self.config.request_overhead)
+            .json(&payload);
+        if let Some(token) = &self.config.auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await.map_err(request_error)?;
+        let status = response.status();
+        let body = self.read_body(response).await?;
+
+        match status {
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => {
+                return Err(SandboxError::Timeout)
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(SandboxError::SecurityViolation(format!(
+                    "Remote engine rejected credentials ({})",
+                    status
+                )))
+            }
+            s if !s.is_success() => {
+                return Err(SandboxError::InternalError(format!(
+                    "Remote engine returned {}: {}",
+                    status,
+                    String::from_utf8_lossy(&body)
+                )))
+            }
+            _ => {}
+        }
+
+        let parsed: serde_json::Value = serde_json::from_slice(&body)?;
+        if let Some(error) = execution_error(&parsed, code) {
+            return Err(error);
+        }
+        ExecutionResult::from_value(parsed)
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: format!("Remote ({})", self.config.endpoint),
+            numpy: true, // Depends on the server
+            matplotlib: true,
+            pandas: true,
+            max_memory_mb: self.config.limits.memory_mb,
+            max_cpu_seconds: self.config.limits.cpu_seconds,
+            security_level: 9, // Code never runs on this machine
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_http_endpoint_is_rejected() {
+        let result = RemotePythonEngine::with_endpoint("http://example.com/execute", None);
+        assert!(matches!(result, Err(SandboxError::SecurityViolation(_))));
+
+        assert!(RemotePythonEngine::with_endpoint("https://example.com/execute", None).is_ok());
+    }
+}
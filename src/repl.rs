@@ -0,0 +1,171 @@
+//! Interactive REPL on top of sessions
+//!
+//! [`ReplHandle`] feeds code to a [`SessionHandle`] the way the interactive
+//! interpreter does: lines are buffered until they form a complete statement,
+//! the value of a trailing expression is echoed back (and bound to `_`), and
+//! each statement gets its own timeout. Errors raised by user code come back
+//! as part of the output rather than as `Err`, since a REPL keeps going.
+
+use crate::{errors::Result, session::SessionHandle};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Output of one complete REPL statement
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplResult {
+    /// Captured stdout
+    pub stdout: String,
+    /// Captured stderr
+    pub stderr: String,
+    /// `repr` of the trailing expression, if it evaluated to something other than `None`
+    pub value: Option<String>,
+    /// Exception raised by the statement, e.g. `NameError: name 'x' is not defined`
+    pub error: Option<String>,
+}
+
+/// Outcome of pushing a line into the REPL
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplOutput {
+    /// The buffer isn't a complete statement yet; push more lines
+    Incomplete,
+    /// The buffered statement ran
+    Complete(ReplResult),
+}
+
+/// Line-oriented interactive interpreter backed by a persistent session
+pub struct ReplHandle {
+    session: SessionHandle,
+    buffer: Vec<String>,
+    statement_timeout: Duration,
+}
+
+impl ReplHandle {
+    /// Wrap a session; statements time out after the session's own timeout
+    pub fn new(session: SessionHandle) -> Self {
+        let statement_timeout = session.options().timeout;
+        Self {
+            session,
+            buffer: Vec::new(),
+            statement_timeout,
+        }
+    }
+
+    /// Set the timeout applied to each statement
+    pub fn with_statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = timeout;
+        self
+    }
+
+    /// Change the timeout applied to each statement
+    pub fn set_statement_timeout(&mut self, timeout: Duration) {
+        self.statement_timeout = timeout;
+    }
+
+    /// Whether lines are buffered waiting for the rest of a statement
+    pub fn is_pending(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Discard buffered lines, e.g. on Ctrl-C in a UI
+    pub fn reset_buffer(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Push one line of input
+    ///
+    /// Compound statements finish with a blank line, as in the interactive
+    /// interpreter.
+    pub async fn push(&mut self, line: &str) -> Result<ReplOutput> {
+        if self.buffer.is_empty() && line.trim().is_empty() {
+            return Ok(ReplOutput::Complete(ReplResult::default()));
+        }
+        self.buffer.push(line.to_string());
+        let source = self.buffer.join("\n");
+
+        let response = self.run(&source, true).await;
+        if !matches!(response, Ok(ReplOutput::Incomplete)) {
+            self.buffer.clear();
+        }
+        response
+    }
+
+    /// Run a whole cell at once, echoing its trailing expression
+    ///
+    /// Unlike [`push`](Self::push) the cell is never treated as incomplete; a
+    /// truncated cell is reported as a `SyntaxError`.
+    pub async fn run_cell(&mut self, code: &str) -> Result<ReplResult> {
+        match self.run(code, false).await? {
+            ReplOutput::Complete(result) => Ok(result),
+            ReplOutput::Incomplete => Ok(ReplResult::default()),
+        }
+    }
+
+    /// The underlying session, e.g. to pass `inputs`
+    pub fn session(&mut self) -> &mut SessionHandle {
+        &mut self.session
+    }
+
+    /// Close the REPL and its interpreter
+    pub fn close(self) {
+        self.session.close();
+    }
+
+    async fn run(&mut self, code: &str, check_complete: bool) -> Result<ReplOutput> {
+        let request = serde_json::json!({
+            "code": code,
+            "inputs": {},
+            "echo": true,
+            "check_complete": check_complete,
+        });
+        let response = self.session.send(request, self.statement_timeout).await?;
+
+        if response["incomplete"].as_bool() == Some(true) {
+            return Ok(ReplOutput::Incomplete);
+        }
+        let text = |key: &str| response[key].as_str().map(str::to_string);
+        Ok(ReplOutput::Complete(ReplResult {
+            stdout: text("stdout").unwrap_or_default(),
+            stderr: text("stderr").unwrap_or_default(),
+            value: text("echo"),
+            error: text("error").filter(|e| !e.is_empty()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ResourceLimits;
+
+    #[tokio::test]
+    async fn test_repl_buffers_blocks_and_echoes() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let session = SessionHandle::start(&python, &ResourceLimits::default(), Default::default())
+            .await
+            .unwrap();
+        let mut repl = ReplHandle::new(session);
+
+        assert_eq!(
+            repl.push("def double(x):").await.unwrap(),
+            ReplOutput::Incomplete
+        );
+        assert_eq!(
+            repl.push("    return x * 2").await.unwrap(),
+            ReplOutput::Incomplete
+        );
+        assert!(matches!(
+            repl.push("").await.unwrap(),
+            ReplOutput::Complete(_)
+        ));
+
+        let ReplOutput::Complete(result) = repl.push("double(21)").await.unwrap() else {
+            panic!("expected a complete statement");
+        };
+        assert_eq!(result.value.as_deref(), Some("42"));
+
+        let result = repl.run_cell("undefined_name").await.unwrap();
+        assert!(result.error.unwrap().starts_with("NameError"));
+    }
+}
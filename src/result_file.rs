@@ -0,0 +1,183 @@
+//! Results too large for stdout
+//!
+//! Engines print their structured output as JSON on stdout, which is capped
+//! at `max_output_bytes`. A `result` that serializes to more than
+//! [`SPILL_THRESHOLD_BYTES`] is written to a file under [`results_dir`]
+//! instead and replaced by a reference:
+//!
+//! ```json
+//! {"type": "result_file", "result_file": "/tmp/pysandbox-results/<id>.json",
+//!  "size_bytes": 52428800, "sha256": "..."}
+//! ```
+//!
+//! [`read_result`] returns the result either way, loading and verifying the
+//! file when it was spilled; [`take_result`] also deletes it.
+
+use crate::{
+    engine::ExecutionResult,
+    errors::{Result, SandboxError},
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Serialized `result`s larger than this are spilled to a file
+pub const SPILL_THRESHOLD_BYTES: usize = 1024 * 1024;
+
+/// File a workspace wrapper spills to, relative to the workspace
+pub(crate) const WORKSPACE_RESULT_FILE: &str = ".rzn_result.json";
+
+/// Directory spilled results are kept in until taken
+pub fn results_dir() -> PathBuf {
+    std::env::temp_dir().join("pysandbox-results")
+}
+
+/// Fresh path in [`results_dir`] for a run to write its output to
+pub(crate) fn spill_path() -> Result<PathBuf> {
+    let dir = results_dir();
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{}.json", uuid::Uuid::new_v4())))
+}
+
+/// Move a result spilled inside `workspace` to [`results_dir`], before the
+/// workspace is removed
+///
+/// Only [`WORKSPACE_RESULT_FILE`] is moved, so output forged by the sandboxed
+/// code cannot point the host at another file.
+pub(crate) fn persist_from_workspace(output: &mut Value, workspace: &Path) -> Result<()> {
+    let Some(reference) = output.get_mut("result").filter(|r| is_reference(r)) else {
+        return Ok(());
+    };
+    if reference["result_file"] != WORKSPACE_RESULT_FILE {
+        return Ok(());
+    }
+    let source = workspace.join(WORKSPACE_RESULT_FILE);
+    if !std::fs::symlink_metadata(&source)?.is_file() {
+        return Err(SandboxError::SecurityViolation(
+            "Spilled result is not a regular file".to_string(),
+        ));
+    }
+    let dest = spill_path()?;
+    if std::fs::rename(&source, &dest).is_err() {
+        std::fs::copy(&source, &dest)?;
+    }
+    reference["result_file"] = Value::String(dest.to_string_lossy().into_owned());
+    Ok(())
+}
+
+/// Whether an execution's `result` was spilled to a file
+pub fn is_spilled(output: &ExecutionResult) -> bool {
+    output.result.as_ref().is_some_and(is_reference)
+}
+
+/// The `result` of an execution, read from its file if it was spilled
+///
+/// Only files in [`results_dir`] are read, and the size and SHA-256 in the
+/// reference are checked.
+pub fn read_result(output: &ExecutionResult) -> Result<Value> {
+    let Some(reference) = output.result.as_ref().filter(|r| is_reference(r)) else {
+        return Ok(output.result.clone().unwrap_or(Value::Null));
+    };
+    let path = spilled_path(reference)?;
+    let data = std::fs::read(&path)?;
+    if reference["size_bytes"].as_u64() != Some(data.len() as u64) {
+        return Err(SandboxError::InternalError(format!(
+            "Result file {} has the wrong size",
+            path.display()
+        )));
+    }
+    if let Some(expected) = reference["sha256"].as_str() {
+        let actual: String = Sha256::digest(&data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if actual != expected {
+            return Err(SandboxError::InternalError(format!(
+                "Result file {} does not match its sha256",
+                path.display()
+            )));
+        }
+    }
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Like [`read_result`], then delete the spilled file
+pub fn take_result(output: &ExecutionResult) -> Result<Value> {
+    let result = read_result(output)?;
+    if let Some(reference) = output.result.as_ref().filter(|r| is_reference(r)) {
+        std::fs::remove_file(spilled_path(reference)?)?;
+    }
+    Ok(result)
+}
+
+fn is_reference(result: &Value) -> bool {
+    result.get("type").and_then(|t| t.as_str()) == Some("result_file")
+}
+
+fn spilled_path(reference: &Value) -> Result<PathBuf> {
+    let path = reference["result_file"]
+        .as_str()
+        .map(PathBuf::from)
+        .ok_or_else(|| SandboxError::InternalError("Result file reference has no path".into()))?;
+    let canonical = path.canonicalize()?;
+    if canonical.parent() != Some(results_dir().canonicalize()?.as_path()) {
+        return Err(SandboxError::SecurityViolation(format!(
+            "Result file {} is outside {}",
+            path.display(),
+            results_dir().display()
+        )));
+    }
+    Ok(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_spilled_results_are_verified() {
+        let workspace = tempfile::tempdir().unwrap();
+        let data = br#"{"rows": [1, 2, 3]}"#;
+        std::fs::write(workspace.path().join(WORKSPACE_RESULT_FILE), data).unwrap();
+        let digest: String = Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let mut output = serde_json::json!({
+            "result": {
+                "type": "result_file",
+                "result_file": WORKSPACE_RESULT_FILE,
+                "size_bytes": data.len(),
+                "sha256": digest,
+            }
+        });
+        persist_from_workspace(&mut output, workspace.path()).unwrap();
+        let output = ExecutionResult::from_value(output).unwrap();
+        assert!(is_spilled(&output));
+        assert!(!workspace.path().join(WORKSPACE_RESULT_FILE).exists());
+        assert_eq!(
+            read_result(&output).unwrap(),
+            serde_json::json!({"rows": [1, 2, 3]})
+        );
+        assert_eq!(
+            output.result_as::<HashMap<String, Vec<u8>>>().unwrap()["rows"],
+            [1, 2, 3]
+        );
+
+        let mut tampered = output.clone();
+        tampered.result.as_mut().unwrap()["sha256"] = serde_json::json!("00");
+        assert!(read_result(&tampered).is_err());
+        take_result(&output).unwrap();
+        assert!(read_result(&output).is_err());
+
+        // A reference outside the results directory is never read
+        let forged = ExecutionResult::from_value(serde_json::json!({
+            "result": {"type": "result_file", "result_file": "/etc/hostname", "size_bytes": 1}
+        }))
+        .unwrap();
+        assert!(read_result(&forged).is_err());
+        let inline = ExecutionResult::from_value(serde_json::json!({"result": 5})).unwrap();
+        assert_eq!(inline.result_as::<u32>().unwrap(), 5);
+    }
+}
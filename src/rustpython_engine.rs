@@ -0,0 +1,375 @@
+use crate::{
+    config::ResourceLimits,
+    engine::{Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine},
+    errors::{execution_error, Result, SandboxError},
+    result_file,
+    sandboxed::{IsolatedWorkspace, SandboxedPythonEngine},
+    stdin::child_stdin,
+    supervisor::{
+        apply_file_limits, memory_watchdog, supervise, ResultChannel, SetupClock, SupervisorLimits,
+    },
+};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::info;
+
+/// Exit code the budget tracer uses when the instruction budget runs out
+const BUDGET_EXIT_CODE: i32 = 124;
+
+/// Configuration for the RustPython engine
+#[derive(Debug, Clone)]
+pub struct RustPythonConfig {
+    /// RustPython interpreter, either a path or a name looked up in PATH.
+    /// Build it with the `freeze-stdlib` feature so it needs no `Lib` directory.
+    pub interpreter: PathBuf,
+    /// Base directory for creating isolated workspaces
+    pub workspace_base: PathBuf,
+    /// Resource limits
+    pub limits: ResourceLimits,
+    /// Trace events (calls, lines, returns) granted per second of `cpu_seconds`.
+    /// `None` disables the instruction budget and relies on rlimits and the
+    /// wall-clock timeout only.
+    pub events_per_cpu_second: Option<u64>,
+}
+
+impl Default for RustPythonConfig {
+    fn default() -> Self {
+        Self {
+            interpreter: PathBuf::from("rustpython"),
+            workspace_base: crate::sandboxed::default_workspace_base(),
+            limits: ResourceLimits::default(),
+            events_per_cpu_second: Some(2_000_000),
+        }
+    }
+}
+
+/// Python engine that runs an external RustPython interpreter, for hosts
+/// without CPython
+///
+/// RustPython isn't linked into this crate: each run spawns the configured
+/// `rustpython` binary as a subprocess, like the native engine does with
+/// CPython. It runs the same guarded workspace wrapper as the sandboxed
+/// engine, with an instruction budget that a `sys.settrace` prelude enforces
+/// inside the child, backed by the CPU rlimit; both surface as
+/// [`SandboxError::Timeout`]. Only pure-Python code and RustPython's built-in
+/// modules are available.
+pub struct RustPythonEngine {
+    interpreter: PathBuf,
+    config: RustPythonConfig,
+}
+
+impl RustPythonEngine {
+    /// Create a new RustPython engine
+    pub fn new(config: RustPythonConfig) -> Result<Self> {
+        let interpreter =
+            which::which(&config.interpreter).map_err(|_| SandboxError::PythonNotFound)?;
+        std::fs::create_dir_all(&config.workspace_base)?;
+        Ok(Self {
+            interpreter,
+            config,
+        })
+    }
+
+    /// Create with an explicit interpreter path and default limits
+    pub fn with_interpreter(interpreter: PathBuf) -> Result<Self> {
+        Self::new(RustPythonConfig {
+            interpreter,
+            ..Default::default()
+        })
+    }
+
+    /// Get the interpreter path being used
+    pub fn interpreter(&self) -> &PathBuf {
+        &self.interpreter
+    }
+
+    /// Apply OS limits to the interpreter process
+    #[cfg(unix)]
+    fn apply_resource_limits(&self, cmd: &mut Command, memory_mb: usize, cpu_seconds: u64) {
+        #[cfg(not(target_os = "macos"))]
+        let memory_bytes = memory_mb * 1024 * 1024;
+        #[cfg(target_os = "macos")]
+        let _ = memory_mb;
+
+        unsafe {
+            cmd.pre_exec(move || {
+                libc::setpgid(0, 0);
+
+                // macOS doesn't support RLIMIT_AS properly, skip it
+                #[cfg(not(target_os = "macos"))]
+                {
+                    let rlimit = libc::rlimit {
+                        rlim_cur: memory_bytes as libc::rlim_t,
+                        rlim_max: memory_bytes as libc::rlim_t,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_AS, &rlimit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+
+                // SIGXCPU at the soft limit rather than SIGKILL at the hard
+                // one, so an overrun is told apart from the memory watchdog
+                let rlimit = libc::rlimit {
+                    rlim_cur: cpu_seconds as libc::rlim_t,
+                    rlim_max: cpu_seconds.saturating_add(1) as libc::rlim_t,
+                };
+                if libc::setrlimit(libc::RLIMIT_CPU, &rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn apply_resource_limits(&self, _cmd: &mut Command, _memory_mb: usize, _cpu_seconds: u64) {
+        // Rely on the instruction budget and timeout
+    }
+}
+
+/// Python prelude that installs the instruction budget tracer
+///
+/// The tracer exits the interpreter directly rather than raising, so user code
+/// can't swallow the budget with a bare `except`. Once armed, `sys.settrace`
+/// and `sys.setprofile` (and their `threading` counterparts) are replaced with
+/// no-ops so user code can't remove it; new threads inherit the tracer.
+fn budget_prelude(budget: Option<u64>) -> String {
+    let Some(budget) = budget else {
+        return String::new();
+    };
+    format!(
+        r#"
+def _rzn_budget_arm(budget):
+    import os, sys
+    settrace = sys.settrace
+    left = [budget]
+    def tracer(frame, event, arg):
+        left[0] -= 1
+        if left[0] < 0:
+            settrace(None)
+            sys.__stderr__.write("Instruction budget exceeded\n")
+            sys.__stderr__.flush()
+            os._exit({exit_code})
+        return tracer
+    def locked(func):
+        pass
+    settrace(tracer)
+    sys.settrace = locked
+    sys.setprofile = locked
+    try:
+        import threading
+        threading.settrace(tracer)
+        threading.settrace = locked
+        threading.setprofile = locked
+    except (ImportError, AttributeError):
+        pass
+    return tracer
+_rzn_budget_tracer = _rzn_budget_arm({budget})
+del _rzn_budget_arm
+# settrace only covers new frames; user code runs in this module's frame
+try:
+    __import__("sys")._getframe().f_trace = _rzn_budget_tracer
+except (AttributeError, TypeError):
+    pass
+"#,
+        budget = budget,
+        exit_code = BUDGET_EXIT_CODE
+    )
+}
+
+#[async_trait]
+impl PythonEngine for RustPythonEngine {
+    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        // Syntax errors are reported by the interpreter
+        Ok(Vec::new())
+    }
+
+    async fn execute(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let setup = SetupClock::start(options);
+        let workspace = IsolatedWorkspace::new(&self.config.workspace_base)?;
+        workspace.write_inputs(&inputs)?;
+        workspace.stage_inputs(&options.input_files)?;
+        let channel = ResultChannel::in_workspace(&workspace.path);
+
+        let memory_mb = self.config.limits.memory_mb.min(options.memory_mb);
+        let cpu_seconds = self.config.limits.cpu_seconds.min(options.cpu_seconds);
+        let budget = self
+            .config
+            .events_per_cpu_second
+            .map(|per_sec| per_sec.saturating_mul(cpu_seconds));
+
+        let wrapper_code = format!(
+            "{}{}",
+            budget_prelude(budget),
+            SandboxedPythonEngine::workspace_wrapper(
+                code,
+                options,
+                false,
+                self.config.limits.max_output_bytes,
+                None,
+                &channel,
+            )?
+        );
+
+        info!("[RUSTPYTHON] Running with {:?}", self.interpreter);
+
+        let mut cmd = Command::new(&self.interpreter);
+        cmd.arg("-c")
+            .arg(&wrapper_code)
+            .current_dir(&workspace.path)
+            .stdin(child_stdin(options.stdin.as_ref()))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .env("SANDBOX_WORKSPACE", &workspace.path)
+            .kill_on_drop(true);
+        for (key, value) in &options.env_vars {
+            cmd.env(key, value);
+        }
+        self.apply_resource_limits(&mut cmd, memory_mb, cpu_seconds);
+        apply_file_limits(
+            &mut cmd,
+            self.config.limits.max_open_files,
+            Some(self.config.limits.max_workspace_bytes),
+        );
+
+        let setup = setup.finish()?;
+        let child = cmd.spawn()?;
+        let limits = SupervisorLimits {
+            memory_watchdog_mb: memory_watchdog(memory_mb),
+            ..SupervisorLimits::for_limits(&self.config.limits)
+        }
+        .with_workspace(&workspace.path, &self.config.limits);
+        let output = supervise(child, options, &limits).await?.with_setup(setup);
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        // Extract structured output
+        if let Some(mut parsed) = channel.take(&stdout) {
+            if let Some(error) = execution_error(&parsed, code) {
+                return Err(error);
+            }
+            result_file::persist_from_workspace(&mut parsed, &workspace.path)?;
+            output.attach_metrics(&mut parsed);
+            return ExecutionResult::from_value(parsed);
+        }
+
+        // Output that bypassed capture overran the pipe cap
+        if output.truncated {
+            return Err(SandboxError::RuntimeError(
+                "Output exceeded the configured size limit".to_string(),
+            ));
+        }
+
+        if output.status.code() == Some(BUDGET_EXIT_CODE) {
+            return Err(SandboxError::Timeout);
+        }
+        // The CPU rlimit backs the budget up when a frame's tracer is cleared
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if output.status.signal() == Some(libc::SIGXCPU) {
+                return Err(SandboxError::Timeout);
+            }
+        }
+        if !output.status.success() {
+            if stderr.contains("MemoryError") {
+                return Err(SandboxError::MemoryLimitExceeded { peak_mb: None });
+            }
+            if stderr.contains("SyntaxError") {
+                return Err(SandboxError::SyntaxError(stderr.to_string()));
+            }
+            return Err(SandboxError::RuntimeError(stderr.to_string()));
+        }
+
+        Ok(ExecutionResult::default())
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: "RustPython (subprocess)".to_string(),
+            numpy: false, // No C extension support
+            matplotlib: false,
+            pandas: false,
+            max_memory_mb: self.config.limits.memory_mb,
+            max_cpu_seconds: self.config.limits.cpu_seconds,
+            security_level: 5, // Same guardrails as native, plus an instruction budget
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_prelude() {
+        assert!(budget_prelude(None).is_empty());
+
+        let prelude = budget_prelude(Some(1000));
+        assert!(prelude.contains("_rzn_budget_arm(1000)"));
+        assert!(prelude.contains("sys.settrace = locked"));
+        assert!(prelude.contains("_exit(124)"));
+    }
+
+    #[tokio::test]
+    async fn test_budget_survives_settrace_reset() {
+        // The prelude is plain Python, so CPython stands in for RustPython
+        let Ok(mut engine) = RustPythonEngine::new(RustPythonConfig {
+            interpreter: PathBuf::from("python3"),
+            events_per_cpu_second: Some(100_000),
+            ..Default::default()
+        }) else {
+            return;
+        };
+        let options = ExecutionOptions {
+            cpu_seconds: 2,
+            ..Default::default()
+        };
+
+        // The tracer stops these long before the CPU rlimit would
+        for code in [
+            "import sys\nsys.settrace(None)\nwhile True:\n    x = 1",
+            "import sys\nsys.setprofile(None)\nsys.settrace(lambda *a: None)\nwhile True:\n    x = 1",
+            "import threading\nthreading.settrace(None)\nwhile True:\n    x = 1",
+        ] {
+            let started = std::time::Instant::now();
+            let result = engine.execute(code, serde_json::json!({}), &options).await;
+            assert!(
+                matches!(result, Err(SandboxError::Timeout)),
+                "{code}: {result:?}"
+            );
+            assert!(started.elapsed() < std::time::Duration::from_secs(2), "{code}");
+        }
+
+        // Loops without line events, or with the frame's tracer cleared, hit
+        // the CPU rlimit instead
+        for code in [
+            "import sys\nsys.settrace(None)\nwhile True: pass",
+            "import sys\nsys._getframe().f_trace = None\nwhile True: pass",
+        ] {
+            let result = engine.execute(code, serde_json::json!({}), &options).await;
+            assert!(
+                matches!(result, Err(SandboxError::Timeout)),
+                "{code}: {result:?}"
+            );
+        }
+
+        let result = engine
+            .execute("x = sum(range(100))", serde_json::json!({}), &options)
+            .await;
+        assert!(result.is_ok(), "{result:?}");
+    }
+}
@@ -1,6 +1,8 @@
 use crate::{
-    config::ResourceLimits, native::NativePythonEngine, PythonEngine, PythonSandbox, Result,
-    SandboxError,
+    config::ResourceLimits,
+    native::NativePythonEngine,
+    pool::{PoolConfig, PooledPythonEngine},
+    PythonEngine, PythonSandbox, Result, SandboxError,
 };
 use std::path::PathBuf;
 
@@ -14,6 +16,10 @@ pub struct SandboxOptions {
     pub python_path: Option<PathBuf>,
     /// Custom resource limits
     pub limits: Option<ResourceLimits>,
+    /// Modules to pre-import; when set, code runs on a warm
+    /// [`PooledPythonEngine`](crate::PooledPythonEngine) instead of a fresh
+    /// interpreter per execution
+    pub warmup: Vec<String>,
 }
 
 impl Default for SandboxOptions {
@@ -23,6 +29,7 @@ impl Default for SandboxOptions {
             prefer_microsandbox: true,
             python_path: None,
             limits: None,
+            warmup: Vec::new(),
         }
     }
 }
@@ -35,6 +42,7 @@ impl SandboxOptions {
             prefer_microsandbox: false, // Use bundled Python directly
             python_path: Some(python_path),
             limits: None,
+            warmup: Vec::new(),
         }
     }
 
@@ -43,11 +51,19 @@ impl SandboxOptions {
         self.limits = Some(limits);
         self
     }
+
+    /// Pre-import `modules`, e.g. `numpy` or `pandas`, before the first execution
+    pub fn with_warmup(mut self, modules: &[&str]) -> Self {
+        self.warmup = modules.iter().map(|m| m.to_string()).collect();
+        self
+    }
 }
 
 /// Create sandbox with custom options
 pub async fn create_sandbox_with_options(options: SandboxOptions) -> Result<PythonSandbox> {
     let mut engines: Vec<Box<dyn PythonEngine>> = vec![];
+    // Registry key of each engine, for `ExecutionOptions::preferred_engine`
+    let mut keys = vec![];
 
     // Try to add microsandbox if available and feature enabled
     #[cfg(feature = "microsandbox-engine")]
@@ -84,7 +100,10 @@ pub async fn create_sandbox_with_options(options: SandboxOptions) -> Result<Pyth
 
             if should_use_microsandbox {
                 match crate::microsandbox_engine::MicrosandboxEngine::new().await {
-                    Ok(engine) => engines.push(Box::new(engine)),
+                    Ok(engine) => {
+                        engines.push(Box::new(engine));
+                        keys.push("microsandbox");
+                    }
                     Err(e) => {
                         tracing::warn!("Failed to create microsandbox engine: {}", e);
                     }
@@ -93,22 +112,51 @@ pub async fn create_sandbox_with_options(options: SandboxOptions) -> Result<Pyth
         }
     }
 
-    // Create native engine (with bundled Python path if provided)
-    let native_engine = match (&options.python_path, &options.limits) {
-        (Some(path), Some(limits)) => {
-            NativePythonEngine::with_python_path_and_limits(path.clone(), limits.clone())?
-        }
-        (Some(path), None) => NativePythonEngine::with_python_path(path.clone())?,
-        (None, Some(limits)) => NativePythonEngine::with_limits(limits.clone())?,
-        (None, None) => NativePythonEngine::new()?,
-    };
-    engines.push(Box::new(native_engine));
+    if options.warmup.is_empty() {
+        // Create native engine (with bundled Python path if provided)
+        let native_engine = match (&options.python_path, &options.limits) {
+            (Some(path), Some(limits)) => {
+                NativePythonEngine::with_python_path_and_limits(path.clone(), limits.clone())?
+            }
+            (Some(path), None) => NativePythonEngine::with_python_path(path.clone())?,
+            (None, Some(limits)) => NativePythonEngine::with_limits(limits.clone())?,
+            (None, None) => NativePythonEngine::new()?,
+        };
+        engines.push(Box::new(native_engine));
+        keys.push("native");
+    } else {
+        // Warm workers keep the imports between executions
+        let config = PoolConfig {
+            limits: options.limits.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        let pool = match &options.python_path {
+            Some(path) => PooledPythonEngine::with_python_path(path.clone(), config)?,
+            None => PooledPythonEngine::new(config)?,
+        };
+        engines.push(Box::new(pool));
+        keys.push("pool");
+    }
 
     if engines.is_empty() {
         return Err(SandboxError::NoEngineAvailable);
     }
 
-    Ok(PythonSandbox::new(engines))
+    let mut sandbox = PythonSandbox::new(engines);
+    for (index, key) in keys.into_iter().enumerate() {
+        sandbox = sandbox.with_engine_key(index, key);
+    }
+    if !options.warmup.is_empty() {
+        let modules: Vec<&str> = options.warmup.iter().map(String::as_str).collect();
+        sandbox.warmup(&modules).await?;
+    }
+    if let Some(path) = options.python_path {
+        sandbox = sandbox.with_session_python(path);
+    }
+    if let Some(limits) = options.limits {
+        sandbox = sandbox.with_session_limits(limits);
+    }
+    Ok(sandbox)
 }
 
 /// Create sandbox with bundled Python
@@ -141,6 +189,7 @@ pub async fn create_sandbox_interactive() -> Result<PythonSandbox> {
 }
 
 /// Prompt user if they want to set up microsandbox
+#[cfg(feature = "microsandbox-engine")]
 fn prompt_setup_microsandbox() -> Result<bool> {
     use std::io::{self, Write};
 
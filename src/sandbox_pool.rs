@@ -0,0 +1,499 @@
+//! Bounded concurrent execution over several engine instances
+//!
+//! A [`SandboxPool`] owns a fixed set of engines, each running one snippet
+//! at a time, so a server can run many users' code concurrently without
+//! starting unbounded interpreters. Calls beyond the free engines wait in
+//! first-come, first-served order; once
+//! [`SandboxPoolConfig::max_queued`] are waiting, further calls fail with
+//! [`SandboxError::QueueFull`]. [`SandboxPool::execute_for`] also caps how
+//! many executions one caller may have running or queued, so a single busy
+//! caller can't take every engine.
+//!
+//! Each call's [`ExecutionOptions::priority`] orders the queue: interactive
+//! calls get the next free engine ahead of every queued batch call, and when
+//! the queue is full an interactive call takes the place of the most recently
+//! queued batch call, which fails with `QueueFull`. Running executions are
+//! never interrupted.
+
+use crate::{
+    engine::{ExecutionOptions, ExecutionResult, PythonEngine},
+    errors::{Result, SandboxError},
+    native::NativePythonEngine,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+
+/// Scheduling class of an execution in a [`SandboxPool`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// A user is waiting on the result
+    #[default]
+    Interactive,
+    /// Background work that can wait behind interactive calls
+    Batch,
+}
+
+/// Queue and per-caller limits of a [`SandboxPool`]
+#[derive(Debug, Clone)]
+pub struct SandboxPoolConfig {
+    /// Calls allowed to wait for an engine; more fail with `QueueFull`
+    pub max_queued: usize,
+    /// Executions one caller may have running or queued at once
+    pub max_per_caller: Option<usize>,
+}
+
+impl Default for SandboxPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_queued: 64,
+            max_per_caller: None,
+        }
+    }
+}
+
+/// Engines shared by concurrent callers, see the [module docs](self)
+pub struct SandboxPool {
+    idle: Mutex<Vec<Box<dyn PythonEngine>>>,
+    slots: Mutex<Slots>,
+    size: usize,
+    queued: AtomicUsize,
+    callers: Mutex<HashMap<String, Arc<Semaphore>>>,
+    config: SandboxPoolConfig,
+}
+
+impl SandboxPool {
+    /// Pool of `n` native engines using Python from PATH
+    pub fn new(n: usize) -> Result<Self> {
+        let engines = (0..n.max(1))
+            .map(|_| Ok(Box::new(NativePythonEngine::new()?) as Box<dyn PythonEngine>))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::with_engines(engines, SandboxPoolConfig::default()))
+    }
+
+    /// Pool over the given engine instances
+    pub fn with_engines(engines: Vec<Box<dyn PythonEngine>>, config: SandboxPoolConfig) -> Self {
+        Self {
+            slots: Mutex::new(Slots {
+                free: engines.len(),
+                next_id: 0,
+                waiting: Default::default(),
+            }),
+            size: engines.len(),
+            idle: Mutex::new(engines),
+            queued: AtomicUsize::new(0),
+            callers: Mutex::new(HashMap::new()),
+            config,
+        }
+    }
+
+    /// Set the queue and per-caller limits
+    pub fn with_config(mut self, config: SandboxPoolConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Number of engines
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Engines free right now
+    pub fn available(&self) -> usize {
+        self.lock_slots().free
+    }
+
+    /// Calls waiting for an engine
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Execute on the next free engine
+    pub async fn execute(
+        &self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let _slot = self.acquire(None, &options).await?;
+        self.run(code, inputs, &options).await
+    }
+
+    /// Execute on behalf of `caller`, within its
+    /// [`max_per_caller`](SandboxPoolConfig::max_per_caller) limit
+    pub async fn execute_for(
+        &self,
+        caller: &str,
+        code: &str,
+        inputs: serde_json::Value,
+        options: ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let _slot = self.acquire(Some(caller), &options).await?;
+        self.run(code, inputs, &options).await
+    }
+
+    async fn run(
+        &self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        let mut engine = CheckedOut::take(&self.idle)?;
+        engine.execute(code, inputs, options).await
+    }
+
+    /// Wait for the caller's turn and a free engine
+    async fn acquire(&self, caller: Option<&str>, options: &ExecutionOptions) -> Result<Slot<'_>> {
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.config.max_queued
+            && self.available() == 0
+            && !(options.priority == Priority::Interactive && self.evict_batch())
+        {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return Err(SandboxError::QueueFull);
+        }
+        let queued = Queued(&self.queued);
+
+        let caller = match (caller, self.config.max_per_caller) {
+            (Some(caller), Some(limit)) => Some(CallerPermit {
+                name: caller.to_string(),
+                permit: Some(permit(self.caller_limit(caller, limit), options).await?),
+                callers: &self.callers,
+            }),
+            _ => None,
+        };
+        let engine = self.engine_slot(options).await?;
+        drop(queued);
+        Ok(Slot {
+            _engine: engine,
+            _caller: caller,
+        })
+    }
+
+    fn lock_slots(&self) -> std::sync::MutexGuard<'_, Slots> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take a free engine, or queue by priority for the next one
+    async fn engine_slot(&self, options: &ExecutionOptions) -> Result<EngineSlot<'_>> {
+        let (id, receiver) = {
+            let mut slots = self.lock_slots();
+            if slots.free > 0 && slots.waiting.iter().all(VecDeque::is_empty) {
+                slots.free -= 1;
+                return Ok(EngineSlot(self));
+            }
+            let (sender, receiver) = oneshot::channel();
+            let id = slots.next_id;
+            slots.next_id += 1;
+            slots.waiting[options.priority as usize].push_back((id, sender));
+            (id, receiver)
+        };
+        let mut waiter = Waiter {
+            pool: self,
+            id,
+            receiver,
+            done: false,
+        };
+        let grant = match &options.cancellation {
+            Some(token) => tokio::select! {
+                grant = &mut waiter.receiver => grant,
+                _ = token.cancelled() => return Err(SandboxError::Cancelled),
+            },
+            None => (&mut waiter.receiver).await,
+        };
+        waiter.done = true;
+        match grant {
+            Ok(Grant::Engine) => Ok(EngineSlot(self)),
+            Ok(Grant::Evicted) => Err(SandboxError::QueueFull),
+            Err(_) => Err(SandboxError::InternalError(
+                "Sandbox pool closed".to_string(),
+            )),
+        }
+    }
+
+    /// Hand a freed engine to the first waiter by priority
+    fn release(&self) {
+        let mut slots = self.lock_slots();
+        for queue in &mut slots.waiting {
+            while let Some((_, sender)) = queue.pop_front() {
+                if sender.send(Grant::Engine).is_ok() {
+                    return;
+                }
+            }
+        }
+        slots.free += 1;
+    }
+
+    /// Turn away the most recently queued batch call
+    fn evict_batch(&self) -> bool {
+        let mut slots = self.lock_slots();
+        while let Some((_, sender)) = slots.waiting[Priority::Batch as usize].pop_back() {
+            if sender.send(Grant::Evicted).is_ok() {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn caller_limit(&self, caller: &str, limit: usize) -> Arc<Semaphore> {
+        self.callers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(caller.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit.max(1))))
+            .clone()
+    }
+}
+
+/// Acquire a permit unless the execution is cancelled first
+async fn permit(
+    semaphore: Arc<Semaphore>,
+    options: &ExecutionOptions,
+) -> Result<OwnedSemaphorePermit> {
+    let permit = async {
+        semaphore
+            .acquire_owned()
+            .await
+            .map_err(|_| SandboxError::InternalError("Sandbox pool closed".to_string()))
+    };
+    match &options.cancellation {
+        Some(token) => tokio::select! {
+            permit = permit => permit,
+            _ = token.cancelled() => Err(SandboxError::Cancelled),
+        },
+        None => permit.await,
+    }
+}
+
+/// Counts a call as queued until dropped
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A caller's share of its limit; the last one out removes the caller
+struct CallerPermit<'a> {
+    name: String,
+    permit: Option<OwnedSemaphorePermit>,
+    callers: &'a Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Drop for CallerPermit<'_> {
+    fn drop(&mut self) {
+        let mut callers = self.callers.lock().unwrap_or_else(|e| e.into_inner());
+        drop(self.permit.take());
+        // Only the map holds it once no call of this caller is in flight
+        if callers
+            .get(&self.name)
+            .is_some_and(|limit| Arc::strong_count(limit) == 1)
+        {
+            callers.remove(&self.name);
+        }
+    }
+}
+
+/// Free engines and the calls waiting for one, by priority
+struct Slots {
+    free: usize,
+    next_id: u64,
+    waiting: [VecDeque<(u64, oneshot::Sender<Grant>)>; 2],
+}
+
+enum Grant {
+    Engine,
+    Evicted,
+}
+
+/// A queued call; dropped before its grant arrives, it leaves the queue
+struct Waiter<'a> {
+    pool: &'a SandboxPool,
+    id: u64,
+    receiver: oneshot::Receiver<Grant>,
+    done: bool,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        self.pool
+            .lock_slots()
+            .waiting
+            .iter_mut()
+            .for_each(|queue| queue.retain(|(id, _)| *id != self.id));
+        // An engine handed over just before leaving goes to the next waiter
+        self.receiver.close();
+        if let Ok(Grant::Engine) = self.receiver.try_recv() {
+            self.pool.release();
+        }
+    }
+}
+
+/// A reserved engine, released to the next waiter when dropped
+struct EngineSlot<'a>(&'a SandboxPool);
+
+impl Drop for EngineSlot<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+struct Slot<'a> {
+    _engine: EngineSlot<'a>,
+    _caller: Option<CallerPermit<'a>>,
+}
+
+/// An engine taken from the idle list, put back when dropped
+struct CheckedOut<'a> {
+    engine: Option<Box<dyn PythonEngine>>,
+    idle: &'a Mutex<Vec<Box<dyn PythonEngine>>>,
+}
+
+impl<'a> CheckedOut<'a> {
+    fn take(idle: &'a Mutex<Vec<Box<dyn PythonEngine>>>) -> Result<Self> {
+        // Holding an engine permit guarantees an idle engine
+        let engine = idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop()
+            .ok_or_else(|| SandboxError::InternalError("No idle engine in pool".to_string()))?;
+        Ok(Self {
+            engine: Some(engine),
+            idle,
+        })
+    }
+}
+
+impl std::ops::Deref for CheckedOut<'_> {
+    type Target = Box<dyn PythonEngine>;
+
+    fn deref(&self) -> &Self::Target {
+        self.engine.as_ref().expect("engine checked out")
+    }
+}
+
+impl std::ops::DerefMut for CheckedOut<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.engine.as_mut().expect("engine checked out")
+    }
+}
+
+impl Drop for CheckedOut<'_> {
+    fn drop(&mut self) {
+        if let Some(engine) = self.engine.take() {
+            self.idle
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(engine);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_sandbox_pool_bounds_queue() {
+        let Ok(engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let pool = Arc::new(SandboxPool::with_engines(
+            vec![Box::new(engine)],
+            SandboxPoolConfig {
+                max_queued: 1,
+                max_per_caller: Some(1),
+            },
+        ));
+        let run = |caller: &'static str, code: &'static str| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                pool.execute_for(caller, code, serde_json::json!({}), Default::default())
+                    .await
+            })
+        };
+
+        let running = run("a", "import time\ntime.sleep(0.5)\nresult = 1");
+        while pool.available() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let waiting = run("b", "result = 2");
+        while pool.queued() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(matches!(
+            pool.execute("result = 3", serde_json::json!({}), Default::default())
+                .await,
+            Err(SandboxError::QueueFull)
+        ));
+
+        assert_eq!(
+            running.await.unwrap().unwrap().result_as::<i64>().unwrap(),
+            1
+        );
+        assert_eq!(
+            waiting.await.unwrap().unwrap().result_as::<i64>().unwrap(),
+            2
+        );
+        assert!(pool.callers.lock().unwrap().is_empty());
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_interactive_calls_jump_the_queue() {
+        let Ok(engine) = NativePythonEngine::new() else {
+            return;
+        };
+        let pool = Arc::new(SandboxPool::with_engines(
+            vec![Box::new(engine)],
+            SandboxPoolConfig {
+                max_queued: 2,
+                max_per_caller: None,
+            },
+        ));
+        let run = |priority: Priority, code: &'static str| {
+            let pool = pool.clone();
+            let options = ExecutionOptions {
+                priority,
+                ..Default::default()
+            };
+            tokio::spawn(async move { pool.execute(code, serde_json::json!({}), options).await })
+        };
+        let queued = |n: usize| {
+            let pool = pool.clone();
+            async move {
+                while pool.queued() < n {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        };
+        let started = "import time\nresult = time.time()";
+
+        let running = run(Priority::Batch, "import time\ntime.sleep(0.5)");
+        while pool.available() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let older_batch = run(Priority::Batch, started);
+        queued(1).await;
+        let newest_batch = run(Priority::Batch, started);
+        queued(2).await;
+        // The queue is full: the newest batch call gives up its place
+        let interactive = run(Priority::Interactive, started);
+
+        running.await.unwrap().unwrap();
+        let interactive: f64 = interactive.await.unwrap().unwrap().result_as().unwrap();
+        assert!(matches!(
+            newest_batch.await.unwrap(),
+            Err(SandboxError::QueueFull)
+        ));
+        let batch: f64 = older_batch.await.unwrap().unwrap().result_as().unwrap();
+        assert!(interactive < batch);
+    }
+}
@@ -1,14 +1,180 @@
 use crate::{
-    config::{ImportPolicy, ResourceLimits},
-    engine::{EngineCapabilities, ExecutionOptions, PythonEngine},
-    errors::{Result, SandboxError},
+    artifacts::ArtifactStore,
+    audit_hook::{render_audit_hook, ARM_AUDIT_HOOK},
+    config::{python_version_requirements, whitelisted_modules, ImportPolicy, ResourceLimits},
+    egress::{guard_options, EgressProxy},
+    engine::{
+        ensure_valid, Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult,
+        PythonEngine,
+    },
+    errors::{execution_error, Result, SandboxError},
+    policy::SandboxPolicy,
+    result_file,
+    stdin::{child_stdin, WORKSPACE_STDIN_FILE},
+    supervisor::{
+        apply_file_limits, output_capture, supervise, ResultChannel, SetupClock, SupervisorLimits,
+        DENIAL_MARKER, MARK_TRUNCATED, RESULT_SERIALIZER, WORKSPACE_INPUTS_FILE,
+        WORKSPACE_OUTPUT_FILE,
+    },
 };
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::Command;
 use tracing::{error, info, warn};
 
+/// Python shared by the import guards: `check_import` and `safe_import` over
+/// `WHITELIST` and `BLACKLIST` (either may be `None`), where the longest
+/// dotted prefix listed decides
+///
+/// Parents of an allowed module are exempt while it loads them, so with `os`
+/// blacklisted and `os.path` whitelisted `from os import path` still works.
+/// `posix` and `nt` follow the rules for `os`, which re-exports them.
+/// Installed versions are read once, before the hook is in place, for the
+/// modules in `VERSION_REQUIREMENTS`; one that fails its requirement can't be
+/// imported.
+pub(crate) const IMPORT_POLICY_CHECK: &str = r#"
+_rzn_loading_parents = set()
+
+def _rzn_release(version):
+    release = []
+    for part in version.split('.'):
+        digits = re.match(r'\d*', part).group()
+        if not digits:
+            break
+        release.append(int(digits))
+        if len(digits) < len(part):
+            break
+    while release and release[-1] == 0:
+        release.pop()
+    return tuple(release)
+
+def _rzn_satisfies(version, requirement):
+    installed = _rzn_release(version)
+    for clause in requirement.split(','):
+        match = re.fullmatch(r'\s*(~=|==|!=|<=|>=|<|>)\s*([\w.]+)\s*', clause)
+        if not match:
+            return False
+        op, wanted = match.group(1), _rzn_release(match.group(2))
+        if op == '~=':
+            prefix = _rzn_release(match.group(2).rsplit('.', 1)[0])
+            ok = installed >= wanted and installed[:len(prefix)] == prefix
+        else:
+            ok = {
+                '==': installed == wanted,
+                '!=': installed != wanted,
+                '<=': installed <= wanted,
+                '>=': installed >= wanted,
+                '<': installed < wanted,
+                '>': installed > wanted,
+            }[op]
+        if not ok:
+            return False
+    return True
+
+def _rzn_outdated_modules():
+    if not VERSION_REQUIREMENTS:
+        return {}
+    import importlib.metadata as metadata
+    distributions = getattr(metadata, 'packages_distributions', dict)()
+    outdated = {}
+    for module, requirement in VERSION_REQUIREMENTS.items():
+        for distribution in distributions.get(module.split('.')[0], [module]):
+            try:
+                version = metadata.version(distribution)
+            except metadata.PackageNotFoundError:
+                continue
+            if not _rzn_satisfies(version, requirement):
+                outdated[module] = f"{distribution} {version} does not satisfy '{requirement}'"
+            break
+    return outdated
+
+_RZN_OUTDATED = _rzn_outdated_modules()
+
+def _rzn_import_rule(module):
+    parts = module.split('.')
+    # posix and nt hold what os re-exports, so they follow its rules
+    if parts[0] in ('posix', 'nt'):
+        parts[0] = 'os'
+    for end in range(len(parts), 0, -1):
+        prefix = '.'.join(parts[:end])
+        if BLACKLIST is not None and prefix in BLACKLIST:
+            return False
+        if WHITELIST is not None and prefix in WHITELIST:
+            return True
+    return None
+
+def _rzn_import_allowed(module):
+    rule = _rzn_import_rule(module)
+    if rule is None:
+        return WHITELIST is None or module.split('.')[0] == 'builtins'
+    return rule
+
+def check_import(module):
+    # os loads its platform module even where os itself is blocked
+    if module.split('.')[0] in ('posix', 'nt') and 'os' in _rzn_loading_parents:
+        return
+    if _rzn_import_rule(module) is False:
+        raise _rzn_denied(ImportError(f"Module '{module}' is blacklisted for safety"), "import", module)
+    if not _rzn_import_allowed(module):
+        raise _rzn_denied(ImportError(f"Module '{module}' is not in whitelist"), "import", module)
+    parts = module.split('.')
+    for end in range(len(parts), 0, -1):
+        outdated = _RZN_OUTDATED.get('.'.join(parts[:end]))
+        if outdated:
+            raise _rzn_denied(ImportError(f"Module '{module}' is blocked: {outdated}"), "import", module)
+
+def _rzn_check_from_import(module, fromlist):
+    names = [module + '.' + item for item in fromlist if item != '*']
+    if _rzn_import_allowed(module):
+        for name in names:
+            check_import(name)
+    elif not names or '*' in fromlist or not all(map(_rzn_import_allowed, names)):
+        check_import(module)
+
+def safe_import(name, globals=None, locals=None, fromlist=(), level=0):
+    # For relative imports (level > 0), allow them - they're within an already-imported package
+    if level > 0:
+        return original_import(name, globals, locals, fromlist, level)
+
+    if fromlist:
+        _rzn_check_from_import(name, fromlist)
+        parents = name.split('.')
+    else:
+        check_import(name)
+        parents = name.split('.')[:-1]
+    parents = {'.'.join(parents[:end]) for end in range(1, len(parents) + 1)}
+    parents -= _rzn_loading_parents
+    _rzn_loading_parents.update(parents)
+    try:
+        return original_import(name, globals, locals, fromlist, level)
+    finally:
+        _rzn_loading_parents.difference_update(parents)
+"#;
+
+/// Python appended to the import guard that enforces `check_import` for
+/// imports that bypass `builtins.__import__` (`importlib.import_module`,
+/// `importlib.__import__`), and drops disallowed modules that are already
+/// cached so those calls can't hand them out
+pub(crate) const IMPORT_POLICY_FINDER: &str = r#"
+class _RznImportPolicyFinder:
+    @staticmethod
+    def find_spec(fullname, path=None, target=None):
+        if fullname not in _rzn_loading_parents:
+            check_import(fullname)
+        return None
+
+sys.meta_path.insert(0, _RznImportPolicyFinder)
+
+for _rzn_cached in list(sys.modules):
+    if _rzn_cached in ('__main__', 'builtins', 'sys'):
+        continue
+    try:
+        check_import(_rzn_cached)
+    except ImportError:
+        sys.modules.pop(_rzn_cached, None)
+"#;
+
 /// Configuration for the sandboxed Python engine
 #[derive(Debug, Clone)]
 pub struct SandboxConfig {
@@ -16,12 +182,12 @@ pub struct SandboxConfig {
     pub python_path: PathBuf,
     /// Path to the sandbox profile file (macOS .sb file)
     pub sandbox_profile: Option<PathBuf>,
+    /// Policy used to generate a sandbox profile when `sandbox_profile` is not set
+    pub policy: Option<SandboxPolicy>,
     /// Base directory for creating isolated workspaces
     pub workspace_base: PathBuf,
     /// Resource limits
     pub limits: ResourceLimits,
-    /// Files to copy into the workspace before execution
-    pub input_files: Vec<(PathBuf, String)>, // (source_path, workspace_name)
 }
 
 impl Default for SandboxConfig {
@@ -29,24 +195,75 @@ impl Default for SandboxConfig {
         Self {
             python_path: PathBuf::from("python3"),
             sandbox_profile: None,
-            workspace_base: std::env::temp_dir().join("pysandbox-workspaces"),
+            policy: None,
+            workspace_base: default_workspace_base(),
             limits: ResourceLimits::default(),
-            input_files: Vec::new(),
         }
     }
 }
 
+/// Export a run's output files to the [`ArtifactStore`] configured by the
+/// environment, if any, adding the export directory and `exported_files` to
+/// its `output`
+pub(crate) fn export_outputs(output: &mut serde_json::Value, run_id: &str, output_dir: &Path) {
+    let Some(store) = ArtifactStore::from_env() else {
+        return;
+    };
+    let exported = match store.export_outputs(run_id, output_dir) {
+        Ok(Some(exported)) => exported,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(
+                "[SANDBOX] Failed to export outputs from {:?}: {}",
+                output_dir, e
+            );
+            return;
+        }
+    };
+    let (export_dir, exported_files) = exported;
+    let exported_files: Vec<serde_json::Value> = exported_files
+        .iter()
+        .filter_map(|artifact| serde_json::to_value(artifact).ok())
+        .collect();
+    let Some(obj) = output.as_object_mut() else {
+        return;
+    };
+    obj.insert(
+        "export_dir".to_string(),
+        serde_json::Value::String(export_dir.to_string_lossy().to_string()),
+    );
+    // Point a DataFrame or ndarray artifact at its exported copy
+    if let Some(artifact) = obj.get_mut("result").and_then(|r| r.get_mut("artifact")) {
+        let exported = exported_files
+            .iter()
+            .find(|f| f.get("name") == artifact.get("file"));
+        if let Some(path) = exported.and_then(|f| f.get("path")) {
+            artifact["path"] = path.clone();
+        }
+    }
+    obj.insert(
+        "exported_files".to_string(),
+        serde_json::Value::Array(exported_files),
+    );
+}
+
+/// Where engines create workspaces unless configured otherwise
+pub fn default_workspace_base() -> PathBuf {
+    std::env::temp_dir().join("pysandbox-workspaces")
+}
+
 /// A workspace-isolated execution context
 pub struct IsolatedWorkspace {
     /// Path to the workspace directory
     pub path: PathBuf,
     /// Whether to clean up on drop
     cleanup_on_drop: bool,
+    _gauge: crate::metrics::GaugeGuard,
 }
 
 impl IsolatedWorkspace {
     /// Create a new isolated workspace
-    pub fn new(base: &PathBuf) -> Result<Self> {
+    pub fn new(base: &Path) -> Result<Self> {
         let id = uuid::Uuid::new_v4().to_string();
         let path = base.join(&id);
         std::fs::create_dir_all(&path)?;
@@ -60,6 +277,7 @@ impl IsolatedWorkspace {
         Ok(Self {
             path,
             cleanup_on_drop: true,
+            _gauge: crate::metrics::registry().track_workspace(),
         })
     }
 
@@ -75,12 +293,29 @@ impl IsolatedWorkspace {
 
     /// Copy a file into the workspace input directory
     pub fn copy_input(&self, source: &PathBuf, name: &str) -> Result<PathBuf> {
-        let dest = self.input_dir().join(name);
+        let dest = self.input_dir().join(checked_input_name(name)?);
         std::fs::copy(source, &dest)?;
         info!("[SANDBOX] Copied input file: {:?} -> {:?}", source, dest);
         Ok(dest)
     }
 
+    /// Write the `inputs` of an execution for the wrapper to load
+    pub fn write_inputs(&self, inputs: &serde_json::Value) -> Result<()> {
+        std::fs::write(
+            self.path.join(WORKSPACE_INPUTS_FILE),
+            serde_json::to_vec(inputs)?,
+        )?;
+        Ok(())
+    }
+
+    /// Copy the `input_files` of an execution into the input directory
+    pub fn stage_inputs(&self, files: &[(PathBuf, String)]) -> Result<()> {
+        for (source, name) in files {
+            self.copy_input(source, name)?;
+        }
+        Ok(())
+    }
+
     /// Copy a file from the workspace output directory
     pub fn copy_output(&self, name: &str, dest: &PathBuf) -> Result<()> {
         let source = self.output_dir().join(name);
@@ -109,11 +344,104 @@ impl IsolatedWorkspace {
     pub fn keep(&mut self) {
         self.cleanup_on_drop = false;
     }
+
+    /// Copy the workspace's current contents aside, to roll back to with
+    /// [`restore`](Self::restore)
+    ///
+    /// Files are copied with the platform's copy, which clones them on
+    /// copy-on-write filesystems. Symlinks are copied as links, never
+    /// followed, so code can't pull host files into a snapshot.
+    pub fn snapshot(&self) -> Result<SnapshotId> {
+        let id = SnapshotId(uuid::Uuid::new_v4().to_string());
+        let dest = self.snapshot_dir(&id);
+        std::fs::create_dir_all(&dest)?;
+        if let Err(e) = copy_tree(&self.path, &dest) {
+            let _ = std::fs::remove_dir_all(&dest);
+            return Err(e);
+        }
+        info!("[SANDBOX] Snapshot {} of workspace {:?}", id, self.path);
+        Ok(id)
+    }
+
+    /// Replace the workspace's contents with a snapshot's
+    ///
+    /// The snapshot stays available, so it can be restored again.
+    pub fn restore(&self, snapshot: &SnapshotId) -> Result<()> {
+        let source = self.snapshot_dir(snapshot);
+        if !source.is_dir() {
+            return Err(SandboxError::InternalError(format!(
+                "Unknown workspace snapshot: {}",
+                snapshot
+            )));
+        }
+        for entry in std::fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if std::fs::symlink_metadata(&path)?.is_dir() {
+                std::fs::remove_dir_all(&path)?;
+            } else {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        copy_tree(&source, &self.path)?;
+        info!(
+            "[SANDBOX] Restored workspace {:?} to {}",
+            self.path, snapshot
+        );
+        Ok(())
+    }
+
+    /// Delete a snapshot
+    pub fn discard_snapshot(&self, snapshot: &SnapshotId) -> Result<()> {
+        std::fs::remove_dir_all(self.snapshot_dir(snapshot))?;
+        Ok(())
+    }
+
+    /// Snapshots live next to the workspace, out of the code's reach
+    fn snapshots_root(&self) -> PathBuf {
+        self.path.with_extension("snapshots")
+    }
+
+    fn snapshot_dir(&self, snapshot: &SnapshotId) -> PathBuf {
+        self.snapshots_root().join(&snapshot.0)
+    }
+}
+
+/// Identifies a snapshot taken with [`IsolatedWorkspace::snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SnapshotId(String);
+
+impl std::fmt::Display for SnapshotId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Copy the contents of `source` into the existing directory `dest`
+fn copy_tree(source: &Path, dest: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            std::fs::create_dir(&to)?;
+            copy_tree(&from, &to)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(&from)?, &to)?;
+            #[cfg(not(unix))]
+            warn!("[SANDBOX] Skipping symlink {:?} in snapshot", from);
+        } else if file_type.is_file() {
+            std::fs::copy(&from, &to)?;
+        }
+    }
+    Ok(())
 }
 
 impl Drop for IsolatedWorkspace {
     fn drop(&mut self) {
         if self.cleanup_on_drop {
+            let _ = std::fs::remove_dir_all(self.snapshots_root());
             if let Err(e) = std::fs::remove_dir_all(&self.path) {
                 warn!(
                     "[SANDBOX] Failed to cleanup workspace {:?}: {}",
@@ -126,11 +454,37 @@ impl Drop for IsolatedWorkspace {
     }
 }
 
+/// Input file names must stay inside the input directory
+fn checked_input_name(name: &str) -> Result<&str> {
+    match Path::new(name).file_name() {
+        Some(file_name) if file_name == name => Ok(name),
+        _ => Err(SandboxError::SecurityViolation(format!(
+            "Invalid input file name: {}",
+            name
+        ))),
+    }
+}
+
+/// Copy `files` into a new temporary directory for engines without a
+/// workspace; `None` when there are no files
+pub(crate) fn stage_input_dir(files: &[(PathBuf, String)]) -> Result<Option<tempfile::TempDir>> {
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let dir = tempfile::Builder::new()
+        .prefix("pysandbox-input-")
+        .tempdir()?;
+    for (source, name) in files {
+        std::fs::copy(source, dir.path().join(checked_input_name(name)?))?;
+    }
+    Ok(Some(dir))
+}
+
 /// Platform-sandboxed Python engine with workspace isolation
 ///
 /// On macOS: Uses sandbox-exec with a restrictive profile
 /// On Windows: Uses Job Objects + Restricted Token (TODO)
-/// On Linux: Uses seccomp/bubblewrap (TODO)
+/// On Linux: Uses bubblewrap, when installed, with a read-only root
 pub struct SandboxedPythonEngine {
     config: SandboxConfig,
 }
@@ -167,7 +521,7 @@ impl SandboxedPythonEngine {
     }
 
     /// Generate import control code based on policy
-    fn generate_import_control(&self, policy: &ImportPolicy) -> String {
+    pub(crate) fn generate_import_control(policy: &ImportPolicy) -> String {
         // Same implementation as NativePythonEngine
         match policy {
             ImportPolicy::Blacklist(blacklist) => {
@@ -188,24 +542,24 @@ impl SandboxedPythonEngine {
 import builtins
 import sys
 
+WHITELIST = None
 BLACKLIST = {blacklist}
+VERSION_REQUIREMENTS = {{}}
 
 original_import = builtins.__import__
 
-def safe_import(name, globals=None, locals=None, fromlist=(), level=0):
-    if level > 0:
-        return original_import(name, globals, locals, fromlist, level)
-    root_module = name.split('.')[0]
-    if root_module in BLACKLIST:
-        raise ImportError(f"Module '{{root_module}}' is blacklisted for safety")
-    return original_import(name, globals, locals, fromlist, level)
+{check}
 
 builtins.__import__ = safe_import
-"#,
-                    blacklist = blacklist_str
+{finder}"#,
+                    blacklist = blacklist_str,
+                    check = IMPORT_POLICY_CHECK,
+                    finder = IMPORT_POLICY_FINDER,
                 )
             }
             ImportPolicy::Whitelist(whitelist) => {
+                let requirements = python_version_requirements(Some(whitelist));
+                let whitelist = &whitelisted_modules(whitelist);
                 let whitelist_str = if whitelist.is_empty() {
                     "set()".to_string()
                 } else {
@@ -226,26 +580,27 @@ import json
 import re
 
 WHITELIST = {whitelist}
+BLACKLIST = None
+VERSION_REQUIREMENTS = {requirements}
 
 original_import = builtins.__import__
 
-def safe_import(name, globals=None, locals=None, fromlist=(), level=0):
-    if level > 0:
-        return original_import(name, globals, locals, fromlist, level)
-    root_module = name.split('.')[0]
-    if root_module not in WHITELIST and root_module != 'builtins':
-        raise ImportError(f"Module '{{root_module}}' is not in whitelist")
-    return original_import(name, globals, locals, fromlist, level)
+{check}
 
 builtins.__import__ = safe_import
-"#,
-                    whitelist = whitelist_str
+{finder}"#,
+                    whitelist = whitelist_str,
+                    requirements = requirements,
+                    check = IMPORT_POLICY_CHECK,
+                    finder = IMPORT_POLICY_FINDER,
                 )
             }
             ImportPolicy::Both {
                 whitelist,
                 blacklist,
             } => {
+                let requirements = python_version_requirements(Some(whitelist));
+                let whitelist = &whitelisted_modules(whitelist);
                 let whitelist_str = if whitelist.is_empty() {
                     "set()".to_string()
                 } else {
@@ -279,51 +634,44 @@ import re
 
 WHITELIST = {whitelist}
 BLACKLIST = {blacklist}
+VERSION_REQUIREMENTS = {requirements}
 
 original_import = builtins.__import__
 
-def safe_import(name, globals=None, locals=None, fromlist=(), level=0):
-    if level > 0:
-        return original_import(name, globals, locals, fromlist, level)
-    root_module = name.split('.')[0]
-    if root_module in BLACKLIST:
-        raise ImportError(f"Module '{{root_module}}' is blacklisted")
-    if root_module not in WHITELIST and root_module != 'builtins':
-        raise ImportError(f"Module '{{root_module}}' is not in whitelist")
-    return original_import(name, globals, locals, fromlist, level)
+{check}
 
 builtins.__import__ = safe_import
-"#,
+{finder}"#,
                     whitelist = whitelist_str,
-                    blacklist = blacklist_str
+                    requirements = requirements,
+                    blacklist = blacklist_str,
+                    check = IMPORT_POLICY_CHECK,
+                    finder = IMPORT_POLICY_FINDER,
                 )
             }
         }
     }
 
     /// Generate network control code based on optional host allowlist
-    fn generate_network_control(&self, allowlist: Option<&[String]>) -> String {
+    ///
+    /// Hostnames are matched against the allowlist and the entries' ports. A
+    /// raw IP is only allowed if an allowed host resolved to it, so
+    /// connecting by address can't bypass the host check. An empty allowlist
+    /// refuses every connection, Unix sockets included.
+    pub(crate) fn generate_network_control(
+        allowlist: Option<&[String]>,
+        max_connections: Option<u32>,
+    ) -> String {
         let Some(allowlist) = allowlist else {
             return String::new();
         };
-        if allowlist.is_empty() {
-            return String::new();
-        }
-
-        let allowlist_str = format!(
-            "[{}]",
-            allowlist
-                .iter()
-                .map(|s| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")))
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
 
         format!(
             r#"
 _RZN_NETWORK_ALLOWLIST = {allowlist}
+_RZN_MAX_CONNECTIONS = {max_connections}
 
-if _RZN_NETWORK_ALLOWLIST:
+if _RZN_NETWORK_ALLOWLIST is not None:
     try:
         import socket
     except Exception:
@@ -335,60 +683,448 @@ if _RZN_NETWORK_ALLOWLIST:
                 return ""
             return str(value).strip().lower().rstrip(".")
 
-        def _rzn_host_allowed(host):
-            h = _rzn_norm_host(host)
-            if not h:
+        def _rzn_rule_matches(pattern, h):
+            if pattern == "*":
                 return True
-            for pattern in _RZN_NETWORK_ALLOWLIST:
-                p = _rzn_norm_host(pattern)
-                if not p:
-                    continue
-                if p == "*":
-                    return True
-                if p.startswith("*."):
-                    base = p[2:]
-                    if h == base or h.endswith("." + base):
-                        return True
-                elif h == p:
-                    return True
-            return False
+            if pattern.startswith("*."):
+                base = pattern[2:]
+                return h == base or h.endswith("." + base)
+            return h == pattern
+
+        def _rzn_rule_ports(host):
+            # Ports rules for `host` allow; None in the set means any
+            h = _rzn_norm_host(host).strip("[]")
+            return {{port for pattern, port in _RZN_NETWORK_ALLOWLIST if _rzn_rule_matches(pattern, h)}}
+
+        def _rzn_host_allowed(host, port=None):
+            if not _rzn_norm_host(host):
+                return True
+            ports = _rzn_rule_ports(host)
+            return bool(ports) and (port is None or None in ports or port in ports)
 
         def _rzn_host_from_address(address):
             if isinstance(address, tuple) and len(address) > 0:
                 return address[0]
             return None
 
+        def _rzn_port_from_address(address):
+            if isinstance(address, tuple) and len(address) > 1 and isinstance(address[1], int):
+                return address[1]
+            return None
+
+        def _rzn_ip(value):
+            # Canonical form of an IP literal, or None for a hostname
+            h = _rzn_norm_host(value).strip("[]").split("%")[0]
+            for family in (socket.AF_INET, socket.AF_INET6):
+                try:
+                    return socket.inet_ntop(family, socket.inet_pton(family, h))
+                except (OSError, ValueError):
+                    pass
+            return None
+
+        def _rzn_in_network(ip, cidr):
+            # Whether canonical `ip` lies in an `address/bits` range
+            network, _, bits = cidr.partition("/")
+            for family in (socket.AF_INET, socket.AF_INET6):
+                try:
+                    a = socket.inet_pton(family, ip)
+                    b = socket.inet_pton(family, network)
+                except (OSError, ValueError):
+                    continue
+                whole, rest = divmod(int(bits), 8)
+                if a[:whole] != b[:whole]:
+                    return False
+                return not rest or (a[whole] >> (8 - rest)) == (b[whole] >> (8 - rest))
+            return False
+
         _rzn_orig_getaddrinfo = socket.getaddrinfo
+        # Addresses allowed hosts resolved to, with the ports their rules
+        # allow. A connect to a raw IP must hit one, so an address can't
+        # stand in for a host off the allowlist.
+        _rzn_pinned_ips = {{}}
+        _rzn_pins_resolved = []
+
+        def _rzn_pin(infos, ports):
+            for info in infos:
+                try:
+                    ip = _rzn_ip(info[4][0])
+                except (IndexError, TypeError):
+                    continue
+                if ip is not None:
+                    _rzn_pinned_ips.setdefault(ip, set()).update(ports)
+            return infos
+
+        def _rzn_resolve_pins():
+            # Exact entries are resolved once, on the first address check;
+            # wildcard entries pin whatever their subdomains resolve to
+            if _rzn_pins_resolved:
+                return
+            _rzn_pins_resolved.append(True)
+            for pattern, port in _RZN_NETWORK_ALLOWLIST:
+                if pattern.startswith("*") or "/" in pattern:
+                    continue
+                literal = _rzn_ip(pattern)
+                if literal is not None:
+                    _rzn_pinned_ips.setdefault(literal, set()).add(port)
+                    continue
+                try:
+                    _rzn_pin(_rzn_orig_getaddrinfo(pattern, None), {{port}})
+                except OSError:
+                    pass
+
+        def _rzn_address_allowed(host, port):
+            ip = _rzn_ip(host)
+            if ip is None:
+                return _rzn_host_allowed(host, port)
+            _rzn_resolve_pins()
+            # `*` entries match any address, ranges the addresses inside
+            # them, others only pinned ones
+            ports = {{
+                p for pattern, p in _RZN_NETWORK_ALLOWLIST
+                if pattern == "*" or ("/" in pattern and _rzn_in_network(ip, pattern))
+            }}
+            ports |= _rzn_pinned_ips.get(ip, set())
+            return bool(ports) and (port is None or None in ports or port in ports)
+
+        def _rzn_check_address(address):
+            host = _rzn_host_from_address(address)
+            if not _RZN_NETWORK_ALLOWLIST:
+                raise _rzn_denied(PermissionError("Network access is blocked"), "network", host)
+            if not _rzn_address_allowed(host, _rzn_port_from_address(address)):
+                raise _rzn_denied(PermissionError(f"Network host not allowed: {{host}}"), "network", host)
+
+        _rzn_connections = [0]
+        def _rzn_count_connection(address):
+            # Only connections leaving the machine count against the budget
+            if _RZN_MAX_CONNECTIONS is None or _rzn_host_from_address(address) is None:
+                return
+            _rzn_connections[0] += 1
+            if _rzn_connections[0] > _RZN_MAX_CONNECTIONS:
+                raise _rzn_denied(
+                    PermissionError(f"Network connection budget of {{_RZN_MAX_CONNECTIONS}} exhausted"),
+                    "network",
+                    _rzn_host_from_address(address),
+                )
+
         def _rzn_guarded_getaddrinfo(host, *args, **kwargs):
+            if _rzn_ip(host) is not None:
+                _rzn_check_address((host,))
+                return _rzn_orig_getaddrinfo(host, *args, **kwargs)
             if not _rzn_host_allowed(host):
-                raise PermissionError(f"Network host not allowed: {{host}}")
-            return _rzn_orig_getaddrinfo(host, *args, **kwargs)
+                raise _rzn_denied(PermissionError(f"Network host not allowed: {{host}}"), "network", host)
+            return _rzn_pin(_rzn_orig_getaddrinfo(host, *args, **kwargs), _rzn_rule_ports(host))
         socket.getaddrinfo = _rzn_guarded_getaddrinfo
 
         _rzn_orig_create_connection = socket.create_connection
         def _rzn_guarded_create_connection(address, *args, **kwargs):
-            host = _rzn_host_from_address(address)
-            if not _rzn_host_allowed(host):
-                raise PermissionError(f"Network host not allowed: {{host}}")
+            _rzn_check_address(address)
             return _rzn_orig_create_connection(address, *args, **kwargs)
         socket.create_connection = _rzn_guarded_create_connection
 
         _rzn_orig_socket_connect = socket.socket.connect
         def _rzn_guarded_socket_connect(sock, address):
-            host = _rzn_host_from_address(address)
-            if not _rzn_host_allowed(host):
-                raise PermissionError(f"Network host not allowed: {{host}}")
+            _rzn_check_address(address)
+            _rzn_count_connection(address)
             return _rzn_orig_socket_connect(sock, address)
         socket.socket.connect = _rzn_guarded_socket_connect
+
+        _rzn_orig_socket_connect_ex = socket.socket.connect_ex
+        def _rzn_guarded_socket_connect_ex(sock, address):
+            _rzn_check_address(address)
+            _rzn_count_connection(address)
+            return _rzn_orig_socket_connect_ex(sock, address)
+        socket.socket.connect_ex = _rzn_guarded_socket_connect_ex
+
+        _rzn_orig_socket_sendto = socket.socket.sendto
+        def _rzn_guarded_socket_sendto(sock, *args):
+            _rzn_check_address(args[-1] if args else None)
+            return _rzn_orig_socket_sendto(sock, *args)
+        socket.socket.sendto = _rzn_guarded_socket_sendto
+"#,
+            allowlist = crate::policy::python_network_rules(allowlist),
+            max_connections = max_connections.map_or("None".to_string(), |n| n.to_string()),
+        )
+    }
+
+    /// Generate code that refuses native extensions outside the import whitelist
+    ///
+    /// C extensions run below every Python-level guard, so when `enabled` the
+    /// `ctypes` and `cffi` backends and third-party extension modules only load
+    /// if their package is whitelisted. Extension modules shipped with the
+    /// standard library stay loadable. Must run before the import guard, which
+    /// may block the modules it needs.
+    pub(crate) fn generate_native_extension_control(
+        enabled: bool,
+        policy: &ImportPolicy,
+    ) -> String {
+        if !enabled {
+            return String::new();
+        }
+        let allowed = match policy {
+            ImportPolicy::Blacklist(_) => Vec::new(),
+            ImportPolicy::Whitelist(whitelist) => {
+                whitelisted_modules(whitelist).into_iter().collect()
+            }
+            ImportPolicy::Both {
+                whitelist,
+                blacklist,
+            } => whitelisted_modules(whitelist)
+                .into_iter()
+                .filter(|module| !blacklist.contains(module))
+                .collect(),
+        };
+        let allowed_str = if allowed.is_empty() {
+            "set()".to_string()
+        } else {
+            format!(
+                "{{{}}}",
+                allowed
+                    .iter()
+                    .map(|s| format!("'{}'", s))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+
+        format!(
+            r#"
+def _rzn_guard_native_extensions(allowed):
+    import _imp
+    import os
+
+    if not hasattr(_imp, "create_dynamic"):
+        return
+
+    # Every extension load, including ExtensionFileLoader used directly, ends here
+    create_dynamic = _imp.create_dynamic
+    stdlib_dynload = os.path.realpath(os.path.join(os.path.dirname(os.__file__), "lib-dynload"))
+    backends = {{"_ctypes": "ctypes", "_cffi_backend": "cffi"}}
+
+    def guarded_create_dynamic(spec, *args):
+        root = spec.name.partition(".")[0]
+        origin = os.path.realpath(spec.origin or "")
+        stem = os.path.basename(origin).partition(".")[0]
+        backend = backends.get(stem) or backends.get(spec.name.rpartition(".")[2])
+        if backend:
+            permitted = backend in allowed
+        else:
+            permitted = root in allowed or os.path.dirname(origin) == stdlib_dynload
+        if not permitted:
+            raise _rzn_denied(
+                ImportError(f"Native extension '{{spec.name}}' is blocked by the sandbox"),
+                "import",
+                spec.name,
+            )
+        return create_dynamic(spec, *args)
+
+    _imp.create_dynamic = guarded_create_dynamic
+
+_rzn_guard_native_extensions({allowed})
+del _rzn_guard_native_extensions
 "#,
-            allowlist = allowlist_str
+            allowed = allowed_str
         )
     }
 
+    /// Build the Python wrapper that runs user code against a workspace
+    ///
+    /// The workspace root is read from `SANDBOX_WORKSPACE` at runtime, so the same
+    /// wrapper works for host paths and container mounts. `stream_output` tees
+    /// user output to stderr for engines that run under the process supervisor,
+    /// and each captured stream keeps at most `max_output_bytes`. A policy with
+    /// `audit_hooks` set adds the audit hook guard, armed just before user code.
+    /// The output is reported through `channel`, which should be
+    /// [`ResultChannel::in_workspace`].
+    pub(crate) fn workspace_wrapper(
+        code: &str,
+        options: &ExecutionOptions,
+        stream_output: bool,
+        max_output_bytes: usize,
+        policy: Option<&SandboxPolicy>,
+        channel: &ResultChannel,
+    ) -> Result<String> {
+        let policy = policy.filter(|p| p.audit_hooks);
+        Ok(format!(
+            r#"
+# Wrapper-only modules are imported before the import guard is installed,
+# so blocked modules like `os` stay usable here without being exposed by name
+import os as _os
+{}{}
+
+# Audit hook setup
+{}
+
+# Security setup
+{}{}
+
+# Network setup
+{}
+
+# Input setup
+import json
+import sys
+import base64
+from io import StringIO
+
+# Set workspace paths as environment variables for the code
+workspace_path = _os.environ.get('SANDBOX_WORKSPACE', '.')
+
+# Inputs, written to the workspace by the host
+with open(_os.path.join(workspace_path, "{}"), encoding="utf-8") as _rzn_inputs_file:
+    inputs = json.load(_rzn_inputs_file)
+del _rzn_inputs_file
+input_dir = _os.path.join(workspace_path, 'input')
+output_dir = _os.path.join(workspace_path, 'output')
+
+# Make these available to user code
+WORKSPACE = workspace_path
+INPUT_DIR = input_dir
+OUTPUT_DIR = output_dir
+
+# Stdin staged by engines that can't pipe it
+if _os.path.isfile(_os.path.join(workspace_path, "{}")):
+    sys.stdin = open(_os.path.join(workspace_path, "{}"), encoding="utf-8")
+
+# Capture stdout/stderr
+{}
+_original_stdout = sys.stdout
+_original_stderr = sys.stderr
+sys.stdout = _captured_stdout
+sys.stderr = _captured_stderr
+
+_exec_result = None
+_exec_error = None
+
+# User code execution
+{}
+_rzn_code_line = _rzn_sys._getframe().f_lineno + 2
+try:
+    {}
+
+    # Capture result variable if set
+    if 'result' in dir() or 'result' in locals():
+        _exec_result = result
+except Exception as e:
+    _exec_error = f"{{type(e).__name__}}: {{e}}"
+    _exec_traceback = _rzn_traceback.format_exc()
+    _exec_denial = _rzn_denial_of(e)
+
+# Restore stdout/stderr
+sys.stdout = _original_stdout
+sys.stderr = _original_stderr
+
+# Serialize the result first, so a large DataFrame written to output_dir is
+# listed with the other output files
+try:
+    _result_value = _rzn_result_value(_exec_result, output_dir)
+except Exception as e:
+    _result_value = {{"type": "unserializable", "repr": str(e)}}
+
+# Collect output files
+_output_files = []
+if _os.path.exists(output_dir):
+    _output_files = _os.listdir(output_dir)
+
+# Output structured result
+_output = {{
+    "stdout": _captured_stdout.getvalue() or None,
+    "stderr": _captured_stderr.getvalue() or None,
+    "result": _result_value,
+    "error": _exec_error,
+    "output_files": _output_files,
+    "workspace": workspace_path
+}}
+if _exec_error:
+    _output["traceback"] = _exec_traceback
+    _output["code_line"] = _rzn_code_line
+    if _exec_denial:
+        _output["denial"] = _exec_denial
+{}
+# Spill a large result into the workspace; the host moves it out
+_rzn_spill_result(_output, _os.path.join(workspace_path, "{}"), "{}", {})
+{}
+
+if _exec_error:
+    sys.exit(1)
+"#,
+            RESULT_SERIALIZER,
+            DENIAL_MARKER,
+            policy.map(render_audit_hook).unwrap_or_default(),
+            Self::generate_native_extension_control(
+                options.block_native_extensions,
+                &options.import_policy
+            ),
+            Self::generate_import_control(&options.import_policy),
+            Self::generate_network_control(
+                options.network_allowlist.as_deref(),
+                options.network_budget.and_then(|b| b.max_connections)
+            ),
+            WORKSPACE_INPUTS_FILE,
+            WORKSPACE_STDIN_FILE,
+            WORKSPACE_STDIN_FILE,
+            output_capture(stream_output, max_output_bytes),
+            if policy.is_some() { ARM_AUDIT_HOOK } else { "" },
+            code.replace('\n', "\n    "),
+            MARK_TRUNCATED,
+            result_file::WORKSPACE_RESULT_FILE,
+            result_file::WORKSPACE_RESULT_FILE,
+            result_file::SPILL_THRESHOLD_BYTES,
+            channel.report(
+                "_output",
+                &format!(
+                    "_os.path.join(workspace_path, \"{}\")",
+                    WORKSPACE_OUTPUT_FILE
+                )
+            ),
+        ))
+    }
+
+    /// Output limit from the limits, tightened by the policy if one is set
+    fn max_output_bytes(&self) -> usize {
+        let limit = self.config.limits.max_output_bytes;
+        match &self.config.policy {
+            Some(policy) => limit.min(policy.resources.max_output_bytes),
+            None => limit,
+        }
+    }
+
+    /// Hand-written profile path, from the config or the policy
+    fn configured_profile(&self) -> Option<&Path> {
+        self.config.sandbox_profile.as_deref().or_else(|| {
+            self.config
+                .policy
+                .as_ref()
+                .and_then(|p| p.custom_sandbox_profile.as_deref())
+        })
+    }
+
+    /// Render a profile from the policy when no hand-written profile is configured
+    fn generate_profile(&self, proxy_port: Option<u16>) -> Result<Option<tempfile::NamedTempFile>> {
+        if !cfg!(target_os = "macos") || self.configured_profile().is_some() {
+            return Ok(None);
+        }
+        match &self.config.policy {
+            Some(policy) => {
+                let file = crate::seatbelt::write_profile_with(policy, proxy_port)?;
+                info!(
+                    "[SANDBOX] Generated sandbox profile from policy '{}': {:?}",
+                    policy.name,
+                    file.path()
+                );
+                Ok(Some(file))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Build the command to execute Python in a sandbox
     #[cfg(target_os = "macos")]
-    fn build_sandboxed_command(&self, workspace: &IsolatedWorkspace) -> Command {
-        if let Some(profile) = &self.config.sandbox_profile {
+    fn build_sandboxed_command(
+        &self,
+        workspace: &IsolatedWorkspace,
+        generated_profile: Option<&Path>,
+    ) -> Command {
+        if let Some(profile) = self.configured_profile().or(generated_profile) {
             if profile.exists() {
                 // Use sandbox-exec with the profile
                 let mut cmd = Command::new("sandbox-exec");
@@ -405,8 +1141,11 @@ if _RZN_NETWORK_ALLOWLIST:
                         .unwrap()
                         .display()
                 ));
+                // Seatbelt matches real paths, so resolve e.g. /var -> /private/var
+                let workspace_path = std::fs::canonicalize(&workspace.path)
+                    .unwrap_or_else(|_| workspace.path.clone());
                 cmd.arg("-D")
-                    .arg(format!("WORKSPACE={}", workspace.path.display()));
+                    .arg(format!("WORKSPACE={}", workspace_path.display()));
                 cmd.arg("-D")
                     .arg(format!("TMPDIR={}", std::env::temp_dir().display()));
 
@@ -432,7 +1171,11 @@ if _RZN_NETWORK_ALLOWLIST:
     }
 
     #[cfg(target_os = "windows")]
-    fn build_sandboxed_command(&self, _workspace: &IsolatedWorkspace) -> Command {
+    fn build_sandboxed_command(
+        &self,
+        _workspace: &IsolatedWorkspace,
+        _generated_profile: Option<&Path>,
+    ) -> Command {
         // TODO: Implement Windows Job Objects + Restricted Token
         // For now, just run Python directly
         warn!("[SANDBOX] Windows sandboxing not yet implemented, running unsandboxed");
@@ -440,49 +1183,162 @@ if _RZN_NETWORK_ALLOWLIST:
     }
 
     #[cfg(target_os = "linux")]
-    fn build_sandboxed_command(&self, workspace: &IsolatedWorkspace) -> Command {
-        // TODO: Implement bubblewrap or seccomp sandboxing
-        // For now, just run Python directly
-        warn!("[SANDBOX] Linux sandboxing not yet implemented, running unsandboxed");
+    fn build_sandboxed_command(
+        &self,
+        workspace: &IsolatedWorkspace,
+        _generated_profile: Option<&Path>,
+    ) -> Command {
+        // TODO: seccomp filter for native extension loading to back
+        // `block_native_extensions`
+        if let Some(bwrap) = bubblewrap() {
+            let network_blocked = self
+                .config
+                .policy
+                .as_ref()
+                .is_some_and(|p| matches!(p.network, crate::policy::NetworkPolicy::Blocked));
+            let mut cmd = Command::new(bwrap);
+            cmd.args(bubblewrap_args(&workspace.path, network_blocked))
+                .arg(&self.config.python_path)
+                // The rest of the host is read-only, so temp files go to the workspace
+                .env("TMPDIR", &workspace.path);
+            info!(
+                "[SANDBOX] Using bubblewrap with a read-only root: {:?}",
+                bwrap
+            );
+            return cmd;
+        }
+        warn!("[SANDBOX] bubblewrap not available, running unsandboxed");
         Command::new(&self.config.python_path)
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
-    fn build_sandboxed_command(&self, _workspace: &IsolatedWorkspace) -> Command {
+    fn build_sandboxed_command(
+        &self,
+        _workspace: &IsolatedWorkspace,
+        _generated_profile: Option<&Path>,
+    ) -> Command {
         Command::new(&self.config.python_path)
     }
 }
 
-#[async_trait]
-impl PythonEngine for SandboxedPythonEngine {
-    async fn validate(&self, code: &str, _options: &ExecutionOptions) -> Result<()> {
-        // Basic syntax validation
-        let validation_code = format!(
-            r#"
-import ast
+/// The `bwrap` binary, if it's installed and can create a sandbox here
+#[cfg(target_os = "linux")]
+fn bubblewrap() -> Option<&'static Path> {
+    static BWRAP: std::sync::OnceLock<Option<PathBuf>> = std::sync::OnceLock::new();
+    BWRAP
+        .get_or_init(|| {
+            let path = which::which("bwrap").ok()?;
+            // Fails without unprivileged user namespaces, e.g. in some containers
+            let status = std::process::Command::new(&path)
+                .args(["--ro-bind", "/", "/", "true"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .ok()?;
+            status.success().then_some(path)
+        })
+        .as_deref()
+}
+
+/// `bwrap` arguments, up to the command, that mount the host read-only and
+/// only the workspace writable
+///
+/// The read-only root covers the interpreter prefix and every managed env's
+/// site-packages, so code can't trojan installed packages that later runs or
+/// the host will import.
+#[cfg(target_os = "linux")]
+fn bubblewrap_args(workspace: &Path, network_blocked: bool) -> Vec<std::ffi::OsString> {
+    let mut args: Vec<std::ffi::OsString> = [
+        "--die-with-parent",
+        "--unshare-ipc",
+        "--ro-bind",
+        "/",
+        "/",
+        "--dev",
+        "/dev",
+        "--proc",
+        "/proc",
+        "--bind",
+    ]
+    .into_iter()
+    .map(Into::into)
+    .collect();
+    args.push(workspace.into());
+    args.push(workspace.into());
+    if network_blocked {
+        args.push("--unshare-net".into());
+    }
+    args.push("--".into());
+    args
+}
+
+/// Static analysis run by `validate` before any interpreter is spawned
+///
+/// Checks the code against `options.import_policy` plus the analyzer's call,
+/// dunder and `exec`/`eval` rules, and `options.code_limits`. Returns `None`
+/// when the interpreter has to check the code itself: without the
+/// `static-analyzer` feature, for an unrestricted policy, or when the grammar
+/// rejects the syntax (it can lag behind the interpreter, which gets the
+/// final say).
+#[cfg(feature = "static-analyzer")]
+pub(crate) fn static_precheck(
+    code: &str,
+    options: &ExecutionOptions,
+) -> Result<Option<Vec<Diagnostic>>> {
+    options.code_limits.check_source(code)?;
+    if matches!(&options.import_policy, ImportPolicy::Blacklist(blacklist) if blacklist.is_empty())
+    {
+        return Ok(None);
+    }
+    let analyzer = crate::analyzer::StaticAnalyzer::new(options.import_policy.clone())
+        .code_limits(options.code_limits.clone());
+    match analyzer.analyze(code) {
+        Ok(findings) => Ok(Some(findings.into_iter().map(Diagnostic::from).collect())),
+        Err(e @ SandboxError::CodeTooComplex(_)) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "static-analyzer"))]
+pub(crate) fn static_precheck(
+    code: &str,
+    options: &ExecutionOptions,
+) -> Result<Option<Vec<Diagnostic>>> {
+    options.code_limits.check_source(code)?;
+    Ok(None)
+}
+
+/// Check syntax with the interpreter's own parser
+pub(crate) async fn syntax_diagnostics(python: &Path, code: &str) -> Result<Vec<Diagnostic>> {
+    const CHECK_SYNTAX: &str = r#"
+import ast, json, sys
 try:
-    ast.parse('''{}''')
-    print("OK")
+    ast.parse(sys.argv[1])
 except SyntaxError as e:
-    print(f"SYNTAX_ERROR: {{e}}")
-"#,
-            code.replace("'''", "\\'\\'\\'")
-        );
-
-        let output = Command::new(&self.config.python_path)
-            .arg("-c")
-            .arg(&validation_code)
-            .output()
-            .await?;
+    print(json.dumps({"rule": "syntax_error", "message": str(e), "line": e.lineno, "col": e.offset, "severity": "error"}))
+"#;
+
+    let output = Command::new(python)
+        .arg("-c")
+        .arg(CHECK_SYNTAX)
+        .arg(code)
+        .output()
+        .await?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if stdout.starts_with("SYNTAX_ERROR:") {
-            return Err(SandboxError::SyntaxError(
-                stdout.trim_start_matches("SYNTAX_ERROR: ").to_string(),
-            ));
+#[async_trait]
+impl PythonEngine for SandboxedPythonEngine {
+    async fn validate(&self, code: &str, options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        if let Some(diagnostics) = static_precheck(code, options)? {
+            return Ok(diagnostics);
         }
-
-        Ok(())
+        syntax_diagnostics(&self.config.python_path, code).await
     }
 
     async fn execute(
@@ -490,118 +1346,37 @@ except SyntaxError as e:
         code: &str,
         inputs: serde_json::Value,
         options: &ExecutionOptions,
-    ) -> Result<serde_json::Value> {
+    ) -> Result<ExecutionResult> {
         // Validate first
-        self.validate(code, options).await?;
+        let setup = SetupClock::start(options);
+        ensure_valid(setup.within(self.validate(code, options)).await?)?;
 
         // Create isolated workspace
         let workspace = IsolatedWorkspace::new(&self.config.workspace_base)?;
+        let channel = ResultChannel::in_workspace(&workspace.path);
 
-        // Copy any configured input files to the workspace
-        for (source, name) in &self.config.input_files {
-            workspace.copy_input(source, name)?;
-        }
+        workspace.write_inputs(&inputs)?;
+        workspace.stage_inputs(&options.input_files)?;
+        let egress = EgressProxy::for_options(options).await?;
 
         // Create execution wrapper
-        let wrapper_code = format!(
-            r#"
-# Security setup
-{}
-
-# Network setup
-{}
-
-# Input setup
-import json
-import sys
-import os
-import base64
-from io import StringIO
-
-inputs = json.loads('''{}''')
-
-# Set workspace paths as environment variables for the code
-workspace_path = os.environ.get('SANDBOX_WORKSPACE', '.')
-input_dir = os.path.join(workspace_path, 'input')
-output_dir = os.path.join(workspace_path, 'output')
-
-# Make these available to user code
-WORKSPACE = workspace_path
-INPUT_DIR = input_dir
-OUTPUT_DIR = output_dir
-
-# Capture stdout/stderr
-_captured_stdout = StringIO()
-_captured_stderr = StringIO()
-_original_stdout = sys.stdout
-_original_stderr = sys.stderr
-sys.stdout = _captured_stdout
-sys.stderr = _captured_stderr
-
-_exec_result = None
-_exec_error = None
-
-# User code execution
-try:
-    {}
-
-    # Capture result variable if set
-    if 'result' in dir() or 'result' in locals():
-        _exec_result = result
-except Exception as e:
-    _exec_error = f"{{type(e).__name__}}: {{e}}"
-
-# Restore stdout/stderr
-sys.stdout = _original_stdout
-sys.stderr = _original_stderr
-
-# Collect output files
-_output_files = []
-if os.path.exists(output_dir):
-    _output_files = os.listdir(output_dir)
-
-# Output structured result
-_output = {{
-    "stdout": _captured_stdout.getvalue() or None,
-    "stderr": _captured_stderr.getvalue() or None,
-    "result": None,
-    "error": _exec_error,
-    "output_files": _output_files,
-    "workspace": workspace_path
-}}
-
-if _exec_result is not None:
-    if isinstance(_exec_result, (dict, list, str, int, float, bool, type(None))):
-        _output["result"] = _exec_result
-    elif isinstance(_exec_result, (bytes, bytearray, memoryview)):
-        _bytes = bytes(_exec_result)
-        _output["result"] = {{
-            "type": "bytes",
-            "encoding": "base64",
-            "data": base64.b64encode(_bytes).decode("utf-8")
-        }}
-    else:
-        _output["result"] = {{"type": str(type(_exec_result).__name__), "repr": str(_exec_result)}}
-
-print("OUTPUT_JSON_START")
-print(json.dumps(_output))
-print("OUTPUT_JSON_END")
-
-if _exec_error:
-    sys.exit(1)
-"#,
-            self.generate_import_control(&options.import_policy),
-            self.generate_network_control(options.network_allowlist.as_deref()),
-            serde_json::to_string(&inputs)?.replace("'", "\\'"),
-            code.replace('\n', "\n    ")
-        );
-
-        // Build sandboxed command
-        let mut cmd = self.build_sandboxed_command(&workspace);
+        let wrapper_code = Self::workspace_wrapper(
+            code,
+            &guard_options(options, egress.as_ref()),
+            options.events.is_some(),
+            self.max_output_bytes(),
+            self.config.policy.as_ref(),
+            &channel,
+        )?;
+
+        // Build sandboxed command (the generated profile must outlive the spawn)
+        let generated_profile = self.generate_profile(egress.as_ref().map(EgressProxy::port))?;
+        let mut cmd =
+            self.build_sandboxed_command(&workspace, generated_profile.as_ref().map(|f| f.path()));
 
         cmd.arg("-c")
             .arg(&wrapper_code)
-            .stdin(Stdio::null())
+            .stdin(child_stdin(options.stdin.as_ref()))
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .env("PYTHONIOENCODING", "utf-8")
@@ -623,199 +1398,87 @@ if _exec_error:
         for (key, value) in &options.env_vars {
             cmd.env(key, value);
         }
+        if let Some(egress) = &egress {
+            egress.apply(&mut cmd);
+        }
 
-        fn resolve_export_base_dir() -> Option<PathBuf> {
-            if let Ok(v) = std::env::var("RZN_PYTHON_EXPORT_DIR") {
-                let trimmed = v.trim().to_string();
-                if !trimmed.is_empty() {
-                    return Some(PathBuf::from(trimmed));
-                }
-            }
-            if let Ok(v) = std::env::var("RZN_APP_BASE_DIR") {
-                let trimmed = v.trim().to_string();
-                if !trimmed.is_empty() {
-                    return Some(
-                        PathBuf::from(trimmed)
-                            .join("generated")
-                            .join("python_exports"),
-                    );
-                }
-            }
-            None
+        // Lead a process group so timeouts and cancellation reach grandchildren
+        #[cfg(unix)]
+        cmd.process_group(0);
+        apply_file_limits(
+            &mut cmd,
+            self.config.limits.max_open_files,
+            Some(self.config.limits.max_workspace_bytes),
+        );
+
+        // Execute with timeout
+        let setup = setup.finish()?;
+        let child = cmd.spawn()?;
+        let limits = SupervisorLimits {
+            max_output_bytes: Some(self.max_output_bytes()),
+            ..SupervisorLimits::for_limits(&self.config.limits)
         }
+        .with_workspace(&workspace.path, &self.config.limits);
+        let output = supervise(child, options, &limits).await?.with_setup(setup);
 
-        fn maybe_export_outputs(
-            workspace: &IsolatedWorkspace,
-        ) -> Option<(PathBuf, Vec<serde_json::Value>)> {
-            let export_base = resolve_export_base_dir()?;
-            if let Err(e) = std::fs::create_dir_all(&export_base) {
-                warn!(
-                    "[SANDBOX] Failed to create export base dir {:?}: {}",
-                    export_base, e
-                );
-                return None;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        // Check for sandbox violations (macOS)
+        if stderr.contains("deny") || stderr.contains("Sandbox") {
+            error!("[SANDBOX] Sandbox violation detected: {}", stderr);
+            return Err(SandboxError::SecurityViolation(
+                "Operation blocked by sandbox".to_string(),
+            ));
+        }
+
+        // Extract structured output
+        if let Some(mut parsed) = channel.take(&stdout) {
+            // Check if there was an execution error
+            if let Some(error) = execution_error(&parsed, code) {
+                return Err(error);
             }
+            result_file::persist_from_workspace(&mut parsed, &workspace.path)?;
 
-            let workspace_id = workspace
+            // Optional export: store OUTPUT_DIR files in an app-controlled
+            // artifact store (e.g., host-managed generated folder) and annotate
+            // the output.
+            let run_id = workspace
                 .path
                 .file_name()
                 .and_then(|s| s.to_str())
                 .unwrap_or("workspace");
-            let export_dir_path = export_base.join(workspace_id);
-            let mut export_dir: Option<PathBuf> = None;
-
-            let mut exported: Vec<serde_json::Value> = Vec::new();
-            let mut total_bytes: u64 = 0;
-            const MAX_FILES: usize = 32;
-            const MAX_TOTAL_BYTES: u64 = 200 * 1024 * 1024; // 200MB guard
-
-            let entries = match std::fs::read_dir(workspace.output_dir()) {
-                Ok(v) => v,
-                Err(e) => {
-                    warn!(
-                        "[SANDBOX] Failed to read workspace output dir {:?}: {}",
-                        workspace.output_dir(),
-                        e
-                    );
-                    return None;
-                }
-            };
-
-            for entry in entries {
-                let entry = match entry {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                };
-                if exported.len() >= MAX_FILES {
-                    break;
-                }
-                let Ok(ft) = entry.file_type() else { continue };
-                if !ft.is_file() || ft.is_symlink() {
-                    continue;
-                }
-                let file_name = entry.file_name();
-                let Some(file_name_str) = file_name.to_str() else {
-                    continue;
-                };
-                let src = entry.path();
-
-                if export_dir.is_none() {
-                    if let Err(e) = std::fs::create_dir_all(&export_dir_path) {
-                        warn!(
-                            "[SANDBOX] Failed to create export dir {:?}: {}",
-                            export_dir_path, e
-                        );
-                        return None;
-                    }
-                    export_dir = Some(export_dir_path.clone());
-                }
-
-                let Some(export_dir) = export_dir.as_ref() else {
-                    continue;
-                };
-                let dest = export_dir.join(&file_name);
-
-                if let Ok(meta) = std::fs::metadata(&src) {
-                    total_bytes = total_bytes.saturating_add(meta.len());
-                    if total_bytes > MAX_TOTAL_BYTES {
-                        warn!(
-                            "[SANDBOX] Output export size limit exceeded ({} bytes), stopping export",
-                            total_bytes
-                        );
-                        break;
-                    }
-                }
-
-                match std::fs::copy(&src, &dest) {
-                    Ok(_) => {
-                        let size = std::fs::metadata(&dest).ok().map(|m| m.len());
-                        exported.push(serde_json::json!({
-                            "name": file_name_str,
-                            "path": dest.to_string_lossy().to_string(),
-                            "size_bytes": size,
-                        }));
-                    }
-                    Err(e) => warn!("[SANDBOX] Failed to export {:?}: {}", src, e),
-                }
+            export_outputs(&mut parsed, run_id, &workspace.output_dir());
+            if let Some(egress) = &egress {
+                egress.report(&mut parsed);
             }
-
-            export_dir.map(|dir| (dir, exported))
+            output.attach_metrics(&mut parsed);
+            return ExecutionResult::from_value(parsed);
         }
 
-        // Execute with timeout
-        let child = cmd.spawn()?;
+        // Output that bypassed capture overran the pipe cap
+        if output.truncated {
+            return Err(SandboxError::RuntimeError(
+                "Output exceeded the configured size limit".to_string(),
+            ));
+        }
 
-        match tokio::time::timeout(options.timeout, child.wait_with_output()).await {
-            Ok(Ok(output)) => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let stderr = String::from_utf8_lossy(&output.stderr);
-
-                // Check for sandbox violations (macOS)
-                if stderr.contains("deny") || stderr.contains("Sandbox") {
-                    error!("[SANDBOX] Sandbox violation detected: {}", stderr);
-                    return Err(SandboxError::SecurityViolation(
-                        "Operation blocked by sandbox".to_string(),
-                    ));
-                }
-
-                // Extract structured output
-                if let Some(start) = stdout.find("OUTPUT_JSON_START") {
-                    if let Some(end) = stdout.find("OUTPUT_JSON_END") {
-                        let json_str = &stdout[start + 17..end].trim();
-                        if let Ok(mut parsed) = serde_json::from_str::<serde_json::Value>(json_str)
-                        {
-                            // Check if there was an execution error
-                            if let Some(error) = parsed.get("error").and_then(|e| e.as_str()) {
-                                if !error.is_empty() {
-                                    return Err(SandboxError::RuntimeError(error.to_string()));
-                                }
-                            }
-
-                            // Optional export: copy OUTPUT_DIR files into an app-controlled directory
-                            // (e.g., host-managed generated folder) and annotate the output.
-                            if let Some((export_dir, exported_files)) =
-                                maybe_export_outputs(&workspace)
-                            {
-                                if let Some(obj) = parsed.as_object_mut() {
-                                    obj.insert(
-                                        "export_dir".to_string(),
-                                        serde_json::Value::String(
-                                            export_dir.to_string_lossy().to_string(),
-                                        ),
-                                    );
-                                    obj.insert(
-                                        "exported_files".to_string(),
-                                        serde_json::Value::Array(exported_files),
-                                    );
-                                }
-                            }
-                            return Ok(parsed);
-                        }
-                    }
-                }
-
-                // Fallback: check for errors
-                if !output.status.success() {
-                    if stderr.contains("MemoryError") {
-                        return Err(SandboxError::MemoryLimitExceeded);
-                    }
-                    return Err(SandboxError::RuntimeError(stderr.to_string()));
-                }
-
-                Ok(serde_json::Value::Null)
+        // Fallback: check for errors
+        if !output.status.success() {
+            if stderr.contains("MemoryError") {
+                return Err(SandboxError::MemoryLimitExceeded { peak_mb: None });
             }
-            Ok(Err(e)) => Err(SandboxError::IoError(e)),
-            Err(_) => Err(SandboxError::Timeout),
+            return Err(SandboxError::RuntimeError(stderr.to_string()));
         }
+
+        Ok(ExecutionResult::default())
     }
 
     fn capabilities(&self) -> EngineCapabilities {
-        let has_sandbox = self
-            .config
-            .sandbox_profile
-            .as_ref()
-            .map(|p| p.exists())
-            .unwrap_or(false);
+        let has_sandbox = self.configured_profile().is_some_and(|p| p.exists())
+            || (cfg!(target_os = "macos") && self.config.policy.is_some());
+        #[cfg(target_os = "linux")]
+        let has_sandbox = has_sandbox || bubblewrap().is_some();
 
         EngineCapabilities {
             name: if has_sandbox {
@@ -863,6 +1526,12 @@ impl SandboxedExecutionBuilder {
         self
     }
 
+    /// Generate the sandbox profile from a policy instead of a .sb file
+    pub fn with_policy(mut self, policy: SandboxPolicy) -> Self {
+        self.config.policy = Some(policy);
+        self
+    }
+
     /// Add an input file to copy into the workspace
     pub fn with_input_file(mut self, source: PathBuf, workspace_name: &str) -> Self {
         self.input_files.push((source, workspace_name.to_string()));
@@ -892,18 +1561,12 @@ impl SandboxedExecutionBuilder {
         // Create workspace
         let workspace = IsolatedWorkspace::new(&self.config.workspace_base)?;
 
-        // Copy input files
-        for (source, name) in &self.input_files {
-            workspace.copy_input(source, name)?;
-        }
-
         // Create engine with the workspace
-        let mut config = self.config.clone();
-        config.input_files = self.input_files.clone();
+        let mut engine = SandboxedPythonEngine::new(self.config.clone())?;
 
-        let mut engine = SandboxedPythonEngine::new(config)?;
-
-        // Execute
+        // Execute, staging the input files
+        let mut options = options;
+        options.input_files.extend(self.input_files);
         let result = engine.execute(code, inputs, &options).await?;
 
         // Copy output files
@@ -925,10 +1588,141 @@ impl SandboxedExecutionBuilder {
 /// Result of a sandboxed execution
 #[derive(Debug)]
 pub struct SandboxedExecutionResult {
-    /// The JSON result from Python
-    pub result: serde_json::Value,
+    /// Output of the run
+    pub result: ExecutionResult,
     /// List of files created in the output directory
     pub output_files: Vec<String>,
     /// Path to the workspace (for manual file retrieval)
     pub workspace_path: PathBuf,
 }
+
+#[cfg(all(test, feature = "static-analyzer"))]
+mod tests {
+    use super::*;
+    use crate::config::CodeLimits;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_workspace_snapshot_and_restore() {
+        let base = tempfile::tempdir().unwrap();
+        let workspace = IsolatedWorkspace::new(base.path()).unwrap();
+        let report = workspace.output_dir().join("report.txt");
+        std::fs::write(&report, "draft").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc/hostname", workspace.path.join("link")).unwrap();
+
+        let snapshot = workspace.snapshot().unwrap();
+        std::fs::write(&report, "broken").unwrap();
+        std::fs::write(workspace.input_dir().join("extra.csv"), "x").unwrap();
+
+        workspace.restore(&snapshot).unwrap();
+        assert_eq!(std::fs::read_to_string(&report).unwrap(), "draft");
+        assert!(!workspace.input_dir().join("extra.csv").exists());
+        #[cfg(unix)]
+        assert!(std::fs::symlink_metadata(workspace.path.join("link"))
+            .unwrap()
+            .is_symlink());
+
+        workspace.discard_snapshot(&snapshot).unwrap();
+        assert!(workspace.restore(&snapshot).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_bubblewrap_binds_only_the_workspace_writable() {
+        let workspace = Path::new("/tmp/pysandbox-workspaces/run");
+        let args = bubblewrap_args(workspace, true);
+        let args: Vec<_> = args.iter().map(|a| a.to_str().unwrap()).collect();
+        let ro_root = args
+            .windows(3)
+            .position(|w| w == ["--ro-bind", "/", "/"])
+            .unwrap();
+        // Later mounts win, so the workspace bind has to come after the root
+        let writable: Vec<_> = args
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| **a == "--bind")
+            .map(|(i, _)| (i > ro_root, args[i + 1]))
+            .collect();
+        assert_eq!(writable, [(true, "/tmp/pysandbox-workspaces/run")]);
+        assert!(args.contains(&"--unshare-net"));
+        assert_eq!(args.last(), Some(&"--"));
+        assert!(!bubblewrap_args(workspace, false).contains(&"--unshare-net".into()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_bubblewrap_keeps_site_packages_read_only() {
+        let (Some(_), Ok(python)) = (bubblewrap(), which::which("python3")) else {
+            return;
+        };
+        let base = tempfile::tempdir().unwrap();
+        let mut engine = SandboxedPythonEngine::new(SandboxConfig {
+            python_path: python,
+            workspace_base: base.path().to_path_buf(),
+            ..Default::default()
+        })
+        .unwrap();
+        let options = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(HashSet::new()),
+            ..Default::default()
+        };
+        let code = r#"
+import os, sysconfig
+def probe(path):
+    try:
+        with open(path, "w") as f:
+            f.write("x")
+        return "ok"
+    except OSError:
+        return "denied"
+result = [
+    probe(os.path.join(sysconfig.get_paths()["purelib"], "pysandbox_trojan.py")),
+    probe(os.path.join(os.environ["SANDBOX_WORKSPACE"], "scratch.txt")),
+]
+"#;
+        let result = engine
+            .execute(code, serde_json::Value::Null, &options)
+            .await
+            .unwrap();
+        assert_eq!(result.result, Some(serde_json::json!(["denied", "ok"])));
+    }
+
+    #[test]
+    fn test_static_precheck_follows_import_policy() {
+        let options = ExecutionOptions::default();
+        let diagnostics = static_precheck("import json\nimport socket", &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "blocked_import");
+        assert_eq!(diagnostics[0].line, Some(2));
+        assert!(static_precheck("import json", &options)
+            .unwrap()
+            .unwrap()
+            .is_empty());
+
+        // Unrestricted policies and unparseable code go to the interpreter
+        assert!(static_precheck("def f(:", &options).unwrap().is_none());
+        let unrestricted = ExecutionOptions {
+            import_policy: ImportPolicy::Blacklist(HashSet::new()),
+            ..Default::default()
+        };
+        assert!(static_precheck("import socket", &unrestricted)
+            .unwrap()
+            .is_none());
+
+        // Size caps hold whatever the policy
+        let tiny = ExecutionOptions {
+            code_limits: CodeLimits {
+                max_source_bytes: 8,
+                ..Default::default()
+            },
+            ..unrestricted
+        };
+        assert!(matches!(
+            static_precheck("print('hello')", &tiny),
+            Err(SandboxError::CodeTooComplex(_))
+        ));
+    }
+}
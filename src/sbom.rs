@@ -0,0 +1,333 @@
+//! Software bills of materials for Python environments
+//!
+//! [`installed_packages`] lists the distributions installed for an
+//! interpreter with their licenses, the archive digest pip recorded for
+//! direct URL installs, and a SHA-256 digest of each one's `RECORD` (which
+//! itself lists a hash for every installed file). [`document`] renders them
+//! as CycloneDX 1.5 or SPDX 2.3 JSON, with the `RECORD` digest as a named
+//! property or annotation rather than a package checksum.
+
+use crate::errors::{Result, SandboxError};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// SBOM document format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[default]
+    CycloneDx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+impl SbomFormat {
+    /// Parse `cyclonedx` or `spdx`, case-insensitively
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cyclonedx" | "cdx" => Some(Self::CycloneDx),
+            "spdx" => Some(Self::Spdx),
+            _ => None,
+        }
+    }
+
+    /// Conventional file name for the document
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::CycloneDx => "sbom.cdx.json",
+            Self::Spdx => "sbom.spdx.json",
+        }
+    }
+}
+
+/// An installed distribution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SbomPackage {
+    pub name: String,
+    pub version: String,
+    /// SPDX license expression, when the package declares one
+    #[serde(default)]
+    pub license_expression: Option<String>,
+    /// Free-text license from the metadata or trove classifiers
+    #[serde(default)]
+    pub license: Option<String>,
+    /// SHA-256 of the archive the distribution was installed from, hex
+    /// encoded, when known (from `direct_url.json`)
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// SHA-256 of the installed distribution's `RECORD`, hex encoded
+    #[serde(default)]
+    pub record_sha256: Option<String>,
+}
+
+impl SbomPackage {
+    /// Package URL, e.g. `pkg:pypi/requests@2.31.0`
+    pub fn purl(&self) -> String {
+        let name = self.name.to_ascii_lowercase().replace(['_', '.'], "-");
+        format!("pkg:pypi/{}@{}", name, self.version)
+    }
+}
+
+/// Python printing the installed distributions as a JSON list
+const LIST_PACKAGES: &str = r#"
+import hashlib, importlib.metadata as m, json
+packages = {}
+for d in m.distributions():
+    name = d.metadata['Name']
+    if not name or name in packages:
+        continue
+    license = d.metadata.get('License')
+    if not license or '\n' in license.strip() or len(license) > 100:
+        classifiers = [c.split(' :: ')[-1] for c in d.metadata.get_all('Classifier') or [] if c.startswith('License ::')]
+        license = ', '.join(classifiers) or None
+    record = d.read_text('RECORD')
+    try:
+        archive = json.loads(d.read_text('direct_url.json') or '{}').get('archive_info') or {}
+    except ValueError:
+        archive = {}
+    sha256 = (archive.get('hashes') or {}).get('sha256')
+    if not sha256 and (archive.get('hash') or '').startswith('sha256='):
+        sha256 = archive['hash'][len('sha256='):]
+    packages[name] = {
+        'name': name,
+        'version': d.version,
+        'license_expression': d.metadata.get('License-Expression'),
+        'license': license.strip() if license else None,
+        'sha256': sha256,
+        'record_sha256': hashlib.sha256(record.encode()).hexdigest() if record else None,
+    }
+print(json.dumps(sorted(packages.values(), key=lambda p: p['name'].lower())))
+"#;
+
+/// The distributions installed for `python`
+pub async fn installed_packages(python: &Path) -> Result<Vec<SbomPackage>> {
+    let output = Command::new(python)
+        .arg("-c")
+        .arg(LIST_PACKAGES)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| {
+            SandboxError::InternalError(format!("Failed to run {}: {}", python.display(), e))
+        })?;
+    if !output.status.success() {
+        return Err(SandboxError::InternalError(format!(
+            "listing installed packages failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Name of the property (CycloneDX) or annotation prefix (SPDX) carrying a
+/// package's `RECORD` digest
+pub const RECORD_SHA256_PROPERTY: &str = "pysandbox:record-sha256";
+
+/// SBOM document describing `packages` as the contents of `name`
+pub fn document(format: SbomFormat, name: &str, packages: &[SbomPackage]) -> Value {
+    let serial = uuid::Uuid::new_v4();
+    let created = crate::audit::iso8601_now();
+    match format {
+        SbomFormat::CycloneDx => {
+            let components: Vec<Value> = packages
+                .iter()
+                .map(|p| {
+                    let licenses = match (&p.license_expression, &p.license) {
+                        (Some(expression), _) => json!([{ "expression": expression }]),
+                        (None, Some(license)) => json!([{ "license": { "name": license } }]),
+                        (None, None) => json!([]),
+                    };
+                    let hashes: Vec<Value> = p
+                        .sha256
+                        .iter()
+                        .map(|h| json!({ "alg": "SHA-256", "content": h }))
+                        .collect();
+                    let properties: Vec<Value> = p
+                        .record_sha256
+                        .iter()
+                        .map(|h| json!({ "name": RECORD_SHA256_PROPERTY, "value": h }))
+                        .collect();
+                    json!({
+                        "type": "library",
+                        "bom-ref": p.purl(),
+                        "name": p.name,
+                        "version": p.version,
+                        "purl": p.purl(),
+                        "licenses": licenses,
+                        "hashes": hashes,
+                        "properties": properties,
+                    })
+                })
+                .collect();
+            json!({
+                "bomFormat": "CycloneDX",
+                "specVersion": "1.5",
+                "serialNumber": format!("urn:uuid:{}", serial),
+                "version": 1,
+                "metadata": {
+                    "timestamp": created,
+                    "tools": [{ "name": "pysandbox-rs", "version": env!("CARGO_PKG_VERSION") }],
+                    "component": { "type": "application", "bom-ref": name, "name": name },
+                },
+                "components": components,
+            })
+        }
+        SbomFormat::Spdx => {
+            let spdx_id = |p: &SbomPackage| {
+                let id: String = format!("{}-{}", p.name, p.version)
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '.' {
+                            c
+                        } else {
+                            '-'
+                        }
+                    })
+                    .collect();
+                format!("SPDXRef-Package-{}", id)
+            };
+            let spdx_packages: Vec<Value> = packages
+                .iter()
+                .map(|p| {
+                    let checksums: Vec<Value> = p
+                        .sha256
+                        .iter()
+                        .map(|h| json!({ "algorithm": "SHA256", "checksumValue": h }))
+                        .collect();
+                    // The RECORD isn't the package, so its digest isn't a checksum
+                    let annotations: Vec<Value> = p
+                        .record_sha256
+                        .iter()
+                        .map(|h| {
+                            json!({
+                                "annotationType": "OTHER",
+                                "annotator": concat!("Tool: pysandbox-rs-", env!("CARGO_PKG_VERSION")),
+                                "annotationDate": format!("{}Z", &created[..19]),
+                                "comment": format!("{}={}", RECORD_SHA256_PROPERTY, h),
+                            })
+                        })
+                        .collect();
+                    let mut package = json!({
+                        "SPDXID": spdx_id(p),
+                        "name": p.name,
+                        "versionInfo": p.version,
+                        "downloadLocation": "NOASSERTION",
+                        "filesAnalyzed": false,
+                        "licenseConcluded": "NOASSERTION",
+                        "licenseDeclared": p.license_expression.as_deref().unwrap_or("NOASSERTION"),
+                        "checksums": checksums,
+                        "annotations": annotations,
+                        "externalRefs": [{
+                            "referenceCategory": "PACKAGE-MANAGER",
+                            "referenceType": "purl",
+                            "referenceLocator": p.purl(),
+                        }],
+                    });
+                    // Free text isn't a valid SPDX expression
+                    if let (None, Some(license)) = (&p.license_expression, &p.license) {
+                        package["licenseComments"] = json!(license);
+                    }
+                    package
+                })
+                .collect();
+            let relationships: Vec<Value> = packages
+                .iter()
+                .map(|p| {
+                    json!({
+                        "spdxElementId": "SPDXRef-DOCUMENT",
+                        "relationshipType": "DESCRIBES",
+                        "relatedSpdxElement": spdx_id(p),
+                    })
+                })
+                .collect();
+            json!({
+                "spdxVersion": "SPDX-2.3",
+                "dataLicense": "CC0-1.0",
+                "SPDXID": "SPDXRef-DOCUMENT",
+                "name": name,
+                "documentNamespace": format!("urn:uuid:{}", serial),
+                "creationInfo": {
+                    // SPDX wants whole seconds
+                    "created": format!("{}Z", &created[..19]),
+                    "creators": [concat!("Tool: pysandbox-rs-", env!("CARGO_PKG_VERSION"))],
+                },
+                "packages": spdx_packages,
+                "relationships": relationships,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sbom_documents() {
+        let packages = [
+            SbomPackage {
+                name: "PyYAML".to_string(),
+                version: "6.0.1".to_string(),
+                license_expression: None,
+                license: Some("MIT".to_string()),
+                sha256: Some("ab".repeat(32)),
+                record_sha256: Some("cd".repeat(32)),
+            },
+            SbomPackage {
+                name: "requests".to_string(),
+                version: "2.31.0".to_string(),
+                license_expression: Some("Apache-2.0".to_string()),
+                license: None,
+                sha256: None,
+                record_sha256: Some("ef".repeat(32)),
+            },
+        ];
+
+        let cdx = document(SbomFormat::CycloneDx, "analysis", &packages);
+        assert_eq!(cdx["bomFormat"], "CycloneDX");
+        assert_eq!(cdx["components"][0]["purl"], "pkg:pypi/pyyaml@6.0.1");
+        assert_eq!(
+            cdx["components"][0]["licenses"][0]["license"]["name"],
+            "MIT"
+        );
+        assert_eq!(
+            cdx["components"][0]["hashes"][0]["content"],
+            "ab".repeat(32)
+        );
+        assert_eq!(
+            cdx["components"][1]["properties"][0],
+            json!({ "name": "pysandbox:record-sha256", "value": "ef".repeat(32) })
+        );
+        assert_eq!(cdx["components"][1]["hashes"], json!([]));
+        assert_eq!(
+            cdx["components"][1]["licenses"][0]["expression"],
+            "Apache-2.0"
+        );
+
+        let spdx = document(SbomFormat::Spdx, "analysis", &packages);
+        assert_eq!(spdx["spdxVersion"], "SPDX-2.3");
+        assert_eq!(spdx["packages"][0]["licenseDeclared"], "NOASSERTION");
+        assert_eq!(spdx["packages"][0]["licenseComments"], "MIT");
+        assert_eq!(spdx["packages"][1]["licenseDeclared"], "Apache-2.0");
+        assert_eq!(
+            spdx["packages"][0]["checksums"][0]["checksumValue"],
+            "ab".repeat(32)
+        );
+        assert_eq!(spdx["packages"][1]["checksums"], json!([]));
+        assert_eq!(
+            spdx["packages"][1]["annotations"][0]["comment"],
+            format!("pysandbox:record-sha256={}", "ef".repeat(32))
+        );
+        assert_eq!(
+            spdx["relationships"][1]["relatedSpdxElement"],
+            "SPDXRef-Package-requests-2.31.0"
+        );
+        // 2024-01-01T00:00:00Z
+        assert_eq!(spdx["creationInfo"]["created"].as_str().unwrap().len(), 20);
+    }
+}
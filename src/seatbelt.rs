@@ -0,0 +1,326 @@
+//! Seatbelt (macOS `sandbox-exec`) profile generation
+//!
+//! Renders a `.sb` profile from a [`SandboxPolicy`] so `PlatformSandboxed`
+//! execution works without shipping a hand-written profile file.
+//!
+//! The rendered profile reads the workspace and interpreter locations from the
+//! `WORKSPACE` and `PYTHON_HOME` parameters that [`crate::SandboxedPythonEngine`]
+//! already passes to `sandbox-exec` via `-D`, so one rendered profile can be
+//! reused across workspaces.
+
+use crate::errors::Result;
+use crate::policy::{FilesystemPolicy, NetworkPolicy, NetworkRule, ProcessPolicy, SandboxPolicy};
+use crate::result_file::WORKSPACE_RESULT_FILE;
+use crate::stdin::WORKSPACE_STDIN_FILE;
+use crate::supervisor::{WORKSPACE_INPUTS_FILE, WORKSPACE_OUTPUT_FILE};
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// System locations the interpreter needs to read in order to start at all
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/System",
+    "/usr/lib",
+    "/usr/share",
+    "/Library/Frameworks",
+    "/Library/Preferences",
+    "/private/var/db/dyld",
+    "/private/etc",
+];
+
+/// Device nodes that are always readable
+const DEVICE_READ_LITERALS: &[&str] = &["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+
+/// Render a Seatbelt profile for the given policy
+pub fn render_profile(policy: &SandboxPolicy) -> String {
+    render(policy, None, true)
+}
+
+/// Render a profile whose only outbound traffic is to a local
+/// [egress proxy](crate::egress) on `proxy_port`, whatever the policy's
+/// network rule
+pub fn render_proxied_profile(policy: &SandboxPolicy, proxy_port: u16) -> String {
+    render(policy, Some(proxy_port), true)
+}
+
+/// `wrapper_files` grants the workspace wrapper's own files when the policy
+/// doesn't grant the workspace
+fn render(policy: &SandboxPolicy, proxy_port: Option<u16>, wrapper_files: bool) -> String {
+    let mut sb = String::new();
+    sb.push_str("(version 1)\n");
+    sb.push_str(&format!(
+        ";; Generated by pysandbox from policy '{}'\n",
+        policy.name.replace('\n', " ")
+    ));
+    sb.push_str("(deny default)\n\n");
+
+    // Baseline needed by any CPython process
+    sb.push_str(";; Baseline\n");
+    sb.push_str("(allow process-exec (subpath (param \"PYTHON_HOME\")))\n");
+    sb.push_str("(allow signal (target self))\n");
+    sb.push_str("(allow sysctl-read)\n");
+    sb.push_str("(allow file-read-metadata)\n");
+    sb.push_str("(allow mach-lookup (global-name \"com.apple.system.logger\"))\n");
+    sb.push_str("(allow file-read* (subpath (param \"PYTHON_HOME\")))\n");
+    sb.push_str("(allow file-read*");
+    for path in SYSTEM_READ_PATHS {
+        sb.push_str(&format!(" (subpath {})", quote(path)));
+    }
+    for path in DEVICE_READ_LITERALS {
+        sb.push_str(&format!(" (literal {})", quote(path)));
+    }
+    sb.push_str(")\n");
+    sb.push_str("(allow file-write-data (literal \"/dev/null\"))\n\n");
+
+    sb.push_str(";; Filesystem\n");
+    sb.push_str(&render_filesystem(&policy.filesystem, wrapper_files));
+    sb.push('\n');
+
+    sb.push_str(";; Network\n");
+    match proxy_port {
+        Some(port) => sb.push_str(&format!(
+            "(allow network-outbound (remote ip \"localhost:{}\"))\n",
+            port
+        )),
+        None => sb.push_str(&render_network(&policy.network)),
+    }
+    sb.push('\n');
+
+    sb.push_str(";; Processes\n");
+    sb.push_str(&render_process(&policy.process));
+
+    sb
+}
+
+/// Render the profile and write it to a temporary `.sb` file
+///
+/// The file is removed when the returned handle is dropped, so keep it alive
+/// until `sandbox-exec` has started.
+pub fn write_profile(policy: &SandboxPolicy) -> Result<tempfile::NamedTempFile> {
+    write_profile_with(policy, None)
+}
+
+/// [`write_profile`], rendering [`render_proxied_profile`] when there is a
+/// proxy port
+pub(crate) fn write_profile_with(
+    policy: &SandboxPolicy,
+    proxy_port: Option<u16>,
+) -> Result<tempfile::NamedTempFile> {
+    write_rendered(render(policy, proxy_port, true))
+}
+
+/// [`write_profile_with`] for engines that grant their own files and may not
+/// pass a `WORKSPACE` parameter
+#[cfg(target_os = "macos")]
+pub(crate) fn write_guard_profile(
+    policy: &SandboxPolicy,
+    proxy_port: Option<u16>,
+) -> Result<tempfile::NamedTempFile> {
+    write_rendered(render(policy, proxy_port, false))
+}
+
+fn write_rendered(profile: String) -> Result<tempfile::NamedTempFile> {
+    let mut file = tempfile::Builder::new()
+        .prefix("pysandbox-")
+        .suffix(".sb")
+        .tempfile()?;
+    file.write_all(profile.as_bytes())?;
+    file.flush()?;
+    Ok(file)
+}
+
+fn render_filesystem(policy: &FilesystemPolicy, wrapper_files: bool) -> String {
+    let rules = policy.rules();
+    let mut out = String::new();
+    if rules.write_any {
+        out.push_str("(allow file-read* file-write*)\n");
+    } else if rules.read_any {
+        out.push_str("(allow file-read*)\n");
+    }
+    out.push_str(&subpath_rule("(allow file-read*", &rules.read));
+    if rules.workspace {
+        out.push_str("(allow file-read* file-write* (subpath (param \"WORKSPACE\")))\n");
+    } else if wrapper_files && !rules.write_any {
+        out.push_str(&wrapper_file_rules());
+    }
+    out.push_str(&subpath_rule("(allow file-read* file-write*", &rules.write));
+    // Later rules take precedence, so denials go last
+    out.push_str(&subpath_rule("(deny file-read* file-write*", &rules.deny));
+    out
+}
+
+/// Access to the workspace files the wrapper itself reads and writes, for
+/// policies that don't grant the workspace: the inputs, staged stdin and
+/// input files, the output directory listing, and the result files
+fn wrapper_file_rules() -> String {
+    let in_workspace = |kind: &str, name: &str| {
+        format!(
+            " ({} (string-append (param \"WORKSPACE\") {}))",
+            kind,
+            quote(&format!("/{}", name))
+        )
+    };
+    let mut out = String::from("(allow file-read*");
+    out.push_str(&in_workspace("literal", WORKSPACE_INPUTS_FILE));
+    out.push_str(&in_workspace("literal", WORKSPACE_STDIN_FILE));
+    out.push_str(&in_workspace("subpath", "input"));
+    out.push_str(&in_workspace("literal", "output"));
+    out.push_str(")\n(allow file-read* file-write*");
+    out.push_str(&in_workspace("literal", WORKSPACE_OUTPUT_FILE));
+    out.push_str(&in_workspace("literal", WORKSPACE_RESULT_FILE));
+    out.push_str(")\n");
+    out
+}
+
+/// Render `<head> (subpath ...)...)`, or nothing when there are no paths
+fn subpath_rule(head: &str, paths: &[PathBuf]) -> String {
+    if paths.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from(head);
+    for path in paths {
+        out.push_str(&format!(" (subpath {})", quote_path(path)));
+    }
+    out.push_str(")\n");
+    out
+}
+
+fn render_network(policy: &NetworkPolicy) -> String {
+    match policy {
+        NetworkPolicy::Blocked => String::new(),
+        NetworkPolicy::LocalhostOnly => "(allow network-outbound (remote ip \"localhost:*\"))\n\
+             (allow network-inbound (local ip \"localhost:*\"))\n"
+            .to_string(),
+        // Seatbelt cannot filter by hostname; per-host checks are done by the
+        // in-process network guard, so only open outbound traffic and DNS here,
+        // limited to the entries' ports when every entry has one.
+        NetworkPolicy::AllowList(entries) => {
+            let ports: Option<BTreeSet<u16>> = entries
+                .iter()
+                .map(|entry| NetworkRule::parse(entry).port)
+                .collect();
+            let outbound = match ports {
+                Some(ports) if !ports.is_empty() => {
+                    let mut rule = String::from("(allow network-outbound");
+                    for port in ports {
+                        rule.push_str(&format!(" (remote ip \"*:{}\")", port));
+                    }
+                    rule.push(')');
+                    rule
+                }
+                _ => "(allow network-outbound)".to_string(),
+            };
+            format!(
+                "{}\n\
+                 (allow system-socket)\n\
+                 (allow mach-lookup (global-name \"com.apple.dnssd.service\"))\n",
+                outbound
+            )
+        }
+        NetworkPolicy::Unrestricted => "(allow network*)\n\
+             (allow system-socket)\n\
+             (allow mach-lookup (global-name \"com.apple.dnssd.service\"))\n"
+            .to_string(),
+    }
+}
+
+fn render_process(policy: &ProcessPolicy) -> String {
+    match policy {
+        ProcessPolicy::Blocked => String::new(),
+        ProcessPolicy::AllowList(executables) => {
+            if executables.is_empty() {
+                return String::new();
+            }
+            let mut out = String::from("(allow process-fork)\n(allow process-exec");
+            for exe in executables {
+                out.push_str(&format!(" (literal {})", quote_path(Path::new(exe))));
+            }
+            out.push_str(")\n");
+            out
+        }
+        ProcessPolicy::Unrestricted => "(allow process*)\n".to_string(),
+    }
+}
+
+/// Quote a path for use in a profile, resolving symlinks (e.g. /var -> /private/var)
+/// because Seatbelt matches against real paths
+fn quote_path(path: &Path) -> String {
+    let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    quote(&resolved.to_string_lossy())
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FilesystemRules;
+
+    #[test]
+    fn test_enterprise_profile_denies_by_default() {
+        let profile = render_profile(&SandboxPolicy::enterprise());
+        assert!(profile.starts_with("(version 1)"));
+        assert!(profile.contains("(deny default)"));
+        assert!(profile.contains("(subpath (param \"WORKSPACE\"))"));
+        assert!(!profile.contains("network"));
+        assert!(!profile.contains("process-fork"));
+    }
+
+    #[test]
+    fn test_read_only_paths_are_quoted() {
+        let policy = SandboxPolicy::custom(SandboxPolicy::balanced())
+            .filesystem(FilesystemPolicy::ReadOnly(vec![PathBuf::from(
+                "/nonexistent/with \"quote\"",
+            )]))
+            .network(NetworkPolicy::LocalhostOnly)
+            .build();
+        let profile = render_profile(&policy);
+        assert!(profile.contains("(subpath \"/nonexistent/with \\\"quote\\\"\")"));
+        assert!(profile.contains("localhost:*"));
+        assert!(!profile.contains("file-write* (subpath (param \"WORKSPACE\"))"));
+        // The wrapper still reads its inputs and writes its result
+        assert!(profile.contains(
+            "(allow file-read* (literal (string-append (param \"WORKSPACE\") \"/.rzn_inputs.json\"))"
+        ));
+        assert!(profile.contains(
+            "(allow file-read* file-write* (literal (string-append (param \"WORKSPACE\") \"/.rzn_output.json\"))"
+        ));
+        assert!(!profile.contains("file-write* (subpath (string-append"));
+    }
+
+    #[test]
+    fn test_write_profile_creates_sb_file() {
+        let file = write_profile(&SandboxPolicy::balanced()).unwrap();
+        assert!(file.path().extension().is_some_and(|e| e == "sb"));
+        let written = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(written, render_profile(&SandboxPolicy::balanced()));
+    }
+
+    #[test]
+    fn test_path_rules_deny_last() {
+        let policy = SandboxPolicy::custom(SandboxPolicy::balanced())
+            .filesystem(FilesystemPolicy::Paths(
+                FilesystemRules::new()
+                    .read("/nonexistent/data")
+                    .write("/nonexistent/out")
+                    .deny("/nonexistent/data/secrets"),
+            ))
+            .build();
+        let profile = render_profile(&policy);
+        let read = profile
+            .find("(allow file-read* (subpath \"/nonexistent/data\"))")
+            .unwrap();
+        let write = profile
+            .find("(allow file-read* file-write* (subpath \"/nonexistent/out\"))")
+            .unwrap();
+        let deny = profile
+            .find("(deny file-read* file-write* (subpath \"/nonexistent/data/secrets\"))")
+            .unwrap();
+        assert!(read < deny && write < deny);
+        assert!(!profile.contains("(param \"WORKSPACE\")))"));
+        // Engines granting their own files don't reference the workspace
+        assert!(!render(&policy, None, false).contains("WORKSPACE\")"));
+    }
+}
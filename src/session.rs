@@ -0,0 +1,268 @@
+//! Persistent stateful sessions
+//!
+//! A [`SessionHandle`] owns one long-lived guarded interpreter (the same
+//! worker the warm pool uses) and runs every call in a shared namespace, so
+//! variables, functions and imports defined by one call are visible to the
+//! next. Guards and limits are fixed when the session is created.
+
+use crate::{
+    config::ResourceLimits,
+    engine::{ExecutionOptions, ExecutionResult},
+    errors::{execution_error, Result, SandboxError},
+    pool::{Worker, WorkerPolicy},
+    sandboxed::stage_input_dir,
+    supervisor::cancelled,
+};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Handle to a persistent interpreter session
+///
+/// Dropping the handle kills the interpreter. A session that times out, is
+/// cancelled through `options.cancellation`, or exits is terminated, and later
+/// calls fail.
+pub struct SessionHandle {
+    id: u64,
+    worker: Option<Worker>,
+    options: ExecutionOptions,
+    cpu_seconds: u64,
+    max_output_bytes: usize,
+    /// `options.input_files`, staged once for the whole session
+    input_dir: Option<tempfile::TempDir>,
+}
+
+impl SessionHandle {
+    /// Start a session interpreter with the given guards and limits
+    pub(crate) async fn start(
+        python_path: &Path,
+        limits: &ResourceLimits,
+        options: ExecutionOptions,
+    ) -> Result<Self> {
+        let policy = WorkerPolicy::from_options(&options);
+        let input_dir = stage_input_dir(&options.input_files)?;
+        let mut worker = Worker::spawn(python_path, &policy, &[], limits)?;
+
+        match tokio::time::timeout(options.setup_budget(), worker.wait_ready()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                worker.kill();
+                return Err(e);
+            }
+            Err(_) => {
+                worker.kill();
+                return Err(SandboxError::Timeout);
+            }
+        }
+
+        let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+        debug!("[SESSION] Started session {}", id);
+        Ok(Self {
+            id,
+            worker: Some(worker),
+            cpu_seconds: limits.cpu_seconds.min(options.cpu_seconds),
+            max_output_bytes: limits.max_output_bytes,
+            input_dir,
+            options,
+        })
+    }
+
+    /// Session identifier, unique within the process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Whether the interpreter is still running
+    pub fn is_alive(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// Execute code in the session namespace
+    pub async fn execute(&mut self, code: &str) -> Result<ExecutionResult> {
+        self.execute_with_inputs(code, serde_json::json!({})).await
+    }
+
+    /// Execute code with `inputs` rebound for this call
+    pub async fn execute_with_inputs(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+    ) -> Result<ExecutionResult> {
+        self.execute_inner(code, inputs, None).await
+    }
+
+    /// Execute code with `inputs`, also stopping when `cancel` fires
+    ///
+    /// A call cancelled this way terminates the session, like one cancelled
+    /// through `options.cancellation`.
+    pub async fn execute_cancellable(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        cancel: &CancellationToken,
+    ) -> Result<ExecutionResult> {
+        self.execute_inner(code, inputs, Some(cancel)).await
+    }
+
+    async fn execute_inner(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<ExecutionResult> {
+        let request = serde_json::json!({ "code": code, "inputs": inputs });
+        let parsed = self
+            .send_until(request, self.options.timeout, cancel)
+            .await?;
+        if let Some(error) = execution_error(&parsed, code) {
+            return Err(error);
+        }
+        ExecutionResult::from_value(parsed)
+    }
+
+    /// Send one request to the session interpreter and return its raw response
+    ///
+    /// Session-wide fields are filled in here. Errors raised by user code are
+    /// left in the response; only a lost or timed-out interpreter is an error,
+    /// and it terminates the session.
+    pub(crate) async fn send(
+        &mut self,
+        request: serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value> {
+        self.send_until(request, timeout, None).await
+    }
+
+    async fn send_until(
+        &mut self,
+        mut request: serde_json::Value,
+        timeout: Duration,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<serde_json::Value> {
+        let worker = self.worker.as_mut().ok_or_else(|| {
+            SandboxError::RuntimeError(format!("Session {} has been terminated", self.id))
+        })?;
+
+        request["env"] = serde_json::json!(self.options.env_vars);
+        request["cpu_seconds"] = self.cpu_seconds.into();
+        request["max_output_bytes"] = self.max_output_bytes.into();
+        request["keep_namespace"] = true.into();
+        request["input_dir"] = serde_json::json!(self.input_dir.as_ref().map(|dir| dir.path()));
+
+        let response = tokio::select! {
+            response = tokio::time::timeout(timeout, worker.request(&request)) => {
+                response.map_err(|_| SandboxError::Timeout)
+            }
+            _ = cancelled(self.options.cancellation.as_ref()) => Err(SandboxError::Cancelled),
+            _ = cancelled(cancel) => Err(SandboxError::Cancelled),
+        };
+
+        let line = match response {
+            Ok(Ok(Some(line))) => line,
+            Ok(Ok(None)) => {
+                let error = worker.exit_error().await;
+                self.terminate();
+                return Err(error);
+            }
+            Ok(Err(e)) => {
+                self.terminate();
+                return Err(e);
+            }
+            Err(error) => {
+                warn!("[SESSION] Session {}: {}, terminating", self.id, error);
+                if let Some(worker) = self.worker.take() {
+                    worker.stop(self.options.kill_grace_period).await;
+                }
+                return Err(error);
+            }
+        };
+
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    /// Options the session was created with
+    pub fn options(&self) -> &ExecutionOptions {
+        &self.options
+    }
+
+    /// Kill the interpreter, discarding the session state
+    pub fn close(mut self) {
+        self.terminate();
+    }
+
+    fn terminate(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            debug!("[SESSION] Closing session {}", self.id);
+            worker.kill();
+        }
+    }
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_keeps_variables() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let mut session =
+            SessionHandle::start(&python, &ResourceLimits::default(), Default::default())
+                .await
+                .unwrap();
+
+        session.execute("counter = 41").await.unwrap();
+        let output = session.execute("result = counter + 1").await.unwrap();
+        assert_eq!(output.result_as::<i64>().unwrap(), 42);
+
+        // `result` is reset between calls
+        let output = session.execute("counter += 1").await.unwrap();
+        assert!(output.result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_stages_input_files() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let source = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(source.path(), "hello").unwrap();
+        let options = ExecutionOptions {
+            input_files: vec![(source.path().to_path_buf(), "doc.txt".to_string())],
+            ..Default::default()
+        };
+        let mut session = SessionHandle::start(&python, &ResourceLimits::default(), options)
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let output = session
+                .execute("result = open(INPUT_DIR + '/doc.txt').read()")
+                .await
+                .unwrap();
+            assert_eq!(output.result_as::<String>().unwrap(), "hello");
+        }
+
+        // Names can't escape the input directory
+        let options = ExecutionOptions {
+            input_files: vec![(source.path().to_path_buf(), "../doc.txt".to_string())],
+            ..Default::default()
+        };
+        assert!(
+            SessionHandle::start(&python, &ResourceLimits::default(), options)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Ed25519 detached signatures
+//!
+//! Keys and signatures are stored as base64 text files, the format
+//! `rzn-plugin-devkit` reads and writes:
+//! - private key: 32-byte Ed25519 seed
+//! - public key: 32-byte Ed25519 verifying key
+//! - signature: 64-byte Ed25519 signature
+
+use crate::errors::{Result, SandboxError};
+use base64::engine::general_purpose::STANDARD as b64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::path::{Path, PathBuf};
+
+/// Path of the detached signature for a file: `<file>.sig`
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut sig = path.as_os_str().to_os_string();
+    sig.push(".sig");
+    PathBuf::from(sig)
+}
+
+/// Decode a base64 value, ignoring surrounding whitespace
+pub fn decode_b64(encoded: &str) -> Result<Vec<u8>> {
+    b64.decode(encoded.trim().as_bytes())
+        .map_err(|e| SandboxError::SecurityViolation(format!("invalid base64: {}", e)))
+}
+
+/// Read a base64 private key file
+pub fn read_signing_key(path: &Path) -> Result<SigningKey> {
+    let key_bytes = decode_b64(&std::fs::read_to_string(path)?)?;
+    if key_bytes.len() < 32 {
+        return Err(SandboxError::SecurityViolation(format!(
+            "invalid Ed25519 private key length: {} (expected at least 32)",
+            key_bytes.len()
+        )));
+    }
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&key_bytes[..32]);
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// Parse a base64 public key
+pub fn parse_verifying_key(encoded: &str) -> Result<VerifyingKey> {
+    let pk_bytes = decode_b64(encoded)?;
+    let pk_arr: [u8; 32] = pk_bytes.as_slice().try_into().map_err(|_| {
+        SandboxError::SecurityViolation(format!(
+            "invalid Ed25519 public key length: {} (expected 32)",
+            pk_bytes.len()
+        ))
+    })?;
+    VerifyingKey::from_bytes(&pk_arr)
+        .map_err(|e| SandboxError::SecurityViolation(format!("invalid Ed25519 public key: {}", e)))
+}
+
+/// Read a base64 public key file
+pub fn read_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    parse_verifying_key(&std::fs::read_to_string(path)?)
+}
+
+/// Sign a message, returning the base64 signature file contents
+pub fn sign(key: &SigningKey, message: &[u8]) -> String {
+    let sig: Signature = key.sign(message);
+    format!("{}\n", b64.encode(sig.to_bytes()))
+}
+
+/// Verify a base64 signature over a message
+pub fn verify(key: &VerifyingKey, message: &[u8], signature: &str) -> Result<()> {
+    let sig_bytes = decode_b64(signature)?;
+    if sig_bytes.len() != 64 {
+        return Err(SandboxError::SecurityViolation(format!(
+            "invalid Ed25519 signature length: {} (expected 64)",
+            sig_bytes.len()
+        )));
+    }
+    let sig = Signature::from_slice(&sig_bytes)
+        .map_err(|e| SandboxError::SecurityViolation(format!("invalid signature: {}", e)))?;
+    key.verify(message, &sig)
+        .map_err(|_| SandboxError::SecurityViolation("signature does not match".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify_round_trip() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let public = parse_verifying_key(&b64.encode(key.verifying_key().to_bytes())).unwrap();
+        let signature = sign(&key, b"policy");
+
+        assert!(verify(&public, b"policy", &signature).is_ok());
+        assert!(verify(&public, b"tampered", &signature).is_err());
+        assert!(verify(&public, b"policy", "bm90IGEgc2lnbmF0dXJl").is_err());
+        assert_eq!(
+            signature_path(Path::new("/etc/org/policy.toml")),
+            PathBuf::from("/etc/org/policy.toml.sig")
+        );
+    }
+}
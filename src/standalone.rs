@@ -0,0 +1,367 @@
+//! Standalone Python runtimes from python-build-standalone
+//!
+//! Hosts that can't rely on a system Python, and don't ship one under
+//! `resources/python/`, can provision a relocatable CPython instead. A
+//! [`StandaloneRuntime`] downloads the python-build-standalone
+//! `install_only` build for the current OS and architecture, checks it
+//! against the release's `SHA256SUMS` (or a digest the caller pins), and
+//! unpacks it into the app data dir, where the worker picks it up as its
+//! bundled runtime. Downloading and unpacking shell out to `curl` and `tar`,
+//! which ship with macOS, Windows 10+ and common Linux distributions.
+
+use crate::errors::{Result, SandboxError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Environment variable overriding where the runtime is installed
+pub const RUNTIME_DIR_ENV: &str = "RZN_PYTHON_RUNTIME_DIR";
+
+/// Environment variable naming a mirror of the GitHub release downloads
+pub const MIRROR_ENV: &str = "RZN_PYTHON_STANDALONE_MIRROR";
+
+/// python-build-standalone release used by default
+pub const DEFAULT_RELEASE: &str = "20241016";
+
+/// CPython version used by default; must be part of [`DEFAULT_RELEASE`]
+pub const DEFAULT_PYTHON_VERSION: &str = "3.12.7";
+
+const DEFAULT_MIRROR: &str =
+    "https://github.com/astral-sh/python-build-standalone/releases/download";
+
+const RUNTIME_FILE_NAME: &str = "runtime.json";
+
+/// python-build-standalone target triple of the running platform
+pub fn target_triple() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("x86_64-unknown-linux-gnu"),
+        ("linux", "aarch64") => Some("aarch64-unknown-linux-gnu"),
+        ("macos", "x86_64") => Some("x86_64-apple-darwin"),
+        ("macos", "aarch64") => Some("aarch64-apple-darwin"),
+        ("windows", "x86_64") => Some("x86_64-pc-windows-msvc"),
+        _ => None,
+    }
+}
+
+/// Digest listed for `asset` in a release's `SHA256SUMS`
+pub fn parse_sha256sums(sums: &str, asset: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (digest, name) = line.trim().split_once(char::is_whitespace)?;
+        // `sha256sum` marks binary mode with a leading '*'
+        (name.trim().trim_start_matches('*') == asset).then(|| digest.to_ascii_lowercase())
+    })
+}
+
+/// A runtime installed by [`StandaloneRuntime::provision`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvisionedRuntime {
+    pub python_version: String,
+    pub release: String,
+    pub target: String,
+    /// Where the archive was downloaded from
+    pub url: String,
+    /// SHA-256 of the archive, hex encoded
+    pub sha256: String,
+    pub python_path: PathBuf,
+}
+
+/// Downloads and installs a python-build-standalone CPython
+#[derive(Debug, Clone)]
+pub struct StandaloneRuntime {
+    base_dir: PathBuf,
+    python_version: String,
+    release: String,
+    mirror: String,
+    sha256: Option<String>,
+}
+
+impl StandaloneRuntime {
+    /// Runtime installed under `base_dir`, using the default release and
+    /// version and [`MIRROR_ENV`] if set
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        let mirror = std::env::var(MIRROR_ENV)
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| DEFAULT_MIRROR.to_string());
+        Self {
+            base_dir: base_dir.into(),
+            python_version: DEFAULT_PYTHON_VERSION.to_string(),
+            release: DEFAULT_RELEASE.to_string(),
+            mirror,
+            sha256: None,
+        }
+    }
+
+    /// Runtime at [`RUNTIME_DIR_ENV`], else `$RZN_APP_BASE_DIR/python_runtime`,
+    /// else `~/.rzn/python_runtime`, else under the temp dir
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        let base_dir = var(RUNTIME_DIR_ENV)
+            .or_else(|| var("RZN_APP_BASE_DIR").map(|dir| dir.join("python_runtime")))
+            .or_else(|| var("HOME").map(|home| home.join(".rzn").join("python_runtime")))
+            .unwrap_or_else(|| std::env::temp_dir().join("rzn").join("python_runtime"));
+        Self::new(base_dir)
+    }
+
+    /// CPython version to install, e.g. `3.11.10`
+    pub fn python_version(mut self, version: impl Into<String>) -> Self {
+        self.python_version = version.into();
+        self
+    }
+
+    /// python-build-standalone release tag containing that version
+    pub fn release(mut self, release: impl Into<String>) -> Self {
+        self.release = release.into();
+        self
+    }
+
+    /// Base URL serving `<release>/<asset>` and `<release>/SHA256SUMS`;
+    /// `file://` URLs work for offline mirrors
+    pub fn mirror(mut self, mirror: impl Into<String>) -> Self {
+        self.mirror = mirror.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Expect this SHA-256 instead of looking it up in `SHA256SUMS`
+    pub fn sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into().trim().to_ascii_lowercase());
+        self
+    }
+
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Archive name for `target`
+    pub fn asset_name(&self, target: &str) -> String {
+        format!(
+            "cpython-{}+{}-{}-install_only.tar.gz",
+            self.python_version, self.release, target
+        )
+    }
+
+    /// Download URL of the archive for `target`
+    pub fn download_url(&self, target: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.mirror,
+            self.release,
+            self.asset_name(target).replace('+', "%2B")
+        )
+    }
+
+    /// The runtime currently installed, if its interpreter is still there
+    pub fn installed(&self) -> Option<ProvisionedRuntime> {
+        let raw = std::fs::read_to_string(self.base_dir.join(RUNTIME_FILE_NAME)).ok()?;
+        serde_json::from_str::<ProvisionedRuntime>(&raw)
+            .ok()
+            .filter(|runtime| runtime.python_path.exists())
+    }
+
+    /// Install the configured runtime, replacing a different one
+    ///
+    /// Returns the installed runtime as is when it already matches. Wrap in
+    /// `tokio::time::timeout` for a deadline; `curl` and `tar` are killed
+    /// when this is dropped.
+    pub async fn provision(&self, force: bool) -> Result<ProvisionedRuntime> {
+        let target = target_triple().ok_or_else(|| {
+            SandboxError::InternalError(format!(
+                "No python-build-standalone build for {}-{}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ))
+        })?;
+        if let Some(runtime) = self.installed() {
+            if !force
+                && runtime.python_version == self.python_version
+                && runtime.release == self.release
+            {
+                return Ok(runtime);
+            }
+        }
+
+        std::fs::create_dir_all(&self.base_dir)?;
+        let staging = tempfile::Builder::new()
+            .prefix(".provision-")
+            .tempdir_in(&self.base_dir)?;
+        let asset = self.asset_name(target);
+        let url = self.download_url(target);
+        let archive = staging.path().join(&asset);
+        download(&url, &archive).await?;
+
+        let expected = match &self.sha256 {
+            Some(sha256) => sha256.clone(),
+            None => {
+                let sums_path = staging.path().join("SHA256SUMS");
+                download(
+                    &format!("{}/{}/SHA256SUMS", self.mirror, self.release),
+                    &sums_path,
+                )
+                .await?;
+                parse_sha256sums(&std::fs::read_to_string(&sums_path)?, &asset).ok_or_else(
+                    || {
+                        SandboxError::InternalError(format!(
+                            "{} is not listed in the release's SHA256SUMS",
+                            asset
+                        ))
+                    },
+                )?
+            }
+        };
+        let actual = file_sha256(&archive)?;
+        if actual != expected {
+            return Err(SandboxError::SecurityViolation(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                asset, expected, actual
+            )));
+        }
+
+        let unpacked = staging.path().join("unpacked");
+        std::fs::create_dir(&unpacked)?;
+        let mut tar = Command::new("tar");
+        tar.arg("-xzf").arg(&archive).arg("-C").arg(&unpacked);
+        run(tar, "tar").await?;
+
+        // install_only archives hold a single `python/` directory
+        let install_dir = self.base_dir.join("python");
+        if install_dir.exists() {
+            std::fs::remove_dir_all(&install_dir)?;
+        }
+        std::fs::rename(unpacked.join("python"), &install_dir).map_err(|e| {
+            SandboxError::InternalError(format!("Unexpected layout in {}: {}", asset, e))
+        })?;
+        let python_path = if cfg!(windows) {
+            install_dir.join("python.exe")
+        } else {
+            install_dir.join("bin").join("python3")
+        };
+        if !python_path.exists() {
+            return Err(SandboxError::InternalError(format!(
+                "{} has no interpreter at {}",
+                asset,
+                python_path.display()
+            )));
+        }
+
+        let runtime = ProvisionedRuntime {
+            python_version: self.python_version.clone(),
+            release: self.release.clone(),
+            target: target.to_string(),
+            url,
+            sha256: actual,
+            python_path,
+        };
+        std::fs::write(
+            self.base_dir.join(RUNTIME_FILE_NAME),
+            serde_json::to_string_pretty(&runtime)?,
+        )?;
+        Ok(runtime)
+    }
+}
+
+async fn download(url: &str, dest: &Path) -> Result<()> {
+    let mut curl = Command::new("curl");
+    curl.arg("--fail")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--location")
+        .arg("--output")
+        .arg(dest)
+        .arg(url);
+    run(curl, "curl").await
+}
+
+async fn run(mut cmd: Command, program: &str) -> Result<()> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| SandboxError::InternalError(format!("Failed to run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(SandboxError::InternalError(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_provision_from_mirror() {
+        let Some(target) = target_triple() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = StandaloneRuntime::new(dir.path().join("runtime"))
+            .mirror(format!("file://{}", dir.path().join("mirror").display()))
+            .release("20990101")
+            .python_version("3.99.0");
+
+        // A fake install_only archive with its SHA256SUMS entry
+        let asset = runtime.asset_name(target);
+        let bin = dir.path().join("build").join("python").join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("python3"), "#!/bin/sh\n").unwrap();
+        let release_dir = dir.path().join("mirror").join("20990101");
+        std::fs::create_dir_all(&release_dir).unwrap();
+        let status = std::process::Command::new("tar")
+            .arg("-czf")
+            .arg(release_dir.join(&asset))
+            .arg("-C")
+            .arg(dir.path().join("build"))
+            .arg("python")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let digest = file_sha256(&release_dir.join(&asset)).unwrap();
+        std::fs::write(
+            release_dir.join("SHA256SUMS"),
+            format!("{}  other.tar.gz\n{}  {}\n", "00".repeat(32), digest, asset),
+        )
+        .unwrap();
+        assert_eq!(
+            parse_sha256sums(&format!("{} *{}", digest, asset), &asset),
+            Some(digest.clone())
+        );
+        assert!(runtime.download_url(target).contains("3.99.0%2B20990101"));
+
+        let installed = runtime.provision(false).await.unwrap();
+        assert_eq!(installed.sha256, digest);
+        assert!(installed.python_path.ends_with("python/bin/python3"));
+        assert_eq!(runtime.installed(), Some(installed));
+
+        let tampered = runtime.clone().sha256("ab".repeat(32));
+        assert!(matches!(
+            tampered.provision(true).await,
+            Err(SandboxError::SecurityViolation(_))
+        ));
+        // A failed provision leaves the installed runtime alone
+        assert!(runtime.installed().is_some());
+    }
+}
@@ -0,0 +1,132 @@
+//! Standard input for executed code
+//!
+//! Set [`ExecutionOptions::stdin`](crate::ExecutionOptions::stdin) to pipe
+//! data to the interpreter, so scripts that read `sys.stdin` run unchanged.
+//! The native, sandboxed and RustPython engines stream it to the child
+//! process. Pool workers read their requests from stdin, and container and
+//! WASM engines send the wrapper there, so those read the data up front and
+//! hand it to the code as `sys.stdin`. Sessions and remote engines take no
+//! stdin.
+
+use crate::errors::Result;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::ChildStdin;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// File a workspace wrapper reads staged stdin from, relative to the workspace
+pub(crate) const WORKSPACE_STDIN_FILE: &str = ".rzn_stdin";
+
+type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Data fed to the standard input of executed code
+#[derive(Debug, Clone)]
+pub enum StdinSource {
+    Bytes(Vec<u8>),
+    /// Read from a host file when the execution starts
+    File(PathBuf),
+    /// Streamed from an async reader, see [`StdinSource::reader`]
+    Reader(SharedReader),
+}
+
+/// An async reader consumed by the first execution that uses it
+///
+/// Options cloned for a fallback engine share the reader, so a retry after
+/// it was read sees empty stdin.
+#[derive(Clone)]
+pub struct SharedReader(Arc<Mutex<Option<BoxedReader>>>);
+
+impl std::fmt::Debug for SharedReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedReader")
+    }
+}
+
+impl StdinSource {
+    /// Stream stdin from `reader`
+    pub fn reader(reader: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        StdinSource::Reader(SharedReader(Arc::new(Mutex::new(Some(Box::new(reader))))))
+    }
+
+    async fn open(&self) -> Result<BoxedReader> {
+        Ok(match self {
+            StdinSource::Bytes(data) => Box::new(std::io::Cursor::new(data.clone())),
+            StdinSource::File(path) => Box::new(tokio::fs::File::open(path).await?),
+            StdinSource::Reader(SharedReader(reader)) => match reader.lock().await.take() {
+                Some(reader) => reader,
+                None => Box::new(tokio::io::empty()),
+            },
+        })
+    }
+
+    /// Read the whole source into memory
+    pub(crate) async fn read_all(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open().await?.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    /// Copy the source into `workspace` for the wrapper to read
+    pub(crate) async fn stage(&self, workspace: &Path) -> Result<()> {
+        let mut file = tokio::fs::File::create(workspace.join(WORKSPACE_STDIN_FILE)).await?;
+        tokio::io::copy(&mut self.open().await?, &mut file).await?;
+        Ok(())
+    }
+
+    /// Stream the source into a child's stdin, closing it at the end
+    pub(crate) fn feed(&self, mut pipe: ChildStdin) -> StdinFeed {
+        let source = self.clone();
+        StdinFeed(tokio::spawn(async move {
+            match source.open().await {
+                // The child may exit without reading it all
+                Ok(mut reader) => {
+                    let _ = tokio::io::copy(&mut reader, &mut pipe).await;
+                }
+                Err(e) => warn!("Failed to open stdin source: {}", e),
+            }
+        }))
+    }
+}
+
+/// Feeds a child's stdin; dropping it stops a source that outlives the child
+pub(crate) struct StdinFeed(JoinHandle<()>);
+
+impl Drop for StdinFeed {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Stdin configuration for a child that streams `stdin`
+pub(crate) fn child_stdin(stdin: Option<&StdinSource>) -> Stdio {
+    match stdin {
+        Some(_) => Stdio::piped(),
+        None => Stdio::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stdin_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("data.csv");
+        std::fs::write(&file, "a,1\n").unwrap();
+        assert_eq!(StdinSource::File(file).read_all().await.unwrap(), b"a,1\n");
+
+        // A reader is consumed by the first use, even through a clone
+        let reader = StdinSource::reader(std::io::Cursor::new(b"streamed".to_vec()));
+        reader.clone().stage(dir.path()).await.unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join(WORKSPACE_STDIN_FILE)).unwrap(),
+            b"streamed"
+        );
+        assert!(reader.read_all().await.unwrap().is_empty());
+    }
+}
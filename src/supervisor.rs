@@ -0,0 +1,1282 @@
+//! Supervision of interpreter child processes
+//!
+//! Engines that run Python as a local child process hand it to [`supervise`],
+//! which collects its output, enforces the wall-clock timeout and reports
+//! progress events while it runs.
+
+use crate::{
+    config::ResourceLimits,
+    engine::{ExecutionMetrics, ExecutionOptions},
+    errors::{Result, SandboxError},
+    events::{EventSender, ExecutionEvent, OutputStream},
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// How often heartbeats are sampled
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the memory watchdog samples RSS
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the workspace size is checked against its quota
+const QUOTA_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Prefix of stderr lines that carry user output chunks
+const CHUNK_MARKER: &str = "__RZN_OUTPUT_CHUNK__ ";
+
+/// Python class for capturing a stream with a byte cap
+///
+/// `_RznCapture(stream, limit)` keeps at most `limit` bytes of UTF-8 and sets
+/// `truncated` once anything is dropped. When `stream` is set, kept writes are
+/// also forwarded to the real stderr as marker lines, which [`supervise`] turns
+/// into [`ExecutionEvent::OutputChunk`]s and strips from the collected stderr.
+/// Requires `json`, `sys` and `StringIO`.
+pub(crate) fn capture_class(stream: bool) -> String {
+    let tee = if stream {
+        format!(
+            r#"sys.__stderr__.write("{marker}" + json.dumps({{"stream": self._rzn_stream, "data": data}}) + "\n")
+            sys.__stderr__.flush()"#,
+            marker = CHUNK_MARKER
+        )
+    } else {
+        "pass".to_string()
+    };
+    format!(
+        r#"class _RznCapture(StringIO):
+    def __init__(self, stream, limit):
+        super().__init__()
+        self._rzn_stream = stream
+        self._rzn_room = limit
+        self.truncated = False
+
+    def write(self, data):
+        size = len(data)
+        if not data:
+            return size
+        encoded = data.encode("utf-8", "replace")
+        if len(encoded) > self._rzn_room:
+            data = encoded[:self._rzn_room].decode("utf-8", "ignore")
+            self.truncated = True
+        self._rzn_room -= min(len(encoded), self._rzn_room)
+        if data:
+            {tee}
+            super().write(data)
+        return size
+"#,
+        tee = tee
+    )
+}
+
+/// Python that sets up capped `_captured_stdout` / `_captured_stderr`
+pub(crate) fn output_capture(stream: bool, max_bytes: usize) -> String {
+    format!(
+        "{}\n_captured_stdout = _RznCapture(\"stdout\", {max})\n_captured_stderr = _RznCapture(\"stderr\", {max})\n",
+        capture_class(stream),
+        max = max_bytes
+    )
+}
+
+/// Python that flags truncated streams in `_output`
+pub(crate) const MARK_TRUNCATED: &str = r#"if _captured_stdout.truncated:
+    _output["stdout_truncated"] = True
+if _captured_stderr.truncated:
+    _output["stderr_truncated"] = True
+"#;
+
+/// Python function `_rzn_result_value(value, artifact_dir=None)` that turns
+/// `result` into JSON
+///
+/// JSON types pass through and bytes become base64. pandas DataFrames and
+/// Series become `{type, shape, columns, dtypes, records}`; frames over 1000
+/// rows or 20000 cells are written to `artifact_dir` as `result.parquet`
+/// (`result.csv` without a parquet engine) with a five-row `preview`, or
+/// truncated to their first 1000 `records` when there is no `artifact_dir`.
+/// numpy arrays become `{type: "ndarray", dtype, shape, data}` with nested
+/// lists; arrays over 20000 elements are saved to `artifact_dir` as
+/// `result.npy`, or flattened and truncated without one. numpy scalars become
+/// plain numbers. Anything else falls back to `{type, repr}`.
+///
+/// `_rzn_spill_result(output, path, reference, threshold)` moves a `result`
+/// whose JSON is over `threshold` bytes to `path`, leaving the
+/// [`crate::result_file`] reference in its place, and
+/// `_rzn_report_output(output, path, nonce)` hands the output to the host
+/// through a [`ResultChannel`]. Self-contained, so all of them can be defined
+/// before the import guard is installed.
+pub(crate) const RESULT_SERIALIZER: &str = r#"
+import base64 as _rzn_b64
+import json as _rzn_json
+import sys as _rzn_sys
+import traceback as _rzn_traceback
+try:
+    import hashlib as _rzn_hashlib
+except ImportError:
+    _rzn_hashlib = None
+
+_RZN_INLINE_ROWS = 1000
+_RZN_INLINE_CELLS = 20000
+
+def _rzn_records(frame):
+    return _rzn_json.loads(frame.to_json(orient="records", date_format="iso"))
+
+def _rzn_write_frame(frame, artifact_dir):
+    try:
+        name = "result.parquet"
+        frame.to_parquet(artifact_dir + "/" + name, index=False)
+        return {"file": name, "format": "parquet"}
+    except Exception:
+        name = "result.csv"
+        frame.to_csv(artifact_dir + "/" + name, index=False)
+        return {"file": name, "format": "csv"}
+
+def _rzn_pandas_value(value, artifact_dir):
+    pd = _rzn_sys.modules["pandas"]
+    series = isinstance(value, pd.Series)
+    if series:
+        frame = value.to_frame(name="value" if value.name is None else value.name)
+    else:
+        frame = value
+    out = {
+        "type": "Series" if series else "DataFrame",
+        "shape": list(value.shape),
+        "columns": [str(c) for c in frame.columns],
+        "dtypes": {str(c): str(t) for c, t in frame.dtypes.items()},
+    }
+    if not isinstance(frame.index, pd.RangeIndex):
+        frame = frame.reset_index()
+    frame = frame.rename(columns=str)
+    if len(frame) <= _RZN_INLINE_ROWS and frame.size <= _RZN_INLINE_CELLS:
+        out["records"] = _rzn_records(frame)
+    elif artifact_dir is None:
+        out["records"] = _rzn_records(frame.head(_RZN_INLINE_ROWS))
+        out["truncated"] = True
+    else:
+        out["artifact"] = _rzn_write_frame(frame, artifact_dir)
+        out["preview"] = _rzn_records(frame.head(5))
+    return out
+
+def _rzn_array_data(array):
+    np = _rzn_sys.modules["numpy"]
+    if array.dtype.kind in "biu":
+        return array.tolist()
+    if array.dtype.kind == "f":
+        finite = np.isfinite(array)
+        if finite.all():
+            return array.tolist()
+        # NaN and infinities are not valid JSON
+        data = array.astype(object)
+        data[~finite] = None
+        return data.tolist()
+    if array.dtype.kind in "cmMUS":
+        return array.astype(str).tolist()
+    data = array.tolist()
+    _rzn_json.dumps(data)
+    return data
+
+def _rzn_numpy_value(value, artifact_dir):
+    out = {"type": "ndarray", "dtype": str(value.dtype), "shape": list(value.shape)}
+    if value.size <= _RZN_INLINE_CELLS:
+        out["data"] = _rzn_array_data(value)
+    elif artifact_dir is None or value.dtype.kind == "O":
+        out["data"] = _rzn_array_data(value.ravel()[:_RZN_INLINE_CELLS])
+        out["truncated"] = True
+    else:
+        _rzn_sys.modules["numpy"].save(artifact_dir + "/result.npy", value, allow_pickle=False)
+        out["artifact"] = {"file": "result.npy", "format": "npy"}
+    return out
+
+def _rzn_result_value(value, artifact_dir=None):
+    if value is None or isinstance(value, (dict, list, str, int, float, bool)):
+        return value
+    if isinstance(value, (bytes, bytearray, memoryview)):
+        return {
+            "type": "bytes",
+            "encoding": "base64",
+            "data": _rzn_b64.b64encode(bytes(value)).decode("utf-8")
+        }
+    pd = _rzn_sys.modules.get("pandas")
+    np = _rzn_sys.modules.get("numpy")
+    try:
+        if pd is not None and isinstance(value, (pd.DataFrame, pd.Series)):
+            return _rzn_pandas_value(value, artifact_dir)
+        if np is not None and isinstance(value, np.ndarray):
+            return _rzn_numpy_value(value, artifact_dir)
+        if np is not None and isinstance(value, np.generic):
+            item = value.item()
+            _rzn_json.dumps(item, allow_nan=False)
+            return item
+    except Exception:
+        pass
+    return {"type": str(type(value).__name__), "repr": str(value)}
+
+# Bound before the import guard restricts open()
+def _rzn_spill_result(output, path, reference, threshold, _open=open):
+    if output.get("result") is None:
+        return
+    data = _rzn_json.dumps(output["result"]).encode("utf-8")
+    if len(data) <= threshold:
+        return
+    with _open(path, "wb") as f:
+        f.write(data)
+    output["result"] = {
+        "type": "result_file",
+        "result_file": reference,
+        "size_bytes": len(data),
+        "sha256": _rzn_hashlib.sha256(data).hexdigest() if _rzn_hashlib else None,
+    }
+
+def _rzn_report_output(output, path, nonce, _open=open):
+    data = _rzn_json.dumps(output, default=str)
+    if path is not None:
+        try:
+            with _open(path, "w", encoding="utf-8") as f:
+                f.write(data)
+            return
+        except OSError:
+            pass
+    _rzn_sys.stdout.write("\n" + nonce + "_START\n" + data + "\n" + nonce + "_END\n")
+    _rzn_sys.stdout.flush()
+"#;
+
+/// Python helpers that tag guard errors as policy denials
+///
+/// `_rzn_denied(error, kind, target)` attaches a `{kind, target}` marker to
+/// `error` and returns it, so guards `raise _rzn_denied(ImportError(...),
+/// "import", name)`. Kinds are `import`, `network`, `file_write` and
+/// `subprocess`. `_rzn_denial_of(error)` finds the marker on `error` or
+/// anything in its cause chain, and the wrapper reports it as `denial` for
+/// [`crate::errors::execution_error`] to map onto a blocked variant. Must be
+/// defined before any guard is installed.
+pub(crate) const DENIAL_MARKER: &str = r#"
+def _rzn_denied(error, kind, target):
+    if isinstance(target, bytes):
+        target = target.decode("utf-8", "replace")
+    error._rzn_denial = {"kind": kind, "target": str(target)}
+    return error
+
+def _rzn_denial_of(error):
+    seen = set()
+    while error is not None and id(error) not in seen:
+        seen.add(id(error))
+        denial = getattr(error, "_rzn_denial", None)
+        if isinstance(denial, dict):
+            return denial
+        error = error.__cause__ or error.__context__
+    return None
+"#;
+
+/// Env var that gives the native wrapper the path of its inputs file
+pub(crate) const INPUTS_FILE_ENV: &str = "RZN_INPUTS_FILE";
+
+/// File a workspace wrapper loads its inputs from, relative to the workspace
+pub(crate) const WORKSPACE_INPUTS_FILE: &str = ".rzn_inputs.json";
+
+/// Write `inputs` to a temporary file for a wrapper to `json.load`
+///
+/// Embedding them in the `-c` source would hit argv limits for large inputs.
+pub(crate) fn write_inputs_file(inputs: &serde_json::Value) -> Result<tempfile::NamedTempFile> {
+    let file = tempfile::Builder::new()
+        .prefix("pysandbox-inputs-")
+        .suffix(".json")
+        .tempfile()?;
+    std::fs::write(file.path(), serde_json::to_vec(inputs)?)?;
+    Ok(file)
+}
+
+/// File a workspace wrapper reports its output to, relative to the workspace
+pub(crate) const WORKSPACE_OUTPUT_FILE: &str = ".rzn_output.json";
+
+/// Where a wrapper reports its structured output
+///
+/// The output goes to a result file so stdout carries only user output. When
+/// the file can't be written, or there is none because the interpreter runs
+/// on another host, it is printed between sentinels carrying a random nonce
+/// that user code can't reproduce. The file is removed on drop.
+pub(crate) struct ResultChannel {
+    file: Option<PathBuf>,
+    nonce: String,
+}
+
+impl ResultChannel {
+    /// Report through `file`, falling back to sentinels
+    pub(crate) fn file(file: PathBuf) -> Self {
+        Self {
+            file: Some(file),
+            nonce: format!("RZN_OUTPUT_{}", uuid::Uuid::new_v4().simple()),
+        }
+    }
+
+    /// Report through [`WORKSPACE_OUTPUT_FILE`] in `workspace`
+    pub(crate) fn in_workspace(workspace: &Path) -> Self {
+        Self::file(workspace.join(WORKSPACE_OUTPUT_FILE))
+    }
+
+    /// Report through sentinels only
+    #[cfg(feature = "jupyter-engine")]
+    pub(crate) fn sentinels() -> Self {
+        Self {
+            file: None,
+            nonce: format!("RZN_OUTPUT_{}", uuid::Uuid::new_v4().simple()),
+        }
+    }
+
+    /// Python statement reporting `output`, writing to `path` as the
+    /// interpreter sees it (`None` for sentinels only)
+    pub(crate) fn report(&self, output: &str, path: &str) -> String {
+        format!(
+            "_rzn_report_output({}, {}, \"{}\")",
+            output, path, self.nonce
+        )
+    }
+
+    /// Output reported by the child, from the file or else from `stdout`
+    ///
+    /// An empty file counts as missing, since the host may create it up front.
+    pub(crate) fn take(&self, stdout: &str) -> Option<serde_json::Value> {
+        let data = self.file.as_ref().and_then(|f| std::fs::read(f).ok());
+        if let Some(data) = data.filter(|data| !data.is_empty()) {
+            return serde_json::from_slice(&data).ok();
+        }
+        let start = format!("{}_START\n", self.nonce);
+        let begin = stdout.find(&start)? + start.len();
+        let end = begin + stdout[begin..].find(&format!("\n{}_END", self.nonce))?;
+        serde_json::from_str(&stdout[begin..end]).ok()
+    }
+}
+
+impl Drop for ResultChannel {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
+
+/// Host-side limits enforced while supervising a child
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SupervisorLimits {
+    /// Kill the process group when its RSS goes over this many MB
+    pub memory_watchdog_mb: Option<usize>,
+    /// Per-stream output limit; the pipes are capped relative to it
+    pub max_output_bytes: Option<usize>,
+    /// Abort once this directory holds more than the given bytes
+    pub workspace_quota: Option<(PathBuf, u64)>,
+}
+
+impl SupervisorLimits {
+    /// Limits for a child started with `limits`
+    pub(crate) fn for_limits(limits: &ResourceLimits) -> Self {
+        Self {
+            memory_watchdog_mb: memory_watchdog(limits.memory_mb),
+            max_output_bytes: Some(limits.max_output_bytes),
+            workspace_quota: None,
+        }
+    }
+
+    /// Also enforce `limits.max_workspace_bytes` on a workspace directory
+    pub(crate) fn with_workspace(mut self, path: &Path, limits: &ResourceLimits) -> Self {
+        self.workspace_quota = Some((path.to_path_buf(), limits.max_workspace_bytes));
+        self
+    }
+
+    /// Bytes kept from each pipe
+    ///
+    /// The stdout envelope carries both captured streams, JSON-escaped, plus
+    /// the result, so the pipe gets headroom over the per-stream limit. The cap
+    /// only exists to bound host memory against writes that bypass capture.
+    fn pipe_cap(&self) -> usize {
+        self.max_output_bytes
+            .map(|max| max.saturating_mul(4).saturating_add(1024 * 1024))
+            .unwrap_or(usize::MAX)
+    }
+}
+
+/// Apply file descriptor and file size rlimits to a command
+///
+/// `max_file_bytes` caps every file the child writes (RLIMIT_FSIZE); Python
+/// ignores SIGXFSZ, so an oversized write fails with `OSError` instead.
+#[cfg(unix)]
+pub(crate) fn apply_file_limits(
+    cmd: &mut Command,
+    max_open_files: u64,
+    max_file_bytes: Option<u64>,
+) {
+    unsafe {
+        cmd.pre_exec(move || {
+            // Only ever lower a limit; raising the hard limit needs privileges
+            let lower = |resource, value: u64| {
+                let mut current = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::getrlimit(resource, &mut current) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let value = (value as libc::rlim_t).min(current.rlim_max);
+                let rlimit = libc::rlimit {
+                    rlim_cur: value,
+                    rlim_max: value,
+                };
+                if libc::setrlimit(resource, &rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            };
+
+            lower(libc::RLIMIT_NOFILE, max_open_files)?;
+            if let Some(max_file_bytes) = max_file_bytes {
+                lower(libc::RLIMIT_FSIZE, max_file_bytes)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn apply_file_limits(
+    _cmd: &mut Command,
+    _max_open_files: u64,
+    _max_file_bytes: Option<u64>,
+) {
+    // Windows would need Job Objects; the workspace quota check still applies
+}
+
+/// Total size of the regular files under a directory, not following symlinks
+pub(crate) fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_type = entry.file_type().ok()?;
+            if file_type.is_dir() {
+                Some(directory_size(&entry.path()))
+            } else if file_type.is_file() {
+                Some(entry.metadata().ok()?.len())
+            } else {
+                None
+            }
+        })
+        .sum()
+}
+
+async fn workspace_usage(quota: &(PathBuf, u64)) -> u64 {
+    let path = quota.0.clone();
+    tokio::task::spawn_blocking(move || directory_size(&path))
+        .await
+        .unwrap_or(0)
+}
+
+/// Output of a supervised child
+#[derive(Debug)]
+pub(crate) struct ChildOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// A pipe went over its cap and was cut short
+    pub truncated: bool,
+    /// Timing and resource usage of the child
+    pub metrics: ExecutionMetrics,
+}
+
+impl ChildOutput {
+    /// Add `metrics` to a result payload
+    pub(crate) fn attach_metrics(&self, payload: &mut serde_json::Value) {
+        if let (Some(payload), Ok(metrics)) =
+            (payload.as_object_mut(), serde_json::to_value(&self.metrics))
+        {
+            payload.insert("metrics".to_string(), metrics);
+        }
+    }
+}
+
+/// CPU times and peak RSS from `wait4`
+#[derive(Debug, Clone, Copy, Default)]
+struct ChildUsage {
+    user_cpu_ms: u64,
+    system_cpu_ms: u64,
+    max_rss_kb: u64,
+}
+
+/// Blocking task that reaps a child with `wait4`
+type Reaper = tokio::task::JoinHandle<std::io::Result<(ExitStatus, ChildUsage)>>;
+
+/// Start reaping a child on a blocking thread, collecting its rusage
+///
+/// tokio only reports the exit status, so on Unix the child is reaped with
+/// `wait4` instead. tokio treats the already-reaped child as gone (`ECHILD`)
+/// from then on. Start this once per child: the thread reaps the child even if
+/// nothing awaits it.
+#[cfg(unix)]
+fn spawn_reaper(child: &Child) -> Option<Reaper> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id()? as libc::pid_t;
+    Some(tokio::task::spawn_blocking(move || {
+        let mut status: libc::c_int = 0;
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        while unsafe { libc::wait4(pid, &mut status, 0, &mut usage) } != pid {
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+
+        let millis = |time: libc::timeval| time.tv_sec as u64 * 1000 + time.tv_usec as u64 / 1000;
+        // ru_maxrss is in bytes on macOS and kilobytes elsewhere
+        let max_rss_kb = if cfg!(target_os = "macos") {
+            usage.ru_maxrss as u64 / 1024
+        } else {
+            usage.ru_maxrss as u64
+        };
+        Ok((
+            ExitStatus::from_raw(status),
+            ChildUsage {
+                user_cpu_ms: millis(usage.ru_utime),
+                system_cpu_ms: millis(usage.ru_stime),
+                max_rss_kb,
+            },
+        ))
+    }))
+}
+
+#[cfg(not(unix))]
+fn spawn_reaper(_child: &Child) -> Option<Reaper> {
+    None
+}
+
+/// Wait for a child to exit, through its reaper if it has one
+async fn reap(
+    child: &mut Child,
+    reaper: &mut Option<Reaper>,
+) -> std::io::Result<(ExitStatus, Option<ChildUsage>)> {
+    match reaper {
+        Some(reaper) => {
+            let (status, usage) = reaper.await.map_err(std::io::Error::other)??;
+            Ok((status, Some(usage)))
+        }
+        None => Ok((child.wait().await?, None)),
+    }
+}
+
+fn metrics(status: ExitStatus, usage: Option<ChildUsage>, wall_time: Duration) -> ExecutionMetrics {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+
+    ExecutionMetrics {
+        wall_time_ms: wall_time.as_millis() as u64,
+        setup_ms: None,
+        user_cpu_ms: usage.map(|u| u.user_cpu_ms),
+        system_cpu_ms: usage.map(|u| u.system_cpu_ms),
+        max_rss_kb: usage.map(|u| u.max_rss_kb),
+        exit_code: status.code(),
+        signal,
+    }
+}
+
+/// Resource usage of a running process
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ProcessSample {
+    pub cpu_secs: f64,
+    pub rss_bytes: u64,
+}
+
+/// Sample CPU time and RSS of a process
+#[cfg(target_os = "linux")]
+pub(crate) fn sample_process(pid: u32) -> Option<ProcessSample> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces; fields resume after its closing paren
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: f64 = fields.get(11)?.parse().ok()?;
+    let stime: f64 = fields.get(12)?.parse().ok()?;
+    let rss_pages: u64 = fields.get(21)?.parse().ok()?;
+
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+    Some(ProcessSample {
+        cpu_secs: (utime + stime) / ticks,
+        rss_bytes: rss_pages * page_size,
+    })
+}
+
+/// Sample CPU time and RSS of a process
+#[cfg(target_os = "macos")]
+#[allow(deprecated)]
+pub(crate) fn sample_process(pid: u32) -> Option<ProcessSample> {
+    let mut info: libc::proc_taskinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+    let read = unsafe {
+        libc::proc_pidinfo(
+            pid as libc::c_int,
+            libc::PROC_PIDTASKINFO,
+            0,
+            &mut info as *mut _ as *mut libc::c_void,
+            size,
+        )
+    };
+    if read != size {
+        return None;
+    }
+
+    // CPU times are in Mach absolute time units
+    let mut timebase = libc::mach_timebase_info { numer: 1, denom: 1 };
+    unsafe {
+        libc::mach_timebase_info(&mut timebase);
+    }
+    let nanos = (info.pti_total_user + info.pti_total_system) as f64 * timebase.numer as f64
+        / timebase.denom.max(1) as f64;
+    Some(ProcessSample {
+        cpu_secs: nanos / 1e9,
+        rss_bytes: info.pti_resident_size,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub(crate) fn sample_process(_pid: u32) -> Option<ProcessSample> {
+    None
+}
+
+/// Memory limit to enforce by sampling, where the OS can't enforce it
+///
+/// macOS ignores `RLIMIT_AS`, so engines there rely on the watchdog in
+/// [`supervise`]; elsewhere the rlimit set before exec does the job.
+pub(crate) fn memory_watchdog(memory_mb: usize) -> Option<usize> {
+    if cfg!(target_os = "macos") {
+        Some(memory_mb)
+    } else {
+        None
+    }
+}
+
+/// Combined RSS of a process group, falling back to its leader alone
+#[cfg(target_os = "macos")]
+fn group_rss_bytes(pgid: u32) -> Option<u64> {
+    let mut pids: Vec<libc::pid_t> = vec![0; 512];
+    let count = unsafe {
+        libc::proc_listpgrppids(
+            pgid as libc::pid_t,
+            pids.as_mut_ptr() as *mut libc::c_void,
+            (pids.len() * std::mem::size_of::<libc::pid_t>()) as libc::c_int,
+        )
+    };
+    if count <= 0 {
+        return sample_process(pgid).map(|sample| sample.rss_bytes);
+    }
+    let total = pids
+        .iter()
+        .take(count as usize)
+        .filter(|pid| **pid > 0)
+        .filter_map(|pid| sample_process(*pid as u32))
+        .map(|sample| sample.rss_bytes)
+        .sum();
+    Some(total)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn group_rss_bytes(pgid: u32) -> Option<u64> {
+    sample_process(pgid).map(|sample| sample.rss_bytes)
+}
+
+fn emit(events: Option<&EventSender>, event: ExecutionEvent) {
+    if let Some(events) = events {
+        // A dropped receiver just means nobody is listening any more
+        let _ = events.send(event);
+    }
+}
+
+/// Read a pipe to the end, keeping at most `cap` bytes
+///
+/// Reading continues past the cap so the child never blocks on a full pipe.
+fn read_capped(
+    mut pipe: impl AsyncRead + Unpin + Send + 'static,
+    cap: usize,
+) -> JoinHandle<(Vec<u8>, bool)> {
+    tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 8192];
+        let mut truncated = false;
+        while let Ok(read) = pipe.read(&mut chunk).await {
+            if read == 0 {
+                break;
+            }
+            let room = cap.saturating_sub(buf.len());
+            if read > room {
+                truncated = true;
+            }
+            buf.extend_from_slice(&chunk[..read.min(room)]);
+        }
+        (buf, truncated)
+    })
+}
+
+/// Read stderr, turning marker lines into output events
+fn read_stderr(
+    pipe: impl AsyncRead + Unpin + Send + 'static,
+    cap: usize,
+    events: EventSender,
+) -> JoinHandle<(Vec<u8>, bool)> {
+    #[derive(serde::Deserialize)]
+    struct Chunk {
+        stream: OutputStream,
+        data: String,
+    }
+
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(pipe);
+        let mut buf = Vec::new();
+        let mut line = Vec::new();
+        let mut truncated = false;
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let chunk = line
+                .strip_prefix(CHUNK_MARKER.as_bytes())
+                .and_then(|json| serde_json::from_slice::<Chunk>(json).ok());
+            match chunk {
+                Some(chunk) => {
+                    let _ = events.send(ExecutionEvent::OutputChunk {
+                        stream: chunk.stream,
+                        data: chunk.data,
+                    });
+                }
+                None => {
+                    let room = cap.saturating_sub(buf.len());
+                    truncated |= line.len() > room;
+                    buf.extend_from_slice(&line[..line.len().min(room)]);
+                }
+            }
+        }
+        (buf, truncated)
+    })
+}
+
+/// Resolves when the token is cancelled, or never without one
+pub(crate) async fn cancelled(token: Option<&CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Stop a child, interrupting it before killing its process group
+///
+/// The group gets SIGINT first so Python raises `KeyboardInterrupt` and runs
+/// `finally` blocks and context-manager exits; whatever is still alive after
+/// `grace` (or straight away when it is zero) is killed with SIGKILL.
+pub(crate) async fn interrupt_then_kill(child: &mut Child, grace: Duration) {
+    // `id()` is gone once the child has been reaped, so grab it up front
+    let pid = child.id();
+
+    #[cfg(unix)]
+    if let Some(pid) = pid {
+        if !grace.is_zero() {
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGINT);
+            }
+            if tokio::time::timeout(grace, child.wait()).await.is_ok() {
+                debug!("[SUPERVISOR] {} exited after interrupt", pid);
+            }
+        }
+        // Grandchildren may outlive or ignore the interrupt
+        unsafe {
+            libc::kill(-(pid as i32), libc::SIGKILL);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = grace;
+
+    let _ = child.start_kill();
+    let _ = child.wait().await;
+}
+
+/// Times the setup of an execution against its setup budget
+pub(crate) struct SetupClock {
+    started: Instant,
+    budget: Duration,
+}
+
+impl SetupClock {
+    pub(crate) fn start(options: &ExecutionOptions) -> Self {
+        Self {
+            started: Instant::now(),
+            budget: options.setup_budget(),
+        }
+    }
+
+    /// Run a setup step within what is left of the budget
+    pub(crate) async fn within<T>(
+        &self,
+        step: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let remaining = self.budget.saturating_sub(self.started.elapsed());
+        tokio::time::timeout(remaining, step)
+            .await
+            .map_err(|_| SandboxError::Timeout)?
+    }
+
+    /// End the setup, failing with `Timeout` if it ran over
+    pub(crate) fn finish(&self) -> Result<Duration> {
+        let elapsed = self.started.elapsed();
+        if elapsed > self.budget {
+            debug!("[SUPERVISOR] Setup took {:?}, over its budget", elapsed);
+            return Err(SandboxError::Timeout);
+        }
+        Ok(elapsed)
+    }
+}
+
+impl ChildOutput {
+    /// Record the setup time in the metrics
+    pub(crate) fn with_setup(mut self, setup: Duration) -> Self {
+        self.metrics.setup_ms = Some(setup.as_millis() as u64);
+        self
+    }
+}
+
+/// Wait for a child with piped stdout/stderr, enforcing `options.timeout`
+///
+/// On timeout or cancellation the child's process group is interrupted, then
+/// killed after `options.kill_grace_period`, and `SandboxError::Timeout` or
+/// `SandboxError::Cancelled` is returned. Children should lead their own
+/// process group so grandchildren are stopped too.
+///
+/// With a memory watchdog set, the group's RSS is sampled and the group is
+/// killed outright once it goes over, returning
+/// `SandboxError::MemoryLimitExceeded` with the observed peak. Pipes are read
+/// with a byte cap so runaway output can't exhaust host memory.
+pub(crate) async fn supervise(
+    mut child: Child,
+    options: &ExecutionOptions,
+    limits: &SupervisorLimits,
+) -> Result<ChildOutput> {
+    let memory_watchdog_mb = limits.memory_watchdog_mb;
+    let cap = limits.pipe_cap();
+    let events = options.events.as_ref();
+    let started = Instant::now();
+    let pid = child.id();
+    emit(events, ExecutionEvent::Started { pid });
+    let _stdin_feed = child
+        .stdin
+        .take()
+        .zip(options.stdin.as_ref())
+        .map(|(pipe, source)| source.feed(pipe));
+
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| read_capped(pipe, cap))
+        .ok_or_else(|| SandboxError::InternalError("Child has no stdout".to_string()))?;
+    let stderr = match (child.stderr.take(), events) {
+        (Some(pipe), Some(events)) => read_stderr(pipe, cap, events.clone()),
+        (Some(pipe), None) => read_capped(pipe, cap),
+        (None, _) => {
+            return Err(SandboxError::InternalError(
+                "Child has no stderr".to_string(),
+            ))
+        }
+    };
+
+    let deadline = tokio::time::sleep(options.timeout);
+    tokio::pin!(deadline);
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    // The first tick completes immediately; skip it so heartbeats follow `Started`
+    heartbeat.tick().await;
+    let mut watchdog = tokio::time::interval(WATCHDOG_INTERVAL);
+    let mut peak_bytes: u64 = 0;
+    let mut quota_check = tokio::time::interval(QUOTA_INTERVAL);
+    let quota = limits.workspace_quota.as_ref();
+    let mut reaper = spawn_reaper(&child);
+
+    let (status, usage) = loop {
+        let error = tokio::select! {
+            reaped = reap(&mut child, &mut reaper) => break reaped?,
+            _ = heartbeat.tick(), if events.is_some() => {
+                if let Some(sample) = pid.and_then(sample_process) {
+                    emit(events, ExecutionEvent::Heartbeat {
+                        cpu_secs: sample.cpu_secs,
+                        rss_mb: sample.rss_bytes as f64 / (1024.0 * 1024.0),
+                        elapsed: started.elapsed(),
+                    });
+                }
+                continue;
+            }
+            _ = watchdog.tick(), if memory_watchdog_mb.is_some() => {
+                let limit_bytes = memory_watchdog_mb.unwrap_or(usize::MAX) as u64 * 1024 * 1024;
+                let rss = pid.and_then(group_rss_bytes).unwrap_or(0);
+                peak_bytes = peak_bytes.max(rss);
+                if rss <= limit_bytes {
+                    continue;
+                }
+                SandboxError::MemoryLimitExceeded {
+                    peak_mb: Some((peak_bytes / (1024 * 1024)) as usize),
+                }
+            }
+            _ = quota_check.tick(), if quota.is_some() => {
+                let used_bytes = match quota {
+                    Some(quota) => workspace_usage(quota).await,
+                    None => 0,
+                };
+                if quota.is_some_and(|(_, max)| used_bytes <= *max) {
+                    continue;
+                }
+                SandboxError::DiskQuotaExceeded { used_bytes }
+            }
+            _ = &mut deadline => SandboxError::Timeout,
+            _ = cancelled(options.cancellation.as_ref()) => SandboxError::Cancelled,
+        };
+
+        debug!("[SUPERVISOR] {}, stopping {:?}", error, pid);
+        // A runaway allocation gets no grace period to grow further
+        let grace = match error {
+            SandboxError::MemoryLimitExceeded { .. } => Duration::ZERO,
+            _ => options.kill_grace_period,
+        };
+        interrupt_then_kill(&mut child, grace).await;
+        // Orphaned grandchildren may still hold the pipes open
+        stdout.abort();
+        stderr.abort();
+        emit(
+            events,
+            ExecutionEvent::Finished {
+                exit_code: None,
+                elapsed: started.elapsed(),
+            },
+        );
+        return Err(error);
+    };
+
+    let wall_time = started.elapsed();
+    let (stdout, stdout_truncated) = stdout.await.unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.await.unwrap_or_default();
+    emit(
+        events,
+        ExecutionEvent::Finished {
+            exit_code: status.code(),
+            elapsed: started.elapsed(),
+        },
+    );
+
+    // Writes that landed between the last check and exit still count
+    if let Some(quota) = quota {
+        let used_bytes = workspace_usage(quota).await;
+        if used_bytes > quota.1 {
+            return Err(SandboxError::DiskQuotaExceeded { used_bytes });
+        }
+    }
+
+    Ok(ChildOutput {
+        status,
+        stdout,
+        stderr,
+        truncated: stdout_truncated || stderr_truncated,
+        metrics: metrics(status, usage, wall_time),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::event_channel;
+    use std::process::Stdio;
+
+    #[tokio::test]
+    async fn test_setup_clock_enforces_budget() {
+        let options = ExecutionOptions {
+            setup_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let setup = SetupClock::start(&options);
+        assert!(setup.within(async { Ok(()) }).await.is_ok());
+        let slow = setup
+            .within(async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(slow, Err(SandboxError::Timeout)));
+        assert!(matches!(setup.finish(), Err(SandboxError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn test_supervise_streams_output_chunks() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let script = format!(
+            "import json, sys\nfrom io import StringIO\n{}\n_captured_stdout.write('hi')\nprint('plain', file=sys.stderr)",
+            output_capture(true, 1024)
+        );
+        let child = Command::new(python)
+            .arg("-c")
+            .arg(script)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let (tx, mut rx) = event_channel();
+        let options = ExecutionOptions {
+            events: Some(tx),
+            ..Default::default()
+        };
+        let output = supervise(child, &options, &SupervisorLimits::default())
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "plain\n");
+
+        drop(options);
+        let mut received = Vec::new();
+        while let Some(event) = rx.recv().await {
+            received.push(event);
+        }
+        assert!(matches!(received[0], ExecutionEvent::Started { .. }));
+        assert!(received.contains(&ExecutionEvent::OutputChunk {
+            stream: OutputStream::Stdout,
+            data: "hi".to_string(),
+        }));
+        assert!(matches!(
+            received.last(),
+            Some(ExecutionEvent::Finished {
+                exit_code: Some(0),
+                ..
+            })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_supervise_reports_rusage() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let child = Command::new(python)
+            .args([
+                "-c",
+                "import time\nsum(range(3_000_000))\ntime.sleep(0.3)\nraise SystemExit(3)",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // Watchdog ticks must not abandon the wait for the child
+        let limits = SupervisorLimits {
+            memory_watchdog_mb: Some(1 << 20),
+            ..Default::default()
+        };
+        let output = supervise(child, &ExecutionOptions::default(), &limits)
+            .await
+            .unwrap();
+        let metrics = &output.metrics;
+        assert_eq!(metrics.exit_code, Some(3));
+        assert_eq!(metrics.signal, None);
+        assert!(metrics.user_cpu_ms.unwrap() + metrics.system_cpu_ms.unwrap() > 0);
+        assert!(metrics.max_rss_kb.unwrap() > 1024);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_interrupts_before_kill() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let mut cmd = Command::new(python);
+        cmd.arg("-c")
+            .arg("import time\ntry:\n    time.sleep(30)\nfinally:\n    print('cleaned up', flush=True)")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        let mut child = cmd.spawn().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        interrupt_then_kill(&mut child, Duration::from_secs(5)).await;
+
+        let mut printed = String::new();
+        stdout.read_to_string(&mut printed).await.unwrap();
+        assert_eq!(printed, "cleaned up\n");
+    }
+
+    #[tokio::test]
+    async fn test_memory_watchdog_reports_peak() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let child = Command::new(python)
+            .arg("-c")
+            .arg("import time\nblob = bytearray(256 * 1024 * 1024)\ntime.sleep(30)")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let limits = SupervisorLimits {
+            memory_watchdog_mb: Some(64),
+            ..Default::default()
+        };
+        let result = supervise(child, &ExecutionOptions::default(), &limits).await;
+        match result {
+            Err(SandboxError::MemoryLimitExceeded {
+                peak_mb: Some(peak),
+            }) => assert!(peak > 64),
+            other => panic!("expected memory limit, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancellation_kills_process_group() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("sleep 30 & echo $!; wait")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        let child = cmd.spawn().unwrap();
+
+        let token = CancellationToken::new();
+        let options = ExecutionOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            token.cancel();
+        });
+
+        let started = Instant::now();
+        let result = supervise(child, &options, &SupervisorLimits::default()).await;
+        canceller.await.unwrap();
+        assert!(matches!(result, Err(SandboxError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+    #[tokio::test]
+    async fn test_result_serializer() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        // The pandas and numpy cases only run where those are installed
+        let script = format!(
+            r#"{}
+import json, sys
+values = {{"bytes": b"hi", "object": object()}}
+try:
+    import pandas as pd
+    values["frame"] = pd.DataFrame({{"a": [1, 2], "b": ["x", None]}}).set_index("b")
+    values["series"] = pd.Series(range(5000))
+except ImportError:
+    pass
+try:
+    import numpy as np
+    values["array"] = np.array([[1.5, np.nan], [3.0, 4.0]])
+    values["scalar"] = np.int64(7)
+    values["large"] = np.zeros(30000, dtype=np.int32)
+except ImportError:
+    pass
+print(json.dumps({{k: _rzn_result_value(v, sys.argv[1]) for k, v in values.items()}}))
+"#,
+            RESULT_SERIALIZER
+        );
+        let output = Command::new(python)
+            .arg("-c")
+            .arg(script)
+            .arg(dir.path())
+            .output()
+            .await
+            .unwrap();
+        let values: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(values["bytes"]["data"], "aGk=");
+        assert_eq!(values["object"]["type"], "object");
+        if let (Some(frame), Some(series)) = (values.get("frame"), values.get("series")) {
+            assert_eq!(frame["shape"], serde_json::json!([2, 1]));
+            assert_eq!(
+                frame["records"],
+                serde_json::json!([{"b": "x", "a": 1}, {"b": null, "a": 2}])
+            );
+            assert_eq!(series["preview"][0]["value"], 0);
+            let file = series["artifact"]["file"].as_str().unwrap();
+            assert!(dir.path().join(file).is_file());
+        }
+        if let Some(array) = values.get("array") {
+            assert_eq!(array["dtype"], "float64");
+            assert_eq!(array["data"], serde_json::json!([[1.5, null], [3.0, 4.0]]));
+            assert_eq!(values["scalar"], 7);
+            assert_eq!(values["large"]["artifact"]["file"], "result.npy");
+            assert!(dir.path().join("result.npy").is_file());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inputs_file_keeps_quotes_and_escapes() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let inputs = serde_json::json!({"text": "it's '''quoted''' \\n and\nsplit"});
+        let file = write_inputs_file(&inputs).unwrap();
+        let output = Command::new(python)
+            .arg("-c")
+            .arg("import json, sys; print(json.dumps(json.load(open(sys.argv[1]))))")
+            .arg(file.path())
+            .output()
+            .await
+            .unwrap();
+        let loaded: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(loaded, inputs);
+    }
+
+    #[tokio::test]
+    async fn test_result_channel() {
+        let Ok(python) = which::which("python3") else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let report = |channel: &ResultChannel| {
+            format!(
+                "{}\nprint('OUTPUT_JSON_START\\n{{}}\\nOUTPUT_JSON_END')\n{}\n",
+                RESULT_SERIALIZER,
+                channel.report("{'result': 1}", "__import__('sys').argv[1]")
+            )
+        };
+
+        // Through the file, leaving stdout to the user
+        let channel = ResultChannel::file(dir.path().join("out.json"));
+        let output = Command::new(&python)
+            .arg("-c")
+            .arg(report(&channel))
+            .arg(dir.path().join("out.json"))
+            .output()
+            .await
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.trim(), "OUTPUT_JSON_START\n{}\nOUTPUT_JSON_END");
+        assert_eq!(channel.take(&stdout).unwrap()["result"], 1);
+        drop(channel);
+        assert!(!dir.path().join("out.json").exists());
+
+        // Through sentinels when the file can't be written
+        let missing = dir.path().join("missing/out.json");
+        let channel = ResultChannel::file(missing.clone());
+        let output = Command::new(&python)
+            .arg("-c")
+            .arg(report(&channel))
+            .arg(&missing)
+            .output()
+            .await
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(channel.take(&stdout).unwrap()["result"], 1);
+    }
+}
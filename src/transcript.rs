@@ -0,0 +1,266 @@
+//! Execution transcripts
+//!
+//! Attach a [`TranscriptStore`] to a [`PythonSandbox`](crate::PythonSandbox)
+//! with `with_transcripts` and each `execute` call is saved as a
+//! [`Transcript`]: the code, its inputs, the effective policy, the structured
+//! output or error, and any exported artifacts. [`replay`] re-runs a
+//! transcript under the current policy so the two runs can be compared, e.g.
+//! from `pysandbox replay <id>`.
+
+use crate::engine::ExecutionResult;
+use crate::errors::{Result, SandboxError};
+use crate::policy::SandboxPolicy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the default transcript directory
+pub const TRANSCRIPT_DIR_ENV: &str = "RZN_TRANSCRIPT_DIR";
+
+/// One recorded execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    pub id: String,
+    /// UTC time the execution finished, ISO 8601
+    pub recorded_at: String,
+    pub code: String,
+    /// SHA-256 of `code`, hex encoded
+    pub code_hash: String,
+    pub inputs: serde_json::Value,
+    /// SHA-256 of `inputs` serialized as JSON, hex encoded
+    pub input_hash: String,
+    /// Policy in effect, if the sandbox knew it
+    pub policy: Option<SandboxPolicy>,
+    /// Structured output of a successful run
+    pub output: Option<serde_json::Value>,
+    /// Error message of a failed run
+    pub error: Option<String>,
+    /// Paths of files exported from the workspace
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    pub duration_ms: u64,
+}
+
+impl Transcript {
+    pub(crate) fn new(
+        code: &str,
+        inputs: &serde_json::Value,
+        policy: Option<SandboxPolicy>,
+        result: &Result<ExecutionResult>,
+        elapsed: std::time::Duration,
+    ) -> Self {
+        let (output, error) = match result {
+            Ok(output) => (Some(output.to_value()), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let artifacts = output
+            .as_ref()
+            .and_then(|o| o.get("exported_files"))
+            .and_then(|f| f.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|f| f.get("path").and_then(|p| p.as_str()))
+            .map(str::to_string)
+            .collect();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            recorded_at: crate::audit::iso8601_now(),
+            code: code.to_string(),
+            code_hash: crate::audit::code_hash(code),
+            inputs: inputs.clone(),
+            input_hash: crate::audit::code_hash(&inputs.to_string()),
+            policy,
+            output,
+            error,
+            artifacts,
+            duration_ms: elapsed.as_millis() as u64,
+        }
+    }
+}
+
+/// Directory of transcripts, one `<id>.json` file each
+#[derive(Debug, Clone)]
+pub struct TranscriptStore {
+    dir: PathBuf,
+}
+
+impl TranscriptStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Store in [`TRANSCRIPT_DIR_ENV`], or `pysandbox-transcripts` under the
+    /// temp directory
+    pub fn from_env() -> Self {
+        match std::env::var_os(TRANSCRIPT_DIR_ENV).filter(|d| !d.is_empty()) {
+            Some(dir) => Self::new(dir),
+            None => Self::new(std::env::temp_dir().join("pysandbox-transcripts")),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Save a transcript, replacing any with the same id
+    pub fn save(&self, transcript: &Transcript) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(&transcript.id)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(transcript)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    /// Load a transcript by id
+    pub fn load(&self, id: &str) -> Result<Transcript> {
+        let path = self.path(id)?;
+        let data = std::fs::read(&path).map_err(|e| {
+            SandboxError::InternalError(format!(
+                "Failed to read transcript {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Ids of all stored transcripts, oldest first
+    pub fn list(&self) -> Result<Vec<String>> {
+        let mut transcripts = Vec::new();
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                    transcripts.push((modified, id.to_string()));
+                }
+            }
+        }
+        transcripts.sort();
+        Ok(transcripts.into_iter().map(|(_, id)| id).collect())
+    }
+
+    /// Ids are file names, so refuse anything that could leave the directory
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(SandboxError::InternalError(format!(
+                "Invalid transcript id: {}",
+                id
+            )));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+/// Result of re-running a transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// Id of the replayed transcript
+    pub id: String,
+    /// Name of the policy the replay ran under
+    pub policy: String,
+    /// The new run
+    pub replayed: Transcript,
+    /// Whether output and error match the recorded run, ignoring metrics
+    pub matches: bool,
+}
+
+/// Re-run `transcript` under `policy`, with Python from PATH
+pub async fn replay(transcript: &Transcript, policy: &SandboxPolicy) -> Result<Replay> {
+    let sandbox = policy.build_sandbox()?;
+    let started = std::time::Instant::now();
+    let result = sandbox
+        .execute(
+            &transcript.code,
+            transcript.inputs.clone(),
+            policy.to_execution_options(),
+        )
+        .await;
+    let replayed = Transcript::new(
+        &transcript.code,
+        &transcript.inputs,
+        Some(policy.clone()),
+        &result,
+        started.elapsed(),
+    );
+    Ok(Replay {
+        id: transcript.id.clone(),
+        policy: policy.name.clone(),
+        matches: comparable(&replayed) == comparable(transcript),
+        replayed,
+    })
+}
+
+/// Output and error without fields that differ between identical runs
+fn comparable(transcript: &Transcript) -> (Option<serde_json::Value>, Option<&str>) {
+    let output = transcript.output.clone().map(|mut output| {
+        if let Some(object) = output.as_object_mut() {
+            for key in [
+                "metrics",
+                "workspace",
+                "export_dir",
+                "exported_files",
+                "transcript_id",
+                // Written by older versions
+                "error",
+            ] {
+                object.remove(key);
+            }
+        }
+        output
+    });
+    (output, transcript.error.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TranscriptStore::new(dir.path());
+        let output = ExecutionResult::from_value(serde_json::json!({
+            "result": 4,
+            "metrics": {"wall_time_ms": 12},
+            "exported_files": [{"name": "plot.png", "path": "/exports/plot.png"}]
+        }))
+        .unwrap();
+        let transcript = Transcript::new(
+            "result = x * 2",
+            &serde_json::json!({"x": 2}),
+            Some(SandboxPolicy::balanced()),
+            &Ok(output),
+            std::time::Duration::from_millis(12),
+        );
+        store.save(&transcript).unwrap();
+
+        let loaded = store.load(&transcript.id).unwrap();
+        assert_eq!(loaded.code, transcript.code);
+        assert_eq!(loaded.output, transcript.output);
+        assert_eq!(loaded.policy.unwrap().name, "Balanced");
+        assert_eq!(loaded.artifacts, ["/exports/plot.png"]);
+        assert_eq!(loaded.input_hash.len(), 64);
+        assert_eq!(store.list().unwrap(), std::slice::from_ref(&transcript.id));
+        assert!(store.load("../etc/passwd").is_err());
+
+        let mut rerun = transcript.clone();
+        rerun.output = Some(serde_json::json!({
+            "stdout": null,
+            "stderr": null,
+            "result": 4,
+            "metrics": {"wall_time_ms": 9},
+            "error": null
+        }));
+        assert_eq!(comparable(&rerun), comparable(&transcript));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{
+    config::ResourceLimits,
+    engine::{Diagnostic, EngineCapabilities, ExecutionOptions, ExecutionResult, PythonEngine},
+    errors::{execution_error, Result, SandboxError},
+    result_file,
+    sandboxed::{IsolatedWorkspace, SandboxedPythonEngine},
+    supervisor::{ResultChannel, SetupClock},
+};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tracing::info;
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+/// Path the workspace is preopened at inside the guest
+const GUEST_WORKSPACE: &str = "/workspace";
+
+/// Maximum captured stdout/stderr per execution
+const MAX_CAPTURE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Configuration for the WebAssembly engine
+#[derive(Debug, Clone)]
+pub struct WasmConfig {
+    /// CPython compiled to WASI (e.g. `python-3.12.0.wasm`)
+    pub python_wasm: PathBuf,
+    /// Host directory holding the Python standard library, if it is not
+    /// embedded in the module
+    pub stdlib_dir: Option<PathBuf>,
+    /// Guest path the standard library is mounted at (read-only)
+    pub stdlib_guest_path: String,
+    /// Base directory for creating isolated workspaces (preopened at /workspace)
+    pub workspace_base: PathBuf,
+    /// Resource limits
+    pub limits: ResourceLimits,
+    /// Fuel granted per second of `cpu_seconds`. `None` disables the
+    /// instruction budget and relies on the wall-clock timeout only.
+    pub fuel_per_cpu_second: Option<u64>,
+}
+
+impl Default for WasmConfig {
+    fn default() -> Self {
+        Self {
+            python_wasm: PathBuf::from("python.wasm"),
+            stdlib_dir: None,
+            stdlib_guest_path: "/usr/local/lib".to_string(),
+            workspace_base: crate::sandboxed::default_workspace_base(),
+            limits: ResourceLimits::default(),
+            fuel_per_cpu_second: Some(1_000_000_000),
+        }
+    }
+}
+
+/// Per-execution store state
+struct WasmState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+/// Outcome of running the module on the blocking thread
+enum WasmRun {
+    Exited(i32),
+    OutOfFuel,
+    Interrupted,
+}
+
+/// Python engine that runs CPython compiled to WASI inside wasmtime
+///
+/// The guest has no sockets and can only see the preopened workspace (and the
+/// standard library, read-only). Executions are deterministic and need no
+/// Python installed on the host; only pure-Python packages are usable.
+pub struct WasmPythonEngine {
+    config: WasmConfig,
+    engine: Engine,
+    module: Option<Module>,
+}
+
+impl WasmPythonEngine {
+    /// Create a new WebAssembly engine
+    pub fn new(config: WasmConfig) -> Result<Self> {
+        if !config.python_wasm.exists() {
+            return Err(SandboxError::PythonNotFound);
+        }
+        std::fs::create_dir_all(&config.workspace_base)?;
+
+        let mut wasm_config = Config::new();
+        wasm_config.epoch_interruption(true);
+        wasm_config.consume_fuel(config.fuel_per_cpu_second.is_some());
+        let engine = Engine::new(&wasm_config).map_err(|e| {
+            SandboxError::InternalError(format!("Failed to create wasm engine: {}", e))
+        })?;
+
+        Ok(Self {
+            config,
+            engine,
+            module: None,
+        })
+    }
+
+    /// Create with a CPython WASI module and its standard library directory
+    pub fn with_python_wasm(python_wasm: PathBuf, stdlib_dir: Option<PathBuf>) -> Result<Self> {
+        Self::new(WasmConfig {
+            python_wasm,
+            stdlib_dir,
+            ..Default::default()
+        })
+    }
+
+    /// Compile the module on first use (CPython takes a few seconds to compile)
+    async fn module(&mut self) -> Result<Module> {
+        if let Some(module) = &self.module {
+            return Ok(module.clone());
+        }
+
+        info!("[WASM] Compiling {:?}", self.config.python_wasm);
+        let engine = self.engine.clone();
+        let path = self.config.python_wasm.clone();
+        let module = tokio::task::spawn_blocking(move || Module::from_file(&engine, &path))
+            .await
+            .map_err(|e| SandboxError::InternalError(format!("Compile task failed: {}", e)))?
+            .map_err(|e| {
+                SandboxError::InternalError(format!("Failed to compile Python wasm module: {}", e))
+            })?;
+
+        self.module = Some(module.clone());
+        Ok(module)
+    }
+
+    fn build_wasi(
+        &self,
+        workspace: &IsolatedWorkspace,
+        wrapper_code: String,
+        options: &ExecutionOptions,
+        stdout: MemoryOutputPipe,
+        stderr: MemoryOutputPipe,
+    ) -> Result<WasiP1Ctx> {
+        let map_err = |e: wasmtime::Error| {
+            SandboxError::InternalError(format!("Failed to preopen directory: {}", e))
+        };
+
+        let mut builder = WasiCtxBuilder::new();
+        builder
+            .args(&["python", "-"])
+            .stdin(MemoryInputPipe::new(wrapper_code))
+            .stdout(stdout)
+            .stderr(stderr)
+            .allow_tcp(false)
+            .allow_udp(false)
+            .allow_ip_name_lookup(false)
+            .env("SANDBOX_WORKSPACE", GUEST_WORKSPACE)
+            .env("PYTHONIOENCODING", "utf-8")
+            .env("PYTHONDONTWRITEBYTECODE", "1")
+            .preopened_dir(
+                &workspace.path,
+                GUEST_WORKSPACE,
+                DirPerms::all(),
+                FilePerms::all(),
+            )
+            .map_err(map_err)?;
+
+        if let Some(stdlib_dir) = &self.config.stdlib_dir {
+            builder
+                .preopened_dir(
+                    stdlib_dir,
+                    &self.config.stdlib_guest_path,
+                    DirPerms::READ,
+                    FilePerms::READ,
+                )
+                .map_err(map_err)?;
+        }
+        for (key, value) in &options.env_vars {
+            builder.env(key, value);
+        }
+
+        Ok(builder.build_p1())
+    }
+}
+
+/// Instantiate and run `_start` to completion on the current thread
+fn run_module(
+    engine: &Engine,
+    module: &Module,
+    state: WasmState,
+    fuel: Option<u64>,
+) -> std::result::Result<WasmRun, wasmtime::Error> {
+    let mut linker: Linker<WasmState> = Linker::new(engine);
+    preview1::add_to_linker_sync(&mut linker, |s: &mut WasmState| &mut s.wasi)?;
+
+    let mut store = Store::new(engine, state);
+    store.limiter(|s| &mut s.limits);
+    store.set_epoch_deadline(1);
+    if let Some(fuel) = fuel {
+        store.set_fuel(fuel)?;
+    }
+
+    let instance = linker.instantiate(&mut store, module)?;
+    let start = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+
+    match start.call(&mut store, ()) {
+        Ok(()) => Ok(WasmRun::Exited(0)),
+        Err(e) => {
+            if let Some(exit) = e.downcast_ref::<I32Exit>() {
+                return Ok(WasmRun::Exited(exit.0));
+            }
+            match e.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => Ok(WasmRun::OutOfFuel),
+                Some(Trap::Interrupt) => Ok(WasmRun::Interrupted),
+                _ => Err(e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl PythonEngine for WasmPythonEngine {
+    async fn validate(&self, _code: &str, _options: &ExecutionOptions) -> Result<Vec<Diagnostic>> {
+        // Syntax errors are reported by the guest interpreter
+        Ok(Vec::new())
+    }
+
+    async fn execute(
+        &mut self,
+        code: &str,
+        inputs: serde_json::Value,
+        options: &ExecutionOptions,
+    ) -> Result<ExecutionResult> {
+        // Compiling the module on first use counts against the setup budget
+        let setup = SetupClock::start(options);
+        let module = setup.within(self.module()).await?;
+        let workspace = IsolatedWorkspace::new(&self.config.workspace_base)?;
+        workspace.write_inputs(&inputs)?;
+        workspace.stage_inputs(&options.input_files)?;
+        // The wrapper goes to the guest's stdin, so stdin data is staged
+        if let Some(stdin) = &options.stdin {
+            setup.within(stdin.stage(&workspace.path)).await?;
+        }
+        let channel = ResultChannel::in_workspace(&workspace.path);
+        let wrapper_code = SandboxedPythonEngine::workspace_wrapper(
+            code,
+            options,
+            false,
+            self.config.limits.max_output_bytes,
+            None,
+            &channel,
+        )?;
+
+        let stdout = MemoryOutputPipe::new(MAX_CAPTURE_BYTES);
+        let stderr = MemoryOutputPipe::new(MAX_CAPTURE_BYTES);
+        let wasi = self.build_wasi(
+            &workspace,
+            wrapper_code,
+            options,
+            stdout.clone(),
+            stderr.clone(),
+        )?;
+
+        let memory_mb = self.config.limits.memory_mb.min(options.memory_mb);
+        let cpu_seconds = self.config.limits.cpu_seconds.min(options.cpu_seconds);
+        let state = WasmState {
+            wasi,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(memory_mb * 1024 * 1024)
+                .instances(1)
+                .build(),
+        };
+        let fuel = self
+            .config
+            .fuel_per_cpu_second
+            .map(|per_sec| per_sec.saturating_mul(cpu_seconds));
+
+        // Run on a plain thread: WASI's sync bindings drive their own runtime
+        setup.finish()?;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let engine = self.engine.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(run_module(&engine, &module, state, fuel));
+        });
+
+        let run = match tokio::time::timeout(options.timeout, rx).await {
+            Ok(Ok(run)) => run.map_err(|e| SandboxError::RuntimeError(format!("{:#}", e)))?,
+            Ok(Err(_)) => {
+                return Err(SandboxError::InternalError(
+                    "Wasm execution thread exited unexpectedly".to_string(),
+                ))
+            }
+            Err(_) => {
+                // Interrupt the guest at its next epoch check so the thread exits
+                self.engine.increment_epoch();
+                return Err(SandboxError::Timeout);
+            }
+        };
+
+        let exit_code = match run {
+            WasmRun::Exited(code) => code,
+            WasmRun::OutOfFuel | WasmRun::Interrupted => return Err(SandboxError::Timeout),
+        };
+
+        let stdout = String::from_utf8_lossy(&stdout.contents()).to_string();
+        let stderr = String::from_utf8_lossy(&stderr.contents()).to_string();
+
+        // Extract structured output
+        if let Some(mut parsed) = channel.take(&stdout) {
+            if let Some(error) = execution_error(&parsed, code) {
+                return Err(error);
+            }
+            result_file::persist_from_workspace(&mut parsed, &workspace.path)?;
+            return ExecutionResult::from_value(parsed);
+        }
+
+        if exit_code != 0 {
+            if stderr.contains("MemoryError") {
+                return Err(SandboxError::MemoryLimitExceeded { peak_mb: None });
+            }
+            return Err(SandboxError::RuntimeError(stderr));
+        }
+
+        Ok(ExecutionResult::default())
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: "WebAssembly Python (wasmtime)".to_string(),
+            numpy: false, // Native extensions can't load in WASI
+            matplotlib: false,
+            pandas: false,
+            max_memory_mb: self.config.limits.memory_mb,
+            max_cpu_seconds: self.config.limits.cpu_seconds,
+            security_level: 9, // Wasm isolation, no sockets
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        self.module = None;
+        Ok(())
+    }
+}